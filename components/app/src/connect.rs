@@ -1,8 +1,13 @@
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::PathBuf;
 use std::time::Duration;
 
 use futures::executor::ThreadPool;
 use futures::task::Spawn;
 
+#[cfg(unix)]
+use common::conn::{FuncFutTransform, FutTransform};
 use common::int_convert::usize_to_u64;
 
 use proto::consts::{MAX_FRAME_LENGTH, TICK_MS};
@@ -13,17 +18,38 @@ use crypto::identity::PublicKey;
 
 use identity::IdentityClient;
 use net::NetConnector;
+#[cfg(unix)]
+use net::UnixConnector;
 use timer::create_timer;
 
 use node::connect::{node_connect, NodeConnection};
 
+/// Node addresses beginning with this prefix are paths of Unix domain sockets.
+/// For example: `unix:/var/run/offst/node.sock`
+pub const UNIX_ADDRESS_PREFIX: &str = "unix:";
+
 #[derive(Debug)]
 pub struct ConnectError;
 
+/// Get the socket path of a Unix domain socket address.
+/// Returns None if `net_address` is not a Unix domain socket address.
+#[cfg(unix)]
+fn unix_socket_path(net_address: &NetAddress) -> Option<PathBuf> {
+    let address_str = net_address.as_str();
+    if !address_str.starts_with(UNIX_ADDRESS_PREFIX) {
+        return None;
+    }
+    Some(PathBuf::from(&address_str[UNIX_ADDRESS_PREFIX.len()..]))
+}
+
 /// Connect to a remote offst-node.
+/// If `opt_socks5_proxy` is provided, the connection is made through a SOCKS5 proxy.
+/// Nodes running on the same machine may be reached through a Unix domain socket, using an
+/// address that begins with `UNIX_ADDRESS_PREFIX`. Such connections never go through the proxy.
 pub async fn connect<S>(
     node_public_key: PublicKey,
     node_net_address: NetAddress,
+    opt_socks5_proxy: Option<SocketAddr>,
     app_identity_client: IdentityClient,
     spawner: S,
 ) -> Result<NodeConnection, ConnectError>
@@ -32,9 +58,6 @@ where
 {
     let resolve_thread_pool = ThreadPool::new().map_err(|_| ConnectError)?;
 
-    // A tcp connector, Used to connect to remote servers:
-    let net_connector = NetConnector::new(MAX_FRAME_LENGTH, resolve_thread_pool, spawner.clone());
-
     // Get a timer client:
     let dur = Duration::from_millis(usize_to_u64(TICK_MS).unwrap());
     let timer_client = create_timer(dur, spawner.clone()).map_err(|_| ConnectError)?;
@@ -42,6 +65,45 @@ where
     // Obtain secure cryptographic random:
     let rng = system_random();
 
+    #[cfg(unix)]
+    {
+        if let Some(socket_path) = unix_socket_path(&node_net_address) {
+            let unix_connector = UnixConnector::new(MAX_FRAME_LENGTH, spawner.clone());
+            let net_connector = FuncFutTransform::new(move |_net_address| {
+                let mut c_unix_connector = unix_connector.clone();
+                let c_socket_path = socket_path.clone();
+                Box::pin(async move { await!(c_unix_connector.transform(c_socket_path)) })
+            });
+            return await!(node_connect(
+                net_connector,
+                node_public_key,
+                node_net_address,
+                timer_client,
+                app_identity_client,
+                rng,
+                spawner
+            ))
+            .map_err(|_| ConnectError);
+        }
+    }
+
+    // A tcp connector, Used to connect to remote servers:
+    let net_connector = match opt_socks5_proxy {
+        Some(socks5_proxy) => NetConnector::new_socks5(
+            MAX_FRAME_LENGTH,
+            socks5_proxy,
+            resolve_thread_pool,
+            timer_client.clone(),
+            spawner.clone(),
+        ),
+        None => NetConnector::new(
+            MAX_FRAME_LENGTH,
+            resolve_thread_pool,
+            timer_client.clone(),
+            spawner.clone(),
+        ),
+    };
+
     await!(node_connect(
         net_connector,
         node_public_key,
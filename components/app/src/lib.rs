@@ -26,6 +26,7 @@ pub use proto::file::relay::load_relay_from_file;
 pub use proto::file::ser_string;
 
 pub use proto::app_server::messages::{AppPermissions, NamedRelayAddress, RelayAddress};
+pub use proto::consts::TICK_MS;
 pub use proto::funder::messages::{Commit, MultiCommit, PaymentStatus, Rate, Receipt};
 pub use proto::funder::signature_buff::verify_receipt;
 pub use proto::index_server::messages::NamedIndexServerAddress;
@@ -33,7 +34,7 @@ pub use proto::report::signature_buff::verify_move_token_hashed_report;
 
 pub use node::connect::{AppBuyer, AppConfig, AppReport, AppRoutes, AppSeller, NodeConnection};
 
-pub use self::connect::{connect, ConnectError};
+pub use self::connect::{connect, ConnectError, UNIX_ADDRESS_PREFIX};
 pub use self::identity::{identity_from_file, IdentityFromFileError};
 
 // TODO: Possibly reduce what we export from report in the future?
@@ -54,15 +55,40 @@ pub mod report {
 
 pub mod invoice {
     pub use crypto::invoice_id::{InvoiceId, INVOICE_ID_LEN};
+    pub use proto::file::invoice::{
+        load_invoice_from_file, store_invoice_to_file, verify_signed_invoice, InvoiceFileError,
+        SignedInvoice,
+    };
+    pub use proto::funder::messages::InvoiceDetails;
 }
 
 pub mod payment {
     pub use crypto::payment_id::{PaymentId, PAYMENT_ID_LEN};
 }
 
+pub mod buyer {
+    pub use node::connect::{
+        BuyerError, PayInvoiceConfig, PayInvoiceError, PayInvoiceEvent, PayInvoiceFailure,
+        RebalanceError,
+    };
+    pub use proto::funder::messages::{FailureReason, RequestFailure};
+}
+
+pub mod seller {
+    pub use node::connect::SellerError;
+    pub use proto::funder::messages::{
+        InvoicePaid, InvoiceStatus, InvoiceTransaction, OpenInvoiceStatus,
+    };
+}
+
 pub mod route {
+    pub use node::connect::{
+        choose_multi_route, choose_multi_route_max_fees, multi_route_fees, MultiRouteChoice,
+    };
     pub use proto::funder::messages::FriendsRoute;
-    pub use proto::index_server::messages::{MultiRoute, RouteCapacityRate};
+    pub use proto::index_server::messages::{
+        HopsLiquidity, Liquidity, MultiRoute, NeighborLiquidity, RouteCapacityRate, RouteSearchMode,
+    };
 
 }
 
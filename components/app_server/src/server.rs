@@ -9,6 +9,7 @@ use futures::{future, stream, Sink, SinkExt, Stream, StreamExt};
 use common::conn::ConnPair;
 use common::select_streams::{select_streams, BoxStream};
 // use common::mutable_state::MutableState;
+use crypto::invoice_id::InvoiceId;
 use crypto::payment_id::PaymentId;
 use crypto::uid::Uid;
 
@@ -92,7 +93,10 @@ pub struct AppServer<B: Clone, TF, TIC, S> {
     /// Data structures to track ongoing requests.
     /// This allows us to multiplex requests/responses to multiple apps:
     route_requests: HashMap<Uid, u128>,
+    liquidity_requests: HashMap<Uid, u128>,
     close_payment_requests: HashMap<PaymentId, u128>,
+    invoice_status_requests: HashMap<InvoiceId, u128>,
+    sign_invoice_requests: HashMap<InvoiceId, u128>,
     transactions: HashMap<Uid, u128>,
     spawner: S,
 }
@@ -105,14 +109,18 @@ fn check_request_permissions<B>(
     match app_request {
         AppRequest::AddRelay(_) => app_permissions.config,
         AppRequest::RemoveRelay(_) => app_permissions.config,
+        AppRequest::SetAcceptSpontaneousPayments(_) => app_permissions.config,
         AppRequest::CreatePayment(_) => app_permissions.buyer,
         AppRequest::CreateTransaction(_) => app_permissions.buyer,
         AppRequest::RequestClosePayment(_) => app_permissions.buyer,
         AppRequest::AckClosePayment(_) => app_permissions.buyer,
+        AppRequest::CreateRebalance(_) => app_permissions.buyer,
 
         AppRequest::AddInvoice(_) => app_permissions.seller,
         AppRequest::CancelInvoice(_) => app_permissions.seller,
         AppRequest::CommitInvoice(_) => app_permissions.seller,
+        AppRequest::RequestInvoiceStatus(_) => app_permissions.seller,
+        AppRequest::RequestSignInvoice(_) => app_permissions.seller,
 
         AppRequest::AddFriend(_) => app_permissions.config,
         AppRequest::SetFriendRelays(_) => app_permissions.config,
@@ -126,6 +134,7 @@ fn check_request_permissions<B>(
         AppRequest::SetFriendRate(_) => app_permissions.config,
         AppRequest::ResetFriendChannel(_) => app_permissions.config,
         AppRequest::RequestRoutes(_) => app_permissions.routes,
+        AppRequest::RequestLiquidity(_) => app_permissions.routes,
         AppRequest::AddIndexServer(_) => app_permissions.config,
         AppRequest::RemoveIndexServer(_) => app_permissions.config,
     }
@@ -154,7 +163,10 @@ where
             app_counter: 0,
             apps: HashMap::new(),
             route_requests: HashMap::new(),
+            liquidity_requests: HashMap::new(),
             close_payment_requests: HashMap::new(),
+            invoice_status_requests: HashMap::new(),
+            sign_invoice_requests: HashMap::new(),
             transactions: HashMap::new(),
             spawner,
         }
@@ -249,6 +261,48 @@ where
                     )));
                 }
             }
+            FunderOutgoingControl::ResponseInvoiceStatus(response_invoice_status) => {
+                // Find the app that issued the request, and forward the response to this app:
+                let app_id = if let Some(app_id) = self
+                    .invoice_status_requests
+                    .remove(&response_invoice_status.invoice_id)
+                {
+                    app_id
+                } else {
+                    warn!("ResponseInvoiceStatus: Could not find app that initiated RequestInvoiceStatus");
+                    return Ok(());
+                };
+                if let Some(app) = self.apps.get_mut(&app_id) {
+                    await!(app.send(AppServerToApp::ResponseInvoiceStatus(
+                        response_invoice_status
+                    )));
+                }
+            }
+            FunderOutgoingControl::ResponseSignInvoice(response_sign_invoice) => {
+                // Find the app that issued the request, and forward the response to this app:
+                let app_id = if let Some(app_id) = self
+                    .sign_invoice_requests
+                    .remove(&response_sign_invoice.invoice_id)
+                {
+                    app_id
+                } else {
+                    warn!(
+                        "ResponseSignInvoice: Could not find app that initiated RequestSignInvoice"
+                    );
+                    return Ok(());
+                };
+                if let Some(app) = self.apps.get_mut(&app_id) {
+                    await!(app.send(AppServerToApp::ResponseSignInvoice(response_sign_invoice)));
+                }
+            }
+            FunderOutgoingControl::InvoicePaid(invoice_paid) => {
+                // Any app with seller permissions might be waiting for this invoice:
+                for app in self.apps.values_mut() {
+                    if app.permissions.seller {
+                        await!(app.send(AppServerToApp::InvoicePaid(invoice_paid.clone())));
+                    }
+                }
+            }
             FunderOutgoingControl::ReportMutations(funder_report_mutations) => {
                 let mut index_mutations = Vec::new();
                 for funder_report_mutation in &funder_report_mutations.mutations {
@@ -328,6 +382,22 @@ where
                     )));
                 }
             }
+            IndexClientToAppServer::ResponseLiquidity(client_response_liquidity) => {
+                // We search for the app that issued the request, and send it the response.
+                let app_id = if let Some(app_id) = self
+                    .liquidity_requests
+                    .remove(&client_response_liquidity.request_id)
+                {
+                    app_id
+                } else {
+                    warn!("ResponseLiquidity: Could not find the app that issued RequestLiquidity request");
+                    return Ok(());
+                };
+
+                if let Some(app) = self.apps.get_mut(&app_id) {
+                    await!(app.send(AppServerToApp::ResponseLiquidity(client_response_liquidity)));
+                }
+            }
         };
         Ok(())
     }
@@ -414,6 +484,7 @@ where
             // Requests that go to funder:
             AddRelay(x) => to_funder!(AddRelay(x)),
             RemoveRelay(x) => to_funder!(RemoveRelay(x)),
+            SetAcceptSpontaneousPayments(x) => to_funder!(SetAcceptSpontaneousPayments(x)),
             CreatePayment(x) => to_funder!(CreatePayment(x)),
             RequestClosePayment(payment_id) => {
                 if self
@@ -429,6 +500,26 @@ where
             AddInvoice(x) => to_funder!(AddInvoice(x)),
             CancelInvoice(x) => to_funder!(CancelInvoice(x)),
             CommitInvoice(x) => to_funder!(CommitInvoice(x)),
+            RequestInvoiceStatus(invoice_id) => {
+                if self
+                    .invoice_status_requests
+                    .insert(invoice_id.clone(), app_id)
+                    .is_some()
+                {
+                    warn!("RequestInvoiceStatus: invoice_id clash.");
+                }
+                to_funder!(RequestInvoiceStatus(invoice_id))
+            }
+            RequestSignInvoice(invoice_details) => {
+                if self
+                    .sign_invoice_requests
+                    .insert(invoice_details.invoice_id.clone(), app_id)
+                    .is_some()
+                {
+                    warn!("RequestSignInvoice: invoice_id clash.");
+                }
+                to_funder!(RequestSignInvoice(invoice_details))
+            }
             AddFriend(x) => to_funder!(AddFriend(x)),
             SetFriendRelays(x) => to_funder!(SetFriendRelays(x)),
             SetFriendName(x) => to_funder!(SetFriendName(x)),
@@ -441,6 +532,12 @@ where
                     .insert(create_transaction.request_id, app_id);
                 to_funder!(CreateTransaction(create_transaction))
             }
+            CreateRebalance(create_rebalance) => {
+                // The result is sent back as a TransactionResult:
+                self.transactions
+                    .insert(create_rebalance.request_id, app_id);
+                to_funder!(CreateRebalance(create_rebalance))
+            }
             RemoveFriend(friend_public_key) => {
                 let remove_friend = proto::funder::messages::RemoveFriend { friend_public_key };
                 to_funder!(RemoveFriend(remove_friend))
@@ -488,6 +585,17 @@ where
                 }
                 to_index_client!(RequestRoutes(request_routes))
             }
+            RequestLiquidity(request_liquidity) => {
+                // Keep track of which application issued this request:
+                if self
+                    .liquidity_requests
+                    .insert(request_liquidity.request_id, app_id)
+                    .is_some()
+                {
+                    warn!("RequestLiquidity: request_id clash.");
+                }
+                to_index_client!(RequestLiquidity(request_liquidity))
+            }
         }
     }
 }
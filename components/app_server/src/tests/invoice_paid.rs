@@ -0,0 +1,121 @@
+use futures::channel::mpsc;
+use futures::executor::ThreadPool;
+use futures::task::Spawn;
+use futures::{SinkExt, StreamExt};
+
+use crypto::hash_lock::{HashedLock, HASHED_LOCK_LEN};
+use crypto::identity::{PublicKey, PUBLIC_KEY_LEN};
+use crypto::invoice_id::{InvoiceId, INVOICE_ID_LEN};
+use crypto::uid::{Uid, UID_LEN};
+
+use proto::app_server::messages::{AppPermissions, AppServerToApp};
+use proto::funder::messages::{FunderOutgoingControl, InvoicePaid, InvoiceTransaction};
+use proto::index_client::messages::{
+    IndexClientReportMutation, IndexClientReportMutations, IndexClientToAppServer,
+};
+use proto::index_server::messages::NamedIndexServerAddress;
+
+use super::utils::spawn_dummy_app_server;
+
+async fn task_app_server_loop_invoice_paid<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let (
+        mut funder_sender,
+        _funder_receiver,
+        mut index_client_sender,
+        _index_client_receiver,
+        mut connections_sender,
+        _initial_node_report,
+    ) = spawn_dummy_app_server(spawner.clone());
+
+    // A seller app:
+    let (_app_sender0, app_server_receiver) = mpsc::channel(0);
+    let (app_server_sender, mut app_receiver0) = mpsc::channel(0);
+    let app_server_conn_pair = (app_server_sender, app_server_receiver);
+    let app_permissions = AppPermissions {
+        routes: false,
+        buyer: false,
+        seller: true,
+        config: false,
+    };
+    await!(connections_sender.send((app_permissions, app_server_conn_pair))).unwrap();
+
+    // An app without seller permissions:
+    let (_app_sender1, app_server_receiver) = mpsc::channel(0);
+    let (app_server_sender, mut app_receiver1) = mpsc::channel(0);
+    let app_server_conn_pair = (app_server_sender, app_server_receiver);
+    let app_permissions = AppPermissions {
+        routes: true,
+        buyer: true,
+        seller: false,
+        config: true,
+    };
+    await!(connections_sender.send((app_permissions, app_server_conn_pair))).unwrap();
+
+    // The apps should receive the current node report as the first message:
+    let to_app_message = await!(app_receiver0.next()).unwrap();
+    match to_app_message {
+        AppServerToApp::Report(_) => {}
+        _ => unreachable!(),
+    };
+    let to_app_message = await!(app_receiver1.next()).unwrap();
+    match to_app_message {
+        AppServerToApp::Report(_) => {}
+        _ => unreachable!(),
+    };
+
+    // The funder notifies that an invoice was fully paid:
+    let invoice_paid = InvoicePaid {
+        invoice_id: InvoiceId::from(&[1; INVOICE_ID_LEN]),
+        total_dest_payment: 20,
+        transactions: vec![InvoiceTransaction {
+            request_id: Uid::from(&[2; UID_LEN]),
+            dest_hashed_lock: HashedLock::from(&[3; HASHED_LOCK_LEN]),
+            dest_payment: 20,
+        }],
+    };
+    await!(funder_sender.send(FunderOutgoingControl::InvoicePaid(invoice_paid.clone()))).unwrap();
+
+    // Only the seller app should get the notification:
+    let to_app_message = await!(app_receiver0.next()).unwrap();
+    match to_app_message {
+        AppServerToApp::InvoicePaid(received_invoice_paid) => {
+            assert_eq!(received_invoice_paid, invoice_paid)
+        }
+        _ => unreachable!(),
+    };
+
+    // Send a dummy report message from IndexClient:
+    let named_index_server_address = NamedIndexServerAddress {
+        public_key: PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]),
+        address: 300u32,
+        name: "IndexServer300".to_string(),
+    };
+    let index_client_report_mutations = IndexClientReportMutations {
+        opt_app_request_id: None,
+        mutations: vec![IndexClientReportMutation::AddIndexServer(
+            named_index_server_address,
+        )],
+    };
+    await!(
+        index_client_sender.send(IndexClientToAppServer::ReportMutations(
+            index_client_report_mutations
+        ))
+    )
+    .unwrap();
+
+    // The next message the second app gets is the report, and not the notification:
+    let to_app_message = await!(app_receiver1.next()).unwrap();
+    match to_app_message {
+        AppServerToApp::ReportMutations(_) => {}
+        _ => unreachable!(),
+    };
+}
+
+#[test]
+fn test_app_server_loop_invoice_paid() {
+    let mut thread_pool = ThreadPool::new().unwrap();
+    thread_pool.run(task_app_server_loop_invoice_paid(thread_pool.clone()));
+}
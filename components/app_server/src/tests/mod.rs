@@ -1,6 +1,7 @@
 mod all_apps_closed;
 mod funder_command;
 mod index_client_command;
+mod invoice_paid;
 mod request_routes;
 mod request_send_funds;
 mod two_apps;
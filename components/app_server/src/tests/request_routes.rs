@@ -10,7 +10,7 @@ use crypto::uid::UID_LEN;
 use proto::app_server::messages::{AppPermissions, AppRequest, AppServerToApp, AppToAppServer};
 use proto::index_client::messages::{
     AppServerToIndexClient, ClientResponseRoutes, IndexClientRequest, IndexClientToAppServer,
-    RequestRoutes, ResponseRoutesResult,
+    RequestRoutes, ResponseRoutesResult, RouteSearchMode,
 };
 
 use super::utils::spawn_dummy_app_server;
@@ -62,6 +62,7 @@ where
         source: PublicKey::from(&[0xee; PUBLIC_KEY_LEN]),
         destination: PublicKey::from(&[0xff; PUBLIC_KEY_LEN]),
         opt_exclude: None,
+        search_mode: RouteSearchMode::Shortest,
     };
 
     let to_app_server = AppToAppServer::new(
@@ -10,8 +10,8 @@ use crypto::uid::{Uid, UID_LEN};
 
 use proto::app_server::messages::{AppPermissions, AppRequest, AppServerToApp, AppToAppServer};
 use proto::funder::messages::{
-    CreatePayment, CreateTransaction, FriendsRoute, FunderControl, FunderOutgoingControl,
-    RequestResult, TransactionResult,
+    CreatePayment, CreateTransaction, FailureReason, FriendsRoute, FunderControl,
+    FunderOutgoingControl, RequestFailure, RequestResult, TransactionResult,
 };
 
 use super::utils::spawn_dummy_app_server;
@@ -64,6 +64,8 @@ where
         invoice_id: InvoiceId::from(&[2; INVOICE_ID_LEN]),
         total_dest_payment: 20,
         dest_public_key: pk_f.clone(),
+        opt_dest_dh_public_key: None,
+        opt_deadline_ticks: None,
     };
     let to_app_server = AppToAppServer::new(
         Uid::from(&[22; UID_LEN]),
@@ -115,7 +117,10 @@ where
     // Funder returns a TransactionResult that is not related to any open request.
     let transaction_result = TransactionResult {
         request_id: Uid::from(&[2; UID_LEN]),
-        result: RequestResult::Failure,
+        result: RequestResult::Failure(RequestFailure {
+            reporting_public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+            reason: FailureReason::NoCapacity,
+        }),
     };
     await!(funder_sender.send(FunderOutgoingControl::TransactionResult(transaction_result)))
         .unwrap();
@@ -127,7 +132,10 @@ where
     // Funder returns a response that corresponds to the open request:
     let transaction_result = TransactionResult {
         request_id: Uid::from(&[3; UID_LEN]),
-        result: RequestResult::Failure,
+        result: RequestResult::Failure(RequestFailure {
+            reporting_public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+            reason: FailureReason::NoCapacity,
+        }),
     };
     await!(funder_sender.send(FunderOutgoingControl::TransactionResult(
         transaction_result.clone()
@@ -149,7 +157,10 @@ where
     // has a matching id:
     let transaction_result = TransactionResult {
         request_id: Uid::from(&[3; UID_LEN]),
-        result: RequestResult::Failure,
+        result: RequestResult::Failure(RequestFailure {
+            reporting_public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+            reason: FailureReason::NoCapacity,
+        }),
     };
     await!(funder_sender.send(FunderOutgoingControl::TransactionResult(transaction_result)))
         .unwrap();
@@ -4,6 +4,7 @@ use futures::{FutureExt, TryFutureExt};
 
 use im::hashmap::HashMap as ImHashMap;
 
+use crypto::dh::{DhPublicKey, DH_PUBLIC_KEY_LEN};
 use crypto::identity::{PublicKey, PUBLIC_KEY_LEN};
 
 use proto::app_server::messages::{NamedRelayAddress, NodeReport};
@@ -59,6 +60,7 @@ where
     // Create a dummy initial_node_report:
     let funder_report = FunderReport {
         local_public_key: PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]),
+        local_dh_public_key: DhPublicKey::from(&[0xab; DH_PUBLIC_KEY_LEN]),
         relays: vec![dummy_named_relay_address(0), dummy_named_relay_address(1)]
             .into_iter()
             .collect(),
@@ -66,6 +68,7 @@ where
         num_open_invoices: 0,
         num_payments: 0,
         num_open_transactions: 0,
+        accept_spontaneous_payments: false,
     };
 
     let server100 = NamedIndexServerAddress {
@@ -12,6 +12,10 @@
     clippy::new_without_default
 )]
 
+#[macro_use]
+extern crate log;
+
+pub mod node_db;
 pub mod stindexlib;
 pub mod stmgrlib;
 pub mod stnodelib;
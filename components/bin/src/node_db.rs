@@ -0,0 +1,193 @@
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use derive_more::*;
+
+use structopt::StructOpt;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use common::mutable_state::MutableState;
+use database::file_db::{FileDb, FileDbError};
+use database::log_db::{LogDb, LogDbError};
+use database::migrate::VersionedState;
+use database::AtomicDb;
+
+/// The kind of storage used for the node database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbBackend {
+    /// A single file, rewritten on every change
+    File,
+    /// A directory containing a snapshot and an append-only log of mutations
+    Log,
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "invalid database backend (Expected \"file\" or \"log\")")]
+pub struct ParseDbBackendError;
+
+impl FromStr for DbBackend {
+    type Err = ParseDbBackendError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(DbBackend::File),
+            "log" => Ok(DbBackend::Log),
+            _ => Err(ParseDbBackendError),
+        }
+    }
+}
+
+/// Options for opening an encrypted node database.
+/// If none of them is provided, the database is not encrypted.
+#[derive(Debug, Clone, StructOpt)]
+pub struct DbSecretOpt {
+    /// Key file for database encryption (Any secret content, for example 32 random bytes)
+    #[structopt(parse(from_os_str), long = "db-keyfile")]
+    pub opt_db_keyfile: Option<PathBuf>,
+    /// Name of an environment variable holding a passphrase for database encryption
+    #[structopt(long = "db-passphrase-env")]
+    pub opt_db_passphrase_env: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum DbSecretError {
+    /// Both a key file and a passphrase were provided
+    MultipleSecrets,
+    ReadKeyFileError,
+    EmptyKeyFile,
+    MissingPassphraseEnv,
+    EmptyPassphrase,
+}
+
+impl DbSecretOpt {
+    /// Obtain the secret used to derive the database encryption key, if any.
+    pub fn load_secret(&self) -> Result<Option<Vec<u8>>, DbSecretError> {
+        match (&self.opt_db_keyfile, &self.opt_db_passphrase_env) {
+            (Some(_), Some(_)) => Err(DbSecretError::MultipleSecrets),
+            (Some(db_keyfile), None) => {
+                let secret = fs::read(db_keyfile).map_err(|_| DbSecretError::ReadKeyFileError)?;
+                if secret.is_empty() {
+                    return Err(DbSecretError::EmptyKeyFile);
+                }
+                Ok(Some(secret))
+            }
+            (None, Some(db_passphrase_env)) => {
+                let passphrase =
+                    env::var(db_passphrase_env).map_err(|_| DbSecretError::MissingPassphraseEnv)?;
+                if passphrase.is_empty() {
+                    return Err(DbSecretError::EmptyPassphrase);
+                }
+                Ok(Some(passphrase.into_bytes()))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NodeDbError<ME> {
+    FileDbError(FileDbError<ME>),
+    LogDbError(LogDbError<ME>),
+}
+
+/// A node database, using one of the available backends.
+pub enum NodeDb<S> {
+    File(FileDb<S>),
+    Log(LogDb<S>),
+}
+
+impl<S> NodeDb<S>
+where
+    S: Debug + Clone + Serialize + DeserializeOwned + MutableState + VersionedState,
+    S::Mutation: Clone + Serialize + DeserializeOwned,
+    S::MutateError: Debug,
+{
+    /// Create a new database using the given backend.
+    /// If a secret is provided, the database is encrypted.
+    pub fn create(
+        db_backend: DbBackend,
+        path_buf: PathBuf,
+        initial_state: S,
+        opt_secret: Option<&[u8]>,
+    ) -> Result<Self, NodeDbError<S::MutateError>> {
+        Ok(match (db_backend, opt_secret) {
+            (DbBackend::File, None) => NodeDb::File(
+                FileDb::create(path_buf, initial_state).map_err(NodeDbError::FileDbError)?,
+            ),
+            (DbBackend::File, Some(secret)) => NodeDb::File(
+                FileDb::create_encrypted(path_buf, initial_state, secret)
+                    .map_err(NodeDbError::FileDbError)?,
+            ),
+            (DbBackend::Log, None) => NodeDb::Log(
+                LogDb::create(path_buf, initial_state).map_err(NodeDbError::LogDbError)?,
+            ),
+            (DbBackend::Log, Some(secret)) => NodeDb::Log(
+                LogDb::create_encrypted(path_buf, initial_state, secret)
+                    .map_err(NodeDbError::LogDbError)?,
+            ),
+        })
+    }
+
+    /// Load an existing database using the given backend.
+    /// A secret must be provided if the database is encrypted.
+    pub fn load(
+        db_backend: DbBackend,
+        path_buf: PathBuf,
+        opt_secret: Option<&[u8]>,
+    ) -> Result<Self, NodeDbError<S::MutateError>> {
+        Ok(match (db_backend, opt_secret) {
+            (DbBackend::File, None) => {
+                NodeDb::File(FileDb::load(path_buf).map_err(NodeDbError::FileDbError)?)
+            }
+            (DbBackend::File, Some(secret)) => NodeDb::File(
+                FileDb::load_encrypted(path_buf, secret).map_err(NodeDbError::FileDbError)?,
+            ),
+            (DbBackend::Log, None) => {
+                NodeDb::Log(LogDb::load(path_buf).map_err(NodeDbError::LogDbError)?)
+            }
+            (DbBackend::Log, Some(secret)) => NodeDb::Log(
+                LogDb::load_encrypted(path_buf, secret).map_err(NodeDbError::LogDbError)?,
+            ),
+        })
+    }
+
+    /// Write the current state to the database, using the current schema version.
+    pub fn save(&mut self) -> Result<(), NodeDbError<S::MutateError>> {
+        match self {
+            NodeDb::File(file_db) => file_db.save().map_err(NodeDbError::FileDbError),
+            NodeDb::Log(log_db) => log_db.compact().map_err(NodeDbError::LogDbError),
+        }
+    }
+}
+
+impl<S> AtomicDb for NodeDb<S>
+where
+    S: Debug + Clone + Serialize + DeserializeOwned + MutableState + VersionedState,
+    S::Mutation: Clone + Serialize + DeserializeOwned,
+    S::MutateError: Debug,
+{
+    type State = S;
+    type Mutation = S::Mutation;
+    type Error = NodeDbError<S::MutateError>;
+
+    fn get_state(&self) -> &Self::State {
+        match self {
+            NodeDb::File(file_db) => file_db.get_state(),
+            NodeDb::Log(log_db) => log_db.get_state(),
+        }
+    }
+
+    fn mutate_db(&mut self, mutations: &[Self::Mutation]) -> Result<(), Self::Error> {
+        match self {
+            NodeDb::File(file_db) => file_db
+                .mutate_db(mutations)
+                .map_err(NodeDbError::FileDbError),
+            NodeDb::Log(log_db) => log_db.mutate_db(mutations).map_err(NodeDbError::LogDbError),
+        }
+    }
+}
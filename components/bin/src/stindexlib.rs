@@ -1,11 +1,14 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
+use futures::channel::mpsc;
 use futures::executor::ThreadPool;
 use futures::task::SpawnExt;
+use futures::StreamExt;
 
 use structopt::StructOpt;
 
@@ -16,7 +19,10 @@ use crypto::crypto_rand::system_random;
 
 use identity::{create_identity, IdentityClient};
 
-use index_server::{net_index_server, NetIndexServerError};
+use index_server::{
+    load_snapshot_from_file, net_index_server, store_snapshot_to_file, IndexServerSnapshot,
+    NetIndexServerError, RateLimitConfig, SnapshotFileError,
+};
 use proto::consts::{MAX_FRAME_LENGTH, TICK_MS};
 use timer::create_timer;
 
@@ -31,6 +37,19 @@ use proto::file::index_server::{load_trusted_servers, IndexServerDirectoryError}
 pub const MAX_CONCURRENT_ENCRYPT: usize = 0x200;
 /// Amount of ticks we wait before attempting to reconnect to a remote index server.
 pub const BACKOFF_TICKS: usize = 0x8;
+/// Amount of ticks between two consecutive saves of the index server state.
+pub const SNAPSHOT_TICKS: usize = 0x40;
+/// Name of the file (inside the state directory) used to save the index server state.
+pub const SNAPSHOT_FILE_NAME: &str = "index.snapshot";
+
+/// Amount of ticks in a window used for counting messages from clients.
+pub const RATE_LIMIT_WINDOW_TICKS: usize = 60 * (1000 / TICK_MS); // 1 minute
+/// Maximum amount of mutations updates a client may send during one window.
+pub const MAX_MUTATIONS_UPDATES: usize = 0x200;
+/// Maximum amount of routes requests a client may send during one window.
+pub const MAX_REQUEST_ROUTES: usize = 0x100;
+/// Amount of ticks a client that exceeded a limit is not allowed to reconnect.
+pub const RATE_LIMIT_BACKOFF_TICKS: usize = 5 * 60 * (1000 / TICK_MS); // 5 minutes
 
 /// stindex: Offst Index Server
 /// A server used to index the Offst network. Collects topology information from nodes, and serves
@@ -50,6 +69,26 @@ pub struct StIndexCmd {
     /// Directory path of trusted index servers
     #[structopt(parse(from_os_str), short = "t", long = "trusted")]
     pub trusted: PathBuf,
+    /// Directory path for saving the index server state.
+    /// If provided, the saved state is loaded on startup.
+    #[structopt(parse(from_os_str), long = "state-dir")]
+    pub state_dir: Option<PathBuf>,
+    /// Connect to other index servers through a SOCKS5 proxy (Example: 127.0.0.1:9050)
+    #[structopt(long = "socks5")]
+    pub socks5: Option<SocketAddr>,
+    /// Amount of ticks in a window used for counting messages from clients (1 minute by default)
+    #[structopt(long = "rate-limit-window-ticks")]
+    pub rate_limit_window_ticks: Option<usize>,
+    /// Maximum amount of mutations updates a client may send during one window (512 by default)
+    #[structopt(long = "max-mutations-updates")]
+    pub max_mutations_updates: Option<usize>,
+    /// Maximum amount of routes requests a client may send during one window (256 by default)
+    #[structopt(long = "max-request-routes")]
+    pub max_request_routes: Option<usize>,
+    /// Amount of ticks a client that exceeded a limit is not allowed to reconnect
+    /// (5 minutes by default)
+    #[structopt(long = "rate-limit-backoff-ticks")]
+    pub rate_limit_backoff_ticks: Option<usize>,
 }
 
 #[allow(clippy::enum_variant_names)]
@@ -61,6 +100,35 @@ pub enum IndexServerBinError {
     LoadIdentityError,
     CreateIdentityError,
     LoadTrustedServersError(IndexServerDirectoryError),
+    LoadSnapshotError(SnapshotFileError),
+    SpawnSnapshotWriterError,
+}
+
+/// Load a saved index server state from the state directory (If exists).
+/// Time that passed since the state was saved is accounted for, so that stale nodes are not
+/// restored.
+fn load_state(state_dir: &Path) -> Result<Option<IndexServerSnapshot>, IndexServerBinError> {
+    let snapshot_path = state_dir.join(SNAPSHOT_FILE_NAME);
+    if !snapshot_path.exists() {
+        return Ok(None);
+    }
+
+    let mut snapshot =
+        load_snapshot_from_file(&snapshot_path).map_err(IndexServerBinError::LoadSnapshotError)?;
+
+    // Calculate the amount of ticks passed since the snapshot was saved:
+    let elapsed_millis = snapshot_path
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0);
+    let elapsed_ticks =
+        usize::try_from(elapsed_millis / (TICK_MS as u128)).unwrap_or(usize::max_value());
+    snapshot.elapse(elapsed_ticks);
+
+    Ok(Some(snapshot))
 }
 
 pub fn stindex(st_index_cmd: StIndexCmd) -> Result<(), IndexServerBinError> {
@@ -69,6 +137,12 @@ pub fn stindex(st_index_cmd: StIndexCmd) -> Result<(), IndexServerBinError> {
         lclient,
         lserver,
         trusted,
+        state_dir,
+        socks5,
+        rate_limit_window_ticks,
+        max_mutations_updates,
+        max_request_routes,
+        rate_limit_backoff_ticks,
     } = st_index_cmd;
 
     let identity = load_identity_from_file(Path::new(&idfile))
@@ -118,11 +192,54 @@ pub fn stindex(st_index_cmd: StIndexCmd) -> Result<(), IndexServerBinError> {
     let (_config_sender, incoming_server_raw_conns) = server_tcp_listener.listen(lserver);
 
     // A tcp connector, Used to connect to remote servers:
-    let raw_server_net_connector =
-        NetConnector::new(MAX_FRAME_LENGTH, resolve_thread_pool, thread_pool.clone());
+    let raw_server_net_connector = match socks5 {
+        Some(socks5_proxy) => NetConnector::new_socks5(
+            MAX_FRAME_LENGTH,
+            socks5_proxy,
+            resolve_thread_pool,
+            timer_client.clone(),
+            thread_pool.clone(),
+        ),
+        None => NetConnector::new(
+            MAX_FRAME_LENGTH,
+            resolve_thread_pool,
+            timer_client.clone(),
+            thread_pool.clone(),
+        ),
+    };
 
     let rng = system_random();
 
+    // Load saved state, and periodically save the current state:
+    let (opt_snapshot, opt_snapshot_sender) = match state_dir {
+        Some(state_dir) => {
+            let opt_snapshot = load_state(&state_dir)?;
+            let snapshot_path = state_dir.join(SNAPSHOT_FILE_NAME);
+            let (snapshot_sender, mut snapshot_receiver) = mpsc::channel(1);
+            let snapshot_writer = async move {
+                while let Some(snapshot) = await!(snapshot_receiver.next()) {
+                    if let Err(e) = store_snapshot_to_file(&snapshot, &snapshot_path) {
+                        error!("store_snapshot_to_file() error: {:?}", e);
+                    }
+                }
+            };
+            // Writing to disk is blocking, so we use a separate thread pool:
+            resolve_thread_pool
+                .clone()
+                .spawn(snapshot_writer)
+                .map_err(|_| IndexServerBinError::SpawnSnapshotWriterError)?;
+            (opt_snapshot, Some(snapshot_sender))
+        }
+        None => (None, None),
+    };
+
+    let rate_limit_config = RateLimitConfig {
+        window_ticks: rate_limit_window_ticks.unwrap_or(RATE_LIMIT_WINDOW_TICKS),
+        max_mutations_updates: max_mutations_updates.unwrap_or(MAX_MUTATIONS_UPDATES),
+        max_request_routes: max_request_routes.unwrap_or(MAX_REQUEST_ROUTES),
+        backoff_ticks: rate_limit_backoff_ticks.unwrap_or(RATE_LIMIT_BACKOFF_TICKS),
+    };
+
     let index_server_fut = net_index_server(
         incoming_client_raw_conns,
         incoming_server_raw_conns,
@@ -133,6 +250,10 @@ pub fn stindex(st_index_cmd: StIndexCmd) -> Result<(), IndexServerBinError> {
         trusted_servers,
         MAX_CONCURRENT_ENCRYPT,
         BACKOFF_TICKS,
+        opt_snapshot,
+        SNAPSHOT_TICKS,
+        opt_snapshot_sender,
+        rate_limit_config,
         graph_service_thread_pool,
         thread_pool.clone(),
     );
@@ -1,4 +1,6 @@
 use std::convert::TryInto;
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 
 use structopt::StructOpt;
@@ -12,6 +14,10 @@ use proto::net::messages::{NetAddress, NetAddressError};
 use proto::node::types::NodeAddress;
 
 use database::file_db::FileDb;
+use database::log_db::LogDb;
+use database::migrate::VersionedState;
+use database::AtomicDb;
+use node::export::{export_node_state, import_node_state, NodeImportError, NodeStateExport};
 use node::NodeState;
 
 use proto::file::app::{store_trusted_app_to_file, TrustedApp};
@@ -20,11 +26,14 @@ use proto::file::index_server::store_index_server_to_file;
 use proto::file::node::store_node_to_file;
 use proto::file::relay::store_relay_to_file;
 
+use crate::node_db::{DbBackend, DbSecretOpt, NodeDb};
+
 #[derive(Debug)]
 pub enum InitNodeDbError {
     OutputAlreadyExists,
     LoadIdentityError,
-    FileDbError,
+    LoadDbSecretError,
+    CreateDbError,
 }
 
 #[derive(Debug, StructOpt)]
@@ -35,6 +44,56 @@ pub struct InitNodeDbCmd {
     /// Database output file path
     #[structopt(parse(from_os_str), short = "o", long = "output")]
     pub output: PathBuf,
+    /// Database backend (file or log)
+    #[structopt(long = "db-backend", default_value = "file")]
+    pub db_backend: DbBackend,
+    #[structopt(flatten)]
+    pub db_secret_opt: DbSecretOpt,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct MigrateDbCmd {
+    /// Database file path
+    #[structopt(parse(from_os_str), short = "d", long = "database")]
+    pub database: PathBuf,
+    /// Database backend (file or log)
+    #[structopt(long = "db-backend", default_value = "file")]
+    pub db_backend: DbBackend,
+    /// Backup output file path (Defaults to the database path with a ".bak" suffix)
+    #[structopt(parse(from_os_str), short = "b", long = "backup")]
+    pub opt_backup: Option<PathBuf>,
+    #[structopt(flatten)]
+    pub db_secret_opt: DbSecretOpt,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ExportDbCmd {
+    /// Database file path
+    #[structopt(parse(from_os_str), short = "d", long = "database")]
+    pub database: PathBuf,
+    /// Database backend (file or log)
+    #[structopt(long = "db-backend", default_value = "file")]
+    pub db_backend: DbBackend,
+    #[structopt(flatten)]
+    pub db_secret_opt: DbSecretOpt,
+    /// JSON output file path
+    #[structopt(parse(from_os_str), short = "o", long = "output")]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ImportDbCmd {
+    /// JSON input file path (Created by export-db)
+    #[structopt(parse(from_os_str), short = "i", long = "input")]
+    pub input: PathBuf,
+    /// Database output file path
+    #[structopt(parse(from_os_str), short = "o", long = "output")]
+    pub output: PathBuf,
+    /// Database backend (file or log)
+    #[structopt(long = "db-backend", default_value = "file")]
+    pub db_backend: DbBackend,
+    #[structopt(flatten)]
+    pub db_secret_opt: DbSecretOpt,
 }
 
 #[derive(Debug, StructOpt)]
@@ -113,6 +172,15 @@ pub enum StMgrCmd {
     /// Initialize a new (empty) node database
     #[structopt(name = "init-node-db")]
     InitNodeDb(InitNodeDbCmd),
+    /// Upgrade a node database file to the current format (Keeps a backup)
+    #[structopt(name = "migrate-db")]
+    MigrateDb(MigrateDbCmd),
+    /// Export a node database to a human readable JSON file
+    #[structopt(name = "export-db")]
+    ExportDb(ExportDbCmd),
+    /// Create a new node database from a JSON file (Created by export-db)
+    #[structopt(name = "import-db")]
+    ImportDb(ImportDbCmd),
     /// Randomly generate a new identity file
     #[structopt(name = "gen-ident")]
     GenIdent(GenIdentCmd),
@@ -130,7 +198,14 @@ pub enum StMgrCmd {
     NodeTicket(NodeTicketCmd),
 }
 
-fn init_node_db(InitNodeDbCmd { idfile, output }: InitNodeDbCmd) -> Result<(), InitNodeDbError> {
+fn init_node_db(
+    InitNodeDbCmd {
+        idfile,
+        output,
+        db_backend,
+        db_secret_opt,
+    }: InitNodeDbCmd,
+) -> Result<(), InitNodeDbError> {
     // Make sure that output does not exist.
     // This program should never override any file!
     // (Otherwise users might erase their database by
@@ -144,9 +219,186 @@ fn init_node_db(InitNodeDbCmd { idfile, output }: InitNodeDbCmd) -> Result<(), I
         load_identity_from_file(&idfile).map_err(|_| InitNodeDbError::LoadIdentityError)?;
     let local_public_key = identity.get_public_key();
 
-    // Create a new database file:
+    let opt_db_secret = db_secret_opt
+        .load_secret()
+        .map_err(|_| InitNodeDbError::LoadDbSecretError)?;
+
+    // Create a new database:
     let initial_state = NodeState::<NetAddress>::new(local_public_key);
-    let _ = FileDb::create(output, initial_state).map_err(|_| InitNodeDbError::FileDbError)?;
+    let _ = NodeDb::create(
+        db_backend,
+        output,
+        initial_state,
+        opt_db_secret.as_ref().map(|secret| &secret[..]),
+    )
+    .map_err(|_| InitNodeDbError::CreateDbError)?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum MigrateDbError {
+    LoadDbSecretError,
+    ReadVersionError,
+    UnsupportedVersion(u32),
+    AlreadyCurrentVersion(u32),
+    BackupAlreadyExists,
+    BackupError,
+    LoadDbError,
+    SaveDbError,
+}
+
+/// Copy all the files of a directory into a new directory
+fn copy_dir(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        fs::copy(entry.path(), dst.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// Upgrade a node database to the current schema version, in place.
+/// A copy of the original database is kept as a backup.
+/// Migrating a database that is already at the current version is an error, and leaves the
+/// database untouched.
+fn migrate_db(
+    MigrateDbCmd {
+        database,
+        db_backend,
+        opt_backup,
+        db_secret_opt,
+    }: MigrateDbCmd,
+) -> Result<(), MigrateDbError> {
+    let opt_db_secret = db_secret_opt
+        .load_secret()
+        .map_err(|_| MigrateDbError::LoadDbSecretError)?;
+    let opt_db_secret = opt_db_secret.as_ref().map(|secret| &secret[..]);
+
+    let version = match db_backend {
+        DbBackend::File => FileDb::<NodeState<NetAddress>>::file_version(&database, opt_db_secret)
+            .map_err(|_| MigrateDbError::ReadVersionError)?,
+        DbBackend::Log => LogDb::<NodeState<NetAddress>>::dir_version(&database, opt_db_secret)
+            .map_err(|_| MigrateDbError::ReadVersionError)?,
+    };
+    let current_version = NodeState::<NetAddress>::migrations().current_version();
+
+    if version > current_version {
+        return Err(MigrateDbError::UnsupportedVersion(version));
+    }
+    if version == current_version {
+        return Err(MigrateDbError::AlreadyCurrentVersion(version));
+    }
+
+    // Loading the database migrates it in memory:
+    let mut node_db =
+        NodeDb::<NodeState<NetAddress>>::load(db_backend, database.clone(), opt_db_secret)
+            .map_err(|_| MigrateDbError::LoadDbError)?;
+
+    let backup = match opt_backup {
+        Some(backup) => backup,
+        None => {
+            let mut backup = database.clone().into_os_string();
+            backup.push(".bak");
+            PathBuf::from(backup)
+        }
+    };
+    // Never override an existing file:
+    if backup.exists() {
+        return Err(MigrateDbError::BackupAlreadyExists);
+    }
+    match db_backend {
+        DbBackend::File => fs::copy(&database, &backup).map(|_| ()),
+        DbBackend::Log => copy_dir(&database, &backup),
+    }
+    .map_err(|_| MigrateDbError::BackupError)?;
+
+    node_db.save().map_err(|_| MigrateDbError::SaveDbError)?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum ExportDbError {
+    OutputAlreadyExists,
+    LoadDbSecretError,
+    LoadDbError,
+    SerializeError,
+    WriteOutputError,
+}
+
+/// Export the state of a node database to a JSON file
+fn export_db(
+    ExportDbCmd {
+        database,
+        db_backend,
+        db_secret_opt,
+        output,
+    }: ExportDbCmd,
+) -> Result<(), ExportDbError> {
+    // Never override an existing file:
+    if output.exists() {
+        return Err(ExportDbError::OutputAlreadyExists);
+    }
+
+    let opt_db_secret = db_secret_opt
+        .load_secret()
+        .map_err(|_| ExportDbError::LoadDbSecretError)?;
+
+    let node_db = NodeDb::<NodeState<NetAddress>>::load(
+        db_backend,
+        database,
+        opt_db_secret.as_ref().map(|secret| &secret[..]),
+    )
+    .map_err(|_| ExportDbError::LoadDbError)?;
+
+    let node_state_export = export_node_state(node_db.get_state());
+    let data = serde_json::to_string_pretty(&node_state_export)
+        .map_err(|_| ExportDbError::SerializeError)?;
+    fs::write(&output, data).map_err(|_| ExportDbError::WriteOutputError)
+}
+
+#[derive(Debug)]
+pub enum ImportDbError {
+    OutputAlreadyExists,
+    ReadInputError,
+    DeserializeError,
+    /// The input file is not a valid node state
+    InvalidNodeState(NodeImportError),
+    LoadDbSecretError,
+    CreateDbError,
+}
+
+/// Create a new node database from a JSON file
+fn import_db(
+    ImportDbCmd {
+        input,
+        output,
+        db_backend,
+        db_secret_opt,
+    }: ImportDbCmd,
+) -> Result<(), ImportDbError> {
+    // Never override an existing database:
+    if output.exists() {
+        return Err(ImportDbError::OutputAlreadyExists);
+    }
+
+    let data = fs::read_to_string(&input).map_err(|_| ImportDbError::ReadInputError)?;
+    let node_state_export: NodeStateExport =
+        serde_json::from_str(&data).map_err(|_| ImportDbError::DeserializeError)?;
+    let node_state =
+        import_node_state(&node_state_export).map_err(ImportDbError::InvalidNodeState)?;
+
+    let opt_db_secret = db_secret_opt
+        .load_secret()
+        .map_err(|_| ImportDbError::LoadDbSecretError)?;
+
+    let _ = NodeDb::create(
+        db_backend,
+        output,
+        node_state,
+        opt_db_secret.as_ref().map(|secret| &secret[..]),
+    )
+    .map_err(|_| ImportDbError::CreateDbError)?;
 
     Ok(())
 }
@@ -348,6 +600,9 @@ fn node_ticket(
 #[derive(Debug)]
 pub enum StmError {
     InitNodeDbError(InitNodeDbError),
+    MigrateDbError(MigrateDbError),
+    ExportDbError(ExportDbError),
+    ImportDbError(ImportDbError),
     GenIdentityError(GenIdentityError),
     AppTicketError(AppTicketError),
     RelayTicketError(RelayTicketError),
@@ -361,6 +616,24 @@ impl From<InitNodeDbError> for StmError {
     }
 }
 
+impl From<MigrateDbError> for StmError {
+    fn from(e: MigrateDbError) -> Self {
+        StmError::MigrateDbError(e)
+    }
+}
+
+impl From<ExportDbError> for StmError {
+    fn from(e: ExportDbError) -> Self {
+        StmError::ExportDbError(e)
+    }
+}
+
+impl From<ImportDbError> for StmError {
+    fn from(e: ImportDbError) -> Self {
+        StmError::ImportDbError(e)
+    }
+}
+
 impl From<GenIdentityError> for StmError {
     fn from(e: GenIdentityError) -> Self {
         StmError::GenIdentityError(e)
@@ -394,6 +667,9 @@ impl From<NodeTicketError> for StmError {
 pub fn stmgr(st_mgr_cmd: StMgrCmd) -> Result<(), StmError> {
     match st_mgr_cmd {
         StMgrCmd::InitNodeDb(i) => init_node_db(i)?,
+        StMgrCmd::MigrateDb(i) => migrate_db(i)?,
+        StMgrCmd::ExportDb(i) => export_db(i)?,
+        StMgrCmd::ImportDb(i) => import_db(i)?,
         StMgrCmd::GenIdent(i) => gen_identity(i)?,
         StMgrCmd::AppTicket(i) => app_ticket(i)?,
         StMgrCmd::RelayTicket(i) => relay_ticket(i)?,
@@ -403,3 +679,153 @@ pub fn stmgr(st_mgr_cmd: StMgrCmd) -> Result<(), StmError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    use crypto::identity::{PublicKey, PUBLIC_KEY_LEN};
+    use database::cipher::{encode_cipher_header, load_cipher};
+    use database::migrate::split_header;
+
+    #[test]
+    fn test_migrate_encrypted_db() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("node.db");
+        let keyfile_path = dir.path().join("node.key");
+        fs::write(&keyfile_path, b"secret").unwrap();
+
+        let local_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let file_db = FileDb::create_encrypted(
+            db_path.clone(),
+            NodeState::<NetAddress>::new(local_public_key.clone()),
+            b"secret",
+        )
+        .unwrap();
+        drop(file_db);
+
+        // Turn it into a legacy (version 0) encrypted database, which has no schema header:
+        let file_buff = fs::read(&db_path).unwrap();
+        let (opt_cipher, sealed_buff) = load_cipher(&file_buff, Some(&b"secret"[..])).unwrap();
+        let cipher = opt_cipher.unwrap();
+        let plain_buff = cipher.open(sealed_buff).unwrap();
+        let (_, state_buff) = split_header(&plain_buff);
+        let mut legacy_buff = encode_cipher_header(cipher.kdf_params());
+        legacy_buff.extend_from_slice(&cipher.seal(state_buff).unwrap());
+        fs::write(&db_path, &legacy_buff).unwrap();
+
+        let migrate_db_cmd = MigrateDbCmd {
+            database: db_path.clone(),
+            db_backend: DbBackend::File,
+            opt_backup: None,
+            db_secret_opt: DbSecretOpt {
+                opt_db_keyfile: Some(keyfile_path),
+                opt_db_passphrase_env: None,
+            },
+        };
+        migrate_db(migrate_db_cmd).unwrap();
+
+        assert_eq!(
+            FileDb::<NodeState<NetAddress>>::file_version(&db_path, Some(&b"secret"[..])).unwrap(),
+            NodeState::<NetAddress>::migrations().current_version()
+        );
+        let file_db =
+            FileDb::<NodeState<NetAddress>>::load_encrypted(db_path.clone(), b"secret").unwrap();
+        assert_eq!(
+            file_db.get_state().funder_state.local_public_key,
+            local_public_key
+        );
+
+        // The original database is kept as a backup:
+        assert_eq!(
+            fs::read(dir.path().join("node.db.bak")).unwrap(),
+            legacy_buff
+        );
+    }
+
+    #[test]
+    fn test_migrate_log_db() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("node.db");
+        let snapshot_path = db_path.join("snapshot");
+
+        let local_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let log_db = LogDb::create(
+            db_path.clone(),
+            NodeState::<NetAddress>::new(local_public_key.clone()),
+        )
+        .unwrap();
+        drop(log_db);
+
+        // Turn it into a legacy (version 0) database, which has no schema header:
+        let snapshot_buff = fs::read(&snapshot_path).unwrap();
+        let (_, legacy_buff) = split_header(&snapshot_buff);
+        fs::write(&snapshot_path, legacy_buff).unwrap();
+        assert_eq!(
+            LogDb::<NodeState<NetAddress>>::dir_version(&db_path, None).unwrap(),
+            0
+        );
+
+        let migrate_db_cmd = MigrateDbCmd {
+            database: db_path.clone(),
+            db_backend: DbBackend::Log,
+            opt_backup: None,
+            db_secret_opt: DbSecretOpt {
+                opt_db_keyfile: None,
+                opt_db_passphrase_env: None,
+            },
+        };
+        migrate_db(migrate_db_cmd).unwrap();
+
+        assert_eq!(
+            LogDb::<NodeState<NetAddress>>::dir_version(&db_path, None).unwrap(),
+            NodeState::<NetAddress>::migrations().current_version()
+        );
+        let log_db = LogDb::<NodeState<NetAddress>>::load(db_path.clone()).unwrap();
+        assert_eq!(
+            log_db.get_state().funder_state.local_public_key,
+            local_public_key
+        );
+
+        // The original database is kept as a backup:
+        assert_eq!(
+            fs::read(dir.path().join("node.db.bak").join("snapshot")).unwrap(),
+            legacy_buff
+        );
+    }
+
+    #[test]
+    fn test_migrate_current_db() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("node.db");
+
+        let local_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let file_db = FileDb::create(
+            db_path.clone(),
+            NodeState::<NetAddress>::new(local_public_key.clone()),
+        )
+        .unwrap();
+        drop(file_db);
+
+        let migrate_db_cmd = MigrateDbCmd {
+            database: db_path.clone(),
+            db_backend: DbBackend::File,
+            opt_backup: None,
+            db_secret_opt: DbSecretOpt {
+                opt_db_keyfile: None,
+                opt_db_passphrase_env: None,
+            },
+        };
+        let current_version = NodeState::<NetAddress>::migrations().current_version();
+        match migrate_db(migrate_db_cmd) {
+            Err(MigrateDbError::AlreadyCurrentVersion(version)) => {
+                assert_eq!(version, current_version)
+            }
+            _ => unreachable!(),
+        };
+
+        // No backup is created:
+        assert!(!dir.path().join("node.db.bak").exists());
+    }
+}
@@ -4,12 +4,14 @@ use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::time::Duration;
 
+use futures::channel::mpsc;
 use futures::executor::ThreadPool;
-use futures::task::SpawnExt;
+use futures::stream::select;
+use futures::task::{Spawn, SpawnExt};
 
 use structopt::StructOpt;
 
-use common::conn::Listener;
+use common::conn::{ConnPairVec, Listener};
 use common::int_convert::usize_to_u64;
 
 use crypto::crypto_rand::system_random;
@@ -19,18 +21,20 @@ use timer::create_timer;
 
 use node::{net_node, NetNodeError, NodeConfig, NodeState};
 
-use database::file_db::FileDb;
-
+#[cfg(unix)]
+use net::UnixListener;
 use net::{NetConnector, TcpListener};
 use proto::consts::{
-    KEEPALIVE_TICKS, MAX_FRAME_LENGTH, MAX_NODE_RELAYS, MAX_OPERATIONS_IN_BATCH, TICKS_TO_REKEY,
-    TICK_MS,
+    KEEPALIVE_TICKS, MAX_FRAME_LENGTH, MAX_NODE_RELAYS, MAX_OPERATIONS_IN_BATCH,
+    PAYMENT_TIMEOUT_TICKS, TICKS_TO_REKEY, TICK_MS,
 };
 use proto::net::messages::NetAddress;
 
 use proto::file::app::load_trusted_apps;
 use proto::file::identity::load_identity_from_file;
 
+use crate::node_db::{DbBackend, DbSecretOpt, NodeDb};
+
 /// Memory allocated to a channel in memory (Used to connect two components)
 const CHANNEL_LEN: usize = 0x20;
 /// The amount of ticks we wait before attempting to reconnect
@@ -55,8 +59,11 @@ pub enum NodeBinError {
     LoadIdentityError,
     CreateThreadPoolError,
     CreateTimerError,
+    LoadDbSecretError,
     LoadDbError,
     SpawnError,
+    NoListenAddress,
+    UnixSocketUnsupported,
     NetNodeError(NetNodeError),
 }
 
@@ -73,23 +80,82 @@ pub struct StNodeCmd {
     pub idfile: PathBuf,
     /// Listening address (Used for communication with apps)
     #[structopt(short = "l", long = "laddr")]
-    pub laddr: SocketAddr,
+    pub laddr: Option<SocketAddr>,
+    /// Unix domain socket path to listen on (Used for communication with local apps).
+    /// Only the owner of the node process may connect to the socket.
+    #[structopt(parse(from_os_str), long = "lsock")]
+    pub lsock: Option<PathBuf>,
     /// Database file path
     #[structopt(parse(from_os_str), short = "d", long = "database")]
     pub database: PathBuf,
+    /// Database backend (file or log)
+    #[structopt(long = "db-backend", default_value = "file")]
+    pub db_backend: DbBackend,
+    #[structopt(flatten)]
+    pub db_secret_opt: DbSecretOpt,
     /// Directory path of trusted applications
     #[structopt(parse(from_os_str), short = "t", long = "trusted")]
     pub trusted: PathBuf,
+    /// Connect to relays and index servers through a SOCKS5 proxy (Example: 127.0.0.1:9050)
+    #[structopt(long = "socks5")]
+    pub socks5: Option<SocketAddr>,
+    /// Default amount of timer ticks a payment may take before it times out, for payments that
+    /// do not specify their own deadline (5 minutes by default)
+    #[structopt(long = "payment-timeout-ticks")]
+    pub payment_timeout_ticks: Option<usize>,
+}
+
+/// Listen for app connections on a Unix domain socket.
+/// If `opt_lsock` is None, the returned stream of connections is empty.
+#[cfg(unix)]
+fn listen_unix_socket<S>(
+    opt_lsock: Option<PathBuf>,
+    spawner: S,
+) -> Result<mpsc::Receiver<ConnPairVec>, NodeBinError>
+where
+    S: Spawn + Send + Clone + 'static,
+{
+    Ok(match opt_lsock {
+        Some(lsock) => {
+            let app_unix_listener = UnixListener::new(MAX_FRAME_LENGTH, spawner);
+            let (_config_sender, incoming_conns) = app_unix_listener.listen(lsock);
+            incoming_conns
+        }
+        None => mpsc::channel(0).1,
+    })
+}
+
+#[cfg(not(unix))]
+fn listen_unix_socket<S>(
+    opt_lsock: Option<PathBuf>,
+    _spawner: S,
+) -> Result<mpsc::Receiver<ConnPairVec>, NodeBinError>
+where
+    S: Spawn + Send + Clone + 'static,
+{
+    match opt_lsock {
+        Some(_) => Err(NodeBinError::UnixSocketUnsupported),
+        None => Ok(mpsc::channel(0).1),
+    }
 }
 
 pub fn stnode(st_node_cmd: StNodeCmd) -> Result<(), NodeBinError> {
     let StNodeCmd {
         idfile,
         laddr,
+        lsock,
         database,
+        db_backend,
+        db_secret_opt,
         trusted,
+        socks5,
+        payment_timeout_ticks,
     } = st_node_cmd;
 
+    if laddr.is_none() && lsock.is_none() {
+        return Err(NodeBinError::NoListenAddress);
+    }
+
     // Parse identity file:
     let identity = load_identity_from_file(&idfile).map_err(|_| NodeBinError::LoadIdentityError)?;
 
@@ -135,6 +201,8 @@ pub fn stnode(st_node_cmd: StNodeCmd) -> Result<(), NodeBinError> {
         max_operations_in_batch: MAX_OPERATIONS_IN_BATCH,
         /// The size we allocate for the user send funds requests queue.
         max_pending_user_requests: MAX_PENDING_USER_REQUESTS,
+        /// Amount of ticks we wait for a payment to complete before marking it as timed out.
+        payment_timeout_ticks: payment_timeout_ticks.unwrap_or(PAYMENT_TIMEOUT_TICKS),
         /// Maximum amount of concurrent index client requests:
         max_open_index_client_requests: MAX_OPEN_INDEX_CLIENT_REQUESTS,
         /// Maximum amount of relays a node may use.
@@ -144,19 +212,47 @@ pub fn stnode(st_node_cmd: StNodeCmd) -> Result<(), NodeBinError> {
     };
 
     // A tcp connector, Used to connect to remote servers:
-    let net_connector =
-        NetConnector::new(MAX_FRAME_LENGTH, resolve_thread_pool, thread_pool.clone());
+    let net_connector = match socks5 {
+        Some(socks5_proxy) => NetConnector::new_socks5(
+            MAX_FRAME_LENGTH,
+            socks5_proxy,
+            resolve_thread_pool,
+            timer_client.clone(),
+            thread_pool.clone(),
+        ),
+        None => NetConnector::new(
+            MAX_FRAME_LENGTH,
+            resolve_thread_pool,
+            timer_client.clone(),
+            thread_pool.clone(),
+        ),
+    };
 
     // Obtain secure cryptographic random:
     let rng = system_random();
 
     // Load database:
-    let atomic_db =
-        FileDb::<NodeState<NetAddress>>::load(database).map_err(|_| NodeBinError::LoadDbError)?;
+    let opt_db_secret = db_secret_opt
+        .load_secret()
+        .map_err(|_| NodeBinError::LoadDbSecretError)?;
+    let atomic_db = NodeDb::<NodeState<NetAddress>>::load(
+        db_backend,
+        database,
+        opt_db_secret.as_ref().map(|secret| &secret[..]),
+    )
+    .map_err(|_| NodeBinError::LoadDbError)?;
 
     // Start listening to apps:
-    let app_tcp_listener = TcpListener::new(MAX_FRAME_LENGTH, thread_pool.clone());
-    let (_config_sender, incoming_app_raw_conns) = app_tcp_listener.listen(laddr);
+    let incoming_app_tcp_conns = match laddr {
+        Some(laddr) => {
+            let app_tcp_listener = TcpListener::new(MAX_FRAME_LENGTH, thread_pool.clone());
+            let (_config_sender, incoming_conns) = app_tcp_listener.listen(laddr);
+            incoming_conns
+        }
+        None => mpsc::channel(0).1,
+    };
+    let incoming_app_unix_conns = listen_unix_socket(lsock, thread_pool.clone())?;
+    let incoming_app_raw_conns = select(incoming_app_tcp_conns, incoming_app_unix_conns);
 
     // Create a closure for loading trusted apps map:
     let get_trusted_apps = move || -> Option<_> {
@@ -1,15 +1,21 @@
+use std::collections::HashSet;
+use std::fs;
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use futures::channel::mpsc;
 use futures::executor::ThreadPool;
 use futures::task::SpawnExt;
+use futures::{SinkExt, StreamExt};
 
 use structopt::StructOpt;
 
+use common::access_control::{AccessControl, AccessControlOp};
 use common::conn::Listener;
 
 use crypto::crypto_rand::system_random;
+use crypto::identity::PublicKey;
 use identity::{create_identity, IdentityClient};
 
 use proto::consts::{MAX_FRAME_LENGTH, TICK_MS};
@@ -17,16 +23,25 @@ use proto::consts::{MAX_FRAME_LENGTH, TICK_MS};
 use common::int_convert::usize_to_u64;
 
 use net::TcpListener;
-use relay::{net_relay_server, NetRelayServerError};
-use timer::create_timer;
+use relay::{net_relay_server, NetRelayServerError, TrafficLimit, TrafficMeter};
+use timer::{create_timer, TimerClient};
 
+use proto::file::friend::load_friend_from_file;
 use proto::file::identity::load_identity_from_file;
+use proto::file::node::load_node_from_file;
+use proto::file::relay::load_relay_from_file;
 
 // TODO: Maybe take as a command line argument in the future?
 /// Maximum amount of concurrent encrypted channel set-ups.
 /// We set this number to avoid DoS from half finished encrypted channel negotiations.
 pub const MAX_CONCURRENT_ENCRYPT: usize = 0x200;
 
+/// Amount of timer ticks between two reports of traffic statistics to the log
+const TRAFFIC_STATS_TICKS: usize = 0x400;
+
+/// Amount of timer ticks between two reloads of the allowed public keys directory
+const ALLOW_DIR_RELOAD_TICKS: usize = 0x40;
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum RelayServerBinError {
@@ -34,6 +49,10 @@ pub enum RelayServerBinError {
     LoadIdentityError,
     CreateIdentityError,
     CreateTimerError,
+    InvalidTrafficLimit,
+    SpawnTrafficStatsError,
+    LoadAllowDirError,
+    SpawnAllowDirError,
     NetRelayServerError(NetRelayServerError),
 }
 
@@ -48,10 +67,195 @@ pub struct StRelayCmd {
     /// Listening address (Example: 0.0.0.0:1337)
     #[structopt(short = "l", long = "laddr")]
     pub laddr: SocketAddr,
+    /// Maximum amount of concurrent connections
+    #[structopt(long = "max-conns", default_value = "4096")]
+    pub max_conns: usize,
+    /// Maximum amount of concurrent connections from a single public key
+    #[structopt(long = "max-conns-per-key", default_value = "64")]
+    pub max_conns_per_key: usize,
+    /// Maximum average amount of bytes per second a single public key may send through the relay
+    /// (Unlimited by default)
+    #[structopt(long = "max-rate")]
+    pub max_rate: Option<u64>,
+    /// Maximum amount of bytes a single public key may send in a burst
+    /// (Defaults to the amount sent in one second using --max-rate)
+    #[structopt(long = "max-burst")]
+    pub max_burst: Option<u64>,
+    /// Directory of node and relay ticket files. If specified, only the public keys of these
+    /// tickets may use the relay. The directory is reloaded periodically.
+    #[structopt(parse(from_os_str), long = "allow-dir")]
+    pub allow_dir: Option<PathBuf>,
+}
+
+/// Calculate a per tick traffic limit from the command line arguments
+fn traffic_limit(
+    opt_max_rate: Option<u64>,
+    opt_max_burst: Option<u64>,
+) -> Result<Option<TrafficLimit>, RelayServerBinError> {
+    let max_rate = match (opt_max_rate, opt_max_burst) {
+        (Some(max_rate), _) => max_rate,
+        (None, None) => return Ok(None),
+        // A burst size is meaningless without a rate:
+        (None, Some(_)) => return Err(RelayServerBinError::InvalidTrafficLimit),
+    };
+
+    let tick_ms = usize_to_u64(TICK_MS).unwrap();
+    let bytes_per_tick = max_rate
+        .checked_mul(tick_ms)
+        .ok_or(RelayServerBinError::InvalidTrafficLimit)?
+        / 1000;
+    if bytes_per_tick == 0 {
+        return Err(RelayServerBinError::InvalidTrafficLimit);
+    }
+
+    let max_burst_bytes = opt_max_burst.unwrap_or(max_rate);
+    if max_burst_bytes < bytes_per_tick {
+        return Err(RelayServerBinError::InvalidTrafficLimit);
+    }
+
+    Ok(Some(TrafficLimit {
+        bytes_per_tick,
+        max_burst_bytes,
+    }))
+}
+
+/// Periodically report traffic statistics to the log
+async fn report_traffic_stats(mut timer_client: TimerClient, traffic_meter: TrafficMeter) {
+    let mut timer_stream = match await!(timer_client.request_timer_stream()) {
+        Ok(timer_stream) => timer_stream,
+        Err(e) => {
+            error!(
+                "report_traffic_stats(): request_timer_stream() error: {:?}",
+                e
+            );
+            return;
+        }
+    };
+
+    let mut ticks: usize = 0;
+    while await!(timer_stream.next()).is_some() {
+        ticks = ticks.saturating_add(1);
+        if ticks < TRAFFIC_STATS_TICKS {
+            continue;
+        }
+        ticks = 0;
+
+        let stats = traffic_meter.stats();
+        info!(
+            "Relayed {} bytes. Recently active public keys: {}",
+            stats.total_bytes(),
+            stats.keys.len()
+        );
+        for (public_key, key_stats) in &stats.keys {
+            debug!("{:?}: {:?}", public_key, key_stats);
+        }
+    }
+}
+
+/// Load a public key from a ticket file. Node (friend) tickets, relay tickets and node address
+/// files are supported.
+fn load_ticket_public_key(path: &Path) -> Option<PublicKey> {
+    if let Ok(friend_address) = load_friend_from_file(path) {
+        return Some(friend_address.public_key);
+    }
+    if let Ok(relay_address) = load_relay_from_file(path) {
+        return Some(relay_address.public_key);
+    }
+    if let Ok(node_address) = load_node_from_file(path) {
+        return Some(node_address.public_key);
+    }
+    None
+}
+
+/// Load the public keys of all the ticket files in a given directory.
+fn load_allowed_public_keys(dir_path: &Path) -> Result<HashSet<PublicKey>, RelayServerBinError> {
+    let mut public_keys = HashSet::new();
+    for entry in fs::read_dir(dir_path).map_err(|_| RelayServerBinError::LoadAllowDirError)? {
+        let path = entry
+            .map_err(|_| RelayServerBinError::LoadAllowDirError)?
+            .path();
+        if path.is_dir() {
+            continue;
+        }
+        match load_ticket_public_key(&path) {
+            Some(public_key) => {
+                public_keys.insert(public_key);
+            }
+            None => {
+                error!("Invalid ticket file: {:?}", path);
+                return Err(RelayServerBinError::LoadAllowDirError);
+            }
+        }
+    }
+    Ok(public_keys)
+}
+
+/// Periodically reload the allowed public keys directory, and send the changes to the relay.
+async fn reload_allowed_public_keys(
+    mut timer_client: TimerClient,
+    allow_dir: PathBuf,
+    mut allowed: HashSet<PublicKey>,
+    mut access_control_sender: mpsc::Sender<AccessControlOp<PublicKey>>,
+) {
+    let mut timer_stream = match await!(timer_client.request_timer_stream()) {
+        Ok(timer_stream) => timer_stream,
+        Err(e) => {
+            error!(
+                "reload_allowed_public_keys(): request_timer_stream() error: {:?}",
+                e
+            );
+            return;
+        }
+    };
+
+    let mut ticks: usize = 0;
+    while await!(timer_stream.next()).is_some() {
+        ticks = ticks.saturating_add(1);
+        if ticks < ALLOW_DIR_RELOAD_TICKS {
+            continue;
+        }
+        ticks = 0;
+
+        let new_allowed = match load_allowed_public_keys(&allow_dir) {
+            Ok(new_allowed) => new_allowed,
+            Err(e) => {
+                // Keep the current allowed public keys until the directory is fixed:
+                warn!("Failed to reload {:?}: {:?}", allow_dir, e);
+                continue;
+            }
+        };
+
+        let mut ops = Vec::new();
+        for public_key in allowed.difference(&new_allowed) {
+            info!("Removing allowed public key: {:?}", public_key);
+            ops.push(AccessControlOp::Remove(public_key.clone()));
+        }
+        for public_key in new_allowed.difference(&allowed) {
+            info!("Adding allowed public key: {:?}", public_key);
+            ops.push(AccessControlOp::Add(public_key.clone()));
+        }
+        allowed = new_allowed;
+
+        for op in ops {
+            if await!(access_control_sender.send(op)).is_err() {
+                return;
+            }
+        }
+    }
 }
 
 pub fn strelay(st_relay_cmd: StRelayCmd) -> Result<(), RelayServerBinError> {
-    let StRelayCmd { idfile, laddr } = st_relay_cmd;
+    let StRelayCmd {
+        idfile,
+        laddr,
+        max_conns,
+        max_conns_per_key,
+        max_rate,
+        max_burst,
+        allow_dir,
+    } = st_relay_cmd;
+
+    let traffic_meter = TrafficMeter::new(traffic_limit(max_rate, max_burst)?);
 
     // Parse identity file:
     let identity =
@@ -72,6 +276,34 @@ pub fn strelay(st_relay_cmd: StRelayCmd) -> Result<(), RelayServerBinError> {
     let timer_client = create_timer(dur, thread_pool.clone())
         .map_err(|_| RelayServerBinError::CreateTimerError)?;
 
+    thread_pool
+        .spawn(report_traffic_stats(
+            timer_client.clone(),
+            traffic_meter.clone(),
+        ))
+        .map_err(|_| RelayServerBinError::SpawnTrafficStatsError)?;
+
+    let (access_control_sender, incoming_access_control) = mpsc::channel(0);
+    let opt_access_control = match allow_dir {
+        Some(allow_dir) => {
+            let allowed = load_allowed_public_keys(&allow_dir)?;
+            let mut access_control = AccessControl::new();
+            for public_key in &allowed {
+                access_control.apply_op(AccessControlOp::Add(public_key.clone()));
+            }
+            thread_pool
+                .spawn(reload_allowed_public_keys(
+                    timer_client.clone(),
+                    allow_dir,
+                    allowed,
+                    access_control_sender,
+                ))
+                .map_err(|_| RelayServerBinError::SpawnAllowDirError)?;
+            Some(access_control)
+        }
+        None => None,
+    };
+
     let rng = system_random();
 
     let tcp_listener = TcpListener::new(MAX_FRAME_LENGTH, thread_pool.clone());
@@ -83,6 +315,11 @@ pub fn strelay(st_relay_cmd: StRelayCmd) -> Result<(), RelayServerBinError> {
         timer_client,
         rng,
         MAX_CONCURRENT_ENCRYPT,
+        max_conns,
+        max_conns_per_key,
+        traffic_meter,
+        opt_access_control,
+        incoming_access_control,
         thread_pool.clone(),
     );
 
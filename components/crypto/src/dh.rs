@@ -4,11 +4,15 @@ use ring::hkdf::extract_and_expand;
 use ring::hmac::SigningKey;
 use ring::rand::SecureRandom;
 
-use super::sym_encrypt::{SymmetricKey, SYMMETRIC_KEY_LEN};
+use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret};
+
+use super::crypto_rand::CryptoRandom;
+use super::sym_encrypt::{Decryptor, Encryptor, SymmetricKey, SYMMETRIC_KEY_LEN};
 use super::CryptoError;
 
 pub const SALT_LEN: usize = 32;
 pub const DH_PUBLIC_KEY_LEN: usize = 32;
+pub const DH_SECRET_LEN: usize = 32;
 pub const SHARED_SECRET_LEN: usize = 32;
 
 define_fixed_bytes!(Salt, SALT_LEN);
@@ -26,6 +30,23 @@ impl Salt {
     }
 }
 
+/// Derive a pair of symmetric keys from a DH shared secret.
+fn derive_symmetric_keys(
+    shared_key: &[u8],
+    sent_salt: &Salt,
+    recv_salt: &Salt,
+) -> (SymmetricKey, SymmetricKey) {
+    let sent_sk = SigningKey::new(&digest::SHA512_256, sent_salt);
+    let recv_sk = SigningKey::new(&digest::SHA512_256, recv_salt);
+
+    let mut send_key = [0x00u8; SYMMETRIC_KEY_LEN];
+    let mut recv_key = [0x00u8; SYMMETRIC_KEY_LEN];
+    extract_and_expand(&sent_sk, shared_key, &[], &mut send_key);
+    extract_and_expand(&recv_sk, shared_key, &[], &mut recv_key);
+
+    (SymmetricKey::from(&send_key), SymmetricKey::from(&recv_key))
+}
+
 pub struct DhPrivateKey(EphemeralPrivateKey);
 
 impl DhPrivateKey {
@@ -62,15 +83,7 @@ impl DhPrivateKey {
             if shared_key.len() != SHARED_SECRET_LEN {
                 Err(CryptoError)
             } else {
-                let sent_sk = SigningKey::new(&digest::SHA512_256, &sent_salt);
-                let recv_sk = SigningKey::new(&digest::SHA512_256, &recv_salt);
-
-                let mut send_key = [0x00u8; SYMMETRIC_KEY_LEN];
-                let mut recv_key = [0x00u8; SYMMETRIC_KEY_LEN];
-                extract_and_expand(&sent_sk, shared_key, &[], &mut send_key);
-                extract_and_expand(&recv_sk, shared_key, &[], &mut recv_key);
-
-                Ok((SymmetricKey::from(&send_key), SymmetricKey::from(&recv_key)))
+                Ok(derive_symmetric_keys(shared_key, &sent_salt, &recv_salt))
             }
         };
 
@@ -84,6 +97,72 @@ impl DhPrivateKey {
     }
 }
 
+/// A DH private key that can be used many times, unlike `DhPrivateKey`.
+/// Others can encrypt messages to the matching public key (See `encrypt_to_dh_public_key()`).
+pub struct StaticDhPrivateKey(StaticSecret);
+
+impl StaticDhPrivateKey {
+    /// Create a private key from uniformly random secret bytes.
+    pub fn from_secret(secret: [u8; DH_SECRET_LEN]) -> StaticDhPrivateKey {
+        StaticDhPrivateKey(StaticSecret::from(secret))
+    }
+
+    /// Compute the public key matching our private key.
+    pub fn compute_public_key(&self) -> DhPublicKey {
+        DhPublicKey(*X25519PublicKey::from(&self.0).as_bytes())
+    }
+
+    /// Decrypt a message created by `encrypt_to_dh_public_key()`.
+    pub fn decrypt(&self, cipher_msg: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if cipher_msg.len() < DH_PUBLIC_KEY_LEN + SALT_LEN {
+            return Err(CryptoError);
+        }
+        let (dh_public_key_bytes, rest) = cipher_msg.split_at(DH_PUBLIC_KEY_LEN);
+        let (salt_bytes, enc_msg) = rest.split_at(SALT_LEN);
+
+        let mut remote_public_key = [0_u8; DH_PUBLIC_KEY_LEN];
+        remote_public_key.copy_from_slice(dh_public_key_bytes);
+        let mut salt = Salt([0_u8; SALT_LEN]);
+        salt.0.copy_from_slice(salt_bytes);
+
+        let shared_secret = self
+            .0
+            .diffie_hellman(&X25519PublicKey::from(remote_public_key));
+        // A remote public key of a small order results in an all zero shared secret:
+        if shared_secret.as_bytes().iter().all(|&byte| byte == 0) {
+            return Err(CryptoError);
+        }
+
+        let (symmetric_key, _) = derive_symmetric_keys(shared_secret.as_bytes(), &salt, &salt);
+        Decryptor::new(&symmetric_key)?.decrypt(enc_msg)
+    }
+}
+
+/// Encrypt a message that only the owner of the private key of `remote_dh_public_key` can
+/// decrypt. The message is encrypted using a key derived from an ephemeral DH private key.
+/// The ephemeral DH public key and the salt are sent along the encrypted message.
+pub fn encrypt_to_dh_public_key<R: CryptoRandom>(
+    plain_msg: &[u8],
+    remote_dh_public_key: &DhPublicKey,
+    rng: &R,
+) -> Result<Vec<u8>, CryptoError> {
+    let dh_private_key = DhPrivateKey::new(rng)?;
+    let dh_public_key = dh_private_key.compute_public_key()?;
+    let salt = Salt::new(rng)?;
+
+    let (symmetric_key, _) = dh_private_key.derive_symmetric_key(
+        remote_dh_public_key.clone(),
+        salt.clone(),
+        salt.clone(),
+    )?;
+
+    let mut cipher_msg = Vec::new();
+    cipher_msg.extend_from_slice(&dh_public_key);
+    cipher_msg.extend_from_slice(&salt);
+    cipher_msg.extend(Encryptor::new(&symmetric_key)?.encrypt(plain_msg)?);
+    Ok(cipher_msg)
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::test_utils::DummyRandom;
@@ -124,4 +203,33 @@ mod tests {
         assert_eq!(send_key_a, recv_key_b);
         assert_eq!(send_key_b, recv_key_a)
     }
+
+    #[test]
+    fn test_static_dh_private_key() {
+        let rng = DummyRandom::new(&[1, 2, 3, 4, 5]);
+        let static_private_key = StaticDhPrivateKey::from_secret([7u8; DH_SECRET_LEN]);
+        let other_private_key = StaticDhPrivateKey::from_secret([8u8; DH_SECRET_LEN]);
+
+        // The same secret results in the same public key:
+        let dh_public_key = static_private_key.compute_public_key();
+        assert_eq!(
+            dh_public_key,
+            StaticDhPrivateKey::from_secret([7u8; DH_SECRET_LEN]).compute_public_key()
+        );
+
+        let plain_msg = b"This is a message";
+        let cipher_msg = encrypt_to_dh_public_key(&plain_msg[..], &dh_public_key, &rng).unwrap();
+
+        assert_eq!(
+            static_private_key.decrypt(&cipher_msg).unwrap(),
+            plain_msg.to_vec()
+        );
+        assert!(other_private_key.decrypt(&cipher_msg).is_err());
+
+        // A modified message can not be decrypted:
+        let mut cipher_msg = cipher_msg;
+        let last_index = cipher_msg.len() - 1;
+        cipher_msg[last_index] ^= 1;
+        assert!(static_private_key.decrypt(&cipher_msg).is_err());
+    }
 }
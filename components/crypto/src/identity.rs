@@ -4,12 +4,18 @@ use std::cmp::Ordering;
 
 use super::CryptoError;
 use crate::crypto_rand::CryptoRandom;
+use crate::dh::{DhPublicKey, StaticDhPrivateKey, DH_SECRET_LEN};
 use crate::hash::sha_512_256;
 use common::big_array::BigArray;
 
 pub const PUBLIC_KEY_LEN: usize = 32;
 pub const SIGNATURE_LEN: usize = 64;
 
+/// The DH private key of an identity is derived from the identity's signature over the hash of
+/// this prefix. No other signed buffer consists of a single hash, so this signature is never
+/// handed out.
+const DH_KEY_PREFIX: &[u8] = b"DH_KEY";
+
 define_fixed_bytes!(PublicKey, PUBLIC_KEY_LEN);
 
 #[derive(Clone, Serialize, Deserialize, From)]
@@ -41,17 +47,37 @@ pub trait Identity {
     fn sign(&self, message: &[u8]) -> Signature;
     /// Get our public identity
     fn get_public_key(&self) -> PublicKey;
+    /// Get our DH public key. Messages encrypted to this key
+    /// (See `crypto::dh::encrypt_to_dh_public_key()`) can be decrypted using `decrypt()`.
+    fn get_dh_public_key(&self) -> DhPublicKey;
+    /// Decrypt a message that was encrypted to our DH public key.
+    fn decrypt(&self, cipher_msg: &[u8]) -> Result<Vec<u8>, CryptoError>;
 }
 
 pub struct SoftwareEd25519Identity {
     key_pair: signature::Ed25519KeyPair,
+    dh_private_key: StaticDhPrivateKey,
+}
+
+/// Derive a DH private key from a signing key pair.
+/// ring does not expose the private key of a key pair, but Ed25519 signatures are deterministic:
+/// Only the owner of the key pair can compute the signature, and it is the same every time.
+fn derive_dh_private_key(key_pair: &signature::Ed25519KeyPair) -> StaticDhPrivateKey {
+    let signature = key_pair.sign(&sha_512_256(DH_KEY_PREFIX));
+    let mut secret = [0u8; DH_SECRET_LEN];
+    secret.copy_from_slice(&sha_512_256(signature.as_ref()));
+    StaticDhPrivateKey::from_secret(secret)
 }
 
 impl SoftwareEd25519Identity {
     pub fn from_pkcs8(pkcs8_bytes: &[u8]) -> Result<Self, CryptoError> {
         let key_pair = signature::Ed25519KeyPair::from_pkcs8(untrusted::Input::from(pkcs8_bytes))?;
+        let dh_private_key = derive_dh_private_key(&key_pair);
 
-        Ok(SoftwareEd25519Identity { key_pair })
+        Ok(SoftwareEd25519Identity {
+            key_pair,
+            dh_private_key,
+        })
     }
 }
 
@@ -80,6 +106,14 @@ impl Identity for SoftwareEd25519Identity {
         public_key_array.clone_from_slice(public_key_ref);
         PublicKey(public_key_array)
     }
+
+    fn get_dh_public_key(&self) -> DhPublicKey {
+        self.dh_private_key.compute_public_key()
+    }
+
+    fn decrypt(&self, cipher_msg: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.dh_private_key.decrypt(cipher_msg)
+    }
 }
 
 // ==================== Convenience for Signature ====================
@@ -170,6 +204,8 @@ impl ::std::fmt::Debug for Signature {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dh::encrypt_to_dh_public_key;
+    use crate::test_utils::DummyRandom;
     use ring::test::rand::FixedByteRandom;
 
     #[test]
@@ -214,4 +250,26 @@ mod tests {
 
         assert!(!verify_signature(message, &public_key2, &signature1));
     }
+
+    #[test]
+    fn test_decrypt() {
+        let secure_rand = FixedByteRandom { byte: 0x2 };
+        let pkcs8 = signature::Ed25519KeyPair::generate_pkcs8(&secure_rand).unwrap();
+        let id1 = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+        // The DH key is derived again when the identity is loaded:
+        let id1_again = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+        assert_eq!(id1.get_dh_public_key(), id1_again.get_dh_public_key());
+
+        let secure_rand = FixedByteRandom { byte: 0x3 };
+        let pkcs8 = signature::Ed25519KeyPair::generate_pkcs8(&secure_rand).unwrap();
+        let id2 = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+        assert_ne!(id1.get_dh_public_key(), id2.get_dh_public_key());
+
+        let rng = DummyRandom::new(&[1, 2, 3, 4, 5]);
+        let message = b"This is a message";
+        let cipher_msg = encrypt_to_dh_public_key(message, &id1.get_dh_public_key(), &rng).unwrap();
+
+        assert_eq!(id1_again.decrypt(&cipher_msg).unwrap(), message.to_vec());
+        assert!(id2.decrypt(&cipher_msg).is_err());
+    }
 }
@@ -2,6 +2,7 @@ use std::iter;
 
 use ring;
 use ring::aead::{open_in_place, seal_in_place, OpeningKey, SealingKey, CHACHA20_POLY1305};
+use ring::{digest, pbkdf2};
 
 use super::{increase_nonce, CryptoError};
 
@@ -103,6 +104,9 @@ impl Decryptor {
 
     /// Decrypt and authenticate a message.
     pub fn decrypt(&mut self, cipher_msg: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if cipher_msg.len() < ENC_NONCE_LEN {
+            return Err(CryptoError);
+        }
         let enc_nonce = &cipher_msg[..ENC_NONCE_LEN];
         if enc_nonce != self.nonce_counter.as_ref() {
             // Nonce doesn't match!
@@ -122,6 +126,13 @@ impl Decryptor {
     }
 }
 
+/// Derive a symmetric key from a secret (For example, a passphrase), using PBKDF2-HMAC-SHA512.
+pub fn derive_symmetric_key(secret: &[u8], salt: &[u8], iterations: u32) -> SymmetricKey {
+    let mut inner = [0x00; SYMMETRIC_KEY_LEN];
+    pbkdf2::derive(&digest::SHA512, iterations, salt, secret, &mut inner);
+    SymmetricKey(inner)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,4 +171,28 @@ mod tests {
 
         assert_eq!(plain_msg, &decrypted_msg[..]);
     }
+
+    #[test]
+    fn test_derive_symmetric_key() {
+        let key1 = derive_symmetric_key(b"passphrase", b"salt", 0x10);
+        let key2 = derive_symmetric_key(b"passphrase", b"salt", 0x10);
+        assert_eq!(key1, key2);
+
+        assert_ne!(
+            key1,
+            derive_symmetric_key(b"passphrase", b"other salt", 0x10)
+        );
+        assert_ne!(
+            key1,
+            derive_symmetric_key(b"other passphrase", b"salt", 0x10)
+        );
+        assert_ne!(key1, derive_symmetric_key(b"passphrase", b"salt", 0x11));
+    }
+
+    #[test]
+    fn test_decrypt_short_message() {
+        let symmetric_key = SymmetricKey::from(&[1; SYMMETRIC_KEY_LEN]);
+        let mut decryptor = Decryptor::new(&symmetric_key).unwrap();
+        assert!(decryptor.decrypt(&[0u8; 4]).is_err());
+    }
 }
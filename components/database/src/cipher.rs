@@ -0,0 +1,232 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crypto::crypto_rand::{system_random, CryptoRandom, OffstSystemRandom};
+use crypto::hash::{sha_512_256, HASH_RESULT_LEN};
+use crypto::sym_encrypt::{derive_symmetric_key, Decryptor, Encryptor, SymmetricKey};
+use crypto::CryptoError;
+
+/// Magic value at the beginning of an encrypted database (Or database cipher file)
+pub const ENC_MAGIC: [u8; 8] = *b"OFFSTENC";
+/// Length of the random salt used for deriving the database key from a secret
+pub const KDF_SALT_LEN: usize = 16;
+/// Default amount of PBKDF2 iterations used for deriving the database key
+pub const DEFAULT_KDF_ITERATIONS: u32 = 100_000;
+/// Length of the plaintext cipher header: magic, kdf salt and kdf iterations (u32)
+pub const CIPHER_HEADER_LEN: usize = 8 + KDF_SALT_LEN + 4;
+
+/// Length of the random salt prepended to every sealed blob
+const BLOB_SALT_LEN: usize = HASH_RESULT_LEN;
+
+#[derive(Debug)]
+pub enum CipherError {
+    /// The database is encrypted, but no secret was provided
+    MissingSecret,
+    /// A secret was provided, but the database is not encrypted
+    NotEncrypted,
+    EncryptError,
+    /// Wrong secret, or corrupted data
+    DecryptError,
+    /// The database begins with the encryption magic, but the cipher header that follows is
+    /// truncated or invalid
+    CorruptHeader,
+}
+
+/// Parameters used to derive the database key from a secret.
+/// Those parameters are not secret, and are stored in plaintext next to the encrypted data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+    pub salt: [u8; KDF_SALT_LEN],
+    pub iterations: u32,
+}
+
+impl KdfParams {
+    /// Create new key derivation parameters with a random salt
+    pub fn new<R: CryptoRandom>(rng: &R) -> Self {
+        let mut salt = [0u8; KDF_SALT_LEN];
+        fill_random(rng, &mut salt).unwrap();
+        KdfParams {
+            salt,
+            iterations: DEFAULT_KDF_ITERATIONS,
+        }
+    }
+}
+
+/// Encode the plaintext header of an encrypted database
+pub fn encode_cipher_header(kdf_params: &KdfParams) -> Vec<u8> {
+    let mut buff = Vec::with_capacity(CIPHER_HEADER_LEN);
+    buff.extend_from_slice(&ENC_MAGIC);
+    buff.extend_from_slice(&kdf_params.salt);
+    buff.write_u32::<LittleEndian>(kdf_params.iterations)
+        .unwrap();
+    buff
+}
+
+/// Split an encrypted database into its key derivation parameters and the encrypted data.
+/// Returns None if the data does not begin with the encryption magic (The database is not
+/// encrypted).
+pub fn decode_cipher_header(buff: &[u8]) -> Result<Option<(KdfParams, &[u8])>, CipherError> {
+    if buff.len() < ENC_MAGIC.len() || buff[..ENC_MAGIC.len()] != ENC_MAGIC {
+        return Ok(None);
+    }
+    if buff.len() < CIPHER_HEADER_LEN {
+        return Err(CipherError::CorruptHeader);
+    }
+    let mut salt = [0u8; KDF_SALT_LEN];
+    salt.copy_from_slice(&buff[ENC_MAGIC.len()..ENC_MAGIC.len() + KDF_SALT_LEN]);
+    let iterations = (&buff[ENC_MAGIC.len() + KDF_SALT_LEN..CIPHER_HEADER_LEN])
+        .read_u32::<LittleEndian>()
+        .map_err(|_| CipherError::CorruptHeader)?;
+    if iterations == 0 {
+        return Err(CipherError::CorruptHeader);
+    }
+    Ok(Some((
+        KdfParams { salt, iterations },
+        &buff[CIPHER_HEADER_LEN..],
+    )))
+}
+
+/// Fill a buffer with random bytes
+fn fill_random<R: CryptoRandom>(rng: &R, buff: &mut [u8]) -> Result<(), CryptoError> {
+    Ok(rng.fill(buff)?)
+}
+
+/// Encrypts and decrypts database blobs (snapshots, log records) at rest.
+///
+/// `Encryptor` always starts counting nonces from zero, so a key must never be used to encrypt
+/// more than one blob. Therefore every blob is encrypted with its own key, derived from the
+/// database key and a random salt that is stored in front of the blob.
+pub struct DbCipher {
+    key: SymmetricKey,
+    kdf_params: KdfParams,
+    rng: OffstSystemRandom,
+}
+
+impl DbCipher {
+    /// Derive a database key from a secret (A passphrase or the contents of a key file)
+    pub fn new(secret: &[u8], kdf_params: KdfParams) -> Self {
+        DbCipher {
+            key: derive_symmetric_key(secret, &kdf_params.salt, kdf_params.iterations),
+            kdf_params,
+            rng: system_random(),
+        }
+    }
+
+    /// Create a cipher for a new database, using random key derivation parameters
+    pub fn new_random(secret: &[u8]) -> Self {
+        let kdf_params = KdfParams::new(&system_random());
+        DbCipher::new(secret, kdf_params)
+    }
+
+    pub fn kdf_params(&self) -> &KdfParams {
+        &self.kdf_params
+    }
+
+    fn blob_key(&self, blob_salt: &[u8]) -> SymmetricKey {
+        let mut data = self.key.to_vec();
+        data.extend_from_slice(blob_salt);
+        SymmetricKey::from(sha_512_256(&data).as_array_ref())
+    }
+
+    /// Encrypt a blob
+    pub fn seal(&self, plain: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let mut blob_salt = [0u8; BLOB_SALT_LEN];
+        fill_random(&self.rng, &mut blob_salt)?;
+
+        let mut encryptor = Encryptor::new(&self.blob_key(&blob_salt))?;
+        let mut sealed = blob_salt.to_vec();
+        sealed.extend(encryptor.encrypt(plain)?);
+        Ok(sealed)
+    }
+
+    /// Decrypt and authenticate a blob
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if sealed.len() < BLOB_SALT_LEN {
+            return Err(CryptoError);
+        }
+        let (blob_salt, cipher_msg) = sealed.split_at(BLOB_SALT_LEN);
+        let mut decryptor = Decryptor::new(&self.blob_key(blob_salt))?;
+        decryptor.decrypt(cipher_msg)
+    }
+}
+
+/// Obtain a cipher for an existing database, according to the plaintext cipher header stored in
+/// the beginning of `buff`. Returns the rest of the buffer, after the cipher header.
+pub fn load_cipher<'a>(
+    buff: &'a [u8],
+    opt_secret: Option<&[u8]>,
+) -> Result<(Option<DbCipher>, &'a [u8]), CipherError> {
+    match (decode_cipher_header(buff)?, opt_secret) {
+        (Some((kdf_params, rest)), Some(secret)) => {
+            Ok((Some(DbCipher::new(secret, kdf_params)), rest))
+        }
+        (Some(_), None) => Err(CipherError::MissingSecret),
+        (None, Some(_)) => Err(CipherError::NotEncrypted),
+        (None, None) => Ok((None, buff)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_kdf_params() -> KdfParams {
+        KdfParams {
+            salt: [3u8; KDF_SALT_LEN],
+            // Low iteration count, to make tests fast:
+            iterations: 0x10,
+        }
+    }
+
+    #[test]
+    fn test_db_cipher_seal_open() {
+        let cipher = DbCipher::new(b"secret", test_kdf_params());
+        let sealed1 = cipher.seal(b"Hello world!").unwrap();
+        let sealed2 = cipher.seal(b"Hello world!").unwrap();
+        // Every blob is encrypted using a different key:
+        assert_ne!(sealed1, sealed2);
+
+        assert_eq!(cipher.open(&sealed1).unwrap(), b"Hello world!");
+        assert_eq!(cipher.open(&sealed2).unwrap(), b"Hello world!");
+
+        // Wrong secret:
+        let other_cipher = DbCipher::new(b"other secret", test_kdf_params());
+        assert!(other_cipher.open(&sealed1).is_err());
+
+        // Tampering is detected:
+        let mut tampered = sealed1.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(cipher.open(&tampered).is_err());
+
+        // Truncated blobs are rejected:
+        assert!(cipher.open(&sealed1[..4]).is_err());
+    }
+
+    #[test]
+    fn test_cipher_header() {
+        let kdf_params = test_kdf_params();
+        let mut buff = encode_cipher_header(&kdf_params);
+        buff.extend_from_slice(&[1, 2, 3]);
+
+        let (decoded_params, rest) = decode_cipher_header(&buff).unwrap().unwrap();
+        assert_eq!(decoded_params, kdf_params);
+        assert_eq!(rest, &[1, 2, 3]);
+
+        assert!(decode_cipher_header(&[1, 2, 3]).unwrap().is_none());
+
+        // Truncated header:
+        match decode_cipher_header(&buff[..CIPHER_HEADER_LEN - 1]) {
+            Err(CipherError::CorruptHeader) => {}
+            _ => unreachable!(),
+        };
+
+        // Zero iterations:
+        let zero_kdf_params = KdfParams {
+            salt: [3u8; KDF_SALT_LEN],
+            iterations: 0,
+        };
+        match decode_cipher_header(&encode_cipher_header(&zero_kdf_params)) {
+            Err(CipherError::CorruptHeader) => {}
+            _ => unreachable!(),
+        };
+    }
+}
@@ -1,6 +1,6 @@
 use std::io;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use std::fmt::Debug;
 use std::fs::File;
@@ -13,6 +13,8 @@ use atomicwrites;
 use bincode;
 
 use crate::atomic_db::AtomicDb;
+use crate::cipher::{encode_cipher_header, load_cipher, CipherError, DbCipher};
+use crate::migrate::{add_header, split_header, MigrateError, VersionedState};
 use common::mutable_state::MutableState;
 
 #[derive(Debug)]
@@ -23,6 +25,8 @@ pub enum FileDbError<ME> {
     DeserializeError(bincode::Error),
     SerializeError(bincode::Error),
     MutateError(ME),
+    MigrateError(MigrateError),
+    CipherError(CipherError),
     FileAlreadyExists,
 }
 
@@ -31,58 +35,171 @@ pub struct FileDb<S> {
     path_buf: PathBuf,
     /// Current state represented by the database:
     state: S,
+    /// Used to encrypt the database file (If the database is encrypted)
+    opt_cipher: Option<DbCipher>,
+}
+
+/// Read the whole database file
+fn read_db_file<ME>(path: &Path) -> Result<Vec<u8>, FileDbError<ME>> {
+    let mut f = File::open(path).map_err(FileDbError::OpenError)?;
+    let mut file_buff = Vec::new();
+    f.read_to_end(&mut file_buff)
+        .map_err(FileDbError::ReadError)?;
+    Ok(file_buff)
+}
+
+/// Read a database file, and decrypt it if necessary.
+/// Returns the schema version, the serialized state and the cipher used for decryption.
+fn read_state_buff<ME>(
+    path: &Path,
+    opt_secret: Option<&[u8]>,
+) -> Result<(u32, Vec<u8>, Option<DbCipher>), FileDbError<ME>> {
+    let file_buff = read_db_file(path)?;
+    let (opt_cipher, rest) =
+        load_cipher(&file_buff, opt_secret).map_err(FileDbError::CipherError)?;
+
+    let plain_buff = match &opt_cipher {
+        Some(cipher) => cipher
+            .open(rest)
+            .map_err(|_| FileDbError::CipherError(CipherError::DecryptError))?,
+        None => rest.to_vec(),
+    };
+
+    let (version, serialized_buff) = split_header(&plain_buff);
+    Ok((version, serialized_buff.to_vec(), opt_cipher))
+}
+
+/// Serialize a state (Prefixed by a header with the current schema version) and save it to file,
+/// atomically. If a cipher is provided, the file is encrypted.
+fn write_db_file<S, ME>(
+    path: &Path,
+    state: &S,
+    opt_cipher: Option<&DbCipher>,
+) -> Result<(), FileDbError<ME>>
+where
+    S: Serialize + VersionedState,
+{
+    let serialized_buff = bincode::serialize(state).map_err(FileDbError::SerializeError)?;
+    let plain_buff = add_header(S::migrations().current_version(), &serialized_buff);
+
+    let file_buff = match opt_cipher {
+        Some(cipher) => {
+            let mut file_buff = encode_cipher_header(cipher.kdf_params());
+            let sealed_buff = cipher
+                .seal(&plain_buff)
+                .map_err(|_| FileDbError::CipherError(CipherError::EncryptError))?;
+            file_buff.extend_from_slice(&sealed_buff);
+            file_buff
+        }
+        None => plain_buff,
+    };
+
+    let af = atomicwrites::AtomicFile::new(path, atomicwrites::AllowOverwrite);
+    af.write(|fw| fw.write_all(&file_buff))
+        .map_err(FileDbError::WriteError)
 }
 
 impl<S> FileDb<S>
 where
-    S: Clone + Serialize + DeserializeOwned + MutableState,
+    S: Clone + Serialize + DeserializeOwned + MutableState + VersionedState,
     S::Mutation: Clone + Serialize + DeserializeOwned,
     S::MutateError: Debug,
 {
-    /// Create a new database file from an initial state
-    /// Aborts if destination file already exists
-    pub fn create(
+    fn create_inner(
         path_buf: PathBuf,
         initial_state: S,
+        opt_cipher: Option<DbCipher>,
     ) -> Result<Self, FileDbError<S::MutateError>> {
         if path_buf.exists() {
             return Err(FileDbError::FileAlreadyExists);
         }
 
         // There is no file, we create a new file:
-        // Serialize the state:
-        let serialized_buff =
-            bincode::serialize(&initial_state).map_err(FileDbError::SerializeError)?;
-        // Save the new state to file, atomically:
-        let af = atomicwrites::AtomicFile::new(&path_buf, atomicwrites::AllowOverwrite);
-        af.write(|fw| fw.write_all(&serialized_buff))
-            .map_err(FileDbError::WriteError)?;
+        write_db_file(&path_buf, &initial_state, opt_cipher.as_ref())?;
+
+        Ok(FileDb {
+            path_buf,
+            state: initial_state,
+            opt_cipher,
+        })
+    }
+
+    /// Create a new database file from an initial state
+    /// Aborts if destination file already exists
+    pub fn create(
+        path_buf: PathBuf,
+        initial_state: S,
+    ) -> Result<Self, FileDbError<S::MutateError>> {
+        FileDb::create_inner(path_buf, initial_state, None)
+    }
+
+    /// Create a new encrypted database file from an initial state.
+    /// The encryption key is derived from `secret` (A passphrase or the contents of a key file).
+    /// Aborts if destination file already exists
+    pub fn create_encrypted(
+        path_buf: PathBuf,
+        initial_state: S,
+        secret: &[u8],
+    ) -> Result<Self, FileDbError<S::MutateError>> {
+        FileDb::create_inner(path_buf, initial_state, Some(DbCipher::new_random(secret)))
+    }
+
+    fn load_inner(
+        path_buf: PathBuf,
+        opt_secret: Option<&[u8]>,
+    ) -> Result<Self, FileDbError<S::MutateError>> {
+        let (version, serialized_buff, opt_cipher) = read_state_buff(&path_buf, opt_secret)?;
+
+        let serialized_buff = S::migrations()
+            .migrate(version, serialized_buff)
+            .map_err(FileDbError::MigrateError)?;
 
         let state: S =
             bincode::deserialize(&serialized_buff).map_err(FileDbError::DeserializeError)?;
 
-        Ok(FileDb { path_buf, state })
+        Ok(FileDb {
+            path_buf,
+            state,
+            opt_cipher,
+        })
     }
 
     /// Load an existing database from file
     /// Returns an error if database file does not exist
+    ///
+    /// Databases written with an older schema version are migrated in memory. Use `save()` to
+    /// write the migrated state back to the file.
     pub fn load(path_buf: PathBuf) -> Result<Self, FileDbError<S::MutateError>> {
-        let mut f = File::open(&path_buf).map_err(FileDbError::OpenError)?;
-        // read the whole file
-        let mut serialized_buff = Vec::new();
-        f.read_to_end(&mut serialized_buff)
-            .map_err(FileDbError::ReadError)?;
+        FileDb::load_inner(path_buf, None)
+    }
 
-        let state: S =
-            bincode::deserialize(&serialized_buff).map_err(FileDbError::DeserializeError)?;
+    /// Load an existing encrypted database from file
+    pub fn load_encrypted(
+        path_buf: PathBuf,
+        secret: &[u8],
+    ) -> Result<Self, FileDbError<S::MutateError>> {
+        FileDb::load_inner(path_buf, Some(secret))
+    }
 
-        Ok(FileDb { path_buf, state })
+    /// Read the schema version of an existing database file.
+    /// `opt_secret` must be provided if the database is encrypted.
+    pub fn file_version(
+        path: &Path,
+        opt_secret: Option<&[u8]>,
+    ) -> Result<u32, FileDbError<S::MutateError>> {
+        let (version, _, _) = read_state_buff(path, opt_secret)?;
+        Ok(version)
+    }
+
+    /// Write the current state to the database file, using the current schema version
+    pub fn save(&self) -> Result<(), FileDbError<S::MutateError>> {
+        write_db_file(&self.path_buf, &self.state, self.opt_cipher.as_ref())
     }
 }
 
 impl<S> AtomicDb for FileDb<S>
 where
-    S: Debug + Clone + Serialize + DeserializeOwned + MutableState,
+    S: Debug + Clone + Serialize + DeserializeOwned + MutableState + VersionedState,
     S::Mutation: Clone + Serialize + DeserializeOwned,
     S::MutateError: Debug,
 {
@@ -104,22 +221,16 @@ where
                 .map_err(FileDbError::MutateError)?;
         }
 
-        // Serialize the state:
-        let serialized_buff =
-            bincode::serialize(&self.state).map_err(FileDbError::SerializeError)?;
-
         // Save the new state to file, atomically:
-        let af = atomicwrites::AtomicFile::new(&self.path_buf, atomicwrites::AllowOverwrite);
-        af.write(|fw| fw.write_all(&serialized_buff))
-            .map_err(FileDbError::WriteError)?;
-
-        Ok(())
+        self.save()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::migrate::{migrate_unchanged, Migrations};
+    use std::fs;
     use tempfile::tempdir;
 
     /// A dummy state (used for testing)
@@ -161,6 +272,23 @@ mod tests {
         }
     }
 
+    /// Schema version 0 of DummyState only had a u8 field.
+    /// Version 1 changed it to a u32.
+    fn migrate_dummy_v0_to_v1(data: &[u8]) -> Result<Vec<u8>, ()> {
+        let x: u8 = bincode::deserialize(data).map_err(|_| ())?;
+        bincode::serialize(&DummyState::new(u32::from(x))).map_err(|_| ())
+    }
+
+    impl VersionedState for DummyState {
+        fn migrations() -> Migrations {
+            Migrations::new().add(migrate_dummy_v0_to_v1)
+        }
+
+        fn mutation_migrations() -> Migrations {
+            Migrations::new().add(migrate_unchanged)
+        }
+    }
+
     #[test]
     fn test_file_db_basic() {
         // Create a temporary directory:
@@ -203,4 +331,92 @@ mod tests {
         // Remove temporary directory:
         dir.close().unwrap();
     }
+
+    #[test]
+    fn test_file_db_migrate() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("database_file");
+
+        // A legacy database file (No header, old schema):
+        let legacy_buff = bincode::serialize(&7u8).unwrap();
+        fs::write(&file_path, &legacy_buff).unwrap();
+        assert_eq!(
+            FileDb::<DummyState>::file_version(&file_path, None).unwrap(),
+            0
+        );
+
+        let file_db = FileDb::<DummyState>::load(file_path.clone()).unwrap();
+        assert_eq!(file_db.get_state().x, 7);
+
+        // Loading does not change the file:
+        assert_eq!(
+            FileDb::<DummyState>::file_version(&file_path, None).unwrap(),
+            0
+        );
+
+        file_db.save().unwrap();
+        drop(file_db);
+        assert_eq!(
+            FileDb::<DummyState>::file_version(&file_path, None).unwrap(),
+            1
+        );
+
+        let file_db = FileDb::<DummyState>::load(file_path.clone()).unwrap();
+        assert_eq!(file_db.get_state().x, 7);
+        drop(file_db);
+
+        // A database from a future schema version can not be loaded:
+        let future_buff = add_header(2, &bincode::serialize(&DummyState::new(7)).unwrap());
+        fs::write(&file_path, &future_buff).unwrap();
+        match FileDb::<DummyState>::load(file_path.clone()) {
+            Err(FileDbError::MigrateError(MigrateError::UnsupportedVersion(2))) => {}
+            _ => unreachable!(),
+        };
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_file_db_encrypted() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("database_file");
+
+        let mut file_db = FileDb::<DummyState>::create_encrypted(
+            file_path.clone(),
+            DummyState::new(0),
+            b"secret",
+        )
+        .unwrap();
+        file_db
+            .mutate_db(&[DummyMutation::Inc, DummyMutation::Inc])
+            .unwrap();
+        drop(file_db);
+
+        // The state is not stored in plaintext:
+        let file_buff = fs::read(&file_path).unwrap();
+        let plain_buff = add_header(1, &bincode::serialize(&DummyState::new(2)).unwrap());
+        assert!(!file_buff
+            .windows(plain_buff.len())
+            .any(|window| window == &plain_buff[..]));
+
+        // Secret is required:
+        match FileDb::<DummyState>::load(file_path.clone()) {
+            Err(FileDbError::CipherError(CipherError::MissingSecret)) => {}
+            _ => unreachable!(),
+        };
+        // Wrong secret:
+        match FileDb::<DummyState>::load_encrypted(file_path.clone(), b"wrong secret") {
+            Err(FileDbError::CipherError(CipherError::DecryptError)) => {}
+            _ => unreachable!(),
+        };
+
+        let file_db = FileDb::<DummyState>::load_encrypted(file_path.clone(), b"secret").unwrap();
+        assert_eq!(file_db.get_state().x, 2);
+        assert_eq!(
+            FileDb::<DummyState>::file_version(&file_path, Some(&b"secret"[..])).unwrap(),
+            1
+        );
+
+        dir.close().unwrap();
+    }
 }
@@ -17,8 +17,11 @@
 extern crate serde_derive;
 
 mod atomic_db;
+pub mod cipher;
 mod database;
 pub mod file_db;
+pub mod log_db;
+pub mod migrate;
 
 pub use self::atomic_db::AtomicDb;
 pub use self::database::{database_loop, DatabaseClient, DatabaseClientError, DatabaseRequest};
@@ -0,0 +1,829 @@
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use atomicwrites;
+use bincode;
+
+use crypto::hash::{sha_512_256, HashResult, HASH_RESULT_LEN};
+
+use crate::atomic_db::AtomicDb;
+use crate::cipher::{encode_cipher_header, load_cipher, CipherError, DbCipher};
+use crate::migrate::{add_header, split_header, MigrateError, VersionedState};
+use common::int_convert::{u32_to_usize, usize_to_u32, usize_to_u64};
+use common::mutable_state::MutableState;
+
+/// Name of the snapshot file inside the database directory
+const SNAPSHOT_FILE_NAME: &str = "snapshot";
+/// Name of the write-ahead log file inside the database directory
+const LOG_FILE_NAME: &str = "log";
+/// Name of the file holding the key derivation parameters (Only exists for encrypted databases)
+const CIPHER_FILE_NAME: &str = "cipher";
+
+/// Size of the checksum over the payload length of a log record
+const LENGTH_CHECKSUM_LEN: usize = 4;
+/// Size of a log record header: payload length (u32), the length checksum and the payload
+/// checksum.
+const RECORD_HEADER_LEN: usize = 4 + LENGTH_CHECKSUM_LEN + HASH_RESULT_LEN;
+/// Size of a serialized sequence number
+const SEQ_LEN: usize = 8;
+
+/// Default amount of log records we accumulate before compacting the log into a new snapshot.
+pub const DEFAULT_MAX_LOG_RECORDS: usize = 0x100;
+
+#[derive(Debug)]
+pub enum LogDbError<ME> {
+    OpenError(io::Error),
+    ReadError(io::Error),
+    WriteError(io::Error),
+    AtomicWriteError(atomicwrites::Error<io::Error>),
+    CreateDirError(io::Error),
+    DeserializeError(bincode::Error),
+    SerializeError(bincode::Error),
+    MutateError(ME),
+    MigrateError(MigrateError),
+    CipherError(CipherError),
+    CorruptedSnapshot,
+    /// A record in the middle of the log is invalid. This can not be the result of a torn write,
+    /// so we refuse to continue.
+    CorruptedLog,
+    RecordTooLarge,
+    DirAlreadyExists,
+}
+
+/// An AtomicDb that appends every batch of mutations to a checksummed write-ahead log,
+/// instead of rewriting the whole state on every change.
+///
+/// The database is a directory containing two files:
+/// - `snapshot`: The full state, together with the sequence number of the next batch.
+/// - `log`: Batches of mutations applied after the snapshot was taken.
+///
+/// Every log record is of the form: `[length: u32][length checksum][checksum][payload]`, where
+/// the payload is a serialized `(seq, mutations)` pair. When the log accumulates enough records, it is compacted
+/// into a new snapshot.
+///
+/// The snapshot begins with a header containing the schema version. The records in the log
+/// always share the schema version of the snapshot: A database written with an older schema
+/// version is migrated in memory on load, and a new snapshot is written before the next record
+/// is appended.
+///
+/// An encrypted database also contains a `cipher` file with the key derivation parameters. In
+/// that case the snapshot and the payload of every log record are encrypted.
+pub struct LogDb<S> {
+    /// Directory containing the snapshot and the log
+    dir_path: PathBuf,
+    /// Log file, opened for appending
+    log_file: File,
+    /// Current state represented by the database:
+    state: S,
+    /// Sequence number of the next batch of mutations
+    next_seq: u64,
+    /// Amount of records currently in the log
+    num_log_records: usize,
+    /// Amount of log records that triggers compaction
+    max_log_records: usize,
+    /// Schema version of the snapshot and the records in the log
+    log_version: u32,
+    /// Used to encrypt the snapshot and the log records (If the database is encrypted)
+    opt_cipher: Option<DbCipher>,
+}
+
+/// Calculate checksum for a log record payload
+fn calc_checksum(payload: &[u8]) -> HashResult {
+    sha_512_256(payload)
+}
+
+/// Calculate checksum for the payload length of a log record
+fn calc_length_checksum(payload_len: u32) -> Vec<u8> {
+    let mut len_buff = Vec::new();
+    len_buff.write_u32::<LittleEndian>(payload_len).unwrap();
+    sha_512_256(&len_buff).as_ref()[..LENGTH_CHECKSUM_LEN].to_vec()
+}
+
+/// Encrypt a blob, if a cipher is provided
+fn seal_blob<ME>(opt_cipher: Option<&DbCipher>, buff: Vec<u8>) -> Result<Vec<u8>, LogDbError<ME>> {
+    match opt_cipher {
+        Some(cipher) => cipher
+            .seal(&buff)
+            .map_err(|_| LogDbError::CipherError(CipherError::EncryptError)),
+        None => Ok(buff),
+    }
+}
+
+/// Decrypt a blob, if a cipher is provided
+fn open_blob<ME>(opt_cipher: Option<&DbCipher>, buff: &[u8]) -> Result<Vec<u8>, LogDbError<ME>> {
+    match opt_cipher {
+        Some(cipher) => cipher
+            .open(buff)
+            .map_err(|_| LogDbError::CipherError(CipherError::DecryptError)),
+        None => Ok(buff.to_vec()),
+    }
+}
+
+/// Split a serialized `(seq, T)` pair into the sequence number and the serialized `T`
+fn split_seq(buff: &[u8]) -> Option<(u64, &[u8])> {
+    if buff.len() < SEQ_LEN {
+        return None;
+    }
+    let seq = (&buff[..SEQ_LEN]).read_u64::<LittleEndian>().ok()?;
+    Some((seq, &buff[SEQ_LEN..]))
+}
+
+/// Write a snapshot of the state atomically, using the current schema version
+fn write_snapshot<S, ME>(
+    snapshot_path: &Path,
+    next_seq: u64,
+    state: &S,
+    opt_cipher: Option<&DbCipher>,
+) -> Result<(), LogDbError<ME>>
+where
+    S: Serialize + VersionedState,
+{
+    let serialized_buff =
+        bincode::serialize(&(next_seq, state)).map_err(LogDbError::SerializeError)?;
+    let serialized_buff = add_header(S::migrations().current_version(), &serialized_buff);
+    let serialized_buff = seal_blob(opt_cipher, serialized_buff)?;
+    let af = atomicwrites::AtomicFile::new(snapshot_path, atomicwrites::AllowOverwrite);
+    af.write(|fw| fw.write_all(&serialized_buff))
+        .map_err(LogDbError::AtomicWriteError)
+}
+
+/// Read the full contents of a file
+fn read_file<ME>(path: &Path) -> Result<Vec<u8>, LogDbError<ME>> {
+    let mut f = File::open(path).map_err(LogDbError::OpenError)?;
+    let mut buff = Vec::new();
+    f.read_to_end(&mut buff).map_err(LogDbError::ReadError)?;
+    Ok(buff)
+}
+
+/// Read the snapshot of a database directory, and decrypt it if necessary.
+/// Returns the cipher used for decryption, the schema version and the serialized
+/// `(next_seq, state)` pair.
+fn read_snapshot<ME>(
+    dir_path: &Path,
+    opt_secret: Option<&[u8]>,
+) -> Result<(Option<DbCipher>, u32, Vec<u8>), LogDbError<ME>> {
+    let cipher_path = dir_path.join(CIPHER_FILE_NAME);
+    let cipher_buff = if cipher_path.exists() {
+        read_file(&cipher_path)?
+    } else {
+        Vec::new()
+    };
+    let (opt_cipher, _) = load_cipher(&cipher_buff, opt_secret).map_err(LogDbError::CipherError)?;
+
+    let snapshot_buff = read_file(&dir_path.join(SNAPSHOT_FILE_NAME))?;
+    let snapshot_buff = open_blob(opt_cipher.as_ref(), &snapshot_buff)?;
+    let (version, serialized_buff) = split_header(&snapshot_buff);
+    Ok((opt_cipher, version, serialized_buff.to_vec()))
+}
+
+/// Encode a log record for a payload
+fn encode_record<ME>(payload: &[u8]) -> Result<Vec<u8>, LogDbError<ME>> {
+    let payload_len = usize_to_u32(payload.len()).ok_or(LogDbError::RecordTooLarge)?;
+
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+    record
+        .write_u32::<LittleEndian>(payload_len)
+        .map_err(LogDbError::WriteError)?;
+    record.extend_from_slice(&calc_length_checksum(payload_len));
+    record.extend_from_slice(calc_checksum(payload).as_ref());
+    record.extend_from_slice(payload);
+    Ok(record)
+}
+
+/// Open the log file for appending
+fn open_log_file<ME>(log_path: &Path) -> Result<File, LogDbError<ME>> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .map_err(LogDbError::OpenError)
+}
+
+/// Parse the next record from the log buffer.
+/// Returns `Ok(None)` if the remaining bytes are a torn (partially written) final record.
+fn parse_record<'a, ME>(buff: &'a [u8]) -> Result<Option<(&'a [u8], usize)>, LogDbError<ME>> {
+    if buff.len() < RECORD_HEADER_LEN {
+        return Ok(None);
+    }
+    let payload_len = (&buff[..4])
+        .read_u32::<LittleEndian>()
+        .map_err(LogDbError::ReadError)?;
+    // A corrupted length could make a record in the middle of the log look like a torn final
+    // record, discarding all the records after it. Therefore the length has its own checksum:
+    if calc_length_checksum(payload_len)[..] != buff[4..4 + LENGTH_CHECKSUM_LEN] {
+        return Err(LogDbError::CorruptedLog);
+    }
+    let payload_len = u32_to_usize(payload_len).ok_or(LogDbError::RecordTooLarge)?;
+    let record_len = RECORD_HEADER_LEN.saturating_add(payload_len);
+    if buff.len() < record_len {
+        return Ok(None);
+    }
+
+    let checksum = &buff[4 + LENGTH_CHECKSUM_LEN..RECORD_HEADER_LEN];
+    let payload = &buff[RECORD_HEADER_LEN..record_len];
+    if calc_checksum(payload).as_ref() != checksum {
+        // A checksum mismatch is only acceptable for the last record in the log,
+        // where it can be explained by a torn write:
+        if record_len == buff.len() {
+            return Ok(None);
+        }
+        return Err(LogDbError::CorruptedLog);
+    }
+
+    Ok(Some((payload, record_len)))
+}
+
+impl<S> LogDb<S>
+where
+    S: Clone + Serialize + DeserializeOwned + MutableState + VersionedState,
+    S::Mutation: Clone + Serialize + DeserializeOwned,
+    S::MutateError: Debug,
+{
+    fn create_inner(
+        dir_path: PathBuf,
+        initial_state: S,
+        opt_cipher: Option<DbCipher>,
+    ) -> Result<Self, LogDbError<S::MutateError>> {
+        if dir_path.exists() {
+            return Err(LogDbError::DirAlreadyExists);
+        }
+        fs::create_dir_all(&dir_path).map_err(LogDbError::CreateDirError)?;
+
+        if let Some(cipher) = &opt_cipher {
+            let af = atomicwrites::AtomicFile::new(
+                &dir_path.join(CIPHER_FILE_NAME),
+                atomicwrites::DisallowOverwrite,
+            );
+            af.write(|fw| fw.write_all(&encode_cipher_header(cipher.kdf_params())))
+                .map_err(LogDbError::AtomicWriteError)?;
+        }
+
+        write_snapshot(
+            &dir_path.join(SNAPSHOT_FILE_NAME),
+            0,
+            &initial_state,
+            opt_cipher.as_ref(),
+        )?;
+        let log_file = open_log_file(&dir_path.join(LOG_FILE_NAME))?;
+
+        Ok(LogDb {
+            dir_path,
+            log_file,
+            state: initial_state,
+            next_seq: 0,
+            num_log_records: 0,
+            max_log_records: DEFAULT_MAX_LOG_RECORDS,
+            log_version: S::migrations().current_version(),
+            opt_cipher,
+        })
+    }
+
+    /// Create a new database directory from an initial state
+    /// Aborts if destination directory already exists
+    pub fn create(dir_path: PathBuf, initial_state: S) -> Result<Self, LogDbError<S::MutateError>> {
+        LogDb::create_inner(dir_path, initial_state, None)
+    }
+
+    /// Create a new encrypted database directory from an initial state.
+    /// The encryption key is derived from `secret` (A passphrase or the contents of a key file).
+    /// Aborts if destination directory already exists
+    pub fn create_encrypted(
+        dir_path: PathBuf,
+        initial_state: S,
+        secret: &[u8],
+    ) -> Result<Self, LogDbError<S::MutateError>> {
+        LogDb::create_inner(dir_path, initial_state, Some(DbCipher::new_random(secret)))
+    }
+
+    /// Load an existing database from a directory, replaying the log on top of the snapshot.
+    /// A torn final record (For example, due to a crash during append) is discarded.
+    ///
+    /// Databases written with an older schema version are migrated in memory. Use `compact()` to
+    /// write the migrated state back to the directory.
+    pub fn load(dir_path: PathBuf) -> Result<Self, LogDbError<S::MutateError>> {
+        LogDb::load_inner(dir_path, None)
+    }
+
+    /// Load an existing encrypted database from a directory
+    pub fn load_encrypted(
+        dir_path: PathBuf,
+        secret: &[u8],
+    ) -> Result<Self, LogDbError<S::MutateError>> {
+        LogDb::load_inner(dir_path, Some(secret))
+    }
+
+    fn load_inner(
+        dir_path: PathBuf,
+        opt_secret: Option<&[u8]>,
+    ) -> Result<Self, LogDbError<S::MutateError>> {
+        let (opt_cipher, log_version, snapshot_buff) = read_snapshot(&dir_path, opt_secret)?;
+        let (mut next_seq, state_buff) =
+            split_seq(&snapshot_buff).ok_or(LogDbError::CorruptedSnapshot)?;
+        let state_buff = S::migrations()
+            .migrate(log_version, state_buff.to_vec())
+            .map_err(LogDbError::MigrateError)?;
+        let mut state: S =
+            bincode::deserialize(&state_buff).map_err(LogDbError::DeserializeError)?;
+        let mutation_migrations = S::mutation_migrations();
+
+        let log_path = dir_path.join(LOG_FILE_NAME);
+        let log_buff = if log_path.exists() {
+            read_file(&log_path)?
+        } else {
+            Vec::new()
+        };
+
+        let mut offset = 0;
+        let mut num_log_records = 0;
+        while let Some((payload, record_len)) = parse_record(&log_buff[offset..])? {
+            let payload = open_blob(opt_cipher.as_ref(), payload)?;
+            let (seq, mutations_buff) = split_seq(&payload).ok_or(LogDbError::CorruptedLog)?;
+            offset += record_len;
+
+            if seq < next_seq {
+                // This batch is already included in the snapshot. This may happen if we crashed
+                // after writing a snapshot, but before truncating the log.
+                continue;
+            }
+            if seq != next_seq {
+                return Err(LogDbError::CorruptedLog);
+            }
+
+            let mutations_buff = mutation_migrations
+                .migrate(log_version, mutations_buff.to_vec())
+                .map_err(LogDbError::MigrateError)?;
+            let mutations: Vec<S::Mutation> =
+                bincode::deserialize(&mutations_buff).map_err(|_| LogDbError::CorruptedLog)?;
+            for mutation in &mutations {
+                state.mutate(mutation).map_err(LogDbError::MutateError)?;
+            }
+            next_seq = next_seq.checked_add(1).ok_or(LogDbError::CorruptedLog)?;
+            num_log_records += 1;
+        }
+
+        let log_file = open_log_file(&log_path)?;
+        if offset < log_buff.len() {
+            // Remove the torn record, so that new records will be appended right after the last
+            // valid record:
+            log_file
+                .set_len(usize_to_u64(offset).unwrap())
+                .map_err(LogDbError::WriteError)?;
+            log_file.sync_all().map_err(LogDbError::WriteError)?;
+        }
+
+        Ok(LogDb {
+            dir_path,
+            log_file,
+            state,
+            next_seq,
+            num_log_records,
+            max_log_records: DEFAULT_MAX_LOG_RECORDS,
+            log_version,
+            opt_cipher,
+        })
+    }
+
+    /// Read the schema version of an existing database directory.
+    /// `opt_secret` must be provided if the database is encrypted.
+    pub fn dir_version(
+        dir_path: &Path,
+        opt_secret: Option<&[u8]>,
+    ) -> Result<u32, LogDbError<S::MutateError>> {
+        let (_, version, _) = read_snapshot(dir_path, opt_secret)?;
+        Ok(version)
+    }
+
+    /// Set the amount of log records that triggers compaction into a new snapshot.
+    pub fn set_max_log_records(&mut self, max_log_records: usize) {
+        self.max_log_records = max_log_records;
+    }
+
+    /// Write the current state as a new snapshot (Using the current schema version), and clear
+    /// the log.
+    pub fn compact(&mut self) -> Result<(), LogDbError<S::MutateError>> {
+        write_snapshot(
+            &self.dir_path.join(SNAPSHOT_FILE_NAME),
+            self.next_seq,
+            &self.state,
+            self.opt_cipher.as_ref(),
+        )?;
+        // If we crash here, the records in the log will be skipped on the next load,
+        // because their sequence numbers are smaller than the snapshot's.
+        self.log_file.set_len(0).map_err(LogDbError::WriteError)?;
+        self.log_file.sync_all().map_err(LogDbError::WriteError)?;
+        self.num_log_records = 0;
+        self.log_version = S::migrations().current_version();
+        Ok(())
+    }
+
+    /// Append a batch of mutations to the log, and wait until it reaches the disk.
+    fn append_record(
+        &mut self,
+        mutations: &[S::Mutation],
+    ) -> Result<(), LogDbError<S::MutateError>> {
+        let payload =
+            bincode::serialize(&(self.next_seq, mutations)).map_err(LogDbError::SerializeError)?;
+        let payload = seal_blob(self.opt_cipher.as_ref(), payload)?;
+        let record = encode_record(&payload)?;
+
+        let prev_len = self
+            .log_file
+            .metadata()
+            .map_err(LogDbError::WriteError)?
+            .len();
+
+        let write_res = self
+            .log_file
+            .write_all(&record)
+            .and_then(|()| self.log_file.sync_data());
+        if let Err(e) = write_res {
+            // Remove a partially written record. Otherwise the next record will be appended
+            // after garbage, and the log will not be loadable:
+            let _ = self.log_file.set_len(prev_len);
+            return Err(LogDbError::WriteError(e));
+        }
+
+        self.next_seq = self
+            .next_seq
+            .checked_add(1)
+            .ok_or(LogDbError::CorruptedLog)?;
+        self.num_log_records += 1;
+        Ok(())
+    }
+}
+
+impl<S> AtomicDb for LogDb<S>
+where
+    S: Debug + Clone + Serialize + DeserializeOwned + MutableState + VersionedState,
+    S::Mutation: Clone + Serialize + DeserializeOwned,
+    S::MutateError: Debug,
+{
+    type State = S;
+    type Mutation = S::Mutation;
+    type Error = LogDbError<S::MutateError>;
+
+    /// Get current state represented by the database
+    fn get_state(&self) -> &Self::State {
+        &self.state
+    }
+
+    /// Apply a set of mutations atomically the database, and append them to the log.
+    fn mutate_db(&mut self, mutations: &[Self::Mutation]) -> Result<(), Self::Error> {
+        // All the records in the log must have the schema version of the snapshot.
+        // If the database was migrated on load, we write a new snapshot first:
+        if self.log_version != S::migrations().current_version() {
+            self.compact()?;
+        }
+
+        // Apply all mutations to state:
+        for mutation in mutations.iter() {
+            self.state
+                .mutate(mutation)
+                .map_err(LogDbError::MutateError)?;
+        }
+
+        self.append_record(mutations)?;
+
+        if self.num_log_records >= self.max_log_records {
+            self.compact()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrate::Migrations;
+    use tempfile::tempdir;
+
+    /// A dummy state (used for testing)
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    struct DummyState {
+        pub x: u32,
+    }
+
+    impl DummyState {
+        pub fn new(x: u32) -> Self {
+            DummyState { x }
+        }
+    }
+
+    /// A dummy mutation (used for testing)
+    #[derive(Debug, Serialize, Deserialize, Clone)]
+    enum DummyMutation {
+        Inc,
+        Dec,
+    }
+
+    #[derive(Debug)]
+    struct DummyMutateError;
+
+    impl MutableState for DummyState {
+        type Mutation = DummyMutation;
+        type MutateError = DummyMutateError;
+
+        fn mutate(&mut self, mutation: &Self::Mutation) -> Result<(), Self::MutateError> {
+            match mutation {
+                DummyMutation::Inc => {
+                    self.x = self.x.saturating_add(1);
+                }
+                DummyMutation::Dec => {
+                    self.x = self.x.saturating_sub(1);
+                }
+            };
+            Ok(())
+        }
+    }
+
+    /// Schema version 0 of DummyState only had a u8 field.
+    /// Version 1 changed it to a u32.
+    fn migrate_dummy_v0_to_v1(data: &[u8]) -> Result<Vec<u8>, ()> {
+        let x: u8 = bincode::deserialize(data).map_err(|_| ())?;
+        bincode::serialize(&DummyState::new(u32::from(x))).map_err(|_| ())
+    }
+
+    /// In schema version 0 a mutation was a bool: true for Inc, false for Dec.
+    fn migrate_dummy_mutations_v0_to_v1(data: &[u8]) -> Result<Vec<u8>, ()> {
+        let mutations: Vec<bool> = bincode::deserialize(data).map_err(|_| ())?;
+        let mutations: Vec<_> = mutations
+            .into_iter()
+            .map(|inc| {
+                if inc {
+                    DummyMutation::Inc
+                } else {
+                    DummyMutation::Dec
+                }
+            })
+            .collect();
+        bincode::serialize(&mutations).map_err(|_| ())
+    }
+
+    impl VersionedState for DummyState {
+        fn migrations() -> Migrations {
+            Migrations::new().add(migrate_dummy_v0_to_v1)
+        }
+
+        fn mutation_migrations() -> Migrations {
+            Migrations::new().add(migrate_dummy_mutations_v0_to_v1)
+        }
+    }
+
+    #[test]
+    fn test_log_db_basic() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("database");
+
+        // We are not allowed to load a nonexistent database:
+        assert!(LogDb::<DummyState>::load(db_path.clone()).is_err());
+
+        let mut log_db = LogDb::<DummyState>::create(db_path.clone(), DummyState::new(0)).unwrap();
+        log_db
+            .mutate_db(&[DummyMutation::Inc, DummyMutation::Inc, DummyMutation::Dec])
+            .unwrap();
+        assert_eq!(log_db.get_state().x, 1);
+
+        log_db
+            .mutate_db(&[DummyMutation::Inc, DummyMutation::Inc, DummyMutation::Dec])
+            .unwrap();
+        assert_eq!(log_db.get_state().x, 2);
+        drop(log_db);
+
+        // Check persistency:
+        let mut log_db = LogDb::<DummyState>::load(db_path.clone()).unwrap();
+        assert_eq!(log_db.get_state().x, 2);
+
+        // Appending after load should work:
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        drop(log_db);
+        let log_db = LogDb::<DummyState>::load(db_path.clone()).unwrap();
+        assert_eq!(log_db.get_state().x, 3);
+
+        // We should not be able to accidentally erase our state:
+        assert!(LogDb::<DummyState>::create(db_path.clone(), DummyState::new(0)).is_err());
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_log_db_compact() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("database");
+
+        let mut log_db = LogDb::<DummyState>::create(db_path.clone(), DummyState::new(0)).unwrap();
+        log_db.set_max_log_records(3);
+        for _ in 0..10 {
+            log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        }
+        assert_eq!(log_db.get_state().x, 10);
+        // 9 records were compacted, only one remains in the log:
+        assert_eq!(log_db.num_log_records, 1);
+        drop(log_db);
+
+        let log_db = LogDb::<DummyState>::load(db_path.clone()).unwrap();
+        assert_eq!(log_db.get_state().x, 10);
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_log_db_crash_before_log_truncate() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("database");
+        let log_path = db_path.join(LOG_FILE_NAME);
+
+        let mut log_db = LogDb::<DummyState>::create(db_path.clone(), DummyState::new(0)).unwrap();
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        let log_buff = fs::read(&log_path).unwrap();
+
+        log_db.compact().unwrap();
+        drop(log_db);
+
+        // Simulate a crash after the snapshot was written, but before the log was truncated:
+        fs::write(&log_path, &log_buff).unwrap();
+
+        // Log records should not be applied twice:
+        let log_db = LogDb::<DummyState>::load(db_path.clone()).unwrap();
+        assert_eq!(log_db.get_state().x, 2);
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_log_db_torn_record() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("database");
+        let log_path = db_path.join(LOG_FILE_NAME);
+
+        let mut log_db = LogDb::<DummyState>::create(db_path.clone(), DummyState::new(0)).unwrap();
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        drop(log_db);
+
+        // Simulate a torn write of the last record:
+        let log_buff = fs::read(&log_path).unwrap();
+        fs::write(&log_path, &log_buff[..log_buff.len() - 3]).unwrap();
+
+        let mut log_db = LogDb::<DummyState>::load(db_path.clone()).unwrap();
+        assert_eq!(log_db.get_state().x, 1);
+
+        // The torn record should have been removed, new records are appended after the last
+        // valid record:
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        drop(log_db);
+
+        let log_db = LogDb::<DummyState>::load(db_path.clone()).unwrap();
+        assert_eq!(log_db.get_state().x, 3);
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_log_db_corrupted_middle_record() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("database");
+        let log_path = db_path.join(LOG_FILE_NAME);
+
+        let mut log_db = LogDb::<DummyState>::create(db_path.clone(), DummyState::new(0)).unwrap();
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        drop(log_db);
+
+        // Corrupt the payload of the first record:
+        let mut log_buff = fs::read(&log_path).unwrap();
+        log_buff[RECORD_HEADER_LEN] ^= 0xff;
+        fs::write(&log_path, &log_buff).unwrap();
+
+        match LogDb::<DummyState>::load(db_path.clone()) {
+            Err(LogDbError::CorruptedLog) => {}
+            _ => unreachable!(),
+        };
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_log_db_corrupted_middle_record_length() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("database");
+        let log_path = db_path.join(LOG_FILE_NAME);
+
+        let mut log_db = LogDb::<DummyState>::create(db_path.clone(), DummyState::new(0)).unwrap();
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        drop(log_db);
+
+        // Corrupt the length of the first record, so that it seems to continue beyond the end
+        // of the log:
+        let mut log_buff = fs::read(&log_path).unwrap();
+        log_buff[3] ^= 0x7f;
+        fs::write(&log_path, &log_buff).unwrap();
+
+        // This is not a torn final record:
+        match LogDb::<DummyState>::load(db_path.clone()) {
+            Err(LogDbError::CorruptedLog) => {}
+            _ => unreachable!(),
+        };
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_log_db_encrypted() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("database");
+
+        let mut log_db =
+            LogDb::<DummyState>::create_encrypted(db_path.clone(), DummyState::new(0), b"secret")
+                .unwrap();
+        log_db.set_max_log_records(2);
+        for _ in 0..3 {
+            log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        }
+        drop(log_db);
+
+        // Secret is required:
+        match LogDb::<DummyState>::load(db_path.clone()) {
+            Err(LogDbError::CipherError(CipherError::MissingSecret)) => {}
+            _ => unreachable!(),
+        };
+        // Wrong secret:
+        match LogDb::<DummyState>::load_encrypted(db_path.clone(), b"wrong secret") {
+            Err(LogDbError::CipherError(CipherError::DecryptError)) => {}
+            _ => unreachable!(),
+        };
+
+        let log_db = LogDb::<DummyState>::load_encrypted(db_path.clone(), b"secret").unwrap();
+        assert_eq!(log_db.get_state().x, 3);
+        drop(log_db);
+
+        // A secret can not be used with a plaintext database:
+        let plain_path = dir.path().join("plain_database");
+        let _ = LogDb::<DummyState>::create(plain_path.clone(), DummyState::new(0)).unwrap();
+        match LogDb::<DummyState>::load_encrypted(plain_path.clone(), b"secret") {
+            Err(LogDbError::CipherError(CipherError::NotEncrypted)) => {}
+            _ => unreachable!(),
+        };
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_log_db_migrate() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("database");
+        let log_path = db_path.join(LOG_FILE_NAME);
+
+        // A legacy database directory (No header, old schema):
+        fs::create_dir_all(&db_path).unwrap();
+        let snapshot_buff = bincode::serialize(&(0u64, 7u8)).unwrap();
+        fs::write(&db_path.join(SNAPSHOT_FILE_NAME), &snapshot_buff).unwrap();
+        let mut log_buff = Vec::new();
+        for (seq, mutations) in [(0u64, vec![true, true]), (1u64, vec![false])].iter() {
+            let payload = bincode::serialize(&(seq, mutations)).unwrap();
+            log_buff.extend_from_slice(&encode_record::<DummyMutateError>(&payload).unwrap());
+        }
+        fs::write(&log_path, &log_buff).unwrap();
+        assert_eq!(LogDb::<DummyState>::dir_version(&db_path, None).unwrap(), 0);
+
+        let mut log_db = LogDb::<DummyState>::load(db_path.clone()).unwrap();
+        assert_eq!(log_db.get_state().x, 8);
+
+        // Loading does not change the database:
+        assert_eq!(LogDb::<DummyState>::dir_version(&db_path, None).unwrap(), 0);
+        assert_eq!(fs::read(&log_path).unwrap(), log_buff);
+
+        // A new snapshot is written before appending a record with the new schema version:
+        log_db.mutate_db(&[DummyMutation::Inc]).unwrap();
+        assert_eq!(log_db.get_state().x, 9);
+        assert_eq!(log_db.num_log_records, 1);
+        drop(log_db);
+        assert_eq!(LogDb::<DummyState>::dir_version(&db_path, None).unwrap(), 1);
+
+        let log_db = LogDb::<DummyState>::load(db_path.clone()).unwrap();
+        assert_eq!(log_db.get_state().x, 9);
+        drop(log_db);
+
+        // A database from a future schema version can not be loaded:
+        let future_buff = add_header(2, &bincode::serialize(&(0u64, DummyState::new(7))).unwrap());
+        fs::write(&db_path.join(SNAPSHOT_FILE_NAME), &future_buff).unwrap();
+        match LogDb::<DummyState>::load(db_path.clone()) {
+            Err(LogDbError::MigrateError(MigrateError::UnsupportedVersion(2))) => {}
+            _ => unreachable!(),
+        };
+
+        dir.close().unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use common::int_convert::{u32_to_usize, usize_to_u32};
+
+/// Magic value at the beginning of every versioned database file
+pub const DB_MAGIC: [u8; 8] = *b"OFFSTDB\0";
+/// Length of the database header: magic value followed by a schema version (u32)
+pub const DB_HEADER_LEN: usize = 8 + 4;
+
+#[derive(Debug)]
+pub enum MigrateError {
+    /// The database was written by a newer version of the software
+    UnsupportedVersion(u32),
+    /// A migration step failed
+    MigrationFailed { from_version: u32 },
+}
+
+/// A migration step: Converts a serialized state of schema version `n` into a serialized state of
+/// schema version `n + 1`.
+pub type MigrateFn = fn(&[u8]) -> Result<Vec<u8>, ()>;
+
+/// A registry of migration steps. The step at index `n` upgrades schema version `n` to `n + 1`,
+/// hence the current schema version is the amount of registered steps.
+///
+/// Schema version 0 is reserved for legacy databases that were written without a header.
+pub struct Migrations {
+    steps: Vec<MigrateFn>,
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Migrations { steps: Vec::new() }
+    }
+
+    /// Register the next migration step
+    pub fn add(mut self, migrate_fn: MigrateFn) -> Self {
+        self.steps.push(migrate_fn);
+        self
+    }
+
+    /// The schema version of states written by this software
+    pub fn current_version(&self) -> u32 {
+        usize_to_u32(self.steps.len()).unwrap()
+    }
+
+    /// Upgrade a serialized state of a given schema version to the current schema version
+    pub fn migrate(&self, version: u32, data: Vec<u8>) -> Result<Vec<u8>, MigrateError> {
+        if version > self.current_version() {
+            return Err(MigrateError::UnsupportedVersion(version));
+        }
+        let mut data = data;
+        let steps = self.steps.iter().skip(u32_to_usize(version).unwrap());
+        for (from_version, migrate_fn) in (version..).zip(steps) {
+            data = migrate_fn(&data).map_err(|_| MigrateError::MigrationFailed { from_version })?;
+        }
+        Ok(data)
+    }
+}
+
+/// A migration step for a schema version that did not change the serialized layout
+pub fn migrate_unchanged(data: &[u8]) -> Result<Vec<u8>, ()> {
+    Ok(data.to_vec())
+}
+
+/// A state that can be stored in a versioned database file
+pub trait VersionedState {
+    /// Migration steps from older schema versions of this state
+    fn migrations() -> Migrations;
+
+    /// Migration steps from older schema versions of a serialized batch of mutations
+    /// (`Vec<Mutation>`). Log based databases keep batches of mutations on disk, and have to
+    /// migrate them together with the state.
+    ///
+    /// Must contain the same amount of steps as `migrations()`.
+    fn mutation_migrations() -> Migrations;
+}
+
+/// Prepend a database header to a serialized state
+pub fn add_header(version: u32, data: &[u8]) -> Vec<u8> {
+    let mut buff = Vec::with_capacity(DB_HEADER_LEN + data.len());
+    buff.extend_from_slice(&DB_MAGIC);
+    buff.write_u32::<LittleEndian>(version).unwrap();
+    buff.extend_from_slice(data);
+    buff
+}
+
+/// Split a database file into its schema version and the serialized state.
+/// A file that does not begin with the magic value is a legacy (version 0) database.
+pub fn split_header(buff: &[u8]) -> (u32, &[u8]) {
+    if buff.len() < DB_HEADER_LEN || buff[..DB_MAGIC.len()] != DB_MAGIC {
+        return (0, buff);
+    }
+    let version = (&buff[DB_MAGIC.len()..DB_HEADER_LEN])
+        .read_u32::<LittleEndian>()
+        .unwrap();
+    (version, &buff[DB_HEADER_LEN..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn append_zero(data: &[u8]) -> Result<Vec<u8>, ()> {
+        let mut data = data.to_vec();
+        data.push(0);
+        Ok(data)
+    }
+
+    fn append_one(data: &[u8]) -> Result<Vec<u8>, ()> {
+        let mut data = data.to_vec();
+        data.push(1);
+        Ok(data)
+    }
+
+    #[test]
+    fn test_migrations_basic() {
+        let migrations = Migrations::new().add(append_zero).add(append_one);
+        assert_eq!(migrations.current_version(), 2);
+
+        assert_eq!(migrations.migrate(0, vec![7]).unwrap(), vec![7, 0, 1]);
+        assert_eq!(migrations.migrate(1, vec![7]).unwrap(), vec![7, 1]);
+        assert_eq!(migrations.migrate(2, vec![7]).unwrap(), vec![7]);
+        match migrations.migrate(3, vec![7]) {
+            Err(MigrateError::UnsupportedVersion(3)) => {}
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn test_header() {
+        let buff = add_header(5, &[1, 2, 3]);
+        assert_eq!(split_header(&buff), (5, &[1, 2, 3][..]));
+
+        // A legacy file has no header:
+        assert_eq!(split_header(&[1, 2, 3]), (0, &[1, 2, 3][..]));
+    }
+}
@@ -1,19 +1,36 @@
+use im::hashmap::HashMap as ImHashMap;
+
+use crypto::invoice_id::InvoiceId;
+use crypto::payment_id::PaymentId;
+
 use super::liveness::{Liveness, LivenessMutation};
 
 #[derive(Clone, Default)]
 pub struct Ephemeral {
     pub liveness: Liveness,
+    /// Amount of timer ticks elapsed for every open payment.
+    /// Counting starts again after a restart of the funder.
+    pub payment_ticks: ImHashMap<PaymentId, usize>,
+    /// Amount of timer ticks elapsed for every open invoice that has an expiry.
+    /// Counting starts again after a restart of the funder.
+    pub invoice_ticks: ImHashMap<InvoiceId, u64>,
 }
 
 #[derive(Debug)]
 pub enum EphemeralMutation {
     LivenessMutation(LivenessMutation),
+    SetPaymentTicks((PaymentId, usize)),
+    RemovePaymentTicks(PaymentId),
+    SetInvoiceTicks((InvoiceId, u64)),
+    RemoveInvoiceTicks(InvoiceId),
 }
 
 impl Ephemeral {
     pub fn new() -> Ephemeral {
         Ephemeral {
             liveness: Liveness::new(),
+            payment_ticks: ImHashMap::new(),
+            invoice_ticks: ImHashMap::new(),
         }
     }
 
@@ -22,6 +39,18 @@ impl Ephemeral {
             EphemeralMutation::LivenessMutation(liveness_mutation) => {
                 self.liveness.mutate(liveness_mutation)
             }
+            EphemeralMutation::SetPaymentTicks((payment_id, ticks)) => {
+                self.payment_ticks.insert(*payment_id, *ticks);
+            }
+            EphemeralMutation::RemovePaymentTicks(payment_id) => {
+                let _ = self.payment_ticks.remove(payment_id);
+            }
+            EphemeralMutation::SetInvoiceTicks((invoice_id, ticks)) => {
+                self.invoice_ticks.insert(invoice_id.clone(), *ticks);
+            }
+            EphemeralMutation::RemoveInvoiceTicks(invoice_id) => {
+                let _ = self.invoice_ticks.remove(invoice_id);
+            }
         }
     }
 }
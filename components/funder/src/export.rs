@@ -0,0 +1,1586 @@
+//! A stable, human readable representation of `FunderState`, used for backup and audit.
+//!
+//! Public keys, signatures, locks and identifiers are encoded using `proto::file::ser_string`.
+//! Large integers (u128, i128) are encoded as decimal strings, because many JSON implementations
+//! can not represent them as numbers.
+//!
+//! The protocol state of every friend (Token channel, pending operations) is exported explicitly.
+//! It is not meant to be edited: Changing it may cause the token channel to become inconsistent.
+
+use std::collections::HashSet;
+use std::convert::TryInto;
+
+use im::hashmap::HashMap as ImHashMap;
+use im::vector::Vector as ImVec;
+
+use crypto::identity::PublicKey;
+use crypto::uid::Uid;
+
+use proto::app_server::messages::{NamedRelayAddress, RelayAddress};
+use proto::file::ser_string::{
+    bytes_to_string, dh_public_key_to_string, hash_result_to_string, hashed_lock_to_string,
+    invoice_id_to_string, payment_id_to_string, plain_lock_to_string, public_key_to_string,
+    rand_value_to_string, signature_to_string, string_to_bytes, string_to_dh_public_key,
+    string_to_hash_result, string_to_hashed_lock, string_to_invoice_id, string_to_payment_id,
+    string_to_plain_lock, string_to_public_key, string_to_rand_value, string_to_signature,
+    string_to_uid, uid_to_string, SerStringError,
+};
+use proto::funder::messages::{
+    CancelSendFundsOp, CollectSendFundsOp, FailureReason, FriendStatus, FriendTcOp, FriendsRoute,
+    MoveToken, PendingTransaction, Rate, Receipt, RequestSendFundsOp, RequestsStatus, ResetTerms,
+    ResponseSendFundsOp, TransactionStage,
+};
+use proto::net::messages::{NetAddress, NetAddressError};
+
+use crate::friend::{
+    BackwardsOp, ChannelInconsistent, ChannelStatus, FriendState, SentLocalRelays,
+};
+use crate::mutual_credit::types::{
+    McBalance, McIdents, McPendingTransactions, McRequestsStatus, MutualCredit, MutualCreditState,
+};
+use crate::state::{
+    FunderState, IncomingTransaction, NewTransactions, OpenInvoice, OpenTransaction, Payment,
+};
+use crate::token_channel::{TcDirection, TcIncoming, TcOutgoing, TokenChannel};
+use crate::types::MoveTokenHashed;
+
+#[derive(Debug)]
+pub enum FunderImportError {
+    SerStringError,
+    NetAddressError(NetAddressError),
+    InvalidNumber,
+    InvalidFriendStatus,
+    InvalidRequestsStatus,
+    InvalidFailureReason,
+    DuplicateRelay,
+    DuplicateFriend,
+    /// A friend has the same public key as the local node
+    FriendIsLocal,
+    /// A move token of a friend belongs to a different pair of public keys
+    ProtocolStateMismatch,
+    DuplicatePendingTransaction,
+    DuplicateInvoice,
+    DuplicateIncomingTransaction,
+    DuplicateTransaction,
+    DuplicatePayment,
+    /// An open transaction refers to a payment that does not exist
+    UnknownPayment,
+}
+
+impl From<SerStringError> for FunderImportError {
+    fn from(_e: SerStringError) -> Self {
+        FunderImportError::SerStringError
+    }
+}
+
+impl From<NetAddressError> for FunderImportError {
+    fn from(e: NetAddressError) -> Self {
+        FunderImportError::NetAddressError(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedRelayExport {
+    pub public_key: String,
+    pub address: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayExport {
+    pub public_key: String,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateExport {
+    pub mul: u32,
+    pub add: u32,
+}
+
+/// A readable summary of a token channel's balance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceExport {
+    pub balance: String,
+    pub local_max_debt: String,
+    pub remote_max_debt: String,
+    pub local_pending_debt: String,
+    pub remote_pending_debt: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SentLocalRelaysExport {
+    NeverSent,
+    Transition {
+        last_sent: Vec<NamedRelayExport>,
+        before_last_sent: Vec<NamedRelayExport>,
+    },
+    LastSent {
+        last_sent: Vec<NamedRelayExport>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestSendFundsExport {
+    pub request_id: String,
+    pub src_hashed_lock: String,
+    /// Only present for spontaneous payments
+    #[serde(default)]
+    pub opt_encrypted_src_plain_lock: Option<String>,
+    /// Public keys of the route, in order
+    pub route: Vec<String>,
+    pub dest_payment: String,
+    pub total_dest_payment: String,
+    pub invoice_id: String,
+    pub left_fees: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResponseSendFundsExport {
+    pub request_id: String,
+    pub dest_hashed_lock: String,
+    pub rand_nonce: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CancelSendFundsExport {
+    pub request_id: String,
+    pub reporting_public_key: String,
+    /// For example: "no_capacity", "timed_out"
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectSendFundsExport {
+    pub request_id: String,
+    pub src_plain_lock: String,
+    pub dest_plain_lock: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum FriendTcOpExport {
+    EnableRequests,
+    DisableRequests,
+    SetRemoteMaxDebt { remote_max_debt: String },
+    RequestSendFunds(RequestSendFundsExport),
+    ResponseSendFunds(ResponseSendFundsExport),
+    CancelSendFunds(CancelSendFundsExport),
+    CollectSendFunds(CollectSendFundsExport),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BackwardsOpExport {
+    Response(ResponseSendFundsExport),
+    Cancel(CancelSendFundsExport),
+    Collect(CollectSendFundsExport),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoveTokenExport {
+    pub operations: Vec<FriendTcOpExport>,
+    pub opt_local_relays: Option<Vec<RelayExport>>,
+    pub old_token: String,
+    pub local_public_key: String,
+    pub remote_public_key: String,
+    pub inconsistency_counter: u64,
+    pub move_token_counter: String,
+    pub balance: String,
+    pub local_pending_debt: String,
+    pub remote_pending_debt: String,
+    pub rand_nonce: String,
+    pub new_token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoveTokenHashedExport {
+    pub prefix_hash: String,
+    pub local_public_key: String,
+    pub remote_public_key: String,
+    pub inconsistency_counter: u64,
+    pub move_token_counter: String,
+    pub balance: String,
+    pub local_pending_debt: String,
+    pub remote_pending_debt: String,
+    pub rand_nonce: String,
+    pub new_token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResetTermsExport {
+    pub reset_token: String,
+    pub inconsistency_counter: u64,
+    pub balance_for_reset: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingTransactionExport {
+    pub request_id: String,
+    pub route: Vec<String>,
+    pub dest_payment: String,
+    pub total_dest_payment: String,
+    pub invoice_id: String,
+    pub left_fees: String,
+    pub src_hashed_lock: String,
+    /// Only present for spontaneous payments
+    #[serde(default)]
+    pub opt_encrypted_src_plain_lock: Option<String>,
+    /// Set once a response was received for this transaction.
+    pub opt_response_dest_hashed_lock: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MutualCreditExport {
+    pub balance: BalanceExport,
+    /// "open" or "closed"
+    pub local_requests_status: String,
+    /// "open" or "closed"
+    pub remote_requests_status: String,
+    pub local_pending_transactions: Vec<PendingTransactionExport>,
+    pub remote_pending_transactions: Vec<PendingTransactionExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ChannelStatusExport {
+    /// Consistent token channel, the token is held by the remote side.
+    Incoming {
+        mutual_credit: MutualCreditExport,
+        move_token_in: MoveTokenHashedExport,
+    },
+    /// Consistent token channel, the token is held by the local side.
+    Outgoing {
+        mutual_credit: MutualCreditExport,
+        move_token_out: MoveTokenExport,
+        opt_prev_move_token_in: Option<MoveTokenHashedExport>,
+    },
+    Inconsistent {
+        opt_last_incoming_move_token: Option<MoveTokenHashedExport>,
+        local_reset_terms: ResetTermsExport,
+        opt_remote_reset_terms: Option<ResetTermsExport>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FriendExport {
+    pub public_key: String,
+    pub name: String,
+    /// "enabled" or "disabled"
+    pub status: String,
+    pub rate: RateExport,
+    pub remote_relays: Vec<RelayExport>,
+    pub sent_local_relays: SentLocalRelaysExport,
+    pub channel_status: ChannelStatusExport,
+    pub wanted_remote_max_debt: String,
+    /// "open" or "closed"
+    pub wanted_local_requests_status: String,
+    pub pending_requests: Vec<RequestSendFundsExport>,
+    pub pending_backwards_ops: Vec<BackwardsOpExport>,
+    pub pending_user_requests: Vec<RequestSendFundsExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IncomingTransactionExport {
+    pub request_id: String,
+    pub dest_plain_lock: String,
+    pub dest_payment: String,
+    #[serde(default)]
+    pub opt_src_plain_lock: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenInvoiceExport {
+    pub invoice_id: String,
+    pub total_dest_payment: String,
+    pub incoming_transactions: Vec<IncomingTransactionExport>,
+    pub opt_expiry_ticks: Option<u64>,
+    #[serde(default)]
+    pub is_spontaneous: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResponseExport {
+    pub dest_hashed_lock: String,
+    pub rand_nonce: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenTransactionExport {
+    pub request_id: String,
+    pub payment_id: String,
+    pub src_plain_lock: String,
+    pub opt_response: Option<ResponseExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptExport {
+    pub response_hash: String,
+    pub invoice_id: String,
+    pub src_plain_lock: String,
+    pub dest_plain_lock: String,
+    pub dest_payment: String,
+    pub total_dest_payment: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum PaymentStageExport {
+    NewTransactions {
+        num_transactions: u64,
+        invoice_id: String,
+        total_dest_payment: String,
+        dest_public_key: String,
+        #[serde(default)]
+        opt_dest_dh_public_key: Option<String>,
+        opt_deadline_ticks: Option<u64>,
+    },
+    InProgress {
+        num_transactions: u64,
+        opt_deadline_ticks: Option<u64>,
+    },
+    Success {
+        num_transactions: u64,
+        receipt: ReceiptExport,
+        ack_uid: String,
+    },
+    Canceled {
+        ack_uid: String,
+    },
+    AfterSuccessAck {
+        num_transactions: u64,
+    },
+    TimedOut {
+        num_transactions: u64,
+        ack_uid: String,
+    },
+    AfterTimeoutAck {
+        num_transactions: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentExport {
+    pub payment_id: String,
+    pub stage: PaymentStageExport,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunderStateExport {
+    pub local_public_key: String,
+    pub relays: Vec<NamedRelayExport>,
+    pub friends: Vec<FriendExport>,
+    pub open_invoices: Vec<OpenInvoiceExport>,
+    pub open_transactions: Vec<OpenTransactionExport>,
+    pub payments: Vec<PaymentExport>,
+    #[serde(default)]
+    pub accept_spontaneous_payments: bool,
+}
+
+fn parse_u128(input: &str) -> Result<u128, FunderImportError> {
+    input.parse().map_err(|_| FunderImportError::InvalidNumber)
+}
+
+fn parse_i128(input: &str) -> Result<i128, FunderImportError> {
+    input.parse().map_err(|_| FunderImportError::InvalidNumber)
+}
+
+fn export_requests_status(requests_status: &RequestsStatus) -> String {
+    match requests_status {
+        RequestsStatus::Open => "open",
+        RequestsStatus::Closed => "closed",
+    }
+    .to_owned()
+}
+
+fn import_requests_status(input: &str) -> Result<RequestsStatus, FunderImportError> {
+    match input {
+        "open" => Ok(RequestsStatus::Open),
+        "closed" => Ok(RequestsStatus::Closed),
+        _ => Err(FunderImportError::InvalidRequestsStatus),
+    }
+}
+
+fn export_failure_reason(reason: &FailureReason) -> String {
+    match reason {
+        FailureReason::UnknownInvoice => "unknown_invoice",
+        FailureReason::FriendNotReady => "friend_not_ready",
+        FailureReason::RequestsClosed => "requests_closed",
+        FailureReason::InsufficientFees => "insufficient_fees",
+        FailureReason::NoCapacity => "no_capacity",
+        FailureReason::InvalidRoute => "invalid_route",
+        FailureReason::RouteTooLong => "route_too_long",
+        FailureReason::ChannelReset => "channel_reset",
+        FailureReason::TimedOut => "timed_out",
+        FailureReason::Rejected => "rejected",
+    }
+    .to_owned()
+}
+
+fn import_failure_reason(input: &str) -> Result<FailureReason, FunderImportError> {
+    Ok(match input {
+        "unknown_invoice" => FailureReason::UnknownInvoice,
+        "friend_not_ready" => FailureReason::FriendNotReady,
+        "requests_closed" => FailureReason::RequestsClosed,
+        "insufficient_fees" => FailureReason::InsufficientFees,
+        "no_capacity" => FailureReason::NoCapacity,
+        "invalid_route" => FailureReason::InvalidRoute,
+        "route_too_long" => FailureReason::RouteTooLong,
+        "channel_reset" => FailureReason::ChannelReset,
+        "timed_out" => FailureReason::TimedOut,
+        "rejected" => FailureReason::Rejected,
+        _ => return Err(FunderImportError::InvalidFailureReason),
+    })
+}
+
+fn export_relay(relay_address: &RelayAddress<NetAddress>) -> RelayExport {
+    RelayExport {
+        public_key: public_key_to_string(&relay_address.public_key),
+        address: relay_address.address.as_str().to_owned(),
+    }
+}
+
+fn import_relay(relay_export: &RelayExport) -> Result<RelayAddress<NetAddress>, FunderImportError> {
+    Ok(RelayAddress {
+        public_key: string_to_public_key(&relay_export.public_key)?,
+        address: relay_export.address.clone().try_into()?,
+    })
+}
+
+fn export_named_relay(named_relay: &NamedRelayAddress<NetAddress>) -> NamedRelayExport {
+    NamedRelayExport {
+        public_key: public_key_to_string(&named_relay.public_key),
+        address: named_relay.address.as_str().to_owned(),
+        name: named_relay.name.clone(),
+    }
+}
+
+fn import_named_relay(
+    relay_export: &NamedRelayExport,
+) -> Result<NamedRelayAddress<NetAddress>, FunderImportError> {
+    Ok(NamedRelayAddress {
+        public_key: string_to_public_key(&relay_export.public_key)?,
+        address: relay_export.address.clone().try_into()?,
+        name: relay_export.name.clone(),
+    })
+}
+
+fn import_named_relays(
+    relay_exports: &[NamedRelayExport],
+) -> Result<ImVec<NamedRelayAddress<NetAddress>>, FunderImportError> {
+    relay_exports.iter().map(import_named_relay).collect()
+}
+
+fn export_sent_local_relays(
+    sent_local_relays: &SentLocalRelays<NetAddress>,
+) -> SentLocalRelaysExport {
+    match sent_local_relays {
+        SentLocalRelays::NeverSent => SentLocalRelaysExport::NeverSent,
+        SentLocalRelays::Transition((last_sent, before_last_sent)) => {
+            SentLocalRelaysExport::Transition {
+                last_sent: last_sent.iter().map(export_named_relay).collect(),
+                before_last_sent: before_last_sent.iter().map(export_named_relay).collect(),
+            }
+        }
+        SentLocalRelays::LastSent(last_sent) => SentLocalRelaysExport::LastSent {
+            last_sent: last_sent.iter().map(export_named_relay).collect(),
+        },
+    }
+}
+
+fn import_sent_local_relays(
+    sent_local_relays_export: &SentLocalRelaysExport,
+) -> Result<SentLocalRelays<NetAddress>, FunderImportError> {
+    Ok(match sent_local_relays_export {
+        SentLocalRelaysExport::NeverSent => SentLocalRelays::NeverSent,
+        SentLocalRelaysExport::Transition {
+            last_sent,
+            before_last_sent,
+        } => SentLocalRelays::Transition((
+            import_named_relays(last_sent)?,
+            import_named_relays(before_last_sent)?,
+        )),
+        SentLocalRelaysExport::LastSent { last_sent } => {
+            SentLocalRelays::LastSent(import_named_relays(last_sent)?)
+        }
+    })
+}
+
+fn export_route(route: &FriendsRoute) -> Vec<String> {
+    route.public_keys.iter().map(public_key_to_string).collect()
+}
+
+fn import_route(route_export: &[String]) -> Result<FriendsRoute, FunderImportError> {
+    let mut public_keys = Vec::new();
+    for public_key in route_export {
+        public_keys.push(string_to_public_key(public_key)?);
+    }
+    Ok(FriendsRoute { public_keys })
+}
+
+fn export_request(request: &RequestSendFundsOp) -> RequestSendFundsExport {
+    RequestSendFundsExport {
+        request_id: uid_to_string(&request.request_id),
+        src_hashed_lock: hashed_lock_to_string(&request.src_hashed_lock),
+        opt_encrypted_src_plain_lock: request
+            .opt_encrypted_src_plain_lock
+            .as_ref()
+            .map(|encrypted_src_plain_lock| bytes_to_string(&encrypted_src_plain_lock[..])),
+        route: export_route(&request.route),
+        dest_payment: request.dest_payment.to_string(),
+        total_dest_payment: request.total_dest_payment.to_string(),
+        invoice_id: invoice_id_to_string(&request.invoice_id),
+        left_fees: request.left_fees.to_string(),
+    }
+}
+
+fn import_request(
+    request_export: &RequestSendFundsExport,
+) -> Result<RequestSendFundsOp, FunderImportError> {
+    Ok(RequestSendFundsOp {
+        request_id: string_to_uid(&request_export.request_id)?,
+        src_hashed_lock: string_to_hashed_lock(&request_export.src_hashed_lock)?,
+        opt_encrypted_src_plain_lock: match &request_export.opt_encrypted_src_plain_lock {
+            Some(encrypted_src_plain_lock) => Some(string_to_bytes(encrypted_src_plain_lock)?),
+            None => None,
+        },
+        route: import_route(&request_export.route)?,
+        dest_payment: parse_u128(&request_export.dest_payment)?,
+        total_dest_payment: parse_u128(&request_export.total_dest_payment)?,
+        invoice_id: string_to_invoice_id(&request_export.invoice_id)?,
+        left_fees: parse_u128(&request_export.left_fees)?,
+    })
+}
+
+fn import_requests(
+    request_exports: &[RequestSendFundsExport],
+) -> Result<ImVec<RequestSendFundsOp>, FunderImportError> {
+    request_exports.iter().map(import_request).collect()
+}
+
+fn export_response(response: &ResponseSendFundsOp) -> ResponseSendFundsExport {
+    ResponseSendFundsExport {
+        request_id: uid_to_string(&response.request_id),
+        dest_hashed_lock: hashed_lock_to_string(&response.dest_hashed_lock),
+        rand_nonce: rand_value_to_string(&response.rand_nonce),
+        signature: signature_to_string(&response.signature),
+    }
+}
+
+fn import_response(
+    response_export: &ResponseSendFundsExport,
+) -> Result<ResponseSendFundsOp, FunderImportError> {
+    Ok(ResponseSendFundsOp {
+        request_id: string_to_uid(&response_export.request_id)?,
+        dest_hashed_lock: string_to_hashed_lock(&response_export.dest_hashed_lock)?,
+        rand_nonce: string_to_rand_value(&response_export.rand_nonce)?,
+        signature: string_to_signature(&response_export.signature)?,
+    })
+}
+
+fn export_cancel(cancel: &CancelSendFundsOp) -> CancelSendFundsExport {
+    CancelSendFundsExport {
+        request_id: uid_to_string(&cancel.request_id),
+        reporting_public_key: public_key_to_string(&cancel.reporting_public_key),
+        reason: export_failure_reason(&cancel.reason),
+    }
+}
+
+fn import_cancel(
+    cancel_export: &CancelSendFundsExport,
+) -> Result<CancelSendFundsOp, FunderImportError> {
+    Ok(CancelSendFundsOp {
+        request_id: string_to_uid(&cancel_export.request_id)?,
+        reporting_public_key: string_to_public_key(&cancel_export.reporting_public_key)?,
+        reason: import_failure_reason(&cancel_export.reason)?,
+    })
+}
+
+fn export_collect(collect: &CollectSendFundsOp) -> CollectSendFundsExport {
+    CollectSendFundsExport {
+        request_id: uid_to_string(&collect.request_id),
+        src_plain_lock: plain_lock_to_string(&collect.src_plain_lock),
+        dest_plain_lock: plain_lock_to_string(&collect.dest_plain_lock),
+    }
+}
+
+fn import_collect(
+    collect_export: &CollectSendFundsExport,
+) -> Result<CollectSendFundsOp, FunderImportError> {
+    Ok(CollectSendFundsOp {
+        request_id: string_to_uid(&collect_export.request_id)?,
+        src_plain_lock: string_to_plain_lock(&collect_export.src_plain_lock)?,
+        dest_plain_lock: string_to_plain_lock(&collect_export.dest_plain_lock)?,
+    })
+}
+
+fn export_friend_tc_op(friend_tc_op: &FriendTcOp) -> FriendTcOpExport {
+    match friend_tc_op {
+        FriendTcOp::EnableRequests => FriendTcOpExport::EnableRequests,
+        FriendTcOp::DisableRequests => FriendTcOpExport::DisableRequests,
+        FriendTcOp::SetRemoteMaxDebt(remote_max_debt) => FriendTcOpExport::SetRemoteMaxDebt {
+            remote_max_debt: remote_max_debt.to_string(),
+        },
+        FriendTcOp::RequestSendFunds(request) => {
+            FriendTcOpExport::RequestSendFunds(export_request(request))
+        }
+        FriendTcOp::ResponseSendFunds(response) => {
+            FriendTcOpExport::ResponseSendFunds(export_response(response))
+        }
+        FriendTcOp::CancelSendFunds(cancel) => {
+            FriendTcOpExport::CancelSendFunds(export_cancel(cancel))
+        }
+        FriendTcOp::CollectSendFunds(collect) => {
+            FriendTcOpExport::CollectSendFunds(export_collect(collect))
+        }
+    }
+}
+
+fn import_friend_tc_op(
+    friend_tc_op_export: &FriendTcOpExport,
+) -> Result<FriendTcOp, FunderImportError> {
+    Ok(match friend_tc_op_export {
+        FriendTcOpExport::EnableRequests => FriendTcOp::EnableRequests,
+        FriendTcOpExport::DisableRequests => FriendTcOp::DisableRequests,
+        FriendTcOpExport::SetRemoteMaxDebt { remote_max_debt } => {
+            FriendTcOp::SetRemoteMaxDebt(parse_u128(remote_max_debt)?)
+        }
+        FriendTcOpExport::RequestSendFunds(request_export) => {
+            FriendTcOp::RequestSendFunds(import_request(request_export)?)
+        }
+        FriendTcOpExport::ResponseSendFunds(response_export) => {
+            FriendTcOp::ResponseSendFunds(import_response(response_export)?)
+        }
+        FriendTcOpExport::CancelSendFunds(cancel_export) => {
+            FriendTcOp::CancelSendFunds(import_cancel(cancel_export)?)
+        }
+        FriendTcOpExport::CollectSendFunds(collect_export) => {
+            FriendTcOp::CollectSendFunds(import_collect(collect_export)?)
+        }
+    })
+}
+
+fn export_backwards_op(backwards_op: &BackwardsOp) -> BackwardsOpExport {
+    match backwards_op {
+        BackwardsOp::Response(response) => BackwardsOpExport::Response(export_response(response)),
+        BackwardsOp::Cancel(cancel) => BackwardsOpExport::Cancel(export_cancel(cancel)),
+        BackwardsOp::Collect(collect) => BackwardsOpExport::Collect(export_collect(collect)),
+    }
+}
+
+fn import_backwards_op(
+    backwards_op_export: &BackwardsOpExport,
+) -> Result<BackwardsOp, FunderImportError> {
+    Ok(match backwards_op_export {
+        BackwardsOpExport::Response(response_export) => {
+            BackwardsOp::Response(import_response(response_export)?)
+        }
+        BackwardsOpExport::Cancel(cancel_export) => {
+            BackwardsOp::Cancel(import_cancel(cancel_export)?)
+        }
+        BackwardsOpExport::Collect(collect_export) => {
+            BackwardsOp::Collect(import_collect(collect_export)?)
+        }
+    })
+}
+
+fn export_move_token(move_token: &MoveToken<NetAddress>) -> MoveTokenExport {
+    MoveTokenExport {
+        operations: move_token
+            .operations
+            .iter()
+            .map(export_friend_tc_op)
+            .collect(),
+        opt_local_relays: move_token
+            .opt_local_relays
+            .as_ref()
+            .map(|local_relays| local_relays.iter().map(export_relay).collect()),
+        old_token: signature_to_string(&move_token.old_token),
+        local_public_key: public_key_to_string(&move_token.local_public_key),
+        remote_public_key: public_key_to_string(&move_token.remote_public_key),
+        inconsistency_counter: move_token.inconsistency_counter,
+        move_token_counter: move_token.move_token_counter.to_string(),
+        balance: move_token.balance.to_string(),
+        local_pending_debt: move_token.local_pending_debt.to_string(),
+        remote_pending_debt: move_token.remote_pending_debt.to_string(),
+        rand_nonce: rand_value_to_string(&move_token.rand_nonce),
+        new_token: signature_to_string(&move_token.new_token),
+    }
+}
+
+fn import_move_token(
+    move_token_export: &MoveTokenExport,
+) -> Result<MoveToken<NetAddress>, FunderImportError> {
+    let mut operations = Vec::new();
+    for operation_export in &move_token_export.operations {
+        operations.push(import_friend_tc_op(operation_export)?);
+    }
+    let opt_local_relays = match &move_token_export.opt_local_relays {
+        Some(local_relays) => Some(
+            local_relays
+                .iter()
+                .map(import_relay)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        None => None,
+    };
+    Ok(MoveToken {
+        operations,
+        opt_local_relays,
+        old_token: string_to_signature(&move_token_export.old_token)?,
+        local_public_key: string_to_public_key(&move_token_export.local_public_key)?,
+        remote_public_key: string_to_public_key(&move_token_export.remote_public_key)?,
+        inconsistency_counter: move_token_export.inconsistency_counter,
+        move_token_counter: parse_u128(&move_token_export.move_token_counter)?,
+        balance: parse_i128(&move_token_export.balance)?,
+        local_pending_debt: parse_u128(&move_token_export.local_pending_debt)?,
+        remote_pending_debt: parse_u128(&move_token_export.remote_pending_debt)?,
+        rand_nonce: string_to_rand_value(&move_token_export.rand_nonce)?,
+        new_token: string_to_signature(&move_token_export.new_token)?,
+    })
+}
+
+fn export_move_token_hashed(move_token_hashed: &MoveTokenHashed) -> MoveTokenHashedExport {
+    MoveTokenHashedExport {
+        prefix_hash: hash_result_to_string(&move_token_hashed.prefix_hash),
+        local_public_key: public_key_to_string(&move_token_hashed.local_public_key),
+        remote_public_key: public_key_to_string(&move_token_hashed.remote_public_key),
+        inconsistency_counter: move_token_hashed.inconsistency_counter,
+        move_token_counter: move_token_hashed.move_token_counter.to_string(),
+        balance: move_token_hashed.balance.to_string(),
+        local_pending_debt: move_token_hashed.local_pending_debt.to_string(),
+        remote_pending_debt: move_token_hashed.remote_pending_debt.to_string(),
+        rand_nonce: rand_value_to_string(&move_token_hashed.rand_nonce),
+        new_token: signature_to_string(&move_token_hashed.new_token),
+    }
+}
+
+fn import_move_token_hashed(
+    move_token_hashed_export: &MoveTokenHashedExport,
+) -> Result<MoveTokenHashed, FunderImportError> {
+    Ok(MoveTokenHashed {
+        prefix_hash: string_to_hash_result(&move_token_hashed_export.prefix_hash)?,
+        local_public_key: string_to_public_key(&move_token_hashed_export.local_public_key)?,
+        remote_public_key: string_to_public_key(&move_token_hashed_export.remote_public_key)?,
+        inconsistency_counter: move_token_hashed_export.inconsistency_counter,
+        move_token_counter: parse_u128(&move_token_hashed_export.move_token_counter)?,
+        balance: parse_i128(&move_token_hashed_export.balance)?,
+        local_pending_debt: parse_u128(&move_token_hashed_export.local_pending_debt)?,
+        remote_pending_debt: parse_u128(&move_token_hashed_export.remote_pending_debt)?,
+        rand_nonce: string_to_rand_value(&move_token_hashed_export.rand_nonce)?,
+        new_token: string_to_signature(&move_token_hashed_export.new_token)?,
+    })
+}
+
+fn import_opt_move_token_hashed(
+    opt_move_token_hashed_export: &Option<MoveTokenHashedExport>,
+) -> Result<Option<MoveTokenHashed>, FunderImportError> {
+    match opt_move_token_hashed_export {
+        Some(move_token_hashed_export) => {
+            Ok(Some(import_move_token_hashed(move_token_hashed_export)?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Make sure that a move token was sent between the expected pair of public keys.
+fn check_move_token_keys(
+    local_public_key: &PublicKey,
+    remote_public_key: &PublicKey,
+    sender_public_key: &PublicKey,
+    receiver_public_key: &PublicKey,
+) -> Result<(), FunderImportError> {
+    if local_public_key != sender_public_key || remote_public_key != receiver_public_key {
+        return Err(FunderImportError::ProtocolStateMismatch);
+    }
+    Ok(())
+}
+
+fn export_reset_terms(reset_terms: &ResetTerms) -> ResetTermsExport {
+    ResetTermsExport {
+        reset_token: signature_to_string(&reset_terms.reset_token),
+        inconsistency_counter: reset_terms.inconsistency_counter,
+        balance_for_reset: reset_terms.balance_for_reset.to_string(),
+    }
+}
+
+fn import_reset_terms(
+    reset_terms_export: &ResetTermsExport,
+) -> Result<ResetTerms, FunderImportError> {
+    Ok(ResetTerms {
+        reset_token: string_to_signature(&reset_terms_export.reset_token)?,
+        inconsistency_counter: reset_terms_export.inconsistency_counter,
+        balance_for_reset: parse_i128(&reset_terms_export.balance_for_reset)?,
+    })
+}
+
+fn export_balance(mc_balance: &McBalance) -> BalanceExport {
+    BalanceExport {
+        balance: mc_balance.balance.to_string(),
+        local_max_debt: mc_balance.local_max_debt.to_string(),
+        remote_max_debt: mc_balance.remote_max_debt.to_string(),
+        local_pending_debt: mc_balance.local_pending_debt.to_string(),
+        remote_pending_debt: mc_balance.remote_pending_debt.to_string(),
+    }
+}
+
+fn import_balance(balance_export: &BalanceExport) -> Result<McBalance, FunderImportError> {
+    Ok(McBalance {
+        balance: parse_i128(&balance_export.balance)?,
+        local_max_debt: parse_u128(&balance_export.local_max_debt)?,
+        remote_max_debt: parse_u128(&balance_export.remote_max_debt)?,
+        local_pending_debt: parse_u128(&balance_export.local_pending_debt)?,
+        remote_pending_debt: parse_u128(&balance_export.remote_pending_debt)?,
+    })
+}
+
+fn export_pending_transactions(
+    pending_transactions: &ImHashMap<Uid, PendingTransaction>,
+) -> Vec<PendingTransactionExport> {
+    let mut pending_transaction_exports: Vec<_> = pending_transactions
+        .values()
+        .map(|pending_transaction| PendingTransactionExport {
+            request_id: uid_to_string(&pending_transaction.request_id),
+            route: export_route(&pending_transaction.route),
+            dest_payment: pending_transaction.dest_payment.to_string(),
+            total_dest_payment: pending_transaction.total_dest_payment.to_string(),
+            invoice_id: invoice_id_to_string(&pending_transaction.invoice_id),
+            left_fees: pending_transaction.left_fees.to_string(),
+            src_hashed_lock: hashed_lock_to_string(&pending_transaction.src_hashed_lock),
+            opt_encrypted_src_plain_lock: pending_transaction
+                .opt_encrypted_src_plain_lock
+                .as_ref()
+                .map(|encrypted_src_plain_lock| bytes_to_string(&encrypted_src_plain_lock[..])),
+            opt_response_dest_hashed_lock: match &pending_transaction.stage {
+                TransactionStage::Request => None,
+                TransactionStage::Response(dest_hashed_lock) => {
+                    Some(hashed_lock_to_string(dest_hashed_lock))
+                }
+            },
+        })
+        .collect();
+    pending_transaction_exports.sort_by(|a, b| a.request_id.cmp(&b.request_id));
+    pending_transaction_exports
+}
+
+fn import_pending_transactions(
+    pending_transaction_exports: &[PendingTransactionExport],
+) -> Result<ImHashMap<Uid, PendingTransaction>, FunderImportError> {
+    let mut pending_transactions = ImHashMap::new();
+    for pending_export in pending_transaction_exports {
+        let pending_transaction = PendingTransaction {
+            request_id: string_to_uid(&pending_export.request_id)?,
+            route: import_route(&pending_export.route)?,
+            dest_payment: parse_u128(&pending_export.dest_payment)?,
+            total_dest_payment: parse_u128(&pending_export.total_dest_payment)?,
+            invoice_id: string_to_invoice_id(&pending_export.invoice_id)?,
+            left_fees: parse_u128(&pending_export.left_fees)?,
+            src_hashed_lock: string_to_hashed_lock(&pending_export.src_hashed_lock)?,
+            opt_encrypted_src_plain_lock: match &pending_export.opt_encrypted_src_plain_lock {
+                Some(encrypted_src_plain_lock) => Some(string_to_bytes(encrypted_src_plain_lock)?),
+                None => None,
+            },
+            stage: match &pending_export.opt_response_dest_hashed_lock {
+                Some(dest_hashed_lock) => {
+                    TransactionStage::Response(string_to_hashed_lock(dest_hashed_lock)?)
+                }
+                None => TransactionStage::Request,
+            },
+        };
+        if pending_transactions
+            .insert(pending_transaction.request_id.clone(), pending_transaction)
+            .is_some()
+        {
+            return Err(FunderImportError::DuplicatePendingTransaction);
+        }
+    }
+    Ok(pending_transactions)
+}
+
+fn export_mutual_credit(mutual_credit: &MutualCredit) -> MutualCreditExport {
+    let mc_state = mutual_credit.state();
+    MutualCreditExport {
+        balance: export_balance(&mc_state.balance),
+        local_requests_status: export_requests_status(&mc_state.requests_status.local),
+        remote_requests_status: export_requests_status(&mc_state.requests_status.remote),
+        local_pending_transactions: export_pending_transactions(
+            &mc_state.pending_transactions.local,
+        ),
+        remote_pending_transactions: export_pending_transactions(
+            &mc_state.pending_transactions.remote,
+        ),
+    }
+}
+
+/// The identities of the mutual credit are not exported, as they are implied by the friend.
+fn import_mutual_credit(
+    local_public_key: &PublicKey,
+    remote_public_key: &PublicKey,
+    mutual_credit_export: &MutualCreditExport,
+) -> Result<MutualCredit, FunderImportError> {
+    Ok(MutualCredit::from_state(MutualCreditState {
+        idents: McIdents {
+            local_public_key: local_public_key.clone(),
+            remote_public_key: remote_public_key.clone(),
+        },
+        balance: import_balance(&mutual_credit_export.balance)?,
+        pending_transactions: McPendingTransactions {
+            local: import_pending_transactions(&mutual_credit_export.local_pending_transactions)?,
+            remote: import_pending_transactions(&mutual_credit_export.remote_pending_transactions)?,
+        },
+        requests_status: McRequestsStatus {
+            local: import_requests_status(&mutual_credit_export.local_requests_status)?,
+            remote: import_requests_status(&mutual_credit_export.remote_requests_status)?,
+        },
+    }))
+}
+
+fn export_channel_status(channel_status: &ChannelStatus<NetAddress>) -> ChannelStatusExport {
+    match channel_status {
+        ChannelStatus::Consistent(token_channel) => match token_channel.get_direction() {
+            TcDirection::Incoming(tc_incoming) => ChannelStatusExport::Incoming {
+                mutual_credit: export_mutual_credit(&tc_incoming.mutual_credit),
+                move_token_in: export_move_token_hashed(&tc_incoming.move_token_in),
+            },
+            TcDirection::Outgoing(tc_outgoing) => ChannelStatusExport::Outgoing {
+                mutual_credit: export_mutual_credit(&tc_outgoing.mutual_credit),
+                move_token_out: export_move_token(&tc_outgoing.move_token_out),
+                opt_prev_move_token_in: tc_outgoing
+                    .opt_prev_move_token_in
+                    .as_ref()
+                    .map(export_move_token_hashed),
+            },
+        },
+        ChannelStatus::Inconsistent(channel_inconsistent) => ChannelStatusExport::Inconsistent {
+            opt_last_incoming_move_token: channel_inconsistent
+                .opt_last_incoming_move_token
+                .as_ref()
+                .map(export_move_token_hashed),
+            local_reset_terms: export_reset_terms(&channel_inconsistent.local_reset_terms),
+            opt_remote_reset_terms: channel_inconsistent
+                .opt_remote_reset_terms
+                .as_ref()
+                .map(export_reset_terms),
+        },
+    }
+}
+
+/// Restore the channel status with a friend.
+/// Incoming move tokens must be sent from the friend to the local node, and outgoing move tokens
+/// from the local node to the friend.
+fn import_channel_status(
+    local_public_key: &PublicKey,
+    remote_public_key: &PublicKey,
+    channel_status_export: &ChannelStatusExport,
+) -> Result<ChannelStatus<NetAddress>, FunderImportError> {
+    let check_incoming = |move_token_in: &MoveTokenHashed| {
+        check_move_token_keys(
+            remote_public_key,
+            local_public_key,
+            &move_token_in.local_public_key,
+            &move_token_in.remote_public_key,
+        )
+    };
+
+    Ok(match channel_status_export {
+        ChannelStatusExport::Incoming {
+            mutual_credit,
+            move_token_in,
+        } => {
+            let move_token_in = import_move_token_hashed(move_token_in)?;
+            check_incoming(&move_token_in)?;
+            let tc_incoming = TcIncoming {
+                mutual_credit: import_mutual_credit(
+                    local_public_key,
+                    remote_public_key,
+                    mutual_credit,
+                )?,
+                move_token_in,
+            };
+            ChannelStatus::Consistent(TokenChannel::from_direction(TcDirection::Incoming(
+                tc_incoming,
+            )))
+        }
+        ChannelStatusExport::Outgoing {
+            mutual_credit,
+            move_token_out,
+            opt_prev_move_token_in,
+        } => {
+            let move_token_out = import_move_token(move_token_out)?;
+            check_move_token_keys(
+                local_public_key,
+                remote_public_key,
+                &move_token_out.local_public_key,
+                &move_token_out.remote_public_key,
+            )?;
+            let opt_prev_move_token_in = import_opt_move_token_hashed(opt_prev_move_token_in)?;
+            if let Some(prev_move_token_in) = &opt_prev_move_token_in {
+                check_incoming(prev_move_token_in)?;
+            }
+            let tc_outgoing = TcOutgoing {
+                mutual_credit: import_mutual_credit(
+                    local_public_key,
+                    remote_public_key,
+                    mutual_credit,
+                )?,
+                move_token_out,
+                opt_prev_move_token_in,
+            };
+            ChannelStatus::Consistent(TokenChannel::from_direction(TcDirection::Outgoing(
+                tc_outgoing,
+            )))
+        }
+        ChannelStatusExport::Inconsistent {
+            opt_last_incoming_move_token,
+            local_reset_terms,
+            opt_remote_reset_terms,
+        } => {
+            let opt_last_incoming_move_token =
+                import_opt_move_token_hashed(opt_last_incoming_move_token)?;
+            if let Some(last_incoming_move_token) = &opt_last_incoming_move_token {
+                check_incoming(last_incoming_move_token)?;
+            }
+            ChannelStatus::Inconsistent(ChannelInconsistent {
+                opt_last_incoming_move_token,
+                local_reset_terms: import_reset_terms(local_reset_terms)?,
+                opt_remote_reset_terms: match opt_remote_reset_terms {
+                    Some(remote_reset_terms) => Some(import_reset_terms(remote_reset_terms)?),
+                    None => None,
+                },
+            })
+        }
+    })
+}
+
+fn export_friend(friend: &FriendState<NetAddress>) -> FriendExport {
+    FriendExport {
+        public_key: public_key_to_string(&friend.remote_public_key),
+        name: friend.name.clone(),
+        status: match friend.status {
+            FriendStatus::Enabled => "enabled",
+            FriendStatus::Disabled => "disabled",
+        }
+        .to_owned(),
+        rate: RateExport {
+            mul: friend.rate.mul,
+            add: friend.rate.add,
+        },
+        remote_relays: friend.remote_relays.iter().map(export_relay).collect(),
+        sent_local_relays: export_sent_local_relays(&friend.sent_local_relays),
+        channel_status: export_channel_status(&friend.channel_status),
+        wanted_remote_max_debt: friend.wanted_remote_max_debt.to_string(),
+        wanted_local_requests_status: export_requests_status(&friend.wanted_local_requests_status),
+        pending_requests: friend.pending_requests.iter().map(export_request).collect(),
+        pending_backwards_ops: friend
+            .pending_backwards_ops
+            .iter()
+            .map(export_backwards_op)
+            .collect(),
+        pending_user_requests: friend
+            .pending_user_requests
+            .iter()
+            .map(export_request)
+            .collect(),
+    }
+}
+
+fn import_friend(
+    local_public_key: &PublicKey,
+    friend_export: &FriendExport,
+) -> Result<FriendState<NetAddress>, FunderImportError> {
+    let remote_public_key = string_to_public_key(&friend_export.public_key)?;
+    if &remote_public_key == local_public_key {
+        return Err(FunderImportError::FriendIsLocal);
+    }
+
+    let status = match friend_export.status.as_str() {
+        "enabled" => FriendStatus::Enabled,
+        "disabled" => FriendStatus::Disabled,
+        _ => return Err(FunderImportError::InvalidFriendStatus),
+    };
+
+    let remote_relays = friend_export
+        .remote_relays
+        .iter()
+        .map(import_relay)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let channel_status = import_channel_status(
+        local_public_key,
+        &remote_public_key,
+        &friend_export.channel_status,
+    )?;
+
+    let pending_backwards_ops = friend_export
+        .pending_backwards_ops
+        .iter()
+        .map(import_backwards_op)
+        .collect::<Result<ImVec<_>, _>>()?;
+
+    Ok(FriendState {
+        local_public_key: local_public_key.clone(),
+        remote_public_key,
+        remote_relays,
+        sent_local_relays: import_sent_local_relays(&friend_export.sent_local_relays)?,
+        name: friend_export.name.clone(),
+        rate: Rate {
+            mul: friend_export.rate.mul,
+            add: friend_export.rate.add,
+        },
+        status,
+        channel_status,
+        wanted_remote_max_debt: parse_u128(&friend_export.wanted_remote_max_debt)?,
+        wanted_local_requests_status: import_requests_status(
+            &friend_export.wanted_local_requests_status,
+        )?,
+        pending_requests: import_requests(&friend_export.pending_requests)?,
+        pending_backwards_ops,
+        pending_user_requests: import_requests(&friend_export.pending_user_requests)?,
+    })
+}
+
+fn export_receipt(receipt: &Receipt) -> ReceiptExport {
+    ReceiptExport {
+        response_hash: hash_result_to_string(&receipt.response_hash),
+        invoice_id: invoice_id_to_string(&receipt.invoice_id),
+        src_plain_lock: plain_lock_to_string(&receipt.src_plain_lock),
+        dest_plain_lock: plain_lock_to_string(&receipt.dest_plain_lock),
+        dest_payment: receipt.dest_payment.to_string(),
+        total_dest_payment: receipt.total_dest_payment.to_string(),
+        signature: signature_to_string(&receipt.signature),
+    }
+}
+
+fn import_receipt(receipt_export: &ReceiptExport) -> Result<Receipt, FunderImportError> {
+    Ok(Receipt {
+        response_hash: string_to_hash_result(&receipt_export.response_hash)?,
+        invoice_id: string_to_invoice_id(&receipt_export.invoice_id)?,
+        src_plain_lock: string_to_plain_lock(&receipt_export.src_plain_lock)?,
+        dest_plain_lock: string_to_plain_lock(&receipt_export.dest_plain_lock)?,
+        dest_payment: parse_u128(&receipt_export.dest_payment)?,
+        total_dest_payment: parse_u128(&receipt_export.total_dest_payment)?,
+        signature: string_to_signature(&receipt_export.signature)?,
+    })
+}
+
+fn export_payment_stage(payment: &Payment) -> PaymentStageExport {
+    match payment {
+        Payment::NewTransactions(new_transactions) => PaymentStageExport::NewTransactions {
+            num_transactions: new_transactions.num_transactions,
+            invoice_id: invoice_id_to_string(&new_transactions.invoice_id),
+            total_dest_payment: new_transactions.total_dest_payment.to_string(),
+            dest_public_key: public_key_to_string(&new_transactions.dest_public_key),
+            opt_dest_dh_public_key: new_transactions
+                .opt_dest_dh_public_key
+                .as_ref()
+                .map(dh_public_key_to_string),
+            opt_deadline_ticks: new_transactions.opt_deadline_ticks,
+        },
+        Payment::InProgress((num_transactions, opt_deadline_ticks)) => {
+            PaymentStageExport::InProgress {
+                num_transactions: *num_transactions,
+                opt_deadline_ticks: *opt_deadline_ticks,
+            }
+        }
+        Payment::Success((num_transactions, receipt, ack_uid)) => PaymentStageExport::Success {
+            num_transactions: *num_transactions,
+            receipt: export_receipt(receipt),
+            ack_uid: uid_to_string(ack_uid),
+        },
+        Payment::Canceled(ack_uid) => PaymentStageExport::Canceled {
+            ack_uid: uid_to_string(ack_uid),
+        },
+        Payment::AfterSuccessAck(num_transactions) => PaymentStageExport::AfterSuccessAck {
+            num_transactions: *num_transactions,
+        },
+        Payment::TimedOut((num_transactions, ack_uid)) => PaymentStageExport::TimedOut {
+            num_transactions: *num_transactions,
+            ack_uid: uid_to_string(ack_uid),
+        },
+        Payment::AfterTimeoutAck(num_transactions) => PaymentStageExport::AfterTimeoutAck {
+            num_transactions: *num_transactions,
+        },
+    }
+}
+
+fn import_payment_stage(stage: &PaymentStageExport) -> Result<Payment, FunderImportError> {
+    Ok(match stage {
+        PaymentStageExport::NewTransactions {
+            num_transactions,
+            invoice_id,
+            total_dest_payment,
+            dest_public_key,
+            opt_dest_dh_public_key,
+            opt_deadline_ticks,
+        } => Payment::NewTransactions(NewTransactions {
+            num_transactions: *num_transactions,
+            invoice_id: string_to_invoice_id(invoice_id)?,
+            total_dest_payment: parse_u128(total_dest_payment)?,
+            dest_public_key: string_to_public_key(dest_public_key)?,
+            opt_dest_dh_public_key: match opt_dest_dh_public_key {
+                Some(dest_dh_public_key) => Some(string_to_dh_public_key(dest_dh_public_key)?),
+                None => None,
+            },
+            opt_deadline_ticks: *opt_deadline_ticks,
+        }),
+        PaymentStageExport::InProgress {
+            num_transactions,
+            opt_deadline_ticks,
+        } => Payment::InProgress((*num_transactions, *opt_deadline_ticks)),
+        PaymentStageExport::Success {
+            num_transactions,
+            receipt,
+            ack_uid,
+        } => Payment::Success((
+            *num_transactions,
+            import_receipt(receipt)?,
+            string_to_uid(ack_uid)?,
+        )),
+        PaymentStageExport::Canceled { ack_uid } => Payment::Canceled(string_to_uid(ack_uid)?),
+        PaymentStageExport::AfterSuccessAck { num_transactions } => {
+            Payment::AfterSuccessAck(*num_transactions)
+        }
+        PaymentStageExport::TimedOut {
+            num_transactions,
+            ack_uid,
+        } => Payment::TimedOut((*num_transactions, string_to_uid(ack_uid)?)),
+        PaymentStageExport::AfterTimeoutAck { num_transactions } => {
+            Payment::AfterTimeoutAck(*num_transactions)
+        }
+    })
+}
+
+/// Convert a FunderState into its exported representation.
+/// Entries are sorted, so that exporting the same state twice results in the same output.
+pub fn export_funder_state(funder_state: &FunderState<NetAddress>) -> FunderStateExport {
+    let relays = funder_state.relays.iter().map(export_named_relay).collect();
+
+    let mut friends: Vec<_> = funder_state.friends.values().map(export_friend).collect();
+    friends.sort_by(|a, b| a.public_key.cmp(&b.public_key));
+
+    let mut open_invoices: Vec<_> = funder_state
+        .open_invoices
+        .iter()
+        .map(|(invoice_id, open_invoice)| {
+            let mut incoming_transactions: Vec<_> = open_invoice
+                .incoming_transactions
+                .values()
+                .map(|incoming_transaction| IncomingTransactionExport {
+                    request_id: uid_to_string(&incoming_transaction.request_id),
+                    dest_plain_lock: plain_lock_to_string(&incoming_transaction.dest_plain_lock),
+                    dest_payment: incoming_transaction.dest_payment.to_string(),
+                    opt_src_plain_lock: incoming_transaction
+                        .opt_src_plain_lock
+                        .as_ref()
+                        .map(plain_lock_to_string),
+                })
+                .collect();
+            incoming_transactions.sort_by(|a, b| a.request_id.cmp(&b.request_id));
+            OpenInvoiceExport {
+                invoice_id: invoice_id_to_string(invoice_id),
+                total_dest_payment: open_invoice.total_dest_payment.to_string(),
+                incoming_transactions,
+                opt_expiry_ticks: open_invoice.opt_expiry_ticks,
+                is_spontaneous: open_invoice.is_spontaneous,
+            }
+        })
+        .collect();
+    open_invoices.sort_by(|a, b| a.invoice_id.cmp(&b.invoice_id));
+
+    let mut open_transactions: Vec<_> = funder_state
+        .open_transactions
+        .iter()
+        .map(|(request_id, open_transaction)| OpenTransactionExport {
+            request_id: uid_to_string(request_id),
+            payment_id: payment_id_to_string(&open_transaction.payment_id),
+            src_plain_lock: plain_lock_to_string(&open_transaction.src_plain_lock),
+            opt_response: open_transaction
+                .opt_response
+                .as_ref()
+                .map(|response| ResponseExport {
+                    dest_hashed_lock: hashed_lock_to_string(&response.dest_hashed_lock),
+                    rand_nonce: rand_value_to_string(&response.rand_nonce),
+                    signature: signature_to_string(&response.signature),
+                }),
+        })
+        .collect();
+    open_transactions.sort_by(|a, b| a.request_id.cmp(&b.request_id));
+
+    let mut payments: Vec<_> = funder_state
+        .payments
+        .iter()
+        .map(|(payment_id, payment)| PaymentExport {
+            payment_id: payment_id_to_string(payment_id),
+            stage: export_payment_stage(payment),
+        })
+        .collect();
+    payments.sort_by(|a, b| a.payment_id.cmp(&b.payment_id));
+
+    FunderStateExport {
+        local_public_key: public_key_to_string(&funder_state.local_public_key),
+        relays,
+        friends,
+        open_invoices,
+        open_transactions,
+        payments,
+        accept_spontaneous_payments: funder_state.accept_spontaneous_payments,
+    }
+}
+
+/// Restore a FunderState from its exported representation.
+/// The export is validated: identifiers must be unique, and every friend's move tokens must
+/// belong to the pair of the local node and the friend.
+pub fn import_funder_state(
+    funder_state_export: &FunderStateExport,
+) -> Result<FunderState<NetAddress>, FunderImportError> {
+    let local_public_key = string_to_public_key(&funder_state_export.local_public_key)?;
+
+    let mut relays = ImVec::new();
+    let mut relay_public_keys = HashSet::new();
+    for relay_export in &funder_state_export.relays {
+        let named_relay = import_named_relay(relay_export)?;
+        if !relay_public_keys.insert(named_relay.public_key.clone()) {
+            return Err(FunderImportError::DuplicateRelay);
+        }
+        relays.push_back(named_relay);
+    }
+
+    let mut friends = ImHashMap::new();
+    for friend_export in &funder_state_export.friends {
+        let friend = import_friend(&local_public_key, friend_export)?;
+        if friends
+            .insert(friend.remote_public_key.clone(), friend)
+            .is_some()
+        {
+            return Err(FunderImportError::DuplicateFriend);
+        }
+    }
+
+    let mut open_invoices = ImHashMap::new();
+    for invoice_export in &funder_state_export.open_invoices {
+        let mut open_invoice = OpenInvoice::new(
+            parse_u128(&invoice_export.total_dest_payment)?,
+            invoice_export.opt_expiry_ticks,
+        );
+        open_invoice.is_spontaneous = invoice_export.is_spontaneous;
+        for incoming_export in &invoice_export.incoming_transactions {
+            let dest_plain_lock = string_to_plain_lock(&incoming_export.dest_plain_lock)?;
+            let incoming_transaction = IncomingTransaction {
+                request_id: string_to_uid(&incoming_export.request_id)?,
+                dest_plain_lock: dest_plain_lock.clone(),
+                dest_payment: parse_u128(&incoming_export.dest_payment)?,
+                opt_src_plain_lock: match &incoming_export.opt_src_plain_lock {
+                    Some(src_plain_lock) => Some(string_to_plain_lock(src_plain_lock)?),
+                    None => None,
+                },
+            };
+            if open_invoice
+                .incoming_transactions
+                .insert(dest_plain_lock.hash(), incoming_transaction)
+                .is_some()
+            {
+                return Err(FunderImportError::DuplicateIncomingTransaction);
+            }
+        }
+        let invoice_id = string_to_invoice_id(&invoice_export.invoice_id)?;
+        if open_invoices.insert(invoice_id, open_invoice).is_some() {
+            return Err(FunderImportError::DuplicateInvoice);
+        }
+    }
+
+    let mut payments = ImHashMap::new();
+    for payment_export in &funder_state_export.payments {
+        let payment_id = string_to_payment_id(&payment_export.payment_id)?;
+        let payment = import_payment_stage(&payment_export.stage)?;
+        if payments.insert(payment_id, payment).is_some() {
+            return Err(FunderImportError::DuplicatePayment);
+        }
+    }
+
+    let mut open_transactions = ImHashMap::new();
+    for transaction_export in &funder_state_export.open_transactions {
+        let payment_id = string_to_payment_id(&transaction_export.payment_id)?;
+        if !payments.contains_key(&payment_id) {
+            return Err(FunderImportError::UnknownPayment);
+        }
+        let request_id = string_to_uid(&transaction_export.request_id)?;
+        let opt_response = match &transaction_export.opt_response {
+            Some(response_export) => Some(ResponseSendFundsOp {
+                request_id,
+                dest_hashed_lock: string_to_hashed_lock(&response_export.dest_hashed_lock)?,
+                rand_nonce: string_to_rand_value(&response_export.rand_nonce)?,
+                signature: string_to_signature(&response_export.signature)?,
+            }),
+            None => None,
+        };
+        let open_transaction = OpenTransaction {
+            payment_id,
+            src_plain_lock: string_to_plain_lock(&transaction_export.src_plain_lock)?,
+            opt_response,
+        };
+        if open_transactions
+            .insert(request_id, open_transaction)
+            .is_some()
+        {
+            return Err(FunderImportError::DuplicateTransaction);
+        }
+    }
+
+    Ok(FunderState {
+        local_public_key,
+        relays,
+        friends,
+        open_invoices,
+        open_transactions,
+        payments,
+        accept_spontaneous_payments: funder_state_export.accept_spontaneous_payments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crypto::hash_lock::{PlainLock, PLAIN_LOCK_LEN};
+    use crypto::identity::PUBLIC_KEY_LEN;
+    use crypto::invoice_id::{InvoiceId, INVOICE_ID_LEN};
+    use crypto::payment_id::{PaymentId, PAYMENT_ID_LEN};
+    use crypto::uid::{Uid, UID_LEN};
+
+    use proto::funder::messages::AddFriend;
+
+    use crate::friend::FriendMutation;
+    use crate::state::FunderMutation;
+
+    fn dummy_funder_state() -> FunderState<NetAddress> {
+        let local_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let relay = NamedRelayAddress {
+            public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+            address: "relay.example.com:1337".to_owned().try_into().unwrap(),
+            name: "relay".to_owned(),
+        };
+        let mut funder_state = FunderState::new(local_public_key.clone(), vec![relay]);
+
+        let add_friend = AddFriend {
+            friend_public_key: PublicKey::from(&[0xcc; PUBLIC_KEY_LEN]),
+            relays: Vec::new(),
+            name: "friend".to_owned(),
+            balance: -5,
+        };
+        funder_state.mutate(&FunderMutation::AddFriend(add_friend));
+
+        let cancel_send_funds = CancelSendFundsOp {
+            request_id: Uid::from(&[4; UID_LEN]),
+            reporting_public_key: local_public_key.clone(),
+            reason: FailureReason::NoCapacity,
+        };
+        funder_state.mutate(&FunderMutation::FriendMutation((
+            PublicKey::from(&[0xcc; PUBLIC_KEY_LEN]),
+            FriendMutation::PushBackPendingBackwardsOp(BackwardsOp::Cancel(cancel_send_funds)),
+        )));
+
+        funder_state.mutate(&FunderMutation::AddInvoice((
+            InvoiceId::from(&[1; INVOICE_ID_LEN]),
+            100,
+            Some(20),
+        )));
+
+        funder_state.mutate(&FunderMutation::AddSpontaneousInvoice((
+            InvoiceId::from(&[5; INVOICE_ID_LEN]),
+            30,
+        )));
+        funder_state.mutate(&FunderMutation::AddIncomingTransaction((
+            InvoiceId::from(&[5; INVOICE_ID_LEN]),
+            Uid::from(&[6; UID_LEN]),
+            PlainLock::from(&[7; PLAIN_LOCK_LEN]),
+            10,
+            Some(PlainLock::from(&[8; PLAIN_LOCK_LEN])),
+        )));
+
+        let payment_id = PaymentId::from(&[2; PAYMENT_ID_LEN]);
+        funder_state.mutate(&FunderMutation::UpdatePayment((
+            payment_id,
+            Payment::Canceled(Uid::from(&[3; UID_LEN])),
+        )));
+
+        funder_state.mutate(&FunderMutation::SetAcceptSpontaneousPayments(true));
+        funder_state
+    }
+
+    #[test]
+    fn test_export_import_funder_state() {
+        let funder_state = dummy_funder_state();
+        let funder_state_export = export_funder_state(&funder_state);
+        assert_eq!(funder_state_export.friends.len(), 1);
+        let mutual_credit = match &funder_state_export.friends[0].channel_status {
+            ChannelStatusExport::Incoming { mutual_credit, .. }
+            | ChannelStatusExport::Outgoing { mutual_credit, .. } => mutual_credit,
+            ChannelStatusExport::Inconsistent { .. } => unreachable!(),
+        };
+        assert_eq!(mutual_credit.balance.balance, "-5");
+        assert_eq!(
+            funder_state_export.friends[0].pending_backwards_ops,
+            vec![BackwardsOpExport::Cancel(CancelSendFundsExport {
+                request_id: uid_to_string(&Uid::from(&[4; UID_LEN])),
+                reporting_public_key: public_key_to_string(&PublicKey::from(
+                    &[0xaa; PUBLIC_KEY_LEN]
+                )),
+                reason: "no_capacity".to_owned(),
+            })]
+        );
+
+        // Exporting through JSON and back should not lose any information:
+        let json = serde_json::to_string_pretty(&funder_state_export).unwrap();
+        let funder_state_export2: FunderStateExport = serde_json::from_str(&json).unwrap();
+        let funder_state2 = import_funder_state(&funder_state_export2).unwrap();
+        assert_eq!(export_funder_state(&funder_state2), funder_state_export);
+    }
+
+    #[test]
+    fn test_import_funder_state_validation() {
+        let funder_state_export = export_funder_state(&dummy_funder_state());
+
+        // Invalid failure reason:
+        let mut export = funder_state_export.clone();
+        export.friends[0].pending_backwards_ops[0] =
+            BackwardsOpExport::Cancel(CancelSendFundsExport {
+                request_id: uid_to_string(&Uid::from(&[4; UID_LEN])),
+                reporting_public_key: export.local_public_key.clone(),
+                reason: "invalid".to_owned(),
+            });
+        match import_funder_state(&export) {
+            Err(FunderImportError::InvalidFailureReason) => {}
+            _ => unreachable!(),
+        };
+
+        // Duplicate friend:
+        let mut export = funder_state_export.clone();
+        let friend = export.friends[0].clone();
+        export.friends.push(friend);
+        match import_funder_state(&export) {
+            Err(FunderImportError::DuplicateFriend) => {}
+            _ => unreachable!(),
+        };
+
+        // Invalid public key:
+        let mut export = funder_state_export.clone();
+        export.local_public_key = "invalid".to_owned();
+        match import_funder_state(&export) {
+            Err(FunderImportError::SerStringError) => {}
+            _ => unreachable!(),
+        };
+
+        // Protocol state of a different friend:
+        let mut export = funder_state_export.clone();
+        export.friends[0].public_key =
+            public_key_to_string(&PublicKey::from(&[0xdd; PUBLIC_KEY_LEN]));
+        match import_funder_state(&export) {
+            Err(FunderImportError::ProtocolStateMismatch) => {}
+            _ => unreachable!(),
+        };
+    }
+}
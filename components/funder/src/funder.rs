@@ -2,7 +2,7 @@ use std::fmt::Debug;
 
 use futures::channel::mpsc;
 use futures::stream::select;
-use futures::{future, stream, SinkExt, StreamExt};
+use futures::{future, stream, SinkExt, Stream, StreamExt};
 
 use common::canonical_serialize::CanonicalSerialize;
 
@@ -23,6 +23,7 @@ use crate::types::{FunderIncoming, FunderIncomingComm, FunderOutgoingComm};
 pub enum FunderError {
     IncomingControlClosed,
     IncomingCommClosed,
+    IncomingTimerClosed,
     IncomingMessagesError,
     DbError,
     SendControlError,
@@ -34,13 +35,15 @@ pub enum FunderEvent<B> {
     FunderIncoming(FunderIncoming<B>),
     IncomingControlClosed,
     IncomingCommClosed,
+    IncomingTimerClosed,
 }
 
-pub async fn inner_funder_loop<B, R>(
+pub async fn inner_funder_loop<B, R, T>(
     mut identity_client: IdentityClient,
     rng: R,
     incoming_control: mpsc::Receiver<FunderIncomingControl<B>>,
     incoming_comm: mpsc::Receiver<FunderIncomingComm<B>>,
+    incoming_timer: T,
     control_sender: mpsc::Sender<FunderOutgoingControl<B>>,
     comm_sender: mpsc::Sender<FunderOutgoingComm<B>>,
     mut funder_state: FunderState<B>,
@@ -48,11 +51,13 @@ pub async fn inner_funder_loop<B, R>(
     max_operations_in_batch: usize,
     max_node_relays: usize,
     max_pending_user_requests: usize,
+    payment_timeout_ticks: usize,
     mut opt_event_sender: Option<mpsc::Sender<FunderEvent<B>>>,
 ) -> Result<(), FunderError>
 where
     B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
     R: CryptoRandom + 'static,
+    T: Stream + Unpin,
 {
     // Transform error type:
     let mut comm_sender = comm_sender.sink_map_err(|_| ());
@@ -74,11 +79,19 @@ where
             FunderEvent::FunderIncoming(FunderIncoming::Comm(incoming_comm_msg))
         })
         .chain(stream::once(future::ready(FunderEvent::IncomingCommClosed)));
+    let incoming_timer = incoming_timer
+        .map(|_| FunderEvent::FunderIncoming(FunderIncoming::TimerTick))
+        .chain(stream::once(future::ready(
+            FunderEvent::IncomingTimerClosed,
+        )));
     // Chain the Init message first:
     let mut incoming_messages = stream::once(future::ready(FunderEvent::FunderIncoming(
         FunderIncoming::Init,
     )))
-    .chain(select(incoming_control, incoming_comm));
+    .chain(select(
+        incoming_control,
+        select(incoming_comm, incoming_timer),
+    ));
 
     while let Some(funder_event) = await!(incoming_messages.next()) {
         // For testing:
@@ -86,6 +99,7 @@ where
         let funder_incoming = match funder_event.clone() {
             FunderEvent::IncomingControlClosed => return Err(FunderError::IncomingControlClosed),
             FunderEvent::IncomingCommClosed => return Err(FunderError::IncomingCommClosed),
+            FunderEvent::IncomingTimerClosed => return Err(FunderError::IncomingTimerClosed),
             FunderEvent::FunderIncoming(funder_incoming) => funder_incoming,
         };
 
@@ -97,6 +111,7 @@ where
             max_node_relays,
             max_operations_in_batch,
             max_pending_user_requests,
+            payment_timeout_ticks,
             funder_incoming
         ));
 
@@ -141,28 +156,32 @@ where
     Ok(())
 }
 
-pub async fn funder_loop<B, R>(
+pub async fn funder_loop<B, R, T>(
     identity_client: IdentityClient,
     rng: R,
     incoming_control: mpsc::Receiver<FunderIncomingControl<B>>,
     incoming_comm: mpsc::Receiver<FunderIncomingComm<B>>,
+    incoming_timer: T,
     control_sender: mpsc::Sender<FunderOutgoingControl<B>>,
     comm_sender: mpsc::Sender<FunderOutgoingComm<B>>,
     max_operations_in_batch: usize,
     max_node_relays: usize,
     max_pending_user_requests: usize,
+    payment_timeout_ticks: usize,
     funder_state: FunderState<B>,
     db_client: DatabaseClient<FunderMutation<B>>,
 ) -> Result<(), FunderError>
 where
     B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
     R: CryptoRandom + 'static,
+    T: Stream + Unpin,
 {
     await!(inner_funder_loop(
         identity_client,
         rng,
         incoming_control,
         incoming_comm,
+        incoming_timer,
         control_sender,
         comm_sender,
         funder_state,
@@ -170,6 +189,7 @@ where
         max_operations_in_batch,
         max_node_relays,
         max_pending_user_requests,
+        payment_timeout_ticks,
         None
     ))
 }
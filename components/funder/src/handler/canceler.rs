@@ -6,7 +6,9 @@ use crypto::crypto_rand::CryptoRandom;
 use crypto::identity::PublicKey;
 use crypto::uid::Uid;
 
-use proto::funder::messages::{FunderOutgoingControl, RequestResult, TransactionResult};
+use proto::funder::messages::{
+    FailureReason, FunderOutgoingControl, RequestFailure, RequestResult, TransactionResult,
+};
 
 use crate::handler::sender::SendCommands;
 use crate::handler::state_wrap::MutableFunderState;
@@ -16,16 +18,35 @@ use crate::friend::{BackwardsOp, ChannelStatus, FriendMutation};
 use crate::state::{FunderMutation, Payment};
 use crate::types::{create_cancel_send_funds, create_pending_transaction};
 
+/// Create a failure result for a request that failed at the local node.
+pub fn local_request_failure<B>(
+    m_state: &MutableFunderState<B>,
+    reason: FailureReason,
+) -> RequestResult
+where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+{
+    RequestResult::Failure(RequestFailure {
+        reporting_public_key: m_state.state().local_public_key.clone(),
+        reason,
+    })
+}
+
 /// Reply to a single request message with a cancellation.
 pub fn reply_with_cancel<B>(
     m_state: &mut MutableFunderState<B>,
     send_commands: &mut SendCommands,
     remote_public_key: &PublicKey,
     request_id: &Uid,
+    reason: FailureReason,
 ) where
     B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
 {
-    let cancel_send_funds = create_cancel_send_funds(*request_id);
+    let cancel_send_funds = create_cancel_send_funds(
+        *request_id,
+        m_state.state().local_public_key.clone(),
+        reason,
+    );
     let friend_mutation =
         FriendMutation::PushBackPendingBackwardsOp(BackwardsOp::Cancel(cancel_send_funds));
     let funder_mutation =
@@ -70,10 +91,13 @@ where
                 new_transactions.num_transactions.checked_sub(1).unwrap();
             Some(Payment::NewTransactions(new_new_transactions))
         }
-        Payment::InProgress(num_transactions) => {
+        Payment::InProgress((num_transactions, opt_deadline_ticks)) => {
             let new_num_transactions = num_transactions.checked_sub(1).unwrap();
             if new_num_transactions > 0 {
-                Some(Payment::InProgress(new_num_transactions))
+                Some(Payment::InProgress((
+                    new_num_transactions,
+                    opt_deadline_ticks,
+                )))
             } else {
                 let ack_uid = Uid::new(rng);
                 Some(Payment::Canceled(ack_uid))
@@ -90,6 +114,10 @@ where
         Payment::Canceled(_) => {
             unreachable!();
         }
+        Payment::TimedOut((num_transactions, ack_uid)) => {
+            let new_num_transactions = num_transactions.checked_sub(1).unwrap();
+            Some(Payment::TimedOut((new_num_transactions, ack_uid)))
+        }
         Payment::AfterSuccessAck(num_transactions) => {
             let new_num_transactions = num_transactions.checked_sub(1).unwrap();
             if new_num_transactions > 0 {
@@ -98,6 +126,14 @@ where
                 None
             }
         }
+        Payment::AfterTimeoutAck(num_transactions) => {
+            let new_num_transactions = num_transactions.checked_sub(1).unwrap();
+            if new_num_transactions > 0 {
+                Some(Payment::AfterTimeoutAck(new_num_transactions))
+            } else {
+                None
+            }
+        }
     };
 
     let funder_mutation = if let Some(new_payment) = opt_new_payment {
@@ -146,8 +182,11 @@ pub fn cancel_local_pending_transactions<B, R>(
             Some(origin_public_key) => {
                 // We have found the friend that is the origin of this request.
                 // We send him a cancel message.
-                let cancel_send_funds =
-                    create_cancel_send_funds(pending_local_transaction.request_id);
+                let cancel_send_funds = create_cancel_send_funds(
+                    pending_local_transaction.request_id,
+                    m_state.state().local_public_key.clone(),
+                    FailureReason::ChannelReset,
+                );
                 let friend_mutation = FriendMutation::PushBackPendingBackwardsOp(
                     BackwardsOp::Cancel(cancel_send_funds),
                 );
@@ -161,7 +200,7 @@ pub fn cancel_local_pending_transactions<B, R>(
                 // We send a cancel message through the control:
                 let transaction_result = TransactionResult {
                     request_id: pending_local_transaction.request_id,
-                    result: RequestResult::Failure,
+                    result: local_request_failure(m_state, FailureReason::ChannelReset),
                 };
                 outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
                 remove_transaction(m_state, rng, &pending_local_transaction.request_id);
@@ -178,6 +217,7 @@ pub fn cancel_pending_requests<B, R>(
     outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
     rng: &R,
     friend_public_key: &PublicKey,
+    reason: FailureReason,
 ) where
     B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
     R: CryptoRandom,
@@ -196,8 +236,11 @@ pub fn cancel_pending_requests<B, R>(
         match opt_origin_public_key {
             Some(origin_public_key) => {
                 let pending_local_transaction = create_pending_transaction(&pending_request);
-                let cancel_send_funds =
-                    create_cancel_send_funds(pending_local_transaction.request_id);
+                let cancel_send_funds = create_cancel_send_funds(
+                    pending_local_transaction.request_id,
+                    m_state.state().local_public_key.clone(),
+                    reason.clone(),
+                );
                 let friend_mutation = FriendMutation::PushBackPendingBackwardsOp(
                     BackwardsOp::Cancel(cancel_send_funds),
                 );
@@ -210,7 +253,7 @@ pub fn cancel_pending_requests<B, R>(
                 // We are the origin of this request:
                 let transaction_result = TransactionResult {
                     request_id: pending_request.request_id,
-                    result: RequestResult::Failure,
+                    result: local_request_failure(m_state, reason.clone()),
                 };
                 outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
                 remove_transaction(m_state, rng, &pending_request.request_id);
@@ -226,6 +269,7 @@ pub fn cancel_pending_user_requests<B, R>(
     outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
     rng: &R,
     friend_public_key: &PublicKey,
+    reason: FailureReason,
 ) where
     B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
     R: CryptoRandom,
@@ -242,7 +286,7 @@ pub fn cancel_pending_user_requests<B, R>(
         // We are the origin of this request:
         let transaction_result = TransactionResult {
             request_id: pending_user_request.request_id,
-            result: RequestResult::Failure,
+            result: local_request_failure(m_state, reason.clone()),
         };
         outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
         remove_transaction(m_state, rng, &pending_user_request.request_id);
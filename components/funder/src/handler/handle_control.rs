@@ -3,6 +3,7 @@ use std::fmt::Debug;
 use common::canonical_serialize::CanonicalSerialize;
 
 use crypto::crypto_rand::CryptoRandom;
+use crypto::dh::encrypt_to_dh_public_key;
 use crypto::hash_lock::PlainLock;
 use crypto::identity::PublicKey;
 use crypto::invoice_id::InvoiceId;
@@ -13,23 +14,28 @@ use crate::friend::{BackwardsOp, ChannelStatus, FriendMutation};
 use crate::state::{FunderMutation, NewTransactions, Payment};
 
 use proto::app_server::messages::{NamedRelayAddress, RelayAddress};
+use proto::consts::MAX_ROUTE_LEN;
 use proto::funder::messages::{
     AckClosePayment, AddFriend, AddInvoice, ChannelerUpdateFriend, CollectSendFundsOp,
-    CreatePayment, CreateTransaction, FriendStatus, FunderControl, FunderOutgoingControl,
-    MultiCommit, PaymentStatus, RemoveFriend, RequestResult, RequestSendFundsOp,
-    ResetFriendChannel, ResponseClosePayment, SetFriendName, SetFriendRate, SetFriendRelays,
-    SetFriendRemoteMaxDebt, SetFriendStatus, SetRequestsStatus, TransactionResult,
+    CreatePayment, CreateRebalance, CreateTransaction, FailureReason, FriendStatus, FunderControl,
+    FunderOutgoingControl, InvoiceDetails, InvoiceStatus, MultiCommit, OpenInvoiceStatus,
+    PaymentStatus, RemoveFriend, RequestResult, RequestSendFundsOp, ResetFriendChannel,
+    ResponseClosePayment, ResponseInvoiceStatus, ResponseSignInvoice, SetFriendName, SetFriendRate,
+    SetFriendRelays, SetFriendRemoteMaxDebt, SetFriendStatus, SetRequestsStatus, TransactionResult,
 };
 use proto::funder::signature_buff::{prepare_commit, verify_multi_commit};
 
 use crate::ephemeral::Ephemeral;
 use crate::handler::canceler::{
     cancel_local_pending_transactions, cancel_pending_requests, cancel_pending_user_requests,
-    reply_with_cancel,
+    local_request_failure, reply_with_cancel,
 };
 use crate::handler::sender::SendCommands;
 use crate::handler::state_wrap::{MutableEphemeral, MutableFunderState};
-use crate::handler::utils::{find_local_pending_transaction, find_request_origin, is_friend_ready};
+use crate::handler::utils::{
+    find_local_pending_transaction, find_remote_request_friend, friend_not_ready_reason,
+    invoice_collected_dest_payment, invoice_transactions, is_friend_ready, is_payment_timed_out,
+};
 
 use crate::types::ChannelerConfig;
 
@@ -54,6 +60,8 @@ pub enum HandleControlError {
     InvoiceAlreadyExists,
     InvoiceDoesNotExist,
     InvalidMultiCommit,
+    NotCircularRoute,
+    InvalidDestDhPublicKey,
 }
 
 fn control_set_friend_remote_max_debt<B>(
@@ -166,9 +174,16 @@ fn disable_friend<B, R>(
         outgoing_control,
         rng,
         friend_public_key,
+        FailureReason::FriendNotReady,
     );
 
-    cancel_pending_user_requests(m_state, outgoing_control, rng, friend_public_key);
+    cancel_pending_user_requests(
+        m_state,
+        outgoing_control,
+        rng,
+        friend_public_key,
+        FailureReason::FriendNotReady,
+    );
 
     // Notify Channeler:
     let channeler_config = ChannelerConfig::RemoveFriend(friend_public_key.clone());
@@ -245,6 +260,20 @@ fn control_remove_relay<B>(
     }
 }
 
+fn control_set_accept_spontaneous_payments<B>(
+    m_state: &mut MutableFunderState<B>,
+    accept_spontaneous_payments: bool,
+) where
+    B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
+{
+    if m_state.state().accept_spontaneous_payments == accept_spontaneous_payments {
+        // Nothing to change:
+        return;
+    }
+    let funder_mutation = FunderMutation::SetAcceptSpontaneousPayments(accept_spontaneous_payments);
+    m_state.mutate(funder_mutation);
+}
+
 fn control_add_friend<B>(m_state: &mut MutableFunderState<B>, add_friend: AddFriend<B>)
 where
     B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
@@ -502,6 +531,8 @@ where
         invoice_id: create_payment.invoice_id.clone(),
         total_dest_payment: create_payment.total_dest_payment,
         dest_public_key: create_payment.dest_public_key.clone(),
+        opt_dest_dh_public_key: create_payment.opt_dest_dh_public_key.clone(),
+        opt_deadline_ticks: create_payment.opt_deadline_ticks,
     });
 
     // Add a new payment entry:
@@ -601,6 +632,17 @@ where
     // Randomly generate a new PlainLock:
     let src_plain_lock = PlainLock::new(rng);
 
+    // A spontaneous payment has no Commit step, therefore we hand out the plain lock
+    // to the destination right away. The plain lock is encrypted to the destination's DH public
+    // key, so that the mediators on the route can not read it:
+    let opt_encrypted_src_plain_lock = match &new_transactions.opt_dest_dh_public_key {
+        Some(dest_dh_public_key) => Some(
+            encrypt_to_dh_public_key(&src_plain_lock, dest_dh_public_key, rng)
+                .map_err(|_| HandleControlError::InvalidDestDhPublicKey)?,
+        ),
+        None => None,
+    };
+
     // Keep PlainLock:
     let funder_mutation = FunderMutation::AddTransaction((
         create_transaction.request_id,
@@ -624,6 +666,7 @@ where
     let request_send_funds = RequestSendFundsOp {
         request_id: create_transaction.request_id,
         src_hashed_lock: src_plain_lock.hash(),
+        opt_encrypted_src_plain_lock,
         route: create_transaction.route,
         dest_payment: create_transaction.dest_payment,
         total_dest_payment: new_transactions.total_dest_payment,
@@ -641,6 +684,39 @@ where
     Ok(())
 }
 
+/// Explain the failure of `control_create_transaction_inner()` to the user.
+fn create_transaction_failure_reason<B>(
+    m_state: &MutableFunderState<B>,
+    ephemeral: &Ephemeral,
+    create_transaction: &CreateTransaction,
+    e: HandleControlError,
+) -> FailureReason
+where
+    B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
+{
+    match e {
+        HandleControlError::InvalidRoute if create_transaction.route.len() > MAX_ROUTE_LEN => {
+            FailureReason::RouteTooLong
+        }
+        HandleControlError::NotFirstInRoute
+        | HandleControlError::PaymentDestNotLastInRoute
+        | HandleControlError::InvalidRoute
+        | HandleControlError::NotCircularRoute => FailureReason::InvalidRoute,
+        HandleControlError::FriendDoesNotExist => FailureReason::FriendNotReady,
+        HandleControlError::FriendNotReady => friend_not_ready_reason(
+            m_state.state(),
+            ephemeral,
+            &create_transaction.route.public_keys[1],
+        ),
+        HandleControlError::NewTransactionsNotAllowed
+            if is_payment_timed_out(m_state.state(), &create_transaction.payment_id) =>
+        {
+            FailureReason::TimedOut
+        }
+        _ => FailureReason::Rejected,
+    }
+}
+
 fn control_create_transaction<B, R>(
     m_state: &mut MutableFunderState<B>,
     ephemeral: &Ephemeral,
@@ -655,6 +731,7 @@ where
     R: CryptoRandom,
 {
     // If we already have this transaction:
+    // - If the payment has timed out, we return a failure.
     // - If we have a ready response, we return a Commit message.
     // - Else, we do nothing.
     //
@@ -665,7 +742,13 @@ where
         .open_transactions
         .get(&create_transaction.request_id)
     {
-        if let (Some(response_send_funds), Some(pending_transaction)) = (
+        if is_payment_timed_out(m_state.state(), &open_transaction.payment_id) {
+            let transaction_result = TransactionResult {
+                request_id: create_transaction.request_id,
+                result: local_request_failure(m_state, FailureReason::TimedOut),
+            };
+            outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
+        } else if let (Some(response_send_funds), Some(pending_transaction)) = (
             &open_transaction.opt_response,
             find_local_pending_transaction(m_state.state(), &create_transaction.request_id),
         ) {
@@ -695,9 +778,10 @@ where
         create_transaction.clone(),
     ) {
         error!("control_create_transaction_inner() failed: {:?}", e);
+        let reason = create_transaction_failure_reason(m_state, ephemeral, &create_transaction, e);
         let transaction_result = TransactionResult {
             request_id: create_transaction.request_id,
-            result: RequestResult::Failure,
+            result: local_request_failure(m_state, reason),
         };
 
         outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
@@ -736,7 +820,10 @@ where
         Payment::NewTransactions(new_transactions) => {
             if new_transactions.num_transactions > 0 {
                 (
-                    Some(Payment::InProgress(new_transactions.num_transactions)),
+                    Some(Payment::InProgress((
+                        new_transactions.num_transactions,
+                        new_transactions.opt_deadline_ticks,
+                    ))),
                     PaymentStatus::InProgress,
                 )
             } else {
@@ -747,7 +834,7 @@ where
                 )
             }
         }
-        Payment::InProgress(num_transactions) => {
+        Payment::InProgress((num_transactions, opt_deadline_ticks)) => {
             (if *num_transactions == 0 {
                 let ack_uid = Uid::new(rng);
                 (
@@ -756,7 +843,10 @@ where
                 )
             } else {
                 (
-                    Some(Payment::InProgress(*num_transactions)),
+                    Some(Payment::InProgress((
+                        *num_transactions,
+                        *opt_deadline_ticks,
+                    ))),
                     PaymentStatus::InProgress,
                 )
             })
@@ -777,6 +867,15 @@ where
             Some(Payment::AfterSuccessAck(*num_transactions)),
             PaymentStatus::PaymentNotFound,
         ),
+        // The remaining transactions (if any) are resolved in the background:
+        Payment::TimedOut((num_transactions, ack_uid)) => (
+            Some(Payment::TimedOut((*num_transactions, *ack_uid))),
+            PaymentStatus::TimedOut(*ack_uid),
+        ),
+        Payment::AfterTimeoutAck(num_transactions) => (
+            Some(Payment::AfterTimeoutAck(*num_transactions)),
+            PaymentStatus::PaymentNotFound,
+        ),
     };
 
     // Send back a ResponseClosePayment:
@@ -822,9 +921,10 @@ where
         .clone();
 
     match payment {
-        Payment::NewTransactions(_) | Payment::InProgress(_) | Payment::AfterSuccessAck(_) => {
-            return Err(HandleControlError::AckStateInvalid)
-        }
+        Payment::NewTransactions(_)
+        | Payment::InProgress(_)
+        | Payment::AfterSuccessAck(_)
+        | Payment::AfterTimeoutAck(_) => return Err(HandleControlError::AckStateInvalid),
         Payment::Success((num_transactions, _receipt, ack_uid)) => {
             // Make sure that ack matches:
             if ack_close_payment.ack_uid != ack_uid {
@@ -853,11 +953,182 @@ where
             let funder_mutation = FunderMutation::RemovePayment(ack_close_payment.payment_id);
             m_state.mutate(funder_mutation);
         }
+        Payment::TimedOut((num_transactions, ack_uid)) => {
+            // Make sure that ack matches:
+            if ack_close_payment.ack_uid != ack_uid {
+                return Err(HandleControlError::AckMismatch);
+            }
+
+            if num_transactions > 0 {
+                // Wait for the remaining transactions in the background:
+                let new_payment = Payment::AfterTimeoutAck(num_transactions);
+                let funder_mutation =
+                    FunderMutation::UpdatePayment((ack_close_payment.payment_id, new_payment));
+                m_state.mutate(funder_mutation);
+            } else {
+                // Remove payment (no pending transactions):
+                let funder_mutation = FunderMutation::RemovePayment(ack_close_payment.payment_id);
+                m_state.mutate(funder_mutation);
+            }
+        }
+    };
+
+    Ok(())
+}
+
+fn control_create_rebalance_inner<B, R>(
+    m_state: &mut MutableFunderState<B>,
+    ephemeral: &Ephemeral,
+    send_commands: &mut SendCommands,
+    rng: &R,
+    max_pending_user_requests: usize,
+    create_rebalance: CreateRebalance,
+) -> Result<(), HandleControlError>
+where
+    B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
+    R: CryptoRandom,
+{
+    let local_public_key = m_state.state().local_public_key.clone();
+    let public_keys = &create_rebalance.route.public_keys;
+
+    // The route must leave through one friend and come back through another friend:
+    // local -- A -- ... -- B -- local
+    if public_keys.len() < 4 {
+        return Err(HandleControlError::NotCircularRoute);
+    }
+    match public_keys.last() {
+        Some(last) if *last == local_public_key => Ok(()),
+        _ => Err(HandleControlError::NotCircularRoute),
+    }?;
+    let in_friend_public_key = &public_keys[public_keys.len() - 2];
+    if *in_friend_public_key == public_keys[1] {
+        return Err(HandleControlError::NotCircularRoute);
+    }
+    if !m_state.state().friends.contains_key(in_friend_public_key) {
+        return Err(HandleControlError::FriendDoesNotExist);
+    }
+
+    // Open an invoice for the credits we are going to receive:
+    control_add_invoice(
+        m_state,
+        AddInvoice {
+            invoice_id: create_rebalance.invoice_id.clone(),
+            total_dest_payment: create_rebalance.dest_payment,
+            opt_expiry_ticks: None,
+        },
+    )?;
+
+    // Pay ourselves:
+    let create_payment = CreatePayment {
+        payment_id: create_rebalance.payment_id,
+        invoice_id: create_rebalance.invoice_id.clone(),
+        total_dest_payment: create_rebalance.dest_payment,
+        dest_public_key: local_public_key,
+        opt_dest_dh_public_key: None,
+        opt_deadline_ticks: None,
     };
+    if let Err(e) = control_create_payment(m_state, create_payment) {
+        let funder_mutation = FunderMutation::RemoveInvoice(create_rebalance.invoice_id);
+        m_state.mutate(funder_mutation);
+        return Err(e);
+    }
+
+    let create_transaction = CreateTransaction {
+        payment_id: create_rebalance.payment_id,
+        request_id: create_rebalance.request_id,
+        route: create_rebalance.route,
+        dest_payment: create_rebalance.dest_payment,
+        fees: create_rebalance.fees,
+    };
+    if let Err(e) = control_create_transaction_inner(
+        m_state,
+        ephemeral,
+        send_commands,
+        rng,
+        max_pending_user_requests,
+        create_transaction,
+    ) {
+        let funder_mutation = FunderMutation::RemovePayment(create_rebalance.payment_id);
+        m_state.mutate(funder_mutation);
+        let funder_mutation = FunderMutation::RemoveInvoice(create_rebalance.invoice_id);
+        m_state.mutate(funder_mutation);
+        return Err(e);
+    }
+
+    // No more transactions are going to be added to this payment. When the transaction is done,
+    // the payment could be closed by the user, as with any other payment.
+    let funder_mutation = FunderMutation::UpdatePayment((
+        create_rebalance.payment_id,
+        Payment::InProgress((1, None)),
+    ));
+    m_state.mutate(funder_mutation);
 
     Ok(())
 }
 
+/// Move credits between two friends, by paying ourselves along a circular route.
+/// We are both the buyer and the seller of this payment, so the invoice is committed
+/// automatically once a response arrives.
+fn control_create_rebalance<B, R>(
+    m_state: &mut MutableFunderState<B>,
+    ephemeral: &Ephemeral,
+    outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
+    send_commands: &mut SendCommands,
+    rng: &R,
+    max_pending_user_requests: usize,
+    create_rebalance: CreateRebalance,
+) -> Result<(), HandleControlError>
+where
+    B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
+    R: CryptoRandom,
+{
+    let create_transaction = CreateTransaction {
+        payment_id: create_rebalance.payment_id,
+        request_id: create_rebalance.request_id,
+        route: create_rebalance.route.clone(),
+        dest_payment: create_rebalance.dest_payment,
+        fees: create_rebalance.fees,
+    };
+
+    // The user might resend this request after a disconnection. We handle this case like a
+    // resent CreateTransaction:
+    if m_state
+        .state()
+        .open_transactions
+        .contains_key(&create_rebalance.request_id)
+    {
+        return control_create_transaction(
+            m_state,
+            ephemeral,
+            outgoing_control,
+            send_commands,
+            rng,
+            max_pending_user_requests,
+            create_transaction,
+        );
+    }
+
+    if let Err(e) = control_create_rebalance_inner(
+        m_state,
+        ephemeral,
+        send_commands,
+        rng,
+        max_pending_user_requests,
+        create_rebalance,
+    ) {
+        error!("control_create_rebalance_inner() failed: {:?}", e);
+        let reason = create_transaction_failure_reason(m_state, ephemeral, &create_transaction, e);
+        let transaction_result = TransactionResult {
+            request_id: create_transaction.request_id,
+            result: local_request_failure(m_state, reason),
+        };
+        outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
+    }
+
+    // As with CreateTransaction, the user always gets a TransactionResult.
+    Ok(())
+}
+
 fn control_add_invoice<B>(
     m_state: &mut MutableFunderState<B>,
     add_invoice: AddInvoice,
@@ -874,14 +1145,96 @@ where
     }
 
     // Add new invoice:
-    let funder_mutation =
-        FunderMutation::AddInvoice((add_invoice.invoice_id, add_invoice.total_dest_payment));
+    let funder_mutation = FunderMutation::AddInvoice((
+        add_invoice.invoice_id,
+        add_invoice.total_dest_payment,
+        add_invoice.opt_expiry_ticks,
+    ));
     m_state.mutate(funder_mutation);
 
     Ok(())
 }
 
-fn control_cancel_invoice<B>(
+fn control_request_invoice_status<B>(
+    m_state: &MutableFunderState<B>,
+    ephemeral: &Ephemeral,
+    outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
+    invoice_id: InvoiceId,
+) -> Result<(), HandleControlError>
+where
+    B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
+{
+    let status = if let Some(open_invoice) = m_state.state().open_invoices.get(&invoice_id) {
+        let transactions = invoice_transactions(open_invoice);
+        let collected_dest_payment = invoice_collected_dest_payment(open_invoice);
+
+        let opt_ticks_left = open_invoice.opt_expiry_ticks.map(|expiry_ticks| {
+            let ticks = ephemeral
+                .invoice_ticks
+                .get(&invoice_id)
+                .cloned()
+                .unwrap_or(0);
+            expiry_ticks.saturating_sub(ticks)
+        });
+
+        InvoiceStatus::Open(OpenInvoiceStatus {
+            total_dest_payment: open_invoice.total_dest_payment,
+            transactions,
+            collected_dest_payment,
+            opt_ticks_left,
+        })
+    } else {
+        InvoiceStatus::InvoiceNotFound
+    };
+
+    let response_invoice_status = ResponseInvoiceStatus { invoice_id, status };
+    outgoing_control.push(FunderOutgoingControl::ResponseInvoiceStatus(
+        response_invoice_status,
+    ));
+    Ok(())
+}
+
+/// Sign the details of an open invoice, to be presented to a buyer.
+/// We only sign for open invoices, so that the node's signature can not be obtained for invoices
+/// it will not accept payments for.
+fn control_request_sign_invoice<B>(
+    m_state: &mut MutableFunderState<B>,
+    outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
+    invoice_details: InvoiceDetails<B>,
+) -> Result<(), HandleControlError>
+where
+    B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
+{
+    let is_open_invoice = match m_state
+        .state()
+        .open_invoices
+        .get(&invoice_details.invoice_id)
+    {
+        // A spontaneous invoice was not issued by us:
+        Some(open_invoice) => {
+            !open_invoice.is_spontaneous
+                && open_invoice.total_dest_payment == invoice_details.total_dest_payment
+        }
+        None => false,
+    };
+
+    if !is_open_invoice {
+        let response_sign_invoice = ResponseSignInvoice {
+            invoice_id: invoice_details.invoice_id,
+            opt_signature: None,
+        };
+        outgoing_control.push(FunderOutgoingControl::ResponseSignInvoice(
+            response_sign_invoice,
+        ));
+        return Err(HandleControlError::InvoiceDoesNotExist);
+    }
+
+    // Signing is asynchronous, so we only queue the invoice details here:
+    m_state.queue_unsigned_invoice(invoice_details);
+    Ok(())
+}
+
+pub fn control_cancel_invoice<B>(
     m_state: &mut MutableFunderState<B>,
     send_commands: &mut SendCommands,
     invoice_id: InvoiceId,
@@ -900,12 +1253,18 @@ where
     for (_, incoming_transaction) in open_invoice.incoming_transactions {
         let request_id = &incoming_transaction.request_id;
         // Explaining the unwrap() below:
-        // We expect that the origin of this request must be from an existing friend.
-        // We can not be the originator of this request.
-        let friend_public_key = find_request_origin(m_state.state(), &request_id)
+        // We expect that this request was sent to us from an existing friend.
+        // (Even if we are the originator of this request, in the case of a circular route)
+        let friend_public_key = find_remote_request_friend(m_state.state(), &request_id)
             .unwrap()
             .clone();
-        reply_with_cancel(m_state, send_commands, &friend_public_key, &request_id);
+        reply_with_cancel(
+            m_state,
+            send_commands,
+            &friend_public_key,
+            &request_id,
+            FailureReason::UnknownInvoice,
+        );
     }
 
     // Remove invoice:
@@ -915,7 +1274,7 @@ where
     Ok(())
 }
 
-fn control_commit_invoice<B>(
+pub fn control_commit_invoice<B>(
     m_state: &mut MutableFunderState<B>,
     send_commands: &mut SendCommands,
     multi_commit: &MultiCommit,
@@ -948,7 +1307,7 @@ where
         };
 
         let friend_public_key = if let Some(friend_public_key) =
-            find_request_origin(m_state.state(), &incoming_transaction.request_id)
+            find_remote_request_friend(m_state.state(), &incoming_transaction.request_id)
         {
             friend_public_key.clone()
         } else {
@@ -1026,6 +1385,11 @@ where
             Ok(())
         }
 
+        FunderControl::SetAcceptSpontaneousPayments(accept_spontaneous_payments) => {
+            control_set_accept_spontaneous_payments(m_state, accept_spontaneous_payments);
+            Ok(())
+        }
+
         FunderControl::RemoveFriend(remove_friend) => control_remove_friend(
             m_state,
             send_commands,
@@ -1078,6 +1442,15 @@ where
         FunderControl::AckClosePayment(ack_close_payment) => {
             control_ack_close_payment(m_state, ack_close_payment)
         }
+        FunderControl::CreateRebalance(create_rebalance) => control_create_rebalance(
+            m_state,
+            m_ephemeral.ephemeral(),
+            outgoing_control,
+            send_commands,
+            rng,
+            max_pending_user_requests,
+            create_rebalance,
+        ),
 
         // Seller API:
         FunderControl::AddInvoice(add_invoice) => control_add_invoice(m_state, add_invoice),
@@ -1087,5 +1460,14 @@ where
         FunderControl::CommitInvoice(multi_commit) => {
             control_commit_invoice(m_state, send_commands, &multi_commit)
         }
+        FunderControl::RequestInvoiceStatus(invoice_id) => control_request_invoice_status(
+            m_state,
+            m_ephemeral.ephemeral(),
+            outgoing_control,
+            invoice_id,
+        ),
+        FunderControl::RequestSignInvoice(invoice_details) => {
+            control_request_sign_invoice(m_state, outgoing_control, invoice_details)
+        }
     }
 }
@@ -7,9 +7,9 @@ use crypto::uid::Uid;
 
 use proto::app_server::messages::RelayAddress;
 use proto::funder::messages::{
-    CancelSendFundsOp, ChannelerUpdateFriend, CollectSendFundsOp, FriendMessage,
-    FunderOutgoingControl, MoveTokenRequest, PendingTransaction, RequestResult, RequestSendFundsOp,
-    ResetTerms, ResponseSendFundsOp, TransactionResult,
+    CancelSendFundsOp, ChannelerUpdateFriend, CollectSendFundsOp, FailureReason, FriendMessage,
+    FunderOutgoingControl, MoveTokenRequest, MultiCommit, PendingTransaction, RequestFailure,
+    RequestResult, RequestSendFundsOp, ResetTerms, ResponseSendFundsOp, TransactionResult,
 };
 use proto::funder::signature_buff::{prepare_commit, prepare_receipt, verify_move_token};
 
@@ -30,11 +30,15 @@ use crate::ephemeral::Ephemeral;
 
 use crate::handler::canceler::{
     cancel_local_pending_transactions, cancel_pending_requests, cancel_pending_user_requests,
-    remove_transaction, reply_with_cancel,
+    local_request_failure, remove_transaction, reply_with_cancel,
 };
+use crate::handler::handle_control::{control_cancel_invoice, control_commit_invoice};
 use crate::handler::sender::SendCommands;
 use crate::handler::state_wrap::{MutableEphemeral, MutableFunderState};
-use crate::handler::utils::{find_request_origin, is_friend_ready};
+use crate::handler::utils::{
+    find_request_origin, friend_not_ready_reason, is_circular_request, is_friend_ready,
+    is_payment_timed_out,
+};
 
 #[derive(Debug)]
 pub enum HandleFriendError {
@@ -160,16 +164,23 @@ fn handle_request_send_funds<B>(
     if next_index >= request_send_funds.route.len() {
         // We are the destination of this request.
 
-        // First make sure that we have a matching open invoice for this transaction:
+        // A spontaneous payment does not require an invoice, but we must have opted in.
+        // The encrypted plain lock is checked against the hashed lock when we sign our
+        // response, as decryption requires our identity.
+        let is_spontaneous = request_send_funds.opt_encrypted_src_plain_lock.is_some();
         let is_invoice_match = if let Some(open_invoice) = m_state
             .state()
             .open_invoices
             .get(&request_send_funds.invoice_id)
         {
-            open_invoice.total_dest_payment == request_send_funds.total_dest_payment
+            // First make sure that we have a matching open invoice for this transaction:
+            open_invoice.is_spontaneous == is_spontaneous
+                && open_invoice.total_dest_payment == request_send_funds.total_dest_payment
                 && request_send_funds.dest_payment <= request_send_funds.total_dest_payment
         } else {
-            false
+            is_spontaneous
+                && m_state.state().accept_spontaneous_payments
+                && request_send_funds.dest_payment <= request_send_funds.total_dest_payment
         };
 
         if !is_invoice_match {
@@ -178,10 +189,26 @@ fn handle_request_send_funds<B>(
                 send_commands,
                 remote_public_key,
                 &request_send_funds.request_id,
+                FailureReason::UnknownInvoice,
             );
             return;
         };
 
+        // The first transaction of a spontaneous payment opens an invoice. The invoice gathers
+        // the incoming transactions until the whole total_dest_payment arrives:
+        if is_spontaneous
+            && !m_state
+                .state()
+                .open_invoices
+                .contains_key(&request_send_funds.invoice_id)
+        {
+            let funder_mutation = FunderMutation::AddSpontaneousInvoice((
+                request_send_funds.invoice_id.clone(),
+                request_send_funds.total_dest_payment,
+            ));
+            m_state.mutate(funder_mutation);
+        }
+
         // We return a response:
         let pending_transaction = create_pending_transaction(&request_send_funds);
         m_state.queue_unsigned_response(remote_public_key.clone(), pending_transaction);
@@ -204,11 +231,16 @@ fn handle_request_send_funds<B>(
     // This friend must be considered online for us to forward the message.
     // If we forward the request to an offline friend, the request could be stuck for a long
     // time before a response arrives.
-    let friend_ready = if friend_exists {
-        is_friend_ready(m_state.state(), ephemeral, &next_public_key)
-    } else {
-        false
-    };
+    let opt_not_ready_reason =
+        if friend_exists && is_friend_ready(m_state.state(), ephemeral, &next_public_key) {
+            None
+        } else {
+            Some(friend_not_ready_reason(
+                m_state.state(),
+                ephemeral,
+                &next_public_key,
+            ))
+        };
 
     // Attempt to take our fee for forwarding the request.
     // Note that the rate is determined by the rate we set with the node that sent us the request
@@ -230,10 +262,26 @@ fn handle_request_send_funds<B>(
         None
     };
 
-    let request_send_funds = match (opt_request_send_funds, friend_ready) {
-        (Some(request_send_funds), true) => request_send_funds,
-        _ => {
-            reply_with_cancel(m_state, send_commands, remote_public_key, &request_id);
+    let request_send_funds = match (opt_request_send_funds, opt_not_ready_reason) {
+        (Some(request_send_funds), None) => request_send_funds,
+        (_, Some(not_ready_reason)) => {
+            reply_with_cancel(
+                m_state,
+                send_commands,
+                remote_public_key,
+                &request_id,
+                not_ready_reason,
+            );
+            return;
+        }
+        (None, None) => {
+            reply_with_cancel(
+                m_state,
+                send_commands,
+                remote_public_key,
+                &request_id,
+                FailureReason::InsufficientFees,
+            );
             return;
         }
     };
@@ -259,19 +307,49 @@ fn handle_response_send_funds<B>(
             m_state.mutate(funder_mutation);
 
             // Send transaction result to user:
-            let src_plain_lock = m_state
+            let open_transaction = m_state
                 .state()
                 .open_transactions
                 .get(&response_send_funds.request_id)
-                .unwrap()
-                .src_plain_lock
-                .clone();
+                .unwrap();
 
-            let commit = prepare_commit(&response_send_funds, &pending_transaction, src_plain_lock);
+            // We never hand out a commit for a payment that has timed out:
+            let result = if is_payment_timed_out(m_state.state(), &open_transaction.payment_id) {
+                local_request_failure(m_state, FailureReason::TimedOut)
+            } else {
+                let commit = prepare_commit(
+                    &response_send_funds,
+                    &pending_transaction,
+                    open_transaction.src_plain_lock.clone(),
+                );
+                RequestResult::Success(commit)
+            };
+
+            if is_circular_request(m_state.state(), &pending_transaction) {
+                // We are also the seller of this payment (Rebalancing).
+                // We commit the invoice right away, so that the credits will be collected:
+                let res = if let RequestResult::Success(commit) = &result {
+                    let multi_commit = MultiCommit {
+                        invoice_id: pending_transaction.invoice_id.clone(),
+                        total_dest_payment: pending_transaction.total_dest_payment,
+                        commits: vec![commit.clone()],
+                    };
+                    control_commit_invoice(m_state, send_commands, &multi_commit)
+                } else {
+                    control_cancel_invoice(
+                        m_state,
+                        send_commands,
+                        pending_transaction.invoice_id.clone(),
+                    )
+                };
+                if let Err(e) = res {
+                    warn!("handle_response_send_funds(): Circular request: {:?}", e);
+                }
+            }
 
             let transaction_result = TransactionResult {
                 request_id: response_send_funds.request_id,
-                result: RequestResult::Success(commit),
+                result,
             };
             outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
         }
@@ -306,11 +384,27 @@ fn handle_cancel_send_funds<B, R>(
             // Update buyer transactions (requests that were originated by us):
             remove_transaction(m_state, rng, &cancel_send_funds.request_id);
 
+            // If we paid ourselves (Rebalancing), the invoice we opened will not be paid:
+            if is_circular_request(m_state.state(), &pending_transaction)
+                && m_state
+                    .state()
+                    .open_invoices
+                    .contains_key(&pending_transaction.invoice_id)
+            {
+                let funder_mutation =
+                    FunderMutation::RemoveInvoice(pending_transaction.invoice_id.clone());
+                m_state.mutate(funder_mutation);
+            }
+
             // Inform user about the transaction failure:
+            let request_failure = RequestFailure {
+                reporting_public_key: cancel_send_funds.reporting_public_key,
+                reason: cancel_send_funds.reason,
+            };
             outgoing_control.push(FunderOutgoingControl::TransactionResult(
                 TransactionResult {
                     request_id: pending_transaction.request_id,
-                    result: RequestResult::Failure,
+                    result: RequestResult::Failure(request_failure),
                 },
             ));
         }
@@ -370,7 +464,7 @@ fn handle_collect_send_funds<B, R>(
                         ack_uid,
                     )))
                 }
-                Payment::InProgress(num_transactions) => {
+                Payment::InProgress((num_transactions, _)) => {
                     // Create a Receipt:
                     let receipt = prepare_receipt(
                         &collect_send_funds,
@@ -390,6 +484,21 @@ fn handle_collect_send_funds<B, R>(
                     *ack_uid,
                 ))),
                 Payment::Canceled(_) => unreachable!(),
+                Payment::TimedOut((num_transactions, _)) => {
+                    // A commit was handed out before the payment timed out, and the seller has
+                    // collected it. The payment did happen after all:
+                    let receipt = prepare_receipt(
+                        &collect_send_funds,
+                        open_transaction.opt_response.as_ref().unwrap(),
+                        &pending_transaction,
+                    );
+                    let ack_uid = Uid::new(rng);
+                    Some(Payment::Success((
+                        num_transactions.checked_sub(1).unwrap(),
+                        receipt,
+                        ack_uid,
+                    )))
+                }
                 Payment::AfterSuccessAck(num_transactions) => {
                     let new_num_transactions = num_transactions.checked_sub(1).unwrap();
                     if new_num_transactions > 0 {
@@ -398,6 +507,16 @@ fn handle_collect_send_funds<B, R>(
                         None
                     }
                 }
+                Payment::AfterTimeoutAck(num_transactions) => {
+                    // The user already acked the timeout, so there is no one left to hand
+                    // the receipt to:
+                    let new_num_transactions = num_transactions.checked_sub(1).unwrap();
+                    if new_num_transactions > 0 {
+                        Some(Payment::AfterTimeoutAck(new_num_transactions))
+                    } else {
+                        None
+                    }
+                }
             };
 
             let funder_mutation = if let Some(new_payment) = opt_new_payment {
@@ -526,8 +645,15 @@ fn handle_move_token_error<B, R>(
         outgoing_control,
         rng,
         remote_public_key,
+        FailureReason::ChannelReset,
+    );
+    cancel_pending_user_requests(
+        m_state,
+        outgoing_control,
+        rng,
+        remote_public_key,
+        FailureReason::ChannelReset,
     );
-    cancel_pending_user_requests(m_state, outgoing_control, rng, remote_public_key);
 
     // Keep outgoing InconsistencyError message details in memory:
     let channel_inconsistent = ChannelInconsistent {
@@ -645,8 +771,15 @@ fn handle_move_token_success<B, R>(
                     outgoing_control,
                     rng,
                     remote_public_key,
+                    FailureReason::RequestsClosed,
+                );
+                cancel_pending_user_requests(
+                    m_state,
+                    outgoing_control,
+                    rng,
+                    remote_public_key,
+                    FailureReason::RequestsClosed,
                 );
-                cancel_pending_user_requests(m_state, outgoing_control, rng, remote_public_key);
             }
 
             handle_move_token_output(
@@ -757,8 +890,15 @@ where
         outgoing_control,
         rng,
         remote_public_key,
+        FailureReason::ChannelReset,
+    );
+    cancel_pending_user_requests(
+        m_state,
+        outgoing_control,
+        rng,
+        remote_public_key,
+        FailureReason::ChannelReset,
     );
-    cancel_pending_user_requests(m_state, outgoing_control, rng, remote_public_key);
 
     // Save remote incoming inconsistency details:
     let new_remote_reset_terms = remote_reset_terms;
@@ -4,7 +4,7 @@ use common::canonical_serialize::CanonicalSerialize;
 
 use crypto::crypto_rand::CryptoRandom;
 
-use proto::funder::messages::{FriendStatus, FunderOutgoingControl};
+use proto::funder::messages::{FailureReason, FriendStatus, FunderOutgoingControl};
 
 use crate::types::IncomingLivenessMessage;
 
@@ -83,8 +83,15 @@ where
                 outgoing_control,
                 rng,
                 &friend_public_key,
+                FailureReason::FriendNotReady,
+            );
+            cancel_pending_user_requests(
+                m_state,
+                outgoing_control,
+                rng,
+                &friend_public_key,
+                FailureReason::FriendNotReady,
             );
-            cancel_pending_user_requests(m_state, outgoing_control, rng, &friend_public_key);
         }
     };
     Ok(())
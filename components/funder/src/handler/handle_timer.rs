@@ -0,0 +1,144 @@
+use common::canonical_serialize::CanonicalSerialize;
+use std::fmt::Debug;
+
+use crypto::crypto_rand::CryptoRandom;
+use crypto::uid::Uid;
+
+use crate::ephemeral::EphemeralMutation;
+use crate::handler::handle_control::control_cancel_invoice;
+use crate::handler::sender::SendCommands;
+use crate::handler::state_wrap::{MutableEphemeral, MutableFunderState};
+use crate::state::{FunderMutation, Payment};
+
+/// Count a timer tick for every payment we are still waiting for.
+/// A payment that was open for its deadline (or `payment_timeout_ticks` ticks, if no deadline was
+/// specified) is marked as timed out.
+///
+/// Invoices with an expiry are counted too, and are canceled once they expire.
+/// A spontaneous invoice that was not fully paid within `payment_timeout_ticks` ticks is canceled
+/// too, releasing the credits frozen along the routes of its transactions.
+pub fn handle_timer_tick<B, R>(
+    m_state: &mut MutableFunderState<B>,
+    m_ephemeral: &mut MutableEphemeral,
+    send_commands: &mut SendCommands,
+    rng: &R,
+    payment_timeout_ticks: usize,
+) where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+    R: CryptoRandom,
+{
+    // Forget about timers of payments that are no longer waited for:
+    let stale_payment_ids = m_ephemeral
+        .ephemeral()
+        .payment_ticks
+        .keys()
+        .filter(
+            |payment_id| match m_state.state().payments.get(payment_id) {
+                Some(Payment::NewTransactions(_)) | Some(Payment::InProgress(_)) => false,
+                _ => true,
+            },
+        )
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for payment_id in stale_payment_ids {
+        m_ephemeral.mutate(EphemeralMutation::RemovePaymentTicks(payment_id));
+    }
+
+    let waiting_payments = m_state
+        .state()
+        .payments
+        .iter()
+        .filter_map(|(payment_id, payment)| match payment {
+            Payment::NewTransactions(new_transactions) => Some((
+                *payment_id,
+                new_transactions.num_transactions,
+                new_transactions.opt_deadline_ticks,
+            )),
+            Payment::InProgress((num_transactions, opt_deadline_ticks)) => {
+                Some((*payment_id, *num_transactions, *opt_deadline_ticks))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    for (payment_id, num_transactions, opt_deadline_ticks) in waiting_payments {
+        let deadline_ticks = opt_deadline_ticks.unwrap_or(payment_timeout_ticks as u64);
+        let ticks = m_ephemeral
+            .ephemeral()
+            .payment_ticks
+            .get(&payment_id)
+            .cloned()
+            .unwrap_or(0)
+            .saturating_add(1);
+
+        if (ticks as u64) < deadline_ticks {
+            m_ephemeral.mutate(EphemeralMutation::SetPaymentTicks((payment_id, ticks)));
+            continue;
+        }
+
+        // The deadline has passed:
+        m_ephemeral.mutate(EphemeralMutation::RemovePaymentTicks(payment_id));
+        let ack_uid = Uid::new(rng);
+        let new_payment = Payment::TimedOut((num_transactions, ack_uid));
+        m_state.mutate(FunderMutation::UpdatePayment((payment_id, new_payment)));
+    }
+
+    handle_invoices_timer_tick(m_state, m_ephemeral, send_commands, payment_timeout_ticks);
+}
+
+fn handle_invoices_timer_tick<B>(
+    m_state: &mut MutableFunderState<B>,
+    m_ephemeral: &mut MutableEphemeral,
+    send_commands: &mut SendCommands,
+    payment_timeout_ticks: usize,
+) where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+{
+    // Forget about timers of invoices that were already committed or canceled:
+    let stale_invoice_ids = m_ephemeral
+        .ephemeral()
+        .invoice_ticks
+        .keys()
+        .filter(|invoice_id| !m_state.state().open_invoices.contains_key(invoice_id))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for invoice_id in stale_invoice_ids {
+        m_ephemeral.mutate(EphemeralMutation::RemoveInvoiceTicks(invoice_id));
+    }
+
+    let expiring_invoices = m_state
+        .state()
+        .open_invoices
+        .iter()
+        .filter_map(|(invoice_id, open_invoice)| {
+            let opt_expiry_ticks = if open_invoice.is_spontaneous {
+                Some(payment_timeout_ticks as u64)
+            } else {
+                open_invoice.opt_expiry_ticks
+            };
+            opt_expiry_ticks.map(|expiry_ticks| (invoice_id.clone(), expiry_ticks))
+        })
+        .collect::<Vec<_>>();
+
+    for (invoice_id, expiry_ticks) in expiring_invoices {
+        let ticks = m_ephemeral
+            .ephemeral()
+            .invoice_ticks
+            .get(&invoice_id)
+            .cloned()
+            .unwrap_or(0)
+            .saturating_add(1);
+
+        if ticks < expiry_ticks {
+            m_ephemeral.mutate(EphemeralMutation::SetInvoiceTicks((invoice_id, ticks)));
+            continue;
+        }
+
+        // The invoice has expired:
+        m_ephemeral.mutate(EphemeralMutation::RemoveInvoiceTicks(invoice_id.clone()));
+        // Explaining the unwrap() below: We have just made sure that the invoice exists.
+        control_cancel_invoice(m_state, send_commands, invoice_id).unwrap();
+    }
+}
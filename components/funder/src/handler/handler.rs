@@ -17,6 +17,7 @@ use crate::handler::handle_control::handle_control_message;
 use crate::handler::handle_friend::{handle_friend_message, HandleFriendError};
 use crate::handler::handle_init::handle_init;
 use crate::handler::handle_liveness::{handle_liveness_message, HandleLivenessError};
+use crate::handler::handle_timer::handle_timer_tick;
 use crate::handler::sender::{create_friend_messages, SendCommands};
 use crate::handler::state_wrap::{MutableEphemeral, MutableFunderState};
 
@@ -53,6 +54,7 @@ pub fn funder_handle_incoming<B, R>(
     rng: &R,
     max_node_relays: usize,
     max_pending_user_requests: usize,
+    payment_timeout_ticks: usize,
     funder_incoming: FunderIncoming<B>,
 ) -> Result<FunderHandleIncomingOutput<B>, FunderHandlerError>
 where
@@ -116,6 +118,17 @@ where
             };
             None
         }
+
+        FunderIncoming::TimerTick => {
+            handle_timer_tick(
+                &mut m_state,
+                &mut m_ephemeral,
+                &mut send_commands,
+                rng,
+                payment_timeout_ticks,
+            );
+            None
+        }
     };
 
     Ok((
@@ -165,6 +178,7 @@ pub async fn funder_handle_message<'a, B, R>(
     max_node_relays: usize,
     max_operations_in_batch: usize,
     max_pending_user_requests: usize,
+    payment_timeout_ticks: usize,
     funder_incoming: FunderIncoming<B>,
 ) -> Result<FunderHandlerOutput<B>, FunderHandlerError>
 where
@@ -175,13 +189,14 @@ where
     let mut m_ephemeral = MutableEphemeral::new(funder_ephemeral);
     let mut outgoing_comms = Vec::new();
 
-    let (send_commands, handle_outgoing_control, outgoing_channeler_config, opt_app_request_id) =
+    let (mut send_commands, handle_outgoing_control, outgoing_channeler_config, opt_app_request_id) =
         funder_handle_incoming(
             &mut m_state,
             &mut m_ephemeral,
             rng,
             max_node_relays,
             max_pending_user_requests,
+            payment_timeout_ticks,
             funder_incoming,
         )?;
 
@@ -190,7 +205,10 @@ where
     }
 
     // Sign all unsigned responses and then queue them as mutations
-    await!(m_state.sign_responses(identity_client, rng));
+    let invoices_paid = await!(m_state.sign_responses(identity_client, &mut send_commands, rng));
+
+    // Sign invoice details requested by the user:
+    let responses_sign_invoice = await!(m_state.sign_invoices(identity_client));
 
     // Send all possible messages according to SendCommands
     // TODO: Maybe we should output outgoing_comms instead of friend_messages and
@@ -240,6 +258,16 @@ where
     // We always send the report mutations first through the outgoing control:
     outgoing_control.extend(handle_outgoing_control);
     outgoing_control.extend(sender_outgoing_control);
+    outgoing_control.extend(
+        responses_sign_invoice
+            .into_iter()
+            .map(FunderOutgoingControl::ResponseSignInvoice),
+    );
+    outgoing_control.extend(
+        invoices_paid
+            .into_iter()
+            .map(FunderOutgoingControl::InvoicePaid),
+    );
 
     Ok(FunderHandlerOutput {
         funder_mutations,
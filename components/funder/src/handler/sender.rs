@@ -8,8 +8,8 @@ use crypto::identity::PublicKey;
 
 use proto::app_server::messages::RelayAddress;
 use proto::funder::messages::{
-    ChannelerUpdateFriend, FriendMessage, FriendTcOp, FunderOutgoingControl, MoveTokenRequest,
-    RequestResult, RequestsStatus, TransactionResult,
+    ChannelerUpdateFriend, FailureReason, FriendMessage, FriendTcOp, FunderOutgoingControl,
+    MoveTokenRequest, RequestsStatus, TransactionResult,
 };
 
 use identity::IdentityClient;
@@ -26,7 +26,7 @@ use crate::friend::{
 use crate::token_channel::{SetDirection, TcDirection, TcMutation, TokenChannel};
 
 use crate::ephemeral::Ephemeral;
-use crate::handler::canceler::remove_transaction;
+use crate::handler::canceler::{local_request_failure, remove_transaction};
 use crate::handler::state_wrap::MutableFunderState;
 use crate::handler::utils::find_request_origin;
 use crate::state::{FunderMutation, FunderState};
@@ -474,8 +474,11 @@ where
             // We send him back a Cancel message:
 
             let pending_transaction = create_pending_transaction(request_send_funds);
-            let cancel_send_funds =
-                BackwardsOp::Cancel(create_cancel_send_funds(pending_transaction.request_id));
+            let cancel_send_funds = BackwardsOp::Cancel(create_cancel_send_funds(
+                pending_transaction.request_id,
+                m_state.state().local_public_key.clone(),
+                FailureReason::NoCapacity,
+            ));
             let friend_mutation = FriendMutation::PushBackPendingBackwardsOp(cancel_send_funds);
             let funder_mutation =
                 FunderMutation::FriendMutation((origin_public_key.clone(), friend_mutation));
@@ -489,7 +492,7 @@ where
 
             let transaction_result = TransactionResult {
                 request_id: request_send_funds.request_id,
-                result: RequestResult::Failure,
+                result: local_request_failure(m_state, FailureReason::NoCapacity),
             };
             outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
         }
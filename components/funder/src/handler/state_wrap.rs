@@ -3,18 +3,28 @@ use std::fmt::Debug;
 use common::canonical_serialize::CanonicalSerialize;
 
 use crypto::crypto_rand::{CryptoRandom, RandValue};
-use crypto::hash_lock::PlainLock;
+use crypto::hash_lock::{HashedLock, PlainLock, PLAIN_LOCK_LEN};
 use crypto::identity::PublicKey;
+use crypto::invoice_id::InvoiceId;
 
-use proto::funder::messages::PendingTransaction;
+use proto::funder::messages::{
+    CollectSendFundsOp, FailureReason, InvoiceDetails, InvoicePaid, PendingTransaction,
+    ResponseSignInvoice,
+};
+use proto::funder::signature_buff::create_invoice_signature_buffer;
 
 use identity::IdentityClient;
 
-use crate::state::{FunderMutation, FunderState};
+use crate::state::{FunderMutation, FunderState, OpenInvoice};
 
 use crate::ephemeral::{Ephemeral, EphemeralMutation};
 use crate::friend::{BackwardsOp, FriendMutation};
-use crate::types::create_response_send_funds;
+use crate::handler::sender::SendCommands;
+use crate::handler::utils::{
+    find_remote_request_friend, invoice_collected_dest_payment, invoice_transactions,
+    is_circular_request,
+};
+use crate::types::{create_cancel_send_funds, create_response_send_funds};
 
 #[derive(Debug, Clone)]
 pub struct SemiResponse {
@@ -26,6 +36,7 @@ pub struct MutableFunderState<B: Clone> {
     initial_state: FunderState<B>,
     state: FunderState<B>,
     unsigned_responses: Vec<SemiResponse>,
+    unsigned_invoices: Vec<InvoiceDetails<B>>,
     mutations: Vec<FunderMutation<B>>,
 }
 
@@ -38,6 +49,7 @@ where
             initial_state: state.clone(),
             state,
             unsigned_responses: Vec::new(),
+            unsigned_invoices: Vec::new(),
             mutations: Vec::new(),
         }
     }
@@ -56,6 +68,12 @@ where
         });
     }
 
+    /// Push invoice details to be signed by the local node.
+    /// Like unsigned responses, signing requires an async function call.
+    pub fn queue_unsigned_invoice(&mut self, invoice_details: InvoiceDetails<B>) {
+        self.unsigned_invoices.push(invoice_details);
+    }
+
     pub fn mutate(&mut self, mutation: FunderMutation<B>) {
         self.state.mutate(&mutation);
         self.mutations.push(mutation);
@@ -65,20 +83,56 @@ where
         &self.state
     }
 
-    /// Sign all unsigned responses and apply them as mutations
+    /// Sign all unsigned responses and apply them as mutations.
+    /// Returns the open invoices that became fully paid by the signed responses.
+    /// Spontaneous invoices are collected as soon as they are fully paid.
     pub async fn sign_responses<'a, R>(
         &'a mut self,
         identity_client: &'a mut IdentityClient,
+        send_commands: &'a mut SendCommands,
         rng: &'a R,
-    ) where
+    ) -> Vec<InvoicePaid>
+    where
         R: CryptoRandom,
     {
+        let mut invoices_paid = Vec::new();
         while let Some(semi_response) = self.unsigned_responses.pop() {
             let SemiResponse {
                 friend_public_key,
                 pending_transaction,
             } = semi_response;
 
+            // A spontaneous payment carries the source plain lock, encrypted to us.
+            // We are going to collect using this plain lock, so it must match the hashed lock:
+            let opt_src_plain_lock = match &pending_transaction.opt_encrypted_src_plain_lock {
+                Some(encrypted_src_plain_lock) => {
+                    match await!(decrypt_src_plain_lock(
+                        identity_client,
+                        encrypted_src_plain_lock,
+                        &pending_transaction.src_hashed_lock
+                    )) {
+                        Some(src_plain_lock) => Some(src_plain_lock),
+                        None => {
+                            let cancel_send_funds = create_cancel_send_funds(
+                                pending_transaction.request_id,
+                                self.state.local_public_key.clone(),
+                                FailureReason::UnknownInvoice,
+                            );
+                            let friend_mutation = FriendMutation::PushBackPendingBackwardsOp(
+                                BackwardsOp::Cancel(cancel_send_funds),
+                            );
+                            let funder_mutation = FunderMutation::FriendMutation((
+                                friend_public_key,
+                                friend_mutation,
+                            ));
+                            self.mutate(funder_mutation);
+                            continue;
+                        }
+                    }
+                }
+                None => None,
+            };
+
             // Randomly generate a dest plain lock:
             let dest_plain_lock = PlainLock::new(rng);
 
@@ -94,7 +148,7 @@ where
             let backwards_op = BackwardsOp::Response(response_send_funds);
             let friend_mutation = FriendMutation::PushBackPendingBackwardsOp(backwards_op);
             let funder_mutation =
-                FunderMutation::FriendMutation((friend_public_key.clone(), friend_mutation));
+                FunderMutation::FriendMutation((friend_public_key, friend_mutation));
             self.mutate(funder_mutation);
 
             // Mutation to add the destination plain lock:
@@ -102,18 +156,157 @@ where
                 pending_transaction.invoice_id,
                 pending_transaction.request_id,
                 dest_plain_lock,
+                pending_transaction.dest_payment,
+                opt_src_plain_lock,
             ));
             self.mutate(funder_mutation);
+
+            // Circular (rebalancing) invoices are committed by us, so there is no one to notify:
+            if is_circular_request(&self.state, &pending_transaction) {
+                continue;
+            }
+
+            // Check if this transaction completed the payment for the invoice:
+            let open_invoice = match self
+                .state
+                .open_invoices
+                .get(&pending_transaction.invoice_id)
+            {
+                Some(open_invoice) => open_invoice.clone(),
+                None => continue,
+            };
+            let collected_dest_payment = invoice_collected_dest_payment(&open_invoice);
+            let prev_collected_dest_payment =
+                collected_dest_payment.saturating_sub(pending_transaction.dest_payment);
+            if prev_collected_dest_payment >= open_invoice.total_dest_payment
+                || collected_dest_payment < open_invoice.total_dest_payment
+            {
+                continue;
+            }
+
+            invoices_paid.push(InvoicePaid {
+                invoice_id: pending_transaction.invoice_id.clone(),
+                total_dest_payment: open_invoice.total_dest_payment,
+                transactions: invoice_transactions(&open_invoice),
+            });
+
+            if open_invoice.is_spontaneous {
+                // There is no Commit to wait for. We collect the credits right away:
+                self.collect_spontaneous_invoice(
+                    send_commands,
+                    &pending_transaction.invoice_id,
+                    &open_invoice,
+                );
+            }
         }
+        invoices_paid
+    }
+
+    /// Collect all the incoming transactions of a fully paid spontaneous invoice, and remove
+    /// the invoice.
+    fn collect_spontaneous_invoice(
+        &mut self,
+        send_commands: &mut SendCommands,
+        invoice_id: &InvoiceId,
+        open_invoice: &OpenInvoice,
+    ) {
+        for incoming_transaction in open_invoice.incoming_transactions.values() {
+            let src_plain_lock =
+                if let Some(src_plain_lock) = &incoming_transaction.opt_src_plain_lock {
+                    src_plain_lock.clone()
+                } else {
+                    warn!("collect_spontaneous_invoice(): Missing source plain lock");
+                    continue;
+                };
+
+            let friend_public_key = if let Some(friend_public_key) =
+                find_remote_request_friend(&self.state, &incoming_transaction.request_id)
+            {
+                friend_public_key.clone()
+            } else {
+                warn!("collect_spontaneous_invoice(): Failed to find request origin");
+                continue;
+            };
+
+            let collect_send_funds = CollectSendFundsOp {
+                request_id: incoming_transaction.request_id,
+                src_plain_lock,
+                dest_plain_lock: incoming_transaction.dest_plain_lock.clone(),
+            };
+            let friend_mutation = FriendMutation::PushBackPendingBackwardsOp(BackwardsOp::Collect(
+                collect_send_funds,
+            ));
+            let funder_mutation =
+                FunderMutation::FriendMutation((friend_public_key.clone(), friend_mutation));
+            self.mutate(funder_mutation);
+
+            // Signal the sender to attempt to send:
+            send_commands.set_try_send(&friend_public_key);
+        }
+
+        let funder_mutation = FunderMutation::RemoveInvoice(invoice_id.clone());
+        self.mutate(funder_mutation);
+    }
+
+    /// Sign all queued invoice details using the local node's identity.
+    pub async fn sign_invoices<'a>(
+        &'a mut self,
+        identity_client: &'a mut IdentityClient,
+    ) -> Vec<ResponseSignInvoice> {
+        let mut responses_sign_invoice = Vec::new();
+        for invoice_details in self.unsigned_invoices.drain(..) {
+            let signature_buff =
+                create_invoice_signature_buffer(&self.state.local_public_key, &invoice_details);
+            let signature = await!(identity_client.request_signature(signature_buff)).unwrap();
+            responses_sign_invoice.push(ResponseSignInvoice {
+                invoice_id: invoice_details.invoice_id,
+                opt_signature: Some(signature),
+            });
+        }
+        responses_sign_invoice
     }
 
     pub fn done(self) -> (FunderState<B>, Vec<FunderMutation<B>>, FunderState<B>) {
         // TODO: Find out how to change this into compile time guarantee:
         assert!(self.unsigned_responses.is_empty());
+        assert!(self.unsigned_invoices.is_empty());
         (self.initial_state, self.mutations, self.state)
     }
 }
 
+/// Decrypt the source plain lock of a spontaneous payment.
+/// Returns None if decryption failed (Including failure to reach the identity), or if the plain
+/// lock does not match the source hashed lock.
+async fn decrypt_src_plain_lock<'a>(
+    identity_client: &'a mut IdentityClient,
+    encrypted_src_plain_lock: &'a [u8],
+    src_hashed_lock: &'a HashedLock,
+) -> Option<PlainLock> {
+    let plain_msg = match await!(identity_client.request_decrypt(encrypted_src_plain_lock.to_vec()))
+    {
+        Ok(Some(plain_msg)) => plain_msg,
+        Ok(None) => return None,
+        Err(e) => {
+            error!(
+                "decrypt_src_plain_lock(): request_decrypt() failed: {:?}",
+                e
+            );
+            return None;
+        }
+    };
+    if plain_msg.len() != PLAIN_LOCK_LEN {
+        return None;
+    }
+    let mut src_plain_lock_array = [0u8; PLAIN_LOCK_LEN];
+    src_plain_lock_array.copy_from_slice(&plain_msg);
+    let src_plain_lock = PlainLock::from(&src_plain_lock_array);
+
+    if src_plain_lock.hash() != *src_hashed_lock {
+        return None;
+    }
+    Some(src_plain_lock)
+}
+
 pub struct MutableEphemeral {
     ephemeral: Ephemeral,
     mutations: Vec<EphemeralMutation>,
@@ -0,0 +1,164 @@
+use super::utils::apply_funder_incoming;
+
+use futures::executor::ThreadPool;
+use futures::task::SpawnExt;
+use futures::{future, FutureExt};
+
+use identity::{create_identity, IdentityClient};
+
+use crypto::crypto_rand::RngContainer;
+use crypto::identity::{generate_pkcs8_key_pair, SoftwareEd25519Identity};
+use crypto::invoice_id::{InvoiceId, INVOICE_ID_LEN};
+use crypto::test_utils::DummyRandom;
+use crypto::uid::{Uid, UID_LEN};
+
+use proto::funder::messages::{
+    AddInvoice, FunderControl, FunderIncomingControl, FunderOutgoingControl, InvoiceStatus,
+};
+
+use crate::ephemeral::Ephemeral;
+use crate::state::FunderState;
+use crate::types::FunderIncoming;
+
+use crate::tests::utils::dummy_named_relay_address;
+
+const TEST_INVOICE_EXPIRY_TICKS: u64 = 4;
+
+async fn task_handler_invoice_expiry<'a>(identity_client: &'a mut IdentityClient) {
+    let pk = await!(identity_client.request_public_key()).unwrap();
+
+    let relays = vec![dummy_named_relay_address(1)];
+    let mut state = FunderState::<u32>::new(pk.clone(), relays);
+    let mut ephemeral = Ephemeral::new();
+
+    let mut rng = RngContainer::new(DummyRandom::new(&[3u8]));
+
+    // Initialize:
+    let funder_incoming = FunderIncoming::Init;
+    await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    // Open an invoice that expires:
+    let invoice_id = InvoiceId::from(&[1u8; INVOICE_ID_LEN]);
+    let add_invoice = AddInvoice {
+        invoice_id: invoice_id.clone(),
+        total_dest_payment: 16,
+        opt_expiry_ticks: Some(TEST_INVOICE_EXPIRY_TICKS),
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[17; UID_LEN]),
+        FunderControl::AddInvoice(add_invoice),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    // Time passes, but not enough for the invoice to expire:
+    for _ in 0..TEST_INVOICE_EXPIRY_TICKS - 1 {
+        await!(Box::pin(apply_funder_incoming(
+            FunderIncoming::TimerTick,
+            &mut state,
+            &mut ephemeral,
+            &mut rng,
+            identity_client
+        )))
+        .unwrap();
+    }
+
+    // Check the invoice status:
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[18; UID_LEN]),
+        FunderControl::RequestInvoiceStatus(invoice_id.clone()),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (_outgoing_comms, outgoing_control) = await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    let response_invoice_status = match outgoing_control.last().unwrap() {
+        FunderOutgoingControl::ResponseInvoiceStatus(response_invoice_status) => {
+            response_invoice_status.clone()
+        }
+        _ => unreachable!(),
+    };
+    assert_eq!(response_invoice_status.invoice_id, invoice_id);
+    let open_invoice_status = match response_invoice_status.status {
+        InvoiceStatus::Open(open_invoice_status) => open_invoice_status,
+        _ => unreachable!(),
+    };
+    assert_eq!(open_invoice_status.total_dest_payment, 16);
+    assert!(open_invoice_status.transactions.is_empty());
+    assert_eq!(open_invoice_status.collected_dest_payment, 0);
+    assert_eq!(open_invoice_status.opt_ticks_left, Some(1));
+
+    // The last tick cancels the invoice:
+    await!(Box::pin(apply_funder_incoming(
+        FunderIncoming::TimerTick,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    assert!(state.open_invoices.get(&invoice_id).is_none());
+    assert!(ephemeral.invoice_ticks.get(&invoice_id).is_none());
+
+    // Check the invoice status again:
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[19; UID_LEN]),
+        FunderControl::RequestInvoiceStatus(invoice_id.clone()),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (_outgoing_comms, outgoing_control) = await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    match outgoing_control.last().unwrap() {
+        FunderOutgoingControl::ResponseInvoiceStatus(response_invoice_status) => {
+            assert_eq!(
+                response_invoice_status.status,
+                InvoiceStatus::InvoiceNotFound
+            );
+        }
+        _ => unreachable!(),
+    };
+}
+
+#[test]
+fn test_handler_invoice_expiry() {
+    let mut thread_pool = ThreadPool::new().unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let pkcs8 = generate_pkcs8_key_pair(&rng);
+    let identity = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+    let (requests_sender, identity_server) = create_identity(identity);
+    let mut identity_client = IdentityClient::new(requests_sender);
+    thread_pool
+        .spawn(identity_server.then(|_| future::ready(())))
+        .unwrap();
+
+    thread_pool.run(task_handler_invoice_expiry(&mut identity_client));
+}
@@ -1,4 +1,8 @@
 mod change_address;
+mod invoice_expiry;
 mod pair_basic;
+mod pair_cancel;
 mod pair_inconsistency;
+mod pair_setup;
+mod payment_timeout;
 mod utils;
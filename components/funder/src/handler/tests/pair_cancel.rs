@@ -0,0 +1,204 @@
+use super::pair_setup::{pair_request_response, PairAfterResponse};
+use super::utils::apply_funder_incoming;
+
+use futures::executor::ThreadPool;
+use futures::task::SpawnExt;
+use futures::{future, FutureExt};
+
+use identity::{create_identity, IdentityClient};
+
+use crypto::identity::{generate_pkcs8_key_pair, SoftwareEd25519Identity};
+use crypto::invoice_id::{InvoiceId, INVOICE_ID_LEN};
+use crypto::test_utils::DummyRandom;
+use crypto::uid::{Uid, UID_LEN};
+
+use proto::funder::messages::{
+    FailureReason, FriendMessage, FunderControl, FunderIncomingControl, FunderOutgoingControl,
+    RequestResult,
+};
+
+use crate::friend::ChannelStatus;
+use crate::types::{FunderIncoming, FunderIncomingComm, FunderOutgoingComm};
+
+async fn task_handler_pair_cancel<'a>(
+    identity_client1: &'a mut IdentityClient,
+    identity_client2: &'a mut IdentityClient,
+) {
+    // NOTE: We use Box::pin() in order to make sure we don't get a too large Future which will
+    // cause a stack overflow.
+    // See:  https://github.com/rust-lang-nursery/futures-rs/issues/1330
+
+    // The response arrived, but Node2 does not hand the Commit to Node1:
+    let PairAfterResponse {
+        identity_client1,
+        pk1,
+        mut state1,
+        mut ephemeral1,
+        identity_client2,
+        pk2,
+        mut state2,
+        mut ephemeral2,
+        mut rng,
+        ..
+    } = await!(Box::pin(pair_request_response(
+        identity_client1,
+        identity_client2
+    )));
+
+    // Node1: Cancel the invoice.
+    // After the response was sent, only the seller may cancel the transaction:
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[21; UID_LEN]),
+        FunderControl::CancelInvoice(InvoiceId::from(&[1u8; INVOICE_ID_LEN])),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (outgoing_comms, _outgoing_control) = await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state1,
+        &mut ephemeral1,
+        &mut rng,
+        identity_client1
+    )))
+    .unwrap();
+
+    assert!(state1.open_invoices.is_empty());
+
+    // Node1 requests the token from Node2:
+    assert_eq!(outgoing_comms.len(), 1);
+    let friend_message =
+        if let FunderOutgoingComm::FriendMessage((_pk, friend_message)) = &outgoing_comms[0] {
+            friend_message.clone()
+        } else {
+            unreachable!();
+        };
+
+    // Node2 receives request token message from Node1:
+    let funder_incoming =
+        FunderIncoming::Comm(FunderIncomingComm::Friend((pk1.clone(), friend_message)));
+    let (outgoing_comms, _outgoing_control) = await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2
+    )))
+    .unwrap();
+
+    // Node2 gives token to Node1:
+    assert_eq!(outgoing_comms.len(), 1);
+    let friend_message =
+        if let FunderOutgoingComm::FriendMessage((_pk, friend_message)) = &outgoing_comms[0] {
+            friend_message.clone()
+        } else {
+            unreachable!();
+        };
+
+    // Node1 receives token from Node2:
+    let funder_incoming =
+        FunderIncoming::Comm(FunderIncomingComm::Friend((pk2.clone(), friend_message)));
+    let (outgoing_comms, _outgoing_control) = await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state1,
+        &mut ephemeral1,
+        &mut rng,
+        identity_client1
+    )))
+    .unwrap();
+
+    // Node1 sends a Cancel message to Node2. The frozen credits are released:
+    assert_eq!(outgoing_comms.len(), 1);
+    let friend_message = match &outgoing_comms[0] {
+        FunderOutgoingComm::FriendMessage((pk, friend_message)) => {
+            if let FriendMessage::MoveTokenRequest(move_token_request) = friend_message {
+                assert_eq!(pk, &pk2);
+                let friend_move_token = &move_token_request.friend_move_token;
+                assert_eq!(friend_move_token.balance, 0);
+                assert_eq!(friend_move_token.local_pending_debt, 0);
+                assert_eq!(friend_move_token.remote_pending_debt, 0);
+            } else {
+                unreachable!();
+            }
+            friend_message.clone()
+        }
+        _ => unreachable!(),
+    };
+
+    // Node2 receives Cancel message from Node1:
+    let funder_incoming =
+        FunderIncoming::Comm(FunderIncomingComm::Friend((pk1.clone(), friend_message)));
+    let (_outgoing_comms, outgoing_control) = await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2
+    )))
+    .unwrap();
+
+    // Node2 is informed about the failure of the transaction:
+    let transaction_result = outgoing_control
+        .iter()
+        .find_map(|outgoing| match outgoing {
+            FunderOutgoingControl::TransactionResult(transaction_result) => {
+                Some(transaction_result.clone())
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(transaction_result.request_id, Uid::from(&[1; UID_LEN]));
+    match &transaction_result.result {
+        RequestResult::Failure(request_failure) => {
+            assert_eq!(request_failure.reporting_public_key, pk1);
+            assert_eq!(request_failure.reason, FailureReason::UnknownInvoice);
+        }
+        _ => unreachable!(),
+    };
+    assert!(state2.open_transactions.is_empty());
+
+    // No credits were transferred:
+    let friend2 = state1.friends.get(&pk2).unwrap();
+    let mutual_credit_state = match &friend2.channel_status {
+        ChannelStatus::Consistent(token_channel) => token_channel.get_mutual_credit().state(),
+        _ => unreachable!(),
+    };
+    assert_eq!(mutual_credit_state.balance.balance, 0);
+    assert_eq!(mutual_credit_state.balance.remote_pending_debt, 0);
+    assert_eq!(mutual_credit_state.balance.local_pending_debt, 0);
+
+    let friend1 = state2.friends.get(&pk1).unwrap();
+    let mutual_credit_state = match &friend1.channel_status {
+        ChannelStatus::Consistent(token_channel) => token_channel.get_mutual_credit().state(),
+        _ => unreachable!(),
+    };
+    assert_eq!(mutual_credit_state.balance.balance, 0);
+    assert_eq!(mutual_credit_state.balance.remote_pending_debt, 0);
+    assert_eq!(mutual_credit_state.balance.local_pending_debt, 0);
+}
+
+#[test]
+fn test_handler_pair_cancel() {
+    let mut thread_pool = ThreadPool::new().unwrap();
+
+    let rng1 = DummyRandom::new(&[1u8]);
+    let pkcs8 = generate_pkcs8_key_pair(&rng1);
+    let identity1 = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+    let (requests_sender1, identity_server1) = create_identity(identity1);
+    let mut identity_client1 = IdentityClient::new(requests_sender1);
+    thread_pool
+        .spawn(identity_server1.then(|_| future::ready(())))
+        .unwrap();
+
+    let rng2 = DummyRandom::new(&[2u8]);
+    let pkcs8 = generate_pkcs8_key_pair(&rng2);
+    let identity2 = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+    let (requests_sender2, identity_server2) = create_identity(identity2);
+    let mut identity_client2 = IdentityClient::new(requests_sender2);
+    thread_pool
+        .spawn(identity_server2.then(|_| future::ready(())))
+        .unwrap();
+
+    thread_pool.run(task_handler_pair_cancel(
+        &mut identity_client1,
+        &mut identity_client2,
+    ));
+}
@@ -0,0 +1,347 @@
+use super::utils::{apply_funder_incoming, TEST_PAYMENT_TIMEOUT_TICKS};
+
+use futures::executor::ThreadPool;
+use futures::task::SpawnExt;
+use futures::{future, FutureExt};
+
+use identity::{create_identity, IdentityClient};
+
+use crypto::crypto_rand::RngContainer;
+use crypto::identity::{
+    generate_pkcs8_key_pair, PublicKey, SoftwareEd25519Identity, PUBLIC_KEY_LEN,
+};
+use crypto::invoice_id::{InvoiceId, INVOICE_ID_LEN};
+use crypto::payment_id::{PaymentId, PAYMENT_ID_LEN};
+use crypto::test_utils::DummyRandom;
+use crypto::uid::{Uid, UID_LEN};
+
+use proto::funder::messages::{
+    AckClosePayment, CreatePayment, CreateTransaction, FailureReason, FriendsRoute, FunderControl,
+    FunderIncomingControl, FunderOutgoingControl, PaymentStatus, RequestResult,
+};
+
+use crate::ephemeral::Ephemeral;
+use crate::state::{FunderMutation, FunderState, Payment};
+use crate::types::FunderIncoming;
+
+use crate::tests::utils::dummy_named_relay_address;
+
+async fn task_handler_payment_timeout<'a>(identity_client: &'a mut IdentityClient) {
+    let pk = await!(identity_client.request_public_key()).unwrap();
+
+    let relays = vec![dummy_named_relay_address(1)];
+    let mut state = FunderState::<u32>::new(pk.clone(), relays);
+    let mut ephemeral = Ephemeral::new();
+
+    let mut rng = RngContainer::new(DummyRandom::new(&[3u8]));
+
+    // Initialize:
+    let funder_incoming = FunderIncoming::Init;
+    await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    // Open a payment:
+    let payment_id = PaymentId::from(&[3u8; PAYMENT_ID_LEN]);
+    let create_payment = CreatePayment {
+        payment_id,
+        invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
+        total_dest_payment: 16,
+        dest_public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+        opt_dest_dh_public_key: None,
+        opt_deadline_ticks: None,
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[17; UID_LEN]),
+        FunderControl::CreatePayment(create_payment),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    // Time passes, but not enough for the payment to time out:
+    for _ in 0..TEST_PAYMENT_TIMEOUT_TICKS - 1 {
+        await!(Box::pin(apply_funder_incoming(
+            FunderIncoming::TimerTick,
+            &mut state,
+            &mut ephemeral,
+            &mut rng,
+            identity_client
+        )))
+        .unwrap();
+    }
+
+    match state.payments.get(&payment_id).unwrap() {
+        Payment::NewTransactions(_) => {}
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        ephemeral.payment_ticks.get(&payment_id),
+        Some(&(TEST_PAYMENT_TIMEOUT_TICKS - 1))
+    );
+
+    // The last tick marks the payment as timed out:
+    await!(Box::pin(apply_funder_incoming(
+        FunderIncoming::TimerTick,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    match state.payments.get(&payment_id).unwrap() {
+        Payment::TimedOut((0, _)) => {}
+        _ => unreachable!(),
+    };
+    assert!(ephemeral.payment_ticks.get(&payment_id).is_none());
+
+    // Check the payment status:
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[18; UID_LEN]),
+        FunderControl::RequestClosePayment(payment_id),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (_outgoing_comms, outgoing_control) = await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    let response_close_payment = match outgoing_control.last().unwrap() {
+        FunderOutgoingControl::ResponseClosePayment(response_close_payment) => {
+            response_close_payment.clone()
+        }
+        _ => unreachable!(),
+    };
+    assert_eq!(response_close_payment.payment_id, payment_id);
+    let ack_uid = match response_close_payment.status {
+        PaymentStatus::TimedOut(ack_uid) => ack_uid,
+        _ => unreachable!(),
+    };
+
+    // Ack the timed out payment:
+    let ack_close_payment = AckClosePayment {
+        payment_id,
+        ack_uid,
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[19; UID_LEN]),
+        FunderControl::AckClosePayment(ack_close_payment),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    // No transactions were pending, so the payment is gone:
+    assert!(state.payments.get(&payment_id).is_none());
+}
+
+async fn task_handler_payment_deadline<'a>(identity_client: &'a mut IdentityClient) {
+    let pk = await!(identity_client.request_public_key()).unwrap();
+
+    let relays = vec![dummy_named_relay_address(1)];
+    let mut state = FunderState::<u32>::new(pk.clone(), relays);
+    let mut ephemeral = Ephemeral::new();
+
+    let mut rng = RngContainer::new(DummyRandom::new(&[3u8]));
+
+    // Initialize:
+    let funder_incoming = FunderIncoming::Init;
+    await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    // Open a payment with a deadline shorter than the default timeout:
+    let payment_id = PaymentId::from(&[3u8; PAYMENT_ID_LEN]);
+    let create_payment = CreatePayment {
+        payment_id,
+        invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
+        total_dest_payment: 16,
+        dest_public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+        opt_dest_dh_public_key: None,
+        opt_deadline_ticks: Some(2),
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[17; UID_LEN]),
+        FunderControl::CreatePayment(create_payment),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    for _ in 0..2 {
+        await!(Box::pin(apply_funder_incoming(
+            FunderIncoming::TimerTick,
+            &mut state,
+            &mut ephemeral,
+            &mut rng,
+            identity_client
+        )))
+        .unwrap();
+    }
+
+    match state.payments.get(&payment_id).unwrap() {
+        Payment::TimedOut((0, _)) => {}
+        _ => unreachable!(),
+    };
+
+    // New transactions fail once the deadline has passed:
+    let create_transaction = CreateTransaction {
+        payment_id,
+        request_id: Uid::from(&[21; UID_LEN]),
+        route: FriendsRoute {
+            public_keys: vec![pk.clone(), PublicKey::from(&[0xbb; PUBLIC_KEY_LEN])],
+        },
+        dest_payment: 16,
+        fees: 0,
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[22; UID_LEN]),
+        FunderControl::CreateTransaction(create_transaction),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (_outgoing_comms, outgoing_control) = await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    match outgoing_control.last().unwrap() {
+        FunderOutgoingControl::TransactionResult(transaction_result) => {
+            assert_eq!(transaction_result.request_id, Uid::from(&[21; UID_LEN]));
+            match &transaction_result.result {
+                RequestResult::Failure(request_failure) => {
+                    assert_eq!(request_failure.reporting_public_key, pk);
+                    assert_eq!(request_failure.reason, FailureReason::TimedOut);
+                }
+                _ => unreachable!(),
+            }
+        }
+        _ => unreachable!(),
+    };
+
+    // Pretend that a transaction is still pending:
+    let ack_uid = Uid::from(&[20; UID_LEN]);
+    state.mutate(&FunderMutation::UpdatePayment((
+        payment_id,
+        Payment::TimedOut((1, ack_uid)),
+    )));
+
+    // The timeout is reported even though a transaction is still pending:
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[18; UID_LEN]),
+        FunderControl::RequestClosePayment(payment_id),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (_outgoing_comms, outgoing_control) = await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    match outgoing_control.last().unwrap() {
+        FunderOutgoingControl::ResponseClosePayment(response_close_payment) => {
+            assert_eq!(response_close_payment.payment_id, payment_id);
+            assert_eq!(
+                response_close_payment.status,
+                PaymentStatus::TimedOut(ack_uid)
+            );
+        }
+        _ => unreachable!(),
+    };
+
+    // Ack the timed out payment:
+    let ack_close_payment = AckClosePayment {
+        payment_id,
+        ack_uid,
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[19; UID_LEN]),
+        FunderControl::AckClosePayment(ack_close_payment),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    await!(Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        identity_client
+    )))
+    .unwrap();
+
+    // The pending transaction is resolved in the background:
+    match state.payments.get(&payment_id).unwrap() {
+        Payment::AfterTimeoutAck(1) => {}
+        _ => unreachable!(),
+    };
+}
+
+#[test]
+fn test_handler_payment_timeout() {
+    let mut thread_pool = ThreadPool::new().unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let pkcs8 = generate_pkcs8_key_pair(&rng);
+    let identity = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+    let (requests_sender, identity_server) = create_identity(identity);
+    let mut identity_client = IdentityClient::new(requests_sender);
+    thread_pool
+        .spawn(identity_server.then(|_| future::ready(())))
+        .unwrap();
+
+    thread_pool.run(task_handler_payment_timeout(&mut identity_client));
+}
+
+#[test]
+fn test_handler_payment_deadline() {
+    let mut thread_pool = ThreadPool::new().unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let pkcs8 = generate_pkcs8_key_pair(&rng);
+    let identity = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+    let (requests_sender, identity_server) = create_identity(identity);
+    let mut identity_client = IdentityClient::new(requests_sender);
+    thread_pool
+        .spawn(identity_server.then(|_| future::ready(())))
+        .unwrap();
+
+    thread_pool.run(task_handler_payment_deadline(&mut identity_client));
+}
@@ -14,6 +14,7 @@ use crate::types::{FunderIncoming, FunderOutgoingComm};
 const TEST_MAX_NODE_RELAYS: usize = 16;
 const TEST_MAX_OPERATIONS_IN_BATCH: usize = 16;
 const TEST_MAX_PENDING_USER_REQUESTS: usize = 16;
+pub const TEST_PAYMENT_TIMEOUT_TICKS: usize = 8;
 
 /// A helper function. Applies an incoming funder message, updating state and ephemeral
 /// accordingly:
@@ -36,6 +37,7 @@ where
         TEST_MAX_NODE_RELAYS,
         TEST_MAX_OPERATIONS_IN_BATCH,
         TEST_MAX_PENDING_USER_REQUESTS,
+        TEST_PAYMENT_TIMEOUT_TICKS,
         funder_incoming
     ))?;
 
@@ -2,23 +2,78 @@ use std::fmt::Debug;
 
 use common::canonical_serialize::CanonicalSerialize;
 
-use proto::funder::messages::PendingTransaction;
+use proto::funder::messages::{FailureReason, InvoiceTransaction, PendingTransaction};
 
 use crypto::identity::PublicKey;
+use crypto::payment_id::PaymentId;
 use crypto::uid::Uid;
 
-use crate::state::FunderState;
+use crate::state::{FunderState, OpenInvoice, Payment};
 
 use crate::ephemeral::Ephemeral;
 use crate::friend::ChannelStatus;
 
+/// Did the payment with the given payment_id time out?
+pub fn is_payment_timed_out<B>(state: &FunderState<B>, payment_id: &PaymentId) -> bool
+where
+    B: Clone,
+{
+    match state.payments.get(payment_id) {
+        Some(Payment::TimedOut(_)) | Some(Payment::AfterTimeoutAck(_)) => true,
+        _ => false,
+    }
+}
+
+/// List the transactions received so far for an open invoice, ordered by request_id.
+pub fn invoice_transactions(open_invoice: &OpenInvoice) -> Vec<InvoiceTransaction> {
+    let mut transactions = open_invoice
+        .incoming_transactions
+        .iter()
+        .map(
+            |(dest_hashed_lock, incoming_transaction)| InvoiceTransaction {
+                request_id: incoming_transaction.request_id,
+                dest_hashed_lock: dest_hashed_lock.clone(),
+                dest_payment: incoming_transaction.dest_payment,
+            },
+        )
+        .collect::<Vec<_>>();
+    transactions.sort_by(|a, b| a.request_id.cmp(&b.request_id));
+    transactions
+}
+
+/// Sum of the dest_payment of all the transactions received for an open invoice.
+pub fn invoice_collected_dest_payment(open_invoice: &OpenInvoice) -> u128 {
+    open_invoice
+        .incoming_transactions
+        .values()
+        .fold(0u128, |acc, incoming_transaction| {
+            acc.saturating_add(incoming_transaction.dest_payment)
+        })
+}
+
 /// Find the originator of a pending local request.
 /// This should be a pending remote request at some other friend.
 /// Returns the public key of a friend. If we are the origin of this request, the function returns None.
+pub fn find_request_origin<'a, B>(
+    state: &'a FunderState<B>,
+    request_id: &Uid,
+) -> Option<&'a PublicKey>
+where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+{
+    // Requests along a circular route (Used for rebalancing) come back to us, and show up as a
+    // pending remote request too. We are still the origin of such requests:
+    if state.open_transactions.contains_key(request_id) {
+        return None;
+    }
+    find_remote_request_friend(state, request_id)
+}
+
+/// Find the friend that sent us a request (A pending remote request).
 ///
 /// TODO: We need to change this search to be O(1) in the future. Possibly by maintaining a map
 /// between request_id and (friend_public_key, friend).
-pub fn find_request_origin<'a, B>(
+pub fn find_remote_request_friend<'a, B>(
     state: &'a FunderState<B>,
     request_id: &Uid,
 ) -> Option<&'a PublicKey>
@@ -44,6 +99,20 @@ where
     None
 }
 
+/// Is this a request along a circular route that starts and ends with us?
+/// (We pay ourselves, usually to rebalance our credits between two friends)
+pub fn is_circular_request<B>(
+    state: &FunderState<B>,
+    pending_transaction: &PendingTransaction,
+) -> bool
+where
+    B: Clone,
+{
+    let public_keys = &pending_transaction.route.public_keys;
+    public_keys.first() == Some(&state.local_public_key)
+        && public_keys.last() == Some(&state.local_public_key)
+}
+
 /// Find an outgoing pending transaction
 pub fn find_local_pending_transaction<'a, B>(
     state: &'a FunderState<B>,
@@ -98,3 +167,27 @@ where
         .remote
         .is_open()
 }
+
+/// Explain why a friend is not ready to forward requests.
+/// Should only be called if `is_friend_ready()` returned false for this friend.
+pub fn friend_not_ready_reason<B>(
+    state: &FunderState<B>,
+    ephemeral: &Ephemeral,
+    friend_public_key: &PublicKey,
+) -> FailureReason
+where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+{
+    let friend = match state.friends.get(friend_public_key) {
+        Some(friend) => friend,
+        None => return FailureReason::FriendNotReady,
+    };
+    if !ephemeral.liveness.is_online(friend_public_key) {
+        return FailureReason::FriendNotReady;
+    }
+
+    match &friend.channel_status {
+        ChannelStatus::Inconsistent(_) => FailureReason::ChannelReset,
+        ChannelStatus::Consistent(_) => FailureReason::RequestsClosed,
+    }
+}
@@ -19,10 +19,12 @@ extern crate log;
 extern crate serde_derive;
 
 mod ephemeral;
+pub mod export;
 mod friend;
 mod funder;
 mod handler;
 mod liveness;
+pub mod migrate;
 mod mutual_credit;
 pub mod report;
 mod state;
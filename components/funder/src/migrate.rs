@@ -0,0 +1,1338 @@
+//! Frozen copies of persisted funder types, as they were laid out in schema version 1 of the node
+//! database. A database of schema version 1 is deserialized into these types, and then converted
+//! into the current types.
+//!
+//! Types that did not change since schema version 1 are used directly.
+//! These types must never change: A layout change requires a new schema version.
+//!
+//! Some values were not recorded by schema version 1, and are filled in with defaults:
+//! - Cancellations carry no reporting node and no reason. The node that sent the cancellation
+//!   (Usually the local node) is used as the reporting node, and the reason is `Rejected`.
+//!   Both fields are not signed, so signed move tokens containing such a cancellation remain
+//!   valid after migration.
+//! - Open invoices never expire.
+//! - The amount an incoming transaction pays for its invoice is taken from the matching pending
+//!   remote request. If no such request exists, the amount can not be reconstructed, and the
+//!   migration fails. Mutations carry no such request, so a mutation adding an incoming
+//!   transaction can not be migrated either. Such databases have to be migrated after their open
+//!   invoices were paid or canceled.
+//! - Spontaneous payments did not exist: They are not accepted, and no payment, request or
+//!   pending transaction is spontaneous.
+
+use std::convert::TryFrom;
+
+use im::hashmap::HashMap as ImHashMap;
+use im::vector::Vector as ImVec;
+
+use crypto::crypto_rand::RandValue;
+use crypto::hash_lock::{HashedLock, PlainLock};
+use crypto::identity::{PublicKey, Signature};
+use crypto::invoice_id::InvoiceId;
+use crypto::payment_id::PaymentId;
+use crypto::uid::Uid;
+
+use proto::app_server::messages::{NamedRelayAddress, RelayAddress};
+use proto::funder::messages::{
+    AddFriend, CancelSendFundsOp, CollectSendFundsOp, FailureReason, FriendStatus, FriendTcOp,
+    FriendsRoute, MoveToken, PendingTransaction, Rate, Receipt, RequestSendFundsOp, RequestsStatus,
+    ResponseSendFundsOp, TransactionStage,
+};
+
+use crate::friend::{
+    BackwardsOp, ChannelInconsistent, ChannelStatus, FriendMutation, FriendState, SentLocalRelays,
+};
+use crate::mutual_credit::types::{
+    McBalance, McIdents, McMutation, McPendingTransactions, McRequestsStatus, MutualCredit,
+    MutualCreditState,
+};
+use crate::state::{
+    FunderMutation, FunderState, IncomingTransaction, NewTransactions, OpenInvoice,
+    OpenTransaction, Payment,
+};
+use crate::token_channel::{
+    SetDirection, TcDirection, TcIncoming, TcMutation, TcOutgoing, TokenChannel,
+};
+use crate::types::MoveTokenHashed;
+
+#[derive(Debug)]
+pub enum MigrateFunderError {
+    /// An incoming transaction has no matching pending remote request, hence the amount it pays
+    /// is unknown.
+    UnknownIncomingPayment(Uid),
+    /// A mutation adds an incoming transaction, but carries no amount.
+    AddIncomingTransaction(Uid),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RequestSendFundsOpV1 {
+    pub request_id: Uid,
+    pub src_hashed_lock: HashedLock,
+    pub route: FriendsRoute,
+    pub dest_payment: u128,
+    pub total_dest_payment: u128,
+    pub invoice_id: InvoiceId,
+    pub left_fees: u128,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CancelSendFundsOpV1 {
+    pub request_id: Uid,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum FriendTcOpV1 {
+    EnableRequests,
+    DisableRequests,
+    SetRemoteMaxDebt(u128),
+    RequestSendFunds(RequestSendFundsOpV1),
+    ResponseSendFunds(ResponseSendFundsOp),
+    CancelSendFunds(CancelSendFundsOpV1),
+    CollectSendFunds(CollectSendFundsOp),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct MoveTokenV1<B> {
+    pub operations: Vec<FriendTcOpV1>,
+    pub opt_local_relays: Option<Vec<RelayAddress<B>>>,
+    pub old_token: Signature,
+    pub local_public_key: PublicKey,
+    pub remote_public_key: PublicKey,
+    pub inconsistency_counter: u64,
+    pub move_token_counter: u128,
+    pub balance: i128,
+    pub local_pending_debt: u128,
+    pub remote_pending_debt: u128,
+    pub rand_nonce: RandValue,
+    pub new_token: Signature,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum BackwardsOpV1 {
+    Response(ResponseSendFundsOp),
+    Cancel(CancelSendFundsOpV1),
+    Collect(CollectSendFundsOp),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PendingTransactionV1 {
+    pub request_id: Uid,
+    pub route: FriendsRoute,
+    pub dest_payment: u128,
+    pub total_dest_payment: u128,
+    pub invoice_id: InvoiceId,
+    pub left_fees: u128,
+    pub src_hashed_lock: HashedLock,
+    pub stage: TransactionStage,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct McPendingTransactionsV1 {
+    pub local: ImHashMap<Uid, PendingTransactionV1>,
+    pub remote: ImHashMap<Uid, PendingTransactionV1>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct MutualCreditStateV1 {
+    pub idents: McIdents,
+    pub balance: McBalance,
+    pub pending_transactions: McPendingTransactionsV1,
+    pub requests_status: McRequestsStatus,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct MutualCreditV1 {
+    pub state: MutualCreditStateV1,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum McMutationV1 {
+    SetLocalRequestsStatus(RequestsStatus),
+    SetRemoteRequestsStatus(RequestsStatus),
+    SetLocalMaxDebt(u128),
+    SetRemoteMaxDebt(u128),
+    SetBalance(i128),
+    InsertLocalPendingTransaction(PendingTransactionV1),
+    RemoveLocalPendingTransaction(Uid),
+    SetLocalPendingTransactionStage((Uid, TransactionStage)),
+    InsertRemotePendingTransaction(PendingTransactionV1),
+    RemoveRemotePendingTransaction(Uid),
+    SetRemotePendingTransactionStage((Uid, TransactionStage)),
+    SetLocalPendingDebt(u128),
+    SetRemotePendingDebt(u128),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TcIncomingV1 {
+    pub mutual_credit: MutualCreditV1,
+    pub move_token_in: MoveTokenHashed,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TcOutgoingV1<B> {
+    pub mutual_credit: MutualCreditV1,
+    pub move_token_out: MoveTokenV1<B>,
+    pub opt_prev_move_token_in: Option<MoveTokenHashed>,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum TcDirectionV1<B> {
+    Incoming(TcIncomingV1),
+    Outgoing(TcOutgoingV1<B>),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TokenChannelV1<B> {
+    pub direction: TcDirectionV1<B>,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum ChannelStatusV1<B> {
+    Inconsistent(ChannelInconsistent),
+    Consistent(TokenChannelV1<B>),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct FriendStateV1<B: Clone> {
+    pub local_public_key: PublicKey,
+    pub remote_public_key: PublicKey,
+    pub remote_relays: Vec<RelayAddress<B>>,
+    pub sent_local_relays: SentLocalRelays<B>,
+    pub name: String,
+    pub rate: Rate,
+    pub status: FriendStatus,
+    pub channel_status: ChannelStatusV1<B>,
+    pub wanted_remote_max_debt: u128,
+    pub wanted_local_requests_status: RequestsStatus,
+    pub pending_requests: ImVec<RequestSendFundsOpV1>,
+    pub pending_backwards_ops: ImVec<BackwardsOpV1>,
+    pub pending_user_requests: ImVec<RequestSendFundsOpV1>,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SetDirectionV1<B> {
+    Incoming(MoveTokenHashed),
+    Outgoing(MoveTokenV1<B>),
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TcMutationV1<B> {
+    McMutation(McMutationV1),
+    SetDirection(SetDirectionV1<B>),
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FriendMutationV1<B: Clone> {
+    TcMutation(TcMutationV1<B>),
+    SetInconsistent(ChannelInconsistent),
+    SetConsistent(TokenChannelV1<B>),
+    SetWantedRemoteMaxDebt(u128),
+    SetWantedLocalRequestsStatus(RequestsStatus),
+    PushBackPendingRequest(RequestSendFundsOpV1),
+    PopFrontPendingRequest,
+    PushBackPendingBackwardsOp(BackwardsOpV1),
+    PopFrontPendingBackwardsOp,
+    PushBackPendingUserRequest(RequestSendFundsOpV1),
+    PopFrontPendingUserRequest,
+    SetStatus(FriendStatus),
+    SetRemoteRelays(Vec<RelayAddress<B>>),
+    SetName(String),
+    SetRate(Rate),
+    SetSentLocalRelays(SentLocalRelays<B>),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NewTransactionsV1 {
+    pub num_transactions: u64,
+    pub invoice_id: InvoiceId,
+    pub total_dest_payment: u128,
+    pub dest_public_key: PublicKey,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum PaymentV1 {
+    NewTransactions(NewTransactionsV1),
+    InProgress(u64),
+    Success((u64, Receipt, Uid)),
+    Canceled(Uid),
+    AfterSuccessAck(u64),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct IncomingTransactionV1 {
+    pub request_id: Uid,
+    pub dest_plain_lock: PlainLock,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct OpenInvoiceV1 {
+    pub total_dest_payment: u128,
+    pub incoming_transactions: ImHashMap<HashedLock, IncomingTransactionV1>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct FunderStateV1<B: Clone> {
+    pub local_public_key: PublicKey,
+    pub relays: ImVec<NamedRelayAddress<B>>,
+    pub friends: ImHashMap<PublicKey, FriendStateV1<B>>,
+    pub open_invoices: ImHashMap<InvoiceId, OpenInvoiceV1>,
+    pub open_transactions: ImHashMap<Uid, OpenTransaction>,
+    pub payments: ImHashMap<PaymentId, PaymentV1>,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FunderMutationV1<B: Clone> {
+    FriendMutation((PublicKey, FriendMutationV1<B>)),
+    AddRelay(NamedRelayAddress<B>),
+    RemoveRelay(PublicKey),
+    AddFriend(AddFriend<B>),
+    RemoveFriend(PublicKey),
+    AddInvoice((InvoiceId, u128)),
+    AddIncomingTransaction((InvoiceId, Uid, PlainLock)),
+    RemoveInvoice(InvoiceId),
+    AddTransaction((Uid, PaymentId, PlainLock)),
+    SetTransactionResponse(ResponseSendFundsOp),
+    RemoveTransaction(Uid),
+    UpdatePayment((PaymentId, PaymentV1)),
+    RemovePayment(PaymentId),
+}
+
+impl From<RequestSendFundsOpV1> for RequestSendFundsOp {
+    fn from(request_send_funds: RequestSendFundsOpV1) -> Self {
+        RequestSendFundsOp {
+            request_id: request_send_funds.request_id,
+            src_hashed_lock: request_send_funds.src_hashed_lock,
+            opt_encrypted_src_plain_lock: None,
+            route: request_send_funds.route,
+            dest_payment: request_send_funds.dest_payment,
+            total_dest_payment: request_send_funds.total_dest_payment,
+            invoice_id: request_send_funds.invoice_id,
+            left_fees: request_send_funds.left_fees,
+        }
+    }
+}
+
+impl CancelSendFundsOpV1 {
+    /// `reporting_public_key` is the public key of the node that sent the cancellation.
+    fn migrate(self, reporting_public_key: &PublicKey) -> CancelSendFundsOp {
+        CancelSendFundsOp {
+            request_id: self.request_id,
+            reporting_public_key: reporting_public_key.clone(),
+            reason: FailureReason::Rejected,
+        }
+    }
+}
+
+impl FriendTcOpV1 {
+    fn migrate(self, sender_public_key: &PublicKey) -> FriendTcOp {
+        match self {
+            FriendTcOpV1::EnableRequests => FriendTcOp::EnableRequests,
+            FriendTcOpV1::DisableRequests => FriendTcOp::DisableRequests,
+            FriendTcOpV1::SetRemoteMaxDebt(remote_max_debt) => {
+                FriendTcOp::SetRemoteMaxDebt(remote_max_debt)
+            }
+            FriendTcOpV1::RequestSendFunds(request_send_funds) => {
+                FriendTcOp::RequestSendFunds(request_send_funds.into())
+            }
+            FriendTcOpV1::ResponseSendFunds(response_send_funds) => {
+                FriendTcOp::ResponseSendFunds(response_send_funds)
+            }
+            FriendTcOpV1::CancelSendFunds(cancel_send_funds) => {
+                FriendTcOp::CancelSendFunds(cancel_send_funds.migrate(sender_public_key))
+            }
+            FriendTcOpV1::CollectSendFunds(collect_send_funds) => {
+                FriendTcOp::CollectSendFunds(collect_send_funds)
+            }
+        }
+    }
+}
+
+impl<B> From<MoveTokenV1<B>> for MoveToken<B> {
+    fn from(move_token: MoveTokenV1<B>) -> Self {
+        // The sender of a move token is its local side:
+        let local_public_key = move_token.local_public_key;
+        MoveToken {
+            operations: move_token
+                .operations
+                .into_iter()
+                .map(|operation| operation.migrate(&local_public_key))
+                .collect(),
+            opt_local_relays: move_token.opt_local_relays,
+            old_token: move_token.old_token,
+            local_public_key,
+            remote_public_key: move_token.remote_public_key,
+            inconsistency_counter: move_token.inconsistency_counter,
+            move_token_counter: move_token.move_token_counter,
+            balance: move_token.balance,
+            local_pending_debt: move_token.local_pending_debt,
+            remote_pending_debt: move_token.remote_pending_debt,
+            rand_nonce: move_token.rand_nonce,
+            new_token: move_token.new_token,
+        }
+    }
+}
+
+impl BackwardsOpV1 {
+    fn migrate(self, sender_public_key: &PublicKey) -> BackwardsOp {
+        match self {
+            BackwardsOpV1::Response(response_send_funds) => {
+                BackwardsOp::Response(response_send_funds)
+            }
+            BackwardsOpV1::Cancel(cancel_send_funds) => {
+                BackwardsOp::Cancel(cancel_send_funds.migrate(sender_public_key))
+            }
+            BackwardsOpV1::Collect(collect_send_funds) => BackwardsOp::Collect(collect_send_funds),
+        }
+    }
+}
+
+impl From<PendingTransactionV1> for PendingTransaction {
+    fn from(pending_transaction: PendingTransactionV1) -> Self {
+        PendingTransaction {
+            request_id: pending_transaction.request_id,
+            route: pending_transaction.route,
+            dest_payment: pending_transaction.dest_payment,
+            total_dest_payment: pending_transaction.total_dest_payment,
+            invoice_id: pending_transaction.invoice_id,
+            left_fees: pending_transaction.left_fees,
+            src_hashed_lock: pending_transaction.src_hashed_lock,
+            opt_encrypted_src_plain_lock: None,
+            stage: pending_transaction.stage,
+        }
+    }
+}
+
+impl From<MutualCreditV1> for MutualCredit {
+    fn from(mutual_credit: MutualCreditV1) -> Self {
+        let state = mutual_credit.state;
+        let pending_transactions = McPendingTransactions {
+            local: state
+                .pending_transactions
+                .local
+                .into_iter()
+                .map(|(request_id, pending_transaction)| (request_id, pending_transaction.into()))
+                .collect(),
+            remote: state
+                .pending_transactions
+                .remote
+                .into_iter()
+                .map(|(request_id, pending_transaction)| (request_id, pending_transaction.into()))
+                .collect(),
+        };
+        MutualCredit::from_state(MutualCreditState {
+            idents: state.idents,
+            balance: state.balance,
+            pending_transactions,
+            requests_status: state.requests_status,
+        })
+    }
+}
+
+impl From<McMutationV1> for McMutation {
+    fn from(mc_mutation: McMutationV1) -> Self {
+        match mc_mutation {
+            McMutationV1::SetLocalRequestsStatus(requests_status) => {
+                McMutation::SetLocalRequestsStatus(requests_status)
+            }
+            McMutationV1::SetRemoteRequestsStatus(requests_status) => {
+                McMutation::SetRemoteRequestsStatus(requests_status)
+            }
+            McMutationV1::SetLocalMaxDebt(local_max_debt) => {
+                McMutation::SetLocalMaxDebt(local_max_debt)
+            }
+            McMutationV1::SetRemoteMaxDebt(remote_max_debt) => {
+                McMutation::SetRemoteMaxDebt(remote_max_debt)
+            }
+            McMutationV1::SetBalance(balance) => McMutation::SetBalance(balance),
+            McMutationV1::InsertLocalPendingTransaction(pending_transaction) => {
+                McMutation::InsertLocalPendingTransaction(pending_transaction.into())
+            }
+            McMutationV1::RemoveLocalPendingTransaction(request_id) => {
+                McMutation::RemoveLocalPendingTransaction(request_id)
+            }
+            McMutationV1::SetLocalPendingTransactionStage(stage) => {
+                McMutation::SetLocalPendingTransactionStage(stage)
+            }
+            McMutationV1::InsertRemotePendingTransaction(pending_transaction) => {
+                McMutation::InsertRemotePendingTransaction(pending_transaction.into())
+            }
+            McMutationV1::RemoveRemotePendingTransaction(request_id) => {
+                McMutation::RemoveRemotePendingTransaction(request_id)
+            }
+            McMutationV1::SetRemotePendingTransactionStage(stage) => {
+                McMutation::SetRemotePendingTransactionStage(stage)
+            }
+            McMutationV1::SetLocalPendingDebt(local_pending_debt) => {
+                McMutation::SetLocalPendingDebt(local_pending_debt)
+            }
+            McMutationV1::SetRemotePendingDebt(remote_pending_debt) => {
+                McMutation::SetRemotePendingDebt(remote_pending_debt)
+            }
+        }
+    }
+}
+
+impl<B> From<TokenChannelV1<B>> for TokenChannel<B> {
+    fn from(token_channel: TokenChannelV1<B>) -> Self {
+        let direction = match token_channel.direction {
+            TcDirectionV1::Incoming(tc_incoming) => TcDirection::Incoming(TcIncoming {
+                mutual_credit: tc_incoming.mutual_credit.into(),
+                move_token_in: tc_incoming.move_token_in,
+            }),
+            TcDirectionV1::Outgoing(tc_outgoing) => TcDirection::Outgoing(TcOutgoing {
+                mutual_credit: tc_outgoing.mutual_credit.into(),
+                move_token_out: tc_outgoing.move_token_out.into(),
+                opt_prev_move_token_in: tc_outgoing.opt_prev_move_token_in,
+            }),
+        };
+        TokenChannel::from_direction(direction)
+    }
+}
+
+impl<B> From<ChannelStatusV1<B>> for ChannelStatus<B> {
+    fn from(channel_status: ChannelStatusV1<B>) -> Self {
+        match channel_status {
+            ChannelStatusV1::Inconsistent(channel_inconsistent) => {
+                ChannelStatus::Inconsistent(channel_inconsistent)
+            }
+            ChannelStatusV1::Consistent(token_channel) => {
+                ChannelStatus::Consistent(token_channel.into())
+            }
+        }
+    }
+}
+
+impl<B> From<FriendStateV1<B>> for FriendState<B>
+where
+    B: Clone,
+{
+    fn from(friend_state: FriendStateV1<B>) -> Self {
+        // Backwards operations are sent by the local node:
+        let local_public_key = friend_state.local_public_key;
+        let pending_backwards_ops = friend_state
+            .pending_backwards_ops
+            .into_iter()
+            .map(|backwards_op| backwards_op.migrate(&local_public_key))
+            .collect();
+
+        FriendState {
+            local_public_key,
+            remote_public_key: friend_state.remote_public_key,
+            remote_relays: friend_state.remote_relays,
+            sent_local_relays: friend_state.sent_local_relays,
+            name: friend_state.name,
+            rate: friend_state.rate,
+            status: friend_state.status,
+            channel_status: friend_state.channel_status.into(),
+            wanted_remote_max_debt: friend_state.wanted_remote_max_debt,
+            wanted_local_requests_status: friend_state.wanted_local_requests_status,
+            pending_requests: friend_state
+                .pending_requests
+                .into_iter()
+                .map(RequestSendFundsOp::from)
+                .collect(),
+            pending_backwards_ops,
+            pending_user_requests: friend_state
+                .pending_user_requests
+                .into_iter()
+                .map(RequestSendFundsOp::from)
+                .collect(),
+        }
+    }
+}
+
+impl<B> From<TcMutationV1<B>> for TcMutation<B> {
+    fn from(tc_mutation: TcMutationV1<B>) -> Self {
+        match tc_mutation {
+            TcMutationV1::McMutation(mc_mutation) => TcMutation::McMutation(mc_mutation.into()),
+            TcMutationV1::SetDirection(SetDirectionV1::Incoming(move_token_hashed)) => {
+                TcMutation::SetDirection(SetDirection::Incoming(move_token_hashed))
+            }
+            TcMutationV1::SetDirection(SetDirectionV1::Outgoing(move_token)) => {
+                TcMutation::SetDirection(SetDirection::Outgoing(move_token.into()))
+            }
+        }
+    }
+}
+
+impl<B> FriendMutationV1<B>
+where
+    B: Clone,
+{
+    /// A batch of mutations does not contain the local public key. Cancellations pushed to the
+    /// pending backwards operations queue are attributed to the friend instead.
+    /// (The reason `Rejected` is never used to choose a failing edge, so this only affects
+    /// reporting).
+    fn migrate(self, friend_public_key: &PublicKey) -> FriendMutation<B> {
+        match self {
+            FriendMutationV1::TcMutation(tc_mutation) => {
+                FriendMutation::TcMutation(tc_mutation.into())
+            }
+            FriendMutationV1::SetInconsistent(channel_inconsistent) => {
+                FriendMutation::SetInconsistent(channel_inconsistent)
+            }
+            FriendMutationV1::SetConsistent(token_channel) => {
+                FriendMutation::SetConsistent(token_channel.into())
+            }
+            FriendMutationV1::SetWantedRemoteMaxDebt(wanted_remote_max_debt) => {
+                FriendMutation::SetWantedRemoteMaxDebt(wanted_remote_max_debt)
+            }
+            FriendMutationV1::SetWantedLocalRequestsStatus(requests_status) => {
+                FriendMutation::SetWantedLocalRequestsStatus(requests_status)
+            }
+            FriendMutationV1::PushBackPendingRequest(request_send_funds) => {
+                FriendMutation::PushBackPendingRequest(request_send_funds.into())
+            }
+            FriendMutationV1::PopFrontPendingRequest => FriendMutation::PopFrontPendingRequest,
+            FriendMutationV1::PushBackPendingBackwardsOp(backwards_op) => {
+                FriendMutation::PushBackPendingBackwardsOp(backwards_op.migrate(friend_public_key))
+            }
+            FriendMutationV1::PopFrontPendingBackwardsOp => {
+                FriendMutation::PopFrontPendingBackwardsOp
+            }
+            FriendMutationV1::PushBackPendingUserRequest(request_send_funds) => {
+                FriendMutation::PushBackPendingUserRequest(request_send_funds.into())
+            }
+            FriendMutationV1::PopFrontPendingUserRequest => {
+                FriendMutation::PopFrontPendingUserRequest
+            }
+            FriendMutationV1::SetStatus(friend_status) => FriendMutation::SetStatus(friend_status),
+            FriendMutationV1::SetRemoteRelays(remote_relays) => {
+                FriendMutation::SetRemoteRelays(remote_relays)
+            }
+            FriendMutationV1::SetName(name) => FriendMutation::SetName(name),
+            FriendMutationV1::SetRate(rate) => FriendMutation::SetRate(rate),
+            FriendMutationV1::SetSentLocalRelays(sent_local_relays) => {
+                FriendMutation::SetSentLocalRelays(sent_local_relays)
+            }
+        }
+    }
+}
+
+impl From<PaymentV1> for Payment {
+    fn from(payment: PaymentV1) -> Self {
+        match payment {
+            PaymentV1::NewTransactions(new_transactions) => {
+                Payment::NewTransactions(NewTransactions {
+                    num_transactions: new_transactions.num_transactions,
+                    invoice_id: new_transactions.invoice_id,
+                    total_dest_payment: new_transactions.total_dest_payment,
+                    dest_public_key: new_transactions.dest_public_key,
+                    opt_dest_dh_public_key: None,
+                    // Payments created before per payment deadlines existed use the node's
+                    // default payment timeout:
+                    opt_deadline_ticks: None,
+                })
+            }
+            PaymentV1::InProgress(num_transactions) => {
+                Payment::InProgress((num_transactions, None))
+            }
+            PaymentV1::Success(success) => Payment::Success(success),
+            PaymentV1::Canceled(ack_uid) => Payment::Canceled(ack_uid),
+            PaymentV1::AfterSuccessAck(num_transactions) => {
+                Payment::AfterSuccessAck(num_transactions)
+            }
+        }
+    }
+}
+
+/// Find the amount paid by a pending remote request, at any of the friends.
+fn find_remote_dest_payment<B>(
+    friends: &ImHashMap<PublicKey, FriendStateV1<B>>,
+    request_id: &Uid,
+) -> Option<u128>
+where
+    B: Clone,
+{
+    friends
+        .values()
+        .filter_map(|friend| match &friend.channel_status {
+            ChannelStatusV1::Consistent(token_channel) => {
+                let mutual_credit = match &token_channel.direction {
+                    TcDirectionV1::Incoming(tc_incoming) => &tc_incoming.mutual_credit,
+                    TcDirectionV1::Outgoing(tc_outgoing) => &tc_outgoing.mutual_credit,
+                };
+                mutual_credit
+                    .state
+                    .pending_transactions
+                    .remote
+                    .get(request_id)
+                    .map(|pending_transaction| pending_transaction.dest_payment)
+            }
+            ChannelStatusV1::Inconsistent(_) => None,
+        })
+        .next()
+}
+
+impl OpenInvoiceV1 {
+    fn migrate<B>(
+        self,
+        friends: &ImHashMap<PublicKey, FriendStateV1<B>>,
+    ) -> Result<OpenInvoice, MigrateFunderError>
+    where
+        B: Clone,
+    {
+        let mut incoming_transactions = ImHashMap::new();
+        for (dest_hashed_lock, incoming_transaction) in self.incoming_transactions {
+            let dest_payment = find_remote_dest_payment(friends, &incoming_transaction.request_id)
+                .ok_or_else(|| {
+                    MigrateFunderError::UnknownIncomingPayment(
+                        incoming_transaction.request_id.clone(),
+                    )
+                })?;
+            let incoming_transaction = IncomingTransaction {
+                request_id: incoming_transaction.request_id,
+                dest_plain_lock: incoming_transaction.dest_plain_lock,
+                dest_payment,
+                opt_src_plain_lock: None,
+            };
+            incoming_transactions.insert(dest_hashed_lock, incoming_transaction);
+        }
+
+        Ok(OpenInvoice {
+            total_dest_payment: self.total_dest_payment,
+            incoming_transactions,
+            opt_expiry_ticks: None,
+            is_spontaneous: false,
+        })
+    }
+}
+
+impl<B> TryFrom<FunderStateV1<B>> for FunderState<B>
+where
+    B: Clone,
+{
+    type Error = MigrateFunderError;
+
+    fn try_from(funder_state: FunderStateV1<B>) -> Result<Self, Self::Error> {
+        let mut open_invoices = ImHashMap::new();
+        for (invoice_id, open_invoice) in funder_state.open_invoices {
+            open_invoices.insert(invoice_id, open_invoice.migrate(&funder_state.friends)?);
+        }
+
+        Ok(FunderState {
+            local_public_key: funder_state.local_public_key,
+            relays: funder_state.relays,
+            friends: funder_state
+                .friends
+                .into_iter()
+                .map(|(friend_public_key, friend_state)| (friend_public_key, friend_state.into()))
+                .collect(),
+            open_invoices,
+            open_transactions: funder_state.open_transactions,
+            payments: funder_state
+                .payments
+                .into_iter()
+                .map(|(payment_id, payment)| (payment_id, payment.into()))
+                .collect(),
+            accept_spontaneous_payments: false,
+        })
+    }
+}
+
+impl<B> TryFrom<FunderMutationV1<B>> for FunderMutation<B>
+where
+    B: Clone,
+{
+    type Error = MigrateFunderError;
+
+    fn try_from(funder_mutation: FunderMutationV1<B>) -> Result<Self, Self::Error> {
+        Ok(match funder_mutation {
+            FunderMutationV1::FriendMutation((friend_public_key, friend_mutation)) => {
+                let friend_mutation = friend_mutation.migrate(&friend_public_key);
+                FunderMutation::FriendMutation((friend_public_key, friend_mutation))
+            }
+            FunderMutationV1::AddRelay(named_relay_address) => {
+                FunderMutation::AddRelay(named_relay_address)
+            }
+            FunderMutationV1::RemoveRelay(public_key) => FunderMutation::RemoveRelay(public_key),
+            FunderMutationV1::AddFriend(add_friend) => FunderMutation::AddFriend(add_friend),
+            FunderMutationV1::RemoveFriend(public_key) => FunderMutation::RemoveFriend(public_key),
+            FunderMutationV1::AddInvoice((invoice_id, total_dest_payment)) => {
+                FunderMutation::AddInvoice((invoice_id, total_dest_payment, None))
+            }
+            FunderMutationV1::AddIncomingTransaction((
+                _invoice_id,
+                request_id,
+                _dest_plain_lock,
+            )) => {
+                return Err(MigrateFunderError::AddIncomingTransaction(request_id));
+            }
+            FunderMutationV1::RemoveInvoice(invoice_id) => {
+                FunderMutation::RemoveInvoice(invoice_id)
+            }
+            FunderMutationV1::AddTransaction(add_transaction) => {
+                FunderMutation::AddTransaction(add_transaction)
+            }
+            FunderMutationV1::SetTransactionResponse(response_send_funds) => {
+                FunderMutation::SetTransactionResponse(response_send_funds)
+            }
+            FunderMutationV1::RemoveTransaction(request_id) => {
+                FunderMutation::RemoveTransaction(request_id)
+            }
+            FunderMutationV1::UpdatePayment((payment_id, payment)) => {
+                FunderMutation::UpdatePayment((payment_id, payment.into()))
+            }
+            FunderMutationV1::RemovePayment(payment_id) => {
+                FunderMutation::RemovePayment(payment_id)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use byteorder::{BigEndian, WriteBytesExt};
+
+    use common::canonical_serialize::CanonicalSerialize;
+    use common::int_convert::usize_to_u64;
+
+    use crypto::crypto_rand::RAND_VALUE_LEN;
+    use crypto::hash::{sha_512_256, HashResult, HASH_RESULT_LEN};
+    use crypto::hash_lock::{HASHED_LOCK_LEN, PLAIN_LOCK_LEN};
+    use crypto::identity::{
+        generate_pkcs8_key_pair, Identity, SoftwareEd25519Identity, PUBLIC_KEY_LEN, SIGNATURE_LEN,
+    };
+    use crypto::invoice_id::INVOICE_ID_LEN;
+    use crypto::payment_id::PAYMENT_ID_LEN;
+    use crypto::test_utils::DummyRandom;
+    use crypto::uid::UID_LEN;
+
+    use proto::funder::signature_buff::verify_move_token;
+
+    #[test]
+    fn test_migrate_funder_state_v1_payments() {
+        let local_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let mut funder_state_v1 = FunderStateV1::<u32> {
+            local_public_key: local_public_key.clone(),
+            relays: ImVec::new(),
+            friends: ImHashMap::new(),
+            open_invoices: ImHashMap::new(),
+            open_transactions: ImHashMap::new(),
+            payments: ImHashMap::new(),
+        };
+
+        let new_transactions = NewTransactionsV1 {
+            num_transactions: 2,
+            invoice_id: InvoiceId::from(&[1; INVOICE_ID_LEN]),
+            total_dest_payment: 100,
+            dest_public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+        };
+        funder_state_v1.payments.insert(
+            PaymentId::from(&[1; PAYMENT_ID_LEN]),
+            PaymentV1::NewTransactions(new_transactions),
+        );
+        funder_state_v1.payments.insert(
+            PaymentId::from(&[2; PAYMENT_ID_LEN]),
+            PaymentV1::InProgress(3),
+        );
+        funder_state_v1.payments.insert(
+            PaymentId::from(&[3; PAYMENT_ID_LEN]),
+            PaymentV1::Canceled(Uid::from(&[4; UID_LEN])),
+        );
+        funder_state_v1.payments.insert(
+            PaymentId::from(&[4; PAYMENT_ID_LEN]),
+            PaymentV1::AfterSuccessAck(5),
+        );
+
+        // Go through the serialized form, as done when loading a database:
+        let data = bincode::serialize(&funder_state_v1).unwrap();
+        let funder_state_v1: FunderStateV1<u32> = bincode::deserialize(&data).unwrap();
+        let funder_state = FunderState::try_from(funder_state_v1).unwrap();
+
+        assert_eq!(funder_state.local_public_key, local_public_key);
+        assert_eq!(funder_state.payments.len(), 4);
+
+        match funder_state
+            .payments
+            .get(&PaymentId::from(&[1; PAYMENT_ID_LEN]))
+            .unwrap()
+        {
+            Payment::NewTransactions(new_transactions) => {
+                assert_eq!(new_transactions.num_transactions, 2);
+                assert_eq!(new_transactions.total_dest_payment, 100);
+                assert_eq!(new_transactions.opt_deadline_ticks, None);
+                assert_eq!(new_transactions.opt_dest_dh_public_key, None);
+            }
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            funder_state
+                .payments
+                .get(&PaymentId::from(&[2; PAYMENT_ID_LEN]))
+                .unwrap(),
+            &Payment::InProgress((3, None))
+        );
+        assert_eq!(
+            funder_state
+                .payments
+                .get(&PaymentId::from(&[3; PAYMENT_ID_LEN]))
+                .unwrap(),
+            &Payment::Canceled(Uid::from(&[4; UID_LEN]))
+        );
+        assert_eq!(
+            funder_state
+                .payments
+                .get(&PaymentId::from(&[4; PAYMENT_ID_LEN]))
+                .unwrap(),
+            &Payment::AfterSuccessAck(5)
+        );
+    }
+
+    #[test]
+    fn test_migrate_funder_mutation_v1_update_payment() {
+        let payment_id = PaymentId::from(&[1; PAYMENT_ID_LEN]);
+        let funder_mutation_v1 =
+            FunderMutationV1::<u32>::UpdatePayment((payment_id, PaymentV1::InProgress(7)));
+
+        let data = bincode::serialize(&funder_mutation_v1).unwrap();
+        let funder_mutation_v1: FunderMutationV1<u32> = bincode::deserialize(&data).unwrap();
+
+        match FunderMutation::try_from(funder_mutation_v1).unwrap() {
+            FunderMutation::UpdatePayment((cur_payment_id, payment)) => {
+                assert_eq!(cur_payment_id, payment_id);
+                assert_eq!(payment, Payment::InProgress((7, None)));
+            }
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn test_migrate_funder_state_v1_open_invoices() {
+        let local_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let friend_public_key = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+        let invoice_id = InvoiceId::from(&[1; INVOICE_ID_LEN]);
+
+        // A friend with a pending remote request, paying 30 credits for the invoice:
+        let mut mutual_credit = MutualCreditV1 {
+            state: MutualCreditStateV1 {
+                idents: McIdents {
+                    local_public_key: local_public_key.clone(),
+                    remote_public_key: friend_public_key.clone(),
+                },
+                balance: McBalance {
+                    balance: 0,
+                    local_max_debt: 0,
+                    remote_max_debt: 100,
+                    local_pending_debt: 0,
+                    remote_pending_debt: 30,
+                },
+                pending_transactions: McPendingTransactionsV1 {
+                    local: ImHashMap::new(),
+                    remote: ImHashMap::new(),
+                },
+                requests_status: McRequestsStatus {
+                    local: RequestsStatus::Open,
+                    remote: RequestsStatus::Closed,
+                },
+            },
+        };
+        mutual_credit.state.pending_transactions.remote.insert(
+            Uid::from(&[2; UID_LEN]),
+            PendingTransactionV1 {
+                request_id: Uid::from(&[2; UID_LEN]),
+                route: FriendsRoute {
+                    public_keys: vec![friend_public_key.clone(), local_public_key.clone()],
+                },
+                dest_payment: 30,
+                total_dest_payment: 100,
+                invoice_id: invoice_id.clone(),
+                left_fees: 0,
+                src_hashed_lock: HashedLock::from(&[3; HASHED_LOCK_LEN]),
+                stage: TransactionStage::Response(HashedLock::from(&[4; HASHED_LOCK_LEN])),
+            },
+        );
+        let move_token_in = MoveTokenHashed {
+            prefix_hash: HashResult::from(&[5; HASH_RESULT_LEN]),
+            local_public_key: friend_public_key.clone(),
+            remote_public_key: local_public_key.clone(),
+            inconsistency_counter: 0,
+            move_token_counter: 1,
+            balance: 0,
+            local_pending_debt: 30,
+            remote_pending_debt: 0,
+            rand_nonce: RandValue::from(&[6; RAND_VALUE_LEN]),
+            new_token: Signature::from(&[7; SIGNATURE_LEN]),
+        };
+        let friend_state_v1 = FriendStateV1::<u32> {
+            local_public_key: local_public_key.clone(),
+            remote_public_key: friend_public_key.clone(),
+            remote_relays: Vec::new(),
+            sent_local_relays: SentLocalRelays::NeverSent,
+            name: "friend".to_owned(),
+            rate: Rate::new(),
+            status: FriendStatus::Enabled,
+            channel_status: ChannelStatusV1::Consistent(TokenChannelV1 {
+                direction: TcDirectionV1::Incoming(TcIncomingV1 {
+                    mutual_credit,
+                    move_token_in,
+                }),
+            }),
+            wanted_remote_max_debt: 0,
+            wanted_local_requests_status: RequestsStatus::Open,
+            pending_requests: ImVec::new(),
+            pending_backwards_ops: ImVec::new(),
+            pending_user_requests: ImVec::new(),
+        };
+
+        let mut open_invoice_v1 = OpenInvoiceV1 {
+            total_dest_payment: 100,
+            incoming_transactions: ImHashMap::new(),
+        };
+        open_invoice_v1.incoming_transactions.insert(
+            HashedLock::from(&[4; HASHED_LOCK_LEN]),
+            IncomingTransactionV1 {
+                request_id: Uid::from(&[2; UID_LEN]),
+                dest_plain_lock: PlainLock::from(&[8; PLAIN_LOCK_LEN]),
+            },
+        );
+
+        let mut funder_state_v1 = FunderStateV1::<u32> {
+            local_public_key: local_public_key.clone(),
+            relays: ImVec::new(),
+            friends: ImHashMap::new(),
+            open_invoices: ImHashMap::new(),
+            open_transactions: ImHashMap::new(),
+            payments: ImHashMap::new(),
+        };
+        funder_state_v1
+            .friends
+            .insert(friend_public_key.clone(), friend_state_v1);
+        funder_state_v1
+            .open_invoices
+            .insert(invoice_id.clone(), open_invoice_v1);
+
+        let data = bincode::serialize(&funder_state_v1).unwrap();
+        let funder_state_v1: FunderStateV1<u32> = bincode::deserialize(&data).unwrap();
+        let funder_state = FunderState::try_from(funder_state_v1.clone()).unwrap();
+
+        let open_invoice = funder_state.open_invoices.get(&invoice_id).unwrap();
+        assert_eq!(open_invoice.total_dest_payment, 100);
+        assert_eq!(open_invoice.opt_expiry_ticks, None);
+        assert!(!open_invoice.is_spontaneous);
+        assert_eq!(open_invoice.incoming_transactions.len(), 1);
+
+        let incoming_transaction = open_invoice
+            .incoming_transactions
+            .get(&HashedLock::from(&[4; HASHED_LOCK_LEN]))
+            .unwrap();
+        assert_eq!(incoming_transaction.request_id, Uid::from(&[2; UID_LEN]));
+        assert_eq!(
+            incoming_transaction.dest_plain_lock,
+            PlainLock::from(&[8; PLAIN_LOCK_LEN])
+        );
+        assert_eq!(incoming_transaction.dest_payment, 30);
+        assert_eq!(incoming_transaction.opt_src_plain_lock, None);
+
+        // The pending request itself is migrated as a non spontaneous request:
+        assert!(!funder_state.accept_spontaneous_payments);
+        let friend = funder_state.friends.get(&friend_public_key).unwrap();
+        let token_channel = match &friend.channel_status {
+            ChannelStatus::Consistent(token_channel) => token_channel,
+            ChannelStatus::Inconsistent(_) => unreachable!(),
+        };
+        let pending_transaction = token_channel
+            .get_mutual_credit()
+            .state()
+            .pending_transactions
+            .remote
+            .get(&Uid::from(&[2; UID_LEN]))
+            .unwrap();
+        assert_eq!(pending_transaction.dest_payment, 30);
+        assert_eq!(
+            pending_transaction.src_hashed_lock,
+            HashedLock::from(&[3; HASHED_LOCK_LEN])
+        );
+        assert_eq!(pending_transaction.opt_encrypted_src_plain_lock, None);
+        assert_eq!(
+            token_channel
+                .get_mutual_credit()
+                .state()
+                .balance
+                .remote_pending_debt,
+            30
+        );
+
+        // An incoming transaction that does not match any pending request pays an unknown
+        // amount, hence the state can not be migrated:
+        let mut funder_state_v1 = funder_state_v1;
+        let open_invoice_v1 = funder_state_v1.open_invoices.get_mut(&invoice_id).unwrap();
+        open_invoice_v1.incoming_transactions.insert(
+            HashedLock::from(&[9; HASHED_LOCK_LEN]),
+            IncomingTransactionV1 {
+                request_id: Uid::from(&[10; UID_LEN]),
+                dest_plain_lock: PlainLock::from(&[11; PLAIN_LOCK_LEN]),
+            },
+        );
+        match FunderState::try_from(funder_state_v1) {
+            Err(MigrateFunderError::UnknownIncomingPayment(request_id)) => {
+                assert_eq!(request_id, Uid::from(&[10; UID_LEN]))
+            }
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn test_migrate_funder_mutation_v1_invoices() {
+        let invoice_id = InvoiceId::from(&[1; INVOICE_ID_LEN]);
+
+        let funder_mutation_v1 = FunderMutationV1::<u32>::AddInvoice((invoice_id.clone(), 100));
+        let data = bincode::serialize(&funder_mutation_v1).unwrap();
+        let funder_mutation_v1: FunderMutationV1<u32> = bincode::deserialize(&data).unwrap();
+
+        match FunderMutation::try_from(funder_mutation_v1).unwrap() {
+            FunderMutation::AddInvoice((cur_invoice_id, total_dest_payment, opt_expiry_ticks)) => {
+                assert_eq!(cur_invoice_id, invoice_id);
+                assert_eq!(total_dest_payment, 100);
+                assert_eq!(opt_expiry_ticks, None);
+            }
+            _ => unreachable!(),
+        };
+
+        let funder_mutation_v1 = FunderMutationV1::<u32>::AddIncomingTransaction((
+            invoice_id.clone(),
+            Uid::from(&[2; UID_LEN]),
+            PlainLock::from(&[3; PLAIN_LOCK_LEN]),
+        ));
+        let data = bincode::serialize(&funder_mutation_v1).unwrap();
+        let funder_mutation_v1: FunderMutationV1<u32> = bincode::deserialize(&data).unwrap();
+
+        // The amount paid by the incoming transaction is unknown:
+        match FunderMutation::try_from(funder_mutation_v1) {
+            Err(MigrateFunderError::AddIncomingTransaction(request_id)) => {
+                assert_eq!(request_id, Uid::from(&[2; UID_LEN]))
+            }
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn test_migrate_funder_mutation_v1_requests() {
+        let local_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let friend_public_key = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+        let request_send_funds = RequestSendFundsOpV1 {
+            request_id: Uid::from(&[1; UID_LEN]),
+            src_hashed_lock: HashedLock::from(&[2; HASHED_LOCK_LEN]),
+            route: FriendsRoute {
+                public_keys: vec![local_public_key.clone(), friend_public_key.clone()],
+            },
+            dest_payment: 20,
+            total_dest_payment: 50,
+            invoice_id: InvoiceId::from(&[3; INVOICE_ID_LEN]),
+            left_fees: 4,
+        };
+
+        let funder_mutation_v1 = FunderMutationV1::<u32>::FriendMutation((
+            friend_public_key.clone(),
+            FriendMutationV1::PushBackPendingUserRequest(request_send_funds.clone()),
+        ));
+        let data = bincode::serialize(&funder_mutation_v1).unwrap();
+        let funder_mutation_v1: FunderMutationV1<u32> = bincode::deserialize(&data).unwrap();
+
+        match FunderMutation::try_from(funder_mutation_v1).unwrap() {
+            FunderMutation::FriendMutation((
+                cur_friend_public_key,
+                FriendMutation::PushBackPendingUserRequest(cur_request_send_funds),
+            )) => {
+                assert_eq!(cur_friend_public_key, friend_public_key);
+                assert_eq!(cur_request_send_funds.request_id, Uid::from(&[1; UID_LEN]));
+                assert_eq!(cur_request_send_funds.dest_payment, 20);
+                assert_eq!(cur_request_send_funds.left_fees, 4);
+                assert_eq!(cur_request_send_funds.opt_encrypted_src_plain_lock, None);
+            }
+            _ => unreachable!(),
+        };
+
+        let pending_transaction = PendingTransactionV1 {
+            request_id: request_send_funds.request_id.clone(),
+            route: request_send_funds.route.clone(),
+            dest_payment: request_send_funds.dest_payment,
+            total_dest_payment: request_send_funds.total_dest_payment,
+            invoice_id: request_send_funds.invoice_id.clone(),
+            left_fees: request_send_funds.left_fees,
+            src_hashed_lock: request_send_funds.src_hashed_lock.clone(),
+            stage: TransactionStage::Request,
+        };
+        let funder_mutation_v1 = FunderMutationV1::<u32>::FriendMutation((
+            friend_public_key.clone(),
+            FriendMutationV1::TcMutation(TcMutationV1::McMutation(
+                McMutationV1::InsertLocalPendingTransaction(pending_transaction),
+            )),
+        ));
+        let data = bincode::serialize(&funder_mutation_v1).unwrap();
+        let funder_mutation_v1: FunderMutationV1<u32> = bincode::deserialize(&data).unwrap();
+
+        match FunderMutation::try_from(funder_mutation_v1).unwrap() {
+            FunderMutation::FriendMutation((
+                _,
+                FriendMutation::TcMutation(TcMutation::McMutation(
+                    McMutation::InsertLocalPendingTransaction(pending_transaction),
+                )),
+            )) => {
+                assert_eq!(pending_transaction.request_id, Uid::from(&[1; UID_LEN]));
+                assert_eq!(pending_transaction.stage, TransactionStage::Request);
+                assert_eq!(pending_transaction.opt_encrypted_src_plain_lock, None);
+            }
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn test_migrate_move_token_v1_cancel() {
+        let local_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let move_token_v1 = MoveTokenV1::<u32> {
+            operations: vec![
+                FriendTcOpV1::EnableRequests,
+                FriendTcOpV1::CancelSendFunds(CancelSendFundsOpV1 {
+                    request_id: Uid::from(&[5; UID_LEN]),
+                }),
+            ],
+            opt_local_relays: None,
+            old_token: Signature::from(&[1; SIGNATURE_LEN]),
+            local_public_key: local_public_key.clone(),
+            remote_public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+            inconsistency_counter: 2,
+            move_token_counter: 3,
+            balance: -4,
+            local_pending_debt: 5,
+            remote_pending_debt: 6,
+            rand_nonce: RandValue::from(&[7; RAND_VALUE_LEN]),
+            new_token: Signature::from(&[8; SIGNATURE_LEN]),
+        };
+
+        let data = bincode::serialize(&move_token_v1).unwrap();
+        let move_token_v1: MoveTokenV1<u32> = bincode::deserialize(&data).unwrap();
+        let move_token = MoveToken::from(move_token_v1);
+
+        assert_eq!(move_token.operations.len(), 2);
+        assert_eq!(move_token.operations[0], FriendTcOp::EnableRequests);
+        assert_eq!(
+            move_token.operations[1],
+            FriendTcOp::CancelSendFunds(CancelSendFundsOp {
+                request_id: Uid::from(&[5; UID_LEN]),
+                reporting_public_key: local_public_key.clone(),
+                reason: FailureReason::Rejected,
+            })
+        );
+        assert_eq!(move_token.local_public_key, local_public_key);
+        assert_eq!(move_token.balance, -4);
+        assert_eq!(move_token.new_token, Signature::from(&[8; SIGNATURE_LEN]));
+    }
+
+    /// The buffer a move token was signed over in schema version 1, built by hand from the
+    /// schema version 1 layout of the operations.
+    fn move_token_v1_signature_buff(move_token_v1: &MoveTokenV1<u32>) -> Vec<u8> {
+        let mut hash_buff = Vec::new();
+        hash_buff.extend_from_slice(&move_token_v1.old_token);
+        hash_buff
+            .write_u64::<BigEndian>(usize_to_u64(move_token_v1.operations.len()).unwrap())
+            .unwrap();
+        for op in &move_token_v1.operations {
+            match op {
+                FriendTcOpV1::EnableRequests => hash_buff.push(0u8),
+                FriendTcOpV1::CancelSendFunds(cancel_send_funds) => {
+                    hash_buff.push(5u8);
+                    hash_buff.extend_from_slice(&cancel_send_funds.request_id);
+                }
+                _ => unimplemented!(),
+            }
+        }
+        hash_buff.extend_from_slice(&move_token_v1.opt_local_relays.canonical_serialize());
+
+        let mut sig_buffer = Vec::new();
+        sig_buffer.extend_from_slice(&sha_512_256(b"NEXT"));
+        sig_buffer.extend_from_slice(&sha_512_256(&hash_buff));
+        sig_buffer.extend_from_slice(&move_token_v1.local_public_key);
+        sig_buffer.extend_from_slice(&move_token_v1.remote_public_key);
+        sig_buffer
+            .write_u64::<BigEndian>(move_token_v1.inconsistency_counter)
+            .unwrap();
+        sig_buffer
+            .write_u128::<BigEndian>(move_token_v1.move_token_counter)
+            .unwrap();
+        sig_buffer
+            .write_i128::<BigEndian>(move_token_v1.balance)
+            .unwrap();
+        sig_buffer
+            .write_u128::<BigEndian>(move_token_v1.local_pending_debt)
+            .unwrap();
+        sig_buffer
+            .write_u128::<BigEndian>(move_token_v1.remote_pending_debt)
+            .unwrap();
+        sig_buffer.extend_from_slice(&move_token_v1.rand_nonce);
+        sig_buffer
+    }
+
+    #[test]
+    fn test_migrate_move_token_v1_cancel_signature() {
+        let rng = DummyRandom::new(&[1u8]);
+        let pkcs8 = generate_pkcs8_key_pair(&rng);
+        let identity = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+        let local_public_key = identity.get_public_key();
+
+        let mut move_token_v1 = MoveTokenV1::<u32> {
+            operations: vec![
+                FriendTcOpV1::EnableRequests,
+                FriendTcOpV1::CancelSendFunds(CancelSendFundsOpV1 {
+                    request_id: Uid::from(&[5; UID_LEN]),
+                }),
+            ],
+            opt_local_relays: None,
+            old_token: Signature::from(&[1; SIGNATURE_LEN]),
+            local_public_key: local_public_key.clone(),
+            remote_public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+            inconsistency_counter: 2,
+            move_token_counter: 3,
+            balance: -4,
+            local_pending_debt: 5,
+            remote_pending_debt: 6,
+            rand_nonce: RandValue::from(&[7; RAND_VALUE_LEN]),
+            new_token: Signature::from(&[0; SIGNATURE_LEN]),
+        };
+        move_token_v1.new_token = identity.sign(&move_token_v1_signature_buff(&move_token_v1));
+
+        let data = bincode::serialize(&move_token_v1).unwrap();
+        let move_token_v1: MoveTokenV1<u32> = bincode::deserialize(&data).unwrap();
+        let move_token = MoveToken::from(move_token_v1);
+
+        // The signature made before the migration is still valid:
+        assert!(verify_move_token(&move_token, &local_public_key));
+    }
+
+    #[test]
+    fn test_migrate_funder_mutation_v1_cancel() {
+        let friend_public_key = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+        let backwards_op = BackwardsOpV1::Cancel(CancelSendFundsOpV1 {
+            request_id: Uid::from(&[5; UID_LEN]),
+        });
+        let funder_mutation_v1 = FunderMutationV1::<u32>::FriendMutation((
+            friend_public_key.clone(),
+            FriendMutationV1::PushBackPendingBackwardsOp(backwards_op),
+        ));
+
+        let data = bincode::serialize(&funder_mutation_v1).unwrap();
+        let funder_mutation_v1: FunderMutationV1<u32> = bincode::deserialize(&data).unwrap();
+
+        match FunderMutation::try_from(funder_mutation_v1).unwrap() {
+            FunderMutation::FriendMutation((
+                cur_friend_public_key,
+                FriendMutation::PushBackPendingBackwardsOp(BackwardsOp::Cancel(cancel_send_funds)),
+            )) => {
+                assert_eq!(cur_friend_public_key, friend_public_key);
+                assert_eq!(cancel_send_funds.request_id, Uid::from(&[5; UID_LEN]));
+                assert_eq!(cancel_send_funds.reporting_public_key, friend_public_key);
+                assert_eq!(cancel_send_funds.reason, FailureReason::Rejected);
+            }
+            _ => unreachable!(),
+        };
+    }
+}
@@ -10,8 +10,8 @@ use crypto::crypto_rand::{RandValue, RAND_VALUE_LEN};
 use crypto::invoice_id::{InvoiceId, INVOICE_ID_LEN};
 
 use proto::funder::messages::{
-    CancelSendFundsOp, CollectSendFundsOp, FriendTcOp, FriendsRoute, RequestSendFundsOp,
-    RequestsStatus, ResponseSendFundsOp,
+    CancelSendFundsOp, CollectSendFundsOp, FailureReason, FriendTcOp, FriendsRoute,
+    RequestSendFundsOp, RequestsStatus, ResponseSendFundsOp,
 };
 use proto::funder::signature_buff::create_response_signature_buffer;
 
@@ -132,6 +132,7 @@ fn test_request_response_collect_send_funds() {
     let request_send_funds = RequestSendFundsOp {
         request_id: request_id.clone(),
         src_hashed_lock: src_plain_lock.hash(),
+        opt_encrypted_src_plain_lock: None,
         route,
         dest_payment: 10,
         total_dest_payment: 10,
@@ -202,6 +203,127 @@ fn test_request_response_collect_send_funds() {
     assert_eq!(mutual_credit.state().balance.remote_pending_debt, 0);
 }
 
+/// A Collect message can only be processed after a Response, and only if it reveals the correct
+/// preimages for both the source and destination hash locks.
+#[test]
+fn test_collect_send_funds_invalid_locks() {
+    let local_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+    let remote_public_key = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+    let balance = 0;
+    let mut mutual_credit = MutualCredit::new(&local_public_key, &remote_public_key, balance);
+
+    apply_incoming(&mut mutual_credit, FriendTcOp::SetRemoteMaxDebt(100)).unwrap();
+    apply_incoming(&mut mutual_credit, FriendTcOp::EnableRequests).unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let pkcs8 = generate_pkcs8_key_pair(&rng);
+    let identity = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+    let public_key_c = identity.get_public_key();
+
+    let request_id = Uid::from(&[3; UID_LEN]);
+    let route = FriendsRoute {
+        public_keys: vec![
+            PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]),
+            PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+            public_key_c.clone(),
+        ],
+    };
+    let src_plain_lock = PlainLock::from(&[1; PLAIN_LOCK_LEN]);
+    let dest_plain_lock = PlainLock::from(&[2; PLAIN_LOCK_LEN]);
+    let wrong_plain_lock = PlainLock::from(&[9; PLAIN_LOCK_LEN]);
+
+    let request_send_funds = RequestSendFundsOp {
+        request_id: request_id.clone(),
+        src_hashed_lock: src_plain_lock.hash(),
+        opt_encrypted_src_plain_lock: None,
+        route,
+        dest_payment: 10,
+        total_dest_payment: 10,
+        invoice_id: InvoiceId::from(&[0; INVOICE_ID_LEN]),
+        left_fees: 5,
+    };
+
+    let pending_transaction = create_pending_transaction(&request_send_funds);
+    apply_outgoing(
+        &mut mutual_credit,
+        &FriendTcOp::RequestSendFunds(request_send_funds),
+    )
+    .unwrap();
+
+    // Collect before a response was received:
+    let collect_send_funds = CollectSendFundsOp {
+        request_id: request_id.clone(),
+        src_plain_lock: src_plain_lock.clone(),
+        dest_plain_lock: dest_plain_lock.clone(),
+    };
+    match apply_incoming(
+        &mut mutual_credit,
+        FriendTcOp::CollectSendFunds(collect_send_funds),
+    ) {
+        Err(ProcessOperationError::NotExpectingCollect) => {}
+        _ => unreachable!(),
+    };
+
+    let mut response_send_funds = ResponseSendFundsOp {
+        request_id: request_id.clone(),
+        dest_hashed_lock: dest_plain_lock.hash(),
+        rand_nonce: RandValue::from(&[5; RAND_VALUE_LEN]),
+        signature: Signature::from(&[0; SIGNATURE_LEN]),
+    };
+    let sign_buffer = create_response_signature_buffer(&response_send_funds, &pending_transaction);
+    response_send_funds.signature = identity.sign(&sign_buffer);
+    apply_incoming(
+        &mut mutual_credit,
+        FriendTcOp::ResponseSendFunds(response_send_funds),
+    )
+    .unwrap();
+
+    // Wrong source lock:
+    let collect_send_funds = CollectSendFundsOp {
+        request_id: request_id.clone(),
+        src_plain_lock: wrong_plain_lock.clone(),
+        dest_plain_lock: dest_plain_lock.clone(),
+    };
+    match apply_incoming(
+        &mut mutual_credit,
+        FriendTcOp::CollectSendFunds(collect_send_funds),
+    ) {
+        Err(ProcessOperationError::InvalidSrcPlainLock) => {}
+        _ => unreachable!(),
+    };
+
+    // Wrong destination lock:
+    let collect_send_funds = CollectSendFundsOp {
+        request_id: request_id.clone(),
+        src_plain_lock: src_plain_lock.clone(),
+        dest_plain_lock: wrong_plain_lock,
+    };
+    match apply_incoming(
+        &mut mutual_credit,
+        FriendTcOp::CollectSendFunds(collect_send_funds),
+    ) {
+        Err(ProcessOperationError::InvalidDestPlainLock) => {}
+        _ => unreachable!(),
+    };
+
+    // Credits are still frozen:
+    assert_eq!(mutual_credit.state().balance.balance, 0);
+    assert_eq!(mutual_credit.state().balance.local_pending_debt, 10 + 5);
+
+    let collect_send_funds = CollectSendFundsOp {
+        request_id,
+        src_plain_lock,
+        dest_plain_lock,
+    };
+    apply_incoming(
+        &mut mutual_credit,
+        FriendTcOp::CollectSendFunds(collect_send_funds),
+    )
+    .unwrap();
+    assert_eq!(mutual_credit.state().balance.balance, -15);
+    assert_eq!(mutual_credit.state().balance.local_pending_debt, 0);
+}
+
 #[test]
 fn test_request_cancel_send_funds() {
     let rng = DummyRandom::new(&[1u8]);
@@ -240,6 +362,7 @@ fn test_request_cancel_send_funds() {
     let request_send_funds = RequestSendFundsOp {
         request_id: request_id.clone(),
         src_hashed_lock: src_plain_lock.hash(),
+        opt_encrypted_src_plain_lock: None,
         route,
         dest_payment: 10,
         total_dest_payment: 10,
@@ -261,7 +384,11 @@ fn test_request_cancel_send_funds() {
 
     // -----[CancelSendFunds]--------
     // ------------------------------
-    let cancel_send_funds = CancelSendFundsOp { request_id };
+    let cancel_send_funds = CancelSendFundsOp {
+        request_id,
+        reporting_public_key: PublicKey::from(&[0xcc; PUBLIC_KEY_LEN]),
+        reason: FailureReason::NoCapacity,
+    };
 
     apply_incoming(
         &mut mutual_credit,
@@ -314,6 +441,7 @@ fn test_request_response_cancel_send_funds() {
     let request_send_funds = RequestSendFundsOp {
         request_id: request_id.clone(),
         src_hashed_lock: src_plain_lock.hash(),
+        opt_encrypted_src_plain_lock: None,
         route,
         dest_payment: 10,
         total_dest_payment: 10,
@@ -364,7 +492,11 @@ fn test_request_response_cancel_send_funds() {
 
     // -----[CancelSendFunds]--------
     // ------------------------------
-    let cancel_send_funds = CancelSendFundsOp { request_id };
+    let cancel_send_funds = CancelSendFundsOp {
+        request_id,
+        reporting_public_key: PublicKey::from(&[0xcc; PUBLIC_KEY_LEN]),
+        reason: FailureReason::NoCapacity,
+    };
 
     apply_incoming(
         &mut mutual_credit,
@@ -139,6 +139,11 @@ impl MutualCredit {
         }
     }
 
+    /// Restore a mutual credit from its state (For example, when importing an exported state).
+    pub fn from_state(state: MutualCreditState) -> MutualCredit {
+        MutualCredit { state }
+    }
+
     /// Calculate required balance for reset.
     /// This would be current balance plus additional future profits.
     pub fn balance_for_reset(&self) -> i128 {
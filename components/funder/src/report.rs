@@ -3,6 +3,8 @@ use im::hashmap::HashMap as ImHashMap;
 use common::canonical_serialize::CanonicalSerialize;
 use common::int_convert::usize_to_u64;
 
+use crypto::dh::DhPublicKey;
+
 use proto::report::messages::{
     AddFriendReport, ChannelInconsistentReport, ChannelStatusReport, DirectionReport,
     FriendLivenessReport, FriendReport, FriendReportMutation, FriendStatusReport, FunderReport,
@@ -156,7 +158,11 @@ where
     }
 }
 
-pub fn create_report<B>(funder_state: &FunderState<B>, ephemeral: &Ephemeral) -> FunderReport<B>
+pub fn create_report<B>(
+    funder_state: &FunderState<B>,
+    ephemeral: &Ephemeral,
+    local_dh_public_key: &DhPublicKey,
+) -> FunderReport<B>
 where
     B: Clone + CanonicalSerialize,
 {
@@ -173,19 +179,24 @@ where
 
     FunderReport {
         local_public_key: funder_state.local_public_key.clone(),
+        local_dh_public_key: local_dh_public_key.clone(),
         relays: funder_state.relays.clone(),
         friends,
         num_open_invoices: usize_to_u64(funder_state.open_invoices.len()).unwrap(),
         num_payments: usize_to_u64(funder_state.payments.len()).unwrap(),
         num_open_transactions: usize_to_u64(funder_state.open_transactions.len()).unwrap(),
+        accept_spontaneous_payments: funder_state.accept_spontaneous_payments,
     }
 }
 
-pub fn create_initial_report<B>(funder_state: &FunderState<B>) -> FunderReport<B>
+pub fn create_initial_report<B>(
+    funder_state: &FunderState<B>,
+    local_dh_public_key: &DhPublicKey,
+) -> FunderReport<B>
 where
     B: Clone + CanonicalSerialize,
 {
-    create_report(funder_state, &Ephemeral::new())
+    create_report(funder_state, &Ephemeral::new(), local_dh_public_key)
 }
 
 pub fn friend_mutation_to_report_mutations<B>(
@@ -340,7 +351,9 @@ where
                 friend_public_key.clone(),
             )]
         }
-        FunderMutation::AddInvoice(_) | FunderMutation::RemoveInvoice(_) => {
+        FunderMutation::AddInvoice(_)
+        | FunderMutation::AddSpontaneousInvoice(_)
+        | FunderMutation::RemoveInvoice(_) => {
             if funder_state_after.open_invoices.len() != funder_state.open_invoices.len() {
                 vec![FunderReportMutation::SetNumOpenInvoices(
                     usize_to_u64(funder_state_after.open_invoices.len()).unwrap(),
@@ -360,6 +373,11 @@ where
             }
         }
         FunderMutation::SetTransactionResponse(_) => vec![],
+        FunderMutation::SetAcceptSpontaneousPayments(accept_spontaneous_payments) => {
+            vec![FunderReportMutation::SetAcceptSpontaneousPayments(
+                *accept_spontaneous_payments,
+            )]
+        }
         FunderMutation::UpdatePayment(_) | FunderMutation::RemovePayment(_) => {
             if funder_state_after.payments.len() != funder_state.payments.len() {
                 vec![FunderReportMutation::SetNumPayments(
@@ -417,5 +435,10 @@ where
                 ))]
             }
         },
+        // Payment and invoice timers are not reported:
+        EphemeralMutation::SetPaymentTicks(_)
+        | EphemeralMutation::RemovePaymentTicks(_)
+        | EphemeralMutation::SetInvoiceTicks(_)
+        | EphemeralMutation::RemoveInvoiceTicks(_) => Vec::new(),
     }
 }
@@ -2,6 +2,7 @@ use im::hashmap::HashMap as ImHashMap;
 use im::vector::Vector as ImVec;
 
 use common::canonical_serialize::CanonicalSerialize;
+use crypto::dh::DhPublicKey;
 use crypto::hash_lock::{HashedLock, PlainLock};
 use crypto::identity::PublicKey;
 use crypto::invoice_id::InvoiceId;
@@ -27,6 +28,9 @@ pub struct FunderState<B: Clone> {
     pub open_transactions: ImHashMap<Uid, OpenTransaction>,
     /// Ongoing payments (For which this node is the buyer):
     pub payments: ImHashMap<PaymentId, Payment>,
+    /// Do we accept payments without a prior invoice?
+    /// Spontaneous payments are collected as soon as the whole total_dest_payment arrives.
+    pub accept_spontaneous_payments: bool,
 }
 
 /// A state of a Payment where new transactions may still be added.
@@ -36,6 +40,12 @@ pub struct NewTransactions {
     pub invoice_id: InvoiceId,
     pub total_dest_payment: u128,
     pub dest_public_key: PublicKey,
+    /// If present, the source plain lock of every transaction is encrypted to this key and sent
+    /// to the destination, which can collect the payment without waiting for a Commit.
+    pub opt_dest_dh_public_key: Option<DhPublicKey>,
+    /// Amount of timer ticks after which the payment times out.
+    /// If not specified, the node's default payment timeout is used.
+    pub opt_deadline_ticks: Option<u64>,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -45,13 +55,19 @@ pub enum Payment {
     // TODO: Think about a better name for this?
     NewTransactions(NewTransactions),
     /// User can no longer add new transactions (user sent a RequestClosePayment)
-    InProgress(u64), // num_transactions
+    InProgress((u64, Option<u64>)), // (num_transactions, opt_deadline_ticks)
     /// A receipt was received:
     Success((u64, Receipt, Uid)), // (num_transactions, Receipt, ack_uid)
     /// The payment will not complete, because all transactions were canceled:
     Canceled(Uid), // ack_uid
     /// User already acked, We now wait for the remaining transactions to finish.
     AfterSuccessAck(u64), // num_transactions
+    /// The payment did not complete in time. No more commits will be handed out for its
+    /// transactions. A commit that was handed out before the timeout may still be collected,
+    /// in which case the payment turns into a Success.
+    TimedOut((u64, Uid)), // (num_transactions, ack_uid)
+    /// User already acked a timeout. The remaining transactions are resolved in the background.
+    AfterTimeoutAck(u64), // num_transactions
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -60,6 +76,11 @@ pub struct IncomingTransaction {
     /// The lock we used on our ResponseSendFundsOp message.
     /// We have to keep it, otherwise we will not be able to send a valid CollectSendFundsOp later.
     pub dest_plain_lock: PlainLock,
+    /// Amount of credits this transaction pays for the invoice.
+    pub dest_payment: u128,
+    /// The source plain lock of a spontaneous payment.
+    /// Used for collecting once the whole invoice was paid.
+    pub opt_src_plain_lock: Option<PlainLock>,
 }
 
 /// A local invoice in progress
@@ -69,13 +90,29 @@ pub struct OpenInvoice {
     pub total_dest_payment: u128,
     /// Multiple transactions are possible for a single invoice in case of a multi-route payment.
     pub incoming_transactions: ImHashMap<HashedLock, IncomingTransaction>,
+    /// Amount of timer ticks after which the invoice is canceled automatically.
+    pub opt_expiry_ticks: Option<u64>,
+    /// Created by the first transaction of a spontaneous payment (And not by the user).
+    /// Credits are collected as soon as the whole total_dest_payment has arrived.
+    pub is_spontaneous: bool,
 }
 
 impl OpenInvoice {
-    pub fn new(total_dest_payment: u128) -> Self {
+    pub fn new(total_dest_payment: u128, opt_expiry_ticks: Option<u64>) -> Self {
         OpenInvoice {
             total_dest_payment,
             incoming_transactions: ImHashMap::new(),
+            opt_expiry_ticks,
+            is_spontaneous: false,
+        }
+    }
+
+    pub fn new_spontaneous(total_dest_payment: u128) -> Self {
+        OpenInvoice {
+            total_dest_payment,
+            incoming_transactions: ImHashMap::new(),
+            opt_expiry_ticks: None,
+            is_spontaneous: true,
         }
     }
 }
@@ -98,14 +135,16 @@ pub enum FunderMutation<B: Clone> {
     RemoveRelay(PublicKey),
     AddFriend(AddFriend<B>),
     RemoveFriend(PublicKey),
-    AddInvoice((InvoiceId, u128)), // (InvoiceId, total_dest_payment)
-    AddIncomingTransaction((InvoiceId, Uid, PlainLock)), // (invoice_id, request_id, dest_plain_lock)
+    AddInvoice((InvoiceId, u128, Option<u64>)), // (InvoiceId, total_dest_payment, opt_expiry_ticks)
+    AddSpontaneousInvoice((InvoiceId, u128)),   // (InvoiceId, total_dest_payment)
+    AddIncomingTransaction((InvoiceId, Uid, PlainLock, u128, Option<PlainLock>)), // (invoice_id, request_id, dest_plain_lock, dest_payment, opt_src_plain_lock)
     RemoveInvoice(InvoiceId),
     AddTransaction((Uid, PaymentId, PlainLock)), // (request_id, payment_id,src_plain_lock)
     SetTransactionResponse(ResponseSendFundsOp), // (request_id, response_send_funds)
     RemoveTransaction(Uid),                      // request_id
     UpdatePayment((PaymentId, Payment)),
     RemovePayment(PaymentId),
+    SetAcceptSpontaneousPayments(bool),
 }
 
 impl<B> FunderState<B>
@@ -123,6 +162,7 @@ where
             open_invoices: ImHashMap::new(),
             open_transactions: ImHashMap::new(),
             payments: ImHashMap::new(),
+            accept_spontaneous_payments: false,
         }
     }
 
@@ -164,15 +204,31 @@ where
             FunderMutation::RemoveFriend(public_key) => {
                 let _ = self.friends.remove(&public_key);
             }
-            FunderMutation::AddInvoice((invoice_id, total_dest_payment)) => {
-                self.open_invoices
-                    .insert(invoice_id.clone(), OpenInvoice::new(*total_dest_payment));
+            FunderMutation::AddInvoice((invoice_id, total_dest_payment, opt_expiry_ticks)) => {
+                self.open_invoices.insert(
+                    invoice_id.clone(),
+                    OpenInvoice::new(*total_dest_payment, *opt_expiry_ticks),
+                );
             }
-            FunderMutation::AddIncomingTransaction((invoice_id, request_id, dest_plain_lock)) => {
+            FunderMutation::AddSpontaneousInvoice((invoice_id, total_dest_payment)) => {
+                self.open_invoices.insert(
+                    invoice_id.clone(),
+                    OpenInvoice::new_spontaneous(*total_dest_payment),
+                );
+            }
+            FunderMutation::AddIncomingTransaction((
+                invoice_id,
+                request_id,
+                dest_plain_lock,
+                dest_payment,
+                opt_src_plain_lock,
+            )) => {
                 let open_invoice = self.open_invoices.get_mut(invoice_id).unwrap();
                 let incoming_transaction = IncomingTransaction {
                     request_id: *request_id,
                     dest_plain_lock: dest_plain_lock.clone(),
+                    dest_payment: *dest_payment,
+                    opt_src_plain_lock: opt_src_plain_lock.clone(),
                 };
                 open_invoice
                     .incoming_transactions
@@ -209,6 +265,9 @@ where
             FunderMutation::RemovePayment(payment_id) => {
                 let _ = self.payments.remove(payment_id);
             }
+            FunderMutation::SetAcceptSpontaneousPayments(accept_spontaneous_payments) => {
+                self.accept_spontaneous_payments = *accept_spontaneous_payments;
+            }
         }
     }
 }
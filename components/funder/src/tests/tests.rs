@@ -6,10 +6,11 @@ use crypto::payment_id::{PaymentId, PAYMENT_ID_LEN};
 use crypto::uid::{Uid, UID_LEN};
 
 use proto::funder::messages::{
-    AckClosePayment, AddInvoice, CreatePayment, CreateTransaction, FriendStatus, FriendsRoute,
-    FunderControl, MultiCommit, PaymentStatus, Rate, RequestResult, RequestsStatus,
-    ResetFriendChannel,
+    AckClosePayment, AddInvoice, CreatePayment, CreateRebalance, CreateTransaction, FailureReason,
+    FriendStatus, FriendsRoute, FunderControl, InvoiceDetails, InvoiceStatus, MultiCommit,
+    PaymentStatus, Rate, RequestResult, RequestsStatus, ResetFriendChannel,
 };
+use proto::funder::signature_buff::verify_invoice_signature;
 use proto::report::messages::{ChannelStatusReport, FunderReport};
 
 use super::utils::{create_node_controls, dummy_named_relay_address, dummy_relay_address};
@@ -49,6 +50,7 @@ async fn task_funder_basic(test_executor: TestExecutor) {
     let add_invoice = AddInvoice {
         invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
         total_dest_payment: 4,
+        opt_expiry_ticks: None,
     };
     await!(node_controls[1].send(FunderControl::AddInvoice(add_invoice)));
 
@@ -58,6 +60,8 @@ async fn task_funder_basic(test_executor: TestExecutor) {
         invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
         total_dest_payment: 4,
         dest_public_key: node_controls[1].public_key.clone(),
+        opt_dest_dh_public_key: None,
+        opt_deadline_ticks: None,
     };
     await!(node_controls[0].send(FunderControl::CreatePayment(create_payment)));
 
@@ -201,6 +205,7 @@ async fn task_funder_forward_payment(test_executor: TestExecutor) {
     let add_invoice = AddInvoice {
         invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
         total_dest_payment: 15,
+        opt_expiry_ticks: None,
     };
     await!(node_controls[2].send(FunderControl::AddInvoice(add_invoice)));
 
@@ -210,6 +215,8 @@ async fn task_funder_forward_payment(test_executor: TestExecutor) {
         invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
         total_dest_payment: 15,
         dest_public_key: node_controls[2].public_key.clone(),
+        opt_dest_dh_public_key: None,
+        opt_deadline_ticks: None,
     };
     await!(node_controls[0].send(FunderControl::CreatePayment(create_payment)));
 
@@ -235,6 +242,45 @@ async fn task_funder_forward_payment(test_executor: TestExecutor) {
         _ => unreachable!(),
     };
 
+    // 2: Expect a notification that the invoice was fully paid:
+    let invoice_paid = await!(node_controls[2].recv_until_invoice_paid()).unwrap();
+    assert_eq!(
+        invoice_paid.invoice_id,
+        InvoiceId::from(&[1u8; INVOICE_ID_LEN])
+    );
+    assert_eq!(invoice_paid.total_dest_payment, 15);
+    assert_eq!(invoice_paid.transactions.len(), 1);
+    assert_eq!(
+        invoice_paid.transactions[0].request_id,
+        Uid::from(&[5u8; UID_LEN])
+    );
+    assert_eq!(
+        invoice_paid.transactions[0].dest_hashed_lock,
+        commit.dest_hashed_lock
+    );
+    assert_eq!(invoice_paid.transactions[0].dest_payment, 15);
+
+    // 2: The invoice was fully paid, but not yet committed:
+    await!(
+        node_controls[2].send(FunderControl::RequestInvoiceStatus(InvoiceId::from(
+            &[1u8; INVOICE_ID_LEN]
+        )))
+    );
+    let response_invoice_status =
+        await!(node_controls[2].recv_until_response_invoice_status()).unwrap();
+    let open_invoice_status = match response_invoice_status.status {
+        InvoiceStatus::Open(open_invoice_status) => open_invoice_status,
+        _ => unreachable!(),
+    };
+    assert_eq!(open_invoice_status.total_dest_payment, 15);
+    assert_eq!(open_invoice_status.collected_dest_payment, 15);
+    assert_eq!(open_invoice_status.transactions.len(), 1);
+    assert_eq!(
+        open_invoice_status.transactions[0].request_id,
+        Uid::from(&[5u8; UID_LEN])
+    );
+    assert_eq!(open_invoice_status.opt_ticks_left, None);
+
     // 0: Create multi commit:
     let multi_commit = MultiCommit {
         invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
@@ -247,6 +293,19 @@ async fn task_funder_forward_payment(test_executor: TestExecutor) {
     // 2: Apply MultiCommit:
     await!(node_controls[2].send(FunderControl::CommitInvoice(multi_commit)));
 
+    // 2: A committed invoice is no longer open:
+    await!(
+        node_controls[2].send(FunderControl::RequestInvoiceStatus(InvoiceId::from(
+            &[1u8; INVOICE_ID_LEN]
+        )))
+    );
+    let response_invoice_status =
+        await!(node_controls[2].recv_until_response_invoice_status()).unwrap();
+    assert_eq!(
+        response_invoice_status.status,
+        InvoiceStatus::InvoiceNotFound
+    );
+
     // Wait until no more progress can be made (We should get a receipt)
     await!(test_executor.wait());
 
@@ -325,6 +384,133 @@ fn test_funder_forward_payment() {
     assert!(res.is_output());
 }
 
+/// Get the balance of a node with one of its friends
+fn friend_balance(report: &FunderReport<u32>, friend_public_key: &PublicKey) -> Option<i128> {
+    let friend = report.friends.get(friend_public_key)?;
+    match &friend.channel_status {
+        ChannelStatusReport::Consistent(tc_report) => Some(tc_report.balance.balance),
+        _ => None,
+    }
+}
+
+async fn task_funder_rebalance(test_executor: TestExecutor) {
+    /*
+     * 0 -- 1
+     *  \  /
+     *   2
+     */
+    let num_nodes = 3;
+    let mut node_controls = await!(create_node_controls(num_nodes, test_executor.clone()));
+
+    let public_keys = node_controls
+        .iter()
+        .map(|nc| nc.public_key.clone())
+        .collect::<Vec<PublicKey>>();
+
+    // Add friends:
+    let relays0 = vec![dummy_relay_address(0)];
+    let relays1 = vec![dummy_relay_address(1)];
+    let relays2 = vec![dummy_relay_address(2)];
+    await!(node_controls[0].add_friend(&public_keys[1], relays1.clone(), "node1", 0));
+    await!(node_controls[0].add_friend(&public_keys[2], relays2.clone(), "node2", 0));
+    await!(node_controls[1].add_friend(&public_keys[0], relays0.clone(), "node0", 0));
+    await!(node_controls[1].add_friend(&public_keys[2], relays2, "node2", 0));
+    await!(node_controls[2].add_friend(&public_keys[0], relays0, "node0", 0));
+    await!(node_controls[2].add_friend(&public_keys[1], relays1, "node1", 0));
+
+    // Enable friends:
+    for (i, j) in &[(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)] {
+        await!(node_controls[*i].set_friend_status(&public_keys[*j], FriendStatus::Enabled));
+    }
+
+    // This is the amount of credits node 1 takes from node 0 for forwarding messages.
+    await!(node_controls[1].set_friend_rate(&public_keys[0], Rate { mul: 0, add: 3 }));
+
+    // Set remote max debt, and open requests, allowing this route: 0 --> 1 --> 2 --> 0
+    await!(node_controls[1].set_remote_max_debt(&public_keys[0], 100));
+    await!(node_controls[2].set_remote_max_debt(&public_keys[1], 100));
+    await!(node_controls[0].set_remote_max_debt(&public_keys[2], 100));
+    await!(node_controls[1].set_requests_status(&public_keys[0], RequestsStatus::Open));
+    await!(node_controls[2].set_requests_status(&public_keys[1], RequestsStatus::Open));
+    await!(node_controls[0].set_requests_status(&public_keys[2], RequestsStatus::Open));
+
+    await!(node_controls[0].wait_until_ready(&public_keys[1]));
+    await!(node_controls[1].wait_until_ready(&public_keys[2]));
+    await!(node_controls[2].wait_until_ready(&public_keys[0]));
+
+    // Node 0 moves credits from its channel with node 1 to its channel with node 2:
+    let create_rebalance = CreateRebalance {
+        payment_id: PaymentId::from(&[2u8; PAYMENT_ID_LEN]),
+        invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
+        request_id: Uid::from(&[5u8; UID_LEN]),
+        route: FriendsRoute {
+            public_keys: vec![
+                public_keys[0].clone(),
+                public_keys[1].clone(),
+                public_keys[2].clone(),
+                public_keys[0].clone(),
+            ],
+        },
+        dest_payment: 15,
+        fees: 3,
+    };
+    await!(node_controls[0].send(FunderControl::CreateRebalance(create_rebalance)));
+    let transaction_result = await!(node_controls[0].recv_until_transaction_result()).unwrap();
+    match transaction_result.result {
+        RequestResult::Success(_commit) => {}
+        _ => unreachable!(),
+    };
+
+    // The invoice is committed automatically. Wait until the credits are collected:
+    await!(test_executor.wait());
+
+    // 0: Close the payment, as with any other payment:
+    await!(
+        node_controls[0].send(FunderControl::RequestClosePayment(PaymentId::from(
+            &[2u8; PAYMENT_ID_LEN]
+        )))
+    );
+    let response_close_payment =
+        await!(node_controls[0].recv_until_response_close_payment()).unwrap();
+    let (receipt, ack_uid) = match response_close_payment.status {
+        PaymentStatus::Success((receipt, ack_uid)) => (receipt, ack_uid),
+        _ => unreachable!(),
+    };
+    assert_eq!(receipt.dest_payment, 15);
+
+    let ack_close_payment = AckClosePayment {
+        payment_id: PaymentId::from(&[2u8; PAYMENT_ID_LEN]),
+        ack_uid,
+    };
+    await!(node_controls[0].send(FunderControl::AckClosePayment(ack_close_payment)));
+
+    // Node 0 paid node 1 (Including fees), and got paid by node 2:
+    let pred = |report: &FunderReport<_>| {
+        friend_balance(report, &public_keys[1]) == Some(-18)
+            && friend_balance(report, &public_keys[2]) == Some(15)
+    };
+    await!(node_controls[0].recv_until(pred));
+
+    let pred = |report: &FunderReport<_>| {
+        friend_balance(report, &public_keys[0]) == Some(18)
+            && friend_balance(report, &public_keys[2]) == Some(-15)
+    };
+    await!(node_controls[1].recv_until(pred));
+
+    let pred = |report: &FunderReport<_>| {
+        friend_balance(report, &public_keys[1]) == Some(15)
+            && friend_balance(report, &public_keys[0]) == Some(-15)
+    };
+    await!(node_controls[2].recv_until(pred));
+}
+
+#[test]
+fn test_funder_rebalance() {
+    let test_executor = TestExecutor::new();
+    let res = test_executor.run(task_funder_rebalance(test_executor.clone()));
+    assert!(res.is_output());
+}
+
 async fn task_funder_payment_failure(test_executor: TestExecutor) {
     /*
      * 0 -- 1 -- 2
@@ -382,6 +568,8 @@ async fn task_funder_payment_failure(test_executor: TestExecutor) {
         invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
         total_dest_payment: 15,
         dest_public_key: node_controls[3].public_key.clone(),
+        opt_dest_dh_public_key: None,
+        opt_deadline_ticks: None,
     };
     await!(node_controls[0].send(FunderControl::CreatePayment(create_payment)));
 
@@ -403,9 +591,12 @@ async fn task_funder_payment_failure(test_executor: TestExecutor) {
     await!(node_controls[0].send(FunderControl::CreateTransaction(create_transaction)));
     let transaction_result = await!(node_controls[0].recv_until_transaction_result()).unwrap();
 
-    // We expect failure:
+    // We expect failure. Node 2 can not forward the request to node 3:
     match transaction_result.result {
-        RequestResult::Failure => {}
+        RequestResult::Failure(request_failure) => {
+            assert_eq!(request_failure.reporting_public_key, public_keys[2]);
+            assert_eq!(request_failure.reason, FailureReason::FriendNotReady);
+        }
         _ => unreachable!(),
     }
 
@@ -453,6 +644,222 @@ fn test_funder_payment_failure() {
     assert!(res.is_output());
 }
 
+async fn task_funder_spontaneous_payment(test_executor: TestExecutor) {
+    /*
+     * 0 -- 1 -- 2
+     */
+    let num_nodes = 3;
+    let mut node_controls = await!(create_node_controls(num_nodes, test_executor.clone()));
+
+    // Create topology:
+    // ----------------
+    let public_keys = node_controls
+        .iter()
+        .map(|nc| nc.public_key.clone())
+        .collect::<Vec<PublicKey>>();
+
+    // Add friends:
+    let relays0 = vec![dummy_relay_address(0)];
+    let relays1 = vec![dummy_relay_address(1)];
+    let relays2 = vec![dummy_relay_address(2)];
+    await!(node_controls[0].add_friend(&public_keys[1], relays1, "node1", 8));
+    await!(node_controls[1].add_friend(&public_keys[0], relays0.clone(), "node0", -8));
+    await!(node_controls[1].add_friend(&public_keys[2], relays2, "node2", 6));
+    await!(node_controls[2].add_friend(&public_keys[1], relays0, "node0", -6));
+
+    // Enable friends:
+    await!(node_controls[0].set_friend_status(&public_keys[1], FriendStatus::Enabled));
+    await!(node_controls[1].set_friend_status(&public_keys[0], FriendStatus::Enabled));
+    await!(node_controls[1].set_friend_status(&public_keys[2], FriendStatus::Enabled));
+    await!(node_controls[2].set_friend_status(&public_keys[1], FriendStatus::Enabled));
+
+    // Set rate:
+    await!(node_controls[1].set_friend_rate(&public_keys[0], Rate { mul: 0, add: 5 }));
+
+    // Set remote max debt:
+    await!(node_controls[0].set_remote_max_debt(&public_keys[1], 200));
+    await!(node_controls[1].set_remote_max_debt(&public_keys[0], 100));
+    await!(node_controls[1].set_remote_max_debt(&public_keys[2], 300));
+    await!(node_controls[2].set_remote_max_debt(&public_keys[1], 400));
+
+    // Open requests, allowing this route: 0 --> 1 --> 2
+    await!(node_controls[1].set_requests_status(&public_keys[0], RequestsStatus::Open));
+    await!(node_controls[2].set_requests_status(&public_keys[1], RequestsStatus::Open));
+
+    await!(node_controls[0].wait_until_ready(&public_keys[1]));
+    await!(node_controls[1].wait_until_ready(&public_keys[2]));
+
+    let route = FriendsRoute {
+        public_keys: vec![
+            public_keys[0].clone(),
+            public_keys[1].clone(),
+            public_keys[2].clone(),
+        ],
+    };
+
+    // Create spontaneous payment 0 --> 2 (Node 2 did not opt in yet):
+    let create_payment = CreatePayment {
+        payment_id: PaymentId::from(&[2u8; PAYMENT_ID_LEN]),
+        invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
+        total_dest_payment: 15,
+        dest_public_key: node_controls[2].public_key.clone(),
+        opt_dest_dh_public_key: Some(node_controls[2].report.local_dh_public_key.clone()),
+        opt_deadline_ticks: None,
+    };
+    await!(node_controls[0].send(FunderControl::CreatePayment(create_payment)));
+
+    let create_transaction = CreateTransaction {
+        payment_id: PaymentId::from(&[2u8; PAYMENT_ID_LEN]),
+        request_id: Uid::from(&[5u8; UID_LEN]),
+        route: route.clone(),
+        dest_payment: 15,
+        fees: 5,
+    };
+    await!(node_controls[0].send(FunderControl::CreateTransaction(create_transaction)));
+    let transaction_result = await!(node_controls[0].recv_until_transaction_result()).unwrap();
+
+    // We expect node 2 to reject the payment:
+    match transaction_result.result {
+        RequestResult::Failure(request_failure) => {
+            assert_eq!(request_failure.reporting_public_key, public_keys[2]);
+            assert_eq!(request_failure.reason, FailureReason::UnknownInvoice);
+        }
+        _ => unreachable!(),
+    }
+
+    // 2: Opt in to spontaneous payments:
+    await!(node_controls[2].set_accept_spontaneous_payments(true));
+
+    // Create spontaneous payment 0 --> 2 (No invoice is added at node 2):
+    let create_payment = CreatePayment {
+        payment_id: PaymentId::from(&[3u8; PAYMENT_ID_LEN]),
+        invoice_id: InvoiceId::from(&[4u8; INVOICE_ID_LEN]),
+        total_dest_payment: 15,
+        dest_public_key: node_controls[2].public_key.clone(),
+        opt_dest_dh_public_key: Some(node_controls[2].report.local_dh_public_key.clone()),
+        opt_deadline_ticks: None,
+    };
+    await!(node_controls[0].send(FunderControl::CreatePayment(create_payment)));
+
+    // Pay part of the payment:
+    let create_transaction = CreateTransaction {
+        payment_id: PaymentId::from(&[3u8; PAYMENT_ID_LEN]),
+        request_id: Uid::from(&[6u8; UID_LEN]),
+        route: route.clone(),
+        dest_payment: 10,
+        fees: 5,
+    };
+    await!(node_controls[0].send(FunderControl::CreateTransaction(create_transaction)));
+    let transaction_result = await!(node_controls[0].recv_until_transaction_result()).unwrap();
+
+    match transaction_result.result {
+        RequestResult::Success(_commit) => {}
+        _ => unreachable!(),
+    };
+
+    await!(test_executor.wait());
+
+    // 2: A partial payment is not collected. It waits for the rest of the payment:
+    await!(
+        node_controls[2].send(FunderControl::RequestInvoiceStatus(InvoiceId::from(
+            &[4u8; INVOICE_ID_LEN]
+        )))
+    );
+    let response_invoice_status =
+        await!(node_controls[2].recv_until_response_invoice_status()).unwrap();
+    let open_invoice_status = match response_invoice_status.status {
+        InvoiceStatus::Open(open_invoice_status) => open_invoice_status,
+        _ => unreachable!(),
+    };
+    assert_eq!(open_invoice_status.total_dest_payment, 15);
+    assert_eq!(open_invoice_status.collected_dest_payment, 10);
+
+    // Pay the rest of the payment:
+    let create_transaction = CreateTransaction {
+        payment_id: PaymentId::from(&[3u8; PAYMENT_ID_LEN]),
+        request_id: Uid::from(&[7u8; UID_LEN]),
+        route,
+        dest_payment: 5,
+        fees: 5,
+    };
+    await!(node_controls[0].send(FunderControl::CreateTransaction(create_transaction)));
+    let transaction_result = await!(node_controls[0].recv_until_transaction_result()).unwrap();
+
+    match transaction_result.result {
+        RequestResult::Success(_commit) => {}
+        _ => unreachable!(),
+    };
+
+    // 2: Expect a notification that the payment has arrived:
+    let invoice_paid = await!(node_controls[2].recv_until_invoice_paid()).unwrap();
+    assert_eq!(
+        invoice_paid.invoice_id,
+        InvoiceId::from(&[4u8; INVOICE_ID_LEN])
+    );
+    assert_eq!(invoice_paid.total_dest_payment, 15);
+    assert_eq!(invoice_paid.transactions.len(), 2);
+
+    // Wait until no more progress can be made (Node 2 collects without a MultiCommit)
+    await!(test_executor.wait());
+
+    // 2: The payment was collected:
+    await!(
+        node_controls[2].send(FunderControl::RequestInvoiceStatus(InvoiceId::from(
+            &[4u8; INVOICE_ID_LEN]
+        )))
+    );
+    let response_invoice_status =
+        await!(node_controls[2].recv_until_response_invoice_status()).unwrap();
+    assert_eq!(
+        response_invoice_status.status,
+        InvoiceStatus::InvoiceNotFound
+    );
+
+    // 0: Expect a receipt:
+    await!(
+        node_controls[0].send(FunderControl::RequestClosePayment(PaymentId::from(
+            &[3u8; PAYMENT_ID_LEN]
+        )))
+    );
+    let response_close_payment =
+        await!(node_controls[0].recv_until_response_close_payment()).unwrap();
+    let (receipt, ack_uid) = match response_close_payment.status {
+        PaymentStatus::Success((receipt, ack_uid)) => (receipt, ack_uid),
+        _ => unreachable!(),
+    };
+
+    // 0: Acknowledge response close:
+    let ack_close_payment = AckClosePayment {
+        payment_id: PaymentId::from(&[3u8; PAYMENT_ID_LEN]),
+        ack_uid,
+    };
+    await!(node_controls[0].send(FunderControl::AckClosePayment(ack_close_payment)));
+
+    assert_eq!(receipt.invoice_id, InvoiceId::from(&[4u8; INVOICE_ID_LEN]));
+    assert_eq!(receipt.total_dest_payment, 15);
+
+    // Make sure that node2 got the credits:
+    let pred = |report: &FunderReport<_>| {
+        let friend = match report.friends.get(&public_keys[1]) {
+            None => return false,
+            Some(friend) => friend,
+        };
+        let tc_report = match &friend.channel_status {
+            ChannelStatusReport::Consistent(tc_report) => tc_report,
+            _ => return false,
+        };
+        tc_report.balance.balance == -6 + 15
+    };
+    await!(node_controls[2].recv_until(pred));
+}
+
+#[test]
+fn test_funder_spontaneous_payment() {
+    let test_executor = TestExecutor::new();
+    let res = test_executor.run(task_funder_spontaneous_payment(test_executor.clone()));
+    assert!(res.is_output());
+}
+
 /// Test a basic inconsistency between two adjacent nodes
 async fn task_funder_inconsistency_basic(test_executor: TestExecutor) {
     let num_nodes = 2;
@@ -574,4 +981,58 @@ fn test_funder_add_relay() {
     assert!(res.is_output());
 }
 
+/// Test signing invoice details by the local node
+async fn task_funder_sign_invoice(test_executor: TestExecutor) {
+    let num_nodes = 1;
+    let mut node_controls = await!(create_node_controls(num_nodes, test_executor));
+    let public_key = node_controls[0].public_key.clone();
+
+    let add_invoice = AddInvoice {
+        invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
+        total_dest_payment: 100,
+        opt_expiry_ticks: None,
+    };
+    await!(node_controls[0].send(FunderControl::AddInvoice(add_invoice)));
+
+    let invoice_details = InvoiceDetails {
+        invoice_id: InvoiceId::from(&[1u8; INVOICE_ID_LEN]),
+        total_dest_payment: 100,
+        memo: "Two apples".to_owned(),
+        opt_expiry: Some(1_560_000_000),
+        relays: vec![dummy_relay_address(0)],
+    };
+    await!(node_controls[0].send(FunderControl::RequestSignInvoice(invoice_details.clone())));
+    let response_sign_invoice =
+        await!(node_controls[0].recv_until_response_sign_invoice()).unwrap();
+    assert_eq!(response_sign_invoice.invoice_id, invoice_details.invoice_id);
+    let signature = response_sign_invoice.opt_signature.unwrap();
+    assert!(verify_invoice_signature(
+        &public_key,
+        &invoice_details,
+        &signature
+    ));
+
+    // The node refuses to sign details that do not match an open invoice:
+    let mut wrong_details = invoice_details.clone();
+    wrong_details.total_dest_payment = 101;
+    await!(node_controls[0].send(FunderControl::RequestSignInvoice(wrong_details)));
+    let response_sign_invoice =
+        await!(node_controls[0].recv_until_response_sign_invoice()).unwrap();
+    assert_eq!(response_sign_invoice.opt_signature, None);
+
+    let mut wrong_details = invoice_details.clone();
+    wrong_details.invoice_id = InvoiceId::from(&[2u8; INVOICE_ID_LEN]);
+    await!(node_controls[0].send(FunderControl::RequestSignInvoice(wrong_details)));
+    let response_sign_invoice =
+        await!(node_controls[0].recv_until_response_sign_invoice()).unwrap();
+    assert_eq!(response_sign_invoice.opt_signature, None);
+}
+
+#[test]
+fn test_funder_sign_invoice() {
+    let test_executor = TestExecutor::new();
+    let res = test_executor.run(task_funder_sign_invoice(test_executor.clone()));
+    assert!(res.is_output());
+}
+
 // TODO: Add a test for multi-route payment
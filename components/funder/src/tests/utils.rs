@@ -21,9 +21,10 @@ use proto::report::messages::{
 
 use proto::app_server::messages::{NamedRelayAddress, RelayAddress};
 use proto::funder::messages::{
-    AddFriend, FriendStatus, FunderControl, FunderIncomingControl, FunderOutgoingControl, Rate,
-    RequestsStatus, ResponseClosePayment, SetFriendRate, SetFriendRemoteMaxDebt, SetFriendStatus,
-    SetRequestsStatus, TransactionResult,
+    AddFriend, FriendStatus, FunderControl, FunderIncomingControl, FunderOutgoingControl,
+    InvoicePaid, Rate, RequestsStatus, ResponseClosePayment, ResponseInvoiceStatus,
+    ResponseSignInvoice, SetFriendRate, SetFriendRemoteMaxDebt, SetFriendStatus, SetRequestsStatus,
+    TransactionResult,
 };
 
 use database::DatabaseClient;
@@ -42,6 +43,7 @@ use crate::types::{
 const TEST_MAX_NODE_RELAYS: usize = 16;
 const TEST_MAX_OPERATIONS_IN_BATCH: usize = 16;
 const TEST_MAX_PENDING_USER_REQUESTS: usize = 16;
+const TEST_PAYMENT_TIMEOUT_TICKS: usize = 8;
 
 // This is required to make sure the tests are not stuck.
 //
@@ -202,6 +204,8 @@ pub struct NodeControl<B: Clone> {
     recv_control: mpsc::Receiver<FunderOutgoingControl<B>>,
     pub report: FunderReport<B>,
     next_app_request_id: u64,
+    /// Kept alive so that the funder's timer stream stays open:
+    _timer_sender: mpsc::Sender<()>,
 }
 
 #[derive(Debug)]
@@ -209,6 +213,9 @@ pub enum NodeRecv<B: Clone> {
     ReportMutations(FunderReportMutations<B>),
     ResponseClosePayment(ResponseClosePayment),
     TransactionResult(TransactionResult),
+    ResponseInvoiceStatus(ResponseInvoiceStatus),
+    ResponseSignInvoice(ResponseSignInvoice),
+    InvoicePaid(InvoicePaid),
 }
 
 impl<B> NodeControl<B>
@@ -261,6 +268,15 @@ where
             FunderOutgoingControl::TransactionResult(transaction_result) => {
                 Some(NodeRecv::TransactionResult(transaction_result))
             }
+            FunderOutgoingControl::ResponseInvoiceStatus(response_invoice_status) => {
+                Some(NodeRecv::ResponseInvoiceStatus(response_invoice_status))
+            }
+            FunderOutgoingControl::ResponseSignInvoice(response_sign_invoice) => {
+                Some(NodeRecv::ResponseSignInvoice(response_sign_invoice))
+            }
+            FunderOutgoingControl::InvoicePaid(invoice_paid) => {
+                Some(NodeRecv::InvoicePaid(invoice_paid))
+            }
         }
     }
 
@@ -273,6 +289,10 @@ where
                 NodeRecv::ReportMutations(_) => {}
                 NodeRecv::TransactionResult(_) => unreachable!(),
                 NodeRecv::ResponseClosePayment(_) => unreachable!(),
+                NodeRecv::ResponseInvoiceStatus(_) => unreachable!(),
+                NodeRecv::ResponseSignInvoice(_) => unreachable!(),
+                // The seller might receive a notification while waiting for a report:
+                NodeRecv::InvoicePaid(_) => {}
             };
         }
     }
@@ -283,6 +303,9 @@ where
                 NodeRecv::ReportMutations(_) => {}
                 NodeRecv::TransactionResult(transaction_result) => return Some(transaction_result),
                 NodeRecv::ResponseClosePayment(_) => {}
+                NodeRecv::ResponseInvoiceStatus(_) => {}
+                NodeRecv::ResponseSignInvoice(_) => {}
+                NodeRecv::InvoicePaid(_) => {}
             };
         }
     }
@@ -295,6 +318,52 @@ where
                 NodeRecv::ResponseClosePayment(response_close_payment) => {
                     return Some(response_close_payment)
                 }
+                NodeRecv::ResponseInvoiceStatus(_) => {}
+                NodeRecv::ResponseSignInvoice(_) => {}
+                NodeRecv::InvoicePaid(_) => {}
+            };
+        }
+    }
+
+    pub async fn recv_until_response_invoice_status(&mut self) -> Option<ResponseInvoiceStatus> {
+        loop {
+            match await!(self.recv())? {
+                NodeRecv::ReportMutations(_) => {}
+                NodeRecv::TransactionResult(_) => {}
+                NodeRecv::ResponseClosePayment(_) => {}
+                NodeRecv::ResponseInvoiceStatus(response_invoice_status) => {
+                    return Some(response_invoice_status)
+                }
+                NodeRecv::ResponseSignInvoice(_) => {}
+                NodeRecv::InvoicePaid(_) => {}
+            };
+        }
+    }
+
+    pub async fn recv_until_response_sign_invoice(&mut self) -> Option<ResponseSignInvoice> {
+        loop {
+            match await!(self.recv())? {
+                NodeRecv::ReportMutations(_) => {}
+                NodeRecv::TransactionResult(_) => {}
+                NodeRecv::ResponseClosePayment(_) => {}
+                NodeRecv::ResponseInvoiceStatus(_) => {}
+                NodeRecv::ResponseSignInvoice(response_sign_invoice) => {
+                    return Some(response_sign_invoice)
+                }
+                NodeRecv::InvoicePaid(_) => {}
+            };
+        }
+    }
+
+    pub async fn recv_until_invoice_paid(&mut self) -> Option<InvoicePaid> {
+        loop {
+            match await!(self.recv())? {
+                NodeRecv::ReportMutations(_) => {}
+                NodeRecv::TransactionResult(_) => {}
+                NodeRecv::ResponseClosePayment(_) => {}
+                NodeRecv::ResponseInvoiceStatus(_) => {}
+                NodeRecv::ResponseSignInvoice(_) => {}
+                NodeRecv::InvoicePaid(invoice_paid) => return Some(invoice_paid),
             };
         }
     }
@@ -367,6 +436,12 @@ where
         await!(self.send(FunderControl::SetFriendRate(set_friend_rate)));
     }
 
+    pub async fn set_accept_spontaneous_payments(&mut self, accept_spontaneous_payments: bool) {
+        await!(self.send(FunderControl::SetAcceptSpontaneousPayments(
+            accept_spontaneous_payments
+        )));
+    }
+
     pub async fn wait_until_ready<'a>(&'a mut self, friend_public_key: &'a PublicKey) {
         let pred = |report: &FunderReport<_>| {
             let friend = match report.friends.get(&friend_public_key) {
@@ -414,10 +489,11 @@ where
             .unwrap();
 
         let public_key = await!(identity_client.request_public_key()).unwrap();
+        let dh_public_key = await!(identity_client.request_dh_public_key()).unwrap();
         let relays = vec![dummy_named_relay_address(i as u8)];
         let funder_state = FunderState::new(public_key.clone(), relays);
         let ephemeral = Ephemeral::new();
-        let base_report = create_report(&funder_state, &ephemeral);
+        let base_report = create_report(&funder_state, &ephemeral, &dh_public_key);
 
         // let report = create_report(&self.state, &self.ephemeral);
         // self.add_outgoing_control(FunderOutgoingControl::Report(report));
@@ -439,11 +515,14 @@ where
         let (send_comm, incoming_comm) = mpsc::channel(CHANNEL_SIZE);
         let (comm_sender, recv_comm) = mpsc::channel(CHANNEL_SIZE);
 
+        let (timer_sender, incoming_timer) = mpsc::channel::<()>(0);
+
         let funder_fut = inner_funder_loop(
             identity_client.clone(),
             DummyRandom::new(&[i as u8]),
             incoming_control,
             incoming_comm,
+            incoming_timer,
             control_sender,
             comm_sender,
             funder_state,
@@ -451,6 +530,7 @@ where
             TEST_MAX_NODE_RELAYS,
             TEST_MAX_OPERATIONS_IN_BATCH,
             TEST_MAX_PENDING_USER_REQUESTS,
+            TEST_PAYMENT_TIMEOUT_TICKS,
             None,
         );
 
@@ -478,6 +558,7 @@ where
             recv_control,
             report: base_report,
             next_app_request_id: 0,
+            _timer_sender: timer_sender,
         });
     }
     node_controls
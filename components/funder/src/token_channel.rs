@@ -205,6 +205,12 @@ where
         }
     }
 
+    /// Restore a token channel from its direction (For example, when importing an exported
+    /// state).
+    pub fn from_direction(direction: TcDirection<B>) -> TokenChannel<B> {
+        TokenChannel { direction }
+    }
+
     /// Get a reference to internal mutual_credit.
     pub fn get_mutual_credit(&self) -> &MutualCredit {
         match &self.direction {
@@ -8,8 +8,8 @@ use crypto::uid::Uid;
 
 use proto::app_server::messages::RelayAddress;
 use proto::funder::messages::{
-    CancelSendFundsOp, ChannelerUpdateFriend, CollectSendFundsOp, FriendMessage, FriendTcOp,
-    FunderIncomingControl, FunderOutgoingControl, MoveToken, PendingTransaction,
+    CancelSendFundsOp, ChannelerUpdateFriend, CollectSendFundsOp, FailureReason, FriendMessage,
+    FriendTcOp, FunderIncomingControl, FunderOutgoingControl, MoveToken, PendingTransaction,
     RequestSendFundsOp, ResponseSendFundsOp, TransactionStage,
 };
 
@@ -73,8 +73,16 @@ pub async fn create_response_send_funds<'a>(
     }
 }
 
-pub fn create_cancel_send_funds(request_id: Uid) -> CancelSendFundsOp {
-    CancelSendFundsOp { request_id }
+pub fn create_cancel_send_funds(
+    request_id: Uid,
+    reporting_public_key: PublicKey,
+    reason: FailureReason,
+) -> CancelSendFundsOp {
+    CancelSendFundsOp {
+        request_id,
+        reporting_public_key,
+        reason,
+    }
 }
 
 pub fn create_pending_transaction(request_send_funds: &RequestSendFundsOp) -> PendingTransaction {
@@ -86,6 +94,7 @@ pub fn create_pending_transaction(request_send_funds: &RequestSendFundsOp) -> Pe
         invoice_id: request_send_funds.invoice_id.clone(),
         left_fees: request_send_funds.left_fees,
         src_hashed_lock: request_send_funds.src_hashed_lock.clone(),
+        opt_encrypted_src_plain_lock: request_send_funds.opt_encrypted_src_plain_lock.clone(),
         stage: TransactionStage::Request,
     }
 }
@@ -236,6 +245,7 @@ pub enum FunderIncoming<B> {
     Init,
     Control(FunderIncomingControl<B>),
     Comm(FunderIncomingComm<B>),
+    TimerTick,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -2,9 +2,12 @@ use futures::channel::{mpsc, oneshot};
 use futures::{Future, TryFutureExt};
 
 use common::futures_compat::send_to_sink;
+use crypto::dh::DhPublicKey;
 use crypto::identity::{PublicKey, Signature};
 
-use super::messages::{ResponsePublicKey, ResponseSignature, ToIdentity};
+use super::messages::{
+    ResponseDecrypt, ResponseDhPublicKey, ResponsePublicKey, ResponseSignature, ToIdentity,
+};
 
 #[derive(Debug)]
 pub enum IdentityClientError {
@@ -62,6 +65,36 @@ impl IdentityClient {
         self.request_response(request, rx)
             .map_ok(|response_public_key: ResponsePublicKey| response_public_key.public_key)
     }
+
+    /// Request the DH public key of the used Identity.
+    /// Returns a Future that resolves to the DH public key.
+    pub fn request_dh_public_key(
+        &self,
+    ) -> impl Future<Output = Result<DhPublicKey, IdentityClientError>> {
+        let (tx, rx) = oneshot::channel();
+        let request = ToIdentity::RequestDhPublicKey {
+            response_sender: tx,
+        };
+        self.request_response(request, rx)
+            .map_ok(|response_dh_public_key: ResponseDhPublicKey| {
+                response_dh_public_key.dh_public_key
+            })
+    }
+
+    /// Request to decrypt a message that was encrypted to the DH public key of the used Identity.
+    /// Returns a Future that resolves to the decrypted message, or None if decryption failed.
+    pub fn request_decrypt(
+        &self,
+        cipher_msg: Vec<u8>,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, IdentityClientError>> {
+        let (tx, rx) = oneshot::channel::<ResponseDecrypt>();
+        let request = ToIdentity::RequestDecrypt {
+            cipher_msg,
+            response_sender: tx,
+        };
+        self.request_response(request, rx)
+            .map_ok(|response_decrypt| response_decrypt.opt_plain_msg)
+    }
 }
 
 #[cfg(test)]
@@ -74,6 +107,7 @@ mod tests {
     use futures::FutureExt;
 
     use crate::identity::create_identity;
+    use crypto::dh::encrypt_to_dh_public_key;
     use crypto::identity::{generate_pkcs8_key_pair, verify_signature, SoftwareEd25519Identity};
     use crypto::test_utils::DummyRandom;
 
@@ -124,5 +158,37 @@ mod tests {
         assert!(verify_signature(&my_message[..], &public_key, &signature));
     }
 
+    #[test]
+    fn test_identity_request_decrypt_with_client() {
+        let secure_rand = DummyRandom::new(&[3u8]);
+        let pkcs8 = generate_pkcs8_key_pair(&secure_rand);
+        let identity = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+
+        let (requests_sender, sm) = create_identity(identity);
+        let smc = IdentityClient::new(requests_sender);
+
+        // Start the Identity service:
+        let mut local_pool = LocalPool::new();
+        let mut spawner = local_pool.spawner();
+        spawner.spawn(sm.then(|_| future::ready(()))).unwrap();
+
+        let dh_public_key = local_pool.run_until(smc.request_dh_public_key()).unwrap();
+
+        let my_message = b"This is my message!";
+        let cipher_msg =
+            encrypt_to_dh_public_key(&my_message[..], &dh_public_key, &secure_rand).unwrap();
+
+        let opt_plain_msg = local_pool
+            .run_until(smc.request_decrypt(cipher_msg))
+            .unwrap();
+        assert_eq!(opt_plain_msg, Some(my_message.to_vec()));
+
+        // A message that was not encrypted to us:
+        let opt_plain_msg = local_pool
+            .run_until(smc.request_decrypt(my_message.to_vec()))
+            .unwrap();
+        assert_eq!(opt_plain_msg, None);
+    }
+
     // TODO: Add tests that check "concurrency": Multiple clients that send requests.
 }
@@ -3,7 +3,9 @@ use futures::prelude::*;
 
 use crypto::identity::Identity;
 
-use super::messages::{ResponsePublicKey, ResponseSignature, ToIdentity};
+use super::messages::{
+    ResponseDecrypt, ResponseDhPublicKey, ResponsePublicKey, ResponseSignature, ToIdentity,
+};
 
 /*
 pub enum IdentityError {
@@ -36,6 +38,21 @@ pub fn create_identity<I: Identity>(
                 });
                 future::ready(())
             }
+            ToIdentity::RequestDhPublicKey { response_sender } => {
+                let _ = response_sender.send(ResponseDhPublicKey {
+                    dh_public_key: identity.get_dh_public_key(),
+                });
+                future::ready(())
+            }
+            ToIdentity::RequestDecrypt {
+                cipher_msg,
+                response_sender,
+            } => {
+                let _ = response_sender.send(ResponseDecrypt {
+                    opt_plain_msg: identity.decrypt(&cipher_msg).ok(),
+                });
+                future::ready(())
+            }
         }
     });
 
@@ -1,3 +1,4 @@
+use crypto::dh::DhPublicKey;
 use crypto::identity::{PublicKey, Signature};
 use futures::channel::oneshot;
 
@@ -12,6 +13,15 @@ pub enum ToIdentity {
     RequestPublicKey {
         response_sender: oneshot::Sender<ResponsePublicKey>,
     },
+    /// Request the public key used for encrypting messages to the identity.
+    RequestDhPublicKey {
+        response_sender: oneshot::Sender<ResponseDhPublicKey>,
+    },
+    /// Request to decrypt a message that was encrypted to the identity DH public key.
+    RequestDecrypt {
+        cipher_msg: Vec<u8>,
+        response_sender: oneshot::Sender<ResponseDecrypt>,
+    },
 }
 
 /// Return requested signature over a message
@@ -23,3 +33,13 @@ pub struct ResponseSignature {
 pub struct ResponsePublicKey {
     pub public_key: PublicKey,
 }
+
+/// Return the identity DH public key.
+pub struct ResponseDhPublicKey {
+    pub dh_public_key: DhPublicKey,
+}
+
+/// Return the decrypted message, or None if decryption failed.
+pub struct ResponseDecrypt {
+    pub opt_plain_msg: Option<Vec<u8>>,
+}
@@ -16,9 +16,10 @@ use crypto::uid::Uid;
 use database::DatabaseClient;
 
 use proto::index_client::messages::{
-    AppServerToIndexClient, ClientResponseRoutes, IndexClientReportMutation,
-    IndexClientReportMutations, IndexClientRequest, IndexClientToAppServer, IndexMutation,
-    RequestRoutes, ResponseRoutesResult,
+    AppServerToIndexClient, ClientResponseLiquidity, ClientResponseRoutes,
+    IndexClientReportMutation, IndexClientReportMutations, IndexClientRequest,
+    IndexClientToAppServer, IndexMutation, RequestLiquidity, RequestRoutes,
+    ResponseLiquidityResult, ResponseRoutesResult,
 };
 use proto::index_server::messages::{IndexServerAddress, NamedIndexServerAddress};
 
@@ -114,6 +115,7 @@ enum IndexClientEvent<ISA> {
     IndexServerConnected(ControlSender),
     IndexServerClosed,
     ResponseRoutes((Uid, ResponseRoutesResult)),
+    ResponseLiquidity((Uid, ResponseLiquidityResult)),
     TimerTick,
 }
 
@@ -321,6 +323,22 @@ where
         .map_err(|_| IndexClientError::SendToAppServerFailed)
     }
 
+    pub async fn return_response_liquidity_failure(
+        &mut self,
+        request_id: Uid,
+    ) -> Result<(), IndexClientError> {
+        let client_response_liquidity = ClientResponseLiquidity {
+            request_id,
+            result: ResponseLiquidityResult::Failure,
+        };
+        await!(self
+            .to_app_server
+            .send(IndexClientToAppServer::ResponseLiquidity(
+                client_response_liquidity
+            )))
+        .map_err(|_| IndexClientError::SendToAppServerFailed)
+    }
+
     pub async fn handle_from_app_server_add_index_server(
         &mut self,
         app_request_id: Uid,
@@ -480,6 +498,68 @@ where
             .map_err(|_| IndexClientError::SpawnError)
     }
 
+    pub async fn handle_from_app_server_request_liquidity(
+        &mut self,
+        app_request_id: Uid,
+        request_liquidity: RequestLiquidity,
+    ) -> Result<(), IndexClientError> {
+        // Send empty report (Indicates that we received the request):
+        let index_client_report_mutations = IndexClientReportMutations {
+            opt_app_request_id: Some(app_request_id),
+            mutations: Vec::new(),
+        };
+        await!(self
+            .to_app_server
+            .send(IndexClientToAppServer::ReportMutations(
+                index_client_report_mutations
+            )))
+        .map_err(|_| IndexClientError::SendToAppServerFailed)?;
+
+        let request_id = request_liquidity.request_id;
+        if self.num_open_requests >= self.max_open_requests {
+            return await!(self.return_response_liquidity_failure(request_id));
+        }
+
+        // Check server connection status:
+        let mut server_connected = match &mut self.conn_status {
+            ConnStatus::Empty(_) | ConnStatus::Connecting(_) => {
+                return await!(self.return_response_liquidity_failure(request_id))
+            }
+            ConnStatus::Connected(server_connected) => server_connected,
+        };
+
+        let mut control_sender = match server_connected.opt_control_sender.take() {
+            Some(control_sender) => control_sender,
+            None => return await!(self.return_response_liquidity_failure(request_id)),
+        };
+
+        let (response_sender, response_receiver) = oneshot::channel();
+        let single_client_control =
+            SingleClientControl::RequestLiquidity((request_liquidity, response_sender));
+
+        match await!(control_sender.send(single_client_control)) {
+            Ok(()) => server_connected.opt_control_sender = Some(control_sender),
+            Err(_) => return await!(self.return_response_liquidity_failure(request_id)),
+        };
+
+        let mut c_event_sender = self.event_sender.clone();
+        let request_fut = async move {
+            let response_liquidity_result = match await!(response_receiver) {
+                Ok(liquidity) => ResponseLiquidityResult::Success(liquidity),
+                Err(_) => ResponseLiquidityResult::Failure,
+            };
+            let _ = await!(c_event_sender.send(IndexClientEvent::ResponseLiquidity((
+                request_id,
+                response_liquidity_result
+            ))));
+        };
+
+        self.num_open_requests = self.num_open_requests.saturating_add(1);
+        self.spawner
+            .spawn(request_fut)
+            .map_err(|_| IndexClientError::SpawnError)
+    }
+
     pub async fn handle_from_app_server_apply_mutations(
         &mut self,
         mut mutations: Vec<IndexMutation>,
@@ -538,6 +618,11 @@ where
                         await!(self
                             .handle_from_app_server_request_routes(app_request_id, request_routes))
                     }
+                    IndexClientRequest::RequestLiquidity(request_liquidity) => await!(self
+                        .handle_from_app_server_request_liquidity(
+                            app_request_id,
+                            request_liquidity
+                        )),
                 }
             }
             AppServerToIndexClient::ApplyMutations(mutations) => {
@@ -628,6 +713,26 @@ where
         .map_err(|_| IndexClientError::SendToAppServerFailed)
     }
 
+    pub async fn handle_response_liquidity(
+        &mut self,
+        request_id: Uid,
+        response_liquidity_result: ResponseLiquidityResult,
+    ) -> Result<(), IndexClientError> {
+        self.num_open_requests = self.num_open_requests.checked_sub(1).unwrap();
+
+        let client_response_liquidity = ClientResponseLiquidity {
+            request_id,
+            result: response_liquidity_result,
+        };
+
+        await!(self
+            .to_app_server
+            .send(IndexClientToAppServer::ResponseLiquidity(
+                client_response_liquidity
+            )))
+        .map_err(|_| IndexClientError::SendToAppServerFailed)
+    }
+
     pub async fn handle_timer_tick(&mut self) -> Result<(), IndexClientError> {
         // Make sure that we are connected to any server:
         let server_connected: &mut ServerConnected<ISA> = match self.conn_status {
@@ -750,6 +855,9 @@ where
             IndexClientEvent::ResponseRoutes((request_id, response_routes_result)) => {
                 await!(index_client.handle_response_routes(request_id, response_routes_result))?
             }
+            IndexClientEvent::ResponseLiquidity((request_id, response_liquidity_result)) => await!(
+                index_client.handle_response_liquidity(request_id, response_liquidity_result)
+            )?,
             IndexClientEvent::TimerTick => await!(index_client.handle_timer_tick())?,
         };
     }
@@ -14,8 +14,8 @@ use crypto::uid::Uid;
 use identity::IdentityClient;
 
 use proto::index_server::messages::{
-    IndexClientToServer, IndexMutation, IndexServerToClient, MultiRoute, MutationsUpdate,
-    RequestRoutes, ResponseRoutes,
+    IndexClientToServer, IndexMutation, IndexServerToClient, Liquidity, MultiRoute,
+    MutationsUpdate, RequestLiquidity, RequestRoutes, ResponseLiquidity, ResponseRoutes,
 };
 
 pub type ServerConn = ConnPair<IndexClientToServer, IndexServerToClient>;
@@ -23,6 +23,7 @@ pub type ServerConn = ConnPair<IndexClientToServer, IndexServerToClient>;
 #[derive(Debug)]
 pub enum SingleClientControl {
     RequestRoutes((RequestRoutes, oneshot::Sender<Vec<MultiRoute>>)),
+    RequestLiquidity((RequestLiquidity, oneshot::Sender<Liquidity>)),
     SendMutations(Vec<IndexMutation>),
 }
 
@@ -55,6 +56,8 @@ struct SingleClient<TS, R> {
     server_time_hash: HashResult,
     /// Unanswered requests, waiting for a response from the server
     open_requests: HashMap<Uid, oneshot::Sender<Vec<MultiRoute>>>,
+    /// Unanswered liquidity requests, waiting for a response from the server
+    open_liquidity_requests: HashMap<Uid, oneshot::Sender<Liquidity>>,
 }
 
 impl<TS, R> SingleClient<TS, R>
@@ -79,6 +82,7 @@ where
             counter: 0,
             server_time_hash,
             open_requests: HashMap::new(),
+            open_liquidity_requests: HashMap::new(),
         }
     }
 
@@ -111,6 +115,28 @@ where
                     );
                 }
             }
+            IndexServerToClient::ResponseLiquidity(response_liquidity) => {
+                let ResponseLiquidity {
+                    request_id,
+                    liquidity,
+                } = response_liquidity;
+                let request_sender = match self.open_liquidity_requests.remove(&request_id) {
+                    Some(request_sender) => request_sender,
+                    None => {
+                        warn!(
+                            "Received a liquidity response for unrecognized request_id: {:?}",
+                            &request_id
+                        );
+                        return Ok(());
+                    }
+                };
+                if request_sender.send(liquidity).is_err() {
+                    warn!(
+                        "Failed to return liquidity response for request_id: {:?} ",
+                        &request_id
+                    );
+                }
+            }
         }
         Ok(())
     }
@@ -131,6 +157,16 @@ where
                 await!(self.to_server.send(to_server_message))
                     .map_err(|_| SingleClientError::SendToServerError)?;
             }
+            SingleClientControl::RequestLiquidity((request_liquidity, response_sender)) => {
+                // Add a new open request:
+                self.open_liquidity_requests
+                    .insert(request_liquidity.request_id, response_sender);
+
+                // Send request to server:
+                let to_server_message = IndexClientToServer::RequestLiquidity(request_liquidity);
+                await!(self.to_server.send(to_server_message))
+                    .map_err(|_| SingleClientError::SendToServerError)?;
+            }
             SingleClientControl::SendMutations(index_mutations) => {
                 let mut mutations_update = MutationsUpdate {
                     node_public_key: self.local_public_key.clone(),
@@ -248,6 +284,8 @@ mod tests {
     use crypto::test_utils::DummyRandom;
     use crypto::uid::UID_LEN;
 
+    use proto::index_server::messages::RouteSearchMode;
+
     use identity::create_identity;
 
     async fn task_first_server_time_hash() {
@@ -331,6 +369,7 @@ mod tests {
             source: PublicKey::from(&[0xcc; PUBLIC_KEY_LEN]),
             destination: PublicKey::from(&[0xdd; PUBLIC_KEY_LEN]),
             opt_exclude: None,
+            search_mode: RouteSearchMode::Shortest,
         };
 
         let (response_sender, response_receiver) = oneshot::channel();
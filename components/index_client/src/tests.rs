@@ -10,7 +10,7 @@ use crypto::uid::{Uid, UID_LEN};
 use proto::funder::messages::Rate;
 use proto::index_client::messages::{
     AppServerToIndexClient, IndexClientReportMutation, IndexClientRequest, IndexClientToAppServer,
-    IndexMutation, RequestRoutes, ResponseRoutesResult, UpdateFriend,
+    IndexMutation, RequestRoutes, ResponseRoutesResult, RouteSearchMode, UpdateFriend,
 };
 use proto::index_server::messages::{IndexServerAddress, NamedIndexServerAddress};
 
@@ -391,6 +391,7 @@ where
         source: PublicKey::from(PublicKey::from(&[0xee; PUBLIC_KEY_LEN])),
         destination: PublicKey::from(PublicKey::from(&[0xff; PUBLIC_KEY_LEN])),
         opt_exclude: None,
+        search_mode: RouteSearchMode::Shortest,
     };
 
     // Request routes from IndexClient (From AppServer):
@@ -496,6 +497,7 @@ where
         source: PublicKey::from(PublicKey::from(&[0xee; PUBLIC_KEY_LEN])),
         destination: PublicKey::from(PublicKey::from(&[0xff; PUBLIC_KEY_LEN])),
         opt_exclude: None,
+        search_mode: RouteSearchMode::Shortest,
     };
 
     // Request routes from IndexClient (From AppServer):
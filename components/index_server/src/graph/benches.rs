@@ -0,0 +1,72 @@
+//! Benchmarks for the route search modes of SimpleCapacityGraph.
+//! Run using `cargo bench -p offst-index-server`.
+
+use test::Bencher;
+
+use super::capacity_graph::{CapacityEdge, CapacityGraph, SearchMode};
+use super::simple_capacity_graph::SimpleCapacityGraph;
+use super::test_utils::ConstRate;
+
+const NUM_NODES: u32 = 1000;
+const NUM_NEIGHBORS: u32 = 6;
+const REQUESTED_CAPACITY: u128 = 20;
+
+/// A deterministic pseudo random generator (Linear congruential generator).
+/// Allows to compare benchmark results between runs.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 33) as u32
+    }
+}
+
+/// Create a random graph with about `NUM_NODES * NUM_NEIGHBORS` bidirectional edges
+fn random_capacity_graph() -> SimpleCapacityGraph<u32, ConstRate> {
+    let mut lcg = Lcg(0);
+    let mut cg = SimpleCapacityGraph::new();
+    for a in 0..NUM_NODES {
+        for _ in 0..NUM_NEIGHBORS {
+            let b = lcg.next() % NUM_NODES;
+            if a == b {
+                continue;
+            }
+            let capacity = u128::from(lcg.next() % 100);
+            cg.update_edge(
+                a,
+                b,
+                CapacityEdge::new((capacity, capacity), ConstRate(lcg.next() % 10)),
+            );
+            cg.update_edge(
+                b,
+                a,
+                CapacityEdge::new((capacity, capacity), ConstRate(lcg.next() % 10)),
+            );
+        }
+    }
+    cg
+}
+
+fn bench_get_multi_routes(bencher: &mut Bencher, search_mode: SearchMode) {
+    let cg = random_capacity_graph();
+    let mut lcg = Lcg(1);
+    bencher.iter(|| {
+        let a = lcg.next() % NUM_NODES;
+        let b = lcg.next() % NUM_NODES;
+        cg.get_multi_routes(&a, &b, REQUESTED_CAPACITY, None, search_mode)
+    });
+}
+
+#[bench]
+fn bench_get_multi_routes_shortest(bencher: &mut Bencher) {
+    bench_get_multi_routes(bencher, SearchMode::Shortest);
+}
+
+#[bench]
+fn bench_get_multi_routes_cheapest(bencher: &mut Bencher) {
+    bench_get_multi_routes(bencher, SearchMode::Cheapest);
+}
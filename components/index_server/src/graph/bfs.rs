@@ -31,7 +31,7 @@ where
     None
 }
 
-fn bfs_backtrack<N>(dst: &N, backtrack: &HashMap<N, Option<N>>) -> Option<Vec<N>>
+pub fn bfs_backtrack<N>(dst: &N, backtrack: &HashMap<N, Option<N>>) -> Option<Vec<N>>
 where
     N: Clone + cmp::Eq + hash::Hash,
 {
@@ -22,7 +22,6 @@ pub struct CapacityEdge<C, T> {
     pub rate: T,
 }
 
-#[allow(unused)]
 impl<C, T> CapacityEdge<C, T> {
     pub fn new(capacity: CapacityPair<C>, rate: T) -> Self {
         Self { capacity, rate }
@@ -32,15 +31,37 @@ impl<C, T> CapacityEdge<C, T> {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CapacityRoute<N, C, T> {
     pub route: Vec<N>,
+    /// Amount of credits that can be sent along the route.
+    /// If the multi route contains a few routes, this is the amount allocated to this route, so
+    /// that all the routes (And the fees paid along them) can be used together.
     pub capacity: C,
     pub rate: T,
 }
 
+/// Criterion used to choose between routes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Prefer routes with less hops
+    Shortest,
+    /// Prefer routes with less fees for the requested capacity
+    Cheapest,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct CapacityMultiRoute<N, C, T> {
     pub routes: Vec<CapacityRoute<N, C, T>>,
 }
 
+/// Amount of credits a node can receive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapacityLiquidity<N, C> {
+    /// Capacity that can be received from every direct neighbor
+    pub neighbors: Vec<(N, C)>,
+    /// For every hop distance `i` (Starting from 1): The amount of nodes exactly `i` hops away,
+    /// and the capacity that can be received from all of those nodes together.
+    pub hops: Vec<(usize, C)>,
+}
+
 pub trait CapacityGraph {
     type Node; // Node type
     type Capacity; // Directed capacity between two neighboring nodes
@@ -73,14 +94,45 @@ pub trait CapacityGraph {
     ///
     /// opt_exclude is an optional edge to exclude (All of the returned routes must not go through this
     /// edge). This can be useful for finding non trivial loops.
+    ///
+    /// search_mode determines which routes are preferred.
     fn get_multi_routes(
         &self,
         a: &Self::Node,
         b: &Self::Node,
         capacity: Self::Capacity,
         opt_exclude: Option<(&Self::Node, &Self::Node)>,
+        search_mode: SearchMode,
     ) -> Vec<CapacityMultiRoute<Self::Node, Self::Capacity, Self::Rate>>;
 
+    /// Get the amount of credits that can be sent to `b`, from every direct neighbor of `b`, and
+    /// from all the nodes exactly `i` hops away from `b`, for every `1 <= i <= max_hops`.
+    fn get_liquidity(
+        &self,
+        b: &Self::Node,
+        max_hops: usize,
+    ) -> CapacityLiquidity<Self::Node, Self::Capacity>;
+
     /// Simulate advancement of time. Used to remove old edges.
     fn tick(&mut self, a: &Self::Node);
+
+    /// Get all the edges in the graph, together with their age (Amount of ticks since the edge
+    /// was last updated). Used for saving the graph to disk.
+    fn export_edges(
+        &self,
+    ) -> Vec<(
+        Self::Node,
+        Self::Node,
+        CapacityEdge<Self::Capacity, Self::Rate>,
+        u128,
+    )>;
+
+    /// Add an edge of a given age. Used for restoring a saved graph.
+    fn import_edge(
+        &mut self,
+        a: Self::Node,
+        b: Self::Node,
+        capacity_edge: CapacityEdge<Self::Capacity, Self::Rate>,
+        age: u128,
+    );
 }
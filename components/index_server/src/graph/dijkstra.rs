@@ -0,0 +1,107 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::{cmp, hash};
+
+use super::bfs::bfs_backtrack;
+
+/// Find a route of minimal cost from `src` to `dst`.
+/// `get_neighbors(node)` returns the neighbors of `node`, each together with the cost of moving
+/// from `node` to that neighbor. If a few routes have the same cost, a route with the least
+/// amount of hops is chosen.
+///
+/// Returns the route together with its total cost.
+pub fn dijkstra<'c, I, N, F>(src: &'c N, dst: &'c N, get_neighbors: F) -> Option<(Vec<N>, u128)>
+where
+    I: Iterator<Item = (&'c N, u128)>,
+    F: Fn(&N) -> I,
+    N: Clone + cmp::Eq + hash::Hash,
+{
+    // Just like bfs(), we never return a trivial route:
+    if src == dst {
+        return None;
+    }
+
+    // Best known (cost, hops) for every node:
+    let mut best: HashMap<N, (u128, usize)> = HashMap::new();
+    let mut backtrack: HashMap<N, Option<N>> = HashMap::new();
+    // Nodes are kept outside of the heap, because we don't require N to be Ord:
+    let mut heap_nodes: Vec<N> = Vec::new();
+    let mut heap = BinaryHeap::new();
+
+    best.insert(src.clone(), (0, 0));
+    backtrack.insert(src.clone(), None);
+    heap_nodes.push(src.clone());
+    heap.push(Reverse((0u128, 0usize, 0usize)));
+
+    while let Some(Reverse((cost, hops, node_index))) = heap.pop() {
+        let node = heap_nodes[node_index].clone();
+        if best.get(&node) != Some(&(cost, hops)) {
+            // A better way to reach this node was already found:
+            continue;
+        }
+        if node == *dst {
+            return Some((bfs_backtrack(dst, &backtrack)?, cost));
+        }
+
+        for (neighbor, edge_cost) in get_neighbors(&node) {
+            let new_cost = match cost.checked_add(edge_cost) {
+                Some(new_cost) => new_cost,
+                None => continue,
+            };
+            let new_hops = hops.saturating_add(1);
+            if let Some(&known) = best.get(neighbor) {
+                if known <= (new_cost, new_hops) {
+                    continue;
+                }
+            }
+            best.insert(neighbor.clone(), (new_cost, new_hops));
+            backtrack.insert(neighbor.clone(), Some(node.clone()));
+            heap_nodes.push(neighbor.clone());
+            heap.push(Reverse((new_cost, new_hops, heap_nodes.len() - 1)));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dijkstra_basic() {
+        /*
+         Example graph (Edge costs in brackets):
+
+              0 --[1]--> 1 --[1]--> 2
+              |                     ^
+              |                     |
+              +--[5]--> 3 --[0]-----+
+              |
+              +--[2]--> 4 --[0]--> 5 --[0]--> 2
+        */
+
+        let mut graph = HashMap::new();
+        graph.insert(0u32, vec![(1u32, 1u128), (3, 5), (4, 2)]);
+        graph.insert(1, vec![(2, 1)]);
+        graph.insert(2, vec![]);
+        graph.insert(3, vec![(2, 0)]);
+        graph.insert(4, vec![(5, 0)]);
+        graph.insert(5, vec![(2, 0)]);
+
+        let get_neighbors = |node: &u32| {
+            graph
+                .get(&node)
+                .unwrap()
+                .iter()
+                .map(|(neighbor, cost)| (neighbor, *cost))
+        };
+
+        // Both 0 -> 1 -> 2 and 0 -> 4 -> 5 -> 2 cost 2. The shorter one is chosen:
+        assert_eq!(dijkstra(&0, &2, get_neighbors), Some((vec![0, 1, 2], 2)));
+        assert_eq!(dijkstra(&0, &5, get_neighbors), Some((vec![0, 4, 5], 2)));
+        assert_eq!(dijkstra(&3, &2, get_neighbors), Some((vec![3, 2], 0)));
+
+        assert_eq!(dijkstra(&2, &0, get_neighbors), None);
+        assert_eq!(dijkstra(&0, &0, get_neighbors), None);
+    }
+}
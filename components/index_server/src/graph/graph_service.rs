@@ -2,7 +2,9 @@ use futures::channel::{mpsc, oneshot};
 use futures::task::{Spawn, SpawnError, SpawnExt};
 use futures::{FutureExt, SinkExt, StreamExt, TryFutureExt};
 
-use super::capacity_graph::{CapacityEdge, CapacityGraph, CapacityMultiRoute};
+use super::capacity_graph::{
+    CapacityEdge, CapacityGraph, CapacityLiquidity, CapacityMultiRoute, SearchMode,
+};
 
 pub enum GraphRequest<N, C, T> {
     /// Change capacities on a directed edge:
@@ -24,10 +26,15 @@ pub enum GraphRequest<N, C, T> {
         N,
         C,
         Option<(N, N)>,
+        SearchMode,
         oneshot::Sender<Vec<CapacityMultiRoute<N, C, T>>>,
-    ), // (from, to, capacity, opt_exclude)
+    ), // (from, to, capacity, opt_exclude, search_mode)
+    /// Get the amount of credits that can be sent to a node, up to a certain hop distance.
+    GetLiquidity(N, usize, oneshot::Sender<CapacityLiquidity<N, C>>), // (to, max_hops)
     /// Expire old outgoing edges for the specified node
     Tick(N, oneshot::Sender<()>),
+    /// Get all edges in the graph, together with their ages
+    ExportEdges(oneshot::Sender<Vec<(N, N, CapacityEdge<C, T>, u128)>>),
 }
 
 #[derive(Debug)]
@@ -52,17 +59,25 @@ where
         GraphRequest::RemoveNode(a, sender) => {
             let _ = sender.send(capacity_graph.remove_node(&a));
         }
-        GraphRequest::GetMultiRoutes(a, b, capacity, opt_exclude, sender) => {
+        GraphRequest::GetMultiRoutes(a, b, capacity, opt_exclude, search_mode, sender) => {
             let routes = match opt_exclude {
-                Some((c, d)) => capacity_graph.get_multi_routes(&a, &b, capacity, Some((&c, &d))),
-                None => capacity_graph.get_multi_routes(&a, &b, capacity, None),
+                Some((c, d)) => {
+                    capacity_graph.get_multi_routes(&a, &b, capacity, Some((&c, &d)), search_mode)
+                }
+                None => capacity_graph.get_multi_routes(&a, &b, capacity, None, search_mode),
             };
             let _ = sender.send(routes);
         }
+        GraphRequest::GetLiquidity(b, max_hops, sender) => {
+            let _ = sender.send(capacity_graph.get_liquidity(&b, max_hops));
+        }
         GraphRequest::Tick(a, sender) => {
             capacity_graph.tick(&a);
             let _ = sender.send(());
         }
+        GraphRequest::ExportEdges(sender) => {
+            let _ = sender.send(capacity_graph.export_edges());
+        }
     }
 }
 
@@ -173,6 +188,7 @@ impl<N, C, T> GraphClient<N, C, T> {
         b: N,
         capacity: C,
         opt_exclude: Option<(N, N)>,
+        search_mode: SearchMode,
     ) -> Result<Vec<CapacityMultiRoute<N, C, T>>, GraphClientError> {
         let (sender, receiver) = oneshot::channel();
         await!(self.requests_sender.send(GraphRequest::GetMultiRoutes(
@@ -180,17 +196,41 @@ impl<N, C, T> GraphClient<N, C, T> {
             b,
             capacity,
             opt_exclude,
+            search_mode,
             sender
         )))?;
         Ok(await!(receiver)?)
     }
 
+    /// Get the amount of credits that can be sent to `b`, from every direct neighbor, and from
+    /// the nodes at every hop distance up to `max_hops`.
+    pub async fn get_liquidity(
+        &mut self,
+        b: N,
+        max_hops: usize,
+    ) -> Result<CapacityLiquidity<N, C>, GraphClientError> {
+        let (sender, receiver) = oneshot::channel();
+        await!(self
+            .requests_sender
+            .send(GraphRequest::GetLiquidity(b, max_hops, sender)))?;
+        Ok(await!(receiver)?)
+    }
+
     /// Remove an edge from the graph
     pub async fn tick(&mut self, a: N) -> Result<(), GraphClientError> {
         let (sender, receiver) = oneshot::channel();
         await!(self.requests_sender.send(GraphRequest::Tick(a, sender)))?;
         Ok(await!(receiver)?)
     }
+
+    /// Get all edges in the graph, together with their ages
+    pub async fn export_edges(
+        &mut self,
+    ) -> Result<Vec<(N, N, CapacityEdge<C, T>, u128)>, GraphClientError> {
+        let (sender, receiver) = oneshot::channel();
+        await!(self.requests_sender.send(GraphRequest::ExportEdges(sender)))?;
+        Ok(await!(receiver)?)
+    }
 }
 
 /// Spawn a graph service, returning a GraphClient on success.
@@ -241,7 +281,7 @@ mod tests {
         await!(graph_client.update_edge(5, 2, CapacityEdge::new((5, 30), ConstRate(1)))).unwrap();
 
         assert_eq!(
-            await!(graph_client.get_multi_routes(2, 5, 29, None)).unwrap(),
+            await!(graph_client.get_multi_routes(2, 5, 29, None, SearchMode::Shortest)).unwrap(),
             vec![CapacityMultiRoute {
                 routes: vec![CapacityRoute {
                     route: vec![2, 5],
@@ -251,7 +291,7 @@ mod tests {
             }]
         );
         assert_eq!(
-            await!(graph_client.get_multi_routes(2, 5, 30, None)).unwrap(),
+            await!(graph_client.get_multi_routes(2, 5, 30, None, SearchMode::Shortest)).unwrap(),
             vec![CapacityMultiRoute {
                 routes: vec![CapacityRoute {
                     route: vec![2, 5],
@@ -261,12 +301,22 @@ mod tests {
             }]
         );
         assert_eq!(
-            await!(graph_client.get_multi_routes(2, 5, 31, None)).unwrap(),
+            await!(graph_client.get_multi_routes(2, 5, 31, None, SearchMode::Shortest)).unwrap(),
             vec![]
         );
 
         await!(graph_client.tick(2)).unwrap();
 
+        let mut edges = await!(graph_client.export_edges()).unwrap();
+        edges.sort_by_key(|(a, _b, _capacity_edge, _age)| *a);
+        assert_eq!(
+            edges,
+            vec![
+                (2, 5, CapacityEdge::new((30, 5), ConstRate(1)), 1),
+                (5, 2, CapacityEdge::new((5, 30), ConstRate(1)), 0),
+            ]
+        );
+
         assert_eq!(
             await!(graph_client.remove_edge(2, 5)).unwrap(),
             Some(CapacityEdge::new((30, 5), ConstRate(1)))
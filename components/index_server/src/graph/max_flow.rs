@@ -0,0 +1,445 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::{cmp, hash};
+
+use super::bfs::{bfs, bfs_backtrack};
+
+/// Flow currently pushed along every directed edge.
+/// We never keep flow in both directions of an edge: Opposite flows cancel each other.
+struct Flows<N> {
+    flows: HashMap<(N, N), u128>,
+    /// For every node, the set of nodes that currently push flow into it.
+    /// Used to find residual edges that go against the direction of the flow.
+    flow_sources: HashMap<N, HashSet<N>>,
+}
+
+impl<N> Flows<N>
+where
+    N: Clone + cmp::Eq + hash::Hash,
+{
+    fn new() -> Self {
+        Flows {
+            flows: HashMap::new(),
+            flow_sources: HashMap::new(),
+        }
+    }
+
+    fn get(&self, a: &N, b: &N) -> u128 {
+        self.flows
+            .get(&(a.clone(), b.clone()))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    fn set(&mut self, a: &N, b: &N, flow: u128) {
+        if flow > 0 {
+            self.flows.insert((a.clone(), b.clone()), flow);
+            self.flow_sources
+                .entry(b.clone())
+                .or_insert_with(HashSet::new)
+                .insert(a.clone());
+        } else {
+            self.flows.remove(&(a.clone(), b.clone()));
+            if let Some(b_sources) = self.flow_sources.get_mut(b) {
+                b_sources.remove(a);
+                if b_sources.is_empty() {
+                    self.flow_sources.remove(b);
+                }
+            }
+        }
+    }
+
+    /// Push `amount` additional credits from `a` to `b`
+    fn push(&mut self, a: &N, b: &N, amount: u128) {
+        let back_flow = self.get(b, a);
+        let cancel = cmp::min(amount, back_flow);
+        self.set(b, a, back_flow - cancel);
+        if amount > cancel {
+            let flow = self.get(a, b);
+            self.set(a, b, flow + (amount - cancel));
+        }
+    }
+}
+
+/// Find a shortest route from any of the nodes in `srcs` to `dst` in the residual graph,
+/// containing at most `max_route_len` nodes.
+/// Returns the route, together with the amount of credits that can be pushed along the route.
+fn augmenting_route<'c, I, N, F>(
+    srcs: &[N],
+    dst: &N,
+    max_route_len: usize,
+    get_neighbors: &F,
+    flows: &Flows<N>,
+) -> Option<(Vec<N>, u128)>
+where
+    I: Iterator<Item = (&'c N, u128)>,
+    F: Fn(&N) -> I,
+    N: Clone + cmp::Eq + hash::Hash + 'c,
+{
+    let mut backtrack: HashMap<N, Option<N>> = HashMap::new();
+    // The residual capacity of the edge used to reach every node:
+    let mut reach_capacity: HashMap<N, u128> = HashMap::new();
+    // The amount of nodes in the route used to reach every node:
+    let mut route_lens: HashMap<N, usize> = HashMap::new();
+    let mut queue: VecDeque<N> = VecDeque::new();
+
+    for src in srcs {
+        backtrack.insert(src.clone(), None);
+        route_lens.insert(src.clone(), 1);
+        queue.push_back(src.clone());
+    }
+
+    while let Some(node) = queue.pop_front() {
+        let route_len = route_lens[&node];
+        if route_len >= max_route_len {
+            // Routes through `node` can not be extended any further:
+            continue;
+        }
+        let mut residuals: HashMap<N, u128> = HashMap::new();
+        for (neighbor, capacity) in get_neighbors(&node) {
+            let residual = capacity.saturating_sub(flows.get(&node, neighbor));
+            residuals.insert(neighbor.clone(), residual);
+        }
+        // Flow pushed into `node` can be canceled:
+        if let Some(node_sources) = flows.flow_sources.get(&node) {
+            for source in node_sources {
+                let residual = residuals.entry(source.clone()).or_insert(0);
+                *residual = residual.saturating_add(flows.get(source, &node));
+            }
+        }
+
+        for (neighbor, residual) in residuals {
+            if residual == 0 || backtrack.contains_key(&neighbor) {
+                continue;
+            }
+            backtrack.insert(neighbor.clone(), Some(node.clone()));
+            reach_capacity.insert(neighbor.clone(), residual);
+            route_lens.insert(neighbor.clone(), route_len + 1);
+            if neighbor == *dst {
+                let route = bfs_backtrack(dst, &backtrack)?;
+                let capacity = route[1..].iter().map(|node| reach_capacity[node]).min()?;
+                return Some((route, capacity));
+            }
+            queue.push_back(neighbor);
+        }
+    }
+    None
+}
+
+/// Split a flow from `src` to `dst` into routes.
+/// Returns every route together with the amount of credits it carries.
+fn decompose_flow<N>(src: &N, dst: &N, mut flows: Flows<N>) -> Vec<(Vec<N>, u128)>
+where
+    N: Clone + cmp::Eq + hash::Hash,
+{
+    let mut out_edges: HashMap<N, Vec<N>> = HashMap::new();
+    for (a, b) in flows.flows.keys() {
+        out_edges
+            .entry(a.clone())
+            .or_insert_with(Vec::new)
+            .push(b.clone());
+    }
+
+    let mut routes = Vec::new();
+    loop {
+        let opt_route = {
+            let flows = &flows;
+            let get_neighbors = |node: &N| {
+                let node = node.clone();
+                out_edges
+                    .get(&node)
+                    .into_iter()
+                    .flatten()
+                    .filter(move |next_node| flows.get(&node, next_node) > 0)
+            };
+            bfs(src, dst, get_neighbors)
+        };
+        let route = match opt_route {
+            Some(route) => route,
+            None => break,
+        };
+
+        // Every iteration removes at least one edge, so this loop must end.
+        let capacity = (0..route.len() - 1)
+            .map(|i| flows.get(&route[i], &route[i + 1]))
+            .min()
+            .unwrap();
+        for i in 0..route.len() - 1 {
+            let flow = flows.get(&route[i], &route[i + 1]);
+            flows.set(&route[i], &route[i + 1], flow - capacity);
+        }
+        routes.push((route, capacity));
+    }
+    routes
+}
+
+/// Find routes from `src` to `dst` that can be used together to send `max_capacity` credits,
+/// using the Edmonds-Karp maximum flow algorithm.
+/// `get_neighbors(node)` returns the neighbors of `node`, each together with the capacity of the
+/// directed edge from `node` to that neighbor.
+///
+/// Only augmenting routes of at most `max_route_len` nodes are used. Note that the returned
+/// routes may still be longer, because flows along different augmenting routes may cancel each
+/// other.
+///
+/// Returns a list of routes, each together with the amount of credits allocated to it.
+/// The routes respect the capacities of all edges when used together. The total amount of
+/// credits is `max_capacity`, or less if the maximum flow from `src` to `dst` is smaller.
+pub fn max_flow<'c, I, N, F>(
+    src: &N,
+    dst: &N,
+    max_capacity: u128,
+    max_route_len: usize,
+    get_neighbors: F,
+) -> Vec<(Vec<N>, u128)>
+where
+    I: Iterator<Item = (&'c N, u128)>,
+    F: Fn(&N) -> I,
+    N: Clone + cmp::Eq + hash::Hash + 'c,
+{
+    // Just like bfs(), we never return trivial routes:
+    if src == dst {
+        return Vec::new();
+    }
+
+    let (flows, _total_capacity) = push_flow(
+        &[src.clone()],
+        dst,
+        max_capacity,
+        max_route_len,
+        &get_neighbors,
+    );
+    decompose_flow(src, dst, flows)
+}
+
+/// Calculate the maximum amount of credits that all the nodes in `srcs` can send together to
+/// `dst`. See max_flow() for the meaning of `get_neighbors`.
+pub fn max_flow_value<'c, I, N, F>(srcs: &[N], dst: &N, get_neighbors: F) -> u128
+where
+    I: Iterator<Item = (&'c N, u128)>,
+    F: Fn(&N) -> I,
+    N: Clone + cmp::Eq + hash::Hash + 'c,
+{
+    let srcs = srcs
+        .iter()
+        .filter(|&src| src != dst)
+        .cloned()
+        .collect::<Vec<_>>();
+    if srcs.is_empty() {
+        return 0;
+    }
+
+    let (_flows, total_capacity) = push_flow(
+        &srcs,
+        dst,
+        u128::max_value(),
+        usize::max_value(),
+        &get_neighbors,
+    );
+    total_capacity
+}
+
+/// Push flow from `srcs` to `dst` along augmenting routes of at most `max_route_len` nodes,
+/// until `max_capacity` credits are pushed, or until no more augmenting routes exist.
+/// Returns the resulting flow, together with the total amount of credits pushed.
+fn push_flow<'c, I, N, F>(
+    srcs: &[N],
+    dst: &N,
+    max_capacity: u128,
+    max_route_len: usize,
+    get_neighbors: &F,
+) -> (Flows<N>, u128)
+where
+    I: Iterator<Item = (&'c N, u128)>,
+    F: Fn(&N) -> I,
+    N: Clone + cmp::Eq + hash::Hash + 'c,
+{
+    let mut flows = Flows::new();
+    let mut total_capacity: u128 = 0;
+    while total_capacity < max_capacity {
+        let (route, capacity) =
+            match augmenting_route(srcs, dst, max_route_len, get_neighbors, &flows) {
+                Some(augmenting_route) => augmenting_route,
+                None => break,
+            };
+        let capacity = cmp::min(capacity, max_capacity - total_capacity);
+        for i in 0..route.len() - 1 {
+            flows.push(&route[i], &route[i + 1], capacity);
+        }
+        total_capacity += capacity;
+    }
+    (flows, total_capacity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total_capacity(routes: &[(Vec<u32>, u128)]) -> u128 {
+        routes.iter().map(|(_route, capacity)| capacity).sum()
+    }
+
+    #[test]
+    fn test_max_flow_basic() {
+        /*
+         Example graph (Edge capacities in brackets):
+
+              0 --[10]--> 1 --[10]--> 3
+              |           |           ^
+              |          [5]          |
+              |           V           |
+              +--[10]--> 2 --[10]-----+
+        */
+
+        let mut graph = HashMap::new();
+        graph.insert(0u32, vec![(1u32, 10u128), (2, 10)]);
+        graph.insert(1, vec![(3, 10), (2, 5)]);
+        graph.insert(2, vec![(3, 10)]);
+        graph.insert(3, vec![]);
+
+        let get_neighbors = |node: &u32| {
+            graph
+                .get(&node)
+                .unwrap()
+                .iter()
+                .map(|(neighbor, capacity)| (neighbor, *capacity))
+        };
+
+        let routes = max_flow(&0, &3, 20, usize::max_value(), get_neighbors);
+        assert_eq!(total_capacity(&routes), 20);
+        for (route, _capacity) in &routes {
+            assert_eq!(route[0], 0);
+            assert_eq!(route[route.len() - 1], 3);
+        }
+
+        // Request less than the maximum flow:
+        let routes = max_flow(&0, &3, 7, usize::max_value(), get_neighbors);
+        assert_eq!(total_capacity(&routes), 7);
+
+        // Request more than the maximum flow:
+        let routes = max_flow(&0, &3, 100, usize::max_value(), get_neighbors);
+        assert_eq!(total_capacity(&routes), 20);
+
+        let routes = max_flow(&1, &3, 100, usize::max_value(), get_neighbors);
+        assert_eq!(total_capacity(&routes), 15);
+
+        assert!(max_flow(&3, &0, 100, usize::max_value(), get_neighbors).is_empty());
+        assert!(max_flow(&0, &0, 100, usize::max_value(), get_neighbors).is_empty());
+    }
+
+    #[test]
+    fn test_max_flow_value_multiple_sources() {
+        /*
+         Example graph (Edge capacities in brackets):
+
+              0 --[10]--> 1 --[4]--> 3
+                          ^
+                          |
+              2 ---[5]----+
+        */
+
+        let mut graph = HashMap::new();
+        graph.insert(0u32, vec![(1u32, 10u128)]);
+        graph.insert(1, vec![(3, 4)]);
+        graph.insert(2, vec![(1, 5)]);
+        graph.insert(3, vec![]);
+
+        let get_neighbors = |node: &u32| {
+            graph
+                .get(&node)
+                .unwrap()
+                .iter()
+                .map(|(neighbor, capacity)| (neighbor, *capacity))
+        };
+
+        assert_eq!(max_flow_value(&[1], &3, get_neighbors), 4);
+        assert_eq!(max_flow_value(&[0], &1, get_neighbors), 10);
+        assert_eq!(max_flow_value(&[0, 2], &1, get_neighbors), 15);
+        assert_eq!(max_flow_value(&[0, 1, 2], &3, get_neighbors), 4);
+        // The destination itself is ignored:
+        assert_eq!(max_flow_value(&[0, 1], &1, get_neighbors), 10);
+        assert_eq!(max_flow_value(&[3], &0, get_neighbors), 0);
+        assert_eq!(max_flow_value(&[], &0, get_neighbors), 0);
+    }
+
+    #[test]
+    fn test_max_flow_cancel_flow() {
+        /*
+         All edges are of capacity 1.
+         The first augmenting route is the shortest one: 0 -> 1 -> 2 -> 3.
+         Getting the maximum flow requires canceling the flow along 1 -> 2:
+
+              0 -----> 1 -----> 5 -----> 6
+              |        |                 |
+              |        V                 V
+              |        2 --------------> 3
+              |        ^
+              V        |
+              4 -----> 7
+        */
+
+        let mut graph = HashMap::new();
+        graph.insert(0u32, vec![(1u32, 1u128), (4, 1)]);
+        graph.insert(1, vec![(2, 1), (5, 1)]);
+        graph.insert(2, vec![(3, 1)]);
+        graph.insert(3, vec![]);
+        graph.insert(4, vec![(7, 1)]);
+        graph.insert(5, vec![(6, 1)]);
+        graph.insert(6, vec![(3, 1)]);
+        graph.insert(7, vec![(2, 1)]);
+
+        let get_neighbors = |node: &u32| {
+            graph
+                .get(&node)
+                .unwrap()
+                .iter()
+                .map(|(neighbor, capacity)| (neighbor, *capacity))
+        };
+
+        let routes = max_flow(&0, &3, 1, usize::max_value(), get_neighbors);
+        assert_eq!(routes, vec![(vec![0, 1, 2, 3], 1)]);
+
+        let mut routes = max_flow(&0, &3, 2, usize::max_value(), get_neighbors);
+        routes.sort();
+        assert_eq!(
+            routes,
+            vec![(vec![0, 1, 5, 6, 3], 1), (vec![0, 4, 7, 2, 3], 1)]
+        );
+    }
+
+    #[test]
+    fn test_max_flow_max_route_len() {
+        /*
+         Example graph (Edge capacities in brackets):
+
+              0 --[10]--> 1 --[10]--> 3
+              |                       ^
+             [10]                    [5]
+              V                       |
+              2 --------[5]---------> 4
+        */
+
+        let mut graph = HashMap::new();
+        graph.insert(0u32, vec![(1u32, 10u128), (2, 10)]);
+        graph.insert(1, vec![(3, 10)]);
+        graph.insert(2, vec![(4, 5)]);
+        graph.insert(3, vec![]);
+        graph.insert(4, vec![(3, 5)]);
+
+        let get_neighbors = |node: &u32| {
+            graph
+                .get(&node)
+                .unwrap()
+                .iter()
+                .map(|(neighbor, capacity)| (neighbor, *capacity))
+        };
+
+        let routes = max_flow(&0, &3, 15, 4, get_neighbors);
+        assert_eq!(total_capacity(&routes), 15);
+
+        // The route through 2 and 4 is too long:
+        let routes = max_flow(&0, &3, 15, 3, get_neighbors);
+        assert_eq!(routes, vec![(vec![0, 1, 3], 10)]);
+
+        assert!(max_flow(&0, &3, 15, 2, get_neighbors).is_empty());
+    }
+}
@@ -1,8 +1,13 @@
 mod bfs;
 pub mod capacity_graph;
+mod dijkstra;
 pub mod graph_service;
+mod max_flow;
 pub mod simple_capacity_graph;
 mod utils;
 
 #[cfg(test)]
 mod test_utils;
+
+#[cfg(test)]
+mod benches;
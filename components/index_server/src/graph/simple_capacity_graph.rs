@@ -1,16 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::{cmp, hash};
 
 use super::bfs::bfs;
 use super::capacity_graph::{
-    CapacityEdge, CapacityGraph, CapacityMultiRoute, CapacityRoute, LinearRate,
+    CapacityEdge, CapacityGraph, CapacityLiquidity, CapacityMultiRoute, CapacityRoute, LinearRate,
+    SearchMode,
 };
+use super::dijkstra::dijkstra;
+use super::max_flow::{max_flow, max_flow_value};
 use super::utils::{option_to_vec, OptionIterator};
 
+use proto::consts::MAX_ROUTE_LEN;
+
 /// Amount of ticks an edge could live regardless of coupon collector's approximation.
 /// This is useful to allow the first edges build (n*log(n) is very small for small n).
 const BASE_MAX_EDGE_AGE: u128 = 16;
 
+/// Maximum amount of max flow computations done when splitting credits between routes. Every
+/// computation keeps more capacity aside for paying fees on the edges that were overloaded by the
+/// previous one.
+const MAX_FEE_ATTEMPTS: usize = 8;
+
 #[derive(Debug, Clone)]
 struct Edge<T> {
     capacity_edge: CapacityEdge<u128, T>,
@@ -72,7 +82,7 @@ pub struct SimpleCapacityGraph<N, T> {
 impl<N, T> SimpleCapacityGraph<N, T>
 where
     N: cmp::Eq + hash::Hash + Clone + std::fmt::Debug,
-    T: LinearRate + Clone,
+    T: LinearRate<K = u128> + Clone,
 {
     pub fn new() -> SimpleCapacityGraph<N, T> {
         Self {
@@ -129,6 +139,19 @@ where
         OptionIterator::new(Some(iter))
     }
 
+    /// Get all the nodes that can send credits directly to `b`
+    fn neighbors_with_recv_capacity(&self, b: &N) -> Vec<N> {
+        match self.nodes.get(b) {
+            Some(b_edges) => b_edges
+                .edges
+                .keys()
+                .filter(|a| self.get_send_capacity(a, b) > 0)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
     /// Calculate the amount of capacity we can send through a route.
     /// This amount if the minimum of all edge capacities of the route.
     fn get_route_capacity(&self, route: &[N]) -> Option<u128> {
@@ -154,17 +177,18 @@ where
     ///
     /// opt_exclude is an optional edge to exclude (The returned route must not go through this
     /// edge). This can be useful for finding non trivial loops.
+    ///
+    /// With `SearchMode::Shortest` the route with the least amount of hops is returned.
+    /// With `SearchMode::Cheapest` the route with the least fees for sending `capacity` credits
+    /// is returned.
     fn get_multi_route(
         &self,
         a: &N,
         b: &N,
         capacity: u128,
         opt_exclude: Option<(&N, &N)>,
+        search_mode: SearchMode,
     ) -> Option<CapacityMultiRoute<N, u128, T>> {
-        // TODO: Update this implementation:
-        // Currently get_route does not attemp to find the cheapest route (according to rate)
-        // It only finds the shortest route and then calculates the rate.
-
         let (opt_e_start, opt_e_end) = match opt_exclude {
             Some((e_start, e_end)) => (Some(e_start), Some(e_end)),
             None => (None, None),
@@ -174,7 +198,23 @@ where
             self.neighbors_with_send_capacity(cur_node.clone(), capacity)
                 .filter(move |&next_node| !cur_node_is_e_start || Some(next_node) != opt_e_end)
         };
-        let route = bfs(a, b, get_neighbors)?;
+        let route = match search_mode {
+            SearchMode::Shortest => bfs(a, b, get_neighbors)?,
+            SearchMode::Cheapest => {
+                let get_neighbors_fees = |cur_node: &N| {
+                    let cur_node = cur_node.clone();
+                    get_neighbors(&cur_node).filter_map(move |next_node| {
+                        // No fees are paid for the last hop (See get_route_rate()):
+                        if next_node == b {
+                            return Some((next_node, 0));
+                        }
+                        let edge = self.get_edge(&cur_node, next_node)?;
+                        Some((next_node, edge.capacity_edge.rate.calc_fee(capacity)?))
+                    })
+                };
+                dijkstra(a, b, get_neighbors_fees)?.0
+            }
+        };
         // We assert that we will always have valid capacity here:
         let capacity = self.get_route_capacity(&route).unwrap();
 
@@ -190,12 +230,107 @@ where
             routes: vec![graph_route],
         })
     }
+
+    /// Get a multi route that can be used to send `capacity` credits, splitting the credits
+    /// between a few routes if needed. The routes are found using a maximum flow computation.
+    ///
+    /// The capacity of every returned route is the amount of credits allocated to that route.
+    /// The fees for the allocated amounts are taken into account, so that all the routes can be
+    /// used together without exceeding the capacity of any edge. Every route contains at most
+    /// `MAX_ROUTE_LEN` nodes.
+    ///
+    /// opt_exclude is an optional edge to exclude (The returned routes must not go through this
+    /// edge).
+    fn get_max_flow_multi_route(
+        &self,
+        a: &N,
+        b: &N,
+        capacity: u128,
+        opt_exclude: Option<(&N, &N)>,
+    ) -> Option<CapacityMultiRoute<N, u128, T>> {
+        let (opt_e_start, opt_e_end) = match opt_exclude {
+            Some((e_start, e_end)) => (Some(e_start), Some(e_end)),
+            None => (None, None),
+        };
+
+        // Capacity kept aside on edges for paying fees, found by previous attempts:
+        let mut fee_reserves: HashMap<(N, N), u128> = HashMap::new();
+        for _ in 0..MAX_FEE_ATTEMPTS {
+            let fee_reserves_ref = &fee_reserves;
+            let get_neighbors = |cur_node: &N| {
+                let cur_node = cur_node.clone();
+                let cur_node_is_e_start = Some(&cur_node) == opt_e_start;
+                self.neighbors_with_send_capacity(cur_node.clone(), 1)
+                    .filter(move |&next_node| !cur_node_is_e_start || Some(next_node) != opt_e_end)
+                    .map(move |next_node| {
+                        let fee_reserve = fee_reserves_ref
+                            .get(&(cur_node.clone(), next_node.clone()))
+                            .cloned()
+                            .unwrap_or(0);
+                        let send_capacity = self
+                            .get_send_capacity(&cur_node, next_node)
+                            .saturating_sub(fee_reserve);
+                        (next_node, send_capacity)
+                    })
+            };
+
+            let flow_routes = max_flow(a, b, capacity, MAX_ROUTE_LEN, get_neighbors);
+            let total_capacity = flow_routes
+                .iter()
+                .fold(0u128, |total, (_route, route_capacity)| {
+                    total.saturating_add(*route_capacity)
+                });
+            if total_capacity < capacity {
+                // The network can not carry the requested amount of credits:
+                return None;
+            }
+
+            let mut routes = Vec::new();
+            // Amount of credits (Including fees) sent along every edge by all the routes together:
+            let mut edge_loads: HashMap<(N, N), u128> = HashMap::new();
+            for (route, route_capacity) in flow_routes {
+                if route.len() > MAX_ROUTE_LEN {
+                    return None;
+                }
+                let rate = self.get_route_rate(&route)?;
+                // The fees are paid along the route, hence every edge of the route carries at
+                // most the allocated amount together with all the fees:
+                let route_load = route_capacity.checked_add(rate.calc_fee(route_capacity)?)?;
+                for i in 0..route.len() - 1 {
+                    let edge_load = edge_loads
+                        .entry((route[i].clone(), route[i + 1].clone()))
+                        .or_insert(0);
+                    *edge_load = edge_load.checked_add(route_load)?;
+                }
+                routes.push(CapacityRoute {
+                    route,
+                    capacity: route_capacity,
+                    rate,
+                });
+            }
+
+            let mut is_overloaded = false;
+            for ((node_a, node_b), edge_load) in edge_loads {
+                let send_capacity = self.get_send_capacity(&node_a, &node_b);
+                if edge_load > send_capacity {
+                    // Keep aside the missing capacity in the next attempt:
+                    let fee_reserve = fee_reserves.entry((node_a, node_b)).or_insert(0);
+                    *fee_reserve = fee_reserve.saturating_add(edge_load - send_capacity);
+                    is_overloaded = true;
+                }
+            }
+            if !is_overloaded {
+                return Some(CapacityMultiRoute { routes });
+            }
+        }
+        None
+    }
 }
 
 impl<N, T> CapacityGraph for SimpleCapacityGraph<N, T>
 where
     N: cmp::Eq + hash::Hash + Clone + std::fmt::Debug,
-    T: LinearRate + Clone,
+    T: LinearRate<K = u128> + Clone,
 {
     type Node = N;
     type Capacity = u128;
@@ -246,8 +381,56 @@ where
         b: &N,
         capacity: u128,
         opt_exclude: Option<(&N, &N)>,
+        search_mode: SearchMode,
     ) -> Vec<CapacityMultiRoute<N, u128, T>> {
-        option_to_vec(self.get_multi_route(a, b, capacity, opt_exclude))
+        if let Some(multi_route) = self.get_multi_route(a, b, capacity, opt_exclude, search_mode) {
+            return vec![multi_route];
+        }
+        // No single route can carry the requested capacity. We try to split the capacity
+        // between a few routes:
+        option_to_vec(self.get_max_flow_multi_route(a, b, capacity, opt_exclude))
+    }
+
+    fn get_liquidity(&self, b: &N, max_hops: usize) -> CapacityLiquidity<N, u128> {
+        let neighbors = self
+            .neighbors_with_recv_capacity(b)
+            .into_iter()
+            .map(|a| {
+                let capacity = self.get_send_capacity(&a, b);
+                (a, capacity)
+            })
+            .collect();
+
+        let get_neighbors = |cur_node: &N| {
+            let cur_node = cur_node.clone();
+            self.neighbors_with_send_capacity(cur_node.clone(), 1)
+                .map(move |next_node| (next_node, self.get_send_capacity(&cur_node, next_node)))
+        };
+
+        // Find the nodes around `b`, layer after layer (by hop distance):
+        let mut visited = HashSet::new();
+        visited.insert(b.clone());
+        let mut layer = vec![b.clone()];
+        let mut hops = Vec::new();
+        for _ in 0..max_hops {
+            let mut next_layer = Vec::new();
+            for node in &layer {
+                for a in self.neighbors_with_recv_capacity(node) {
+                    if visited.insert(a.clone()) {
+                        next_layer.push(a);
+                    }
+                }
+            }
+            if next_layer.is_empty() {
+                break;
+            }
+            // Credits sent from nodes further away must pass through the inner layers:
+            let capacity = max_flow_value(&next_layer, b, &get_neighbors);
+            hops.push((next_layer.len(), capacity));
+            layer = next_layer;
+        }
+
+        CapacityLiquidity { neighbors, hops }
     }
 
     fn tick(&mut self, a: &N) {
@@ -255,6 +438,21 @@ where
             node_edges.tick();
         }
     }
+
+    fn export_edges(&self) -> Vec<(N, N, CapacityEdge<u128, T>, u128)> {
+        let mut edges = Vec::new();
+        for (a, node_edges) in &self.nodes {
+            for (b, edge) in &node_edges.edges {
+                edges.push((a.clone(), b.clone(), edge.capacity_edge.clone(), edge.age));
+            }
+        }
+        edges
+    }
+
+    fn import_edge(&mut self, a: N, b: N, capacity_edge: CapacityEdge<u128, T>, age: u128) {
+        let a_entry = self.nodes.entry(a).or_insert_with(NodeEdges::new);
+        a_entry.edges.insert(b, Edge { capacity_edge, age });
+    }
 }
 
 #[cfg(test)]
@@ -339,50 +537,249 @@ mod tests {
     fn test_get_multi_route() {
         let cg = example_capacity_graph();
 
-        let multi_route = cg.get_multi_route(&2, &5, 29, None).unwrap();
+        let multi_route = cg
+            .get_multi_route(&2, &5, 29, None, SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        let multi_route = cg.get_multi_route(&2, &5, 30, None).unwrap();
+        let multi_route = cg
+            .get_multi_route(&2, &5, 30, None, SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        assert!(cg.get_multi_route(&2, &5, 31, None).is_none());
+        assert!(cg
+            .get_multi_route(&2, &5, 31, None, SearchMode::Shortest)
+            .is_none());
 
-        let multi_route = cg.get_multi_route(&0, &5, 25, None).unwrap();
+        let multi_route = cg
+            .get_multi_route(&0, &5, 25, None, SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        let multi_route = cg.get_multi_route(&0, &5, 29, None).unwrap();
+        let multi_route = cg
+            .get_multi_route(&0, &5, 29, None, SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        let multi_route = cg.get_multi_route(&0, &5, 30, None).unwrap();
+        let multi_route = cg
+            .get_multi_route(&0, &5, 30, None, SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        assert!(cg.get_multi_route(&0, &5, 31, None).is_none());
+        assert!(cg
+            .get_multi_route(&0, &5, 31, None, SearchMode::Shortest)
+            .is_none());
 
         // Block an essential edge:
-        assert!(cg.get_multi_route(&0, &5, 25, Some((&3, &4))).is_none());
+        assert!(cg
+            .get_multi_route(&0, &5, 25, Some((&3, &4)), SearchMode::Shortest)
+            .is_none());
 
         // Block an essential edge but the at the reversed direction:
-        let multi_route = cg.get_multi_route(&0, &5, 25, Some((&4, &3))).unwrap();
+        let multi_route = cg
+            .get_multi_route(&0, &5, 25, Some((&4, &3)), SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
         // Block an edge not used for the route:
-        let multi_route = cg.get_multi_route(&0, &5, 25, Some((&1, &2))).unwrap();
+        let multi_route = cg
+            .get_multi_route(&0, &5, 25, Some((&1, &2)), SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
         // Use excluded edge to find a loop from 1 to 1:
-        let multi_route = cg.get_multi_route(&2, &1, 6, Some((&2, &1))).unwrap();
+        let multi_route = cg
+            .get_multi_route(&2, &1, 6, Some((&2, &1)), SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![2, 4, 3, 1]);
         assert_eq!(multi_route.routes[0].capacity, 6);
 
         // Request for too much capacity:
-        assert!(cg.get_multi_route(&2, &1, 7, Some((&2, &1))).is_none());
+        assert!(cg
+            .get_multi_route(&2, &1, 7, Some((&2, &1)), SearchMode::Shortest)
+            .is_none());
+    }
+
+    #[test]
+    fn test_get_multi_route_cheapest() {
+        /*
+         * Example graph (Rates in brackets):
+         *
+         *    0 --[10]--> 1 --[1]--> 4
+         *    |                      ^
+         *    |                      |
+         *    +--[1]--> 2 --[1]--> 3 --[1]--+
+         *
+         */
+
+        let mut cg = SimpleCapacityGraph::<u32, ConstRate>::new();
+        let mut add_edge = |a, b, rate| {
+            cg.update_edge(a, b, CapacityEdge::new((30, 30), ConstRate(rate)));
+            cg.update_edge(b, a, CapacityEdge::new((30, 30), ConstRate(rate)));
+        };
+
+        add_edge(0, 1, 10);
+        add_edge(1, 4, 1);
+        add_edge(0, 2, 1);
+        add_edge(2, 3, 1);
+        add_edge(3, 4, 1);
+
+        let multi_route = cg
+            .get_multi_route(&0, &4, 20, None, SearchMode::Shortest)
+            .unwrap();
+        assert_eq!(multi_route.routes[0].route, vec![0, 1, 4]);
+        assert_eq!(multi_route.routes[0].rate, ConstRate(10));
+
+        let multi_route = cg
+            .get_multi_route(&0, &4, 20, None, SearchMode::Cheapest)
+            .unwrap();
+        assert_eq!(multi_route.routes[0].route, vec![0, 2, 3, 4]);
+        assert_eq!(multi_route.routes[0].rate, ConstRate(2));
+        assert_eq!(multi_route.routes[0].capacity, 30);
+
+        // Block the cheap route:
+        let multi_route = cg
+            .get_multi_route(&0, &4, 20, Some((&2, &3)), SearchMode::Cheapest)
+            .unwrap();
+        assert_eq!(multi_route.routes[0].route, vec![0, 1, 4]);
+
+        // Request for too much capacity:
+        assert!(cg
+            .get_multi_route(&0, &4, 31, None, SearchMode::Cheapest)
+            .is_none());
+    }
+
+    #[test]
+    fn test_get_multi_routes_max_flow() {
+        /*
+         * Example graph (Send capacities in brackets):
+         *
+         *    0 --[10]--> 1 --[10]--> 3
+         *    |                       ^
+         *    |                       |
+         *    +--[10]--> 2 --[10]-----+
+         *
+         */
+
+        let mut cg = SimpleCapacityGraph::<u32, ConstRate>::new();
+        let mut add_edge = |a, b, rate| {
+            cg.update_edge(a, b, CapacityEdge::new((10, 10), ConstRate(rate)));
+            cg.update_edge(b, a, CapacityEdge::new((10, 10), ConstRate(rate)));
+        };
+
+        add_edge(0, 1, 1);
+        add_edge(1, 3, 1);
+        add_edge(0, 2, 2);
+        add_edge(2, 3, 2);
+
+        // A single route is enough:
+        let multi_routes = cg.get_multi_routes(&0, &3, 10, None, SearchMode::Shortest);
+        assert_eq!(multi_routes.len(), 1);
+        assert_eq!(multi_routes[0].routes.len(), 1);
+
+        // No single route is enough. The capacity is split between two routes:
+        let multi_routes = cg.get_multi_routes(&0, &3, 15, None, SearchMode::Shortest);
+        assert_eq!(multi_routes.len(), 1);
+        let mut routes = multi_routes[0].routes.clone();
+        routes.sort_by(|route1, route2| route1.route.cmp(&route2.route));
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].route, vec![0, 1, 3]);
+        assert_eq!(routes[0].rate, ConstRate(1));
+        assert_eq!(routes[1].route, vec![0, 2, 3]);
+        assert_eq!(routes[1].rate, ConstRate(2));
+        assert_eq!(routes[0].capacity + routes[1].capacity, 15);
+        // The fees (1 and 2) can be paid without exceeding the capacity of the edges:
+        assert!(routes[0].capacity <= 9);
+        assert!(routes[1].capacity <= 8);
+
+        // The maximum amount that can be sent, taking the fees into account:
+        let multi_routes = cg.get_multi_routes(&0, &3, 17, None, SearchMode::Cheapest);
+        assert_eq!(multi_routes.len(), 1);
+        let mut routes = multi_routes[0].routes.clone();
+        routes.sort_by(|route1, route2| route1.route.cmp(&route2.route));
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].capacity, 9);
+        assert_eq!(routes[1].capacity, 8);
+
+        // Request for too much capacity. The edges could carry 18 credits, but not the fees:
+        assert!(cg
+            .get_multi_routes(&0, &3, 18, None, SearchMode::Shortest)
+            .is_empty());
+
+        // Excluding an edge leaves only one route:
+        assert!(cg
+            .get_multi_routes(&0, &3, 15, Some((&2, &3)), SearchMode::Shortest)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_get_liquidity() {
+        let cg = example_capacity_graph();
+
+        let mut liquidity = cg.get_liquidity(&2, 4);
+        liquidity.neighbors.sort();
+        assert_eq!(liquidity.neighbors, vec![(1, 10), (4, 30), (5, 5)]);
+        // Nodes 0 and 3 can send credits to 2 only through 1 and 4:
+        assert_eq!(liquidity.hops, vec![(3, 45), (2, 40)]);
+
+        let liquidity = cg.get_liquidity(&5, 4);
+        assert_eq!(liquidity.neighbors, vec![(2, 30)]);
+        assert_eq!(liquidity.hops, vec![(1, 30), (2, 30), (2, 30)]);
+
+        let liquidity = cg.get_liquidity(&2, 1);
+        assert_eq!(liquidity.hops, vec![(3, 45)]);
+
+        // Unknown node:
+        let liquidity = cg.get_liquidity(&6, 4);
+        assert!(liquidity.neighbors.is_empty());
+        assert!(liquidity.hops.is_empty());
+    }
+
+    #[test]
+    fn test_export_import_edges() {
+        let cg = example_capacity_graph();
+        let edges = cg.export_edges();
+        assert_eq!(edges.len(), 12);
+
+        let mut cg2 = SimpleCapacityGraph::<u32, ConstRate>::new();
+        for (a, b, capacity_edge, age) in edges {
+            cg2.import_edge(a, b, capacity_edge, age);
+        }
+
+        let multi_route = cg2
+            .get_multi_route(&0, &5, 25, None, SearchMode::Shortest)
+            .unwrap();
+        assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
+        assert_eq!(multi_route.routes[0].capacity, 30);
+
+        // Ages are restored. Edges that were imported old expire earlier:
+        let mut cg3 = SimpleCapacityGraph::<u32, ConstRate>::new();
+        let max_edge_age = max_edge_age(1);
+        cg3.import_edge(
+            0,
+            1,
+            CapacityEdge::new((30, 30), ConstRate(1)),
+            max_edge_age - 2,
+        );
+        cg3.import_edge(1, 0, CapacityEdge::new((30, 30), ConstRate(1)), 0);
+        assert!(cg3
+            .get_multi_route(&0, &1, 30, None, SearchMode::Shortest)
+            .is_some());
+        cg3.tick(&0);
+        assert!(cg3
+            .get_multi_route(&0, &1, 30, None, SearchMode::Shortest)
+            .is_some());
+        cg3.tick(&0);
+        assert!(cg3
+            .get_multi_route(&0, &1, 30, None, SearchMode::Shortest)
+            .is_none());
     }
 
     #[test]
@@ -395,11 +792,15 @@ mod tests {
         cg.update_edge(2, 3, CapacityEdge::new((30, 10), ConstRate(1)));
         cg.update_edge(3, 2, CapacityEdge::new((10, 30), ConstRate(1)));
 
-        let multi_route = cg.get_multi_route(&0, &1, 30, None).unwrap();
+        let multi_route = cg
+            .get_multi_route(&0, &1, 30, None, SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        let multi_route = cg.get_multi_route(&2, &3, 30, None).unwrap();
+        let multi_route = cg
+            .get_multi_route(&2, &3, 30, None, SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![2, 3]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
@@ -407,20 +808,28 @@ mod tests {
         for _ in 0..max_edge_age - 1 {
             cg.tick(&0);
 
-            let multi_route = cg.get_multi_route(&0, &1, 30, None).unwrap();
+            let multi_route = cg
+                .get_multi_route(&0, &1, 30, None, SearchMode::Shortest)
+                .unwrap();
             assert_eq!(multi_route.routes[0].route, vec![0, 1]);
             assert_eq!(multi_route.routes[0].capacity, 30);
 
-            let multi_route = cg.get_multi_route(&2, &3, 30, None).unwrap();
+            let multi_route = cg
+                .get_multi_route(&2, &3, 30, None, SearchMode::Shortest)
+                .unwrap();
             assert_eq!(multi_route.routes[0].route, vec![2, 3]);
             assert_eq!(multi_route.routes[0].capacity, 30);
         }
 
         // At this point 0->1 and 1->0 should expire, but 2->3 and 3->2 don't expire:
         cg.tick(&0);
-        assert!(cg.get_multi_route(&0, &1, 30, None).is_none());
+        assert!(cg
+            .get_multi_route(&0, &1, 30, None, SearchMode::Shortest)
+            .is_none());
 
-        let multi_route = cg.get_multi_route(&2, &3, 30, None).unwrap();
+        let multi_route = cg
+            .get_multi_route(&2, &3, 30, None, SearchMode::Shortest)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![2, 3]);
         assert_eq!(multi_route.routes[0].capacity, 30);
     }
@@ -4,6 +4,7 @@
 #![feature(generators)]
 #![feature(never_type)]
 #![feature(map_get_key_value)]
+#![cfg_attr(test, feature(test))]
 #![deny(trivial_numeric_casts, warnings)]
 #![allow(intra_doc_link_resolution_failure)]
 #![allow(
@@ -16,13 +17,26 @@
 #[macro_use]
 extern crate log;
 
+#[cfg(test)]
+extern crate test;
+
 #[macro_use]
 extern crate common;
 
+#[macro_use]
+extern crate serde_derive;
+
 mod backoff_connector;
 mod graph;
 mod net_server;
+mod rate_limit;
 mod server;
+mod snapshot;
 mod verifier;
 
 pub use net_server::{net_index_server, NetIndexServerError};
+pub use rate_limit::{RateLimitConfig, RateLimitStats};
+pub use snapshot::{
+    load_snapshot_from_file, store_snapshot_to_file, EdgeSnapshot, IndexServerSnapshot,
+    NodeSnapshot, SnapshotFileError,
+};
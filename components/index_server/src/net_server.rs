@@ -34,6 +34,8 @@ pub use crate::server::{ClientConn, ServerConn};
 use crate::backoff_connector::BackoffConnector;
 use crate::graph::graph_service::create_graph_service;
 use crate::graph::simple_capacity_graph::SimpleCapacityGraph;
+use crate::rate_limit::RateLimitConfig;
+use crate::snapshot::{restore_snapshot, IndexServerSnapshot};
 use crate::verifier::simple_verifier::SimpleVerifier;
 
 #[derive(Debug)]
@@ -54,6 +56,10 @@ async fn index_server<A, IS, IC, SC, R, GS, S>(
     mut timer_client: TimerClient,
     ticks_to_live: usize,
     backoff_ticks: usize,
+    opt_snapshot: Option<IndexServerSnapshot>,
+    snapshot_ticks: usize,
+    opt_snapshot_sender: Option<mpsc::Sender<IndexServerSnapshot>>,
+    rate_limit_config: RateLimitConfig,
     rng: R,
     graph_service_spawner: GS,
     spawner: S,
@@ -67,9 +73,14 @@ where
     S: Spawn + Clone + Send,
     GS: Spawn + Send + 'static,
 {
-    let verifier = SimpleVerifier::new(ticks_to_live, rng);
+    let mut verifier = SimpleVerifier::new(ticks_to_live, rng);
+    let mut capacity_graph = SimpleCapacityGraph::new();
+
+    // Restore saved state, so that we can serve routes right away:
+    if let Some(snapshot) = opt_snapshot {
+        restore_snapshot(snapshot, &mut verifier, &mut capacity_graph);
+    }
 
-    let capacity_graph = SimpleCapacityGraph::new();
     let graph_client = create_graph_service(capacity_graph, graph_service_spawner, spawner.clone())
         .map_err(|_| IndexServerError::CreateGraphServiceError)?;
 
@@ -88,6 +99,9 @@ where
         compare_public_key,
         verifier,
         timer_stream,
+        snapshot_ticks,
+        opt_snapshot_sender,
+        rate_limit_config,
         spawner,
         None
     ))
@@ -306,6 +320,10 @@ pub async fn net_index_server<A, ICC, ISC, SC, R, GS, S>(
     trusted_servers: HashMap<PublicKey, A>,
     max_concurrent_encrypt: usize,
     backoff_ticks: usize,
+    opt_snapshot: Option<IndexServerSnapshot>,
+    snapshot_ticks: usize,
+    opt_snapshot_sender: Option<mpsc::Sender<IndexServerSnapshot>>,
+    rate_limit_config: RateLimitConfig,
     graph_service_spawner: GS,
     mut spawner: S,
 ) -> Result<(), NetIndexServerError>
@@ -404,6 +422,10 @@ where
         timer_client,
         INDEX_NODE_TIMEOUT_TICKS,
         backoff_ticks,
+        opt_snapshot,
+        snapshot_ticks,
+        opt_snapshot_sender,
+        rate_limit_config,
         rng,
         graph_service_spawner,
         spawner.clone()
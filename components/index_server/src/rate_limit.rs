@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::{cmp, hash};
+
+/// Limits on the amount of messages a client may send to the index server.
+/// Messages are counted in windows of `window_ticks` timer ticks.
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Amount of timer ticks in a counting window
+    pub window_ticks: usize,
+    /// Maximum amount of mutations updates a client may send during one window
+    pub max_mutations_updates: usize,
+    /// Maximum amount of routes requests a client may send during one window
+    pub max_request_routes: usize,
+    /// Amount of timer ticks a client that exceeded a limit is not allowed to reconnect
+    pub backoff_ticks: usize,
+}
+
+/// Kinds of messages sent from a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientMessageKind {
+    MutationsUpdate,
+    RequestRoutes,
+}
+
+/// Counters kept since the index server was started.
+/// Useful for operators to monitor abusive clients.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimitStats {
+    /// Amount of mutations updates accepted
+    pub mutations_updates: u64,
+    /// Amount of routes requests accepted
+    pub request_routes: u64,
+    /// Amount of messages dropped because a client exceeded a limit
+    pub dropped_messages: u64,
+    /// Amount of clients that were disconnected because they exceeded a limit
+    pub disconnected_clients: u64,
+    /// Amount of connection attempts refused, because the client is backing off
+    pub refused_connections: u64,
+}
+
+#[derive(Debug, Default)]
+struct ClientCounters {
+    mutations_updates: usize,
+    request_routes: usize,
+}
+
+/// Counts messages sent from every client during the current window.
+/// There is at most one connection for every client public key, so the limits apply both per
+/// public key and per connection.
+pub struct RateLimiter<K> {
+    config: RateLimitConfig,
+    ticks_in_window: usize,
+    counters: HashMap<K, ClientCounters>,
+    /// Clients that exceeded a limit, together with the amount of ticks left until they are
+    /// allowed to reconnect.
+    backoffs: HashMap<K, usize>,
+    stats: RateLimitStats,
+}
+
+impl<K> RateLimiter<K>
+where
+    K: cmp::Eq + hash::Hash + Clone,
+{
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            config,
+            ticks_in_window: 0,
+            counters: HashMap::new(),
+            backoffs: HashMap::new(),
+            stats: RateLimitStats::default(),
+        }
+    }
+
+    /// Check if a client is allowed to connect
+    pub fn allow_connection(&mut self, client: &K) -> bool {
+        if self.backoffs.contains_key(client) {
+            self.stats.refused_connections = self.stats.refused_connections.saturating_add(1);
+            return false;
+        }
+        true
+    }
+
+    /// Count a message sent from a client.
+    /// Returns false if the client exceeded its limit (or is already backing off). In that case
+    /// the message should be dropped and the client should be disconnected.
+    pub fn count_message(&mut self, client: &K, message_kind: ClientMessageKind) -> bool {
+        if self.backoffs.contains_key(client) {
+            self.stats.dropped_messages = self.stats.dropped_messages.saturating_add(1);
+            return false;
+        }
+
+        let counters = self
+            .counters
+            .entry(client.clone())
+            .or_insert_with(ClientCounters::default);
+        let (counter, max_count, total) = match message_kind {
+            ClientMessageKind::MutationsUpdate => (
+                &mut counters.mutations_updates,
+                self.config.max_mutations_updates,
+                &mut self.stats.mutations_updates,
+            ),
+            ClientMessageKind::RequestRoutes => (
+                &mut counters.request_routes,
+                self.config.max_request_routes,
+                &mut self.stats.request_routes,
+            ),
+        };
+
+        if *counter >= max_count {
+            // Limit exceeded. The client will have to back off:
+            self.counters.remove(client);
+            self.backoffs
+                .insert(client.clone(), self.config.backoff_ticks);
+            self.stats.dropped_messages = self.stats.dropped_messages.saturating_add(1);
+            self.stats.disconnected_clients = self.stats.disconnected_clients.saturating_add(1);
+            return false;
+        }
+
+        *counter += 1;
+        *total = total.saturating_add(1);
+        true
+    }
+
+    /// Simulate advancement of time.
+    /// Returns true if a counting window has ended.
+    pub fn tick(&mut self) -> bool {
+        self.backoffs.retain(|_client, ticks_left| {
+            *ticks_left = ticks_left.saturating_sub(1);
+            *ticks_left > 0
+        });
+
+        self.ticks_in_window = self.ticks_in_window.saturating_add(1);
+        if self.ticks_in_window < self.config.window_ticks {
+            return false;
+        }
+        self.ticks_in_window = 0;
+        self.counters.clear();
+        true
+    }
+
+    pub fn stats(&self) -> &RateLimitStats {
+        &self.stats
+    }
+}
+
+/// Counts the requests that are currently being served, for every client and in total.
+/// Used to bound the amount of work the index server does concurrently.
+pub struct PendingRequests<K> {
+    max_per_client: usize,
+    max_total: usize,
+    counters: HashMap<K, usize>,
+    total: usize,
+}
+
+impl<K> PendingRequests<K>
+where
+    K: cmp::Eq + hash::Hash + Clone,
+{
+    pub fn new(max_per_client: usize, max_total: usize) -> Self {
+        PendingRequests {
+            max_per_client,
+            max_total,
+            counters: HashMap::new(),
+            total: 0,
+        }
+    }
+
+    /// Try to count a new pending request for a client.
+    /// Returns false if a limit was reached.
+    pub fn try_add(&mut self, client: &K) -> bool {
+        if self.total >= self.max_total {
+            return false;
+        }
+        let counter = self.counters.entry(client.clone()).or_insert(0);
+        if *counter >= self.max_per_client {
+            return false;
+        }
+        *counter += 1;
+        self.total += 1;
+        true
+    }
+
+    /// Mark a pending request of a client as done
+    pub fn remove(&mut self, client: &K) {
+        let is_empty = match self.counters.get_mut(client) {
+            Some(counter) => {
+                *counter = counter.saturating_sub(1);
+                self.total = self.total.saturating_sub(1);
+                *counter == 0
+            }
+            None => return,
+        };
+        if is_empty {
+            self.counters.remove(client);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> RateLimitConfig {
+        RateLimitConfig {
+            window_ticks: 4,
+            max_mutations_updates: 2,
+            max_request_routes: 3,
+            backoff_ticks: 6,
+        }
+    }
+
+    #[test]
+    fn test_rate_limiter_window() {
+        let mut rate_limiter = RateLimiter::<u32>::new(test_config());
+
+        for _ in 0..2 {
+            assert!(rate_limiter.count_message(&0, ClientMessageKind::MutationsUpdate));
+        }
+        for _ in 0..3 {
+            assert!(rate_limiter.count_message(&0, ClientMessageKind::RequestRoutes));
+        }
+        // Counters are kept separately for every client:
+        assert!(rate_limiter.count_message(&1, ClientMessageKind::MutationsUpdate));
+
+        // A new window starts, and counting starts again:
+        for _ in 0..3 {
+            assert!(!rate_limiter.tick());
+        }
+        assert!(rate_limiter.tick());
+
+        for _ in 0..2 {
+            assert!(rate_limiter.count_message(&0, ClientMessageKind::MutationsUpdate));
+        }
+
+        let stats = rate_limiter.stats();
+        assert_eq!(stats.mutations_updates, 5);
+        assert_eq!(stats.request_routes, 3);
+        assert_eq!(stats.dropped_messages, 0);
+        assert_eq!(stats.disconnected_clients, 0);
+    }
+
+    #[test]
+    fn test_rate_limiter_backoff() {
+        let mut rate_limiter = RateLimiter::<u32>::new(test_config());
+
+        for _ in 0..2 {
+            assert!(rate_limiter.count_message(&0, ClientMessageKind::MutationsUpdate));
+        }
+        // Limit exceeded:
+        assert!(!rate_limiter.count_message(&0, ClientMessageKind::MutationsUpdate));
+        // Messages of the client are dropped while it backs off:
+        assert!(!rate_limiter.count_message(&0, ClientMessageKind::RequestRoutes));
+        assert!(!rate_limiter.allow_connection(&0));
+        // Other clients are not affected:
+        assert!(rate_limiter.count_message(&1, ClientMessageKind::RequestRoutes));
+        assert!(rate_limiter.allow_connection(&1));
+
+        for _ in 0..5 {
+            rate_limiter.tick();
+            assert!(!rate_limiter.allow_connection(&0));
+        }
+        rate_limiter.tick();
+        assert!(rate_limiter.allow_connection(&0));
+        assert!(rate_limiter.count_message(&0, ClientMessageKind::MutationsUpdate));
+
+        let stats = rate_limiter.stats();
+        assert_eq!(stats.mutations_updates, 3);
+        assert_eq!(stats.request_routes, 1);
+        assert_eq!(stats.dropped_messages, 2);
+        assert_eq!(stats.disconnected_clients, 1);
+        assert_eq!(stats.refused_connections, 6);
+    }
+
+    #[test]
+    fn test_pending_requests() {
+        let mut pending_requests = PendingRequests::<u32>::new(2, 3);
+
+        assert!(pending_requests.try_add(&0));
+        assert!(pending_requests.try_add(&0));
+        // Client limit reached:
+        assert!(!pending_requests.try_add(&0));
+        assert!(pending_requests.try_add(&1));
+        // Total limit reached:
+        assert!(!pending_requests.try_add(&2));
+
+        pending_requests.remove(&1);
+        assert!(pending_requests.try_add(&2));
+        assert!(!pending_requests.try_add(&1));
+
+        pending_requests.remove(&0);
+        assert!(pending_requests.try_add(&0));
+        // Removing a request of a client without pending requests has no effect:
+        pending_requests.remove(&3);
+        assert!(!pending_requests.try_add(&3));
+    }
+}
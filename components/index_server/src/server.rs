@@ -7,24 +7,38 @@ use futures::task::{Spawn, SpawnExt};
 use futures::{future, select, stream, FutureExt, SinkExt, Stream, StreamExt, TryFutureExt};
 
 use common::conn::{ConnPair, FutTransform};
+use common::int_convert::{usize_to_u32, usize_to_u64};
 use common::select_streams::{select_streams, BoxStream};
 
 use crypto::identity::PublicKey;
 use crypto::uid::Uid;
 
 use proto::index_server::messages::{
-    ForwardMutationsUpdate, IndexClientToServer, IndexMutation, IndexServerToClient,
-    IndexServerToServer, MultiRoute, MutationsUpdate, ResponseRoutes, RouteCapacityRate,
-    TimeProofLink,
+    ForwardMutationsUpdate, HopsLiquidity, IndexClientToServer, IndexMutation, IndexServerToClient,
+    IndexServerToServer, Liquidity, MultiRoute, NeighborLiquidity, RequestLiquidity, RequestRoutes,
+    ResponseLiquidity, ResponseRoutes, RouteCapacityRate, RouteSearchMode, TimeProofLink,
 };
 
 use proto::funder::messages::{FriendsRoute, Rate};
 
-use crate::graph::capacity_graph::{CapacityEdge, LinearRate};
+use crate::graph::capacity_graph::{CapacityEdge, LinearRate, SearchMode};
 use crate::graph::graph_service::{GraphClient, GraphClientError};
+use crate::rate_limit::{ClientMessageKind, PendingRequests, RateLimitConfig, RateLimiter};
+use crate::snapshot::{EdgeSnapshot, IndexServerSnapshot, NodeSnapshot};
 
 use crate::verifier::Verifier;
 
+/// Maximum hop distance from the destination considered when calculating liquidity.
+const MAX_LIQUIDITY_HOPS: usize = 4;
+
+/// Maximum amount of routes and liquidity requests of a single client that may be served
+/// concurrently. Further requests of the client are dropped.
+const MAX_PENDING_REQUESTS_PER_CLIENT: usize = 4;
+
+/// Maximum amount of routes and liquidity requests of all clients that may be served
+/// concurrently.
+const MAX_PENDING_REQUESTS: usize = 0x100;
+
 pub type ServerConn = ConnPair<IndexServerToServer, IndexServerToServer>;
 pub type ClientConn = ConnPair<IndexServerToClient, IndexClientToServer>;
 
@@ -89,6 +103,19 @@ impl<T> Connected<T> {
         }
         Ok(())
     }
+
+    /// Get a copy of the sender to the remote entity (If no failure occurred)
+    pub fn clone_sender(&self) -> Option<mpsc::Sender<T>> {
+        self.opt_sender.clone()
+    }
+}
+
+/// A connected client
+#[derive(Debug)]
+struct ConnectedClient {
+    connected: Connected<IndexServerToClient>,
+    /// Dropping this sender closes the connection to the client
+    opt_close_sender: Option<oneshot::Sender<()>>,
 }
 
 #[derive(Debug)]
@@ -117,8 +144,15 @@ struct IndexServer<A, S, SC, V, CMP> {
     verifier: V,
     compare_public_key: CMP,
     remote_servers: HashMap<PublicKey, RemoteServer<A>>,
-    clients: HashMap<PublicKey, Connected<IndexServerToClient>>,
+    clients: HashMap<PublicKey, ConnectedClient>,
+    rate_limiter: RateLimiter<PublicKey>,
+    pending_requests: PendingRequests<PublicKey>,
     event_sender: mpsc::Sender<IndexServerEvent>,
+    /// Amount of timer ticks between two snapshots
+    snapshot_ticks: usize,
+    /// Amount of timer ticks since the last snapshot
+    ticks_since_snapshot: usize,
+    opt_snapshot_sender: Option<mpsc::Sender<IndexServerSnapshot>>,
     spawner: S,
 }
 
@@ -134,8 +168,10 @@ enum IndexServerEvent {
     ServerConnection((PublicKey, ServerConn)),
     FromServer((PublicKey, Option<IndexServerToServer>)),
     ClientConnection((PublicKey, ClientConn)),
+    FromClient((PublicKey, IndexClientToServer)),
     ClientClosed(PublicKey),
-    ClientMutationsUpdate(MutationsUpdate),
+    /// A routes or liquidity request of a client was served
+    RequestDone(PublicKey),
     TimerTick,
     ClientListenerClosed,
     ServerListenerClosed,
@@ -171,6 +207,9 @@ where
         compare_public_key: CMP,
         verifier: V,
         event_sender: mpsc::Sender<IndexServerEvent>,
+        snapshot_ticks: usize,
+        opt_snapshot_sender: Option<mpsc::Sender<IndexServerSnapshot>>,
+        rate_limit_config: RateLimitConfig,
         spawner: S,
     ) -> Result<Self, ServerLoopError> {
         let mut index_server = IndexServer {
@@ -181,7 +220,15 @@ where
             compare_public_key,
             remote_servers: HashMap::new(),
             clients: HashMap::new(),
+            rate_limiter: RateLimiter::new(rate_limit_config),
+            pending_requests: PendingRequests::new(
+                MAX_PENDING_REQUESTS_PER_CLIENT,
+                MAX_PENDING_REQUESTS,
+            ),
             event_sender,
+            snapshot_ticks,
+            ticks_since_snapshot: 0,
+            opt_snapshot_sender,
             spawner,
         };
 
@@ -375,6 +422,143 @@ where
         Ok(())
     }
 
+    /// Close the connection to a client
+    fn disconnect_client(&mut self, public_key: &PublicKey) {
+        if let Some(client) = self.clients.get_mut(public_key) {
+            client.opt_close_sender.take();
+        }
+    }
+
+    pub async fn handle_from_client(
+        &mut self,
+        public_key: PublicKey,
+        client_msg: IndexClientToServer,
+    ) -> Result<(), ServerLoopError> {
+        let message_kind = match &client_msg {
+            IndexClientToServer::MutationsUpdate(_) => ClientMessageKind::MutationsUpdate,
+            // Calculating liquidity is about as expensive as finding routes:
+            IndexClientToServer::RequestRoutes(_) | IndexClientToServer::RequestLiquidity(_) => {
+                ClientMessageKind::RequestRoutes
+            }
+        };
+        if !self.rate_limiter.count_message(&public_key, message_kind) {
+            warn!(
+                "{}: Client {:?} exceeded the {:?} limit. Disconnecting.",
+                self.local_public_key[0], public_key, message_kind
+            );
+            self.disconnect_client(&public_key);
+            return Ok(());
+        }
+
+        match client_msg {
+            IndexClientToServer::MutationsUpdate(mutations_update) => {
+                let forward_mutations_update = ForwardMutationsUpdate {
+                    mutations_update,
+                    time_proof_chain: Vec::new(),
+                };
+                await!(self.handle_forward_mutations_update(None, forward_mutations_update))
+            }
+            IndexClientToServer::RequestRoutes(request_routes) => {
+                self.spawn_request_routes(&public_key, request_routes)
+            }
+            IndexClientToServer::RequestLiquidity(request_liquidity) => {
+                self.spawn_request_liquidity(&public_key, request_liquidity)
+            }
+        }
+    }
+
+    fn clone_client_sender(
+        &self,
+        public_key: &PublicKey,
+    ) -> Option<mpsc::Sender<IndexServerToClient>> {
+        self.clients
+            .get(public_key)
+            .and_then(|client| client.connected.clone_sender())
+    }
+
+    /// Calculate routes for a client in a separate task, and send the response to the client.
+    fn spawn_request_routes(
+        &mut self,
+        public_key: &PublicKey,
+        request_routes: RequestRoutes,
+    ) -> Result<(), ServerLoopError> {
+        let mut sender = match self.clone_client_sender(public_key) {
+            Some(sender) => sender,
+            None => return Ok(()),
+        };
+
+        if !self.pending_requests.try_add(public_key) {
+            warn!(
+                "{}: Client {:?} has too many pending requests. Dropping request.",
+                self.local_public_key[0], public_key
+            );
+            return Ok(());
+        }
+
+        let mut c_event_sender = self.event_sender.clone();
+        let c_public_key = public_key.clone();
+        let mut c_graph_client = self.graph_client.clone();
+        let request_routes_fut = async move {
+            let response_routes = await!(get_response_routes(&mut c_graph_client, request_routes))?;
+            let message = IndexServerToClient::ResponseRoutes(response_routes);
+            await!(sender.send(message)).map_err(|_| ServerLoopError::ClientSenderError)
+        }
+            .map_err(|e| error!("spawn_request_routes() error: {:?}", e))
+            .then(|_| {
+                async move {
+                    let _ =
+                        await!(c_event_sender.send(IndexServerEvent::RequestDone(c_public_key)));
+                }
+            });
+
+        self.spawner
+            .spawn(request_routes_fut)
+            .map_err(|_| ServerLoopError::SpawnError)
+    }
+
+    /// Calculate liquidity for a client in a separate task, and send the response to the client.
+    fn spawn_request_liquidity(
+        &mut self,
+        public_key: &PublicKey,
+        request_liquidity: RequestLiquidity,
+    ) -> Result<(), ServerLoopError> {
+        let mut sender = match self.clone_client_sender(public_key) {
+            Some(sender) => sender,
+            None => return Ok(()),
+        };
+
+        if !self.pending_requests.try_add(public_key) {
+            warn!(
+                "{}: Client {:?} has too many pending requests. Dropping request.",
+                self.local_public_key[0], public_key
+            );
+            return Ok(());
+        }
+
+        let mut c_event_sender = self.event_sender.clone();
+        let c_public_key = public_key.clone();
+        let mut c_graph_client = self.graph_client.clone();
+        let request_liquidity_fut = async move {
+            let response_liquidity = await!(get_response_liquidity(
+                &mut c_graph_client,
+                request_liquidity
+            ))?;
+            let message = IndexServerToClient::ResponseLiquidity(response_liquidity);
+            await!(sender.send(message)).map_err(|_| ServerLoopError::ClientSenderError)
+        }
+            .map_err(|e| error!("spawn_request_liquidity() error: {:?}", e))
+            .then(|_| {
+                async move {
+                    let _ =
+                        await!(c_event_sender.send(IndexServerEvent::RequestDone(c_public_key)));
+                }
+            });
+
+        self.spawner
+            .spawn(request_liquidity_fut)
+            .map_err(|_| ServerLoopError::SpawnError)
+    }
+
     pub async fn handle_timer_tick(&mut self) -> Result<(), ServerLoopError> {
         let (time_hash, removed_nodes) = self.verifier.tick();
 
@@ -384,8 +568,18 @@ where
         }
 
         // Try to send time tick to all connected clients:
-        for connected_client in self.clients.values_mut() {
-            let _ = connected_client.try_send(IndexServerToClient::TimeHash(time_hash.clone()));
+        for client in self.clients.values_mut() {
+            let _ = client
+                .connected
+                .try_send(IndexServerToClient::TimeHash(time_hash.clone()));
+        }
+
+        if self.rate_limiter.tick() {
+            info!(
+                "{}: Rate limit stats: {:?}",
+                self.local_public_key[0],
+                self.rate_limiter.stats()
+            );
         }
 
         // Update the graph service about removed nodes:
@@ -393,57 +587,158 @@ where
             await!(self.graph_client.remove_node(node_public_key))?;
         }
 
+        self.ticks_since_snapshot = self.ticks_since_snapshot.saturating_add(1);
+        if self.ticks_since_snapshot >= self.snapshot_ticks {
+            self.ticks_since_snapshot = 0;
+            await!(self.send_snapshot())?;
+        }
+
         Ok(())
     }
-}
 
-async fn client_handler(
-    mut graph_client: GraphClient<PublicKey, u128, Rate>,
-    _public_key: PublicKey, // TODO: unused?
-    client_conn: ClientConn,
-    mut event_sender: mpsc::Sender<IndexServerEvent>,
-) -> Result<(), ServerLoopError> {
-    let (mut sender, mut receiver) = client_conn;
+    /// Create a snapshot of the current state of the index server
+    pub async fn create_snapshot(&mut self) -> Result<IndexServerSnapshot, ServerLoopError> {
+        let nodes = self
+            .verifier
+            .export_nodes()
+            .into_iter()
+            .map(
+                |(public_key, session_id, counter, ticks_to_live)| NodeSnapshot {
+                    public_key,
+                    session_id,
+                    counter,
+                    ticks_to_live,
+                },
+            )
+            .collect();
+
+        let edges = await!(self.graph_client.export_edges())?
+            .into_iter()
+            .map(
+                |(from_public_key, to_public_key, capacity_edge, age)| EdgeSnapshot {
+                    from_public_key,
+                    to_public_key,
+                    send_capacity: capacity_edge.capacity.0,
+                    recv_capacity: capacity_edge.capacity.1,
+                    rate: capacity_edge.rate,
+                    age,
+                },
+            )
+            .collect();
 
-    while let Some(client_msg) = await!(receiver.next()) {
-        match client_msg {
-            IndexClientToServer::MutationsUpdate(mutations_update) => {
-                // Forward to main server future to process:
-                await!(event_sender.send(IndexServerEvent::ClientMutationsUpdate(mutations_update)))
-                    .map_err(|_| ServerLoopError::ClientEventSenderError)?;
-            }
-            IndexClientToServer::RequestRoutes(request_routes) => {
-                let graph_multi_routes = await!(graph_client.get_multi_routes(
-                    request_routes.source.clone(),
-                    request_routes.destination.clone(),
-                    request_routes.capacity,
-                    request_routes.opt_exclude.clone()
-                ))?;
-                let multi_routes = graph_multi_routes
-                    .into_iter()
-                    .map(|graph_multi_route| MultiRoute {
-                        routes: graph_multi_route
-                            .routes
-                            .into_iter()
-                            .map(|graph_route| RouteCapacityRate {
-                                route: FriendsRoute {
-                                    public_keys: graph_route.route,
-                                },
-                                capacity: graph_route.capacity,
-                                rate: graph_route.rate,
-                            })
-                            .collect(),
-                    })
-                    .collect::<Vec<_>>();
+        Ok(IndexServerSnapshot { nodes, edges })
+    }
 
-                let response_routes = ResponseRoutes {
-                    request_id: request_routes.request_id,
-                    multi_routes,
-                };
-                let message = IndexServerToClient::ResponseRoutes(response_routes);
-                await!(sender.send(message)).map_err(|_| ServerLoopError::ClientSenderError)?;
+    /// Send a snapshot of the current state to be saved
+    async fn send_snapshot(&mut self) -> Result<(), ServerLoopError> {
+        if self.opt_snapshot_sender.is_none() {
+            return Ok(());
+        }
+        let snapshot = await!(self.create_snapshot())?;
+        if let Some(snapshot_sender) = &mut self.opt_snapshot_sender {
+            // We don't wait for the previous snapshot to be saved:
+            if let Err(e) = snapshot_sender.try_send(snapshot) {
+                warn!("send_snapshot(): Failed to send snapshot: {:?}", e);
             }
         }
+        Ok(())
+    }
+}
+
+/// Calculate routes according to a client's request
+async fn get_response_routes(
+    graph_client: &mut GraphClient<PublicKey, u128, Rate>,
+    request_routes: RequestRoutes,
+) -> Result<ResponseRoutes, ServerLoopError> {
+    let search_mode = match request_routes.search_mode {
+        RouteSearchMode::Shortest => SearchMode::Shortest,
+        RouteSearchMode::Cheapest => SearchMode::Cheapest,
+    };
+    let graph_multi_routes = await!(graph_client.get_multi_routes(
+        request_routes.source.clone(),
+        request_routes.destination.clone(),
+        request_routes.capacity,
+        request_routes.opt_exclude.clone(),
+        search_mode
+    ))?;
+    let multi_routes = graph_multi_routes
+        .into_iter()
+        .map(|graph_multi_route| MultiRoute {
+            routes: graph_multi_route
+                .routes
+                .into_iter()
+                .map(|graph_route| RouteCapacityRate {
+                    route: FriendsRoute {
+                        public_keys: graph_route.route,
+                    },
+                    capacity: graph_route.capacity,
+                    rate: graph_route.rate,
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(ResponseRoutes {
+        request_id: request_routes.request_id,
+        multi_routes,
+    })
+}
+
+/// Calculate liquidity according to a client's request
+async fn get_response_liquidity(
+    graph_client: &mut GraphClient<PublicKey, u128, Rate>,
+    request_liquidity: RequestLiquidity,
+) -> Result<ResponseLiquidity, ServerLoopError> {
+    let graph_liquidity = await!(
+        graph_client.get_liquidity(request_liquidity.destination.clone(), MAX_LIQUIDITY_HOPS)
+    )?;
+
+    let neighbors = graph_liquidity
+        .neighbors
+        .into_iter()
+        .map(|(public_key, recv_capacity)| NeighborLiquidity {
+            public_key,
+            recv_capacity,
+        })
+        .collect();
+
+    let hops = graph_liquidity
+        .hops
+        .into_iter()
+        .enumerate()
+        .map(|(index, (num_nodes, recv_capacity))| HopsLiquidity {
+            // Distances start from 1:
+            hops: usize_to_u32(index + 1).unwrap(),
+            num_nodes: usize_to_u64(num_nodes).unwrap(),
+            recv_capacity,
+        })
+        .collect();
+
+    Ok(ResponseLiquidity {
+        request_id: request_liquidity.request_id,
+        liquidity: Liquidity { neighbors, hops },
+    })
+}
+
+/// Forward messages from a client to the main server loop, until the client closes the
+/// connection, or until the server decides to close the connection.
+async fn client_handler(
+    public_key: PublicKey,
+    receiver: mpsc::Receiver<IndexClientToServer>,
+    close_receiver: oneshot::Receiver<()>,
+    mut event_sender: mpsc::Sender<IndexServerEvent>,
+) -> Result<(), ServerLoopError> {
+    let receiver = receiver.map(Some).chain(stream::once(future::ready(None)));
+    let close_receiver = stream::once(close_receiver).map(|_| None);
+    let mut incoming = select_streams![receiver, close_receiver];
+
+    while let Some(Some(client_msg)) = await!(incoming.next()) {
+        // Forward to main server future to process:
+        await!(event_sender.send(IndexServerEvent::FromClient((
+            public_key.clone(),
+            client_msg
+        ))))
+        .map_err(|_| ServerLoopError::ClientEventSenderError)?;
     }
     Ok(())
 }
@@ -458,6 +753,9 @@ pub async fn server_loop<A, IS, IC, SC, CMP, V, TS, S>(
     compare_public_key: CMP,
     verifier: V,
     timer_stream: TS,
+    snapshot_ticks: usize,
+    opt_snapshot_sender: Option<mpsc::Sender<IndexServerSnapshot>>,
+    rate_limit_config: RateLimitConfig,
     spawner: S,
     mut opt_debug_event_sender: Option<mpsc::Sender<()>>,
 ) -> Result<(), ServerLoopError>
@@ -486,6 +784,9 @@ where
         compare_public_key,
         verifier,
         event_sender,
+        snapshot_ticks,
+        opt_snapshot_sender,
+        rate_limit_config,
         spawner,
     )?;
 
@@ -593,17 +894,23 @@ where
                     error!("Client {:?} already connected! Aborting.", public_key);
                     continue;
                 }
+                if !index_server.rate_limiter.allow_connection(&public_key) {
+                    warn!(
+                        "Client {:?} is backing off. Refusing connection.",
+                        public_key
+                    );
+                    continue;
+                }
 
-                // Duplicate the client sender:
-                let (ref sender, _) = client_conn;
-                let c_sender = sender.clone();
+                let (sender, receiver) = client_conn;
+                let (close_sender, close_receiver) = oneshot::channel();
 
                 let mut c_event_sender = index_server.event_sender.clone();
                 let c_public_key = public_key.clone();
                 let client_handler_fut = client_handler(
-                    index_server.graph_client.clone(),
                     public_key.clone(),
-                    client_conn,
+                    receiver,
+                    close_receiver,
                     index_server.event_sender.clone(),
                 )
                 .map_err(|e| error!("client_handler() error: {:?}", e))
@@ -619,18 +926,23 @@ where
                     .spawner
                     .spawn(client_handler_fut)
                     .map_err(|_| ServerLoopError::SpawnError)?;
-                index_server
-                    .clients
-                    .insert(public_key, Connected::new(c_sender));
+                let client = ConnectedClient {
+                    connected: Connected::new(sender),
+                    opt_close_sender: Some(close_sender),
+                };
+                index_server.clients.insert(public_key, client);
             }
-            IndexServerEvent::ClientMutationsUpdate(mutations_update) => {
-                let forward_mutations_update = ForwardMutationsUpdate {
-                    mutations_update,
-                    time_proof_chain: Vec::new(),
+            IndexServerEvent::FromClient((public_key, client_msg)) => {
+                let is_mutations_update = match &client_msg {
+                    IndexClientToServer::MutationsUpdate(_) => true,
+                    _ => false,
                 };
-                await!(
-                    index_server.handle_forward_mutations_update(None, forward_mutations_update)
-                )?;
+                await!(index_server.handle_from_client(public_key, client_msg))?;
+                // Routes and liquidity requests are served by separate tasks, and do not change
+                // the graph. We don't report them as debug events:
+                if !is_mutations_update {
+                    continue;
+                }
             }
             IndexServerEvent::ClientClosed(public_key) => {
                 // Client connection closed
@@ -638,6 +950,11 @@ where
                     error!("A non existent client {:?} was closed.", public_key);
                 }
             }
+            IndexServerEvent::RequestDone(public_key) => {
+                index_server.pending_requests.remove(&public_key);
+                // Not reported as a debug event, like the routes and liquidity requests:
+                continue;
+            }
             IndexServerEvent::TimerTick => await!(index_server.handle_timer_tick())?,
             IndexServerEvent::ClientListenerClosed => {
                 warn!("server_loop() client listener closed!");
@@ -674,8 +991,9 @@ mod tests {
 
     use common::dummy_connector::{ConnRequest, DummyConnector};
     use identity::{create_identity, IdentityClient};
-    use proto::index_server::messages::RequestRoutes;
+    use proto::index_server::messages::MutationsUpdate;
 
+    use crate::graph::capacity_graph::CapacityLiquidity;
     use crate::graph::graph_service::GraphRequest;
     use crate::verifier::simple_verifier::SimpleVerifier;
 
@@ -684,6 +1002,19 @@ mod tests {
     /// forwarding or when sending time hash ticks.
     const CHANNEL_SIZE: usize = 16;
 
+    /// Amount of timer ticks between snapshots. Snapshots are not saved during tests.
+    const TEST_SNAPSHOT_TICKS: usize = 16;
+
+    /// Limits that are never exceeded during tests
+    fn test_rate_limit_config() -> RateLimitConfig {
+        RateLimitConfig {
+            window_ticks: 16,
+            max_mutations_updates: 0x100,
+            max_request_routes: 0x100,
+            backoff_ticks: 16,
+        }
+    }
+
     fn create_identity_client<S>(mut spawner: S, seed: &[u8]) -> IdentityClient
     where
         S: Spawn,
@@ -736,6 +1067,9 @@ mod tests {
             compare_public_key,
             verifier,
             timer_stream,
+            TEST_SNAPSHOT_TICKS,
+            None,
+            test_rate_limit_config(),
             spawner.clone(),
             None,
         )
@@ -761,16 +1095,25 @@ mod tests {
             source: PublicKey::from(&[8; PUBLIC_KEY_LEN]),
             destination: PublicKey::from(&[9; PUBLIC_KEY_LEN]),
             opt_exclude: None,
+            search_mode: RouteSearchMode::Shortest,
         };
         await!(client_sender.send(IndexClientToServer::RequestRoutes(request_routes))).unwrap();
 
         // Handle the graph request:
         match await!(graph_requests_receiver.next()).unwrap() {
-            GraphRequest::GetMultiRoutes(src, dest, capacity, opt_exclude, response_sender) => {
+            GraphRequest::GetMultiRoutes(
+                src,
+                dest,
+                capacity,
+                opt_exclude,
+                search_mode,
+                response_sender,
+            ) => {
                 assert_eq!(src, PublicKey::from(&[8; PUBLIC_KEY_LEN]));
                 assert_eq!(dest, PublicKey::from(&[9; PUBLIC_KEY_LEN]));
                 assert_eq!(capacity, 100);
                 assert_eq!(opt_exclude, None);
+                assert_eq!(search_mode, SearchMode::Shortest);
                 response_sender.send(Vec::new()).unwrap();
             }
             _ => unreachable!(),
@@ -784,6 +1127,48 @@ mod tests {
             _ => unreachable!(),
         };
 
+        // Client requests liquidity:
+        let request_id = Uid::from(&[1; UID_LEN]);
+        let request_liquidity = RequestLiquidity {
+            request_id: request_id.clone(),
+            destination: PublicKey::from(&[9; PUBLIC_KEY_LEN]),
+        };
+        await!(client_sender.send(IndexClientToServer::RequestLiquidity(request_liquidity)))
+            .unwrap();
+
+        // Handle the graph request:
+        match await!(graph_requests_receiver.next()).unwrap() {
+            GraphRequest::GetLiquidity(dest, max_hops, response_sender) => {
+                assert_eq!(dest, PublicKey::from(&[9; PUBLIC_KEY_LEN]));
+                assert_eq!(max_hops, MAX_LIQUIDITY_HOPS);
+                let capacity_liquidity = CapacityLiquidity {
+                    neighbors: vec![(PublicKey::from(&[8; PUBLIC_KEY_LEN]), 30)],
+                    hops: vec![(1, 30), (3, 20)],
+                };
+                response_sender.send(capacity_liquidity).unwrap();
+            }
+            _ => unreachable!(),
+        }
+
+        match await!(client_receiver.next()).unwrap() {
+            IndexServerToClient::ResponseLiquidity(response_liquidity) => {
+                assert_eq!(response_liquidity.request_id, request_id);
+                let liquidity = response_liquidity.liquidity;
+                assert_eq!(
+                    liquidity.neighbors,
+                    vec![NeighborLiquidity {
+                        public_key: PublicKey::from(&[8; PUBLIC_KEY_LEN]),
+                        recv_capacity: 30,
+                    }]
+                );
+                assert_eq!(liquidity.hops.len(), 2);
+                assert_eq!(liquidity.hops[1].hops, 2);
+                assert_eq!(liquidity.hops[1].num_nodes, 3);
+                assert_eq!(liquidity.hops[1].recv_capacity, 20);
+            }
+            _ => unreachable!(),
+        };
+
         // Server should periodically send time hashes to the client:
         await!(tick_sender.send(())).unwrap();
 
@@ -840,6 +1225,113 @@ mod tests {
         thread_pool.run(task_index_server_loop_single_server(thread_pool.clone()));
     }
 
+    async fn task_index_server_loop_rate_limit<S>(mut spawner: S)
+    where
+        S: Spawn + Clone + Send + 'static,
+    {
+        let server_pk = PublicKey::from(&[0; PUBLIC_KEY_LEN]);
+
+        let local_public_key = server_pk.clone();
+        let trusted_servers: HashMap<PublicKey, u8> = HashMap::new();
+
+        let (_server_connections_sender, incoming_server_connections) = mpsc::channel(0);
+        let (mut client_connections_sender, incoming_client_connections) = mpsc::channel(0);
+
+        let (conn_request_sender, _conn_request_receiver) = mpsc::channel(0);
+        let server_connector = DummyConnector::new(conn_request_sender);
+
+        let (mut tick_sender, timer_stream) = mpsc::channel::<()>(0);
+
+        let (graph_requests_sender, mut graph_requests_receiver) = mpsc::channel(0);
+        let graph_client = GraphClient::new(graph_requests_sender);
+
+        let compare_public_key = |pk_a: &PublicKey, pk_b: &PublicKey| pk_a.cmp(pk_b);
+
+        let rng = DummyRandom::new(&[0u8]);
+        let verifier = SimpleVerifier::new(8, rng);
+
+        // Only one routes request is allowed per window:
+        let rate_limit_config = RateLimitConfig {
+            window_ticks: 16,
+            max_mutations_updates: 0x100,
+            max_request_routes: 1,
+            backoff_ticks: 16,
+        };
+
+        let server_loop_fut = server_loop(
+            local_public_key,
+            trusted_servers,
+            incoming_server_connections,
+            incoming_client_connections,
+            server_connector,
+            graph_client,
+            compare_public_key,
+            verifier,
+            timer_stream,
+            TEST_SNAPSHOT_TICKS,
+            None,
+            rate_limit_config,
+            spawner.clone(),
+            None,
+        )
+        .map_err(|e| error!("Error in server_loop(): {:?}", e))
+        .map(|_| ());
+
+        spawner.spawn(server_loop_fut).unwrap();
+
+        let client_public_key = PublicKey::from(&[1; PUBLIC_KEY_LEN]);
+
+        let (mut client_sender, server_receiver) = mpsc::channel(CHANNEL_SIZE);
+        let (server_sender, mut client_receiver) = mpsc::channel(CHANNEL_SIZE);
+        await!(client_connections_sender
+            .send((client_public_key.clone(), (server_sender, server_receiver))))
+        .unwrap();
+
+        let request_routes = RequestRoutes {
+            request_id: Uid::from(&[0; UID_LEN]),
+            capacity: 100,
+            source: PublicKey::from(&[8; PUBLIC_KEY_LEN]),
+            destination: PublicKey::from(&[9; PUBLIC_KEY_LEN]),
+            opt_exclude: None,
+            search_mode: RouteSearchMode::Shortest,
+        };
+
+        // The first request is served:
+        await!(client_sender.send(IndexClientToServer::RequestRoutes(request_routes.clone())))
+            .unwrap();
+        match await!(graph_requests_receiver.next()).unwrap() {
+            GraphRequest::GetMultiRoutes(_, _, _, _, _, response_sender) => {
+                response_sender.send(Vec::new()).unwrap();
+            }
+            _ => unreachable!(),
+        }
+        match await!(client_receiver.next()).unwrap() {
+            IndexServerToClient::ResponseRoutes(_) => {}
+            _ => unreachable!(),
+        };
+
+        // The second request exceeds the limit. The client is disconnected:
+        await!(client_sender.send(IndexClientToServer::RequestRoutes(request_routes))).unwrap();
+        assert!(await!(client_receiver.next()).is_none());
+
+        // The client may not reconnect while backing off:
+        let (_client_sender, server_receiver) = mpsc::channel(CHANNEL_SIZE);
+        let (server_sender, mut client_receiver) = mpsc::channel(CHANNEL_SIZE);
+        await!(client_connections_sender
+            .send((client_public_key.clone(), (server_sender, server_receiver))))
+        .unwrap();
+
+        // A connected client would have received a time hash:
+        await!(tick_sender.send(())).unwrap();
+        assert!(await!(client_receiver.next()).is_none());
+    }
+
+    #[test]
+    fn test_index_server_loop_rate_limit() {
+        let mut thread_pool = ThreadPool::new().unwrap();
+        thread_pool.run(task_index_server_loop_rate_limit(thread_pool.clone()));
+    }
+
     // ###########################################################
     // ###########################################################
 
@@ -896,6 +1388,9 @@ mod tests {
             compare_public_key,
             verifier,
             timer_stream,
+            TEST_SNAPSHOT_TICKS,
+            None,
+            test_rate_limit_config(),
             spawner.clone(),
             Some(debug_event_sender),
         )
@@ -1026,16 +1521,25 @@ mod tests {
             source: PublicKey::from(&[8; PUBLIC_KEY_LEN]),
             destination: PublicKey::from(&[9; PUBLIC_KEY_LEN]),
             opt_exclude: None,
+            search_mode: RouteSearchMode::Cheapest,
         };
         await!(client_sender.send(IndexClientToServer::RequestRoutes(request_routes))).unwrap();
 
         // Handle the graph request:
         match await!(test_servers[0].graph_requests_receiver.next()).unwrap() {
-            GraphRequest::GetMultiRoutes(src, dest, capacity, opt_exclude, response_sender) => {
+            GraphRequest::GetMultiRoutes(
+                src,
+                dest,
+                capacity,
+                opt_exclude,
+                search_mode,
+                response_sender,
+            ) => {
                 assert_eq!(src, PublicKey::from(&[8; PUBLIC_KEY_LEN]));
                 assert_eq!(dest, PublicKey::from(&[9; PUBLIC_KEY_LEN]));
                 assert_eq!(capacity, 100);
                 assert_eq!(opt_exclude, None);
+                assert_eq!(search_mode, SearchMode::Cheapest);
                 response_sender.send(Vec::new()).unwrap();
             }
             _ => unreachable!(),
@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use atomicwrites::{AllowOverwrite, AtomicFile};
+
+use crypto::identity::PublicKey;
+use crypto::uid::Uid;
+
+use proto::funder::messages::Rate;
+
+use crate::graph::capacity_graph::{CapacityEdge, CapacityGraph};
+use crate::verifier::Verifier;
+
+/// Saved state of a node known to the index server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeSnapshot {
+    pub public_key: PublicKey,
+    /// Last session id used by the node to send mutations
+    pub session_id: Uid,
+    /// Last counter used by the node to send mutations.
+    /// Protects against replay of old mutations after a restart.
+    pub counter: u64,
+    /// Amount of ticks left until the node is removed (unless it sends new mutations)
+    pub ticks_to_live: usize,
+}
+
+/// Saved state of a directed edge in the capacity graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeSnapshot {
+    pub from_public_key: PublicKey,
+    pub to_public_key: PublicKey,
+    pub send_capacity: u128,
+    pub recv_capacity: u128,
+    pub rate: Rate,
+    /// Amount of ticks since the edge was last updated
+    pub age: u128,
+}
+
+/// Saved state of an index server.
+/// Allows an index server to serve routes right after a restart, without waiting for all nodes
+/// to send their state again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexServerSnapshot {
+    pub nodes: Vec<NodeSnapshot>,
+    pub edges: Vec<EdgeSnapshot>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotFileError {
+    IoError(io::Error),
+    WriteError(atomicwrites::Error<io::Error>),
+    SerializeError(bincode::Error),
+}
+
+impl From<io::Error> for SnapshotFileError {
+    fn from(e: io::Error) -> Self {
+        SnapshotFileError::IoError(e)
+    }
+}
+
+impl From<bincode::Error> for SnapshotFileError {
+    fn from(e: bincode::Error) -> Self {
+        SnapshotFileError::SerializeError(e)
+    }
+}
+
+impl IndexServerSnapshot {
+    pub fn new() -> Self {
+        IndexServerSnapshot {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    /// Account for `elapsed_ticks` time ticks that passed since the snapshot was taken (For
+    /// example, while the index server was down).
+    /// Nodes that ran out of ticks are removed, together with all of their edges.
+    pub fn elapse(&mut self, elapsed_ticks: usize) {
+        for node in &mut self.nodes {
+            node.ticks_to_live = node.ticks_to_live.saturating_sub(elapsed_ticks);
+        }
+
+        self.nodes.retain(|node| node.ticks_to_live > 0);
+        // Note: Edges of unknown nodes are also removed, as they can not expire anymore.
+        let live_nodes = self
+            .nodes
+            .iter()
+            .map(|node| &node.public_key)
+            .collect::<HashSet<_>>();
+        self.edges
+            .retain(|edge| live_nodes.contains(&edge.from_public_key));
+    }
+}
+
+/// Load the state saved in a snapshot into a verifier and a capacity graph
+pub(crate) fn restore_snapshot<V, CG>(
+    snapshot: IndexServerSnapshot,
+    verifier: &mut V,
+    capacity_graph: &mut CG,
+) where
+    V: Verifier<Node = PublicKey, SessionId = Uid>,
+    CG: CapacityGraph<Node = PublicKey, Capacity = u128, Rate = Rate>,
+{
+    for node in snapshot.nodes {
+        verifier.import_node(
+            node.public_key,
+            node.session_id,
+            node.counter,
+            node.ticks_to_live,
+        );
+    }
+    for edge in snapshot.edges {
+        let capacity_edge = CapacityEdge::new((edge.send_capacity, edge.recv_capacity), edge.rate);
+        capacity_graph.import_edge(
+            edge.from_public_key,
+            edge.to_public_key,
+            capacity_edge,
+            edge.age,
+        );
+    }
+}
+
+/// Load an index server snapshot from a file
+pub fn load_snapshot_from_file(path: &Path) -> Result<IndexServerSnapshot, SnapshotFileError> {
+    let mut file = File::open(path)?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(bincode::deserialize(&data)?)
+}
+
+/// Store an index server snapshot to a file, atomically.
+/// An existing snapshot file will be overwritten.
+pub fn store_snapshot_to_file(
+    snapshot: &IndexServerSnapshot,
+    path: &Path,
+) -> Result<(), SnapshotFileError> {
+    let data = bincode::serialize(snapshot)?;
+    AtomicFile::new(path, AllowOverwrite)
+        .write(|f| f.write_all(&data))
+        .map_err(SnapshotFileError::WriteError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crypto::identity::PUBLIC_KEY_LEN;
+    use crypto::uid::UID_LEN;
+
+    fn example_snapshot() -> IndexServerSnapshot {
+        let pk_a = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let pk_b = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+
+        let mut snapshot = IndexServerSnapshot::new();
+        snapshot.nodes.push(NodeSnapshot {
+            public_key: pk_a.clone(),
+            session_id: Uid::from(&[0; UID_LEN]),
+            counter: 5,
+            ticks_to_live: 10,
+        });
+        snapshot.nodes.push(NodeSnapshot {
+            public_key: pk_b.clone(),
+            session_id: Uid::from(&[1; UID_LEN]),
+            counter: 7,
+            ticks_to_live: 20,
+        });
+        snapshot.edges.push(EdgeSnapshot {
+            from_public_key: pk_a.clone(),
+            to_public_key: pk_b.clone(),
+            send_capacity: 100,
+            recv_capacity: 200,
+            rate: Rate { mul: 1, add: 2 },
+            age: 3,
+        });
+        snapshot.edges.push(EdgeSnapshot {
+            from_public_key: pk_b,
+            to_public_key: pk_a,
+            send_capacity: 200,
+            recv_capacity: 100,
+            rate: Rate { mul: 0, add: 1 },
+            age: 0,
+        });
+        snapshot
+    }
+
+    #[test]
+    fn test_snapshot_elapse() {
+        let mut snapshot = example_snapshot();
+        snapshot.elapse(5);
+        assert_eq!(snapshot.nodes.len(), 2);
+        assert_eq!(snapshot.edges.len(), 2);
+        assert_eq!(snapshot.nodes[0].ticks_to_live, 5);
+
+        // First node times out, together with its edges:
+        snapshot.elapse(5);
+        assert_eq!(snapshot.nodes.len(), 1);
+        assert_eq!(snapshot.edges.len(), 1);
+        assert_eq!(
+            snapshot.edges[0].from_public_key,
+            snapshot.nodes[0].public_key
+        );
+
+        snapshot.elapse(100);
+        assert!(snapshot.nodes.is_empty());
+        assert!(snapshot.edges.is_empty());
+    }
+
+    #[test]
+    fn test_store_load_snapshot() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("index.snapshot");
+
+        let snapshot = example_snapshot();
+        store_snapshot_to_file(&snapshot, &file_path).unwrap();
+        assert_eq!(load_snapshot_from_file(&file_path).unwrap(), snapshot);
+
+        // Overwrite the existing snapshot:
+        let snapshot = IndexServerSnapshot::new();
+        store_snapshot_to_file(&snapshot, &file_path).unwrap();
+        assert_eq!(load_snapshot_from_file(&file_path).unwrap(), snapshot);
+    }
+}
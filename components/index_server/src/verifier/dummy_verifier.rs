@@ -59,4 +59,13 @@ impl<N, B, U> Verifier for DummyVerifier<N, B, U> {
         // Nothing happens
         None
     }
+
+    fn export_nodes(&self) -> Vec<(N, U, u64, usize)> {
+        // We don't remember any nodes
+        Vec::new()
+    }
+
+    fn import_node(&mut self, _node: N, _session_id: U, _counter: u64, _ticks_to_live: usize) {
+        // Nothing happens
+    }
 }
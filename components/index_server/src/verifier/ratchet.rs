@@ -1,3 +1,4 @@
+use std::cmp;
 use std::collections::HashMap;
 
 struct Ratchet<U> {
@@ -100,6 +101,32 @@ where
         };
         ratchet.update(session_id, counter)
     }
+
+    /// Export the state of all ratchets: (node, session_id, counter, cur_ticks_to_live)
+    pub fn export(&self) -> Vec<(N, U, u64, usize)> {
+        self.ratchets
+            .iter()
+            .map(|(node, ratchet)| {
+                (
+                    node.clone(),
+                    ratchet.session_id.clone(),
+                    ratchet.counter,
+                    ratchet.cur_ticks_to_live,
+                )
+            })
+            .collect()
+    }
+
+    /// Restore the state of a ratchet.
+    /// `cur_ticks_to_live` can not exceed the ticks to live configured for this pool.
+    pub fn import(&mut self, node: N, session_id: U, counter: u64, cur_ticks_to_live: usize) {
+        if cur_ticks_to_live == 0 {
+            return;
+        }
+        let mut ratchet = Ratchet::new(session_id, counter, self.ratchet_ticks_to_live);
+        ratchet.cur_ticks_to_live = cmp::min(cur_ticks_to_live, self.ratchet_ticks_to_live);
+        self.ratchets.insert(node, ratchet);
+    }
 }
 
 #[cfg(test)]
@@ -234,4 +261,30 @@ mod tests {
         // A proof that node 1u128 was not removed:
         assert!(!ratchet_pool.update(&1u128, &5u128, 101));
     }
+
+    #[test]
+    fn test_ratchet_pool_export_import() {
+        let ratchet_ticks_to_live = 8;
+        let mut ratchet_pool = RatchetPool::new(ratchet_ticks_to_live);
+
+        assert!(ratchet_pool.update(&0u128, &0u128, 5));
+        assert_eq!(ratchet_pool.tick(), vec![]);
+
+        let exported = ratchet_pool.export();
+        assert_eq!(exported, vec![(0u128, 0u128, 5u64, 7usize)]);
+
+        let mut ratchet_pool = RatchetPool::new(ratchet_ticks_to_live);
+        for (node, session_id, counter, cur_ticks_to_live) in exported {
+            ratchet_pool.import(node, session_id, counter, cur_ticks_to_live);
+        }
+
+        // Old messages can not be replayed after import:
+        assert!(!ratchet_pool.update(&0u128, &0u128, 5));
+
+        // Remaining ticks to live were restored:
+        for _ in 0..6 {
+            assert_eq!(ratchet_pool.tick(), vec![]);
+        }
+        assert_eq!(ratchet_pool.tick(), vec![0u128]);
+    }
 }
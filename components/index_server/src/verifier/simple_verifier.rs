@@ -79,6 +79,17 @@ where
     fn remove_neighbor(&mut self, neighbor: &B) -> Option<HashResult> {
         self.hash_clock.remove_neighbor(neighbor)
     }
+
+    fn export_nodes(&self) -> Vec<(N, U, u64, usize)> {
+        self.ratchet_pool.export()
+    }
+
+    fn import_node(&mut self, node: N, session_id: U, counter: u64, ticks_to_live: usize) {
+        // Note that the hash clock is not restored. Time proofs from before the restart can not
+        // be verified anyways.
+        self.ratchet_pool
+            .import(node, session_id, counter, ticks_to_live)
+    }
 }
 
 #[cfg(test)]
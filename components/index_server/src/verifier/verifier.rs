@@ -32,4 +32,18 @@ pub trait Verifier {
     /// Remove a neighbor. This method should be invoked when a neighbor disconnects.
     /// If not called, the time proofs (list of hashes) will be larger than needed.
     fn remove_neighbor(&mut self, neighbor: &Self::Neighbor) -> Option<HashResult>;
+
+    /// Export the state of all known nodes, to be saved to disk.
+    /// Returns tuples of (node, session_id, counter, ticks_to_live)
+    fn export_nodes(&self) -> Vec<(Self::Node, Self::SessionId, u64, usize)>;
+
+    /// Restore the state of a node from a saved state.
+    /// The node will be removed after `ticks_to_live` ticks unless it sends new messages.
+    fn import_node(
+        &mut self,
+        node: Self::Node,
+        session_id: Self::SessionId,
+        counter: u64,
+        ticks_to_live: usize,
+    );
 }
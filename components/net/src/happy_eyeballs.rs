@@ -0,0 +1,319 @@
+//! Connect to one of a few addresses, using staggered connection attempts.
+//! Loosely based on "Happy Eyeballs" (RFC 8305): Addresses of different families are
+//! interleaved, a new attempt is started every `attempt_delay_ticks` (or immediately once an
+//! attempt fails), and the first successful connection is used.
+
+use std::net::SocketAddr;
+
+use futures::channel::mpsc;
+use futures::future::RemoteHandle;
+use futures::task::{Spawn, SpawnExt};
+use futures::{stream, SinkExt, StreamExt};
+
+use common::conn::{ConnPairVec, FutTransform};
+use timer::utils::future_timeout;
+use timer::TimerClient;
+
+/// Order addresses so that address families alternate, starting with the family of the first
+/// address. The relative order of addresses of the same family is kept.
+pub fn interleave_families(socket_addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_ipv6 = match socket_addrs.first() {
+        Some(socket_addr) => socket_addr.is_ipv6(),
+        None => return Vec::new(),
+    };
+    let (first_family, second_family): (Vec<_>, Vec<_>) = socket_addrs
+        .into_iter()
+        .partition(|socket_addr| socket_addr.is_ipv6() == first_is_ipv6);
+
+    let mut res = Vec::new();
+    let mut first_iter = first_family.into_iter();
+    let mut second_iter = second_family.into_iter();
+    loop {
+        match (first_iter.next(), second_iter.next()) {
+            (None, None) => break,
+            (opt_first, opt_second) => {
+                res.extend(opt_first);
+                res.extend(opt_second);
+            }
+        }
+    }
+    res
+}
+
+#[derive(Debug)]
+enum ConnectEvent {
+    AttemptDone(Option<ConnPairVec>),
+    TimerTick,
+}
+
+/// Start a single connection attempt, limited to `attempt_timeout_ticks`.
+/// The result of the attempt is sent through `result_sender`.
+fn spawn_attempt<C, A, S>(
+    mut connector: C,
+    address: A,
+    mut timer_client: TimerClient,
+    attempt_timeout_ticks: usize,
+    mut result_sender: mpsc::Sender<ConnectEvent>,
+    spawner: &mut S,
+) -> Option<RemoteHandle<()>>
+where
+    C: FutTransform<Input = A, Output = Option<ConnPairVec>> + Send + 'static,
+    A: Send + 'static,
+    S: Spawn,
+{
+    let attempt_fut = async move {
+        let opt_conn_pair = match await!(timer_client.request_timer_stream()) {
+            Ok(timer_stream) => await!(future_timeout(
+                connector.transform(address),
+                timer_stream,
+                attempt_timeout_ticks
+            ))
+            .and_then(|opt_conn_pair| opt_conn_pair),
+            Err(e) => {
+                warn!("spawn_attempt(): request_timer_stream() error: {:?}", e);
+                None
+            }
+        };
+        let _ = await!(result_sender.send(ConnectEvent::AttemptDone(opt_conn_pair)));
+    };
+    spawner.spawn_with_handle(attempt_fut).ok()
+}
+
+/// Try to connect to `addresses` (In the given order), using staggered attempts.
+/// A new attempt is started every `attempt_delay_ticks`, or right away when an attempt fails.
+/// Every attempt is given `attempt_timeout_ticks` to succeed.
+///
+/// Returns the first connection that was successfully established. All other attempts are
+/// canceled.
+pub async fn connect_staggered<C, A, S>(
+    connector: C,
+    addresses: Vec<A>,
+    mut timer_client: TimerClient,
+    attempt_delay_ticks: usize,
+    attempt_timeout_ticks: usize,
+    mut spawner: S,
+) -> Option<ConnPairVec>
+where
+    C: FutTransform<Input = A, Output = Option<ConnPairVec>> + Clone + Send + 'static,
+    A: Send + 'static,
+    S: Spawn,
+{
+    let timer_stream = await!(timer_client.request_timer_stream()).ok()?;
+    let timer_stream = timer_stream.map(|_| ConnectEvent::TimerTick);
+
+    let (result_sender, result_receiver) = mpsc::channel(0);
+    let mut events = stream::select(timer_stream, result_receiver);
+
+    let mut addresses = addresses.into_iter();
+    // Handles to all running attempts. Dropping a handle cancels the attempt:
+    let mut attempt_handles = Vec::new();
+    let mut pending_attempts: usize = 0;
+    let mut ticks_since_attempt: usize = 0;
+
+    let mut start_next_attempt = |attempt_handles: &mut Vec<_>| -> bool {
+        let address = match addresses.next() {
+            Some(address) => address,
+            None => return false,
+        };
+        match spawn_attempt(
+            connector.clone(),
+            address,
+            timer_client.clone(),
+            attempt_timeout_ticks,
+            result_sender.clone(),
+            &mut spawner,
+        ) {
+            Some(handle) => {
+                attempt_handles.push(handle);
+                true
+            }
+            None => false,
+        }
+    };
+
+    if !start_next_attempt(&mut attempt_handles) {
+        return None;
+    }
+    pending_attempts = pending_attempts.checked_add(1).unwrap();
+
+    while let Some(event) = await!(events.next()) {
+        match event {
+            ConnectEvent::AttemptDone(Some(conn_pair)) => return Some(conn_pair),
+            ConnectEvent::AttemptDone(None) => {
+                pending_attempts = pending_attempts.checked_sub(1).unwrap();
+                // An attempt failed. We don't wait for the next tick to start another one:
+                if start_next_attempt(&mut attempt_handles) {
+                    pending_attempts = pending_attempts.checked_add(1).unwrap();
+                    ticks_since_attempt = 0;
+                } else if pending_attempts == 0 {
+                    // All addresses failed:
+                    return None;
+                }
+            }
+            ConnectEvent::TimerTick => {
+                ticks_since_attempt = ticks_since_attempt.saturating_add(1);
+                if ticks_since_attempt >= attempt_delay_ticks
+                    && start_next_attempt(&mut attempt_handles)
+                {
+                    pending_attempts = pending_attempts.checked_add(1).unwrap();
+                    ticks_since_attempt = 0;
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use futures::channel::oneshot;
+    use futures::future;
+
+    use common::conn::BoxFuture;
+    use common::test_executor::TestExecutor;
+    use timer::create_timer_incoming;
+
+    #[test]
+    fn test_interleave_families() {
+        let v4 = |i: u8| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, i)), 1337);
+        let v6 = |i: u16| SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, i)), 1337);
+
+        assert_eq!(
+            interleave_families(vec![v6(1), v6(2), v6(3), v4(1), v4(2)]),
+            vec![v6(1), v4(1), v6(2), v4(2), v6(3)]
+        );
+        assert_eq!(
+            interleave_families(vec![v4(1), v4(2), v6(1)]),
+            vec![v4(1), v6(1), v4(2)]
+        );
+        assert_eq!(interleave_families(vec![v4(1), v4(2)]), vec![v4(1), v4(2)]);
+        assert!(interleave_families(Vec::new()).is_empty());
+    }
+
+    #[derive(Debug, Clone)]
+    enum MockAddress {
+        /// Connection attempt never finishes
+        Hang,
+        /// Connection attempt fails immediately
+        Fail,
+        /// Connection succeeds. The resulting connection receives the given byte.
+        Succeed(u8),
+    }
+
+    #[derive(Clone)]
+    struct MockConnector;
+
+    impl FutTransform for MockConnector {
+        type Input = MockAddress;
+        type Output = Option<ConnPairVec>;
+
+        fn transform(&mut self, address: Self::Input) -> BoxFuture<'_, Self::Output> {
+            match address {
+                MockAddress::Hang => Box::pin(async move {
+                    // The sender is kept alive, so the receiver never resolves:
+                    let (sender, receiver) = oneshot::channel::<()>();
+                    let _ = await!(receiver);
+                    drop(sender);
+                    None
+                }),
+                MockAddress::Fail => Box::pin(future::ready(None)),
+                MockAddress::Succeed(id) => {
+                    let (sender, _) = mpsc::channel(0);
+                    let (mut remote_sender, receiver) = mpsc::channel(1);
+                    remote_sender.try_send(vec![id]).unwrap();
+                    Box::pin(future::ready(Some((sender, receiver))))
+                }
+            }
+        }
+    }
+
+    /// Spawn connect_staggered(). The result is sent through the returned oneshot receiver.
+    fn spawn_connect(
+        addresses: Vec<MockAddress>,
+        timer_client: TimerClient,
+        test_executor: &TestExecutor,
+    ) -> oneshot::Receiver<Option<ConnPairVec>> {
+        let (res_sender, res_receiver) = oneshot::channel();
+        let c_test_executor = test_executor.clone();
+        test_executor
+            .clone()
+            .spawn(async move {
+                let res = await!(connect_staggered(
+                    MockConnector,
+                    addresses,
+                    timer_client,
+                    4,  // attempt_delay_ticks
+                    16, // attempt_timeout_ticks
+                    c_test_executor
+                ));
+                let _ = res_sender.send(res);
+            })
+            .unwrap();
+        res_receiver
+    }
+
+    async fn task_connect_staggered_fallback(test_executor: TestExecutor) {
+        let (mut tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, test_executor.clone()).unwrap();
+
+        let addresses = vec![
+            MockAddress::Fail,
+            MockAddress::Hang,
+            MockAddress::Succeed(2),
+            MockAddress::Succeed(3),
+        ];
+        let mut res_receiver = spawn_connect(addresses, timer_client, &test_executor);
+        await!(test_executor.wait());
+
+        // The first address failed, so the second address is attempted right away.
+        // The second attempt hangs, so the third attempt starts only after 4 ticks:
+        for _ in 0..3 {
+            await!(tick_sender.send(())).unwrap();
+            await!(test_executor.wait());
+            assert_eq!(res_receiver.try_recv().unwrap().map(|_| ()), None);
+        }
+        await!(tick_sender.send(())).unwrap();
+        await!(test_executor.wait());
+
+        let (_sender, mut receiver) = res_receiver.try_recv().unwrap().unwrap().unwrap();
+        assert_eq!(await!(receiver.next()).unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn test_connect_staggered_fallback() {
+        let test_executor = TestExecutor::new();
+        let res = test_executor.run(task_connect_staggered_fallback(test_executor.clone()));
+        assert!(res.is_output());
+    }
+
+    async fn task_connect_staggered_timeout(test_executor: TestExecutor) {
+        let (mut tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, test_executor.clone()).unwrap();
+
+        let addresses = vec![MockAddress::Hang, MockAddress::Hang];
+        let mut res_receiver = spawn_connect(addresses, timer_client, &test_executor);
+        await!(test_executor.wait());
+
+        // The second attempt starts after 4 ticks, and times out 16 ticks later:
+        for _ in 0..19 {
+            await!(tick_sender.send(())).unwrap();
+            await!(test_executor.wait());
+            assert_eq!(res_receiver.try_recv().unwrap().map(|_| ()), None);
+        }
+        await!(tick_sender.send(())).unwrap();
+        await!(test_executor.wait());
+
+        assert!(res_receiver.try_recv().unwrap().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_connect_staggered_timeout() {
+        let test_executor = TestExecutor::new();
+        let res = test_executor.run(task_connect_staggered_timeout(test_executor.clone()));
+        assert!(res.is_output());
+    }
+}
@@ -16,14 +16,24 @@
 #[macro_use]
 extern crate log;
 
+mod happy_eyeballs;
 mod net_connector;
 mod resolver;
+mod socks5_connector;
 mod tcp_connector;
 mod tcp_listener;
 #[cfg(test)]
 mod tests;
 mod types;
+#[cfg(unix)]
+mod unix_connector;
+#[cfg(unix)]
+mod unix_listener;
 mod utils;
 
 pub use self::net_connector::NetConnector;
 pub use self::tcp_listener::TcpListener;
+#[cfg(unix)]
+pub use self::unix_connector::UnixConnector;
+#[cfg(unix)]
+pub use self::unix_listener::UnixListener;
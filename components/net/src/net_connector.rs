@@ -1,29 +1,71 @@
+use std::net::SocketAddr;
+
 use common::conn::{BoxFuture, ConnPairVec, FutTransform};
 use futures::task::Spawn;
 
+use proto::consts::{CONNECT_ATTEMPT_DELAY_TICKS, CONNECT_ATTEMPT_TIMEOUT_TICKS};
 use proto::net::messages::NetAddress;
 
+use timer::TimerClient;
+
+use crate::happy_eyeballs::{connect_staggered, interleave_families};
 use crate::resolver::Resolver;
+use crate::socks5_connector::Socks5Connector;
 use crate::tcp_connector::TcpConnector;
 
 #[derive(Clone)]
 pub struct NetConnector<S, RS> {
     resolver: Resolver<RS>,
     tcp_connector: TcpConnector<S>,
+    opt_socks5_connector: Option<Socks5Connector<S>>,
+    timer_client: TimerClient,
+    spawner: S,
 }
 
-impl<S, RS> NetConnector<S, RS> {
-    pub fn new(max_frame_length: usize, resolve_spawner: RS, spawner: S) -> Self {
+impl<S, RS> NetConnector<S, RS>
+where
+    S: Clone,
+{
+    pub fn new(
+        max_frame_length: usize,
+        resolve_spawner: RS,
+        timer_client: TimerClient,
+        spawner: S,
+    ) -> Self {
         NetConnector {
             resolver: Resolver::new(resolve_spawner),
-            tcp_connector: TcpConnector::new(max_frame_length, spawner),
+            tcp_connector: TcpConnector::new(max_frame_length, spawner.clone()),
+            opt_socks5_connector: None,
+            timer_client,
+            spawner,
+        }
+    }
+
+    /// Create a connector that connects to remote TCP addresses through a SOCKS5 proxy.
+    /// Domain names are resolved by the proxy.
+    pub fn new_socks5(
+        max_frame_length: usize,
+        socks5_proxy: SocketAddr,
+        resolve_spawner: RS,
+        timer_client: TimerClient,
+        spawner: S,
+    ) -> Self {
+        let socks5_connector = Socks5Connector::new(
+            socks5_proxy,
+            max_frame_length,
+            timer_client.clone(),
+            spawner.clone(),
+        );
+        NetConnector {
+            opt_socks5_connector: Some(socks5_connector),
+            ..NetConnector::new(max_frame_length, resolve_spawner, timer_client, spawner)
         }
     }
 }
 
 impl<S, RS> FutTransform for NetConnector<S, RS>
 where
-    S: Spawn + Send,
+    S: Spawn + Clone + Send + 'static,
     RS: Spawn + Send,
 {
     type Input = NetAddress;
@@ -32,11 +74,20 @@ where
     fn transform(&mut self, net_address: Self::Input) -> BoxFuture<'_, Self::Output> {
         debug!("Connecting to {:?}", net_address);
         Box::pin(async move {
-            let socket_addr_vec = await!(self.resolver.transform(net_address));
-            // A trivial implementation: We try to connect to the first address on the list.
-            // TODO: Maybe choose a random address in the future?
-            let socket_addr = socket_addr_vec.get(0)?;
-            await!(self.tcp_connector.transform(*socket_addr))
+            if let Some(socks5_connector) = &mut self.opt_socks5_connector {
+                return await!(socks5_connector.transform(net_address));
+            }
+
+            // Try all the resolved addresses, until one of them succeeds:
+            let socket_addrs = interleave_families(await!(self.resolver.transform(net_address)));
+            await!(connect_staggered(
+                self.tcp_connector.clone(),
+                socket_addrs,
+                self.timer_client.clone(),
+                CONNECT_ATTEMPT_DELAY_TICKS,
+                CONNECT_ATTEMPT_TIMEOUT_TICKS,
+                self.spawner.clone()
+            ))
         })
     }
 }
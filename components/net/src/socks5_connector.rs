@@ -0,0 +1,263 @@
+//! A connector that tunnels connections through a SOCKS5 proxy (RFC 1928).
+//! Only the CONNECT command without authentication is supported.
+//! Domain names are sent to the proxy as is, and are resolved by the proxy.
+
+use std::convert::TryFrom;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+use common::conn::{BoxFuture, ConnPairVec, FutTransform};
+
+use futures::compat::Future01CompatExt;
+use futures::task::Spawn;
+
+use tokio::io::{read_exact, write_all};
+use tokio::net::TcpStream;
+
+use proto::consts::CONNECT_ATTEMPT_TIMEOUT_TICKS;
+use proto::net::messages::NetAddress;
+
+use timer::utils::future_timeout;
+use timer::TimerClient;
+
+use crate::utils::stream_to_conn_pair;
+
+const SOCKS_VERSION: u8 = 5;
+const AUTH_METHOD_NONE: u8 = 0;
+const CMD_CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+const REPLY_SUCCEEDED: u8 = 0;
+
+#[derive(Debug)]
+pub enum Socks5Error {
+    IoError(io::Error),
+    InvalidAddress,
+    InvalidVersion,
+    NoAcceptableAuthMethod,
+    /// The proxy refused to connect. Contains the reply code sent by the proxy.
+    ConnectRefused(u8),
+    InvalidReply,
+}
+
+impl From<io::Error> for Socks5Error {
+    fn from(e: io::Error) -> Self {
+        Socks5Error::IoError(e)
+    }
+}
+
+/// Split an address of the form `host:port`.
+/// IPv6 hosts may be enclosed in brackets. For example: `[::1]:1337`
+fn split_host_port(address: &str) -> Option<(&str, u16)> {
+    let colon_index = address.rfind(':')?;
+    let port = address[colon_index + 1..].parse::<u16>().ok()?;
+    let host = &address[..colon_index];
+    let host = if host.starts_with('[') && host.ends_with(']') {
+        &host[1..host.len() - 1]
+    } else {
+        host
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some((host, port))
+}
+
+/// Encode the destination address of a CONNECT request:
+/// Address type, address and port.
+pub(crate) fn encode_dest_address(net_address: &NetAddress) -> Result<Vec<u8>, Socks5Error> {
+    let (host, port) = split_host_port(net_address.as_str()).ok_or(Socks5Error::InvalidAddress)?;
+
+    let mut res = Vec::new();
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ipv4_addr)) => {
+            res.push(ATYP_IPV4);
+            res.extend_from_slice(&ipv4_addr.octets());
+        }
+        Ok(IpAddr::V6(ipv6_addr)) => {
+            res.push(ATYP_IPV6);
+            res.extend_from_slice(&ipv6_addr.octets());
+        }
+        Err(_) => {
+            // A domain name. It will be resolved by the proxy:
+            let host_len = u8::try_from(host.len()).map_err(|_| Socks5Error::InvalidAddress)?;
+            res.push(ATYP_DOMAIN);
+            res.push(host_len);
+            res.extend_from_slice(host.as_bytes());
+        }
+    }
+    res.extend_from_slice(&port.to_be_bytes());
+    Ok(res)
+}
+
+/// Ask a SOCKS5 proxy (connected through `tcp_stream`) to connect to `net_address`.
+/// On success, returns a stream that is connected to `net_address`.
+async fn socks5_handshake(
+    tcp_stream: TcpStream,
+    net_address: &NetAddress,
+) -> Result<TcpStream, Socks5Error> {
+    let dest_address = encode_dest_address(net_address)?;
+
+    // Greeting: We only support connecting without authentication:
+    let (tcp_stream, _) =
+        await!(write_all(tcp_stream, vec![SOCKS_VERSION, 1, AUTH_METHOD_NONE]).compat())?;
+    let (tcp_stream, method_selection) = await!(read_exact(tcp_stream, [0u8; 2]).compat())?;
+    if method_selection[0] != SOCKS_VERSION {
+        return Err(Socks5Error::InvalidVersion);
+    }
+    if method_selection[1] != AUTH_METHOD_NONE {
+        return Err(Socks5Error::NoAcceptableAuthMethod);
+    }
+
+    // Connect request:
+    let mut request = vec![SOCKS_VERSION, CMD_CONNECT, 0];
+    request.extend_from_slice(&dest_address);
+    let (tcp_stream, _) = await!(write_all(tcp_stream, request).compat())?;
+
+    // Reply: version, reply code, reserved, address type, bound address and port.
+    let (tcp_stream, reply_header) = await!(read_exact(tcp_stream, [0u8; 4]).compat())?;
+    if reply_header[0] != SOCKS_VERSION {
+        return Err(Socks5Error::InvalidVersion);
+    }
+    if reply_header[1] != REPLY_SUCCEEDED {
+        return Err(Socks5Error::ConnectRefused(reply_header[1]));
+    }
+    let (tcp_stream, bound_address_len) = match reply_header[3] {
+        ATYP_IPV4 => (tcp_stream, 4),
+        ATYP_IPV6 => (tcp_stream, 16),
+        ATYP_DOMAIN => {
+            let (tcp_stream, domain_len) = await!(read_exact(tcp_stream, [0u8; 1]).compat())?;
+            (tcp_stream, usize::from(domain_len[0]))
+        }
+        _ => return Err(Socks5Error::InvalidReply),
+    };
+    // We have no use for the bound address and port:
+    let (tcp_stream, _) =
+        await!(read_exact(tcp_stream, vec![0u8; bound_address_len + 2]).compat())?;
+
+    Ok(tcp_stream)
+}
+
+/// Connect to the SOCKS5 proxy at `proxy_address`, and ask it to connect to `net_address`.
+async fn connect_through_proxy(
+    proxy_address: SocketAddr,
+    net_address: &NetAddress,
+) -> Result<TcpStream, Socks5Error> {
+    let tcp_stream = await!(TcpStream::connect(&proxy_address).compat())?;
+    await!(socks5_handshake(tcp_stream, net_address))
+}
+
+/// Connect to remote addresses through a SOCKS5 proxy
+#[derive(Clone)]
+pub struct Socks5Connector<S> {
+    proxy_address: SocketAddr,
+    max_frame_length: usize,
+    timer_client: TimerClient,
+    spawner: S,
+}
+
+impl<S> Socks5Connector<S> {
+    pub fn new(
+        proxy_address: SocketAddr,
+        max_frame_length: usize,
+        timer_client: TimerClient,
+        spawner: S,
+    ) -> Self {
+        Socks5Connector {
+            proxy_address,
+            max_frame_length,
+            timer_client,
+            spawner,
+        }
+    }
+}
+
+impl<S> FutTransform for Socks5Connector<S>
+where
+    S: Spawn + Send,
+{
+    type Input = NetAddress;
+    type Output = Option<ConnPairVec>;
+
+    fn transform(&mut self, net_address: Self::Input) -> BoxFuture<'_, Self::Output> {
+        Box::pin(async move {
+            let timer_stream = match await!(self.timer_client.request_timer_stream()) {
+                Ok(timer_stream) => timer_stream,
+                Err(e) => {
+                    warn!("Socks5Connector: request_timer_stream() error: {:?}", e);
+                    return None;
+                }
+            };
+
+            // Connecting to the proxy and the handshake are limited in time, just like a direct
+            // connection attempt:
+            let connect_fut = Box::pin(connect_through_proxy(self.proxy_address, &net_address));
+            let tcp_stream = match await!(future_timeout(
+                connect_fut,
+                timer_stream,
+                CONNECT_ATTEMPT_TIMEOUT_TICKS
+            )) {
+                Some(Ok(tcp_stream)) => tcp_stream,
+                Some(Err(e)) => {
+                    warn!(
+                        "Socks5Connector: Failed connecting to {:?} through {:?}: {:?}",
+                        net_address, self.proxy_address, e
+                    );
+                    return None;
+                }
+                None => {
+                    warn!(
+                        "Socks5Connector: Timeout connecting to {:?} through {:?}",
+                        net_address, self.proxy_address
+                    );
+                    return None;
+                }
+            };
+
+            Some(stream_to_conn_pair(
+                tcp_stream,
+                self.max_frame_length,
+                &mut self.spawner,
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn net_address(address: &str) -> NetAddress {
+        address.to_owned().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_encode_dest_address() {
+        assert_eq!(
+            encode_dest_address(&net_address("127.0.0.1:1337")).unwrap(),
+            vec![ATYP_IPV4, 127, 0, 0, 1, 0x05, 0x39]
+        );
+
+        let mut expected = vec![ATYP_IPV6];
+        expected.extend_from_slice(&[0; 15]);
+        expected.extend_from_slice(&[1, 0x05, 0x39]);
+        assert_eq!(
+            encode_dest_address(&net_address("[::1]:1337")).unwrap(),
+            expected
+        );
+
+        let mut expected = vec![ATYP_DOMAIN, 11];
+        expected.extend_from_slice(b"example.com");
+        expected.extend_from_slice(&[0x01, 0xbb]);
+        assert_eq!(
+            encode_dest_address(&net_address("example.com:443")).unwrap(),
+            expected
+        );
+
+        assert!(encode_dest_address(&net_address("example.com")).is_err());
+        assert!(encode_dest_address(&net_address(":443")).is_err());
+        assert!(encode_dest_address(&net_address("example.com:70000")).is_err());
+    }
+}
@@ -6,7 +6,7 @@ use futures::task::Spawn;
 use std::net::SocketAddr;
 use tokio::net::TcpStream;
 
-use crate::utils::tcp_stream_to_conn_pair;
+use crate::utils::stream_to_conn_pair;
 
 #[derive(Debug, Clone)]
 pub struct TcpConnector<S> {
@@ -34,7 +34,7 @@ where
         Box::pin(async move {
             let tcp_stream = await!(TcpStream::connect(&socket_addr).compat()).ok()?;
 
-            Some(tcp_stream_to_conn_pair(
+            Some(stream_to_conn_pair(
                 tcp_stream,
                 self.max_frame_length,
                 &mut self.spawner,
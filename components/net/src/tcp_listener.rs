@@ -6,7 +6,7 @@ use futures::channel::mpsc;
 use futures::task::{Spawn, SpawnExt};
 use futures::{SinkExt, StreamExt};
 
-use crate::utils::tcp_stream_to_conn_pair;
+use crate::utils::stream_to_conn_pair;
 use common::conn::{ConnPairVec, Listener};
 
 use futures::compat::Stream01CompatExt;
@@ -55,8 +55,7 @@ where
         let c_max_frame_length = self.max_frame_length;
         let _ = self.spawner.spawn(async move {
             while let Some(Ok(tcp_stream)) = await!(incoming_conns.next()) {
-                let conn_pair =
-                    tcp_stream_to_conn_pair(tcp_stream, c_max_frame_length, &mut c_spawner);
+                let conn_pair = stream_to_conn_pair(tcp_stream, c_max_frame_length, &mut c_spawner);
                 if let Err(e) = await!(conn_receiver_sender.send(conn_pair)) {
                     warn!("TcpListener::listen(): Send error: {:?}", e);
                     return;
@@ -1,19 +1,30 @@
 use std::convert::TryInto;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
 
 use env_logger;
 
+use futures::channel::mpsc;
+use futures::compat::{Future01CompatExt, Stream01CompatExt};
 use futures::executor::ThreadPool;
-use futures::task::Spawn;
+use futures::task::{Spawn, SpawnExt};
 use futures::{SinkExt, StreamExt};
 
 use common::conn::{FutTransform, Listener};
 use proto::net::messages::NetAddress;
+use timer::{create_timer, TimerClient};
 
+use crate::happy_eyeballs::connect_staggered;
 use crate::net_connector::NetConnector;
 use crate::tcp_connector::TcpConnector;
 use crate::tcp_listener::TcpListener;
+#[cfg(unix)]
+use crate::unix_connector::UnixConnector;
+#[cfg(unix)]
+use crate::unix_listener::UnixListener;
+use crate::utils::stream_to_conn_pair;
 
+use tokio::io::{read_exact, write_all};
 use tokio::net::TcpListener as TokioTcpListener;
 
 /// Get an available port we can listen on
@@ -29,6 +40,14 @@ fn get_available_port_v4() -> u16 {
 
 const TEST_MAX_FRAME_LEN: usize = 0x100;
 
+/// Create a timer client with short ticks
+fn create_test_timer<S>(spawner: S) -> TimerClient
+where
+    S: Spawn,
+{
+    create_timer(Duration::from_millis(10), spawner).unwrap()
+}
+
 async fn task_tcp_client_server_v4<S>(spawner: S)
 where
     S: Spawn + Clone + Send + 'static,
@@ -82,7 +101,12 @@ where
     let socket_addr = SocketAddr::new(IpAddr::V4(loopback), available_port);
 
     let tcp_listener = TcpListener::new(TEST_MAX_FRAME_LEN, spawner.clone());
-    let mut net_connector = NetConnector::new(TEST_MAX_FRAME_LEN, spawner.clone(), spawner.clone());
+    let mut net_connector = NetConnector::new(
+        TEST_MAX_FRAME_LEN,
+        spawner.clone(),
+        create_test_timer(spawner.clone()),
+        spawner.clone(),
+    );
 
     let (_config_sender, mut incoming_connections) = tcp_listener.listen(socket_addr.clone());
 
@@ -107,6 +131,48 @@ fn test_net_connector_v4_basic() {
     thread_pool.run(task_net_connector_v4_basic(thread_pool.clone()));
 }
 
+async fn task_connect_staggered_tcp<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let loopback = Ipv4Addr::new(127, 0, 0, 1);
+    // Nobody listens on this port:
+    let closed_addr = SocketAddr::new(IpAddr::V4(loopback), get_available_port_v4());
+
+    let mut listen_addrs = Vec::new();
+    let mut incoming_conns_vec = Vec::new();
+    for _ in 0..2 {
+        let socket_addr = SocketAddr::new(IpAddr::V4(loopback), get_available_port_v4());
+        let tcp_listener = TcpListener::new(TEST_MAX_FRAME_LEN, spawner.clone());
+        let (config_sender, incoming_conns) = tcp_listener.listen(socket_addr.clone());
+        listen_addrs.push(socket_addr);
+        incoming_conns_vec.push((config_sender, incoming_conns));
+    }
+
+    let tcp_connector = TcpConnector::new(TEST_MAX_FRAME_LEN, spawner.clone());
+    // The first address fails, so we should connect to the second address:
+    let (mut client_sender, _client_receiver) = await!(connect_staggered(
+        tcp_connector,
+        vec![closed_addr, listen_addrs[0], listen_addrs[1]],
+        create_test_timer(spawner.clone()),
+        100, // attempt_delay_ticks
+        100, // attempt_timeout_ticks
+        spawner.clone()
+    ))
+    .unwrap();
+
+    let (_config_sender, incoming_conns) = &mut incoming_conns_vec[0];
+    let (_server_sender, mut server_receiver) = await!(incoming_conns.next()).unwrap();
+    await!(client_sender.send(vec![1, 2, 3])).unwrap();
+    assert_eq!(await!(server_receiver.next()).unwrap(), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_connect_staggered_tcp() {
+    let mut thread_pool = ThreadPool::new().unwrap();
+    thread_pool.run(task_connect_staggered_tcp(thread_pool.clone()));
+}
+
 async fn task_net_connector_v4_drop_sender<S>(spawner: S)
 where
     S: Spawn + Clone + Send + 'static,
@@ -116,7 +182,12 @@ where
     let socket_addr = SocketAddr::new(IpAddr::V4(loopback), available_port);
 
     let tcp_listener = TcpListener::new(TEST_MAX_FRAME_LEN, spawner.clone());
-    let mut net_connector = NetConnector::new(TEST_MAX_FRAME_LEN, spawner.clone(), spawner.clone());
+    let mut net_connector = NetConnector::new(
+        TEST_MAX_FRAME_LEN,
+        spawner.clone(),
+        create_test_timer(spawner.clone()),
+        spawner.clone(),
+    );
 
     let (_config_sender, mut incoming_connections) = tcp_listener.listen(socket_addr.clone());
 
@@ -140,3 +211,193 @@ fn test_net_connector_v4_drop_sender() {
     let mut thread_pool = ThreadPool::new().unwrap();
     thread_pool.run(task_net_connector_v4_drop_sender(thread_pool.clone()));
 }
+
+#[cfg(unix)]
+async fn task_unix_client_server<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    let dir = tempdir().unwrap();
+    let socket_path = dir.path().join("node.sock");
+
+    let unix_listener = UnixListener::new(TEST_MAX_FRAME_LEN, spawner.clone());
+    let mut unix_connector = UnixConnector::new(TEST_MAX_FRAME_LEN, spawner.clone());
+
+    let (_config_sender, mut incoming_connections) = unix_listener.listen(socket_path.clone());
+
+    // Only the owner may connect to the socket:
+    let metadata = fs::metadata(&socket_path).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+    for _ in 0..5 {
+        let (mut client_sender, mut client_receiver) =
+            await!(unix_connector.transform(socket_path.clone())).unwrap();
+        let (mut server_sender, mut server_receiver) = await!(incoming_connections.next()).unwrap();
+
+        await!(client_sender.send(vec![1, 2, 3])).unwrap();
+        assert_eq!(await!(server_receiver.next()).unwrap(), vec![1, 2, 3]);
+
+        await!(server_sender.send(vec![3, 2, 1])).unwrap();
+        assert_eq!(await!(client_receiver.next()).unwrap(), vec![3, 2, 1]);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_client_server() {
+    let mut thread_pool = ThreadPool::new().unwrap();
+    thread_pool.run(task_unix_client_server(thread_pool.clone()));
+}
+
+#[cfg(unix)]
+async fn task_unix_listener_existing_path<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    use std::fs;
+    use std::os::unix::net::UnixListener as StdUnixListener;
+    use tempfile::tempdir;
+
+    let dir = tempdir().unwrap();
+    let socket_path = dir.path().join("node.sock");
+
+    // A stale socket, left by a previous run:
+    drop(StdUnixListener::bind(&socket_path).unwrap());
+
+    // The stale socket is replaced:
+    let unix_listener = UnixListener::new(TEST_MAX_FRAME_LEN, spawner.clone());
+    let (_config_sender, mut incoming_connections) = unix_listener.listen(socket_path.clone());
+    let mut unix_connector = UnixConnector::new(TEST_MAX_FRAME_LEN, spawner.clone());
+
+    let (mut client_sender, _client_receiver) =
+        await!(unix_connector.transform(socket_path.clone())).unwrap();
+    let (_server_sender, mut server_receiver) = await!(incoming_connections.next()).unwrap();
+    await!(client_sender.send(vec![1, 2, 3])).unwrap();
+    assert_eq!(await!(server_receiver.next()).unwrap(), vec![1, 2, 3]);
+
+    // A socket that is still in use is not replaced:
+    let unix_listener = UnixListener::new(TEST_MAX_FRAME_LEN, spawner.clone());
+    let (_config_sender, mut incoming_connections2) = unix_listener.listen(socket_path.clone());
+    assert!(await!(incoming_connections2.next()).is_none());
+
+    // The original listener still accepts connections:
+    let (mut client_sender, _client_receiver) =
+        await!(unix_connector.transform(socket_path.clone())).unwrap();
+    let (_server_sender, mut server_receiver) = await!(incoming_connections.next()).unwrap();
+    await!(client_sender.send(vec![3, 2, 1])).unwrap();
+    assert_eq!(await!(server_receiver.next()).unwrap(), vec![3, 2, 1]);
+
+    // Files that are not sockets are never removed:
+    let file_path = dir.path().join("file");
+    fs::write(&file_path, b"data").unwrap();
+    let unix_listener = UnixListener::new(TEST_MAX_FRAME_LEN, spawner.clone());
+    let (_config_sender, mut incoming_connections3) = unix_listener.listen(file_path.clone());
+    assert!(await!(incoming_connections3.next()).is_none());
+    assert_eq!(fs::read(&file_path).unwrap(), b"data");
+
+    // No temporary files are left behind:
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_listener_existing_path() {
+    let mut thread_pool = ThreadPool::new().unwrap();
+    thread_pool.run(task_unix_listener_existing_path(thread_pool.clone()));
+}
+
+/// A minimal in-process SOCKS5 proxy stand-in.
+/// Accepts CONNECT requests (without authentication) and reports the requested destination
+/// (host, port) through `dest_sender`. Instead of connecting to the destination, it echoes back
+/// every frame it receives.
+async fn socks5_echo_proxy<S>(
+    tokio_listener: TokioTcpListener,
+    mut dest_sender: mpsc::Sender<(String, u16)>,
+    mut spawner: S,
+) where
+    S: Spawn + Clone + Send + 'static,
+{
+    let mut incoming = tokio_listener.incoming().compat();
+    while let Some(Ok(tcp_stream)) = await!(incoming.next()) {
+        let (tcp_stream, greeting) = await!(read_exact(tcp_stream, [0u8; 3]).compat()).unwrap();
+        assert_eq!(greeting, [5, 1, 0]);
+        let (tcp_stream, _) = await!(write_all(tcp_stream, [5u8, 0]).compat()).unwrap();
+
+        let (tcp_stream, header) = await!(read_exact(tcp_stream, [0u8; 5]).compat()).unwrap();
+        // Version, CONNECT, reserved, domain address type:
+        assert_eq!(&header[..4], &[5, 1, 0, 3]);
+        let domain_len = usize::from(header[4]);
+        let (tcp_stream, domain) =
+            await!(read_exact(tcp_stream, vec![0u8; domain_len]).compat()).unwrap();
+        let (tcp_stream, port) = await!(read_exact(tcp_stream, [0u8; 2]).compat()).unwrap();
+        let domain = String::from_utf8(domain).unwrap();
+        await!(dest_sender.send((domain, u16::from_be_bytes(port)))).unwrap();
+
+        // Success. Bound address is 0.0.0.0:0:
+        let reply = [5u8, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        let (tcp_stream, _) = await!(write_all(tcp_stream, reply).compat()).unwrap();
+
+        let (mut sender, mut receiver) =
+            stream_to_conn_pair(tcp_stream, TEST_MAX_FRAME_LEN, &mut spawner);
+        spawner
+            .spawn(async move {
+                while let Some(data) = await!(receiver.next()) {
+                    if await!(sender.send(data)).is_err() {
+                        return;
+                    }
+                }
+            })
+            .unwrap();
+    }
+}
+
+async fn task_net_connector_socks5<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let loopback = Ipv4Addr::new(127, 0, 0, 1);
+    let tokio_listener = TokioTcpListener::bind(&SocketAddr::new(IpAddr::V4(loopback), 0)).unwrap();
+    let proxy_address = tokio_listener.local_addr().unwrap();
+
+    let (dest_sender, mut dest_receiver) = mpsc::channel(0);
+    spawner
+        .clone()
+        .spawn(socks5_echo_proxy(
+            tokio_listener,
+            dest_sender,
+            spawner.clone(),
+        ))
+        .unwrap();
+
+    let mut net_connector = NetConnector::new_socks5(
+        TEST_MAX_FRAME_LEN,
+        proxy_address,
+        spawner.clone(),
+        create_test_timer(spawner.clone()),
+        spawner.clone(),
+    );
+
+    for i in 0..3u8 {
+        // The domain name is resolved remotely by the proxy:
+        let net_address: NetAddress = "relay.example.onion:1337".to_owned().try_into().unwrap();
+        let (mut client_sender, mut client_receiver) =
+            await!(net_connector.transform(net_address)).unwrap();
+        assert_eq!(
+            await!(dest_receiver.next()).unwrap(),
+            ("relay.example.onion".to_owned(), 1337)
+        );
+
+        await!(client_sender.send(vec![i, 2, 3])).unwrap();
+        assert_eq!(await!(client_receiver.next()).unwrap(), vec![i, 2, 3]);
+    }
+}
+
+#[test]
+fn test_net_connector_socks5() {
+    let mut thread_pool = ThreadPool::new().unwrap();
+    thread_pool.run(task_net_connector_socks5(thread_pool.clone()));
+}
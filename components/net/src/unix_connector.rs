@@ -0,0 +1,44 @@
+use common::conn::{BoxFuture, ConnPairVec, FutTransform};
+
+use futures::compat::Future01CompatExt;
+use futures::task::Spawn;
+
+use std::path::PathBuf;
+use tokio::net::UnixStream;
+
+use crate::utils::stream_to_conn_pair;
+
+#[derive(Debug, Clone)]
+pub struct UnixConnector<S> {
+    max_frame_length: usize,
+    spawner: S,
+}
+
+impl<S> UnixConnector<S> {
+    pub fn new(max_frame_length: usize, spawner: S) -> Self {
+        UnixConnector {
+            max_frame_length,
+            spawner,
+        }
+    }
+}
+
+impl<S> FutTransform for UnixConnector<S>
+where
+    S: Spawn + Send,
+{
+    type Input = PathBuf;
+    type Output = Option<ConnPairVec>;
+
+    fn transform(&mut self, socket_path: Self::Input) -> BoxFuture<'_, Self::Output> {
+        Box::pin(async move {
+            let unix_stream = await!(UnixStream::connect(&socket_path).compat()).ok()?;
+
+            Some(stream_to_conn_pair(
+                unix_stream,
+                self.max_frame_length,
+                &mut self.spawner,
+            ))
+        })
+    }
+}
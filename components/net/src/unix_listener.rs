@@ -0,0 +1,131 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::net::UnixStream as StdUnixStream;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use tokio::net::UnixListener as TokioUnixListener;
+
+use futures::channel::mpsc;
+use futures::task::{Spawn, SpawnExt};
+use futures::{SinkExt, StreamExt};
+
+use crate::utils::stream_to_conn_pair;
+use common::conn::{ConnPairVec, Listener};
+
+use futures::compat::Stream01CompatExt;
+
+/// File permissions of a listening socket: Only the owner may connect.
+const SOCKET_FILE_MODE: u32 = 0o600;
+/// Permissions of the temporary directory a socket is created in.
+const PRIVATE_DIR_MODE: u32 = 0o700;
+
+/// Bind to a Unix domain socket path.
+///
+/// A stale socket file left by a previous run is removed. A socket that is still in use (Some
+/// process accepts connections on it) is never removed, and neither are other kinds of files.
+///
+/// The socket is created inside a private temporary directory, where its permissions are set
+/// before it is moved into place. This way nobody else can connect to the socket before its
+/// permissions are restricted.
+fn bind_socket(socket_path: &Path) -> io::Result<TokioUnixListener> {
+    if let Ok(metadata) = fs::symlink_metadata(socket_path) {
+        if !metadata.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "Path exists and is not a socket",
+            ));
+        }
+        if StdUnixStream::connect(socket_path).is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, "Socket is in use"));
+        }
+        fs::remove_file(socket_path)?;
+    }
+
+    let file_name = socket_path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid socket path"))?;
+    let private_dir = socket_path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        process::id()
+    ));
+    fs::DirBuilder::new()
+        .mode(PRIVATE_DIR_MODE)
+        .create(&private_dir)?;
+
+    let temp_socket_path = private_dir.join(file_name);
+    let res = TokioUnixListener::bind(&temp_socket_path).and_then(|listener| {
+        fs::set_permissions(
+            &temp_socket_path,
+            fs::Permissions::from_mode(SOCKET_FILE_MODE),
+        )?;
+        fs::rename(&temp_socket_path, socket_path)?;
+        Ok(listener)
+    });
+    if res.is_err() {
+        let _ = fs::remove_file(&temp_socket_path);
+    }
+    let _ = fs::remove_dir(&private_dir);
+    res
+}
+
+/// Listen for incoming connections on a Unix domain socket.
+/// Only the owner of the socket file may connect. Access can be widened by changing the
+/// permissions of the socket file.
+pub struct UnixListener<S> {
+    max_frame_length: usize,
+    spawner: S,
+}
+
+impl<S> UnixListener<S> {
+    pub fn new(max_frame_length: usize, spawner: S) -> Self {
+        UnixListener {
+            max_frame_length,
+            spawner,
+        }
+    }
+}
+
+impl<S> Listener for UnixListener<S>
+where
+    S: Spawn + Send + Clone + 'static,
+{
+    type Connection = ConnPairVec;
+    type Config = ();
+    type Arg = PathBuf;
+
+    fn listen(
+        mut self,
+        socket_path: Self::Arg,
+    ) -> (mpsc::Sender<Self::Config>, mpsc::Receiver<Self::Connection>) {
+        let (config_sender, _config_sender_receiver) = mpsc::channel(0);
+        let (mut conn_receiver_sender, conn_receiver) = mpsc::channel(0);
+
+        let listener = match bind_socket(&socket_path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!("Failed listening on {:?}: {:?}", socket_path, e);
+                // Return empty channels:
+                return (config_sender, conn_receiver);
+            }
+        };
+
+        let mut incoming_conns = listener.incoming().compat();
+        let mut c_spawner = self.spawner.clone();
+        let c_max_frame_length = self.max_frame_length;
+        let _ = self.spawner.spawn(async move {
+            while let Some(Ok(unix_stream)) = await!(incoming_conns.next()) {
+                let conn_pair =
+                    stream_to_conn_pair(unix_stream, c_max_frame_length, &mut c_spawner);
+                if let Err(e) = await!(conn_receiver_sender.send(conn_pair)) {
+                    warn!("UnixListener::listen(): Send error: {:?}", e);
+                    return;
+                }
+            }
+        });
+
+        (config_sender, conn_receiver)
+    }
+}
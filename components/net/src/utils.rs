@@ -9,7 +9,7 @@ use futures_01::sink::Sink as Sink01;
 use futures_01::stream::Stream as Stream01;
 
 use tokio::codec::{Framed, LengthDelimitedCodec};
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
 
 use common::conn::ConnPairVec;
 
@@ -95,17 +95,16 @@ where
     (user_sender, user_receiver)
 }
 
-pub fn tcp_stream_to_conn_pair<S>(
-    tcp_stream: TcpStream,
-    max_frame_length: usize,
-    spawner: &mut S,
-) -> ConnPairVec
+/// Split a byte stream (For example, a TCP stream or a Unix domain socket stream) into length
+/// prefixed frames.
+pub fn stream_to_conn_pair<T, S>(stream: T, max_frame_length: usize, spawner: &mut S) -> ConnPairVec
 where
+    T: AsyncRead + AsyncWrite + Send + 'static,
     S: Spawn + Send,
 {
     let mut codec = LengthDelimitedCodec::new();
     codec.set_max_frame_length(max_frame_length);
-    let (sender_01, receiver_01) = Framed::new(tcp_stream, codec).split();
+    let (sender_01, receiver_01) = Framed::new(stream, codec).split();
 
     // Conversion layer between Vec<u8> to Bytes:
     let sender_01 = sender_01
@@ -4,5 +4,15 @@ mod node_connection;
 pub use self::connect::{node_connect, NodeConnection};
 
 pub use self::node_connection::{
-    buyer::AppBuyer, config::AppConfig, report::AppReport, routes::AppRoutes, seller::AppSeller,
+    buyer::{
+        AppBuyer, BuyerError, PayInvoiceConfig, PayInvoiceError, PayInvoiceEvent,
+        PayInvoiceFailure, RebalanceError,
+    },
+    config::AppConfig,
+    multi_route_util::{
+        choose_multi_route, choose_multi_route_max_fees, multi_route_fees, MultiRouteChoice,
+    },
+    report::AppReport,
+    routes::AppRoutes,
+    seller::{AppSeller, SellerError},
 };
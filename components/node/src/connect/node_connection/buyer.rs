@@ -1,8 +1,12 @@
+use std::collections::HashSet;
+
 use common::multi_consumer::MultiConsumerClient;
 use futures::channel::mpsc;
+use futures::future::join_all;
 use futures::{SinkExt, StreamExt};
 
 use crypto::crypto_rand::{CryptoRandom, OffstSystemRandom};
+use crypto::dh::DhPublicKey;
 use crypto::identity::PublicKey;
 use crypto::invoice_id::InvoiceId;
 use crypto::payment_id::PaymentId;
@@ -10,9 +14,14 @@ use crypto::uid::Uid;
 
 use proto::app_server::messages::{AppRequest, AppToAppServer};
 use proto::funder::messages::{
-    AckClosePayment, Commit, CreatePayment, CreateTransaction, FriendsRoute, PaymentStatus,
-    RequestResult, ResponseClosePayment, TransactionResult,
+    AckClosePayment, Commit, CreatePayment, CreateRebalance, CreateTransaction, FailureReason,
+    FriendsRoute, MultiCommit, PaymentStatus, RequestFailure, RequestResult, ResponseClosePayment,
+    TransactionResult,
 };
+use proto::index_server::messages::{MultiRoute, RouteSearchMode};
+
+use super::multi_route_util::choose_multi_route_max_fees;
+use super::routes::AppRoutes;
 
 // TODO: Different in naming convention from AppConfigError and AppRoutesError:
 #[derive(Debug)]
@@ -22,12 +31,149 @@ pub enum BuyerError {
     ConnectivityError,
     /// A remote error occurred when trying to send funds.
     /// (Not enough credits, Some node cancelled along the route)
-    NodeError,
+    NodeError(RequestFailure),
+    /// A remote error occurred when trying to send funds.
+    /// The node did not report any details (Older nodes).
+    UnknownNodeError,
     /// The request was issued, but no response was received.
     /// The request should be saved (By the caller) and resent at another time.
     NoResponse,
 }
 
+#[derive(Debug)]
+pub enum PayInvoiceError {
+    BuyerError(BuyerError),
+    RequestRoutesError,
+    NoSuitableRoute,
+    /// Paying the remaining amount would exceed the fees budget
+    MaxFeesExceeded,
+    /// Routes were requested too many times
+    MaxAttemptsReached,
+    /// A transaction failed for a reason that can not be fixed by using another route
+    TransactionFailed(RequestFailure),
+    /// The deadline of the payment has passed before the full amount was delivered
+    TimedOut,
+}
+
+/// A failed `AppBuyer::pay_invoice()`.
+#[derive(Debug)]
+pub struct PayInvoiceFailure {
+    pub error: PayInvoiceError,
+    /// Commits of transactions that succeeded before the failure.
+    /// Those transactions are still pending: The funds they carry are only released once the
+    /// seller cancels the invoice (Or the invoice expires).
+    pub commits: Vec<Commit>,
+}
+
+impl From<BuyerError> for PayInvoiceError {
+    fn from(e: BuyerError) -> Self {
+        PayInvoiceError::BuyerError(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum RebalanceError {
+    BuyerError(BuyerError),
+    /// Credits can only be moved between two different friends
+    SameFriend,
+    RequestRoutesError,
+    NoSuitableRoute,
+    /// All suitable routes require more than the fees budget
+    MaxFeesExceeded,
+}
+
+impl From<BuyerError> for RebalanceError {
+    fn from(e: BuyerError) -> Self {
+        RebalanceError::BuyerError(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PayInvoiceConfig {
+    /// Maximum total amount of credits we are willing to pay as fees
+    pub max_fees: u128,
+    /// Maximum amount of times we request routes for the remaining amount
+    pub max_attempts: usize,
+    /// Preference for the routes requested from the index servers
+    pub search_mode: RouteSearchMode,
+    /// Amount of ticks after which the node stops creating transactions for the payment.
+    /// If None, the node's default payment timeout is used.
+    pub opt_deadline_ticks: Option<u64>,
+}
+
+/// Progress of an ongoing `AppBuyer::pay_invoice()`
+#[derive(Debug, Clone)]
+pub enum PayInvoiceEvent {
+    /// Requesting routes for the remaining amount of credits
+    RequestRoutes {
+        remaining: u128,
+        opt_exclude: Option<(PublicKey, PublicKey)>,
+    },
+    TransactionSuccess {
+        route: FriendsRoute,
+        dest_payment: u128,
+        fees: u128,
+    },
+    TransactionFailure {
+        route: FriendsRoute,
+        dest_payment: u128,
+        request_failure: RequestFailure,
+    },
+}
+
+/// Find the edge that caused a transaction to fail, so that it could be avoided in the next
+/// attempt. Returns None if using a different route will not help.
+fn failing_edge(
+    route: &FriendsRoute,
+    request_failure: &RequestFailure,
+) -> Option<(PublicKey, PublicKey)> {
+    let reporting_public_key = &request_failure.reporting_public_key;
+    let index = route.pk_to_index(reporting_public_key)?;
+    match request_failure.reason {
+        FailureReason::FriendNotReady
+        | FailureReason::RequestsClosed
+        | FailureReason::NoCapacity
+        | FailureReason::ChannelReset => {
+            let next_public_key = route.index_to_pk(index.checked_add(1)?)?;
+            Some((reporting_public_key.clone(), next_public_key.clone()))
+        }
+        FailureReason::InsufficientFees => {
+            let prev_public_key = route.index_to_pk(index.checked_sub(1)?)?;
+            Some((prev_public_key.clone(), reporting_public_key.clone()))
+        }
+        FailureReason::UnknownInvoice
+        | FailureReason::InvalidRoute
+        | FailureReason::RouteTooLong
+        | FailureReason::TimedOut
+        | FailureReason::Rejected => None,
+    }
+}
+
+/// Remove routes that go through any of the given edges.
+/// MultiRoutes that are left without any routes are removed too.
+fn filter_multi_routes(
+    multi_routes: Vec<MultiRoute>,
+    excluded_edges: &HashSet<(PublicKey, PublicKey)>,
+) -> Vec<MultiRoute> {
+    multi_routes
+        .into_iter()
+        .map(|multi_route| MultiRoute {
+            routes: multi_route
+                .routes
+                .into_iter()
+                .filter(|route_capacity_rate| {
+                    !route_capacity_rate
+                        .route
+                        .public_keys
+                        .windows(2)
+                        .any(|pair| excluded_edges.contains(&(pair[0].clone(), pair[1].clone())))
+                })
+                .collect(),
+        })
+        .filter(|multi_route| !multi_route.routes.is_empty())
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct AppBuyer<R = OffstSystemRandom> {
     sender: mpsc::Sender<AppToAppServer>,
@@ -57,18 +203,65 @@ where
         }
     }
 
+    /// Create a payment for an invoice.
+    /// If `opt_deadline_ticks` is not specified, the node's default payment timeout is used.
     pub async fn create_payment(
         &mut self,
         payment_id: PaymentId,
         invoice_id: InvoiceId,
         total_dest_payment: u128,
         dest_public_key: PublicKey,
+        opt_deadline_ticks: Option<u64>,
+    ) -> Result<(), BuyerError> {
+        await!(self.create_payment_inner(
+            payment_id,
+            invoice_id,
+            total_dest_payment,
+            dest_public_key,
+            None,
+            opt_deadline_ticks
+        ))
+    }
+
+    /// Create a payment that is not preceded by an invoice.
+    /// The destination must have opted in to accept spontaneous payments.
+    /// `dest_dh_public_key` is the DH public key advertised by the destination.
+    /// Returns the generated invoice id, to be used for the following transactions.
+    pub async fn create_spontaneous_payment(
+        &mut self,
+        payment_id: PaymentId,
+        total_dest_payment: u128,
+        dest_public_key: PublicKey,
+        dest_dh_public_key: DhPublicKey,
+    ) -> Result<InvoiceId, BuyerError> {
+        let invoice_id = InvoiceId::new(&self.rng);
+        await!(self.create_payment_inner(
+            payment_id,
+            invoice_id.clone(),
+            total_dest_payment,
+            dest_public_key,
+            Some(dest_dh_public_key),
+            None
+        ))?;
+        Ok(invoice_id)
+    }
+
+    async fn create_payment_inner(
+        &mut self,
+        payment_id: PaymentId,
+        invoice_id: InvoiceId,
+        total_dest_payment: u128,
+        dest_public_key: PublicKey,
+        opt_dest_dh_public_key: Option<DhPublicKey>,
+        opt_deadline_ticks: Option<u64>,
     ) -> Result<(), BuyerError> {
         let create_payment = CreatePayment {
             payment_id,
             invoice_id,
             total_dest_payment,
             dest_public_key,
+            opt_dest_dh_public_key,
+            opt_deadline_ticks,
         };
 
         let app_request_id = Uid::new(&self.rng);
@@ -125,7 +318,58 @@ where
             }
             match transaction_result.result {
                 RequestResult::Success(commit) => return Ok(commit),
-                RequestResult::Failure => return Err(BuyerError::NodeError),
+                RequestResult::Failure(request_failure) => {
+                    return Err(BuyerError::NodeError(request_failure))
+                }
+                RequestResult::UnknownFailure => return Err(BuyerError::UnknownNodeError),
+            }
+        }
+
+        // We lost connectivity before we got any response:
+        Err(BuyerError::NoResponse)
+    }
+
+    /// Pay ourselves along a circular route, moving credits from the first friend on the route
+    /// to the last friend on the route.
+    pub async fn create_rebalance(
+        &mut self,
+        payment_id: PaymentId,
+        invoice_id: InvoiceId,
+        request_id: Uid,
+        route: FriendsRoute,
+        dest_payment: u128,
+        fees: u128,
+    ) -> Result<Commit, BuyerError> {
+        let create_rebalance = CreateRebalance {
+            payment_id,
+            invoice_id,
+            request_id,
+            route,
+            dest_payment,
+            fees,
+        };
+        let app_request_id = Uid::new(&self.rng);
+        let to_app_server = AppToAppServer::new(
+            app_request_id,
+            AppRequest::CreateRebalance(create_rebalance),
+        );
+
+        let mut incoming_transaction_results = await!(self.transaction_results_mc.request_stream())
+            .map_err(|_| BuyerError::ConnectivityError)?;
+
+        await!(self.sender.send(to_app_server)).map_err(|_| BuyerError::ConnectivityError)?;
+
+        while let Some(transaction_result) = await!(incoming_transaction_results.next()) {
+            if transaction_result.request_id != request_id {
+                // This is not our request
+                continue;
+            }
+            match transaction_result.result {
+                RequestResult::Success(commit) => return Ok(commit),
+                RequestResult::Failure(request_failure) => {
+                    return Err(BuyerError::NodeError(request_failure))
+                }
+                RequestResult::UnknownFailure => return Err(BuyerError::UnknownNodeError),
             }
         }
 
@@ -193,3 +437,379 @@ where
         Err(BuyerError::NoResponse)
     }
 }
+
+impl<R> AppBuyer<R>
+where
+    R: CryptoRandom + Clone,
+{
+    /// Pay an invoice, possibly splitting the payment into a few transactions along multiple
+    /// routes. Failed transactions are retried along fresh routes, avoiding all the edges that
+    /// failed so far, until the full amount is delivered, the fees budget or attempts limit is
+    /// exhausted, or the deadline of the payment has passed.
+    ///
+    /// On success, returns a MultiCommit that should be handed to the seller.
+    /// On failure, the payment is closed and the commits obtained so far are returned.
+    pub async fn pay_invoice<'a, RR>(
+        &'a mut self,
+        app_routes: &'a mut AppRoutes<RR>,
+        local_public_key: PublicKey,
+        payment_id: PaymentId,
+        invoice_id: InvoiceId,
+        total_dest_payment: u128,
+        dest_public_key: PublicKey,
+        pay_invoice_config: PayInvoiceConfig,
+        opt_event_sender: Option<mpsc::Sender<PayInvoiceEvent>>,
+    ) -> Result<MultiCommit, PayInvoiceFailure>
+    where
+        RR: CryptoRandom,
+    {
+        await!(self.create_payment(
+            payment_id,
+            invoice_id.clone(),
+            total_dest_payment,
+            dest_public_key.clone(),
+            pay_invoice_config.opt_deadline_ticks
+        ))
+        .map_err(|e| PayInvoiceFailure {
+            error: e.into(),
+            commits: Vec::new(),
+        })?;
+
+        let mut commits = Vec::new();
+        let res = await!(self.pay_invoice_transactions(
+            app_routes,
+            local_public_key,
+            payment_id,
+            total_dest_payment,
+            dest_public_key,
+            pay_invoice_config,
+            opt_event_sender,
+            &mut commits,
+        ));
+
+        // No more transactions will be created for this payment:
+        let _ = await!(self.request_close_payment(payment_id));
+
+        match res {
+            Ok(()) => Ok(MultiCommit {
+                invoice_id,
+                total_dest_payment,
+                commits,
+            }),
+            Err(error) => Err(PayInvoiceFailure { error, commits }),
+        }
+    }
+
+    async fn pay_invoice_transactions<'a, RR>(
+        &'a mut self,
+        app_routes: &'a mut AppRoutes<RR>,
+        local_public_key: PublicKey,
+        payment_id: PaymentId,
+        total_dest_payment: u128,
+        dest_public_key: PublicKey,
+        pay_invoice_config: PayInvoiceConfig,
+        mut opt_event_sender: Option<mpsc::Sender<PayInvoiceEvent>>,
+        commits: &'a mut Vec<Commit>,
+    ) -> Result<(), PayInvoiceError>
+    where
+        RR: CryptoRandom,
+    {
+        let mut remaining = total_dest_payment;
+        let mut fees_left = pay_invoice_config.max_fees;
+        // Note that only one edge can be excluded when requesting routes.
+        // We always exclude the last edge that failed, and filter out routes that go through any
+        // of the other failed edges.
+        let mut opt_exclude = None;
+        let mut failed_edges = HashSet::new();
+        let mut attempts = 0usize;
+
+        while remaining > 0 {
+            if attempts >= pay_invoice_config.max_attempts {
+                return Err(PayInvoiceError::MaxAttemptsReached);
+            }
+            attempts = attempts.checked_add(1).unwrap();
+
+            if let Some(ref mut event_sender) = opt_event_sender {
+                let _ = await!(event_sender.send(PayInvoiceEvent::RequestRoutes {
+                    remaining,
+                    opt_exclude: opt_exclude.clone(),
+                }));
+            }
+
+            let multi_routes = await!(app_routes.request_routes(
+                remaining,
+                local_public_key.clone(),
+                dest_public_key.clone(),
+                opt_exclude.clone(),
+                pay_invoice_config.search_mode.clone()
+            ))
+            .map_err(|_| PayInvoiceError::RequestRoutesError)?;
+            let multi_routes = filter_multi_routes(multi_routes, &failed_edges);
+
+            let (multi_route_index, multi_route_choice, _round_fees) =
+                match choose_multi_route_max_fees(&multi_routes, remaining, fees_left) {
+                    Some(choice) => choice,
+                    None => {
+                        // Check if we could have paid without the fees budget:
+                        return Err(
+                            if choose_multi_route_max_fees(
+                                &multi_routes,
+                                remaining,
+                                u128::max_value(),
+                            )
+                            .is_some()
+                            {
+                                PayInvoiceError::MaxFeesExceeded
+                            } else {
+                                PayInvoiceError::NoSuitableRoute
+                            },
+                        );
+                    }
+                };
+            let multi_route = &multi_routes[multi_route_index];
+
+            let transactions = multi_route_choice
+                .into_iter()
+                .map(|(route_index, dest_payment)| {
+                    let route = &multi_route.routes[route_index];
+                    // choose_multi_route_max_fees() already made sure that fees can be
+                    // calculated without overflow:
+                    let fees = route.rate.calc_fee(dest_payment).unwrap();
+                    (route.route.clone(), dest_payment, fees)
+                })
+                .collect::<Vec<_>>();
+
+            let fut_list = transactions
+                .iter()
+                .map(|(route, dest_payment, fees)| {
+                    let mut c_self = self.clone();
+                    let request_id = Uid::new(&self.rng);
+                    // TODO: Possibly a more efficient way than Box::pin?
+                    Box::pin(async move {
+                        await!(c_self.create_transaction(
+                            payment_id,
+                            request_id,
+                            route.clone(),
+                            *dest_payment,
+                            *fees
+                        ))
+                    })
+                })
+                .collect::<Vec<_>>();
+            let results = await!(join_all(fut_list));
+
+            // Go over all the results before reporting an error, so that no commit is lost:
+            let mut opt_error = None;
+            for ((route, dest_payment, fees), res) in transactions.into_iter().zip(results) {
+                let event = match res {
+                    Ok(commit) => {
+                        commits.push(commit);
+                        remaining = remaining.checked_sub(dest_payment).unwrap();
+                        fees_left = fees_left.checked_sub(fees).unwrap();
+                        PayInvoiceEvent::TransactionSuccess {
+                            route,
+                            dest_payment,
+                            fees,
+                        }
+                    }
+                    Err(BuyerError::NodeError(request_failure)) => {
+                        if request_failure.reason == FailureReason::TimedOut {
+                            opt_error = Some(PayInvoiceError::TimedOut);
+                        } else if let Some(edge) = failing_edge(&route, &request_failure) {
+                            failed_edges.insert(edge.clone());
+                            opt_exclude = Some(edge);
+                        } else {
+                            opt_error =
+                                Some(PayInvoiceError::TransactionFailed(request_failure.clone()));
+                        }
+                        PayInvoiceEvent::TransactionFailure {
+                            route,
+                            dest_payment,
+                            request_failure,
+                        }
+                    }
+                    Err(e) => {
+                        opt_error = Some(e.into());
+                        continue;
+                    }
+                };
+                if let Some(ref mut event_sender) = opt_event_sender {
+                    let _ = await!(event_sender.send(event));
+                }
+            }
+            if let Some(error) = opt_error {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Move `amount` credits from our relationship with `out_public_key` to our relationship with
+    /// `in_public_key`. This is done by paying ourselves along a cycle that leaves through
+    /// `out_public_key` and comes back through `in_public_key`.
+    ///
+    /// At most `max_fees` credits are paid as fees. Fees that were not used along the cycle are
+    /// returned to us together with the payment.
+    ///
+    /// On success, returns the cycle that was used. The payment should later be closed using
+    /// `request_close_payment()`, like any other payment.
+    pub async fn rebalance<'a, RR>(
+        &'a mut self,
+        app_routes: &'a mut AppRoutes<RR>,
+        local_public_key: PublicKey,
+        payment_id: PaymentId,
+        out_public_key: PublicKey,
+        in_public_key: PublicKey,
+        amount: u128,
+        max_fees: u128,
+        search_mode: RouteSearchMode,
+    ) -> Result<FriendsRoute, RebalanceError>
+    where
+        RR: CryptoRandom,
+    {
+        if out_public_key == in_public_key {
+            return Err(RebalanceError::SameFriend);
+        }
+
+        // Excluding the direct edge to the in friend forces the index servers to find a non
+        // trivial route, which we then close into a cycle:
+        let multi_routes = await!(app_routes.request_routes(
+            amount,
+            local_public_key.clone(),
+            in_public_key.clone(),
+            Some((local_public_key.clone(), in_public_key.clone())),
+            search_mode
+        ))
+        .map_err(|_| RebalanceError::RequestRoutesError)?;
+
+        // Note that the rate reported by the index servers does not include the fees taken by
+        // the in friend for forwarding the payment back to us.
+        let (route, route_fees) = multi_routes
+            .iter()
+            .flat_map(|multi_route| multi_route.routes.iter())
+            .filter(|route_capacity_rate| {
+                route_capacity_rate.capacity >= amount
+                    && route_capacity_rate.route.index_to_pk(1) == Some(&out_public_key)
+            })
+            .filter_map(|route_capacity_rate| {
+                let fees = route_capacity_rate.rate.calc_fee(amount)?;
+                Some((&route_capacity_rate.route, fees))
+            })
+            .min_by_key(|(_route, fees)| *fees)
+            .ok_or(RebalanceError::NoSuitableRoute)?;
+
+        if route_fees > max_fees {
+            return Err(RebalanceError::MaxFeesExceeded);
+        }
+
+        let mut public_keys = route.public_keys.clone();
+        public_keys.push(local_public_key);
+        let cycle = FriendsRoute { public_keys };
+
+        await!(self.create_rebalance(
+            payment_id,
+            InvoiceId::new(&self.rng),
+            Uid::new(&self.rng),
+            cycle.clone(),
+            amount,
+            max_fees
+        ))?;
+
+        Ok(cycle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crypto::identity::PUBLIC_KEY_LEN;
+
+    use proto::funder::messages::Rate;
+    use proto::index_server::messages::RouteCapacityRate;
+
+    /// A helper function to create a test public key
+    fn pk(i: u8) -> PublicKey {
+        PublicKey::from(&[i; PUBLIC_KEY_LEN])
+    }
+
+    #[test]
+    fn test_failing_edge() {
+        let route = FriendsRoute {
+            public_keys: vec![pk(0), pk(1), pk(2), pk(3)],
+        };
+
+        let request_failure = RequestFailure {
+            reporting_public_key: pk(1),
+            reason: FailureReason::NoCapacity,
+        };
+        assert_eq!(failing_edge(&route, &request_failure), Some((pk(1), pk(2))));
+
+        let request_failure = RequestFailure {
+            reporting_public_key: pk(2),
+            reason: FailureReason::InsufficientFees,
+        };
+        assert_eq!(failing_edge(&route, &request_failure), Some((pk(1), pk(2))));
+
+        // The destination can not point at a next node:
+        let request_failure = RequestFailure {
+            reporting_public_key: pk(3),
+            reason: FailureReason::FriendNotReady,
+        };
+        assert_eq!(failing_edge(&route, &request_failure), None);
+
+        // Using another route will not help:
+        let request_failure = RequestFailure {
+            reporting_public_key: pk(3),
+            reason: FailureReason::UnknownInvoice,
+        };
+        assert_eq!(failing_edge(&route, &request_failure), None);
+
+        // Reporter is not on the route:
+        let request_failure = RequestFailure {
+            reporting_public_key: pk(4),
+            reason: FailureReason::NoCapacity,
+        };
+        assert_eq!(failing_edge(&route, &request_failure), None);
+    }
+
+    #[test]
+    fn test_filter_multi_routes() {
+        let route_capacity_rate = |public_keys: Vec<PublicKey>| RouteCapacityRate {
+            route: FriendsRoute { public_keys },
+            capacity: 100,
+            rate: Rate { mul: 0, add: 1 },
+        };
+
+        let multi_routes = vec![
+            MultiRoute {
+                routes: vec![
+                    route_capacity_rate(vec![pk(0), pk(1), pk(3)]),
+                    route_capacity_rate(vec![pk(0), pk(2), pk(3)]),
+                ],
+            },
+            MultiRoute {
+                routes: vec![route_capacity_rate(vec![pk(0), pk(2), pk(1), pk(3)])],
+            },
+            MultiRoute {
+                routes: vec![route_capacity_rate(vec![pk(0), pk(4), pk(3)])],
+            },
+        ];
+
+        let mut excluded_edges = HashSet::new();
+        excluded_edges.insert((pk(0), pk(1)));
+        excluded_edges.insert((pk(1), pk(3)));
+
+        let multi_routes = filter_multi_routes(multi_routes, &excluded_edges);
+        assert_eq!(multi_routes.len(), 2);
+        assert_eq!(multi_routes[0].routes.len(), 1);
+        assert_eq!(
+            multi_routes[0].routes[0].route.public_keys,
+            vec![pk(0), pk(2), pk(3)]
+        );
+        assert_eq!(
+            multi_routes[1].routes[0].route.public_keys,
+            vec![pk(0), pk(4), pk(3)]
+        );
+    }
+}
@@ -74,6 +74,17 @@ where
         await!(self.send_request(AppRequest::RemoveRelay(relay_public_key)))
     }
 
+    /// Accept (or stop accepting) payments that were not preceded by an invoice.
+    /// Spontaneous payments are collected as soon as they arrive.
+    pub async fn set_accept_spontaneous_payments(
+        &mut self,
+        accept_spontaneous_payments: bool,
+    ) -> Result<(), AppConfigError> {
+        await!(self.send_request(AppRequest::SetAcceptSpontaneousPayments(
+            accept_spontaneous_payments
+        )))
+    }
+
     pub async fn add_friend(
         &mut self,
         friend_public_key: PublicKey,
@@ -1,5 +1,6 @@
 pub mod buyer;
 pub mod config;
+pub mod multi_route_util;
 pub mod report;
 pub mod routes;
 pub mod seller;
@@ -0,0 +1,353 @@
+use num_bigint::BigUint;
+// use num_traits::identities::Zero;
+use num_traits::cast::ToPrimitive;
+use num_traits::ops::checked::CheckedSub;
+
+use proto::index_server::messages::MultiRoute;
+
+pub type MultiRouteChoice = Vec<(usize, u128)>;
+
+/// Attempt to fill the given amount as much as possible, trying not to saturate any route.
+/// Returns `Some((i, added_credits(i)))` if in index `i` we can already fill `amount_left`, or
+/// `None` if we can never fill the wanted amount.
+fn fill_amount(amount: u128, sorted_routes: &[(Option<usize>, u128)]) -> Option<(usize, BigUint)> {
+    let mut amount_left = BigUint::from(amount);
+    // Chosen route indices (Indices with respect to the original `routes`)
+    for i in 1..sorted_routes.len() {
+        let prev_i = i.checked_sub(1).unwrap();
+        // sorted_routes is sorted, therefore we can be sure that the next
+        // checked_sub(...).unwrap() will not panic:
+        let added_credits: BigUint = BigUint::from(
+            sorted_routes[prev_i]
+                .1
+                .checked_sub(sorted_routes[i].1)
+                .unwrap(),
+        ) * i;
+
+        if let Some(new_amount_left) = amount_left.checked_sub(&added_credits) {
+            amount_left = new_amount_left;
+        } else {
+            return Some((i, amount_left));
+        }
+    }
+    None
+}
+
+/// Find a safe choice for how much credits to push through each route in a MultiRoute.
+/// Returns a vector representing how many credits to push through every chosen route (if successful).
+/// For example: (5usize, 100u128) means: push 100 credits through the route that was given in
+/// index 5.
+fn safe_multi_route_amounts(multi_route: &MultiRoute, amount: u128) -> Option<MultiRouteChoice> {
+    let routes = &multi_route.routes;
+    let sorted_routes = {
+        let mut sorted_routes: Vec<_> = routes
+            .iter()
+            .enumerate()
+            .map(|(j, route)| (Some(j), route.rate.max_payable(route.capacity)))
+            .collect();
+
+        // Reverse sort: (Largest is first)
+        sorted_routes.sort_by(|(_, a), (_, b)| b.cmp(a));
+        // Add a zero entry in the end:
+        sorted_routes.push((None, 0u128));
+        sorted_routes
+    };
+
+    let (num_routes, amount_left) = fill_amount(amount, &sorted_routes[..])?;
+    // Add this amount to the first `num_routes` biggest entries:
+    let div_extra = (&amount_left / num_routes).to_u128().unwrap();
+    // Add `1` to this amount of elements:
+    let mod_extra = (&amount_left % num_routes).to_u128().unwrap();
+
+    let mut chosen_routes = Vec::new();
+    let mut accum_credits = 0u128;
+    for i in (0..num_routes).rev() {
+        // Add the extra part to accum_credits
+        let num_credits = accum_credits.checked_add(div_extra).unwrap();
+        let num_credits = if (i as u128) < mod_extra {
+            num_credits.checked_add(1).unwrap()
+        } else {
+            num_credits
+        };
+        chosen_routes.push((sorted_routes[i].0.unwrap(), num_credits));
+
+        // TODO: How to do this part more elegantly?
+        if let Some(prev_i) = i.checked_sub(1) {
+            let diff = sorted_routes[prev_i]
+                .1
+                .checked_sub(sorted_routes[i].1)
+                .unwrap();
+            accum_credits = accum_credits.checked_add(diff).unwrap();
+        }
+    }
+    Some(chosen_routes)
+}
+
+/// Choose a route for pushing `amount` credits
+pub fn choose_multi_route(
+    multi_routes: &[MultiRoute],
+    amount: u128,
+) -> Option<(usize, MultiRouteChoice)> {
+    // We naively select the first multi-route we find suitable:
+    // TODO: Possibly improve this later:
+    for (i, multi_route) in multi_routes.iter().enumerate() {
+        if let Some(multi_route_choice) = safe_multi_route_amounts(multi_route, amount) {
+            return Some((i, multi_route_choice));
+        }
+    }
+    None
+}
+
+/// Calculate the total amount of fees paid for pushing credits along a MultiRoute according to
+/// the given choice. Returns None on overflow.
+pub fn multi_route_fees(
+    multi_route: &MultiRoute,
+    multi_route_choice: &[(usize, u128)],
+) -> Option<u128> {
+    let mut total_fees = 0u128;
+    for (route_index, dest_payment) in multi_route_choice {
+        let fees = multi_route.routes[*route_index]
+            .rate
+            .calc_fee(*dest_payment)?;
+        total_fees = total_fees.checked_add(fees)?;
+    }
+    Some(total_fees)
+}
+
+/// Find a cheap choice for how much credits to push through each route in a MultiRoute.
+/// Routes are filled one by one, up to their maximum payable amount. At every step we pick the
+/// route with the lowest fee per credit, for the amount of credits it would carry.
+/// Note that this choice might saturate some of the routes.
+fn cheap_multi_route_amounts(multi_route: &MultiRoute, amount: u128) -> Option<MultiRouteChoice> {
+    let mut route_indices: Vec<_> = (0..multi_route.routes.len()).collect();
+
+    let mut chosen_routes = Vec::new();
+    let mut amount_left = amount;
+    while amount_left > 0 {
+        // (Position in route_indices, num_credits, fees)
+        let mut opt_best: Option<(usize, u128, u128)> = None;
+        for (pos, &j) in route_indices.iter().enumerate() {
+            let route = &multi_route.routes[j];
+            let num_credits = std::cmp::min(route.rate.max_payable(route.capacity), amount_left);
+            if num_credits == 0 {
+                continue;
+            }
+            let fees = match route.rate.calc_fee(num_credits) {
+                Some(fees) => fees,
+                None => continue,
+            };
+            // Compare fees / num_credits without losing precision:
+            let is_cheaper = opt_best.map_or(true, |(_, best_credits, best_fees)| {
+                BigUint::from(fees) * BigUint::from(best_credits)
+                    < BigUint::from(best_fees) * BigUint::from(num_credits)
+            });
+            if is_cheaper {
+                opt_best = Some((pos, num_credits, fees));
+            }
+        }
+
+        let (pos, num_credits, _fees) = opt_best?;
+        chosen_routes.push((route_indices.remove(pos), num_credits));
+        amount_left = amount_left.checked_sub(num_credits).unwrap();
+    }
+    Some(chosen_routes)
+}
+
+/// Choose a route for pushing `amount` credits, paying at most `max_fees` credits in fees.
+/// Returns the index of the chosen multi route, the amount of credits to push through every
+/// route, and the expected total fees.
+///
+/// Choices that do not saturate any route are preferred. If none of those fits the fees budget,
+/// we fall back to the cheapest choice found.
+pub fn choose_multi_route_max_fees(
+    multi_routes: &[MultiRoute],
+    amount: u128,
+    max_fees: u128,
+) -> Option<(usize, MultiRouteChoice, u128)> {
+    let mut opt_best_safe: Option<(usize, MultiRouteChoice, u128)> = None;
+    let mut opt_best_cheap: Option<(usize, MultiRouteChoice, u128)> = None;
+
+    for (i, multi_route) in multi_routes.iter().enumerate() {
+        if let Some(multi_route_choice) = safe_multi_route_amounts(multi_route, amount) {
+            if let Some(fees) = multi_route_fees(multi_route, &multi_route_choice) {
+                if fees <= max_fees && opt_best_safe.as_ref().map_or(true, |best| fees < best.2) {
+                    opt_best_safe = Some((i, multi_route_choice, fees));
+                }
+            }
+        }
+        if let Some(multi_route_choice) = cheap_multi_route_amounts(multi_route, amount) {
+            if let Some(fees) = multi_route_fees(multi_route, &multi_route_choice) {
+                if fees <= max_fees && opt_best_cheap.as_ref().map_or(true, |best| fees < best.2) {
+                    opt_best_cheap = Some((i, multi_route_choice, fees));
+                }
+            }
+        }
+    }
+
+    opt_best_safe.or(opt_best_cheap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crypto::identity::{PublicKey, PUBLIC_KEY_LEN};
+    use proto::funder::messages::{FriendsRoute, Rate};
+    use proto::index_server::messages::RouteCapacityRate;
+
+    /// A helper function to create a test public key
+    fn pk(i: u8) -> PublicKey {
+        PublicKey::from(&[i; PUBLIC_KEY_LEN])
+    }
+
+    #[test]
+    fn test_safe_multi_route_amounts() {
+        let mut multi_route = MultiRoute { routes: vec![] };
+        multi_route.routes.push(RouteCapacityRate {
+            route: FriendsRoute {
+                public_keys: vec![pk(0), pk(1), pk(2), pk(3), pk(4)],
+            },
+            capacity: 100u128,
+            rate: Rate {
+                add: 1,
+                mul: 0x12345678,
+            },
+        });
+
+        multi_route.routes.push(RouteCapacityRate {
+            route: FriendsRoute {
+                public_keys: vec![pk(0), pk(5), pk(6), pk(4)],
+            },
+            capacity: 200u128,
+            rate: Rate {
+                add: 5,
+                mul: 0x00100000,
+            },
+        });
+
+        multi_route.routes.push(RouteCapacityRate {
+            route: FriendsRoute {
+                public_keys: vec![pk(0), pk(7), pk(8), pk(9), pk(4)],
+            },
+            capacity: 300u128,
+            rate: Rate {
+                add: 20,
+                mul: 0x20000000,
+            },
+        });
+        assert!(safe_multi_route_amounts(&multi_route, 601).is_none());
+
+        let multi_route_choice = safe_multi_route_amounts(&multi_route, 300).unwrap();
+
+        let mut total_credits = 0u128;
+        for route_choice in &multi_route_choice {
+            let route = &multi_route.routes[route_choice.0];
+            // Make sure that we don't take too much:
+            assert!(route_choice.1 < route.rate.max_payable(route.capacity));
+            total_credits = total_credits.checked_add(route_choice.1).unwrap();
+        }
+        assert_eq!(total_credits, 300);
+    }
+
+    #[test]
+    fn test_choose_multi_route_max_fees() {
+        let cheap_multi_route = MultiRoute {
+            routes: vec![
+                RouteCapacityRate {
+                    route: FriendsRoute {
+                        public_keys: vec![pk(0), pk(1), pk(4)],
+                    },
+                    capacity: 100u128,
+                    rate: Rate { add: 1, mul: 0 },
+                },
+                RouteCapacityRate {
+                    route: FriendsRoute {
+                        public_keys: vec![pk(0), pk(2), pk(4)],
+                    },
+                    capacity: 100u128,
+                    rate: Rate {
+                        add: 1,
+                        mul: 0x80000000,
+                    },
+                },
+            ],
+        };
+
+        let expensive_multi_route = MultiRoute {
+            routes: vec![RouteCapacityRate {
+                route: FriendsRoute {
+                    public_keys: vec![pk(0), pk(3), pk(4)],
+                },
+                capacity: 1000u128,
+                rate: Rate { add: 50, mul: 0 },
+            }],
+        };
+
+        let multi_routes = vec![expensive_multi_route, cheap_multi_route];
+
+        // Both multi routes can carry the payment without being saturated.
+        // The cheaper one should be chosen:
+        let (multi_route_index, multi_route_choice, fees) =
+            choose_multi_route_max_fees(&multi_routes, 60, u128::max_value()).unwrap();
+        assert_eq!(multi_route_index, 1);
+        assert_eq!(fees, 8);
+        assert_eq!(
+            fees,
+            multi_route_fees(&multi_routes[1], &multi_route_choice).unwrap()
+        );
+        let total_credits: u128 = multi_route_choice.iter().map(|(_, credits)| credits).sum();
+        assert_eq!(total_credits, 60);
+
+        // Safe choice along the cheap multi route costs 1 + (1 + 6) = 8.
+        // Saturating the first route costs only 1:
+        let (multi_route_index, multi_route_choice, fees) =
+            choose_multi_route_max_fees(&multi_routes, 60, 5).unwrap();
+        assert_eq!(multi_route_index, 1);
+        assert_eq!(multi_route_choice, vec![(0, 60)]);
+        assert_eq!(fees, 1);
+
+        // Fees budget is too small:
+        assert!(choose_multi_route_max_fees(&multi_routes, 60, 0).is_none());
+
+        // Not enough capacity:
+        assert!(choose_multi_route_max_fees(&multi_routes, 2000, u128::max_value()).is_none());
+    }
+
+    #[test]
+    fn test_cheap_multi_route_amounts() {
+        let multi_route = MultiRoute {
+            routes: vec![
+                RouteCapacityRate {
+                    route: FriendsRoute {
+                        public_keys: vec![pk(0), pk(1), pk(3)],
+                    },
+                    capacity: 2000u128,
+                    rate: Rate { add: 1000, mul: 0 },
+                },
+                RouteCapacityRate {
+                    route: FriendsRoute {
+                        public_keys: vec![pk(0), pk(2), pk(3)],
+                    },
+                    capacity: 2000u128,
+                    rate: Rate { add: 0, mul: 1 },
+                },
+            ],
+        };
+
+        // For small amounts, the tiny commission is cheaper than the flat rate:
+        let multi_route_choice = cheap_multi_route_amounts(&multi_route, 100).unwrap();
+        assert_eq!(multi_route_choice, vec![(1, 100)]);
+        assert_eq!(
+            multi_route_fees(&multi_route, &multi_route_choice).unwrap(),
+            0
+        );
+
+        // The second route is filled first, the rest goes through the first route.
+        // (Maximum payable amounts are 1000 for the first route and 1999 for the second route):
+        let multi_route_choice = cheap_multi_route_amounts(&multi_route, 2500).unwrap();
+        assert_eq!(multi_route_choice, vec![(1, 1999), (0, 501)]);
+
+        // Not enough capacity:
+        assert!(cheap_multi_route_amounts(&multi_route, 3000).is_none());
+    }
+}
@@ -78,6 +78,16 @@ where
             .spawn(routes_fut)
             .map_err(|_| NodeConnectionError::SpawnError)?;
 
+        let (mut incoming_liquidity_sender, incoming_liquidity) = mpsc::channel(0);
+        let (requests_sender, incoming_requests) = mpsc::channel(0);
+        let liquidity_mc = MultiConsumerClient::new(requests_sender);
+        let liquidity_fut = multi_consumer_service(incoming_liquidity, incoming_requests)
+            .map_err(|e| error!("Liquidity multi_consumer_service() error: {:?}", e))
+            .map(|_| ());
+        spawner
+            .spawn(liquidity_fut)
+            .map_err(|_| NodeConnectionError::SpawnError)?;
+
         let (mut incoming_transaction_results_sender, incoming_transaction_results) =
             mpsc::channel(0);
         let (requests_sender, incoming_requests) = mpsc::channel(0);
@@ -102,6 +112,40 @@ where
             .spawn(response_close_payments_fut)
             .map_err(|_| NodeConnectionError::SpawnError)?;
 
+        let (mut incoming_response_invoice_statuses_sender, incoming_response_invoice_statuses) =
+            mpsc::channel(0);
+        let (requests_sender, incoming_requests) = mpsc::channel(0);
+        let response_invoice_statuses_mc = MultiConsumerClient::new(requests_sender);
+        let response_invoice_statuses_fut =
+            multi_consumer_service(incoming_response_invoice_statuses, incoming_requests)
+                .map_err(|e| error!("Seller multi_consumer_service() error: {:?}", e))
+                .map(|_| ());
+        spawner
+            .spawn(response_invoice_statuses_fut)
+            .map_err(|_| NodeConnectionError::SpawnError)?;
+
+        let (mut incoming_response_sign_invoices_sender, incoming_response_sign_invoices) =
+            mpsc::channel(0);
+        let (requests_sender, incoming_requests) = mpsc::channel(0);
+        let response_sign_invoices_mc = MultiConsumerClient::new(requests_sender);
+        let response_sign_invoices_fut =
+            multi_consumer_service(incoming_response_sign_invoices, incoming_requests)
+                .map_err(|e| error!("Seller multi_consumer_service() error: {:?}", e))
+                .map(|_| ());
+        spawner
+            .spawn(response_sign_invoices_fut)
+            .map_err(|_| NodeConnectionError::SpawnError)?;
+
+        let (mut incoming_invoices_paid_sender, incoming_invoices_paid) = mpsc::channel(0);
+        let (requests_sender, incoming_requests) = mpsc::channel(0);
+        let invoices_paid_mc = MultiConsumerClient::new(requests_sender);
+        let invoices_paid_fut = multi_consumer_service(incoming_invoices_paid, incoming_requests)
+            .map_err(|e| error!("Seller multi_consumer_service() error: {:?}", e))
+            .map(|_| ());
+        spawner
+            .spawn(invoices_paid_fut)
+            .map_err(|_| NodeConnectionError::SpawnError)?;
+
         let (mut incoming_done_app_requests_sender, incoming_done_app_requests) = mpsc::channel(0);
         let (requests_sender, incoming_requests) = mpsc::channel(0);
         let done_app_requests_mc = MultiConsumerClient::new(requests_sender);
@@ -126,6 +170,18 @@ where
                             let _ = await!(incoming_response_close_payments_sender
                                 .send(response_close_payment));
                         }
+                        AppServerToApp::ResponseInvoiceStatus(response_invoice_status) => {
+                            let _ = await!(incoming_response_invoice_statuses_sender
+                                .send(response_invoice_status));
+                        }
+                        AppServerToApp::ResponseSignInvoice(response_sign_invoice) => {
+                            let _ =
+                                await!(incoming_response_sign_invoices_sender
+                                    .send(response_sign_invoice));
+                        }
+                        AppServerToApp::InvoicePaid(invoice_paid) => {
+                            let _ = await!(incoming_invoices_paid_sender.send(invoice_paid));
+                        }
                         AppServerToApp::Report(_node_report) => {
                             // TODO: Maybe somehow redesign the type AppServerToApp
                             // so that we don't have this edge case?
@@ -144,6 +200,10 @@ where
                         AppServerToApp::ResponseRoutes(client_response_routes) => {
                             let _ = await!(incoming_routes_sender.send(client_response_routes));
                         }
+                        AppServerToApp::ResponseLiquidity(client_response_liquidity) => {
+                            let _ =
+                                await!(incoming_liquidity_sender.send(client_response_liquidity));
+                        }
                     }
                 }
             })
@@ -163,6 +223,7 @@ where
             Some(AppRoutes::new(
                 sender.clone(),
                 routes_mc.clone(),
+                liquidity_mc.clone(),
                 rng.clone(),
             ))
         } else {
@@ -184,6 +245,9 @@ where
         let opt_seller = if app_permissions.seller {
             Some(AppSeller::new(
                 sender.clone(),
+                response_invoice_statuses_mc.clone(),
+                response_sign_invoices_mc.clone(),
+                invoices_paid_mc.clone(),
                 done_app_requests_mc.clone(),
                 rng.clone(),
             ))
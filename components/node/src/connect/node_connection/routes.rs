@@ -8,8 +8,12 @@ use crypto::identity::PublicKey;
 use crypto::uid::Uid;
 
 use proto::app_server::messages::{AppRequest, AppToAppServer};
-use proto::index_client::messages::{ClientResponseRoutes, ResponseRoutesResult};
-use proto::index_server::messages::{MultiRoute, RequestRoutes};
+use proto::index_client::messages::{
+    ClientResponseLiquidity, ClientResponseRoutes, ResponseLiquidityResult, ResponseRoutesResult,
+};
+use proto::index_server::messages::{
+    Liquidity, MultiRoute, RequestLiquidity, RequestRoutes, RouteSearchMode,
+};
 
 #[derive(Debug)]
 pub struct AppRoutesError;
@@ -18,6 +22,7 @@ pub struct AppRoutesError;
 pub struct AppRoutes<R = OffstSystemRandom> {
     sender: mpsc::Sender<AppToAppServer>,
     routes_mc: MultiConsumerClient<ClientResponseRoutes>,
+    liquidity_mc: MultiConsumerClient<ClientResponseLiquidity>,
     rng: R,
 }
 
@@ -55,11 +60,13 @@ where
     pub(super) fn new(
         sender: mpsc::Sender<AppToAppServer>,
         routes_mc: MultiConsumerClient<ClientResponseRoutes>,
+        liquidity_mc: MultiConsumerClient<ClientResponseLiquidity>,
         rng: R,
     ) -> Self {
         AppRoutes {
             sender,
             routes_mc,
+            liquidity_mc,
             rng,
         }
     }
@@ -70,6 +77,7 @@ where
         source: PublicKey,
         destination: PublicKey,
         opt_exclude: Option<(PublicKey, PublicKey)>,
+        search_mode: RouteSearchMode,
     ) -> Result<Vec<MultiRoute>, AppRoutesError> {
         let request_routes_id = Uid::new(&self.rng);
         let request_routes = RequestRoutes {
@@ -78,6 +86,7 @@ where
             source,
             destination,
             opt_exclude,
+            search_mode,
         };
 
         let app_request = AppRequest::RequestRoutes(request_routes);
@@ -102,4 +111,39 @@ where
         }
         Err(AppRoutesError)
     }
+
+    /// Find out how many credits `destination` can currently receive, from every direct neighbor
+    /// and from every hop distance in the network around it.
+    pub async fn request_liquidity(
+        &mut self,
+        destination: PublicKey,
+    ) -> Result<Liquidity, AppRoutesError> {
+        let request_liquidity_id = Uid::new(&self.rng);
+        let request_liquidity = RequestLiquidity {
+            request_id: request_liquidity_id,
+            destination,
+        };
+
+        let app_request = AppRequest::RequestLiquidity(request_liquidity);
+        let to_app_server = AppToAppServer::new(Uid::new(&self.rng), app_request);
+
+        // Start listening for incoming response liquidity messages:
+        let mut incoming_liquidity =
+            await!(self.liquidity_mc.request_stream()).map_err(|_| AppRoutesError)?;
+
+        // Send our request to offst node:
+        await!(self.sender.send(to_app_server)).map_err(|_| AppRoutesError)?;
+
+        while let Some(client_response_liquidity) = await!(incoming_liquidity.next()) {
+            if client_response_liquidity.request_id != request_liquidity_id {
+                // This is not our request
+                continue;
+            }
+            match client_response_liquidity.result {
+                ResponseLiquidityResult::Success(liquidity) => return Ok(liquidity),
+                ResponseLiquidityResult::Failure => return Err(AppRoutesError),
+            }
+        }
+        Err(AppRoutesError)
+    }
 }
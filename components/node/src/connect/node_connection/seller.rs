@@ -3,11 +3,15 @@ use futures::channel::mpsc;
 use futures::{SinkExt, StreamExt};
 
 use crypto::crypto_rand::{CryptoRandom, OffstSystemRandom};
+use crypto::identity::Signature;
 use crypto::invoice_id::InvoiceId;
 use crypto::uid::Uid;
 
 use proto::app_server::messages::{AppRequest, AppToAppServer};
-use proto::funder::messages::{AddInvoice, MultiCommit};
+use proto::funder::messages::{
+    AddInvoice, InvoiceDetails, InvoicePaid, InvoiceStatus, MultiCommit, ResponseInvoiceStatus,
+    ResponseSignInvoice,
+};
 
 // TODO: Different in naming convention from AppConfigError and AppRoutesError:
 #[derive(Debug)]
@@ -26,6 +30,9 @@ pub enum SellerError {
 #[derive(Clone)]
 pub struct AppSeller<R = OffstSystemRandom> {
     sender: mpsc::Sender<AppToAppServer>,
+    response_invoice_statuses_mc: MultiConsumerClient<ResponseInvoiceStatus>,
+    response_sign_invoices_mc: MultiConsumerClient<ResponseSignInvoice>,
+    invoices_paid_mc: MultiConsumerClient<InvoicePaid>,
     done_app_requests_mc: MultiConsumerClient<Uid>,
     rng: R,
 }
@@ -36,25 +43,35 @@ where
 {
     pub(super) fn new(
         sender: mpsc::Sender<AppToAppServer>,
+        response_invoice_statuses_mc: MultiConsumerClient<ResponseInvoiceStatus>,
+        response_sign_invoices_mc: MultiConsumerClient<ResponseSignInvoice>,
+        invoices_paid_mc: MultiConsumerClient<InvoicePaid>,
         done_app_requests_mc: MultiConsumerClient<Uid>,
         rng: R,
     ) -> Self {
         AppSeller {
             sender,
+            response_invoice_statuses_mc,
+            response_sign_invoices_mc,
+            invoices_paid_mc,
             done_app_requests_mc,
             rng,
         }
     }
 
+    /// Open a new invoice. If `opt_expiry_ticks` is given, the node cancels the invoice
+    /// automatically after this amount of timer ticks.
     pub async fn add_invoice(
         &mut self,
         invoice_id: InvoiceId,
         total_dest_payment: u128,
+        opt_expiry_ticks: Option<u64>,
     ) -> Result<(), SellerError> {
         let app_request_id = Uid::new(&self.rng);
         let add_invoice = AddInvoice {
             invoice_id,
             total_dest_payment,
+            opt_expiry_ticks,
         };
         let to_app_server =
             AppToAppServer::new(app_request_id, AppRequest::AddInvoice(add_invoice));
@@ -119,4 +136,94 @@ where
         // We lost connectivity before we got any response:
         Err(SellerError::NoResponse)
     }
+
+    /// Get the current status of an invoice: Which transactions were received so far,
+    /// and how long until the invoice expires.
+    pub async fn request_invoice_status(
+        &mut self,
+        invoice_id: InvoiceId,
+    ) -> Result<InvoiceStatus, SellerError> {
+        let app_request_id = Uid::new(&self.rng);
+        let to_app_server = AppToAppServer::new(
+            app_request_id,
+            AppRequest::RequestInvoiceStatus(invoice_id.clone()),
+        );
+
+        let mut incoming_response_invoice_statuses =
+            await!(self.response_invoice_statuses_mc.request_stream())
+                .map_err(|_| SellerError::ConnectivityError)?;
+
+        await!(self.sender.send(to_app_server)).map_err(|_| SellerError::ConnectivityError)?;
+
+        while let Some(response_invoice_status) = await!(incoming_response_invoice_statuses.next())
+        {
+            if response_invoice_status.invoice_id != invoice_id {
+                // This is not our request
+                continue;
+            }
+            return Ok(response_invoice_status.status);
+        }
+
+        // We lost connectivity before we got any response:
+        Err(SellerError::NoResponse)
+    }
+
+    /// Ask the node to sign the details of an open invoice, using the node's identity.
+    /// The signature allows a buyer to verify that the invoice was issued by this node.
+    /// The node refuses (NodeError) if there is no open invoice with matching
+    /// invoice_id and total_dest_payment.
+    pub async fn sign_invoice(
+        &mut self,
+        invoice_details: InvoiceDetails,
+    ) -> Result<Signature, SellerError> {
+        let invoice_id = invoice_details.invoice_id.clone();
+        let app_request_id = Uid::new(&self.rng);
+        let to_app_server = AppToAppServer::new(
+            app_request_id,
+            AppRequest::RequestSignInvoice(invoice_details),
+        );
+
+        let mut incoming_response_sign_invoices =
+            await!(self.response_sign_invoices_mc.request_stream())
+                .map_err(|_| SellerError::ConnectivityError)?;
+
+        await!(self.sender.send(to_app_server)).map_err(|_| SellerError::ConnectivityError)?;
+
+        while let Some(response_sign_invoice) = await!(incoming_response_sign_invoices.next()) {
+            if response_sign_invoice.invoice_id != invoice_id {
+                // This is not our request
+                continue;
+            }
+            return response_sign_invoice
+                .opt_signature
+                .ok_or(SellerError::NodeError);
+        }
+
+        // We lost connectivity before we got any response:
+        Err(SellerError::NoResponse)
+    }
+
+    /// Wait until the incoming transactions for an invoice reach its total_dest_payment.
+    /// The returned transactions can then be used to ask the buyer for a MultiCommit.
+    ///
+    /// Only notifications sent after this method was called are considered. Use
+    /// `request_invoice_status()` to find out about invoices that might have been paid earlier.
+    pub async fn wait_invoice_paid(
+        &mut self,
+        invoice_id: InvoiceId,
+    ) -> Result<InvoicePaid, SellerError> {
+        let mut incoming_invoices_paid = await!(self.invoices_paid_mc.request_stream())
+            .map_err(|_| SellerError::ConnectivityError)?;
+
+        while let Some(invoice_paid) = await!(incoming_invoices_paid.next()) {
+            if invoice_paid.invoice_id != invoice_id {
+                // This is not our invoice
+                continue;
+            }
+            return Ok(invoice_paid);
+        }
+
+        // We lost connectivity before we got any response:
+        Err(SellerError::NoResponse)
+    }
 }
@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+use std::convert::TryInto;
+
+use funder::export::{
+    export_funder_state, import_funder_state, FunderImportError, FunderStateExport,
+};
+use index_client::IndexClientConfig;
+
+use proto::file::ser_string::{public_key_to_string, string_to_public_key, SerStringError};
+use proto::index_server::messages::NamedIndexServerAddress;
+use proto::net::messages::{NetAddress, NetAddressError};
+
+use crate::types::NodeState;
+
+/// Version of the exported node state format.
+/// Must be incremented on every incompatible change to the exported structures.
+pub const NODE_EXPORT_VERSION: u32 = 2;
+
+#[derive(Debug)]
+pub enum NodeImportError {
+    /// The export was created by a newer version of the software
+    UnsupportedVersion(u32),
+    FunderImportError(FunderImportError),
+    SerStringError,
+    NetAddressError(NetAddressError),
+    DuplicateIndexServer,
+}
+
+impl From<FunderImportError> for NodeImportError {
+    fn from(e: FunderImportError) -> Self {
+        NodeImportError::FunderImportError(e)
+    }
+}
+
+impl From<SerStringError> for NodeImportError {
+    fn from(_e: SerStringError) -> Self {
+        NodeImportError::SerStringError
+    }
+}
+
+impl From<NetAddressError> for NodeImportError {
+    fn from(e: NetAddressError) -> Self {
+        NodeImportError::NetAddressError(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedIndexServerExport {
+    pub public_key: String,
+    pub address: String,
+    pub name: String,
+}
+
+/// A stable, human readable representation of a node's state.
+/// See `funder::export` for the encoding conventions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeStateExport {
+    pub version: u32,
+    pub funder_state: FunderStateExport,
+    pub index_servers: Vec<NamedIndexServerExport>,
+}
+
+/// Convert a NodeState into its exported representation
+pub fn export_node_state(node_state: &NodeState<NetAddress>) -> NodeStateExport {
+    let index_servers = node_state
+        .index_client_config
+        .index_servers
+        .iter()
+        .map(|index_server| NamedIndexServerExport {
+            public_key: public_key_to_string(&index_server.public_key),
+            address: index_server.address.as_str().to_owned(),
+            name: index_server.name.clone(),
+        })
+        .collect();
+
+    NodeStateExport {
+        version: NODE_EXPORT_VERSION,
+        funder_state: export_funder_state(&node_state.funder_state),
+        index_servers,
+    }
+}
+
+/// Restore a NodeState from its exported representation, validating it on the way.
+pub fn import_node_state(
+    node_state_export: &NodeStateExport,
+) -> Result<NodeState<NetAddress>, NodeImportError> {
+    if node_state_export.version != NODE_EXPORT_VERSION {
+        return Err(NodeImportError::UnsupportedVersion(
+            node_state_export.version,
+        ));
+    }
+
+    let funder_state = import_funder_state(&node_state_export.funder_state)?;
+
+    let mut index_client_config = IndexClientConfig::new();
+    let mut index_server_public_keys = HashSet::new();
+    for index_server_export in &node_state_export.index_servers {
+        let public_key = string_to_public_key(&index_server_export.public_key)?;
+        if !index_server_public_keys.insert(public_key.clone()) {
+            return Err(NodeImportError::DuplicateIndexServer);
+        }
+        index_client_config
+            .index_servers
+            .push(NamedIndexServerAddress {
+                public_key,
+                address: index_server_export.address.clone().try_into()?,
+                name: index_server_export.name.clone(),
+            });
+    }
+
+    Ok(NodeState {
+        funder_state,
+        index_client_config,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crypto::identity::{PublicKey, PUBLIC_KEY_LEN};
+
+    #[test]
+    fn test_export_import_node_state() {
+        let mut node_state = NodeState::new(PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]));
+        node_state
+            .index_client_config
+            .index_servers
+            .push(NamedIndexServerAddress {
+                public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+                address: "index.example.com:1338".to_owned().try_into().unwrap(),
+                name: "index".to_owned(),
+            });
+
+        let node_state_export = export_node_state(&node_state);
+        let node_state2 = import_node_state(&node_state_export).unwrap();
+        assert_eq!(export_node_state(&node_state2), node_state_export);
+
+        // Duplicate index server:
+        let mut export = node_state_export.clone();
+        let index_server = export.index_servers[0].clone();
+        export.index_servers.push(index_server);
+        match import_node_state(&export) {
+            Err(NodeImportError::DuplicateIndexServer) => {}
+            _ => unreachable!(),
+        };
+
+        // Unknown version:
+        let mut export = node_state_export.clone();
+        export.version = NODE_EXPORT_VERSION + 1;
+        match import_node_state(&export) {
+            Err(NodeImportError::UnsupportedVersion(_)) => {}
+            _ => unreachable!(),
+        };
+    }
+}
@@ -20,6 +20,7 @@ extern crate serde_derive;
 
 mod adapters;
 pub mod connect;
+pub mod export;
 mod net_node;
 mod node;
 mod types;
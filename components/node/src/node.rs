@@ -6,11 +6,12 @@ use derive_more::*;
 
 use common::conn::{ConnPairVec, FutTransform};
 use crypto::crypto_rand::CryptoRandom;
+use crypto::dh::DhPublicKey;
 use crypto::identity::PublicKey;
 
 use database::DatabaseClient;
 use identity::IdentityClient;
-use timer::TimerClient;
+use timer::{TimerClient, TimerTick};
 
 use app_server::{app_server_loop, AppServerError, IncomingAppConnection};
 use channeler::{spawn_channeler, ChannelerError};
@@ -38,6 +39,8 @@ use crate::types::{create_node_report, NodeConfig, NodeMutation, NodeState};
 #[derive(Debug, From)]
 pub enum NodeError {
     RequestPublicKeyError,
+    RequestDhPublicKeyError,
+    RequestTimerStreamError,
     SpawnError,
     ChannelerError(ChannelerError),
     FunderError(FunderError),
@@ -101,6 +104,7 @@ where
 fn node_spawn_funder<R, S>(
     node_config: &NodeConfig,
     identity_client: IdentityClient,
+    timer_stream: mpsc::Receiver<TimerTick>,
     funder_state: FunderState<NetAddress>,
     mut database_client: DatabaseClient<NodeMutation<NetAddress>>,
     mut from_channeler: mpsc::Receiver<ChannelerToFunder>,
@@ -209,11 +213,13 @@ where
         rng.clone(),
         from_app_server,
         incoming_comm,
+        timer_stream,
         to_app_server,
         outgoing_comm_sender,
         node_config.max_node_relays,
         node_config.max_operations_in_batch,
         node_config.max_pending_user_requests,
+        node_config.payment_timeout_ticks,
         funder_state,
         funder_db_client,
     );
@@ -226,6 +232,7 @@ where
 async fn node_spawn_index_client<'a, C, R, S>(
     node_config: &'a NodeConfig,
     local_public_key: PublicKey,
+    local_dh_public_key: &'a DhPublicKey,
     identity_client: IdentityClient,
     timer_client: TimerClient,
     node_state: &'a NodeState<NetAddress>,
@@ -245,7 +252,7 @@ where
     R: CryptoRandom + Clone + 'static,
     S: Spawn + Clone + Send + Sync + 'static,
 {
-    let initial_node_report = create_node_report(&node_state);
+    let initial_node_report = create_node_report(&node_state, local_dh_public_key);
 
     // Database adapter:
     let (request_sender, mut request_receiver) = mpsc::channel(0);
@@ -341,7 +348,11 @@ where
     let local_public_key = await!(identity_client.request_public_key())
         .map_err(|_| NodeError::RequestPublicKeyError)?;
 
-    let initial_node_report = create_node_report(&node_state);
+    // Get local DH public key, used by buyers to encrypt spontaneous payments to us:
+    let local_dh_public_key = await!(identity_client.request_dh_public_key())
+        .map_err(|_| NodeError::RequestDhPublicKeyError)?;
+
+    let initial_node_report = create_node_report(&node_state, &local_dh_public_key);
 
     // Channeler <--> Funder
     let (channeler_to_funder_sender, channeler_to_funder_receiver) =
@@ -367,9 +378,13 @@ where
     let (funder_to_app_server_sender, funder_to_app_server_receiver) =
         mpsc::channel(node_config.channel_len);
 
+    let funder_timer_stream = await!(timer_client.request_timer_stream())
+        .map_err(|_| NodeError::RequestTimerStreamError)?;
+
     let funder_handle = node_spawn_funder(
         &node_config,
         identity_client.clone(),
+        funder_timer_stream,
         node_state.funder_state.clone(),
         database_client.clone(),
         channeler_to_funder_receiver,
@@ -403,6 +418,7 @@ where
     let index_client_handle = await!(node_spawn_index_client(
         &node_config,
         local_public_key,
+        &local_dh_public_key,
         identity_client,
         timer_client,
         &node_state,
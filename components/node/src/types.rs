@@ -1,7 +1,15 @@
+use std::convert::TryFrom;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
 use common::canonical_serialize::CanonicalSerialize;
 use common::mutable_state::MutableState;
 
+use crypto::dh::DhPublicKey;
 use crypto::identity::PublicKey;
+use database::migrate::{migrate_unchanged, Migrations, VersionedState};
+use funder::migrate::{FunderMutationV1, FunderStateV1};
 use funder::report::create_initial_report;
 use funder::{FunderMutation, FunderState};
 use index_client::{IndexClientConfig, IndexClientConfigMutation};
@@ -57,6 +65,83 @@ where
     }
 }
 
+/// NodeState, as laid out in schema version 1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NodeStateV1<B: Clone> {
+    funder_state: FunderStateV1<B>,
+    index_client_config: IndexClientConfig<B>,
+}
+
+/// NodeMutation, as laid out in schema version 1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum NodeMutationV1<B: Clone> {
+    Funder(FunderMutationV1<B>),
+    IndexClient(IndexClientConfigMutation<B>),
+}
+
+/// Migrate a legacy (headerless) NodeState. Only a header was added in version 1, the layout of
+/// the state itself did not change.
+fn migrate_node_state_v0_to_v1(data: &[u8]) -> Result<Vec<u8>, ()> {
+    Ok(data.to_vec())
+}
+
+/// Version 2 changed the layout of the funder's payments (Per payment deadlines and timeouts) and
+/// of cancellations (Reporting node and reason).
+fn migrate_node_state_v1_to_v2<B>(data: &[u8]) -> Result<Vec<u8>, ()>
+where
+    B: Clone + Serialize + DeserializeOwned,
+{
+    let node_state_v1: NodeStateV1<B> = bincode::deserialize(data).map_err(|_| ())?;
+    let node_state = NodeState {
+        funder_state: FunderState::try_from(node_state_v1.funder_state).map_err(|_| ())?,
+        index_client_config: node_state_v1.index_client_config,
+    };
+    bincode::serialize(&node_state).map_err(|_| ())
+}
+
+/// Migrate a batch of mutations (`Vec<NodeMutation>`) from version 1 to version 2.
+fn migrate_node_mutations_v1_to_v2<B>(data: &[u8]) -> Result<Vec<u8>, ()>
+where
+    B: Clone + Serialize + DeserializeOwned,
+{
+    let node_mutations_v1: Vec<NodeMutationV1<B>> = bincode::deserialize(data).map_err(|_| ())?;
+    let node_mutations = node_mutations_v1
+        .into_iter()
+        .map(|node_mutation_v1| {
+            Ok(match node_mutation_v1 {
+                NodeMutationV1::Funder(funder_mutation) => {
+                    NodeMutation::Funder(FunderMutation::try_from(funder_mutation).map_err(|_| ())?)
+                }
+                NodeMutationV1::IndexClient(index_client_mutation) => {
+                    NodeMutation::IndexClient(index_client_mutation)
+                }
+            })
+        })
+        .collect::<Result<Vec<NodeMutation<B>>, ()>>()?;
+    bincode::serialize(&node_mutations).map_err(|_| ())
+}
+
+impl<B> VersionedState for NodeState<B>
+where
+    B: Clone + Serialize + DeserializeOwned,
+{
+    /// Every change to the serialized layout of NodeState (Including FunderState and
+    /// IndexClientConfig) must be accompanied by a new migration step here.
+    fn migrations() -> Migrations {
+        Migrations::new()
+            .add(migrate_node_state_v0_to_v1)
+            .add(migrate_node_state_v1_to_v2::<B>)
+    }
+
+    /// Every change to the serialized layout of NodeMutation must be accompanied by a new
+    /// migration step here, in the same schema version as in `migrations()`.
+    fn mutation_migrations() -> Migrations {
+        Migrations::new()
+            .add(migrate_unchanged)
+            .add(migrate_node_mutations_v1_to_v2::<B>)
+    }
+}
+
 /// Create an initial IndexClientReport, based on an IndexClientConfig
 fn create_index_client_report<B>(index_client_config: &IndexClientConfig<B>) -> IndexClientReport<B>
 where
@@ -70,12 +155,15 @@ where
 }
 
 /// Create an initial NodeReport, based on a NodeState
-pub fn create_node_report<B>(node_state: &NodeState<B>) -> NodeReport<B>
+pub fn create_node_report<B>(
+    node_state: &NodeState<B>,
+    local_dh_public_key: &DhPublicKey,
+) -> NodeReport<B>
 where
     B: Clone + CanonicalSerialize,
 {
     NodeReport {
-        funder_report: create_initial_report(&node_state.funder_state),
+        funder_report: create_initial_report(&node_state.funder_state, local_dh_public_key),
         index_client_report: create_index_client_report(&node_state.index_client_config),
     }
 }
@@ -99,6 +187,8 @@ pub struct NodeConfig {
     pub max_operations_in_batch: usize,
     /// The size we allocate for the user send funds requests queue.
     pub max_pending_user_requests: usize,
+    /// Amount of ticks we wait for a payment to complete before marking it as timed out.
+    pub payment_timeout_ticks: usize,
     /// Maximum amount of concurrent index client requests:
     pub max_open_index_client_requests: usize,
     /// Maximum amount of relays a node may use.
@@ -6,14 +6,15 @@ use crypto::payment_id::PaymentId;
 use crypto::uid::Uid;
 
 use crate::funder::messages::{
-    AckClosePayment, AddFriend, AddInvoice, CreatePayment, CreateTransaction, MultiCommit,
-    ResetFriendChannel, ResponseClosePayment, SetFriendName, SetFriendRate, SetFriendRelays,
+    AckClosePayment, AddFriend, AddInvoice, CreatePayment, CreateRebalance, CreateTransaction,
+    InvoiceDetails, InvoicePaid, MultiCommit, ResetFriendChannel, ResponseClosePayment,
+    ResponseInvoiceStatus, ResponseSignInvoice, SetFriendName, SetFriendRate, SetFriendRelays,
     SetFriendRemoteMaxDebt, TransactionResult,
 };
 use crate::index_client::messages::{
-    ClientResponseRoutes, IndexClientReport, IndexClientReportMutation,
+    ClientResponseLiquidity, ClientResponseRoutes, IndexClientReport, IndexClientReportMutation,
 };
-use crate::index_server::messages::{NamedIndexServerAddress, RequestRoutes};
+use crate::index_server::messages::{NamedIndexServerAddress, RequestLiquidity, RequestRoutes};
 use crate::net::messages::NetAddress;
 use crate::report::messages::{FunderReport, FunderReportMutation};
 
@@ -88,10 +89,15 @@ where
     /// Funds:
     TransactionResult(TransactionResult),
     ResponseClosePayment(ResponseClosePayment),
+    /// Invoices:
+    ResponseInvoiceStatus(ResponseInvoiceStatus),
+    InvoicePaid(InvoicePaid),
+    ResponseSignInvoice(ResponseSignInvoice),
     /// Reports about current state:
     Report(NodeReport<B>),
     ReportMutations(ReportMutations<B>),
     ResponseRoutes(ClientResponseRoutes),
+    ResponseLiquidity(ClientResponseLiquidity),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -105,6 +111,8 @@ pub enum AppRequest<B = NetAddress> {
     /// Manage locally used relays:
     AddRelay(NamedRelayAddress<B>),
     RemoveRelay(PublicKey),
+    /// Accept payments without a prior invoice (Tips, donations):
+    SetAcceptSpontaneousPayments(bool),
     /// Friend management:
     AddFriend(AddFriend<B>),
     SetFriendRelays(SetFriendRelays<B>),
@@ -122,15 +130,22 @@ pub enum AppRequest<B = NetAddress> {
     CreateTransaction(CreateTransaction),
     RequestClosePayment(PaymentId),
     AckClosePayment(AckClosePayment),
+    /// Pay ourselves along a circular route, moving credits between two friends:
+    CreateRebalance(CreateRebalance),
     /// Seller:
     AddInvoice(AddInvoice),
     CancelInvoice(InvoiceId),
     CommitInvoice(MultiCommit),
+    RequestInvoiceStatus(InvoiceId),
+    /// Sign the details of an open invoice, to be sent to a buyer:
+    RequestSignInvoice(InvoiceDetails<B>),
     /// Request routes from one node to another:
     RequestRoutes(RequestRoutes),
     /// Manage index servers:
     AddIndexServer(NamedIndexServerAddress<B>),
     RemoveIndexServer(PublicKey),
+    /// Request the amount of credits a node can receive:
+    RequestLiquidity(RequestLiquidity),
 }
 #[derive(Debug, PartialEq, Eq)]
 pub struct AppToAppServer<B = NetAddress> {
@@ -1,12 +1,13 @@
 use std::io;
 
 use crate::capnp_common::{
-    read_commit, read_custom_int128, read_custom_u_int128, read_invoice_id, read_multi_commit,
-    read_named_index_server_address, read_named_relay_address, read_payment_id, read_public_key,
-    read_rate, read_receipt, read_relay_address, read_signature, read_uid, write_commit,
-    write_custom_int128, write_custom_u_int128, write_invoice_id, write_multi_commit,
-    write_named_index_server_address, write_named_relay_address, write_payment_id,
-    write_public_key, write_rate, write_receipt, write_relay_address, write_signature, write_uid,
+    read_commit, read_custom_int128, read_custom_u_int128, read_dh_public_key, read_hashed_lock,
+    read_invoice_id, read_multi_commit, read_named_index_server_address, read_named_relay_address,
+    read_payment_id, read_public_key, read_rate, read_receipt, read_relay_address, read_signature,
+    read_uid, write_commit, write_custom_int128, write_custom_u_int128, write_dh_public_key,
+    write_hashed_lock, write_invoice_id, write_multi_commit, write_named_index_server_address,
+    write_named_relay_address, write_payment_id, write_public_key, write_rate, write_receipt,
+    write_relay_address, write_signature, write_uid,
 };
 use capnp;
 use capnp::serialize_packed;
@@ -15,22 +16,28 @@ use common::int_convert::usize_to_u32;
 use crate::serialize::SerializeError;
 use app_server_capnp;
 
-use crate::index_client::messages::{ClientResponseRoutes, ResponseRoutesResult};
+use crate::index_client::messages::{
+    ClientResponseLiquidity, ClientResponseRoutes, ResponseLiquidityResult, ResponseRoutesResult,
+};
 
 use crate::report::serialize::{
     deser_node_report, deser_node_report_mutation, ser_node_report, ser_node_report_mutation,
 };
 use index_server::serialize::{
-    deser_multi_route, deser_request_routes, ser_multi_route, ser_request_routes,
+    deser_liquidity, deser_multi_route, deser_request_liquidity, deser_request_routes,
+    ser_liquidity, ser_multi_route, ser_request_liquidity, ser_request_routes,
 };
 
 use crate::funder::messages::{
-    AckClosePayment, AddFriend, AddInvoice, CreatePayment, CreateTransaction, PaymentStatus,
-    ReceiptAck, RequestResult, ResetFriendChannel, ResponseClosePayment, SetFriendName,
-    SetFriendRate, SetFriendRelays, SetFriendRemoteMaxDebt, TransactionResult,
-    UserRequestSendFunds,
+    AckClosePayment, AddFriend, AddInvoice, CreatePayment, CreateRebalance, CreateTransaction,
+    InvoiceDetails, InvoicePaid, InvoiceStatus, InvoiceTransaction, OpenInvoiceStatus,
+    PaymentStatus, ReceiptAck, RequestFailure, RequestResult, ResetFriendChannel,
+    ResponseClosePayment, ResponseInvoiceStatus, ResponseSignInvoice, SetFriendName, SetFriendRate,
+    SetFriendRelays, SetFriendRemoteMaxDebt, TransactionResult, UserRequestSendFunds,
+};
+use crate::funder::serialize::{
+    deser_failure_reason, deser_friends_route, ser_failure_reason, ser_friends_route,
 };
-use crate::funder::serialize::{deser_friends_route, ser_friends_route};
 
 use crate::app_server::messages::{
     AppPermissions, AppRequest, AppServerToApp, AppToAppServer, ReportMutations,
@@ -271,6 +278,59 @@ fn deser_client_response_routes(
     })
 }
 
+fn ser_response_liquidity_result(
+    response_liquidity_result: &ResponseLiquidityResult,
+    response_liquidity_result_builder: &mut app_server_capnp::response_liquidity_result::Builder,
+) {
+    match response_liquidity_result {
+        ResponseLiquidityResult::Success(liquidity) => ser_liquidity(
+            liquidity,
+            &mut response_liquidity_result_builder.reborrow().init_success(),
+        ),
+        ResponseLiquidityResult::Failure => {
+            response_liquidity_result_builder.reborrow().set_failure(())
+        }
+    }
+}
+
+fn deser_response_liquidity_result(
+    response_liquidity_result_reader: &app_server_capnp::response_liquidity_result::Reader,
+) -> Result<ResponseLiquidityResult, SerializeError> {
+    Ok(match response_liquidity_result_reader.which()? {
+        app_server_capnp::response_liquidity_result::Success(liquidity_reader) => {
+            ResponseLiquidityResult::Success(deser_liquidity(&liquidity_reader?)?)
+        }
+        app_server_capnp::response_liquidity_result::Failure(()) => {
+            ResponseLiquidityResult::Failure
+        }
+    })
+}
+
+fn ser_client_response_liquidity(
+    client_response_liquidity: &ClientResponseLiquidity,
+    client_response_liquidity_builder: &mut app_server_capnp::client_response_liquidity::Builder,
+) {
+    write_uid(
+        &client_response_liquidity.request_id,
+        &mut client_response_liquidity_builder
+            .reborrow()
+            .init_request_id(),
+    );
+    ser_response_liquidity_result(
+        &client_response_liquidity.result,
+        &mut client_response_liquidity_builder.reborrow().init_result(),
+    );
+}
+
+fn deser_client_response_liquidity(
+    client_response_liquidity_reader: &app_server_capnp::client_response_liquidity::Reader,
+) -> Result<ClientResponseLiquidity, SerializeError> {
+    Ok(ClientResponseLiquidity {
+        request_id: read_uid(&client_response_liquidity_reader.get_request_id()?)?,
+        result: deser_response_liquidity_result(&client_response_liquidity_reader.get_result()?)?,
+    })
+}
+
 /*
 fn ser_add_index_server(add_index_server: &AddIndexServer<NetAddress>,
                             add_index_server_builder: &mut app_server_capnp::add_index_server::Builder) {
@@ -343,16 +403,52 @@ fn ser_create_payment(
         &create_payment.dest_public_key,
         &mut create_payment_builder.reborrow().init_dest_public_key(),
     );
+
+    let mut opt_dest_dh_public_key_builder = create_payment_builder
+        .reborrow()
+        .init_opt_dest_dh_public_key();
+    match &create_payment.opt_dest_dh_public_key {
+        Some(dest_dh_public_key) => write_dh_public_key(
+            dest_dh_public_key,
+            &mut opt_dest_dh_public_key_builder
+                .reborrow()
+                .init_dest_dh_public_key(),
+        ),
+        None => opt_dest_dh_public_key_builder.set_empty(()),
+    };
+
+    let mut opt_deadline_ticks_builder =
+        create_payment_builder.reborrow().init_opt_deadline_ticks();
+    match create_payment.opt_deadline_ticks {
+        Some(deadline_ticks) => opt_deadline_ticks_builder.set_deadline_ticks(deadline_ticks),
+        None => opt_deadline_ticks_builder.set_empty(()),
+    };
 }
 
 fn deser_create_payment(
     create_payment_reader: &app_server_capnp::create_payment::Reader,
 ) -> Result<CreatePayment, SerializeError> {
+    let opt_dest_dh_public_key = match create_payment_reader.get_opt_dest_dh_public_key().which()? {
+        app_server_capnp::create_payment::opt_dest_dh_public_key::DestDhPublicKey(
+            dest_dh_public_key,
+        ) => Some(read_dh_public_key(&dest_dh_public_key?)?),
+        app_server_capnp::create_payment::opt_dest_dh_public_key::Empty(()) => None,
+    };
+
+    let opt_deadline_ticks = match create_payment_reader.get_opt_deadline_ticks().which()? {
+        app_server_capnp::create_payment::opt_deadline_ticks::DeadlineTicks(deadline_ticks) => {
+            Some(deadline_ticks)
+        }
+        app_server_capnp::create_payment::opt_deadline_ticks::Empty(()) => None,
+    };
+
     Ok(CreatePayment {
         payment_id: read_payment_id(&create_payment_reader.get_payment_id()?)?,
         invoice_id: read_invoice_id(&create_payment_reader.get_invoice_id()?)?,
         total_dest_payment: read_custom_u_int128(&create_payment_reader.get_total_dest_payment()?)?,
         dest_public_key: read_public_key(&create_payment_reader.get_dest_public_key()?)?,
+        opt_dest_dh_public_key,
+        opt_deadline_ticks,
     })
 }
 
@@ -398,6 +494,54 @@ fn deser_create_transaction(
     })
 }
 
+fn ser_create_rebalance(
+    create_rebalance: &CreateRebalance,
+    create_rebalance_builder: &mut app_server_capnp::create_rebalance::Builder,
+) {
+    write_payment_id(
+        &create_rebalance.payment_id,
+        &mut create_rebalance_builder.reborrow().init_payment_id(),
+    );
+
+    write_invoice_id(
+        &create_rebalance.invoice_id,
+        &mut create_rebalance_builder.reborrow().init_invoice_id(),
+    );
+
+    write_uid(
+        &create_rebalance.request_id,
+        &mut create_rebalance_builder.reborrow().init_request_id(),
+    );
+
+    ser_friends_route(
+        &create_rebalance.route,
+        &mut create_rebalance_builder.reborrow().init_route(),
+    );
+
+    write_custom_u_int128(
+        create_rebalance.dest_payment,
+        &mut create_rebalance_builder.reborrow().init_dest_payment(),
+    );
+
+    write_custom_u_int128(
+        create_rebalance.fees,
+        &mut create_rebalance_builder.reborrow().init_fees(),
+    );
+}
+
+fn deser_create_rebalance(
+    create_rebalance_reader: &app_server_capnp::create_rebalance::Reader,
+) -> Result<CreateRebalance, SerializeError> {
+    Ok(CreateRebalance {
+        payment_id: read_payment_id(&create_rebalance_reader.get_payment_id()?)?,
+        invoice_id: read_invoice_id(&create_rebalance_reader.get_invoice_id()?)?,
+        request_id: read_uid(&create_rebalance_reader.get_request_id()?)?,
+        route: deser_friends_route(&create_rebalance_reader.get_route()?)?,
+        dest_payment: read_custom_u_int128(&create_rebalance_reader.get_dest_payment()?)?,
+        fees: read_custom_u_int128(&create_rebalance_reader.get_fees()?)?,
+    })
+}
+
 fn ser_add_invoice(
     add_invoice: &AddInvoice,
     add_invoice_builder: &mut app_server_capnp::add_invoice::Builder,
@@ -411,14 +555,82 @@ fn ser_add_invoice(
         add_invoice.total_dest_payment,
         &mut add_invoice_builder.reborrow().init_total_dest_payment(),
     );
+
+    let mut opt_expiry_ticks_builder = add_invoice_builder.reborrow().init_opt_expiry_ticks();
+    match add_invoice.opt_expiry_ticks {
+        Some(expiry_ticks) => opt_expiry_ticks_builder.set_expiry_ticks(expiry_ticks),
+        None => opt_expiry_ticks_builder.set_empty(()),
+    };
 }
 
 fn deser_add_invoice(
     add_invoice_reader: &app_server_capnp::add_invoice::Reader,
 ) -> Result<AddInvoice, SerializeError> {
+    let opt_expiry_ticks = match add_invoice_reader.get_opt_expiry_ticks().which()? {
+        app_server_capnp::add_invoice::opt_expiry_ticks::ExpiryTicks(expiry_ticks) => {
+            Some(expiry_ticks)
+        }
+        app_server_capnp::add_invoice::opt_expiry_ticks::Empty(()) => None,
+    };
+
     Ok(AddInvoice {
         invoice_id: read_invoice_id(&add_invoice_reader.get_invoice_id()?)?,
         total_dest_payment: read_custom_u_int128(&add_invoice_reader.get_total_dest_payment()?)?,
+        opt_expiry_ticks,
+    })
+}
+
+fn ser_invoice_details(
+    invoice_details: &InvoiceDetails,
+    invoice_details_builder: &mut app_server_capnp::invoice_details::Builder,
+) {
+    write_invoice_id(
+        &invoice_details.invoice_id,
+        &mut invoice_details_builder.reborrow().init_invoice_id(),
+    );
+
+    write_custom_u_int128(
+        invoice_details.total_dest_payment,
+        &mut invoice_details_builder.reborrow().init_total_dest_payment(),
+    );
+
+    invoice_details_builder.set_memo(&invoice_details.memo);
+
+    let mut opt_expiry_builder = invoice_details_builder.reborrow().init_opt_expiry();
+    match invoice_details.opt_expiry {
+        Some(expiry) => opt_expiry_builder.set_expiry(expiry),
+        None => opt_expiry_builder.set_empty(()),
+    };
+
+    let relays_len = usize_to_u32(invoice_details.relays.len()).unwrap();
+    let mut relays_builder = invoice_details_builder.reborrow().init_relays(relays_len);
+    for (index, relay_address) in invoice_details.relays.iter().enumerate() {
+        let mut relay_address_builder = relays_builder.reborrow().get(usize_to_u32(index).unwrap());
+        write_relay_address(relay_address, &mut relay_address_builder);
+    }
+}
+
+fn deser_invoice_details(
+    invoice_details_reader: &app_server_capnp::invoice_details::Reader,
+) -> Result<InvoiceDetails, SerializeError> {
+    let opt_expiry = match invoice_details_reader.get_opt_expiry().which()? {
+        app_server_capnp::invoice_details::opt_expiry::Expiry(expiry) => Some(expiry),
+        app_server_capnp::invoice_details::opt_expiry::Empty(()) => None,
+    };
+
+    let mut relays = Vec::new();
+    for relay_address in invoice_details_reader.get_relays()? {
+        relays.push(read_relay_address(&relay_address)?);
+    }
+
+    Ok(InvoiceDetails {
+        invoice_id: read_invoice_id(&invoice_details_reader.get_invoice_id()?)?,
+        total_dest_payment: read_custom_u_int128(
+            &invoice_details_reader.get_total_dest_payment()?,
+        )?,
+        memo: invoice_details_reader.get_memo()?.to_owned(),
+        opt_expiry,
+        relays,
     })
 }
 
@@ -481,7 +693,21 @@ fn ser_request_result(
                 &mut request_result_builder.reborrow().init_success(),
             );
         }
-        RequestResult::Failure => request_result_builder.reborrow().set_failure(()),
+        RequestResult::Failure(request_failure) => {
+            let mut request_failure_builder =
+                request_result_builder.reborrow().init_failure_details();
+            write_public_key(
+                &request_failure.reporting_public_key,
+                &mut request_failure_builder
+                    .reborrow()
+                    .init_reporting_public_key(),
+            );
+            ser_failure_reason(
+                &request_failure.reason,
+                &mut request_failure_builder.reborrow().init_reason(),
+            );
+        }
+        RequestResult::UnknownFailure => request_result_builder.set_failure(()),
     }
 }
 
@@ -492,7 +718,16 @@ fn deser_request_result(
         app_server_capnp::request_result::Success(commit_reader) => {
             RequestResult::Success(read_commit(&commit_reader?)?)
         }
-        app_server_capnp::request_result::Failure(()) => RequestResult::Failure,
+        app_server_capnp::request_result::Failure(()) => RequestResult::UnknownFailure,
+        app_server_capnp::request_result::FailureDetails(request_failure_reader) => {
+            let request_failure_reader = request_failure_reader?;
+            RequestResult::Failure(RequestFailure {
+                reporting_public_key: read_public_key(
+                    &request_failure_reader.get_reporting_public_key()?,
+                )?,
+                reason: deser_failure_reason(&request_failure_reader.get_reason()?)?,
+            })
+        }
     })
 }
 
@@ -544,6 +779,10 @@ fn ser_payment_status(
             let mut ack_uid_builder = payment_status_builder.reborrow().init_canceled();
             write_uid(ack_uid, &mut ack_uid_builder);
         }
+        PaymentStatus::TimedOut(ack_uid) => {
+            let mut ack_uid_builder = payment_status_builder.reborrow().init_timed_out();
+            write_uid(ack_uid, &mut ack_uid_builder);
+        }
     }
 }
 
@@ -563,6 +802,9 @@ fn deser_payment_status(
         app_server_capnp::payment_status::Canceled(ack_uid_reader) => {
             PaymentStatus::Canceled(read_uid(&ack_uid_reader?)?)
         }
+        app_server_capnp::payment_status::TimedOut(ack_uid_reader) => {
+            PaymentStatus::TimedOut(read_uid(&ack_uid_reader?)?)
+        }
     })
 }
 
@@ -590,6 +832,223 @@ fn deser_response_close_payment(
     })
 }
 
+fn ser_invoice_transaction(
+    invoice_transaction: &InvoiceTransaction,
+    invoice_transaction_builder: &mut app_server_capnp::invoice_transaction::Builder,
+) {
+    write_uid(
+        &invoice_transaction.request_id,
+        &mut invoice_transaction_builder.reborrow().init_request_id(),
+    );
+    write_custom_u_int128(
+        invoice_transaction.dest_payment,
+        &mut invoice_transaction_builder.reborrow().init_dest_payment(),
+    );
+    write_hashed_lock(
+        &invoice_transaction.dest_hashed_lock,
+        &mut invoice_transaction_builder
+            .reborrow()
+            .init_dest_hashed_lock(),
+    );
+}
+
+fn deser_invoice_transaction(
+    invoice_transaction_reader: &app_server_capnp::invoice_transaction::Reader,
+) -> Result<InvoiceTransaction, SerializeError> {
+    Ok(InvoiceTransaction {
+        request_id: read_uid(&invoice_transaction_reader.get_request_id()?)?,
+        dest_hashed_lock: read_hashed_lock(&invoice_transaction_reader.get_dest_hashed_lock()?)?,
+        dest_payment: read_custom_u_int128(&invoice_transaction_reader.get_dest_payment()?)?,
+    })
+}
+
+fn ser_open_invoice_status(
+    open_invoice_status: &OpenInvoiceStatus,
+    open_invoice_status_builder: &mut app_server_capnp::open_invoice_status::Builder,
+) {
+    write_custom_u_int128(
+        open_invoice_status.total_dest_payment,
+        &mut open_invoice_status_builder
+            .reborrow()
+            .init_total_dest_payment(),
+    );
+
+    let transactions_len = usize_to_u32(open_invoice_status.transactions.len()).unwrap();
+    let mut transactions_builder = open_invoice_status_builder
+        .reborrow()
+        .init_transactions(transactions_len);
+    for (index, invoice_transaction) in open_invoice_status.transactions.iter().enumerate() {
+        let mut invoice_transaction_builder = transactions_builder
+            .reborrow()
+            .get(usize_to_u32(index).unwrap());
+        ser_invoice_transaction(invoice_transaction, &mut invoice_transaction_builder);
+    }
+
+    write_custom_u_int128(
+        open_invoice_status.collected_dest_payment,
+        &mut open_invoice_status_builder
+            .reborrow()
+            .init_collected_dest_payment(),
+    );
+
+    let mut opt_ticks_left_builder = open_invoice_status_builder.reborrow().init_opt_ticks_left();
+    match open_invoice_status.opt_ticks_left {
+        Some(ticks_left) => opt_ticks_left_builder.set_ticks_left(ticks_left),
+        None => opt_ticks_left_builder.set_empty(()),
+    };
+}
+
+fn deser_open_invoice_status(
+    open_invoice_status_reader: &app_server_capnp::open_invoice_status::Reader,
+) -> Result<OpenInvoiceStatus, SerializeError> {
+    let mut transactions = Vec::new();
+    for invoice_transaction_reader in open_invoice_status_reader.get_transactions()? {
+        transactions.push(deser_invoice_transaction(&invoice_transaction_reader)?);
+    }
+
+    let opt_ticks_left = match open_invoice_status_reader.get_opt_ticks_left().which()? {
+        app_server_capnp::open_invoice_status::opt_ticks_left::TicksLeft(ticks_left) => {
+            Some(ticks_left)
+        }
+        app_server_capnp::open_invoice_status::opt_ticks_left::Empty(()) => None,
+    };
+
+    Ok(OpenInvoiceStatus {
+        total_dest_payment: read_custom_u_int128(
+            &open_invoice_status_reader.get_total_dest_payment()?,
+        )?,
+        transactions,
+        collected_dest_payment: read_custom_u_int128(
+            &open_invoice_status_reader.get_collected_dest_payment()?,
+        )?,
+        opt_ticks_left,
+    })
+}
+
+fn ser_invoice_status(
+    invoice_status: &InvoiceStatus,
+    invoice_status_builder: &mut app_server_capnp::invoice_status::Builder,
+) {
+    match invoice_status {
+        InvoiceStatus::InvoiceNotFound => {
+            invoice_status_builder.reborrow().set_invoice_not_found(())
+        }
+        InvoiceStatus::Open(open_invoice_status) => ser_open_invoice_status(
+            open_invoice_status,
+            &mut invoice_status_builder.reborrow().init_open(),
+        ),
+    }
+}
+
+fn deser_invoice_status(
+    invoice_status_reader: &app_server_capnp::invoice_status::Reader,
+) -> Result<InvoiceStatus, SerializeError> {
+    Ok(match invoice_status_reader.which()? {
+        app_server_capnp::invoice_status::InvoiceNotFound(()) => InvoiceStatus::InvoiceNotFound,
+        app_server_capnp::invoice_status::Open(open_invoice_status_reader) => {
+            InvoiceStatus::Open(deser_open_invoice_status(&open_invoice_status_reader?)?)
+        }
+    })
+}
+
+fn ser_response_invoice_status(
+    response_invoice_status: &ResponseInvoiceStatus,
+    response_invoice_status_builder: &mut app_server_capnp::response_invoice_status::Builder,
+) {
+    write_invoice_id(
+        &response_invoice_status.invoice_id,
+        &mut response_invoice_status_builder.reborrow().init_invoice_id(),
+    );
+
+    ser_invoice_status(
+        &response_invoice_status.status,
+        &mut response_invoice_status_builder.reborrow().init_status(),
+    );
+}
+
+fn deser_response_invoice_status(
+    response_invoice_status_reader: &app_server_capnp::response_invoice_status::Reader,
+) -> Result<ResponseInvoiceStatus, SerializeError> {
+    Ok(ResponseInvoiceStatus {
+        invoice_id: read_invoice_id(&response_invoice_status_reader.get_invoice_id()?)?,
+        status: deser_invoice_status(&response_invoice_status_reader.get_status()?)?,
+    })
+}
+
+fn ser_invoice_paid(
+    invoice_paid: &InvoicePaid,
+    invoice_paid_builder: &mut app_server_capnp::invoice_paid::Builder,
+) {
+    write_invoice_id(
+        &invoice_paid.invoice_id,
+        &mut invoice_paid_builder.reborrow().init_invoice_id(),
+    );
+    write_custom_u_int128(
+        invoice_paid.total_dest_payment,
+        &mut invoice_paid_builder.reborrow().init_total_dest_payment(),
+    );
+
+    let transactions_len = usize_to_u32(invoice_paid.transactions.len()).unwrap();
+    let mut transactions_builder = invoice_paid_builder
+        .reborrow()
+        .init_transactions(transactions_len);
+    for (index, invoice_transaction) in invoice_paid.transactions.iter().enumerate() {
+        let mut invoice_transaction_builder = transactions_builder
+            .reborrow()
+            .get(usize_to_u32(index).unwrap());
+        ser_invoice_transaction(invoice_transaction, &mut invoice_transaction_builder);
+    }
+}
+
+fn deser_invoice_paid(
+    invoice_paid_reader: &app_server_capnp::invoice_paid::Reader,
+) -> Result<InvoicePaid, SerializeError> {
+    let mut transactions = Vec::new();
+    for invoice_transaction_reader in invoice_paid_reader.get_transactions()? {
+        transactions.push(deser_invoice_transaction(&invoice_transaction_reader)?);
+    }
+
+    Ok(InvoicePaid {
+        invoice_id: read_invoice_id(&invoice_paid_reader.get_invoice_id()?)?,
+        total_dest_payment: read_custom_u_int128(&invoice_paid_reader.get_total_dest_payment()?)?,
+        transactions,
+    })
+}
+
+fn ser_response_sign_invoice(
+    response_sign_invoice: &ResponseSignInvoice,
+    response_sign_invoice_builder: &mut app_server_capnp::response_sign_invoice::Builder,
+) {
+    write_invoice_id(
+        &response_sign_invoice.invoice_id,
+        &mut response_sign_invoice_builder.reborrow().init_invoice_id(),
+    );
+
+    let mut opt_signature_builder = response_sign_invoice_builder
+        .reborrow()
+        .init_opt_signature();
+    match &response_sign_invoice.opt_signature {
+        Some(signature) => write_signature(signature, &mut opt_signature_builder.init_signature()),
+        None => opt_signature_builder.set_empty(()),
+    };
+}
+
+fn deser_response_sign_invoice(
+    response_sign_invoice_reader: &app_server_capnp::response_sign_invoice::Reader,
+) -> Result<ResponseSignInvoice, SerializeError> {
+    let opt_signature = match response_sign_invoice_reader.get_opt_signature().which()? {
+        app_server_capnp::response_sign_invoice::opt_signature::Signature(signature_reader) => {
+            Some(read_signature(&signature_reader?)?)
+        }
+        app_server_capnp::response_sign_invoice::opt_signature::Empty(()) => None,
+    };
+
+    Ok(ResponseSignInvoice {
+        invoice_id: read_invoice_id(&response_sign_invoice_reader.get_invoice_id()?)?,
+        opt_signature,
+    })
+}
+
 fn ser_report_mutations(
     report_mutations: &ReportMutations,
     report_mutations_builder: &mut app_server_capnp::report_mutations::Builder,
@@ -657,6 +1116,24 @@ fn ser_app_server_to_app(
                 .reborrow()
                 .init_response_close_payment(),
         ),
+        AppServerToApp::ResponseInvoiceStatus(response_invoice_status) => {
+            ser_response_invoice_status(
+                response_invoice_status,
+                &mut app_server_to_app_builder
+                    .reborrow()
+                    .init_response_invoice_status(),
+            )
+        }
+        AppServerToApp::InvoicePaid(invoice_paid) => ser_invoice_paid(
+            invoice_paid,
+            &mut app_server_to_app_builder.reborrow().init_invoice_paid(),
+        ),
+        AppServerToApp::ResponseSignInvoice(response_sign_invoice) => ser_response_sign_invoice(
+            response_sign_invoice,
+            &mut app_server_to_app_builder
+                .reborrow()
+                .init_response_sign_invoice(),
+        ),
         AppServerToApp::Report(node_report) => ser_node_report(
             node_report,
             &mut app_server_to_app_builder.reborrow().init_report(),
@@ -669,6 +1146,12 @@ fn ser_app_server_to_app(
             response_routes,
             &mut app_server_to_app_builder.reborrow().init_response_routes(),
         ),
+        AppServerToApp::ResponseLiquidity(response_liquidity) => ser_client_response_liquidity(
+            response_liquidity,
+            &mut app_server_to_app_builder
+                .reborrow()
+                .init_response_liquidity(),
+        ),
     }
 }
 
@@ -686,6 +1169,19 @@ fn deser_app_server_to_app(
         ) => AppServerToApp::ResponseClosePayment(deser_response_close_payment(
             &response_close_payment_reader?,
         )?),
+        app_server_capnp::app_server_to_app::ResponseInvoiceStatus(
+            response_invoice_status_reader,
+        ) => AppServerToApp::ResponseInvoiceStatus(deser_response_invoice_status(
+            &response_invoice_status_reader?,
+        )?),
+        app_server_capnp::app_server_to_app::InvoicePaid(invoice_paid_reader) => {
+            AppServerToApp::InvoicePaid(deser_invoice_paid(&invoice_paid_reader?)?)
+        }
+        app_server_capnp::app_server_to_app::ResponseSignInvoice(response_sign_invoice_reader) => {
+            AppServerToApp::ResponseSignInvoice(deser_response_sign_invoice(
+                &response_sign_invoice_reader?,
+            )?)
+        }
         app_server_capnp::app_server_to_app::Report(node_report_reader) => {
             AppServerToApp::Report(deser_node_report(&node_report_reader?)?)
         }
@@ -697,6 +1193,11 @@ fn deser_app_server_to_app(
                 &client_response_routes_reader?,
             )?)
         }
+        app_server_capnp::app_server_to_app::ResponseLiquidity(
+            client_response_liquidity_reader,
+        ) => AppServerToApp::ResponseLiquidity(deser_client_response_liquidity(
+            &client_response_liquidity_reader?,
+        )?),
     })
 }
 
@@ -713,6 +1214,9 @@ fn ser_app_request(
             public_key,
             &mut app_request_builder.reborrow().init_remove_relay(),
         ),
+        AppRequest::SetAcceptSpontaneousPayments(accept) => app_request_builder
+            .reborrow()
+            .set_set_accept_spontaneous_payments(*accept),
         AppRequest::CreatePayment(create_payment) => ser_create_payment(
             create_payment,
             &mut app_request_builder.reborrow().init_create_payment(),
@@ -729,6 +1233,10 @@ fn ser_app_request(
             ack_close_payment,
             &mut app_request_builder.reborrow().init_ack_close_payment(),
         ),
+        AppRequest::CreateRebalance(create_rebalance) => ser_create_rebalance(
+            create_rebalance,
+            &mut app_request_builder.reborrow().init_create_rebalance(),
+        ),
         AppRequest::AddInvoice(add_invoice) => ser_add_invoice(
             add_invoice,
             &mut app_request_builder.reborrow().init_add_invoice(),
@@ -741,6 +1249,14 @@ fn ser_app_request(
             multi_commit,
             &mut app_request_builder.reborrow().init_commit_invoice(),
         ),
+        AppRequest::RequestInvoiceStatus(invoice_id) => write_invoice_id(
+            invoice_id,
+            &mut app_request_builder.reborrow().init_request_invoice_status(),
+        ),
+        AppRequest::RequestSignInvoice(invoice_details) => ser_invoice_details(
+            invoice_details,
+            &mut app_request_builder.reborrow().init_request_sign_invoice(),
+        ),
         AppRequest::AddFriend(add_friend) => ser_add_friend(
             add_friend,
             &mut app_request_builder.reborrow().init_add_friend(),
@@ -803,6 +1319,10 @@ fn ser_app_request(
             public_key,
             &mut app_request_builder.reborrow().init_remove_index_server(),
         ),
+        AppRequest::RequestLiquidity(request_liquidity) => ser_request_liquidity(
+            request_liquidity,
+            &mut app_request_builder.reborrow().init_request_liquidity(),
+        ),
     }
 }
 
@@ -816,6 +1336,9 @@ fn deser_app_request(
         app_server_capnp::app_request::RemoveRelay(public_key_reader) => {
             AppRequest::RemoveRelay(read_public_key(&public_key_reader?)?)
         }
+        app_server_capnp::app_request::SetAcceptSpontaneousPayments(accept) => {
+            AppRequest::SetAcceptSpontaneousPayments(accept)
+        }
         app_server_capnp::app_request::CreatePayment(create_payment_reader) => {
             AppRequest::CreatePayment(deser_create_payment(&create_payment_reader?)?)
         }
@@ -828,12 +1351,21 @@ fn deser_app_request(
         app_server_capnp::app_request::AckClosePayment(ack_close_payment_reader) => {
             AppRequest::AckClosePayment(deser_ack_close_payment(&ack_close_payment_reader?)?)
         }
+        app_server_capnp::app_request::CreateRebalance(create_rebalance_reader) => {
+            AppRequest::CreateRebalance(deser_create_rebalance(&create_rebalance_reader?)?)
+        }
         app_server_capnp::app_request::AddInvoice(add_invoice_reader) => {
             AppRequest::AddInvoice(deser_add_invoice(&add_invoice_reader?)?)
         }
         app_server_capnp::app_request::CancelInvoice(invoice_id_reader) => {
             AppRequest::CancelInvoice(read_invoice_id(&invoice_id_reader?)?)
         }
+        app_server_capnp::app_request::RequestInvoiceStatus(invoice_id_reader) => {
+            AppRequest::RequestInvoiceStatus(read_invoice_id(&invoice_id_reader?)?)
+        }
+        app_server_capnp::app_request::RequestSignInvoice(invoice_details_reader) => {
+            AppRequest::RequestSignInvoice(deser_invoice_details(&invoice_details_reader?)?)
+        }
         app_server_capnp::app_request::CommitInvoice(multi_commit_reader) => {
             AppRequest::CommitInvoice(read_multi_commit(&multi_commit_reader?)?)
         }
@@ -883,6 +1415,9 @@ fn deser_app_request(
         app_server_capnp::app_request::RemoveIndexServer(public_key_reader) => {
             AppRequest::RemoveIndexServer(read_public_key(&public_key_reader?)?)
         }
+        app_server_capnp::app_request::RequestLiquidity(request_liquidity_reader) => {
+            AppRequest::RequestLiquidity(deser_request_liquidity(&request_liquidity_reader?)?)
+        }
     })
 }
 
@@ -976,9 +1511,16 @@ pub fn deserialize_app_to_app_server(data: &[u8]) -> Result<AppToAppServer, Seri
 mod tests {
     use super::*;
     use crate::app_server::messages::{NodeReportMutation, RelayAddress};
-    use crate::index_client::messages::IndexClientReportMutation;
+    use crate::funder::messages::{FailureReason, FriendsRoute};
+    use crate::index_client::messages::{
+        HopsLiquidity, IndexClientReportMutation, Liquidity, NeighborLiquidity, RequestLiquidity,
+    };
     use crate::report::messages::FunderReportMutation;
-    use crypto::identity::{PublicKey, PUBLIC_KEY_LEN};
+    use crypto::dh::{DhPublicKey, DH_PUBLIC_KEY_LEN};
+    use crypto::hash_lock::{HashedLock, HASHED_LOCK_LEN};
+    use crypto::identity::{PublicKey, Signature, PUBLIC_KEY_LEN, SIGNATURE_LEN};
+    use crypto::invoice_id::{InvoiceId, INVOICE_ID_LEN};
+    use crypto::payment_id::{PaymentId, PAYMENT_ID_LEN};
     use crypto::uid::{Uid, UID_LEN};
     use std::convert::TryInto;
 
@@ -1048,6 +1590,246 @@ mod tests {
         let data = serialize_app_to_app_server(&app_to_app_server);
         let app_to_app_server2 = deserialize_app_to_app_server(&data).unwrap();
         assert_eq!(app_to_app_server, app_to_app_server2);
+
+        let create_payment = CreatePayment {
+            payment_id: PaymentId::from(&[2; PAYMENT_ID_LEN]),
+            invoice_id: InvoiceId::from(&[3; INVOICE_ID_LEN]),
+            total_dest_payment: 40,
+            dest_public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+            opt_dest_dh_public_key: Some(DhPublicKey::from(&[0xcc; DH_PUBLIC_KEY_LEN])),
+            opt_deadline_ticks: Some(0x100),
+        };
+        let app_to_app_server = AppToAppServer {
+            app_request_id: Uid::from(&[4; UID_LEN]),
+            app_request: AppRequest::CreatePayment(create_payment),
+        };
+
+        let data = serialize_app_to_app_server(&app_to_app_server);
+        let app_to_app_server2 = deserialize_app_to_app_server(&data).unwrap();
+        assert_eq!(app_to_app_server, app_to_app_server2);
+
+        let app_to_app_server = AppToAppServer {
+            app_request_id: Uid::from(&[5; UID_LEN]),
+            app_request: AppRequest::SetAcceptSpontaneousPayments(true),
+        };
+
+        let data = serialize_app_to_app_server(&app_to_app_server);
+        let app_to_app_server2 = deserialize_app_to_app_server(&data).unwrap();
+        assert_eq!(app_to_app_server, app_to_app_server2);
+    }
+
+    #[test]
+    fn test_serialize_response_liquidity() {
+        let liquidity = Liquidity {
+            neighbors: vec![
+                NeighborLiquidity {
+                    public_key: PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]),
+                    recv_capacity: 100,
+                },
+                NeighborLiquidity {
+                    public_key: PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+                    recv_capacity: 50,
+                },
+            ],
+            hops: vec![
+                HopsLiquidity {
+                    hops: 1,
+                    num_nodes: 2,
+                    recv_capacity: 150,
+                },
+                HopsLiquidity {
+                    hops: 2,
+                    num_nodes: 3,
+                    recv_capacity: 40,
+                },
+            ],
+        };
+        let client_response_liquidity = ClientResponseLiquidity {
+            request_id: Uid::from(&[2; UID_LEN]),
+            result: ResponseLiquidityResult::Success(liquidity),
+        };
+        let app_server_to_app = AppServerToApp::ResponseLiquidity(client_response_liquidity);
+
+        let data = serialize_app_server_to_app(&app_server_to_app);
+        let app_server_to_app2 = deserialize_app_server_to_app(&data).unwrap();
+        assert_eq!(app_server_to_app, app_server_to_app2);
+
+        let app_to_app_server = AppToAppServer {
+            app_request_id: Uid::from(&[3; UID_LEN]),
+            app_request: AppRequest::RequestLiquidity(RequestLiquidity {
+                request_id: Uid::from(&[4; UID_LEN]),
+                destination: PublicKey::from(&[0xcc; PUBLIC_KEY_LEN]),
+            }),
+        };
+
+        let data = serialize_app_to_app_server(&app_to_app_server);
+        let app_to_app_server2 = deserialize_app_to_app_server(&data).unwrap();
+        assert_eq!(app_to_app_server, app_to_app_server2);
+    }
+
+    #[test]
+    fn test_serialize_transaction_result() {
+        let results = vec![
+            RequestResult::Failure(RequestFailure {
+                reporting_public_key: PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]),
+                reason: FailureReason::NoCapacity,
+            }),
+            RequestResult::UnknownFailure,
+        ];
+
+        for result in results {
+            let transaction_result = TransactionResult {
+                request_id: Uid::from(&[1; UID_LEN]),
+                result,
+            };
+            let app_server_to_app = AppServerToApp::TransactionResult(transaction_result);
+
+            let data = serialize_app_server_to_app(&app_server_to_app);
+            let app_server_to_app2 = deserialize_app_server_to_app(&data).unwrap();
+            assert_eq!(app_server_to_app, app_server_to_app2);
+        }
+    }
+
+    #[test]
+    fn test_serialize_create_rebalance() {
+        let local_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let create_rebalance = CreateRebalance {
+            payment_id: PaymentId::from(&[1; PAYMENT_ID_LEN]),
+            invoice_id: InvoiceId::from(&[2; INVOICE_ID_LEN]),
+            request_id: Uid::from(&[3; UID_LEN]),
+            route: FriendsRoute {
+                public_keys: vec![
+                    local_public_key.clone(),
+                    PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]),
+                    PublicKey::from(&[0xcc; PUBLIC_KEY_LEN]),
+                    local_public_key,
+                ],
+            },
+            dest_payment: 100,
+            fees: 5,
+        };
+        let app_to_app_server = AppToAppServer {
+            app_request_id: Uid::from(&[4; UID_LEN]),
+            app_request: AppRequest::CreateRebalance(create_rebalance),
+        };
+
+        let data = serialize_app_to_app_server(&app_to_app_server);
+        let app_to_app_server2 = deserialize_app_to_app_server(&data).unwrap();
+        assert_eq!(app_to_app_server, app_to_app_server2);
+    }
+
+    #[test]
+    fn test_serialize_invoice_status() {
+        let app_to_app_server = AppToAppServer {
+            app_request_id: Uid::from(&[1; UID_LEN]),
+            app_request: AppRequest::AddInvoice(AddInvoice {
+                invoice_id: InvoiceId::from(&[2; INVOICE_ID_LEN]),
+                total_dest_payment: 100,
+                opt_expiry_ticks: Some(30),
+            }),
+        };
+
+        let data = serialize_app_to_app_server(&app_to_app_server);
+        let app_to_app_server2 = deserialize_app_to_app_server(&data).unwrap();
+        assert_eq!(app_to_app_server, app_to_app_server2);
+
+        let app_to_app_server = AppToAppServer {
+            app_request_id: Uid::from(&[3; UID_LEN]),
+            app_request: AppRequest::RequestInvoiceStatus(InvoiceId::from(&[2; INVOICE_ID_LEN])),
+        };
+
+        let data = serialize_app_to_app_server(&app_to_app_server);
+        let app_to_app_server2 = deserialize_app_to_app_server(&data).unwrap();
+        assert_eq!(app_to_app_server, app_to_app_server2);
+
+        let open_invoice_status = OpenInvoiceStatus {
+            total_dest_payment: 100,
+            transactions: vec![
+                InvoiceTransaction {
+                    request_id: Uid::from(&[4; UID_LEN]),
+                    dest_hashed_lock: HashedLock::from(&[7; HASHED_LOCK_LEN]),
+                    dest_payment: 60,
+                },
+                InvoiceTransaction {
+                    request_id: Uid::from(&[5; UID_LEN]),
+                    dest_hashed_lock: HashedLock::from(&[8; HASHED_LOCK_LEN]),
+                    dest_payment: 15,
+                },
+            ],
+            collected_dest_payment: 75,
+            opt_ticks_left: Some(12),
+        };
+        let response_invoice_status = ResponseInvoiceStatus {
+            invoice_id: InvoiceId::from(&[2; INVOICE_ID_LEN]),
+            status: InvoiceStatus::Open(open_invoice_status),
+        };
+        let app_server_to_app = AppServerToApp::ResponseInvoiceStatus(response_invoice_status);
+
+        let data = serialize_app_server_to_app(&app_server_to_app);
+        let app_server_to_app2 = deserialize_app_server_to_app(&data).unwrap();
+        assert_eq!(app_server_to_app, app_server_to_app2);
+
+        let response_invoice_status = ResponseInvoiceStatus {
+            invoice_id: InvoiceId::from(&[6; INVOICE_ID_LEN]),
+            status: InvoiceStatus::InvoiceNotFound,
+        };
+        let app_server_to_app = AppServerToApp::ResponseInvoiceStatus(response_invoice_status);
+
+        let data = serialize_app_server_to_app(&app_server_to_app);
+        let app_server_to_app2 = deserialize_app_server_to_app(&data).unwrap();
+        assert_eq!(app_server_to_app, app_server_to_app2);
+
+        let invoice_paid = InvoicePaid {
+            invoice_id: InvoiceId::from(&[2; INVOICE_ID_LEN]),
+            total_dest_payment: 100,
+            transactions: vec![InvoiceTransaction {
+                request_id: Uid::from(&[4; UID_LEN]),
+                dest_hashed_lock: HashedLock::from(&[7; HASHED_LOCK_LEN]),
+                dest_payment: 100,
+            }],
+        };
+        let app_server_to_app = AppServerToApp::InvoicePaid(invoice_paid);
+
+        let data = serialize_app_server_to_app(&app_server_to_app);
+        let app_server_to_app2 = deserialize_app_server_to_app(&data).unwrap();
+        assert_eq!(app_server_to_app, app_server_to_app2);
+    }
+
+    #[test]
+    fn test_serialize_sign_invoice() {
+        let invoice_details = InvoiceDetails {
+            invoice_id: InvoiceId::from(&[2; INVOICE_ID_LEN]),
+            total_dest_payment: 100,
+            memo: "Two apples".to_owned(),
+            opt_expiry: Some(1_560_000_000),
+            relays: vec![RelayAddress {
+                public_key: PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]),
+                address: "127.0.0.1:1337".to_owned().try_into().unwrap(),
+            }],
+        };
+        let app_to_app_server = AppToAppServer {
+            app_request_id: Uid::from(&[1; UID_LEN]),
+            app_request: AppRequest::RequestSignInvoice(invoice_details),
+        };
+
+        let data = serialize_app_to_app_server(&app_to_app_server);
+        let app_to_app_server2 = deserialize_app_to_app_server(&data).unwrap();
+        assert_eq!(app_to_app_server, app_to_app_server2);
+
+        for opt_signature in [None, Some(Signature::from(&[3; SIGNATURE_LEN]))]
+            .iter()
+            .cloned()
+        {
+            let response_sign_invoice = ResponseSignInvoice {
+                invoice_id: InvoiceId::from(&[2; INVOICE_ID_LEN]),
+                opt_signature,
+            };
+            let app_server_to_app = AppServerToApp::ResponseSignInvoice(response_sign_invoice);
+
+            let data = serialize_app_server_to_app(&app_server_to_app);
+            let app_server_to_app2 = deserialize_app_server_to_app(&data).unwrap();
+            assert_eq!(app_server_to_app, app_server_to_app2);
+        }
     }
 
     // TODO: More tests are required here
@@ -24,10 +24,22 @@ pub const CONN_TIMEOUT_TICKS: usize = 4;
 /// length for such frame, measured in bytes.
 pub const MAX_FRAME_LENGTH: usize = 1 << 20; // 1[MB]
 
+/// Amount of ticks a buyer waits for a payment to complete before the payment times out.
+pub const PAYMENT_TIMEOUT_TICKS: usize = 5 * 60 * (1000 / TICK_MS); // 5 minutes
+
 /// Index server: The amount of ticks it takes for an idle node to be removed from the
 /// index server database.
 pub const INDEX_NODE_TIMEOUT_TICKS: usize = 60 * (1000 / TICK_MS); // 1 minute
 
+/// When connecting to a NetAddress that resolves to multiple addresses: The amount of ticks to
+/// wait before also attempting the next address, while previous attempts are still in progress.
+/// RFC 8305 suggests a delay of about 250 milliseconds. Ticks are coarser than that, so we wait
+/// one second instead.
+pub const CONNECT_ATTEMPT_DELAY_TICKS: usize = 1000 / TICK_MS; // 1 second
+
+/// The amount of ticks we are willing to wait for a single TCP connection attempt.
+pub const CONNECT_ATTEMPT_TIMEOUT_TICKS: usize = 10 * (1000 / TICK_MS); // 10 seconds
+
 /// Maximum length for an address string used in NetAddress
 pub const MAX_NET_ADDRESS_LENGTH: usize = 256;
 
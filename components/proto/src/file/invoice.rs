@@ -0,0 +1,234 @@
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use toml;
+
+use crypto::identity::{PublicKey, Signature};
+use net::messages::NetAddressError;
+
+use crate::file::ser_string::{
+    invoice_id_to_string, public_key_to_string, signature_to_string, string_to_invoice_id,
+    string_to_public_key, string_to_signature, SerStringError,
+};
+
+use crate::app_server::messages::RelayAddress;
+use crate::file::relay::RelayFile;
+use crate::funder::messages::InvoiceDetails;
+use crate::funder::signature_buff::verify_invoice_signature;
+
+/// An invoice document, issued by a seller and sent to a buyer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedInvoice {
+    /// Public key of the seller's node. Payments for this invoice are sent to this public key.
+    pub dest_public_key: PublicKey,
+    pub details: InvoiceDetails,
+    /// Signature by the seller's node over dest_public_key and details.
+    pub signature: Signature,
+}
+
+impl SignedInvoice {
+    /// Check if the invoice has expired.
+    /// `now` is the current time, in seconds since the UNIX epoch.
+    pub fn is_expired(&self, now: u64) -> bool {
+        match self.details.opt_expiry {
+            Some(expiry) => now >= expiry,
+            None => false,
+        }
+    }
+}
+
+/// Verify that a signed invoice was issued by the seller (dest_public_key)
+pub fn verify_signed_invoice(signed_invoice: &SignedInvoice) -> bool {
+    verify_invoice_signature(
+        &signed_invoice.dest_public_key,
+        &signed_invoice.details,
+        &signed_invoice.signature,
+    )
+}
+
+#[derive(Debug, From)]
+pub enum InvoiceFileError {
+    IoError(io::Error),
+    TomlDeError(toml::de::Error),
+    TomlSeError(toml::ser::Error),
+    SerStringError,
+    ParseTotalDestPaymentError,
+    NetAddressError(NetAddressError),
+}
+
+impl From<SerStringError> for InvoiceFileError {
+    fn from(_e: SerStringError) -> Self {
+        InvoiceFileError::SerStringError
+    }
+}
+
+/// A helper structure for serialize and deserializing SignedInvoice.
+#[derive(Serialize, Deserialize)]
+struct InvoiceFile {
+    invoice_id: String,
+    dest_public_key: String,
+    total_dest_payment: String,
+    memo: String,
+    /// Seconds since the UNIX epoch
+    expiry: Option<u64>,
+    signature: String,
+    relays: Vec<RelayFile>,
+}
+
+/// Load SignedInvoice from a file
+/// Note that the signature is not verified. Use `verify_signed_invoice` to verify it.
+pub fn load_invoice_from_file(path: &Path) -> Result<SignedInvoice, InvoiceFileError> {
+    let data = fs::read_to_string(&path)?;
+    let invoice_file: InvoiceFile = toml::from_str(&data)?;
+
+    let total_dest_payment = invoice_file
+        .total_dest_payment
+        .parse()
+        .map_err(|_| InvoiceFileError::ParseTotalDestPaymentError)?;
+
+    let mut relays = Vec::new();
+    for relay_file in invoice_file.relays {
+        relays.push(RelayAddress {
+            public_key: string_to_public_key(&relay_file.public_key)?,
+            address: relay_file.address.try_into()?,
+        });
+    }
+
+    Ok(SignedInvoice {
+        dest_public_key: string_to_public_key(&invoice_file.dest_public_key)?,
+        details: InvoiceDetails {
+            invoice_id: string_to_invoice_id(&invoice_file.invoice_id)?,
+            total_dest_payment,
+            memo: invoice_file.memo,
+            opt_expiry: invoice_file.expiry,
+            relays,
+        },
+        signature: string_to_signature(&invoice_file.signature)?,
+    })
+}
+
+/// Store SignedInvoice to file
+pub fn store_invoice_to_file(
+    signed_invoice: &SignedInvoice,
+    path: &Path,
+) -> Result<(), InvoiceFileError> {
+    let SignedInvoice {
+        ref dest_public_key,
+        ref details,
+        ref signature,
+    } = signed_invoice;
+
+    let relay_files = details
+        .relays
+        .iter()
+        .map(|relay_address| RelayFile {
+            public_key: public_key_to_string(&relay_address.public_key),
+            address: relay_address.address.as_str().to_string(),
+        })
+        .collect();
+
+    let invoice_file = InvoiceFile {
+        invoice_id: invoice_id_to_string(&details.invoice_id),
+        dest_public_key: public_key_to_string(dest_public_key),
+        total_dest_payment: details.total_dest_payment.to_string(),
+        memo: details.memo.clone(),
+        expiry: details.opt_expiry,
+        signature: signature_to_string(signature),
+        relays: relay_files,
+    };
+
+    let data = toml::to_string(&invoice_file)?;
+
+    let mut file = File::create(path)?;
+    file.write_all(&data.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    use crypto::identity::{
+        generate_pkcs8_key_pair, Identity, SoftwareEd25519Identity, PUBLIC_KEY_LEN,
+    };
+    use crypto::invoice_id::{InvoiceId, INVOICE_ID_LEN};
+    use crypto::test_utils::DummyRandom;
+
+    use crate::funder::signature_buff::create_invoice_signature_buffer;
+
+    fn create_signed_invoice(opt_expiry: Option<u64>) -> SignedInvoice {
+        let rng = DummyRandom::new(&[1u8]);
+        let pkcs8 = generate_pkcs8_key_pair(&rng);
+        let identity = SoftwareEd25519Identity::from_pkcs8(&pkcs8).unwrap();
+
+        let details = InvoiceDetails {
+            invoice_id: InvoiceId::from(&[1; INVOICE_ID_LEN]),
+            total_dest_payment: 100,
+            memo: "Two apples".to_owned(),
+            opt_expiry,
+            relays: vec![RelayAddress {
+                public_key: PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]),
+                address: "127.0.0.1:1337".to_owned().try_into().unwrap(),
+            }],
+        };
+        let dest_public_key = identity.get_public_key();
+        let signature = identity.sign(&create_invoice_signature_buffer(&dest_public_key, &details));
+
+        SignedInvoice {
+            dest_public_key,
+            details,
+            signature,
+        }
+    }
+
+    #[test]
+    fn test_store_load_invoice() {
+        // Create a temporary directory:
+        let dir = tempdir().unwrap();
+
+        for opt_expiry in &[None, Some(1_560_000_000)] {
+            let file_path = dir.path().join("invoice_file");
+            let signed_invoice = create_signed_invoice(*opt_expiry);
+
+            store_invoice_to_file(&signed_invoice, &file_path).unwrap();
+            let signed_invoice2 = load_invoice_from_file(&file_path).unwrap();
+
+            assert_eq!(signed_invoice, signed_invoice2);
+        }
+    }
+
+    #[test]
+    fn test_verify_signed_invoice() {
+        let signed_invoice = create_signed_invoice(None);
+        assert!(verify_signed_invoice(&signed_invoice));
+
+        // Forged memo:
+        let mut forged_invoice = signed_invoice.clone();
+        forged_invoice.details.memo = "Two oranges".to_owned();
+        assert!(!verify_signed_invoice(&forged_invoice));
+
+        // Forged amount:
+        let mut forged_invoice = signed_invoice.clone();
+        forged_invoice.details.total_dest_payment = 101;
+        assert!(!verify_signed_invoice(&forged_invoice));
+
+        // Forged seller:
+        let mut forged_invoice = signed_invoice.clone();
+        forged_invoice.dest_public_key = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+        assert!(!verify_signed_invoice(&forged_invoice));
+    }
+
+    #[test]
+    fn test_signed_invoice_is_expired() {
+        assert!(!create_signed_invoice(None).is_expired(u64::max_value()));
+
+        let signed_invoice = create_signed_invoice(Some(1000));
+        assert!(!signed_invoice.is_expired(999));
+        assert!(signed_invoice.is_expired(1000));
+        assert!(signed_invoice.is_expired(1001));
+    }
+}
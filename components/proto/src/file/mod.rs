@@ -2,6 +2,7 @@ pub mod app;
 pub mod friend;
 pub mod identity;
 pub mod index_server;
+pub mod invoice;
 pub mod node;
 pub mod relay;
 pub mod ser_string;
@@ -1,10 +1,12 @@
 use base64::{self, URL_SAFE_NO_PAD};
 use crypto::crypto_rand::{RandValue, RAND_VALUE_LEN};
+use crypto::dh::{DhPublicKey, DH_PUBLIC_KEY_LEN};
 use crypto::hash::{HashResult, HASH_RESULT_LEN};
 use crypto::hash_lock::{HashedLock, PlainLock, HASHED_LOCK_LEN, PLAIN_LOCK_LEN};
 use crypto::identity::{PublicKey, Signature, PUBLIC_KEY_LEN, SIGNATURE_LEN};
 use crypto::invoice_id::{InvoiceId, INVOICE_ID_LEN};
 use crypto::payment_id::{PaymentId, PAYMENT_ID_LEN};
+use crypto::uid::{Uid, UID_LEN};
 
 // TODO: Possibly remove this module into offst-crypto
 // Will require the extra base64 dependency in offst-crypto.
@@ -43,6 +45,13 @@ str_convert_funcs!(
     PUBLIC_KEY_LEN
 );
 
+str_convert_funcs!(
+    dh_public_key_to_string,
+    string_to_dh_public_key,
+    DhPublicKey,
+    DH_PUBLIC_KEY_LEN
+);
+
 str_convert_funcs!(
     signature_to_string,
     string_to_signature,
@@ -92,6 +101,18 @@ str_convert_funcs!(
     HASHED_LOCK_LEN
 );
 
+str_convert_funcs!(uid_to_string, string_to_uid, Uid, UID_LEN);
+
+/// Convert bytes of arbitrary length (For example, an encrypted message) into a string
+pub fn bytes_to_string(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, URL_SAFE_NO_PAD)
+}
+
+/// Convert a string into bytes of arbitrary length
+pub fn string_to_bytes(input_str: &str) -> Result<Vec<u8>, SerStringError> {
+    base64::decode_config(input_str, URL_SAFE_NO_PAD).map_err(|_| SerStringError)
+}
+
 // TODO: How to make the macro work nicely with the private key conversion code?
 
 // TODO: Find a better way to represent private key.
@@ -5,6 +5,7 @@ use num_bigint::BigUint;
 use num_traits::cast::ToPrimitive;
 
 use crypto::crypto_rand::RandValue;
+use crypto::dh::DhPublicKey;
 use crypto::hash::{self, HashResult};
 use crypto::hash_lock::{HashedLock, PlainLock};
 use crypto::identity::{PublicKey, Signature};
@@ -68,6 +69,10 @@ pub struct FriendsRoute {
 pub struct RequestSendFundsOp {
     pub request_id: Uid,
     pub src_hashed_lock: HashedLock,
+    /// The plain lock of src_hashed_lock, encrypted to the destination.
+    /// Sent only for spontaneous payments. It allows the destination to collect the payment
+    /// without waiting for a Commit.
+    pub opt_encrypted_src_plain_lock: Option<Vec<u8>>,
     pub route: FriendsRoute,
     pub dest_payment: u128,
     pub total_dest_payment: u128,
@@ -83,9 +88,40 @@ pub struct ResponseSendFundsOp<S = Signature> {
     pub signature: S,
 }
 
+/// The reason a request to send funds could not be completed.
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub enum FailureReason {
+    /// The destination has no matching open invoice.
+    /// (Or it does not accept spontaneous payments)
+    UnknownInvoice,
+    /// The next node on the route is not a friend, or it is offline.
+    FriendNotReady,
+    /// The next node on the route does not accept requests.
+    RequestsClosed,
+    /// The fees left in the request are not enough to pay the reporting node.
+    InsufficientFees,
+    /// Not enough trust (capacity) with the next node on the route.
+    NoCapacity,
+    /// The route is malformed.
+    InvalidRoute,
+    /// The route has more than `MAX_ROUTE_LEN` nodes.
+    RouteTooLong,
+    /// The token channel with the next node on the route was reset, or the friend was removed.
+    ChannelReset,
+    /// The payment of this request did not complete in time.
+    TimedOut,
+    /// The request was rejected locally (For example, the pending requests queue is full).
+    Rejected,
+}
+
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct CancelSendFundsOp {
     pub request_id: Uid,
+    /// The node that canceled the request.
+    /// Note that this field is not signed, so it can not be fully trusted.
+    pub reporting_public_key: PublicKey,
+    /// Note that this field is not signed either.
+    pub reason: FailureReason,
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -199,6 +235,7 @@ pub struct PendingTransaction {
     pub invoice_id: InvoiceId,
     pub left_fees: u128,
     pub src_hashed_lock: HashedLock,
+    pub opt_encrypted_src_plain_lock: Option<Vec<u8>>,
     pub stage: TransactionStage,
 }
 
@@ -218,6 +255,39 @@ impl CanonicalSerialize for RequestSendFundsOp {
         // We do not sign over`left_fees`, because this field changes as the request message is
         // forwarded.
         // res_bytes.write_u128::<BigEndian>(self.left_fees).unwrap();
+
+        // The encrypted plain lock is appended only if it exists, so that requests without it
+        // serialize exactly as before:
+        if let Some(encrypted_src_plain_lock) = &self.opt_encrypted_src_plain_lock {
+            res_bytes
+                .write_u64::<BigEndian>(usize_to_u64(encrypted_src_plain_lock.len()).unwrap())
+                .unwrap();
+            res_bytes.extend_from_slice(encrypted_src_plain_lock);
+        }
+        res_bytes
+    }
+}
+
+impl<B> CanonicalSerialize for InvoiceDetails<B>
+where
+    B: CanonicalSerialize,
+{
+    fn canonical_serialize(&self) -> Vec<u8> {
+        let mut res_bytes = Vec::new();
+        res_bytes.extend_from_slice(&self.invoice_id);
+        res_bytes
+            .write_u128::<BigEndian>(self.total_dest_payment)
+            .unwrap();
+        // The memo is of variable length, so we use its hash:
+        res_bytes.extend_from_slice(&hash::sha_512_256(self.memo.as_bytes()));
+        match self.opt_expiry {
+            None => res_bytes.push(0),
+            Some(expiry) => {
+                res_bytes.push(1);
+                res_bytes.write_u64::<BigEndian>(expiry).unwrap();
+            }
+        }
+        res_bytes.extend_from_slice(&self.relays.canonical_serialize());
         res_bytes
     }
 }
@@ -236,6 +306,8 @@ impl CanonicalSerialize for ResponseSendFundsOp {
 impl CanonicalSerialize for CancelSendFundsOp {
     fn canonical_serialize(&self) -> Vec<u8> {
         let mut res_bytes = Vec::new();
+        // reporting_public_key and reason are informational only. They are left out, so that
+        // the signed move token format stays the same as before they were added:
         res_bytes.extend_from_slice(&self.request_id);
         res_bytes
     }
@@ -266,7 +338,13 @@ impl CanonicalSerialize for FriendTcOp {
                 res_bytes.write_u128::<BigEndian>(*remote_max_debt).unwrap();
             }
             FriendTcOp::RequestSendFunds(request_send_funds) => {
-                res_bytes.push(3u8);
+                // A request with an encrypted plain lock has its own tag, so that it can not be
+                // confused with a request without it followed by other operations:
+                if request_send_funds.opt_encrypted_src_plain_lock.is_some() {
+                    res_bytes.push(7u8);
+                } else {
+                    res_bytes.push(3u8);
+                }
                 res_bytes.append(&mut request_send_funds.canonical_serialize())
             }
             FriendTcOp::ResponseSendFunds(response_send_funds) => {
@@ -539,6 +617,16 @@ pub struct CreatePayment {
     pub invoice_id: InvoiceId,
     pub total_dest_payment: u128,
     pub dest_public_key: PublicKey,
+    /// A spontaneous payment does not require an invoice from the destination.
+    /// The invoice_id is generated by the buyer, and the destination collects the credits
+    /// once the whole payment arrived, without waiting for a Commit. The destination must accept
+    /// spontaneous payments.
+    /// The src_plain_lock of every transaction is encrypted to the destination's advertised
+    /// DH public key (See FunderReport::local_dh_public_key).
+    pub opt_dest_dh_public_key: Option<DhPublicKey>,
+    /// Amount of timer ticks after which the payment times out.
+    /// If not specified, the node's default payment timeout is used.
+    pub opt_deadline_ticks: Option<u64>,
 }
 
 /// Start a payment, possibly by paying through multiple routes.
@@ -554,6 +642,24 @@ pub struct CreateTransaction {
     pub fees: u128,
 }
 
+/// Move credits between two of our friends, by paying ourselves along a circular route:
+/// We pay the first friend on the route, and receive the payment back from the last friend on
+/// the route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateRebalance {
+    /// Randomly generated payment_id (by the user). Used to close the payment later.
+    pub payment_id: PaymentId,
+    /// Randomly generated invoice_id (by the user). A matching invoice is opened locally.
+    pub invoice_id: InvoiceId,
+    /// Randomly generated request_id (by the user). The result will be sent back as a
+    /// TransactionResult with this request_id.
+    pub request_id: Uid,
+    /// A circular route: Starts and ends with the local public key.
+    pub route: FriendsRoute,
+    pub dest_payment: u128,
+    pub fees: u128,
+}
+
 /// Start an invoice (A request for payment).
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AddInvoice {
@@ -561,6 +667,25 @@ pub struct AddInvoice {
     pub invoice_id: InvoiceId,
     /// Total amount of credits to be paid.
     pub total_dest_payment: u128,
+    /// Amount of timer ticks after which the invoice is canceled automatically.
+    /// None means that the invoice never expires.
+    pub opt_expiry_ticks: Option<u64>,
+}
+
+/// The details of an invoice, as presented to a buyer.
+/// The seller's node signs over these details (together with its public key), allowing the buyer
+/// to verify that the invoice was issued by the seller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvoiceDetails<B = NetAddress> {
+    pub invoice_id: InvoiceId,
+    pub total_dest_payment: u128,
+    /// A free text description of the invoice (What is being paid for).
+    pub memo: String,
+    /// Time (In seconds since the UNIX epoch) after which the invoice should not be paid.
+    /// None means that the invoice never expires.
+    pub opt_expiry: Option<u64>,
+    /// Relays of the seller. Allows the buyer to reach the seller.
+    pub relays: Vec<RelayAddress<B>>,
 }
 
 /// Start an invoice (A request for payment).
@@ -576,6 +701,7 @@ pub enum FunderControl<B> {
     RemoveRelay(PublicKey),
     AddFriend(AddFriend<B>),
     RemoveFriend(RemoveFriend),
+    SetAcceptSpontaneousPayments(bool),
     SetRequestsStatus(SetRequestsStatus),
     SetFriendStatus(SetFriendStatus),
     SetFriendRemoteMaxDebt(SetFriendRemoteMaxDebt),
@@ -588,10 +714,13 @@ pub enum FunderControl<B> {
     CreateTransaction(CreateTransaction), // TODO
     RequestClosePayment(PaymentId),
     AckClosePayment(AckClosePayment),
+    CreateRebalance(CreateRebalance),
     // Seller API:
     AddInvoice(AddInvoice),
     CancelInvoice(InvoiceId),
     CommitInvoice(MultiCommit),
+    RequestInvoiceStatus(InvoiceId),
+    RequestSignInvoice(InvoiceDetails<B>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -631,11 +760,19 @@ impl UserRequestSendFunds {
     */
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestFailure {
+    /// The node that reported the failure (Possibly the local node).
+    pub reporting_public_key: PublicKey,
+    pub reason: FailureReason,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RequestResult {
     Success(Commit),
-    // TODO: Should we add more information to the failure here?
-    Failure,
+    Failure(RequestFailure),
+    /// A failure without details. Only reported by older nodes.
+    UnknownFailure,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -651,6 +788,7 @@ pub enum PaymentStatus {
     InProgress,              // Can not be acked
     Success((Receipt, Uid)), // (Receipt, ack_id)
     Canceled(Uid),           // ack_id
+    TimedOut(Uid),           // ack_id
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -659,10 +797,67 @@ pub struct ResponseClosePayment {
     pub status: PaymentStatus,
 }
 
+/// A transaction that was received for an open invoice
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvoiceTransaction {
+    pub request_id: Uid,
+    /// The lock we used on our ResponseSendFundsOp message for this transaction.
+    pub dest_hashed_lock: HashedLock,
+    pub dest_payment: u128,
+}
+
+/// The current state of an open invoice
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenInvoiceStatus {
+    /// Total amount of credits to be paid.
+    pub total_dest_payment: u128,
+    /// Transactions received so far for this invoice.
+    pub transactions: Vec<InvoiceTransaction>,
+    /// Sum of the dest_payment of all received transactions.
+    pub collected_dest_payment: u128,
+    /// Amount of timer ticks left until the invoice is canceled.
+    /// None means that the invoice never expires.
+    pub opt_ticks_left: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvoiceStatus {
+    /// The invoice does not exist. It might have been committed, canceled or expired.
+    InvoiceNotFound,
+    Open(OpenInvoiceStatus),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseInvoiceStatus {
+    pub invoice_id: InvoiceId,
+    pub status: InvoiceStatus,
+}
+
+/// Incoming transactions for an open invoice have reached its total_dest_payment.
+/// The seller may now ask the buyer for a MultiCommit.
+/// The credits of a spontaneous payment are collected right away, without a MultiCommit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvoicePaid {
+    pub invoice_id: InvoiceId,
+    pub total_dest_payment: u128,
+    pub transactions: Vec<InvoiceTransaction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseSignInvoice {
+    pub invoice_id: InvoiceId,
+    /// None if the node refused to sign. The node only signs the details of an open invoice with
+    /// a matching total_dest_payment.
+    pub opt_signature: Option<Signature>,
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum FunderOutgoingControl<B: Clone> {
     TransactionResult(TransactionResult),
     ResponseClosePayment(ResponseClosePayment),
+    ResponseInvoiceStatus(ResponseInvoiceStatus),
+    ResponseSignInvoice(ResponseSignInvoice),
+    InvoicePaid(InvoicePaid),
     ReportMutations(FunderReportMutations<B>),
 }
@@ -13,8 +13,8 @@ use std::io;
 use funder_capnp;
 
 use super::messages::{
-    CancelSendFundsOp, CollectSendFundsOp, FriendMessage, FriendTcOp, FriendsRoute, MoveToken,
-    MoveTokenRequest, RequestSendFundsOp, ResetTerms, ResponseSendFundsOp,
+    CancelSendFundsOp, CollectSendFundsOp, FailureReason, FriendMessage, FriendTcOp, FriendsRoute,
+    MoveToken, MoveTokenRequest, RequestSendFundsOp, ResetTerms, ResponseSendFundsOp,
 };
 
 use crate::serialize::SerializeError;
@@ -76,6 +76,15 @@ fn ser_request_send_funds_op(
         request_send_funds.left_fees,
         &mut request_send_funds_op_builder.reborrow().init_left_fees(),
     );
+
+    let mut opt_encrypted_src_plain_lock_builder = request_send_funds_op_builder
+        .reborrow()
+        .init_opt_encrypted_src_plain_lock();
+    match &request_send_funds.opt_encrypted_src_plain_lock {
+        Some(encrypted_src_plain_lock) => opt_encrypted_src_plain_lock_builder
+            .set_encrypted_src_plain_lock(encrypted_src_plain_lock),
+        None => opt_encrypted_src_plain_lock_builder.set_empty(()),
+    }
 }
 
 fn ser_response_send_funds_op(
@@ -104,6 +113,24 @@ fn ser_response_send_funds_op(
     );
 }
 
+pub fn ser_failure_reason(
+    failure_reason: &FailureReason,
+    failure_reason_builder: &mut funder_capnp::failure_reason::Builder,
+) {
+    match failure_reason {
+        FailureReason::UnknownInvoice => failure_reason_builder.set_unknown_invoice(()),
+        FailureReason::FriendNotReady => failure_reason_builder.set_friend_not_ready(()),
+        FailureReason::RequestsClosed => failure_reason_builder.set_requests_closed(()),
+        FailureReason::InsufficientFees => failure_reason_builder.set_insufficient_fees(()),
+        FailureReason::NoCapacity => failure_reason_builder.set_no_capacity(()),
+        FailureReason::InvalidRoute => failure_reason_builder.set_invalid_route(()),
+        FailureReason::RouteTooLong => failure_reason_builder.set_route_too_long(()),
+        FailureReason::ChannelReset => failure_reason_builder.set_channel_reset(()),
+        FailureReason::TimedOut => failure_reason_builder.set_timed_out(()),
+        FailureReason::Rejected => failure_reason_builder.set_rejected(()),
+    }
+}
+
 fn ser_cancel_send_funds_op(
     cancel_send_funds: &CancelSendFundsOp,
     cancel_send_funds_op_builder: &mut funder_capnp::cancel_send_funds_op::Builder,
@@ -112,6 +139,16 @@ fn ser_cancel_send_funds_op(
         &cancel_send_funds.request_id,
         &mut cancel_send_funds_op_builder.reborrow().init_request_id(),
     );
+    write_public_key(
+        &cancel_send_funds.reporting_public_key,
+        &mut cancel_send_funds_op_builder
+            .reborrow()
+            .init_reporting_public_key(),
+    );
+    ser_failure_reason(
+        &cancel_send_funds.reason,
+        &mut cancel_send_funds_op_builder.reborrow().init_reason(),
+    );
 }
 
 fn ser_collect_send_funds_op(
@@ -321,9 +358,20 @@ pub fn deser_friends_route(
 fn deser_request_send_funds_op(
     request_send_funds_op_reader: &funder_capnp::request_send_funds_op::Reader,
 ) -> Result<RequestSendFundsOp, SerializeError> {
+    let opt_encrypted_src_plain_lock = match request_send_funds_op_reader
+        .get_opt_encrypted_src_plain_lock()
+        .which()?
+    {
+        funder_capnp::request_send_funds_op::opt_encrypted_src_plain_lock::Empty(()) => None,
+        funder_capnp::request_send_funds_op::opt_encrypted_src_plain_lock::EncryptedSrcPlainLock(
+            encrypted_src_plain_lock,
+        ) => Some(encrypted_src_plain_lock?.to_vec()),
+    };
+
     Ok(RequestSendFundsOp {
         request_id: read_uid(&request_send_funds_op_reader.get_request_id()?)?,
         src_hashed_lock: read_hashed_lock(&request_send_funds_op_reader.get_src_hashed_lock()?)?,
+        opt_encrypted_src_plain_lock,
         route: deser_friends_route(&request_send_funds_op_reader.get_route()?)?,
         dest_payment: read_custom_u_int128(&request_send_funds_op_reader.get_dest_payment()?)?,
         total_dest_payment: read_custom_u_int128(
@@ -345,11 +393,32 @@ fn deser_response_send_funds_op(
     })
 }
 
+pub fn deser_failure_reason(
+    failure_reason_reader: &funder_capnp::failure_reason::Reader,
+) -> Result<FailureReason, SerializeError> {
+    Ok(match failure_reason_reader.which()? {
+        funder_capnp::failure_reason::UnknownInvoice(()) => FailureReason::UnknownInvoice,
+        funder_capnp::failure_reason::FriendNotReady(()) => FailureReason::FriendNotReady,
+        funder_capnp::failure_reason::RequestsClosed(()) => FailureReason::RequestsClosed,
+        funder_capnp::failure_reason::InsufficientFees(()) => FailureReason::InsufficientFees,
+        funder_capnp::failure_reason::NoCapacity(()) => FailureReason::NoCapacity,
+        funder_capnp::failure_reason::InvalidRoute(()) => FailureReason::InvalidRoute,
+        funder_capnp::failure_reason::RouteTooLong(()) => FailureReason::RouteTooLong,
+        funder_capnp::failure_reason::ChannelReset(()) => FailureReason::ChannelReset,
+        funder_capnp::failure_reason::TimedOut(()) => FailureReason::TimedOut,
+        funder_capnp::failure_reason::Rejected(()) => FailureReason::Rejected,
+    })
+}
+
 fn deser_cancel_send_funds_op(
     cancel_send_funds_op_reader: &funder_capnp::cancel_send_funds_op::Reader,
 ) -> Result<CancelSendFundsOp, SerializeError> {
     Ok(CancelSendFundsOp {
         request_id: read_uid(&cancel_send_funds_op_reader.get_request_id()?)?,
+        reporting_public_key: read_public_key(
+            &cancel_send_funds_op_reader.get_reporting_public_key()?,
+        )?,
+        reason: deser_failure_reason(&cancel_send_funds_op_reader.get_reason()?)?,
     })
 }
 
@@ -499,6 +568,7 @@ mod tests {
         let request_send_funds = RequestSendFundsOp {
             request_id: Uid::from(&[22; UID_LEN]),
             src_hashed_lock: HashedLock::from(&[1u8; HASHED_LOCK_LEN]),
+            opt_encrypted_src_plain_lock: Some(vec![3u8; 124]),
             route,
             dest_payment: 48,
             total_dest_payment: 60,
@@ -514,6 +584,8 @@ mod tests {
 
         let cancel_send_funds = CancelSendFundsOp {
             request_id: Uid::from(&[10; UID_LEN]),
+            reporting_public_key: PublicKey::from(&[0x77; PUBLIC_KEY_LEN]),
+            reason: FailureReason::NoCapacity,
         };
 
         let collect_send_funds = CollectSendFundsOp {
@@ -2,19 +2,20 @@ use byteorder::{BigEndian, WriteBytesExt};
 
 use crypto::hash::{self, sha_512_256, HashResult};
 use crypto::hash_lock::PlainLock;
-use crypto::identity::{verify_signature, PublicKey};
+use crypto::identity::{verify_signature, PublicKey, Signature};
 use crypto::invoice_id::InvoiceId;
 
 use common::canonical_serialize::CanonicalSerialize;
 use common::int_convert::usize_to_u64;
 
 use super::messages::{
-    CollectSendFundsOp, Commit, MoveToken, MultiCommit, PendingTransaction, Receipt,
-    ResponseSendFundsOp,
+    CollectSendFundsOp, Commit, InvoiceDetails, MoveToken, MultiCommit, PendingTransaction,
+    Receipt, ResponseSendFundsOp,
 };
 
 pub const FUNDS_RESPONSE_PREFIX: &[u8] = b"FUND_RESPONSE";
 pub const FUNDS_CANCEL_PREFIX: &[u8] = b"FUND_CANCEL";
+pub const INVOICE_PREFIX: &[u8] = b"INVOICE";
 
 /// Create the buffer we sign over at the Response funds.
 /// Note that the signature is not just over the Response funds bytes. The signed buffer also
@@ -85,6 +86,34 @@ pub fn verify_receipt(receipt: &Receipt, public_key: &PublicKey) -> bool {
     verify_signature(&data, public_key, &receipt.signature)
 }
 
+/// Create the buffer the seller signs over when issuing an invoice document.
+pub fn create_invoice_signature_buffer<B>(
+    dest_public_key: &PublicKey,
+    invoice_details: &InvoiceDetails<B>,
+) -> Vec<u8>
+where
+    B: CanonicalSerialize,
+{
+    let mut sbuffer = Vec::new();
+    sbuffer.extend_from_slice(&hash::sha_512_256(INVOICE_PREFIX));
+    sbuffer.extend_from_slice(dest_public_key);
+    sbuffer.extend_from_slice(&invoice_details.canonical_serialize());
+    sbuffer
+}
+
+/// Verify that invoice details were signed by the seller (dest_public_key)
+pub fn verify_invoice_signature<B>(
+    dest_public_key: &PublicKey,
+    invoice_details: &InvoiceDetails<B>,
+    signature: &Signature,
+) -> bool
+where
+    B: CanonicalSerialize,
+{
+    let data = create_invoice_signature_buffer(dest_public_key, invoice_details);
+    verify_signature(&data, dest_public_key, signature)
+}
+
 /// Create a Commit (out of band) message given a ResponseSendFunds
 pub fn prepare_commit(
     response_send_funds: &ResponseSendFundsOp,
@@ -4,7 +4,10 @@ use crypto::identity::PublicKey;
 use crypto::uid::Uid;
 
 use crate::funder::messages::Rate;
-pub use crate::index_server::messages::{IndexMutation, RequestRoutes, UpdateFriend};
+pub use crate::index_server::messages::{
+    HopsLiquidity, IndexMutation, Liquidity, NeighborLiquidity, RequestLiquidity, RequestRoutes,
+    RouteSearchMode, UpdateFriend,
+};
 use crate::index_server::messages::{MultiRoute, NamedIndexServerAddress};
 
 #[derive(Debug, Clone)]
@@ -58,6 +61,18 @@ pub struct ClientResponseRoutes {
     pub result: ResponseRoutesResult,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseLiquidityResult {
+    Success(Liquidity),
+    Failure,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientResponseLiquidity {
+    pub request_id: Uid,
+    pub result: ResponseLiquidityResult,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IndexClientReportMutations<ISA> {
     pub opt_app_request_id: Option<Uid>,
@@ -68,6 +83,7 @@ pub struct IndexClientReportMutations<ISA> {
 pub enum IndexClientToAppServer<ISA> {
     ReportMutations(IndexClientReportMutations<ISA>),
     ResponseRoutes(ClientResponseRoutes),
+    ResponseLiquidity(ClientResponseLiquidity),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -75,6 +91,7 @@ pub enum IndexClientRequest<ISA> {
     AddIndexServer(NamedIndexServerAddress<ISA>),
     RemoveIndexServer(PublicKey),
     RequestRoutes(RequestRoutes),
+    RequestLiquidity(RequestLiquidity),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
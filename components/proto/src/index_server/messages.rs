@@ -6,6 +6,15 @@ use crypto::uid::Uid;
 use crate::funder::messages::{FriendsRoute, Rate};
 use crate::net::messages::NetAddress;
 
+/// How should the index server choose routes
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RouteSearchMode {
+    /// Routes with the least amount of hops
+    Shortest,
+    /// Routes with the least amount of fees for the requested capacity
+    Cheapest,
+}
+
 /// IndexClient -> IndexServer
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct RequestRoutes {
@@ -18,6 +27,7 @@ pub struct RequestRoutes {
     /// This directed edge must not show up any any route inside the multi-route.
     /// Useful for finding non trivial directed loops.
     pub opt_exclude: Option<(PublicKey, PublicKey)>,
+    pub search_mode: RouteSearchMode,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -45,6 +55,50 @@ pub struct ResponseRoutes {
     pub multi_routes: Vec<MultiRoute>,
 }
 
+/// IndexClient -> IndexServer
+/// Ask how many credits a node can currently receive.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RequestLiquidity {
+    pub request_id: Uid,
+    /// The node receiving credits
+    pub destination: PublicKey,
+}
+
+/// Inbound capacity from a direct neighbor
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NeighborLiquidity {
+    /// Public key of a direct neighbor of the destination
+    pub public_key: PublicKey,
+    /// Amount of credits the neighbor can send directly to the destination
+    pub recv_capacity: u128,
+}
+
+/// Inbound capacity from all the nodes at a certain distance from the destination
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HopsLiquidity {
+    /// Distance (in hops) from the destination
+    pub hops: u32,
+    /// Amount of nodes exactly `hops` hops away from the destination
+    pub num_nodes: u64,
+    /// Amount of credits all those nodes can send together to the destination
+    pub recv_capacity: u128,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Liquidity {
+    /// Inbound capacity from every direct neighbor
+    pub neighbors: Vec<NeighborLiquidity>,
+    /// Inbound capacity aggregated by hop distance, ordered by increasing distance
+    pub hops: Vec<HopsLiquidity>,
+}
+
+/// IndexServer -> IndexClient
+#[derive(Debug, Clone)]
+pub struct ResponseLiquidity {
+    pub request_id: Uid,
+    pub liquidity: Liquidity,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UpdateFriend {
     /// Friend's public key
@@ -118,12 +172,14 @@ pub struct ForwardMutationsUpdate {
 pub enum IndexServerToClient {
     TimeHash(HashResult),
     ResponseRoutes(ResponseRoutes),
+    ResponseLiquidity(ResponseLiquidity),
 }
 
 #[derive(Debug)]
 pub enum IndexClientToServer {
     MutationsUpdate(MutationsUpdate),
     RequestRoutes(RequestRoutes),
+    RequestLiquidity(RequestLiquidity),
 }
 
 #[derive(Debug)]
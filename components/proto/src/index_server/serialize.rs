@@ -10,9 +10,10 @@ use common::int_convert::usize_to_u32;
 use index_capnp;
 
 use super::messages::{
-    ForwardMutationsUpdate, IndexClientToServer, IndexMutation, IndexServerToClient,
-    IndexServerToServer, MultiRoute, MutationsUpdate, RequestRoutes, ResponseRoutes,
-    RouteCapacityRate, TimeProofLink, UpdateFriend,
+    ForwardMutationsUpdate, HopsLiquidity, IndexClientToServer, IndexMutation, IndexServerToClient,
+    IndexServerToServer, Liquidity, MultiRoute, MutationsUpdate, NeighborLiquidity,
+    RequestLiquidity, RequestRoutes, ResponseLiquidity, ResponseRoutes, RouteCapacityRate,
+    RouteSearchMode, TimeProofLink, UpdateFriend,
 };
 
 use crate::funder::serialize::{deser_friends_route, ser_friends_route};
@@ -57,6 +58,12 @@ pub fn ser_request_routes(
             opt_exclude_builder.set_empty(());
         }
     }
+
+    let mut search_mode_builder = request_routes_builder.reborrow().init_search_mode();
+    match &request_routes.search_mode {
+        RouteSearchMode::Shortest => search_mode_builder.set_shortest(()),
+        RouteSearchMode::Cheapest => search_mode_builder.set_cheapest(()),
+    }
 }
 
 pub fn deser_request_routes(
@@ -72,12 +79,18 @@ pub fn deser_request_routes(
         index_capnp::request_routes::opt_exclude::Empty(()) => None,
     };
 
+    let search_mode = match request_routes_reader.get_search_mode()?.which()? {
+        index_capnp::route_search_mode::Shortest(()) => RouteSearchMode::Shortest,
+        index_capnp::route_search_mode::Cheapest(()) => RouteSearchMode::Cheapest,
+    };
+
     Ok(RequestRoutes {
         request_id: read_uid(&request_routes_reader.get_request_id()?)?,
         capacity: read_custom_u_int128(&request_routes_reader.get_capacity()?)?,
         source: read_public_key(&request_routes_reader.get_source()?)?,
         destination: read_public_key(&request_routes_reader.get_destination()?)?,
         opt_exclude,
+        search_mode,
     })
 }
 
@@ -169,6 +182,134 @@ fn deser_response_routes(
     })
 }
 
+pub fn ser_request_liquidity(
+    request_liquidity: &RequestLiquidity,
+    request_liquidity_builder: &mut index_capnp::request_liquidity::Builder,
+) {
+    write_uid(
+        &request_liquidity.request_id,
+        &mut request_liquidity_builder.reborrow().init_request_id(),
+    );
+    write_public_key(
+        &request_liquidity.destination,
+        &mut request_liquidity_builder.reborrow().init_destination(),
+    );
+}
+
+pub fn deser_request_liquidity(
+    request_liquidity_reader: &index_capnp::request_liquidity::Reader,
+) -> Result<RequestLiquidity, SerializeError> {
+    Ok(RequestLiquidity {
+        request_id: read_uid(&request_liquidity_reader.get_request_id()?)?,
+        destination: read_public_key(&request_liquidity_reader.get_destination()?)?,
+    })
+}
+
+fn ser_neighbor_liquidity(
+    neighbor_liquidity: &NeighborLiquidity,
+    neighbor_liquidity_builder: &mut index_capnp::neighbor_liquidity::Builder,
+) {
+    write_public_key(
+        &neighbor_liquidity.public_key,
+        &mut neighbor_liquidity_builder.reborrow().init_public_key(),
+    );
+    write_custom_u_int128(
+        neighbor_liquidity.recv_capacity,
+        &mut neighbor_liquidity_builder.reborrow().init_recv_capacity(),
+    );
+}
+
+fn deser_neighbor_liquidity(
+    neighbor_liquidity_reader: &index_capnp::neighbor_liquidity::Reader,
+) -> Result<NeighborLiquidity, SerializeError> {
+    Ok(NeighborLiquidity {
+        public_key: read_public_key(&neighbor_liquidity_reader.get_public_key()?)?,
+        recv_capacity: read_custom_u_int128(&neighbor_liquidity_reader.get_recv_capacity()?)?,
+    })
+}
+
+fn ser_hops_liquidity(
+    hops_liquidity: &HopsLiquidity,
+    hops_liquidity_builder: &mut index_capnp::hops_liquidity::Builder,
+) {
+    hops_liquidity_builder.set_hops(hops_liquidity.hops);
+    hops_liquidity_builder.set_num_nodes(hops_liquidity.num_nodes);
+    write_custom_u_int128(
+        hops_liquidity.recv_capacity,
+        &mut hops_liquidity_builder.reborrow().init_recv_capacity(),
+    );
+}
+
+fn deser_hops_liquidity(
+    hops_liquidity_reader: &index_capnp::hops_liquidity::Reader,
+) -> Result<HopsLiquidity, SerializeError> {
+    Ok(HopsLiquidity {
+        hops: hops_liquidity_reader.get_hops(),
+        num_nodes: hops_liquidity_reader.get_num_nodes(),
+        recv_capacity: read_custom_u_int128(&hops_liquidity_reader.get_recv_capacity()?)?,
+    })
+}
+
+pub fn ser_liquidity(
+    liquidity: &Liquidity,
+    liquidity_builder: &mut index_capnp::liquidity::Builder,
+) {
+    let neighbors_len = usize_to_u32(liquidity.neighbors.len()).unwrap();
+    let mut neighbors_builder = liquidity_builder.reborrow().init_neighbors(neighbors_len);
+    for (index, neighbor_liquidity) in liquidity.neighbors.iter().enumerate() {
+        let mut neighbor_liquidity_builder = neighbors_builder
+            .reborrow()
+            .get(usize_to_u32(index).unwrap());
+        ser_neighbor_liquidity(neighbor_liquidity, &mut neighbor_liquidity_builder);
+    }
+
+    let hops_len = usize_to_u32(liquidity.hops.len()).unwrap();
+    let mut hops_builder = liquidity_builder.reborrow().init_hops(hops_len);
+    for (index, hops_liquidity) in liquidity.hops.iter().enumerate() {
+        let mut hops_liquidity_builder = hops_builder.reborrow().get(usize_to_u32(index).unwrap());
+        ser_hops_liquidity(hops_liquidity, &mut hops_liquidity_builder);
+    }
+}
+
+pub fn deser_liquidity(
+    liquidity_reader: &index_capnp::liquidity::Reader,
+) -> Result<Liquidity, SerializeError> {
+    let mut neighbors = Vec::new();
+    for neighbor_liquidity_reader in liquidity_reader.get_neighbors()? {
+        neighbors.push(deser_neighbor_liquidity(&neighbor_liquidity_reader)?);
+    }
+
+    let mut hops = Vec::new();
+    for hops_liquidity_reader in liquidity_reader.get_hops()? {
+        hops.push(deser_hops_liquidity(&hops_liquidity_reader)?);
+    }
+
+    Ok(Liquidity { neighbors, hops })
+}
+
+fn ser_response_liquidity(
+    response_liquidity: &ResponseLiquidity,
+    response_liquidity_builder: &mut index_capnp::response_liquidity::Builder,
+) {
+    write_uid(
+        &response_liquidity.request_id,
+        &mut response_liquidity_builder.reborrow().init_request_id(),
+    );
+    ser_liquidity(
+        &response_liquidity.liquidity,
+        &mut response_liquidity_builder.reborrow().init_liquidity(),
+    );
+}
+
+fn deser_response_liquidity(
+    response_liquidity_reader: &index_capnp::response_liquidity::Reader,
+) -> Result<ResponseLiquidity, SerializeError> {
+    Ok(ResponseLiquidity {
+        request_id: read_uid(&response_liquidity_reader.get_request_id()?)?,
+        liquidity: deser_liquidity(&response_liquidity_reader.get_liquidity()?)?,
+    })
+}
+
 fn ser_update_friend(
     update_friend: &UpdateFriend,
     update_friend_builder: &mut index_capnp::update_friend::Builder,
@@ -376,6 +517,12 @@ fn ser_index_server_to_client(
                 .init_response_routes();
             ser_response_routes(response_routes, &mut response_routes_builder);
         }
+        IndexServerToClient::ResponseLiquidity(response_liquidity) => {
+            let mut response_liquidity_builder = index_server_to_client_builder
+                .reborrow()
+                .init_response_liquidity();
+            ser_response_liquidity(response_liquidity, &mut response_liquidity_builder);
+        }
     }
 }
 
@@ -389,6 +536,11 @@ fn deser_index_server_to_client(
         index_capnp::index_server_to_client::ResponseRoutes(response_routes_reader) => {
             IndexServerToClient::ResponseRoutes(deser_response_routes(&response_routes_reader?)?)
         }
+        index_capnp::index_server_to_client::ResponseLiquidity(response_liquidity_reader) => {
+            IndexServerToClient::ResponseLiquidity(deser_response_liquidity(
+                &response_liquidity_reader?,
+            )?)
+        }
     })
 }
 
@@ -409,6 +561,12 @@ fn ser_index_client_to_server(
                 .init_request_routes();
             ser_request_routes(request_routes, &mut request_routes_builder);
         }
+        IndexClientToServer::RequestLiquidity(request_liquidity) => {
+            let mut request_liquidity_builder = index_client_to_server_builder
+                .reborrow()
+                .init_request_liquidity();
+            ser_request_liquidity(request_liquidity, &mut request_liquidity_builder);
+        }
     }
 }
 
@@ -422,6 +580,11 @@ fn deser_index_client_to_server(
         index_capnp::index_client_to_server::RequestRoutes(request_routes_reader) => {
             IndexClientToServer::RequestRoutes(deser_request_routes(&request_routes_reader?)?)
         }
+        index_capnp::index_client_to_server::RequestLiquidity(request_liquidity_reader) => {
+            IndexClientToServer::RequestLiquidity(deser_request_liquidity(
+                &request_liquidity_reader?,
+            )?)
+        }
     })
 }
 
@@ -139,7 +139,8 @@ where
         | FunderReportMutation::RemoveRelay(_)
         | FunderReportMutation::SetNumOpenInvoices(_)
         | FunderReportMutation::SetNumPayments(_)
-        | FunderReportMutation::SetNumOpenTransactions(_) => None,
+        | FunderReportMutation::SetNumOpenTransactions(_)
+        | FunderReportMutation::SetAcceptSpontaneousPayments(_) => None,
         FunderReportMutation::AddFriend(add_friend_report) => {
             create_update_friend(&add_friend_report.friend_public_key)
         }
@@ -4,6 +4,7 @@ use im::vector::Vector as ImVec;
 use common::mutable_state::MutableState;
 
 use crypto::crypto_rand::RandValue;
+use crypto::dh::DhPublicKey;
 use crypto::hash::HashResult;
 use crypto::identity::{PublicKey, Signature};
 use crypto::uid::Uid;
@@ -174,11 +175,15 @@ where
     B: Clone,
 {
     pub local_public_key: PublicKey,
+    /// Public key used by buyers to encrypt spontaneous payments to us.
+    pub local_dh_public_key: DhPublicKey,
     pub relays: ImVec<NamedRelayAddress<B>>,
     pub friends: ImHashMap<PublicKey, FriendReport<B>>,
     pub num_open_invoices: u64,
     pub num_payments: u64,
     pub num_open_transactions: u64,
+    /// Do we accept payments without a prior invoice?
+    pub accept_spontaneous_payments: bool,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -226,6 +231,7 @@ where
     SetNumOpenInvoices(u64),
     SetNumPayments(u64),
     SetNumOpenTransactions(u64),
+    SetAcceptSpontaneousPayments(bool),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -395,6 +401,10 @@ where
                 self.num_open_transactions = *num_open_transactions;
                 Ok(())
             }
+            FunderReportMutation::SetAcceptSpontaneousPayments(accept_spontaneous_payments) => {
+                self.accept_spontaneous_payments = *accept_spontaneous_payments;
+                Ok(())
+            }
         }
     }
 }
@@ -2,11 +2,11 @@ use im::hashmap::HashMap as ImHashMap;
 use im::vector::Vector as ImVec;
 
 use crate::capnp_common::{
-    read_custom_int128, read_custom_u_int128, read_hash, read_named_index_server_address,
-    read_named_relay_address, read_public_key, read_rand_nonce, read_rate, read_relay_address,
-    read_signature, write_custom_int128, write_custom_u_int128, write_hash,
-    write_named_index_server_address, write_named_relay_address, write_public_key,
-    write_rand_nonce, write_rate, write_relay_address, write_signature,
+    read_custom_int128, read_custom_u_int128, read_dh_public_key, read_hash,
+    read_named_index_server_address, read_named_relay_address, read_public_key, read_rand_nonce,
+    read_rate, read_relay_address, read_signature, write_custom_int128, write_custom_u_int128,
+    write_dh_public_key, write_hash, write_named_index_server_address, write_named_relay_address,
+    write_public_key, write_rand_nonce, write_rate, write_relay_address, write_signature,
 };
 use common::int_convert::usize_to_u32;
 use crypto::identity::PublicKey;
@@ -676,6 +676,11 @@ fn ser_funder_report(
         &mut funder_report_builder.reborrow().init_local_public_key(),
     );
 
+    write_dh_public_key(
+        &funder_report.local_dh_public_key,
+        &mut funder_report_builder.reborrow().init_local_dh_public_key(),
+    );
+
     let relays_len = usize_to_u32(funder_report.relays.len()).unwrap();
     let mut relays_builder = funder_report_builder.reborrow().init_relays(relays_len);
     for (index, named_relay_address) in funder_report.relays.iter().enumerate() {
@@ -694,6 +699,8 @@ fn ser_funder_report(
     funder_report_builder.set_num_open_invoices(funder_report.num_open_invoices);
     funder_report_builder.set_num_payments(funder_report.num_payments);
     funder_report_builder.set_num_open_transactions(funder_report.num_open_transactions);
+    funder_report_builder
+        .set_accept_spontaneous_payments(funder_report.accept_spontaneous_payments);
 }
 
 fn deser_funder_report(
@@ -712,11 +719,13 @@ fn deser_funder_report(
 
     Ok(FunderReport {
         local_public_key: read_public_key(&funder_report_reader.get_local_public_key()?)?,
+        local_dh_public_key: read_dh_public_key(&funder_report_reader.get_local_dh_public_key()?)?,
         relays: named_relays.into_iter().collect(),
         friends,
         num_open_invoices: funder_report_reader.get_num_open_invoices(),
         num_payments: funder_report_reader.get_num_payments(),
         num_open_transactions: funder_report_reader.get_num_open_transactions(),
+        accept_spontaneous_payments: funder_report_reader.get_accept_spontaneous_payments(),
     })
 }
 
@@ -1020,6 +1029,11 @@ fn ser_funder_report_mutation(
                 .reborrow()
                 .set_set_num_open_transactions(*num_open_transactions);
         }
+        FunderReportMutation::SetAcceptSpontaneousPayments(accept_spontaneous_payments) => {
+            funder_report_mutation_builder
+                .reborrow()
+                .set_set_accept_spontaneous_payments(*accept_spontaneous_payments);
+        }
     }
 }
 
@@ -1053,6 +1067,9 @@ fn deser_funder_report_mutation(
         report_capnp::funder_report_mutation::SetNumOpenTransactions(set_num_open_transactions) => {
             FunderReportMutation::SetNumOpenTransactions(set_num_open_transactions)
         }
+        report_capnp::funder_report_mutation::SetAcceptSpontaneousPayments(
+            accept_spontaneous_payments,
+        ) => FunderReportMutation::SetAcceptSpontaneousPayments(accept_spontaneous_payments),
     })
 }
 
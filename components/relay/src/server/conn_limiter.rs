@@ -1,10 +1,13 @@
-#![allow(unused)]
 use core::pin::Pin;
-use futures::channel::oneshot;
-use futures::task::Context;
-use futures::{Poll, Sink, Stream, StreamExt};
+use std::collections::HashMap;
 use std::marker::Unpin;
 
+use futures::channel::{mpsc, oneshot};
+use futures::stream::select;
+use futures::task::{Context, Spawn, SpawnExt};
+use futures::{future, select, stream, FutureExt, Poll, Sink, SinkExt, Stream, StreamExt};
+
+use common::conn::ConnPairVec;
 use crypto::identity::PublicKey;
 
 /// A struct that reports when it is dropped.
@@ -41,12 +44,274 @@ impl<T> Drop for Tracked<T> {
     }
 }
 
-async fn conn_limiter<M, K, KE, T>(incoming_conns: T, max_conns: usize) -> Result<(), ()>
+#[derive(Debug)]
+pub enum ConnLimiterError {
+    SpawnError,
+    SendConnError,
+}
+
+enum ConnLimiterEvent {
+    IncomingConn((PublicKey, ConnPairVec)),
+    IncomingConnsClosed,
+    ConnClosed(PublicKey),
+}
+
+/// Limit the amount of concurrent connections.
+/// `max_conns` is the maximum amount of concurrent connections, and `max_conns_per_key` is the
+/// maximum amount of concurrent connections from a single public key.
+///
+/// Connections exceeding one of the limits are dropped. Other connections are forwarded to
+/// `outgoing_conns`. A connection releases its slot once it is closed by either side.
+pub async fn conn_limiter<IC, OC, S>(
+    incoming_conns: IC,
+    mut outgoing_conns: OC,
+    max_conns: usize,
+    max_conns_per_key: usize,
+    mut spawner: S,
+) -> Result<(), ConnLimiterError>
 where
-    T: Stream<Item = (M, K, PublicKey)>,
-    M: Stream<Item = Vec<u8>>,
-    K: Sink<Vec<u8>, SinkError = KE>,
+    IC: Stream<Item = (PublicKey, ConnPairVec)> + Unpin,
+    OC: Sink<(PublicKey, ConnPairVec)> + Unpin,
+    S: Spawn,
 {
-    let mut cur_conns: usize = 0;
-    unimplemented!();
+    let incoming_conns = incoming_conns
+        .map(ConnLimiterEvent::IncomingConn)
+        .chain(stream::once(future::ready(
+            ConnLimiterEvent::IncomingConnsClosed,
+        )));
+
+    let (closed_sender, closed_receiver) = mpsc::channel::<PublicKey>(0);
+    let closed_receiver = closed_receiver.map(ConnLimiterEvent::ConnClosed);
+
+    let mut events = select(incoming_conns, closed_receiver);
+
+    let mut num_conns: usize = 0;
+    let mut key_conns: HashMap<PublicKey, usize> = HashMap::new();
+    let mut incoming_closed = false;
+
+    while let Some(event) = await!(events.next()) {
+        match event {
+            ConnLimiterEvent::IncomingConn((public_key, (mut sender, receiver))) => {
+                if num_conns >= max_conns {
+                    warn!("conn_limiter: Dropping connection: max_conns exceeded");
+                    continue;
+                }
+                let cur_key_conns = key_conns.get(&public_key).cloned().unwrap_or(0);
+                if cur_key_conns >= max_conns_per_key {
+                    warn!(
+                        "conn_limiter: Dropping connection from {:?}: max_conns_per_key exceeded",
+                        public_key
+                    );
+                    continue;
+                }
+
+                // Forward all incoming messages through a tracked receiver, and all outgoing
+                // messages through a new sender. The tracked receiver is dropped when the
+                // connection is closed by either side. In particular, we find out that the
+                // connection was dropped by our side even if the remote side is silent:
+                let (drop_sender, drop_receiver) = oneshot::channel();
+                let mut tracked_receiver = Tracked::new(receiver, drop_sender);
+                let (mut receiver_sender, new_receiver) = mpsc::channel(0);
+                let (new_sender, mut sender_receiver) = mpsc::channel(0);
+                let forward_fut = async move {
+                    let mut fut_incoming = receiver_sender.send_all(&mut tracked_receiver).fuse();
+                    let mut fut_outgoing = sender.send_all(&mut sender_receiver).fuse();
+                    select! {
+                        _ = fut_incoming => {},
+                        _ = fut_outgoing => {},
+                    }
+                };
+                spawner
+                    .spawn(forward_fut)
+                    .map_err(|_| ConnLimiterError::SpawnError)?;
+
+                let mut c_closed_sender = closed_sender.clone();
+                let c_public_key = public_key.clone();
+                let closed_fut = async move {
+                    let _ = await!(drop_receiver);
+                    let _ = await!(c_closed_sender.send(c_public_key));
+                };
+                spawner
+                    .spawn(closed_fut)
+                    .map_err(|_| ConnLimiterError::SpawnError)?;
+
+                num_conns = num_conns.checked_add(1).unwrap();
+                key_conns.insert(public_key.clone(), cur_key_conns.checked_add(1).unwrap());
+
+                await!(outgoing_conns.send((public_key, (new_sender, new_receiver))))
+                    .map_err(|_| ConnLimiterError::SendConnError)?;
+            }
+            ConnLimiterEvent::IncomingConnsClosed => {
+                incoming_closed = true;
+            }
+            ConnLimiterEvent::ConnClosed(public_key) => {
+                num_conns = num_conns.checked_sub(1).unwrap();
+                let cur_key_conns = key_conns.remove(&public_key).unwrap();
+                let new_key_conns = cur_key_conns.checked_sub(1).unwrap();
+                if new_key_conns > 0 {
+                    key_conns.insert(public_key, new_key_conns);
+                }
+            }
+        }
+        if incoming_closed && num_conns == 0 {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use common::test_executor::TestExecutor;
+    use crypto::identity::PUBLIC_KEY_LEN;
+
+    #[test]
+    fn test_tracked_drop() {
+        let (drop_sender, mut drop_receiver) = oneshot::channel();
+        let tracked = Tracked::new(stream::iter(vec![1u32, 2, 3]), drop_sender);
+        assert_eq!(drop_receiver.try_recv(), Ok(None));
+        drop(tracked);
+        assert_eq!(drop_receiver.try_recv(), Ok(Some(())));
+    }
+
+    /// Create a connection pair. Returns the local side and the remote side.
+    fn create_conn() -> (ConnPairVec, ConnPairVec) {
+        let (local_sender, remote_receiver) = mpsc::channel(0);
+        let (remote_sender, local_receiver) = mpsc::channel(0);
+        (
+            (local_sender, local_receiver),
+            (remote_sender, remote_receiver),
+        )
+    }
+
+    async fn task_conn_limiter_basic(test_executor: TestExecutor) {
+        let (mut incoming_sender, incoming_conns) = mpsc::channel(0);
+        let (outgoing_sender, mut outgoing_conns) = mpsc::channel(0);
+
+        let limiter_fut = conn_limiter(
+            incoming_conns,
+            outgoing_sender,
+            3, // max_conns
+            2, // max_conns_per_key
+            test_executor.clone(),
+        )
+        .map(|res| res.unwrap());
+        test_executor.clone().spawn(limiter_fut).unwrap();
+
+        let pk_a = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let pk_b = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+        let pk_c = PublicKey::from(&[0xcc; PUBLIC_KEY_LEN]);
+
+        // Two connections from pk_a are allowed:
+        let mut remotes = Vec::new();
+        let mut locals = Vec::new();
+        for _ in 0..2 {
+            let (local, remote) = create_conn();
+            await!(incoming_sender.send((pk_a.clone(), local))).unwrap();
+            let (public_key, local) = await!(outgoing_conns.next()).unwrap();
+            assert_eq!(public_key, pk_a);
+            remotes.push(remote);
+            locals.push(local);
+        }
+
+        // Messages go through the limited connection:
+        let (remote_sender, _remote_receiver) = &mut remotes[1];
+        await!(remote_sender.send(vec![1, 2, 3])).unwrap();
+        let (_local_sender, local_receiver) = &mut locals[1];
+        assert_eq!(await!(local_receiver.next()).unwrap(), vec![1, 2, 3]);
+
+        // A third connection from pk_a is refused:
+        let (local, (_remote_sender, mut remote_receiver)) = create_conn();
+        await!(incoming_sender.send((pk_a.clone(), local))).unwrap();
+        assert!(await!(remote_receiver.next()).is_none());
+
+        // A connection from pk_b is allowed:
+        let (local, remote_b) = create_conn();
+        await!(incoming_sender.send((pk_b.clone(), local))).unwrap();
+        let (public_key, _local_b) = await!(outgoing_conns.next()).unwrap();
+        assert_eq!(public_key, pk_b);
+
+        // Total amount of connections exceeded. A connection from pk_c is refused:
+        let (local, (_remote_sender, mut remote_receiver)) = create_conn();
+        await!(incoming_sender.send((pk_c.clone(), local))).unwrap();
+        assert!(await!(remote_receiver.next()).is_none());
+
+        // The remote side closes the first connection of pk_a, freeing a slot:
+        drop(remotes.remove(0));
+        await!(test_executor.wait());
+
+        let (local, _remote) = create_conn();
+        await!(incoming_sender.send((pk_a.clone(), local))).unwrap();
+        let (public_key, _local) = await!(outgoing_conns.next()).unwrap();
+        assert_eq!(public_key, pk_a);
+
+        // All the slots are taken again:
+        let (local, (_remote_sender, mut remote_receiver)) = create_conn();
+        await!(incoming_sender.send((pk_c.clone(), local))).unwrap();
+        assert!(await!(remote_receiver.next()).is_none());
+
+        drop(remote_b);
+    }
+
+    #[test]
+    fn test_conn_limiter_basic() {
+        let test_executor = TestExecutor::new();
+        let res = test_executor.run(task_conn_limiter_basic(test_executor.clone()));
+        assert!(res.is_output());
+    }
+
+    async fn task_conn_limiter_local_close(test_executor: TestExecutor) {
+        let (mut incoming_sender, incoming_conns) = mpsc::channel(0);
+        let (outgoing_sender, mut outgoing_conns) = mpsc::channel(0);
+
+        let limiter_fut = conn_limiter(
+            incoming_conns,
+            outgoing_sender,
+            1, // max_conns
+            1, // max_conns_per_key
+            test_executor.clone(),
+        )
+        .map(|res| res.unwrap());
+        test_executor.clone().spawn(limiter_fut).unwrap();
+
+        let pk_a = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let pk_b = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+
+        let (local, remote) = create_conn();
+        await!(incoming_sender.send((pk_a.clone(), local))).unwrap();
+        let (public_key, mut local) = await!(outgoing_conns.next()).unwrap();
+        assert_eq!(public_key, pk_a);
+
+        // Messages go through the limited connection in the other direction too:
+        let (local_sender, _local_receiver) = &mut local;
+        await!(local_sender.send(vec![1, 2, 3])).unwrap();
+        let (_remote_sender, mut remote_receiver) = remote;
+        assert_eq!(await!(remote_receiver.next()).unwrap(), vec![1, 2, 3]);
+
+        // All the slots are taken:
+        let (conn, (_remote_sender_b, mut remote_receiver_b)) = create_conn();
+        await!(incoming_sender.send((pk_b.clone(), conn))).unwrap();
+        assert!(await!(remote_receiver_b.next()).is_none());
+
+        // Our side closes the connection, while the remote side stays silent
+        // (_remote_sender is kept alive):
+        drop(local);
+        assert!(await!(remote_receiver.next()).is_none());
+        await!(test_executor.wait());
+
+        // The slot was released:
+        let (conn, _remote_b) = create_conn();
+        await!(incoming_sender.send((pk_b.clone(), conn))).unwrap();
+        let (public_key, _local_b) = await!(outgoing_conns.next()).unwrap();
+        assert_eq!(public_key, pk_b);
+    }
+
+    #[test]
+    fn test_conn_limiter_local_close() {
+        let test_executor = TestExecutor::new();
+        let res = test_executor.run(task_conn_limiter_local_close(test_executor.clone()));
+        assert!(res.is_output());
+    }
 }
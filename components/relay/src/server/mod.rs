@@ -2,4 +2,5 @@ mod conn_limiter;
 mod conn_processor;
 pub mod net_server;
 mod server;
+pub mod traffic;
 mod types;
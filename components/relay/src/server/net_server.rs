@@ -6,6 +6,7 @@ use futures::{FutureExt, Stream, StreamExt, TryFutureExt};
 
 use derive_more::*;
 
+use common::access_control::{AccessControl, AccessControlOp};
 use common::conn::{BoxFuture, ConnPairVec, FutTransform};
 use common::transform_pool::transform_pool_loop;
 
@@ -21,9 +22,11 @@ use timer::TimerClient;
 use secure_channel::SecureChannel;
 use version::VersionPrefix;
 
+use super::conn_limiter::conn_limiter;
 use super::conn_processor::conn_processor;
 use super::server::relay_server_loop;
 pub use super::server::RelayServerError;
+use super::traffic::TrafficMeter;
 
 /// A relay server loop. Incoming connections should contain both (sender, receiver) and a
 /// public_key of the remote side (Should be obtained after authentication).
@@ -32,16 +35,23 @@ pub use super::server::RelayServerError;
 /// its purpose.
 /// `keepalive_ticks` is the amount of time we are willing to let the remote side to be idle before
 /// we disconnect. It is also used to timeout open half tunnels that were not claimed.
-async fn relay_server<IC, S>(
+/// `traffic_meter` counts (and possibly limits) the traffic relayed for every public key.
+/// `opt_access_control` (if not None) limits the public keys that may use the relay. It can be
+/// changed at runtime using `incoming_access_control`.
+async fn relay_server<IC, IAC, S>(
     incoming_conns: IC,
     timer_client: TimerClient,
     conn_timeout_ticks: usize,
     keepalive_ticks: usize,
+    traffic_meter: TrafficMeter,
+    opt_access_control: Option<AccessControl<PublicKey>>,
+    incoming_access_control: IAC,
     spawner: S,
 ) -> Result<(), RelayServerError>
 where
     S: Spawn + Clone + Send + 'static,
     IC: Stream<Item = (PublicKey, ConnPairVec)> + Unpin + Send + 'static,
+    IAC: Stream<Item = AccessControlOp<PublicKey>> + Unpin + Send + 'static,
 {
     let keepalive_transform =
         KeepAliveChannel::new(timer_client.clone(), keepalive_ticks, spawner.clone());
@@ -66,6 +76,9 @@ where
         timer_client,
         processed_conns,
         half_tunnel_ticks,
+        traffic_meter,
+        opt_access_control,
+        incoming_access_control,
         spawner
     ))
 }
@@ -104,16 +117,30 @@ where
     }
 }
 
-pub async fn net_relay_server<IRC, R, S>(
+/// Run a relay server over incoming raw connections.
+///
+/// `max_conns` is the maximum amount of concurrent connections the relay will serve, and
+/// `max_conns_per_key` is the maximum amount of concurrent connections it will serve for a single
+/// public key. Relayed traffic is accounted for (and possibly limited) using `traffic_meter`.
+///
+/// If `opt_access_control` is not None, the relay only serves the public keys it allows.
+/// Operations received from `incoming_access_control` are applied to the access control.
+pub async fn net_relay_server<IRC, IAC, R, S>(
     incoming_raw_conns: IRC,
     identity_client: IdentityClient,
     timer_client: TimerClient,
     rng: R,
     max_concurrent_encrypt: usize,
+    max_conns: usize,
+    max_conns_per_key: usize,
+    traffic_meter: TrafficMeter,
+    opt_access_control: Option<AccessControl<PublicKey>>,
+    incoming_access_control: IAC,
     mut spawner: S,
 ) -> Result<(), NetRelayServerError>
 where
     IRC: Stream<Item = ConnPairVec> + Unpin + Send + 'static,
+    IAC: Stream<Item = AccessControlOp<PublicKey>> + Unpin + Send + 'static,
     R: CryptoRandom + Clone + 'static,
     S: Spawn + Clone + Send + Sync + 'static,
 {
@@ -152,11 +179,31 @@ where
         .spawn(enc_pool_fut)
         .map_err(|_| NetRelayServerError::SpawnError)?;
 
-    await!(relay_server(
+    let (limited_conns_sender, incoming_limited_conns) =
+        mpsc::channel::<(PublicKey, ConnPairVec)>(0);
+
+    let limiter_fut = conn_limiter(
         incoming_enc_conns,
+        limited_conns_sender,
+        max_conns,
+        max_conns_per_key,
+        spawner.clone(),
+    )
+    .map_err(|e| error!("conn_limiter() error: {:?}", e))
+    .map(|_| ());
+
+    spawner
+        .spawn(limiter_fut)
+        .map_err(|_| NetRelayServerError::SpawnError)?;
+
+    await!(relay_server(
+        incoming_limited_conns,
         timer_client,
         CONN_TIMEOUT_TICKS,
         KEEPALIVE_TICKS,
+        traffic_meter,
+        opt_access_control,
+        incoming_access_control,
         spawner.clone()
     ))?;
     Ok(())
@@ -5,6 +5,7 @@ use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::marker::Unpin;
 
+use common::access_control::{AccessControl, AccessControlOp};
 use common::futures_compat::send_to_sink;
 use common::select_streams::{select_streams, BoxStream};
 use crypto::identity::PublicKey;
@@ -12,6 +13,7 @@ use timer::TimerClient;
 
 use proto::relay::messages::{IncomingConnection, RejectConnection};
 
+use super::traffic::TrafficMeter;
 use super::types::{IncomingAccept, IncomingConn, IncomingConnInner};
 
 struct ConnPair<M, K> {
@@ -57,6 +59,7 @@ enum RelayServerEvent<ML, KL, MA, KA, MC, KC> {
     TunnelClosed(TunnelClosed),
     ListenerMessage((PublicKey, RejectConnection)),
     ListenerClosed(PublicKey),
+    AccessControlOp(AccessControlOp<PublicKey>),
     TimerTick,
     TimerClosed,
 }
@@ -71,6 +74,7 @@ impl<ML, KL, MA, KA, MC, KC> fmt::Debug for RelayServerEvent<ML, KL, MA, KA, MC,
             RelayServerEvent::TunnelClosed(_) => write!(f, "RelayServerEvent::TunnelClosed"),
             RelayServerEvent::ListenerMessage(_) => write!(f, "RelayServerEvent::ListenerMessage"),
             RelayServerEvent::ListenerClosed(_) => write!(f, "RelayServerEvent::ListenerClosed"),
+            RelayServerEvent::AccessControlOp(_) => write!(f, "RelayServerEvent::AccessControlOp"),
             RelayServerEvent::TimerTick => write!(f, "RelayServerEvent::TimerTick"),
             RelayServerEvent::TimerClosed => write!(f, "RelayServerEvent::TimerClosed"),
         }
@@ -89,12 +93,43 @@ pub enum RelayServerError {
     EventReceiverError,
 }
 
+#[derive(Debug)]
+enum ForwardTrafficError {
+    SendError,
+    TrafficMeterClosed,
+}
+
+/// Forward messages from `receiver` to `sender`, charging `from_public_key` for the traffic.
+/// If `from_public_key` runs out of quota, forwarding is paused until the quota is refilled.
+async fn forward_traffic<M, K>(
+    mut receiver: M,
+    mut sender: K,
+    from_public_key: PublicKey,
+    to_public_key: PublicKey,
+    traffic_meter: TrafficMeter,
+) -> Result<(), ForwardTrafficError>
+where
+    M: Stream<Item = Vec<u8>> + Unpin,
+    K: Sink<Vec<u8>, SinkError = ()> + Unpin,
+{
+    while let Some(data) = await!(receiver.next()) {
+        while let Err(refill_receiver) =
+            traffic_meter.try_consume(&from_public_key, &to_public_key, data.len())
+        {
+            await!(refill_receiver).map_err(|_| ForwardTrafficError::TrafficMeterClosed)?;
+        }
+        await!(sender.send(data)).map_err(|_| ForwardTrafficError::SendError)?;
+    }
+    Ok(())
+}
+
 fn handle_accept<MT, KT, MA, KA, TCL>(
     listeners: &mut HashMap<PublicKey, Listener<MT, KT>>,
     acceptor_public_key: PublicKey,
     incoming_accept: IncomingAccept<MA, KA>,
     // TODO: This should be a oneshot:
     tunnel_closed_sender: TCL,
+    traffic_meter: TrafficMeter,
     mut spawner: impl Spawn,
 ) -> Result<(), RelayServerError>
 where
@@ -109,8 +144,8 @@ where
         None => return Err(RelayServerError::ListeningNotInProgress),
     };
     let IncomingAccept {
-        mut receiver,
-        mut sender,
+        receiver,
+        sender,
         accept_public_key,
     } = incoming_accept;
     let conn_pair = match listener.half_tunnels.remove(&accept_public_key) {
@@ -120,27 +155,39 @@ where
     let c_accept_public_key = accept_public_key.clone();
 
     let ConnPair {
-        sender: mut remote_sender,
-        receiver: mut remote_receiver,
+        sender: remote_sender,
+        receiver: remote_receiver,
     } = conn_pair;
 
-    let send_fut1 = async move {
-        await!(remote_sender
-            .send_all(&mut receiver)
-            .map_err(|e| error!("send_fut1 error: {:?}", e))
-            .then(|_| future::ready(())))
-    };
+    // Traffic sent by the acceptor is charged to the acceptor, and traffic sent by the initiator
+    // is charged to the initiator:
+    let send_fut1 = forward_traffic(
+        receiver,
+        remote_sender,
+        acceptor_public_key.clone(),
+        accept_public_key.clone(),
+        traffic_meter.clone(),
+    )
+    .map_err(|e| error!("send_fut1 error: {:?}", e))
+    .then(|_| future::ready(()));
+
+    let c_acceptor_public_key = acceptor_public_key.clone();
     let send_fut2 = async move {
-        await!(sender
-            .send_all(&mut remote_receiver)
-            .map_err(|e| error!("send_fut2 error: {:?}", e))
-            .then(move |_| {
-                let tunnel_closed = TunnelClosed {
-                    init_public_key: c_accept_public_key,
-                    listen_public_key: acceptor_public_key,
-                };
-                send_to_sink(tunnel_closed_sender, tunnel_closed).then(|_| future::ready(()))
-            }))
+        await!(forward_traffic(
+            remote_receiver,
+            sender,
+            accept_public_key,
+            c_acceptor_public_key,
+            traffic_meter
+        )
+        .map_err(|e| error!("send_fut2 error: {:?}", e))
+        .then(move |_| {
+            let tunnel_closed = TunnelClosed {
+                init_public_key: c_accept_public_key,
+                listen_public_key: acceptor_public_key,
+            };
+            send_to_sink(tunnel_closed_sender, tunnel_closed).then(|_| future::ready(()))
+        }))
     };
 
     spawner.spawn(send_fut1).unwrap();
@@ -149,10 +196,18 @@ where
     Ok(())
 }
 
-pub async fn relay_server_loop<ML, KL, MA, KA, MC, KC, S>(
+/// Main loop of the relay server.
+///
+/// If `opt_access_control` is not None, only public keys allowed by the access control may listen,
+/// accept or connect through the relay. The access control can be changed at runtime by sending
+/// operations through `incoming_access_control`.
+pub async fn relay_server_loop<ML, KL, MA, KA, MC, KC, S, IAC>(
     mut timer_client: TimerClient,
     incoming_conns: S,
     half_tunnel_ticks: usize,
+    traffic_meter: TrafficMeter,
+    mut opt_access_control: Option<AccessControl<PublicKey>>,
+    incoming_access_control: IAC,
     mut spawner: impl Spawn + Clone,
 ) -> Result<(), RelayServerError>
 where
@@ -163,6 +218,7 @@ where
     MC: Stream<Item = Vec<u8>> + Unpin + Send + 'static,
     KC: Sink<Vec<u8>, SinkError = ()> + Unpin + Send + 'static,
     S: Stream<Item = IncomingConn<ML, KL, MA, KA, MC, KC>> + Unpin + Send,
+    IAC: Stream<Item = AccessControlOp<PublicKey>> + Unpin + Send,
 {
     let timer_stream = await!(timer_client.request_timer_stream())
         .map_err(|_| RelayServerError::RequestTimerStreamError)?;
@@ -176,9 +232,16 @@ where
             RelayServerEvent::IncomingConnsClosed,
         )));
 
+    let incoming_access_control = incoming_access_control.map(RelayServerEvent::AccessControlOp);
+
     let (event_sender, event_receiver) = mpsc::channel::<RelayServerEvent<_, _, _, _, _, _>>(0);
 
-    let mut relay_server_events = select_streams![timer_stream, incoming_conns, event_receiver];
+    let mut relay_server_events = select_streams![
+        timer_stream,
+        incoming_conns,
+        incoming_access_control,
+        event_receiver
+    ];
 
     let mut incoming_conns_closed = false;
     let mut listeners: HashMap<PublicKey, Listener<_, _>> = HashMap::new();
@@ -188,6 +251,15 @@ where
         match relay_server_event {
             RelayServerEvent::IncomingConn(incoming_conn) => {
                 let IncomingConn { public_key, inner } = incoming_conn;
+                if let Some(access_control) = &opt_access_control {
+                    if !access_control.is_allowed(&public_key) {
+                        debug!(
+                            "relay_server_loop(): Discarding connection from {:?}: Not allowed",
+                            public_key
+                        );
+                        continue;
+                    }
+                }
                 match inner {
                     IncomingConnInner::Listen(incoming_listen) => {
                         if listeners.contains_key(&public_key) {
@@ -240,6 +312,7 @@ where
                             public_key.clone(),
                             incoming_accept,
                             tunnel_closed_sender,
+                            traffic_meter.clone(),
                             spawner.clone(),
                         )
                         .map_err(|e| warn!("handle_accept() error: {:?}", e));
@@ -310,7 +383,31 @@ where
                     listeners.remove(&public_key);
                 }
             }
+            RelayServerEvent::AccessControlOp(access_control_op) => {
+                let access_control = match &mut opt_access_control {
+                    Some(access_control) => access_control,
+                    None => {
+                        warn!(
+                            "relay_server_loop(): Access control is disabled. Ignoring operation."
+                        );
+                        continue;
+                    }
+                };
+                if let AccessControlOp::Remove(public_key) = &access_control_op {
+                    // Stop listening for a public key that is no longer allowed.
+                    // Pending half tunnels are closed. Open tunnels are left intact.
+                    // The listener is removed when we get ListenerClosed.
+                    if let Some(listener) = listeners.get_mut(public_key) {
+                        listener.opt_sender = None;
+                        listener.half_tunnels = HashMap::new();
+                    }
+                }
+                access_control.apply_op(access_control_op);
+            }
             RelayServerEvent::TimerTick => {
+                // Refill traffic quotas:
+                traffic_meter.tick();
+
                 // Remove old half tunnels:
                 for listener in listeners.values_mut() {
                     listener
@@ -339,6 +436,7 @@ mod tests {
     use futures::task::{Spawn, SpawnExt};
 
     use super::super::types::{IncomingAccept, IncomingConnect, IncomingListen};
+    use common::test_executor::TestExecutor;
     use crypto::identity::{PublicKey, PUBLIC_KEY_LEN};
     use timer::create_timer_incoming;
 
@@ -357,6 +455,9 @@ mod tests {
             timer_client,
             incoming_conns,
             half_tunnel_ticks,
+            TrafficMeter::new(None),
+            None,
+            stream::empty(),
             spawner.clone(),
         );
 
@@ -474,6 +575,9 @@ mod tests {
             timer_client,
             incoming_conns,
             half_tunnel_ticks,
+            TrafficMeter::new(None),
+            None,
+            stream::empty(),
             spawner.clone(),
         );
 
@@ -578,6 +682,109 @@ mod tests {
             .unwrap();
     }
 
+    async fn task_relay_server_access_control(test_executor: TestExecutor) {
+        // Create a mock time service:
+        let (_tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, test_executor.clone()).unwrap();
+
+        let (mut outgoing_conns, incoming_conns) = mpsc::channel::<_>(0);
+        let (mut access_control_sender, incoming_access_control) = mpsc::channel(0);
+
+        let a_public_key = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let b_public_key = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+
+        // Only a is allowed:
+        let mut access_control = AccessControl::new();
+        access_control.apply_op(AccessControlOp::Add(a_public_key.clone()));
+
+        let half_tunnel_ticks: usize = 16;
+        let fut_relay_server = relay_server_loop(
+            timer_client,
+            incoming_conns,
+            half_tunnel_ticks,
+            TrafficMeter::new(None),
+            Some(access_control),
+            incoming_access_control,
+            test_executor.clone(),
+        )
+        .map_err(|e| error!("relay_server_loop() error: {:?}", e))
+        .map(|_| ());
+        test_executor.clone().spawn(fut_relay_server).unwrap();
+
+        let (_a_ac, c_ac) = mpsc::channel::<RejectConnection>(0);
+        let (c_ca, mut a_ca) = mpsc::channel::<IncomingConnection>(0);
+        let incoming_conn_a = IncomingConn {
+            public_key: a_public_key.clone(),
+            inner: IncomingConnInner::Listen(IncomingListen {
+                receiver: c_ac,
+                sender: c_ca.sink_map_err(|_| ()),
+            }),
+        };
+        await!(outgoing_conns.send(incoming_conn_a)).unwrap();
+
+        // b is not allowed to connect:
+        let (_b_bc, c_bc) = mpsc::channel::<Vec<u8>>(0);
+        let (c_cb, mut b_cb) = mpsc::channel::<Vec<u8>>(0);
+        let incoming_conn_b = IncomingConn {
+            public_key: b_public_key.clone(),
+            inner: IncomingConnInner::Connect(IncomingConnect {
+                receiver: c_bc,
+                sender: c_cb.sink_map_err(|_| ()),
+                connect_public_key: a_public_key.clone(),
+            }),
+        };
+        await!(outgoing_conns.send(incoming_conn_b)).unwrap();
+        assert!(await!(b_cb.next()).is_none());
+
+        // Allow b:
+        await!(access_control_sender.send(AccessControlOp::Add(b_public_key.clone()))).unwrap();
+        await!(test_executor.wait());
+
+        let (_b_bc, c_bc) = mpsc::channel::<Vec<u8>>(0);
+        let (c_cb, _b_cb) = mpsc::channel::<Vec<u8>>(0);
+        let incoming_conn_b = IncomingConn {
+            public_key: b_public_key.clone(),
+            inner: IncomingConnInner::Connect(IncomingConnect {
+                receiver: c_bc,
+                sender: c_cb.sink_map_err(|_| ()),
+                connect_public_key: a_public_key.clone(),
+            }),
+        };
+        await!(outgoing_conns.send(incoming_conn_b)).unwrap();
+        assert_eq!(
+            await!(a_ca.next()).unwrap(),
+            IncomingConnection {
+                public_key: b_public_key.clone()
+            }
+        );
+
+        // Disallow a. a should not be able to listen anymore:
+        await!(access_control_sender.send(AccessControlOp::Remove(a_public_key.clone()))).unwrap();
+        assert!(await!(a_ca.next()).is_none());
+
+        // This is done to help the compiler deduce the types for IncomingConn:
+        if false {
+            let (_a_ac1, c_ac1) = mpsc::channel::<Vec<u8>>(0);
+            let (c_ca1, _a_ca1) = mpsc::channel::<Vec<u8>>(0);
+            let incoming_conn_accept_a = IncomingConn {
+                public_key: a_public_key.clone(),
+                inner: IncomingConnInner::Accept(IncomingAccept {
+                    receiver: c_ac1,
+                    sender: c_ca1.sink_map_err(|_| ()),
+                    accept_public_key: b_public_key.clone(),
+                }),
+            };
+            await!(outgoing_conns.send(incoming_conn_accept_a)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_relay_server_access_control() {
+        let test_executor = TestExecutor::new();
+        let res = test_executor.run(task_relay_server_access_control(test_executor.clone()));
+        assert!(res.is_output());
+    }
+
     // TODO: Add tests:
     // - Timeout of half tunnels
     //      (Do some action first, to make sure timer_stream was already obtained).
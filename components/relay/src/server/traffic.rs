@@ -0,0 +1,309 @@
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use futures::channel::oneshot;
+
+use common::int_convert::usize_to_u64;
+use crypto::identity::PublicKey;
+
+/// Amount of timer ticks in a statistics window.
+/// Counters of public keys that were idle during a whole window are removed.
+pub const STATS_WINDOW_TICKS: usize = 0x400;
+
+/// Token bucket limits on the traffic a single public key may push through the relay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrafficLimit {
+    /// Amount of bytes added to the bucket of every public key on every timer tick
+    pub bytes_per_tick: u64,
+    /// Maximum amount of bytes a bucket can hold (Maximum burst size)
+    pub max_burst_bytes: u64,
+}
+
+/// Traffic counters of a single public key
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyTrafficStats {
+    /// Amount of bytes sent from this public key through the relay
+    pub bytes_sent: u64,
+    /// Amount of bytes relayed to this public key
+    pub bytes_received: u64,
+    /// Amount of timer ticks messages from this public key were delayed, because the public key
+    /// ran out of quota.
+    pub throttled_ticks: u64,
+}
+
+/// Traffic counters kept since the relay was started
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrafficStats {
+    /// Counters of public keys that were active recently
+    pub keys: HashMap<PublicKey, KeyTrafficStats>,
+    /// Amount of bytes sent from public keys whose counters were removed
+    pub removed_bytes_sent: u64,
+}
+
+impl TrafficStats {
+    /// Total amount of bytes relayed
+    pub fn total_bytes(&self) -> u64 {
+        self.keys
+            .values()
+            .fold(self.removed_bytes_sent, |total, key_stats| {
+                total.saturating_add(key_stats.bytes_sent)
+            })
+    }
+}
+
+struct TrafficMeterInner {
+    opt_limit: Option<TrafficLimit>,
+    /// Amount of bytes every public key may still send.
+    /// Public keys with a full bucket are not kept.
+    buckets: HashMap<PublicKey, u64>,
+    /// Senders waiting for the next refill of the buckets
+    waiters: Vec<oneshot::Sender<()>>,
+    stats: TrafficStats,
+    /// Public keys that sent or received messages during the current statistics window
+    active_keys: HashSet<PublicKey>,
+    /// Amount of ticks passed in the current statistics window
+    window_ticks: usize,
+}
+
+/// Traffic accounting, shared between all the tunnels of a relay.
+#[derive(Clone)]
+pub struct TrafficMeter {
+    arc_mutex_inner: Arc<Mutex<TrafficMeterInner>>,
+}
+
+impl TrafficMeter {
+    /// Create a new TrafficMeter. If `opt_limit` is None, traffic is only counted.
+    pub fn new(opt_limit: Option<TrafficLimit>) -> Self {
+        let inner = TrafficMeterInner {
+            opt_limit,
+            buckets: HashMap::new(),
+            waiters: Vec::new(),
+            stats: TrafficStats::default(),
+            active_keys: HashSet::new(),
+            window_ticks: 0,
+        };
+        TrafficMeter {
+            arc_mutex_inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    /// Try to charge `from_public_key` for relaying `num_bytes` bytes to `to_public_key`.
+    ///
+    /// If `from_public_key` ran out of quota, nothing is charged, and a receiver is returned. The
+    /// receiver is notified on the next refill of the buckets.
+    pub(crate) fn try_consume(
+        &self,
+        from_public_key: &PublicKey,
+        to_public_key: &PublicKey,
+        num_bytes: usize,
+    ) -> Result<(), oneshot::Receiver<()>> {
+        let mut inner = self.arc_mutex_inner.lock().unwrap();
+        let num_bytes = usize_to_u64(num_bytes).unwrap();
+        inner.active_keys.insert(from_public_key.clone());
+
+        if let Some(limit) = inner.opt_limit.clone() {
+            let tokens = inner
+                .buckets
+                .get(from_public_key)
+                .cloned()
+                .unwrap_or(limit.max_burst_bytes);
+
+            let new_tokens = if tokens >= num_bytes {
+                tokens - num_bytes
+            } else if tokens >= limit.max_burst_bytes {
+                // A message larger than the bucket passes only when the bucket is full:
+                0
+            } else {
+                let (waiter, refill_receiver) = oneshot::channel();
+                inner.waiters.push(waiter);
+                let key_stats = inner
+                    .stats
+                    .keys
+                    .entry(from_public_key.clone())
+                    .or_insert_with(KeyTrafficStats::default);
+                key_stats.throttled_ticks = key_stats.throttled_ticks.saturating_add(1);
+                return Err(refill_receiver);
+            };
+            inner.buckets.insert(from_public_key.clone(), new_tokens);
+        }
+
+        let from_stats = inner
+            .stats
+            .keys
+            .entry(from_public_key.clone())
+            .or_insert_with(KeyTrafficStats::default);
+        from_stats.bytes_sent = from_stats.bytes_sent.saturating_add(num_bytes);
+
+        let to_stats = inner
+            .stats
+            .keys
+            .entry(to_public_key.clone())
+            .or_insert_with(KeyTrafficStats::default);
+        to_stats.bytes_received = to_stats.bytes_received.saturating_add(num_bytes);
+        inner.active_keys.insert(to_public_key.clone());
+
+        Ok(())
+    }
+
+    /// Refill the buckets of all public keys. Should be called on every timer tick.
+    /// When a statistics window ends, counters of public keys that were idle during the whole
+    /// window are removed.
+    pub(crate) fn tick(&self) {
+        let mut inner = self.arc_mutex_inner.lock().unwrap();
+        let inner = &mut *inner;
+        inner.window_ticks = inner.window_ticks.saturating_add(1);
+        if inner.window_ticks >= STATS_WINDOW_TICKS {
+            inner.window_ticks = 0;
+            let active_keys = mem::replace(&mut inner.active_keys, HashSet::new());
+            let removed_bytes_sent = &mut inner.stats.removed_bytes_sent;
+            inner.stats.keys.retain(|public_key, key_stats| {
+                if active_keys.contains(public_key) {
+                    return true;
+                }
+                *removed_bytes_sent = removed_bytes_sent.saturating_add(key_stats.bytes_sent);
+                false
+            });
+        }
+
+        if let Some(limit) = inner.opt_limit.clone() {
+            inner.buckets.retain(|_public_key, tokens| {
+                *tokens = tokens
+                    .saturating_add(limit.bytes_per_tick)
+                    .min(limit.max_burst_bytes);
+                *tokens < limit.max_burst_bytes
+            });
+        }
+
+        for waiter in inner.waiters.drain(..) {
+            let _ = waiter.send(());
+        }
+    }
+
+    /// Get the current traffic counters
+    pub fn stats(&self) -> TrafficStats {
+        self.arc_mutex_inner.lock().unwrap().stats.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crypto::identity::PUBLIC_KEY_LEN;
+
+    #[test]
+    fn test_traffic_meter_unlimited() {
+        let pk_a = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let pk_b = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+
+        let traffic_meter = TrafficMeter::new(None);
+        for _ in 0..10 {
+            assert!(traffic_meter.try_consume(&pk_a, &pk_b, 1000).is_ok());
+        }
+        assert!(traffic_meter.try_consume(&pk_b, &pk_a, 7).is_ok());
+
+        let stats = traffic_meter.stats();
+        assert_eq!(stats.keys[&pk_a].bytes_sent, 10_000);
+        assert_eq!(stats.keys[&pk_a].bytes_received, 7);
+        assert_eq!(stats.keys[&pk_b].bytes_sent, 7);
+        assert_eq!(stats.keys[&pk_b].bytes_received, 10_000);
+        assert_eq!(stats.total_bytes(), 10_007);
+    }
+
+    #[test]
+    fn test_traffic_meter_limit() {
+        let pk_a = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let pk_b = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+
+        let traffic_meter = TrafficMeter::new(Some(TrafficLimit {
+            bytes_per_tick: 10,
+            max_burst_bytes: 25,
+        }));
+
+        // A full bucket allows a burst:
+        assert!(traffic_meter.try_consume(&pk_a, &pk_b, 20).is_ok());
+        let mut refill_receiver = traffic_meter.try_consume(&pk_a, &pk_b, 10).unwrap_err();
+        // Other public keys are not affected:
+        assert!(traffic_meter.try_consume(&pk_b, &pk_a, 25).is_ok());
+
+        assert_eq!(refill_receiver.try_recv(), Ok(None));
+        traffic_meter.tick();
+        assert_eq!(refill_receiver.try_recv(), Ok(Some(())));
+
+        // 5 + 10 bytes are now available:
+        assert!(traffic_meter.try_consume(&pk_a, &pk_b, 10).is_ok());
+        assert!(traffic_meter.try_consume(&pk_a, &pk_b, 10).is_err());
+
+        // Buckets do not grow beyond max_burst_bytes:
+        for _ in 0..10 {
+            traffic_meter.tick();
+        }
+        assert!(traffic_meter.try_consume(&pk_a, &pk_b, 25).is_ok());
+        assert!(traffic_meter.try_consume(&pk_a, &pk_b, 1).is_err());
+
+        let stats = traffic_meter.stats();
+        assert_eq!(stats.keys[&pk_a].bytes_sent, 55);
+        assert_eq!(stats.keys[&pk_a].throttled_ticks, 3);
+        assert_eq!(stats.keys[&pk_b].bytes_received, 55);
+        assert_eq!(stats.keys[&pk_b].throttled_ticks, 0);
+    }
+
+    #[test]
+    fn test_traffic_meter_large_message() {
+        let pk_a = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let pk_b = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+
+        let traffic_meter = TrafficMeter::new(Some(TrafficLimit {
+            bytes_per_tick: 10,
+            max_burst_bytes: 20,
+        }));
+
+        // A message larger than the bucket passes when the bucket is full:
+        assert!(traffic_meter.try_consume(&pk_a, &pk_b, 100).is_ok());
+        assert!(traffic_meter.try_consume(&pk_a, &pk_b, 100).is_err());
+        traffic_meter.tick();
+        assert!(traffic_meter.try_consume(&pk_a, &pk_b, 100).is_err());
+        traffic_meter.tick();
+        assert!(traffic_meter.try_consume(&pk_a, &pk_b, 100).is_ok());
+    }
+
+    #[test]
+    fn test_traffic_meter_remove_idle() {
+        let pk_a = PublicKey::from(&[0xaa; PUBLIC_KEY_LEN]);
+        let pk_b = PublicKey::from(&[0xbb; PUBLIC_KEY_LEN]);
+        let pk_c = PublicKey::from(&[0xcc; PUBLIC_KEY_LEN]);
+
+        let traffic_meter = TrafficMeter::new(None);
+        assert!(traffic_meter.try_consume(&pk_a, &pk_b, 10).is_ok());
+
+        // pk_a and pk_b were active during the first window:
+        for _ in 0..STATS_WINDOW_TICKS {
+            traffic_meter.tick();
+        }
+        assert_eq!(traffic_meter.stats().keys.len(), 2);
+
+        // Only pk_b and pk_c are active during the second window:
+        assert!(traffic_meter.try_consume(&pk_c, &pk_b, 5).is_ok());
+        for _ in 0..STATS_WINDOW_TICKS {
+            traffic_meter.tick();
+        }
+
+        let stats = traffic_meter.stats();
+        assert_eq!(stats.keys.len(), 2);
+        assert!(!stats.keys.contains_key(&pk_a));
+        assert_eq!(stats.keys[&pk_b].bytes_received, 15);
+        assert_eq!(stats.keys[&pk_c].bytes_sent, 5);
+        // Removed counters still count towards the total:
+        assert_eq!(stats.removed_bytes_sent, 10);
+        assert_eq!(stats.total_bytes(), 15);
+
+        // Nobody is active during the third window:
+        for _ in 0..STATS_WINDOW_TICKS {
+            traffic_meter.tick();
+        }
+        let stats = traffic_meter.stats();
+        assert!(stats.keys.is_empty());
+        assert_eq!(stats.total_bytes(), 15);
+    }
+}
@@ -2,19 +2,27 @@ use std::fs;
 use std::io;
 use std::path::PathBuf;
 
-use futures::future::select_all;
+use futures::channel::mpsc;
+use futures::future::join;
+use futures::StreamExt;
 
-use app::{AppBuyer, AppRoutes, MultiCommit, NodeConnection, PaymentStatus, PublicKey};
+use app::buyer::{FailureReason, PayInvoiceConfig, PayInvoiceError, PayInvoiceEvent};
+use app::route::RouteSearchMode;
+use app::ser_string::public_key_to_string;
+use app::{AppBuyer, AppRoutes, NodeConnection, PaymentStatus, PublicKey};
 
 use structopt::StructOpt;
 
-use app::gen::{gen_payment_id, gen_uid};
+use app::gen::gen_payment_id;
+use app::invoice::{load_invoice_from_file, verify_signed_invoice};
 
-use crate::file::invoice::load_invoice_from_file;
 use crate::file::multi_commit::store_multi_commit_to_file;
 use crate::file::payment::{load_payment_from_file, store_payment_to_file, Payment};
 use crate::file::receipt::store_receipt_to_file;
-use crate::multi_route_util::choose_multi_route;
+use crate::utils::unix_time_now;
+
+/// Maximum amount of times we request new routes when paying an invoice
+const MAX_PAY_INVOICE_ATTEMPTS: usize = 8;
 
 /// Pay an invoice
 #[derive(Clone, Debug, StructOpt)]
@@ -28,6 +36,13 @@ pub struct PayInvoiceCmd {
     /// Output commit file
     #[structopt(parse(from_os_str), short = "c", long = "commit")]
     pub commit_file: PathBuf,
+    /// Maximum total amount of credits to pay as fees (Unlimited by default)
+    #[structopt(long = "max-fees")]
+    pub max_fees: Option<u128>,
+    /// Amount of ticks after which the node stops trying to deliver the payment
+    /// (The node's payment timeout by default)
+    #[structopt(long = "deadline-ticks")]
+    pub deadline_ticks: Option<u64>,
 }
 
 /// Check payment status (And obtain receipt if successful)
@@ -61,15 +76,19 @@ pub enum BuyerError {
     AppRoutesError,
     SendBuyerError,
     NoSuitableRoute,
+    MaxFeesExceeded,
     CommitFileAlreadyExists,
     ReceiptFileAlreadyExists,
     PaymentFileAlreadyExists,
     StoreReceiptError,
     ReceiptAckError,
     LoadInvoiceError,
+    InvalidInvoiceSignature,
+    InvoiceExpired,
     WriteError,
     CreatePaymentFailed,
     CreateTransactionFailed,
+    PaymentTimedOut,
     StoreCommitError,
     RequestClosePaymentError,
     AckClosePaymentError,
@@ -79,6 +98,22 @@ pub enum BuyerError {
     RemovePaymentError,
 }
 
+/// A human readable description of a failure reason
+fn failure_reason_str(reason: &FailureReason) -> &'static str {
+    match reason {
+        FailureReason::UnknownInvoice => "No matching open invoice at the destination",
+        FailureReason::FriendNotReady => "Next node on the route is not ready",
+        FailureReason::RequestsClosed => "Next node on the route does not accept requests",
+        FailureReason::InsufficientFees => "Insufficient fees",
+        FailureReason::NoCapacity => "Insufficient capacity",
+        FailureReason::InvalidRoute => "Invalid route",
+        FailureReason::RouteTooLong => "Route is too long",
+        FailureReason::ChannelReset => "Channel was reset",
+        FailureReason::TimedOut => "Payment timed out",
+        FailureReason::Rejected => "Request was rejected",
+    }
+}
+
 /// Pay an invoice
 async fn buyer_pay_invoice(
     pay_invoice_cmd: PayInvoiceCmd,
@@ -91,6 +126,8 @@ async fn buyer_pay_invoice(
         invoice_file,
         payment_file,
         commit_file,
+        max_fees,
+        deadline_ticks,
     } = pay_invoice_cmd;
 
     // Make sure that we will be able to write the Payment file
@@ -108,34 +145,15 @@ async fn buyer_pay_invoice(
     let invoice =
         load_invoice_from_file(&invoice_file).map_err(|_| BuyerError::LoadInvoiceError)?;
 
-    // TODO: We might get routes with the exact capacity,
-    // but this will not be enough for sending our amount because
-    // we also need to pay nodes on the way.
-    // We might need to solve this issue at the index server side
-    // (Should the Server take into account the extra credits that should be paid along the way?).
-    let multi_routes = await!(app_routes.request_routes(
-        invoice.dest_payment,
-        local_public_key, // source
-        invoice.dest_public_key.clone(),
-        None
-    )) // No exclusion of edges
-    .map_err(|_| BuyerError::AppRoutesError)?;
-
-    let (route_index, multi_route_choice) = choose_multi_route(&multi_routes, invoice.dest_payment)
-        .ok_or(BuyerError::NoSuitableRoute)?;
-    let multi_route = &multi_routes[route_index];
-
-    // Calculate total fees:
-    // TODO: Possibly ask the user if he wants to pay this amount of fees at this point.
-    let mut total_fees = 0u128;
-    for (route_index, dest_payment) in &multi_route_choice {
-        let fee = multi_route.routes[*route_index]
-            .rate
-            .calc_fee(*dest_payment)
-            .unwrap();
-        total_fees = total_fees.checked_add(fee).unwrap();
+    // Make sure that the invoice was issued by the seller:
+    if !verify_signed_invoice(&invoice) {
+        return Err(BuyerError::InvalidInvoiceSignature);
+    }
+
+    // Don't pay for an invoice the seller might have already canceled:
+    if invoice.is_expired(unix_time_now()) {
+        return Err(BuyerError::InvoiceExpired);
     }
-    writeln!(writer, "Total fees: {}", total_fees).map_err(|_| BuyerError::WriteError)?;
 
     // Create a new payment
     let payment_id = gen_payment_id();
@@ -144,55 +162,77 @@ async fn buyer_pay_invoice(
     // Keep payment id for later reference:
     store_payment_to_file(&payment, &payment_file).map_err(|_| BuyerError::StorePaymentError)?;
 
-    await!(app_buyer.create_payment(
+    let pay_invoice_config = PayInvoiceConfig {
+        max_fees: max_fees.unwrap_or(u128::max_value()),
+        max_attempts: MAX_PAY_INVOICE_ATTEMPTS,
+        search_mode: RouteSearchMode::Cheapest,
+        opt_deadline_ticks: deadline_ticks,
+    };
+
+    let (event_sender, mut event_receiver) = mpsc::channel(0);
+    let fut_pay_invoice = app_buyer.pay_invoice(
+        &mut app_routes,
+        local_public_key,
         payment_id,
-        invoice.invoice_id.clone(),
-        invoice.dest_payment,
-        invoice.dest_public_key.clone()
-    ))
-    .map_err(|_| BuyerError::CreatePaymentFailed)?;
-
-    // Create new transactions (One for every route). On the first failure cancel all
-    // transactions. Succeed only if all transactions succeed.
-
-    let mut fut_list = Vec::new();
-    for (route_index, dest_payment) in &multi_route_choice {
-        let route = &multi_route.routes[*route_index];
-        let request_id = gen_uid();
-        let mut c_app_buyer = app_buyer.clone();
-        fut_list.push(
-            // TODO: Possibly a more efficient way than Box::pin?
-            Box::pin(async move {
-                await!(c_app_buyer.create_transaction(
-                    payment_id,
-                    request_id,
-                    route.route.clone(),
-                    *dest_payment,
-                    route.rate.calc_fee(*dest_payment).unwrap(),
-                ))
-            }),
-        );
-    }
+        invoice.details.invoice_id.clone(),
+        invoice.details.total_dest_payment,
+        invoice.dest_public_key.clone(),
+        pay_invoice_config,
+        Some(event_sender),
+    );
+
+    // Report progress to the user while the payment is being made:
+    let fut_events = async {
+        let mut total_fees = 0u128;
+        while let Some(event) = await!(event_receiver.next()) {
+            let write_res = match event {
+                PayInvoiceEvent::RequestRoutes { remaining, .. } => {
+                    writeln!(writer, "Requesting routes for {} credits...", remaining)
+                }
+                PayInvoiceEvent::TransactionSuccess { fees, .. } => {
+                    total_fees = total_fees.saturating_add(fees);
+                    writeln!(writer, "Transaction succeeded. Total fees: {}", total_fees)
+                }
+                PayInvoiceEvent::TransactionFailure {
+                    request_failure, ..
+                } => writeln!(
+                    writer,
+                    "Transaction failed at node {}: {}",
+                    public_key_to_string(&request_failure.reporting_public_key),
+                    failure_reason_str(&request_failure.reason)
+                ),
+            };
+            write_res.map_err(|_| BuyerError::WriteError)?;
+        }
+        Ok::<_, BuyerError>(())
+    };
 
-    let mut commits = Vec::new();
-    for _ in 0..multi_route_choice.len() {
-        let (output, _fut_index, new_fut_list) = await!(select_all(fut_list));
-        match output {
-            Ok(commit) => commits.push(commit),
-            Err(_) => {
-                let _ = await!(app_buyer.request_close_payment(payment_id));
-                return Err(BuyerError::CreateTransactionFailed);
+    let (pay_invoice_res, events_res) = await!(join(fut_pay_invoice, fut_events));
+    events_res?;
+
+    let multi_commit = match pay_invoice_res {
+        Ok(multi_commit) => multi_commit,
+        Err(pay_invoice_failure) => {
+            if !pay_invoice_failure.commits.is_empty() {
+                writeln!(
+                    writer,
+                    "{} transactions succeeded before the failure. \
+                     Their credits will be released once the invoice is canceled.",
+                    pay_invoice_failure.commits.len()
+                )
+                .map_err(|_| BuyerError::WriteError)?;
             }
+            return Err(match pay_invoice_failure.error {
+                PayInvoiceError::BuyerError(_) => BuyerError::CreatePaymentFailed,
+                PayInvoiceError::RequestRoutesError => BuyerError::AppRoutesError,
+                PayInvoiceError::NoSuitableRoute => BuyerError::NoSuitableRoute,
+                PayInvoiceError::MaxFeesExceeded => BuyerError::MaxFeesExceeded,
+                PayInvoiceError::MaxAttemptsReached | PayInvoiceError::TransactionFailed(_) => {
+                    BuyerError::CreateTransactionFailed
+                }
+                PayInvoiceError::TimedOut => BuyerError::PaymentTimedOut,
+            });
         }
-        fut_list = new_fut_list;
-    }
-
-    let _ = await!(app_buyer.request_close_payment(payment_id));
-
-    let multi_commit = MultiCommit {
-        invoice_id: invoice.invoice_id.clone(),
-        total_dest_payment: invoice.dest_payment,
-        commits,
     };
 
     writeln!(writer, "Payment successful!").map_err(|_| BuyerError::WriteError)?;
@@ -253,6 +293,11 @@ async fn buyer_payment_status(
         PaymentStatus::Canceled(ack_uid) => {
             writeln!(writer, "Payment was canceled.").map_err(|_| BuyerError::WriteError)?;
 
+            Some(ack_uid)
+        }
+        PaymentStatus::TimedOut(ack_uid) => {
+            writeln!(writer, "Payment timed out.").map_err(|_| BuyerError::WriteError)?;
+
             Some(ack_uid)
         }
     };
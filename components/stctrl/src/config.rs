@@ -2,12 +2,16 @@ use std::path::PathBuf;
 
 use structopt::StructOpt;
 
+use app::buyer::RebalanceError;
+use app::gen::gen_payment_id;
 use app::report::{ChannelStatusReport, NodeReport};
+use app::route::RouteSearchMode;
 use app::{
-    load_friend_from_file, load_index_server_from_file, load_relay_from_file, AppConfig,
-    NamedIndexServerAddress, NamedRelayAddress, NodeConnection, Rate,
+    load_friend_from_file, load_index_server_from_file, load_relay_from_file, AppBuyer, AppConfig,
+    AppRoutes, NamedIndexServerAddress, NamedRelayAddress, NodeConnection, PublicKey, Rate,
 };
 
+use crate::file::payment::{store_payment_to_file, Payment};
 use crate::utils::friend_public_key_by_name;
 
 /// Add a relay
@@ -148,6 +152,27 @@ pub struct ResetFriendCmd {
     pub friend_name: String,
 }
 
+/// Move credits between two friends, by paying ourselves along a cycle that leaves through one
+/// friend and comes back through the other.
+#[derive(Clone, Debug, StructOpt)]
+pub struct RebalanceCmd {
+    /// Name of the friend to pay through (Our balance with this friend decreases)
+    #[structopt(long = "out")]
+    pub out_friend_name: String,
+    /// Name of the friend to get paid through (Our balance with this friend increases)
+    #[structopt(long = "in")]
+    pub in_friend_name: String,
+    /// Amount of credits to move
+    #[structopt(long = "amount", short = "a")]
+    pub amount: u128,
+    /// Maximum total amount of credits to pay as fees
+    #[structopt(long = "max-fees")]
+    pub max_fees: u128,
+    /// Output payment file (Used to track the payment)
+    #[structopt(parse(from_os_str), short = "p", long = "payment")]
+    pub payment_file: PathBuf,
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum ConfigCmd {
     /// Add a relay server
@@ -193,6 +218,9 @@ pub enum ConfigCmd {
     /// Reset mutual credit with a friend according to friend's terms
     #[structopt(name = "reset-friend")]
     ResetFriend(ResetFriendCmd),
+    /// Move credits from one friend to another, paying ourselves along a cycle
+    #[structopt(name = "rebalance")]
+    Rebalance(RebalanceCmd),
 }
 
 #[derive(Debug)]
@@ -217,6 +245,15 @@ pub enum ConfigError {
     ParseMaxDebtError,
     ChannelNotInconsistent,
     UnknownRemoteResetTerms,
+    NoBuyerPermissions,
+    NoRoutesPermissions,
+    PaymentFileAlreadyExists,
+    StorePaymentError,
+    SameFriend,
+    RequestRoutesError,
+    NoSuitableRoute,
+    MaxFeesExceeded,
+    RebalanceFailed,
 }
 
 async fn config_add_relay(
@@ -513,6 +550,62 @@ async fn config_reset_friend(
         .map_err(|_| ConfigError::AppConfigError)
 }
 
+async fn config_rebalance(
+    rebalance_cmd: RebalanceCmd,
+    local_public_key: PublicKey,
+    mut app_buyer: AppBuyer,
+    mut app_routes: AppRoutes,
+    node_report: NodeReport,
+) -> Result<(), ConfigError> {
+    let RebalanceCmd {
+        out_friend_name,
+        in_friend_name,
+        amount,
+        max_fees,
+        payment_file,
+    } = rebalance_cmd;
+
+    let out_public_key = friend_public_key_by_name(&node_report, &out_friend_name)
+        .ok_or(ConfigError::FriendNameNotFound)?
+        .clone();
+    let in_public_key = friend_public_key_by_name(&node_report, &in_friend_name)
+        .ok_or(ConfigError::FriendNameNotFound)?
+        .clone();
+
+    // Make sure that we will be able to write the Payment file
+    // before we do the actual payment:
+    if payment_file.exists() {
+        return Err(ConfigError::PaymentFileAlreadyExists);
+    }
+
+    // Create a new payment
+    let payment_id = gen_payment_id();
+    let payment = Payment { payment_id };
+
+    // Keep payment id for later reference:
+    store_payment_to_file(&payment, &payment_file).map_err(|_| ConfigError::StorePaymentError)?;
+
+    await!(app_buyer.rebalance(
+        &mut app_routes,
+        local_public_key,
+        payment_id,
+        out_public_key,
+        in_public_key,
+        amount,
+        max_fees,
+        RouteSearchMode::Cheapest
+    ))
+    .map_err(|e| match e {
+        RebalanceError::SameFriend => ConfigError::SameFriend,
+        RebalanceError::RequestRoutesError => ConfigError::RequestRoutesError,
+        RebalanceError::NoSuitableRoute => ConfigError::NoSuitableRoute,
+        RebalanceError::MaxFeesExceeded => ConfigError::MaxFeesExceeded,
+        RebalanceError::BuyerError(_) => ConfigError::RebalanceFailed,
+    })?;
+
+    Ok(())
+}
+
 pub async fn config(
     config_cmd: ConfigCmd,
     mut node_connection: NodeConnection,
@@ -592,6 +685,24 @@ pub async fn config(
             app_config,
             node_report
         ))?,
+        ConfigCmd::Rebalance(rebalance_cmd) => {
+            let local_public_key = node_report.funder_report.local_public_key.clone();
+            let app_buyer = node_connection
+                .buyer()
+                .ok_or(ConfigError::NoBuyerPermissions)?
+                .clone();
+            let app_routes = node_connection
+                .routes()
+                .ok_or(ConfigError::NoRoutesPermissions)?
+                .clone();
+            await!(config_rebalance(
+                rebalance_cmd,
+                local_public_key,
+                app_buyer,
+                app_routes,
+                node_report
+            ))?
+        }
     }
 
     Ok(())
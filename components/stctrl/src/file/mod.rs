@@ -1,4 +1,3 @@
-pub mod invoice;
 pub mod multi_commit;
 pub mod payment;
 pub mod receipt;
@@ -8,7 +8,9 @@ use app::report::{
     ChannelStatusReport, FriendReport, FriendStatusReport, NodeReport, RequestsStatusReport,
 };
 use app::ser_string::public_key_to_string;
-use app::{store_friend_to_file, AppReport, FriendAddress, NodeConnection, RelayAddress};
+use app::{
+    store_friend_to_file, AppReport, AppRoutes, FriendAddress, NodeConnection, RelayAddress,
+};
 
 use crate::file::token::store_token_to_file;
 use crate::utils::friend_public_key_by_name;
@@ -54,6 +56,10 @@ pub struct ExportTicketCmd {
     pub ticket_file: PathBuf,
 }
 
+/// Display the amount of credits this node can currently receive
+#[derive(Clone, Debug, StructOpt)]
+pub struct LiquidityCmd {}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum InfoCmd {
     // /// Show local public key (Used as address for sending funds)
@@ -77,6 +83,9 @@ pub enum InfoCmd {
     /// Export ticket for this node
     #[structopt(name = "export-ticket")]
     ExportTicket(ExportTicketCmd),
+    /// Show the amount of credits this node can receive, from friends and from the network
+    #[structopt(name = "liquidity")]
+    Liquidity(LiquidityCmd),
 }
 
 #[derive(Debug)]
@@ -96,6 +105,8 @@ pub enum InfoError {
     InvalidReceipt,
     DestPaymentMismatch,
     InvoiceIdMismatch,
+    NoRoutesPermissions,
+    RequestLiquidityError,
 }
 
 /// Get a most recently known node report:
@@ -379,6 +390,56 @@ pub async fn info_export_ticket(
     Ok(())
 }
 
+/// Show how many credits this node can receive from every friend, and from all the nodes at
+/// every hop distance (As known to the index server).
+pub async fn info_liquidity(
+    mut app_routes: AppRoutes,
+    mut app_report: AppReport,
+    writer: &mut impl io::Write,
+) -> Result<(), InfoError> {
+    let report = await!(get_report(&mut app_report))?;
+    let local_public_key = report.funder_report.local_public_key.clone();
+
+    let liquidity = await!(app_routes.request_liquidity(local_public_key))
+        .map_err(|_| InfoError::RequestLiquidityError)?;
+
+    let mut table = Table::new();
+    table.set_titles(row!["friend", "receive capacity"]);
+    for neighbor_liquidity in &liquidity.neighbors {
+        // Show friend's name if possible:
+        let friend_str = match report
+            .funder_report
+            .friends
+            .get(&neighbor_liquidity.public_key)
+        {
+            Some(friend_report) => friend_report.name.clone(),
+            None => public_key_to_string(&neighbor_liquidity.public_key),
+        };
+        table.add_row(row![friend_str, neighbor_liquidity.recv_capacity]);
+    }
+
+    if !table.is_empty() {
+        table.print(writer).map_err(|_| InfoError::WriteError)?;
+    } else {
+        writeln!(writer, "No friends can send credits.").map_err(|_| InfoError::WriteError)?;
+    }
+
+    let mut table = Table::new();
+    table.set_titles(row!["hops", "nodes", "receive capacity"]);
+    for hops_liquidity in &liquidity.hops {
+        table.add_row(row![
+            hops_liquidity.hops,
+            hops_liquidity.num_nodes,
+            hops_liquidity.recv_capacity
+        ]);
+    }
+
+    if !table.is_empty() {
+        table.print(writer).map_err(|_| InfoError::WriteError)?;
+    }
+    Ok(())
+}
+
 pub async fn info(
     info_cmd: InfoCmd,
     mut node_connection: NodeConnection,
@@ -398,6 +459,13 @@ pub async fn info(
         InfoCmd::ExportTicket(export_ticket_cmd) => {
             await!(info_export_ticket(export_ticket_cmd, app_report))?
         }
+        InfoCmd::Liquidity(_liquidity_cmd) => {
+            let app_routes = node_connection
+                .routes()
+                .ok_or(InfoError::NoRoutesPermissions)?
+                .clone();
+            await!(info_liquidity(app_routes, app_report, writer))?
+        }
     }
     Ok(())
 }
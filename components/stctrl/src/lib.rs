@@ -20,7 +20,6 @@ extern crate prettytable;
 #[macro_use]
 extern crate serde_derive;
 
-mod multi_route_util;
 
 pub mod buyer;
 pub mod config;
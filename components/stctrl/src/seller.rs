@@ -1,11 +1,15 @@
 use std::fs;
+use std::io;
 use std::path::PathBuf;
 
 use app::gen::gen_invoice_id;
-use app::{AppSeller, NodeConnection, PublicKey};
+use app::invoice::{load_invoice_from_file, store_invoice_to_file, InvoiceDetails, SignedInvoice};
+use app::report::NodeReport;
+use app::seller::InvoiceStatus;
+use app::{AppSeller, NodeConnection, RelayAddress, TICK_MS};
 
-use crate::file::invoice::{load_invoice_from_file, store_invoice_to_file, Invoice};
 use crate::file::multi_commit::load_multi_commit_from_file;
+use crate::utils::unix_time_now;
 
 use structopt::StructOpt;
 
@@ -18,6 +22,12 @@ pub struct CreateInvoiceCmd {
     /// Path of output invoice file
     #[structopt(parse(from_os_str), short = "i", long = "invoice")]
     pub invoice_file: PathBuf,
+    /// Amount of timer ticks after which the invoice is canceled (Never expires by default)
+    #[structopt(long = "expiry")]
+    pub expiry_ticks: Option<u64>,
+    /// A description of the invoice, shown to the buyer
+    #[structopt(short = "m", long = "memo", default_value = "")]
+    pub memo: String,
 }
 
 /// Cancel invoice
@@ -39,6 +49,14 @@ pub struct CommitInvoiceCmd {
     pub commit_file: PathBuf,
 }
 
+/// Check invoice status (Transactions received so far and expiry)
+#[derive(Clone, Debug, StructOpt)]
+pub struct InvoiceStatusCmd {
+    /// Path to invoice file
+    #[structopt(parse(from_os_str), short = "i", long = "invoice")]
+    pub invoice_file: PathBuf,
+}
+
 /// Funds sending related commands
 #[derive(Clone, Debug, StructOpt)]
 pub enum SellerCmd {
@@ -51,6 +69,9 @@ pub enum SellerCmd {
     /// Commit an invoice (Using a Commit message from buyer)
     #[structopt(name = "commit-invoice")]
     CommitInvoice(CommitInvoiceCmd),
+    /// Check the status of an invoice
+    #[structopt(name = "invoice-status")]
+    InvoiceStatus(InvoiceStatusCmd),
 }
 
 #[derive(Debug)]
@@ -67,16 +88,21 @@ pub enum SellerError {
     CommitInvoiceError,
     InvoiceCommitMismatch,
     RemoveInvoiceError,
+    InvoiceStatusError,
+    SignInvoiceError,
+    WriteError,
 }
 
 async fn seller_create_invoice(
     create_invoice_cmd: CreateInvoiceCmd,
-    local_public_key: PublicKey,
+    node_report: NodeReport,
     mut app_seller: AppSeller,
 ) -> Result<(), SellerError> {
     let CreateInvoiceCmd {
         amount,
         invoice_file,
+        expiry_ticks,
+        memo,
     } = create_invoice_cmd;
 
     // Make sure we don't override an existing invoice file:
@@ -86,18 +112,53 @@ async fn seller_create_invoice(
 
     let invoice_id = gen_invoice_id();
 
-    let dest_public_key = local_public_key;
+    await!(app_seller.add_invoice(invoice_id.clone(), amount, expiry_ticks))
+        .map_err(|_| SellerError::AddInvoiceError)?;
+
+    // The buyer should not pay after the node cancels the invoice:
+    let opt_expiry = expiry_ticks.map(|expiry_ticks| {
+        let expiry_secs = expiry_ticks.saturating_mul(TICK_MS as u64) / 1000;
+        unix_time_now().saturating_add(expiry_secs)
+    });
+
+    // Let the buyer know how to reach us:
+    let relays = node_report
+        .funder_report
+        .relays
+        .iter()
+        .cloned()
+        .map(RelayAddress::from)
+        .collect();
 
-    let invoice = Invoice {
+    let details = InvoiceDetails {
         invoice_id: invoice_id.clone(),
-        dest_public_key,
-        dest_payment: amount,
+        total_dest_payment: amount,
+        memo,
+        opt_expiry,
+        relays,
     };
 
-    await!(app_seller.add_invoice(invoice_id.clone(), amount))
-        .map_err(|_| SellerError::AddInvoiceError)?;
+    // If we fail to hand out the invoice, we cancel it, so that it doesn't stay open at the node:
+    let signature = match await!(app_seller.sign_invoice(details.clone())) {
+        Ok(signature) => signature,
+        Err(_) => {
+            let _ = await!(app_seller.cancel_invoice(invoice_id));
+            return Err(SellerError::SignInvoiceError);
+        }
+    };
+
+    let signed_invoice = SignedInvoice {
+        dest_public_key: node_report.funder_report.local_public_key.clone(),
+        details,
+        signature,
+    };
 
-    store_invoice_to_file(&invoice, &invoice_file).map_err(|_| SellerError::StoreInvoiceError)
+    if store_invoice_to_file(&signed_invoice, &invoice_file).is_err() {
+        let _ = await!(app_seller.cancel_invoice(invoice_id));
+        return Err(SellerError::StoreInvoiceError);
+    }
+
+    Ok(())
 }
 
 async fn seller_cancel_invoice(
@@ -109,7 +170,7 @@ async fn seller_cancel_invoice(
     let invoice =
         load_invoice_from_file(&invoice_file).map_err(|_| SellerError::LoadInvoiceError)?;
 
-    await!(app_seller.cancel_invoice(invoice.invoice_id))
+    await!(app_seller.cancel_invoice(invoice.details.invoice_id))
         .map_err(|_| SellerError::CancelInvoiceError)?;
 
     fs::remove_file(&invoice_file).map_err(|_| SellerError::RemoveInvoiceError)
@@ -133,26 +194,71 @@ async fn seller_commit_invoice(
     let multi_commit =
         load_multi_commit_from_file(&commit_file).map_err(|_| SellerError::LoadCommitError)?;
 
-    if multi_commit.invoice_id != invoice.invoice_id {
+    if multi_commit.invoice_id != invoice.details.invoice_id {
         return Err(SellerError::InvoiceCommitMismatch);
     }
 
     await!(app_seller.commit_invoice(multi_commit)).map_err(|_| SellerError::CommitInvoiceError)
 }
 
+async fn seller_invoice_status(
+    invoice_status_cmd: InvoiceStatusCmd,
+    mut app_seller: AppSeller,
+    writer: &mut impl io::Write,
+) -> Result<(), SellerError> {
+    let InvoiceStatusCmd { invoice_file } = invoice_status_cmd;
+
+    let invoice =
+        load_invoice_from_file(&invoice_file).map_err(|_| SellerError::LoadInvoiceError)?;
+
+    let invoice_status = await!(app_seller.request_invoice_status(invoice.details.invoice_id))
+        .map_err(|_| SellerError::InvoiceStatusError)?;
+
+    let open_invoice_status = match invoice_status {
+        InvoiceStatus::InvoiceNotFound => {
+            writeln!(
+                writer,
+                "Invoice could not be found (It might have been committed, canceled or expired)"
+            )
+            .map_err(|_| SellerError::WriteError)?;
+            return Ok(());
+        }
+        InvoiceStatus::Open(open_invoice_status) => open_invoice_status,
+    };
+
+    writeln!(
+        writer,
+        "Collected: {}/{} credits",
+        open_invoice_status.collected_dest_payment, open_invoice_status.total_dest_payment
+    )
+    .map_err(|_| SellerError::WriteError)?;
+    writeln!(
+        writer,
+        "Transactions received: {}",
+        open_invoice_status.transactions.len()
+    )
+    .map_err(|_| SellerError::WriteError)?;
+    match open_invoice_status.opt_ticks_left {
+        Some(ticks_left) => writeln!(writer, "Expires in: {} ticks", ticks_left),
+        None => writeln!(writer, "Expires in: never"),
+    }
+    .map_err(|_| SellerError::WriteError)?;
+
+    Ok(())
+}
+
 pub async fn seller(
     seller_cmd: SellerCmd,
     mut node_connection: NodeConnection,
+    writer: &mut impl io::Write,
 ) -> Result<(), SellerError> {
-    // Get our local public key:
+    // Get our local public key and relays:
     let mut app_report = node_connection.report().clone();
     let (node_report, incoming_mutations) =
         await!(app_report.incoming_reports()).map_err(|_| SellerError::GetReportError)?;
     // We currently don't need live updates about report mutations:
     drop(incoming_mutations);
 
-    let local_public_key = node_report.funder_report.local_public_key.clone();
-
     let app_seller = node_connection
         .seller()
         .ok_or(SellerError::NoSellerPermissions)?
@@ -161,7 +267,7 @@ pub async fn seller(
     match seller_cmd {
         SellerCmd::CreateInvoice(create_invoice_cmd) => await!(seller_create_invoice(
             create_invoice_cmd,
-            local_public_key,
+            node_report,
             app_seller,
         ))?,
         SellerCmd::CancelInvoice(cancel_invoice_cmd) => {
@@ -171,6 +277,11 @@ pub async fn seller(
         SellerCmd::CommitInvoice(commit_invoice_cmd) => {
             await!(seller_commit_invoice(commit_invoice_cmd, app_seller))?
         }
+        SellerCmd::InvoiceStatus(invoice_status_cmd) => await!(seller_invoice_status(
+            invoice_status_cmd,
+            app_seller,
+            writer
+        ))?,
     }
 
     Ok(())
@@ -1,4 +1,5 @@
 use std::io;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 use futures::executor::ThreadPool;
@@ -80,6 +81,9 @@ pub struct StCtrlCmd {
     /// Node ticket file path
     #[structopt(parse(from_os_str), short = "T", long = "ticket")]
     pub node_ticket: PathBuf,
+    /// Connect to the node through a SOCKS5 proxy (Example: 127.0.0.1:9050)
+    #[structopt(long = "socks5")]
+    pub socks5: Option<SocketAddr>,
     #[structopt(flatten)]
     pub subcommand: StCtrlSubcommand,
 }
@@ -90,6 +94,7 @@ pub fn stctrl(st_ctrl_cmd: StCtrlCmd, writer: &mut impl io::Write) -> Result<(),
     let StCtrlCmd {
         idfile,
         node_ticket,
+        socks5,
         subcommand,
     } = st_ctrl_cmd;
 
@@ -117,6 +122,7 @@ pub fn stctrl(st_ctrl_cmd: StCtrlCmd, writer: &mut impl io::Write) -> Result<(),
         let node_connection = await!(connect(
             node_address.public_key,
             node_address.address,
+            socks5,
             app_identity_client,
             c_thread_pool.clone()
         ))
@@ -128,7 +134,9 @@ pub fn stctrl(st_ctrl_cmd: StCtrlCmd, writer: &mut impl io::Write) -> Result<(),
             StCtrlSubcommand::Buyer(buyer_cmd) => {
                 await!(buyer(buyer_cmd, node_connection, writer))?
             }
-            StCtrlSubcommand::Seller(seller_cmd) => await!(seller(seller_cmd, node_connection))?,
+            StCtrlSubcommand::Seller(seller_cmd) => {
+                await!(seller(seller_cmd, node_connection, writer))?
+            }
         }
         Ok(())
     })
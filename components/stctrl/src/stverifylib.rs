@@ -3,11 +3,12 @@ use std::path::PathBuf;
 
 use structopt::StructOpt;
 
-use crate::file::invoice::load_invoice_from_file;
 use crate::file::receipt::load_receipt_from_file;
 use crate::file::token::load_token_from_file;
+use crate::utils::unix_time_now;
 
-use app::ser_string::public_key_to_string;
+use app::invoice::{load_invoice_from_file, verify_signed_invoice};
+use app::ser_string::{invoice_id_to_string, public_key_to_string};
 use app::{verify_move_token_hashed_report, verify_receipt};
 
 #[derive(Debug)]
@@ -16,6 +17,8 @@ pub enum StVerifyError {
     WriteError,
     TokenInvalid,
     LoadInvoiceError,
+    InvalidInvoice,
+    InvoiceExpired,
     LoadReceiptError,
     InvoiceIdMismatch,
     DestPaymentMismatch,
@@ -31,6 +34,15 @@ pub struct VerifyTokenCmd {
     pub token: PathBuf,
 }
 
+/// Verify an invoice received from a seller.
+/// Makes sure that the invoice was signed by the seller and has not expired.
+#[derive(Clone, Debug, StructOpt)]
+pub struct VerifyInvoiceCmd {
+    /// Path of invoice file
+    #[structopt(parse(from_os_str), short = "i", long = "invoice")]
+    pub invoice: PathBuf,
+}
+
 /// Verify receipt file
 #[derive(Clone, Debug, StructOpt)]
 pub struct VerifyReceiptCmd {
@@ -51,6 +63,9 @@ pub enum StVerifyCmd {
     /// Verify friend's last token
     #[structopt(name = "verify-token")]
     VerifyToken(VerifyTokenCmd),
+    /// Verify an invoice received from a seller
+    #[structopt(name = "verify-invoice")]
+    VerifyInvoice(VerifyInvoiceCmd),
     /// Verify a receipt against an invoice
     #[structopt(name = "verify-receipt")]
     VerifyReceipt(VerifyReceiptCmd),
@@ -116,6 +131,62 @@ fn stverify_verify_token(
     }
 }
 
+/// Verify a given invoice
+/// If the given invoice is valid, output invoice details
+fn stverify_verify_invoice(
+    verify_invoice_cmd: VerifyInvoiceCmd,
+    writer: &mut impl io::Write,
+) -> Result<(), StVerifyError> {
+    let invoice = load_invoice_from_file(&verify_invoice_cmd.invoice)
+        .map_err(|_| StVerifyError::LoadInvoiceError)?;
+
+    if !verify_signed_invoice(&invoice) {
+        return Err(StVerifyError::InvalidInvoice);
+    }
+
+    if invoice.is_expired(unix_time_now()) {
+        return Err(StVerifyError::InvoiceExpired);
+    }
+
+    writeln!(writer, "Invoice is valid!").map_err(|_| StVerifyError::WriteError)?;
+    writeln!(writer).map_err(|_| StVerifyError::WriteError)?;
+    writeln!(
+        writer,
+        "invoice_id: {}",
+        invoice_id_to_string(&invoice.details.invoice_id)
+    )
+    .map_err(|_| StVerifyError::WriteError)?;
+    writeln!(
+        writer,
+        "dest_public_key: {}",
+        public_key_to_string(&invoice.dest_public_key)
+    )
+    .map_err(|_| StVerifyError::WriteError)?;
+    writeln!(
+        writer,
+        "total_dest_payment: {}",
+        invoice.details.total_dest_payment
+    )
+    .map_err(|_| StVerifyError::WriteError)?;
+    writeln!(writer, "memo: {}", invoice.details.memo).map_err(|_| StVerifyError::WriteError)?;
+    match invoice.details.opt_expiry {
+        Some(expiry) => writeln!(writer, "expiry: {}", expiry),
+        None => writeln!(writer, "expiry: never"),
+    }
+    .map_err(|_| StVerifyError::WriteError)?;
+    for relay in &invoice.details.relays {
+        writeln!(
+            writer,
+            "relay: {} ({})",
+            relay.address.as_str(),
+            public_key_to_string(&relay.public_key)
+        )
+        .map_err(|_| StVerifyError::WriteError)?;
+    }
+
+    Ok(())
+}
+
 /// Verify a given receipt
 fn stverify_verify_receipt(
     verify_receipt_cmd: VerifyReceiptCmd,
@@ -127,13 +198,18 @@ fn stverify_verify_receipt(
     let receipt = load_receipt_from_file(&verify_receipt_cmd.receipt)
         .map_err(|_| StVerifyError::LoadReceiptError)?;
 
+    // Make sure that the invoice was issued by the seller:
+    if !verify_signed_invoice(&invoice) {
+        return Err(StVerifyError::InvalidInvoice);
+    }
+
     // Make sure that the invoice and receipt files match:
     // Verify invoice_id match:
-    if invoice.invoice_id != receipt.invoice_id {
+    if invoice.details.invoice_id != receipt.invoice_id {
         return Err(StVerifyError::InvoiceIdMismatch);
     }
     // Verify dest_payment match:
-    if invoice.dest_payment != receipt.total_dest_payment {
+    if invoice.details.total_dest_payment != receipt.total_dest_payment {
         return Err(StVerifyError::DestPaymentMismatch);
     }
 
@@ -153,6 +229,9 @@ pub fn stverify(
         StVerifyCmd::VerifyToken(verify_token_cmd) => {
             stverify_verify_token(verify_token_cmd, writer)
         }
+        StVerifyCmd::VerifyInvoice(verify_invoice_cmd) => {
+            stverify_verify_invoice(verify_invoice_cmd, writer)
+        }
         StVerifyCmd::VerifyReceipt(verify_receipt_cmd) => {
             stverify_verify_receipt(verify_receipt_cmd, writer)
         }
@@ -1,3 +1,5 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use app::report::NodeReport;
 use app::PublicKey;
 
@@ -14,3 +16,11 @@ pub fn friend_public_key_by_name<'a>(
     }
     None
 }
+
+/// Current time, in seconds since the UNIX epoch
+pub fn unix_time_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
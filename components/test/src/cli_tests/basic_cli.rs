@@ -2,6 +2,7 @@ use std::{str, thread, time};
 
 use tempfile::tempdir;
 
+use bin::node_db::{DbBackend, DbSecretOpt};
 use bin::stindexlib::{stindex, StIndexCmd};
 use bin::stnodelib::{stnode, StNodeCmd};
 use bin::strelaylib::{strelay, StRelayCmd};
@@ -14,9 +15,13 @@ use stctrl::config::{
 // use stctrl::info::VerifyTokenCmd;
 use stctrl::buyer::{BuyerCmd, BuyerError, PayInvoiceCmd, PaymentStatusCmd};
 use stctrl::info::{BalanceCmd, ExportTicketCmd, FriendLastTokenCmd, FriendsCmd, InfoCmd};
-use stctrl::seller::{CancelInvoiceCmd, CommitInvoiceCmd, CreateInvoiceCmd, SellerCmd};
+use stctrl::seller::{
+    CancelInvoiceCmd, CommitInvoiceCmd, CreateInvoiceCmd, InvoiceStatusCmd, SellerCmd,
+};
 use stctrl::stctrllib::{stctrl, StCtrlCmd, StCtrlError, StCtrlSubcommand};
-use stctrl::stverifylib::{stverify, StVerifyCmd, VerifyReceiptCmd, VerifyTokenCmd};
+use stctrl::stverifylib::{
+    stverify, StVerifyCmd, VerifyInvoiceCmd, VerifyReceiptCmd, VerifyTokenCmd,
+};
 
 use crate::cli_tests::stctrl_setup::{create_stctrl_setup, StCtrlSetup};
 
@@ -32,6 +37,12 @@ fn spawn_entities(stctrl_setup: &StCtrlSetup) {
         lclient: stctrl_setup.index0_client_addr.parse().unwrap(),
         lserver: stctrl_setup.index0_server_addr.parse().unwrap(),
         trusted: stctrl_setup.temp_dir_path.join("index0").join("trusted"),
+        state_dir: None,
+        socks5: None,
+        rate_limit_window_ticks: None,
+        max_mutations_updates: None,
+        max_request_routes: None,
+        rate_limit_backoff_ticks: None,
     };
     // TODO: How can we close this thread?
     thread::spawn(move || {
@@ -48,6 +59,12 @@ fn spawn_entities(stctrl_setup: &StCtrlSetup) {
         lclient: stctrl_setup.index1_client_addr.parse().unwrap(),
         lserver: stctrl_setup.index1_server_addr.parse().unwrap(),
         trusted: stctrl_setup.temp_dir_path.join("index1").join("trusted"),
+        state_dir: None,
+        socks5: None,
+        rate_limit_window_ticks: None,
+        max_mutations_updates: None,
+        max_request_routes: None,
+        rate_limit_backoff_ticks: None,
     };
     // TODO: How can we close this thread?
     thread::spawn(move || {
@@ -62,6 +79,11 @@ fn spawn_entities(stctrl_setup: &StCtrlSetup) {
             .join("relay0")
             .join("relay0.ident"),
         laddr: stctrl_setup.relay0_addr.parse().unwrap(),
+        max_conns: 4096,
+        max_conns_per_key: 64,
+        max_rate: None,
+        max_burst: None,
+        allow_dir: None,
     };
     // TODO: How can we close this thread?
     thread::spawn(move || {
@@ -76,6 +98,11 @@ fn spawn_entities(stctrl_setup: &StCtrlSetup) {
             .join("relay1")
             .join("relay1.ident"),
         laddr: stctrl_setup.relay1_addr.parse().unwrap(),
+        max_conns: 4096,
+        max_conns_per_key: 64,
+        max_rate: None,
+        max_burst: None,
+        allow_dir: None,
     };
     // TODO: How can we close this thread?
     thread::spawn(move || {
@@ -86,9 +113,17 @@ fn spawn_entities(stctrl_setup: &StCtrlSetup) {
     // Spawn node0:
     let st_node_cmd = StNodeCmd {
         idfile: stctrl_setup.temp_dir_path.join("node0").join("node0.ident"),
-        laddr: stctrl_setup.node0_addr.clone().parse().unwrap(),
+        laddr: Some(stctrl_setup.node0_addr.clone().parse().unwrap()),
+        lsock: None,
         database: stctrl_setup.temp_dir_path.join("node0").join("node0.db"),
+        db_backend: DbBackend::File,
+        db_secret_opt: DbSecretOpt {
+            opt_db_keyfile: None,
+            opt_db_passphrase_env: None,
+        },
         trusted: stctrl_setup.temp_dir_path.join("node0").join("trusted"),
+        socks5: None,
+        payment_timeout_ticks: None,
     };
     // TODO: How can we close this thread?
     thread::spawn(move || {
@@ -99,9 +134,17 @@ fn spawn_entities(stctrl_setup: &StCtrlSetup) {
     // Spawn node1:
     let st_node_cmd = StNodeCmd {
         idfile: stctrl_setup.temp_dir_path.join("node1").join("node1.ident"),
-        laddr: stctrl_setup.node1_addr.clone().parse().unwrap(),
+        laddr: Some(stctrl_setup.node1_addr.clone().parse().unwrap()),
+        lsock: None,
         database: stctrl_setup.temp_dir_path.join("node1").join("node1.db"),
+        db_backend: DbBackend::File,
+        db_secret_opt: DbSecretOpt {
+            opt_db_keyfile: None,
+            opt_db_passphrase_env: None,
+        },
         trusted: stctrl_setup.temp_dir_path.join("node1").join("trusted"),
+        socks5: None,
+        payment_timeout_ticks: None,
     };
     // TODO: How can we close this thread?
     thread::spawn(move || {
@@ -126,6 +169,7 @@ fn get_node_public_key(stctrl_setup: &StCtrlSetup, index: usize) -> String {
             .temp_dir_path
             .join(format!("node{}", index))
             .join(format!("node{}.ticket", index)),
+        socks5: None,
         subcommand,
     };
 
@@ -158,6 +202,7 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
 
@@ -195,6 +240,7 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -224,6 +270,7 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -252,6 +299,7 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -284,6 +332,7 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -311,6 +360,7 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -334,6 +384,7 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -354,6 +405,7 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
 
@@ -387,6 +439,7 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -407,6 +460,7 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
 
@@ -439,6 +493,7 @@ fn set_max_debt(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node0")
             .join("node0.ticket"),
+        socks5: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -455,6 +510,7 @@ fn set_max_debt(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node1")
             .join("node1.ticket"),
+        socks5: None,
         subcommand,
     };
 
@@ -481,6 +537,8 @@ fn create_cancel_invoice(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node0")
             .join("temp_invoice.invoice"),
+        expiry_ticks: None,
+        memo: String::new(),
     };
     let seller_cmd = SellerCmd::CreateInvoice(create_invoice_cmd);
     let subcommand = StCtrlSubcommand::Seller(seller_cmd);
@@ -491,6 +549,7 @@ fn create_cancel_invoice(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node0")
             .join("node0.ticket"),
+        socks5: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd.clone(), &mut Vec::new()).unwrap();
@@ -512,6 +571,7 @@ fn create_cancel_invoice(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node0")
             .join("node0.ticket"),
+        socks5: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd.clone(), &mut Vec::new()).unwrap();
@@ -537,6 +597,8 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node0")
             .join("test1.invoice"),
+        expiry_ticks: None,
+        memo: "Test invoice".to_owned(),
     };
     let seller_cmd = SellerCmd::CreateInvoice(create_invoice_cmd);
     let subcommand = StCtrlSubcommand::Seller(seller_cmd);
@@ -547,10 +609,27 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node0")
             .join("node0.ticket"),
+        socks5: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd.clone(), &mut Vec::new()).unwrap();
 
+    // Node1: verify the invoice:
+    // --------------------------
+    let verify_invoice_cmd = VerifyInvoiceCmd {
+        invoice: stctrl_setup
+            .temp_dir_path
+            .join("node0")
+            .join("test1.invoice"),
+    };
+
+    let stverify_cmd = StVerifyCmd::VerifyInvoice(verify_invoice_cmd);
+    let mut output = Vec::new();
+    stverify(stverify_cmd, &mut output).unwrap();
+    let output_string = str::from_utf8(&output).unwrap();
+    assert!(output_string.contains("Invoice is valid!"));
+    assert!(output_string.contains("memo: Test invoice"));
+
     // Node1: pay the invoice:
     // -----------------------
     loop {
@@ -567,6 +646,8 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join("node1")
                 .join("test1.commit"),
+            max_fees: None,
+            deadline_ticks: None,
         };
         let buyer_cmd = BuyerCmd::PayInvoice(pay_invoice_cmd);
         let subcommand = StCtrlSubcommand::Buyer(buyer_cmd);
@@ -577,6 +658,7 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join("node1")
                 .join("node1.ticket"),
+            socks5: None,
             subcommand,
         };
 
@@ -589,6 +671,32 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
         thread::sleep(time::Duration::from_millis(100));
     }
 
+    // Node0: Check the invoice status:
+    let invoice_status_cmd = InvoiceStatusCmd {
+        invoice_file: stctrl_setup
+            .temp_dir_path
+            .join("node0")
+            .join("test1.invoice"),
+    };
+
+    let seller_cmd = SellerCmd::InvoiceStatus(invoice_status_cmd);
+    let subcommand = StCtrlSubcommand::Seller(seller_cmd);
+
+    let st_ctrl_cmd = StCtrlCmd {
+        idfile: stctrl_setup.temp_dir_path.join("app0").join("app0.ident"),
+        node_ticket: stctrl_setup
+            .temp_dir_path
+            .join("node0")
+            .join("node0.ticket"),
+        socks5: None,
+        subcommand,
+    };
+    let mut output = Vec::new();
+    stctrl(st_ctrl_cmd.clone(), &mut output).unwrap();
+    assert!(str::from_utf8(&output)
+        .unwrap()
+        .contains("Collected: 50/50 credits"));
+
     // Node0: Commit the invoice:
     let commit_invoice_cmd = CommitInvoiceCmd {
         invoice_file: stctrl_setup
@@ -610,6 +718,7 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node0")
             .join("node0.ticket"),
+        socks5: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd.clone(), &mut Vec::new()).unwrap();
@@ -635,6 +744,7 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node1")
             .join("node1.ticket"),
+        socks5: None,
         subcommand,
     };
 
@@ -687,6 +797,7 @@ fn check_balance(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node1")
             .join("node1.ticket"),
+        socks5: None,
         subcommand,
     };
 
@@ -711,6 +822,7 @@ fn export_token(stctrl_setup: &StCtrlSetup) {
             .temp_dir_path
             .join("node1")
             .join("node1.ticket"),
+        socks5: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -750,6 +862,7 @@ fn close_disable(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -770,6 +883,7 @@ fn close_disable(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
 
@@ -802,6 +916,7 @@ fn close_disable(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -822,6 +937,7 @@ fn close_disable(stctrl_setup: &StCtrlSetup) {
                 .temp_dir_path
                 .join(format!("node{}", j))
                 .join(format!("node{}.ticket", j)),
+            socks5: None,
             subcommand,
         };
 
@@ -1,6 +1,7 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use bin::node_db::{DbBackend, DbSecretOpt};
 use bin::stmgrlib::{
     stmgr, AppTicketCmd, GenIdentCmd, IndexTicketCmd, InitNodeDbCmd, NodeTicketCmd, RelayTicketCmd,
     StMgrCmd,
@@ -124,6 +125,11 @@ pub fn create_stctrl_setup(temp_dir_path: &Path) -> StCtrlSetup {
         let init_node_db_cmd = InitNodeDbCmd {
             idfile: temp_dir_path.join(node).join(format!("{}.ident", node)),
             output: temp_dir_path.join(node).join(format!("{}.db", node)),
+            db_backend: DbBackend::File,
+            db_secret_opt: DbSecretOpt {
+                opt_db_keyfile: None,
+                opt_db_passphrase_env: None,
+            },
         };
         stmgr(StMgrCmd::InitNodeDb(init_node_db_cmd)).unwrap();
     }
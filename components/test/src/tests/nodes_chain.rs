@@ -8,6 +8,7 @@ use common::test_executor::TestExecutor;
 
 use proto::app_server::messages::AppPermissions;
 use proto::funder::messages::{MultiCommit, PaymentStatus, Rate};
+use proto::index_server::messages::RouteSearchMode;
 
 use timer::create_timer_incoming;
 
@@ -410,7 +411,7 @@ async fn task_nodes_chain(mut test_executor: TestExecutor) {
     await!(apps[4]
         .seller()
         .unwrap()
-        .add_invoice(invoice_id.clone(), total_dest_payment))
+        .add_invoice(invoice_id.clone(), total_dest_payment, None))
     .unwrap();
 
     // Node0: Request a route to node 4:
@@ -418,7 +419,8 @@ async fn task_nodes_chain(mut test_executor: TestExecutor) {
         20,
         node_public_key(0),
         node_public_key(4),
-        None
+        None,
+        RouteSearchMode::Shortest
     ))
     .unwrap();
 
@@ -435,6 +437,7 @@ async fn task_nodes_chain(mut test_executor: TestExecutor) {
         invoice_id.clone(),
         total_dest_payment,
         node_public_key(4),
+        None,
     ))
     .unwrap();
 
@@ -504,7 +507,7 @@ async fn task_nodes_chain(mut test_executor: TestExecutor) {
     await!(apps[3]
         .seller()
         .unwrap()
-        .add_invoice(invoice_id.clone(), total_dest_payment))
+        .add_invoice(invoice_id.clone(), total_dest_payment, None))
     .unwrap();
 
     // Node5: Request a route to node 3:
@@ -512,7 +515,8 @@ async fn task_nodes_chain(mut test_executor: TestExecutor) {
         10,
         node_public_key(5),
         node_public_key(3),
-        None
+        None,
+        RouteSearchMode::Cheapest
     ))
     .unwrap();
 
@@ -529,6 +533,7 @@ async fn task_nodes_chain(mut test_executor: TestExecutor) {
         invoice_id.clone(),
         total_dest_payment,
         node_public_key(3),
+        None,
     ))
     .unwrap();
 
@@ -584,6 +589,87 @@ async fn task_nodes_chain(mut test_executor: TestExecutor) {
         }
         _ => unreachable!(),
     }
+
+    // A payment through the chain 0 -> 1 -> 2 -> 4, canceled by the seller after the response:
+    // ==========================================================================================
+
+    let payment_id = PaymentId::from(&[6u8; PAYMENT_ID_LEN]);
+    let invoice_id = InvoiceId::from(&[7u8; INVOICE_ID_LEN]);
+    let request_id = Uid::from(&[8u8; UID_LEN]);
+    let total_dest_payment = 10u128;
+    let fees = 2u128; // Fees for Node1 and Node2
+
+    // Node4: Create an invoice:
+    await!(apps[4]
+        .seller()
+        .unwrap()
+        .add_invoice(invoice_id.clone(), total_dest_payment, None))
+    .unwrap();
+
+    // Node0: Request a route to node 4:
+    let multi_routes = await!(apps[0].routes().unwrap().request_routes(
+        20,
+        node_public_key(0),
+        node_public_key(4),
+        None,
+        RouteSearchMode::Shortest
+    ))
+    .unwrap();
+
+    assert!(multi_routes.len() > 0);
+    let route = multi_routes[0].routes[0].route.clone();
+
+    // Node0: Open a payment to pay the invoice issued by Node4:
+    await!(apps[0].buyer().unwrap().create_payment(
+        payment_id.clone(),
+        invoice_id.clone(),
+        total_dest_payment,
+        node_public_key(4),
+        None,
+    ))
+    .unwrap();
+
+    // Node0: Create one transaction for the given route.
+    // The response arrives, but the Commit is never handed to Node4:
+    let _commit = await!(apps[0].buyer().unwrap().create_transaction(
+        payment_id.clone(),
+        request_id.clone(),
+        route,
+        total_dest_payment,
+        fees,
+    ))
+    .unwrap();
+
+    // Node0: Close payment (No more transactions will be sent through this payment)
+    let _ = await!(apps[0]
+        .buyer()
+        .unwrap()
+        .request_close_payment(payment_id.clone()))
+    .unwrap();
+
+    // Node4: Cancel the invoice. Only the seller may cancel the transaction at this stage:
+    await!(apps[4].seller().unwrap().cancel_invoice(invoice_id.clone())).unwrap();
+
+    // Wait some time:
+    await!(advance_time(5, &mut tick_sender, &test_executor));
+
+    // Node0: The payment was canceled:
+    let payment_status = await!(apps[0]
+        .buyer()
+        .unwrap()
+        .request_close_payment(payment_id.clone()))
+    .unwrap();
+
+    match &payment_status {
+        PaymentStatus::Canceled(ack_uid) => {
+            await!(apps[0]
+                .buyer()
+                .unwrap()
+                .ack_close_payment(payment_id.clone(), ack_uid.clone()))
+            .unwrap();
+        }
+        _ => unreachable!(),
+    }
 }
 
 #[test]
@@ -1,14 +1,15 @@
 use std::collections::HashMap;
 
 use futures::channel::mpsc;
-use futures::StreamExt;
+use futures::{future, StreamExt};
 
 use tempfile::tempdir;
 
 use common::test_executor::TestExecutor;
 
 use proto::app_server::messages::AppPermissions;
-use proto::funder::messages::{FriendsRoute, MultiCommit, PaymentStatus};
+use proto::funder::messages::{FriendsRoute, InvoiceStatus, MultiCommit, PaymentStatus};
+use proto::index_server::messages::RouteSearchMode;
 
 use timer::create_timer_incoming;
 
@@ -47,14 +48,15 @@ where
     let invoice_id = InvoiceId::from(&[3u8; INVOICE_ID_LEN]);
     let request_id = Uid::from(&[5u8; UID_LEN]);
 
-    await!(app_seller.add_invoice(invoice_id.clone(), total_dest_payment)).unwrap();
+    await!(app_seller.add_invoice(invoice_id.clone(), total_dest_payment, None)).unwrap();
 
     // Node0: Request routes:
     let mut routes = await!(app_routes.request_routes(
         total_dest_payment.checked_add(fees).unwrap(),
         buyer_public_key.clone(),
         seller_public_key.clone(),
-        None
+        None,
+        RouteSearchMode::Shortest
     ))
     .unwrap();
 
@@ -66,19 +68,32 @@ where
         payment_id.clone(),
         invoice_id.clone(),
         total_dest_payment,
-        seller_public_key.clone()
+        seller_public_key.clone(),
+        None
     ))
     .unwrap();
 
-    // Node0: Create one transaction for the given route:
-    let commit = await!(app_buyer.create_transaction(
-        payment_id.clone(),
-        request_id.clone(),
-        route.route.clone(),
-        total_dest_payment,
-        fees,
-    ))
-    .unwrap();
+    // Node0: Create one transaction for the given route.
+    // Node1: Wait until the invoice is paid.
+    let (res_commit, res_invoice_paid) = await!(future::join(
+        app_buyer.create_transaction(
+            payment_id.clone(),
+            request_id.clone(),
+            route.route.clone(),
+            total_dest_payment,
+            fees,
+        ),
+        app_seller.wait_invoice_paid(invoice_id.clone())
+    ));
+    let commit = res_commit.unwrap();
+    let invoice_paid = res_invoice_paid.unwrap();
+    assert_eq!(invoice_paid.total_dest_payment, total_dest_payment);
+    assert_eq!(invoice_paid.transactions.len(), 1);
+    assert_eq!(invoice_paid.transactions[0].request_id, request_id);
+    assert_eq!(
+        invoice_paid.transactions[0].dest_hashed_lock,
+        commit.dest_hashed_lock
+    );
 
     // Node0: Close payment (No more transactions will be sent through this payment)
     let _ = await!(app_buyer.request_close_payment(payment_id.clone())).unwrap();
@@ -90,6 +105,18 @@ where
         commits: vec![commit],
     };
 
+    // Node1: The invoice was paid, but not yet committed:
+    match await!(app_seller.request_invoice_status(invoice_id.clone())).unwrap() {
+        InvoiceStatus::Open(open_invoice_status) => {
+            assert_eq!(
+                open_invoice_status.collected_dest_payment,
+                total_dest_payment
+            );
+            assert_eq!(open_invoice_status.transactions.len(), 1);
+        }
+        InvoiceStatus::InvoiceNotFound => unreachable!(),
+    };
+
     // Node0 now passes the MultiCommit to Node1 out of band.
 
     // Node1: Apply the MultiCommit
@@ -400,7 +427,7 @@ async fn task_two_nodes_payment(mut test_executor: TestExecutor) {
     let invoice_id = InvoiceId::from(&[9u8; INVOICE_ID_LEN]);
     let request_id = Uid::from(&[10u8; UID_LEN]);
 
-    await!(seller0.add_invoice(invoice_id.clone(), total_dest_payment)).unwrap();
+    await!(seller0.add_invoice(invoice_id.clone(), total_dest_payment, None)).unwrap();
 
     // Node1: Open a payment to pay the invoice issued by Node1:
     await!(buyer1.create_payment(
@@ -408,6 +435,7 @@ async fn task_two_nodes_payment(mut test_executor: TestExecutor) {
         invoice_id.clone(),
         total_dest_payment,
         node_public_key(0),
+        None,
     ))
     .unwrap();
 
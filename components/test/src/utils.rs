@@ -4,7 +4,7 @@ use std::path::PathBuf;
 use futures::channel::mpsc;
 use futures::future::RemoteHandle;
 use futures::task::{Spawn, SpawnExt};
-use futures::{future, FutureExt, SinkExt, TryFutureExt};
+use futures::{future, stream, FutureExt, SinkExt, TryFutureExt};
 
 use crypto::identity::{generate_pkcs8_key_pair, Identity, PublicKey, SoftwareEd25519Identity};
 
@@ -14,7 +14,10 @@ use crypto::test_utils::DummyRandom;
 use common::test_executor::TestExecutor;
 
 use proto::app_server::messages::{AppPermissions, NamedRelayAddress, RelayAddress};
-use proto::consts::{KEEPALIVE_TICKS, MAX_NODE_RELAYS, MAX_OPERATIONS_IN_BATCH, TICKS_TO_REKEY};
+use proto::consts::{
+    KEEPALIVE_TICKS, MAX_NODE_RELAYS, MAX_OPERATIONS_IN_BATCH, PAYMENT_TIMEOUT_TICKS,
+    TICKS_TO_REKEY,
+};
 use proto::index_server::messages::NamedIndexServerAddress;
 use proto::net::messages::NetAddress;
 
@@ -25,8 +28,8 @@ use node::{net_node, NodeConfig, NodeState};
 
 use database::file_db::FileDb;
 
-use index_server::net_index_server;
-use relay::net_relay_server;
+use index_server::{net_index_server, RateLimitConfig};
+use relay::{net_relay_server, TrafficMeter};
 
 use timer::TimerClient;
 
@@ -36,9 +39,21 @@ use crate::sim_network::{net_address, SimNetworkClient};
 const CHANNEL_LEN: usize = 0x20;
 /// The amount of ticks we wait before attempting to reconnect
 const BACKOFF_TICKS: usize = 0x8;
+/// Amount of ticks between saves of the index server state (Unused, as no state is saved)
+const SNAPSHOT_TICKS: usize = 0x40;
+/// Amount of ticks in a window used by index servers to count messages from clients
+const RATE_LIMIT_WINDOW_TICKS: usize = 0x40;
+/// Maximum amount of mutations updates an index server accepts from a client in one window
+const MAX_MUTATIONS_UPDATES: usize = 0x1000;
+/// Maximum amount of routes requests an index server accepts from a client in one window
+const MAX_REQUEST_ROUTES: usize = 0x1000;
 /// Maximum amount of encryption set ups (diffie hellman) that we allow to occur at the same
 /// time.
 const MAX_CONCURRENT_ENCRYPT: usize = 0x8;
+/// Maximum amount of concurrent connections served by a relay
+const MAX_RELAY_CONNS: usize = 0x100;
+/// Maximum amount of concurrent connections served by a relay for a single public key
+const MAX_RELAY_CONNS_PER_KEY: usize = 0x20;
 /// The size we allocate for the user send funds requests queue.
 const MAX_PENDING_USER_REQUESTS: usize = 0x20;
 /// Maximum amount of concurrent index client requests:
@@ -138,6 +153,8 @@ fn default_node_config() -> NodeConfig {
         max_operations_in_batch: MAX_OPERATIONS_IN_BATCH,
         /// The size we allocate for the user send funds requests queue.
         max_pending_user_requests: MAX_PENDING_USER_REQUESTS,
+        /// Amount of ticks we wait for a payment to complete before marking it as timed out.
+        payment_timeout_ticks: PAYMENT_TIMEOUT_TICKS,
         /// Maximum amount of concurrent index client requests:
         max_open_index_client_requests: MAX_OPEN_INDEX_CLIENT_REQUESTS,
         /// Maximum amount of relays a node may use.
@@ -341,6 +358,15 @@ pub async fn create_index_server<S>(
         trusted_servers,
         MAX_CONCURRENT_ENCRYPT,
         BACKOFF_TICKS,
+        None,
+        SNAPSHOT_TICKS,
+        None,
+        RateLimitConfig {
+            window_ticks: RATE_LIMIT_WINDOW_TICKS,
+            max_mutations_updates: MAX_MUTATIONS_UPDATES,
+            max_request_routes: MAX_REQUEST_ROUTES,
+            backoff_ticks: BACKOFF_TICKS,
+        },
         spawner.clone(),
         spawner.clone(),
     )
@@ -371,6 +397,11 @@ pub async fn create_relay<S>(
         timer_client,
         rng,
         MAX_CONCURRENT_ENCRYPT,
+        MAX_RELAY_CONNS,
+        MAX_RELAY_CONNS_PER_KEY,
+        TrafficMeter::new(None),
+        None,
+        stream::empty(),
         spawner.clone(),
     )
     .map_err(|e| error!("net_relay_server() error: {:?}", e))